@@ -1,20 +1,33 @@
+#![cfg_attr(feature = "nightly", feature(track_path))]
+
 use anyhow::Context as _;
-use bevy::{render::color::Color, ui};
-use bevy_peacock_style::{parse_stylesheet, Selector, SelectorEntry, StyleProp, StylePropList};
+use bevy::{render::color::Color, text::TextAlignment, ui};
+use bevy_peacock_style::{
+    lint_stylesheet_with, parse_stylesheet_with, AnimateIn, CalcLength, CalcProperty,
+    DuplicateRules, Length, Selector, SelectorEntry, SelectorSpans, SourcePos, SourceSpan,
+    SpanTable, StyleProp, StylePropList, TextOverflow, Timing, Transition, TransitionProperty,
+};
 use proc_macro::TokenStream;
 use quote::{format_ident, quote};
 use std::{
     env,
+    fs::File,
+    io::{self, BufRead, BufReader},
     path::{Path, PathBuf},
 };
 use syn::{
     parse::{Parse, ParseStream, Result},
-    parse_macro_input, Ident, LitStr, Token,
+    parse_macro_input, Ident, LitBool, LitStr, Token,
 };
 
 struct StylesheetInput {
     mod_name: Ident,
     path: String,
+    duplicates: DuplicateRules,
+    spans: bool,
+    embed_source: bool,
+    deny_warnings: bool,
+    rename_keywords: bool,
 }
 
 impl Parse for StylesheetInput {
@@ -22,61 +35,300 @@ impl Parse for StylesheetInput {
         let mod_name: Ident = input.parse()?;
         input.parse::<Token![,]>()?;
         let path = input.parse::<LitStr>()?;
+        let mut duplicates = DuplicateRules::Error;
+        let mut spans = false;
+        let mut embed_source = true;
+        let mut deny_warnings = false;
+        let mut rename_keywords = false;
+        while input.parse::<Option<Token![,]>>()?.is_some() && !input.is_empty() {
+            let option: Ident = input.parse()?;
+            // Every option can be given on its own, which turns it on, or with a value.
+            let value = match input.parse::<Option<Token![=]>>()? {
+                Some(_) => input.parse::<LitBool>()?.value,
+                None => true,
+            };
+            if option == "merge_duplicates" {
+                duplicates = if value {
+                    DuplicateRules::Merge
+                } else {
+                    DuplicateRules::Error
+                };
+            } else if option == "spans" {
+                spans = value;
+            } else if option == "embed_source" {
+                embed_source = value;
+            } else if option == "deny_warnings" {
+                deny_warnings = value;
+            } else if option == "rename_keywords" {
+                rename_keywords = value;
+            } else {
+                return Err(syn::Error::new_spanned(
+                    option,
+                    "unknown option, expected `merge_duplicates`, `spans`, `embed_source`, \
+                    `deny_warnings` or `rename_keywords`",
+                ));
+            }
+        }
         Ok(StylesheetInput {
             mod_name,
             path: path.value(),
+            duplicates,
+            spans,
+            embed_source,
+            deny_warnings,
+            rename_keywords,
         })
     }
 }
 
+/// Strip a `//` comment, and any trailing whitespace, from a line of stylesheet source. A `//`
+/// within a string literal, such as in a url, isn't a comment.
+fn strip_line_comment(line: &str) -> &str {
+    let mut in_string = false;
+    let mut escaped = false;
+    for (index, c) in line.char_indices() {
+        if in_string {
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == '"' {
+                in_string = false;
+            }
+        } else if c == '"' {
+            in_string = true;
+        } else if c == '/' && line[index + 1..].starts_with('/') {
+            return line[..index].trim_end();
+        }
+    }
+    line.trim_end()
+}
+
+/// Read stylesheet source a line at a time, stripping comments as it goes, so that only the
+/// meaningful part of the file is ever held in memory. Every line is kept, even if it ends up
+/// empty, so that line and column numbers still refer to the original file.
+fn read_stripped(mut reader: impl BufRead) -> io::Result<String> {
+    let mut stripped = String::new();
+    let mut line = String::new();
+    while reader.read_line(&mut line)? > 0 {
+        stripped.push_str(strip_line_comment(&line));
+        stripped.push('\n');
+        line.clear();
+    }
+    Ok(stripped)
+}
+
+/// A 64-bit FNV-1a hash of the stylesheet source. This must be stable across builds and
+/// compiler versions, which rules out `std::hash`.
+fn source_hash(src: &str) -> u64 {
+    src.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// The path of an imported stylesheet, the hash of its source, and its rules.
+type ImportedStylesheet = (PathBuf, u64, Vec<(String, StylePropList)>);
+
+/// Words which can't be used as identifiers: Rust's strict and reserved keywords.
+const RUST_KEYWORDS: &[&str] = &[
+    "as", "break", "const", "continue", "crate", "else", "enum", "extern", "false", "fn", "for",
+    "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub", "ref", "return",
+    "self", "Self", "static", "struct", "super", "trait", "true", "type", "unsafe", "use", "where",
+    "while", "async", "await", "dyn", "abstract", "become", "box", "do", "final", "macro",
+    "override", "priv", "typeof", "unsized", "virtual", "yield", "try", "gen",
+];
+
+/// Names of the other items in the generated module, which a style can't share.
+const GENERATED_ITEMS: &[&str] = &["register_styles", "SOURCE_HASH"];
+
+/// Return the identifier of the static generated for each of the style names, in order.
+/// Fails, with a message naming the style, if a name isn't a valid Rust identifier, is a
+/// keyword, or gives the same identifier as another style or item of the module. With
+/// `rename_keywords`, keywords get a `_` suffix, as in `loop_`, rather than failing.
+fn rule_idents<'a>(
+    names: impl IntoIterator<Item = &'a str>,
+    rename_keywords: bool,
+) -> std::result::Result<Vec<String>, String> {
+    let mut idents: Vec<(&str, String)> = Vec::new();
+    for name in names {
+        let valid = name.starts_with(|c: char| c.is_ascii_alphabetic())
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+        if !valid {
+            return Err(format!(
+                "style name '{}' isn't a valid Rust identifier; names must start with an ASCII \
+                letter, followed by ASCII letters, digits or '_'",
+                name
+            ));
+        }
+        let ident = match (RUST_KEYWORDS.contains(&name), rename_keywords) {
+            (false, _) => name.to_owned(),
+            (true, true) => format!("{}_", name),
+            (true, false) => {
+                return Err(format!(
+                    "style name '{}' is a Rust keyword; rename it or use the `rename_keywords` \
+                    option",
+                    name
+                ))
+            }
+        };
+        if GENERATED_ITEMS.contains(&ident.as_str()) {
+            return Err(format!(
+                "style name '{}' is reserved for an item of the generated module; rename it",
+                name
+            ));
+        }
+        if let Some((other, _)) = idents.iter().find(|(_, other)| *other == ident) {
+            return Err(format!(
+                "style names '{}' and '{}' both generate the identifier `{}`; rename one of them",
+                other, name, ident
+            ));
+        }
+        idents.push((name, ident));
+    }
+    Ok(idents.into_iter().map(|(_, ident)| ident).collect())
+}
+
+/// Fail if linting the stylesheet finds any likely mistakes, listing each of them.
+fn deny_lint_warnings(
+    file_path: &Path,
+    src: &str,
+    duplicates: DuplicateRules,
+) -> anyhow::Result<()> {
+    let warnings = match lint_stylesheet_with(src, duplicates) {
+        Ok(warnings) if warnings.is_empty() => return Ok(()),
+        Ok(warnings) => warnings,
+        // Parsing errors are reported by the caller.
+        Err(_) => return Ok(()),
+    };
+    let mut message = format!(
+        "stylesheet file {} has {} lint warning(s):",
+        file_path.display(),
+        warnings.len()
+    );
+    for warning in warnings {
+        message.push_str(&format!(
+            "\n{}:{}:{} in rule '{}': {}",
+            file_path.display(),
+            warning.pos.line,
+            warning.pos.column,
+            warning.rule,
+            warning.message
+        ));
+    }
+    Err(anyhow::anyhow!(message))
+}
+
 fn import_stylesheet_from_path(
     path_str: &String,
-) -> anyhow::Result<(PathBuf, Vec<(String, StylePropList)>)> {
+    duplicates: DuplicateRules,
+    deny_warnings: bool,
+) -> anyhow::Result<ImportedStylesheet> {
     let manifest_dir_env =
         env::var_os("CARGO_MANIFEST_DIR").context("CARGO_MANIFEST_DIR env var not found")?;
     let manifest_path = Path::new(&manifest_dir_env);
     let file_path = manifest_path.join(Path::new(&path_str));
 
-    let stylesheet_src = std::fs::read_to_string(&file_path)
+    let stylesheet_src = File::open(&file_path)
+        .and_then(|file| read_stripped(BufReader::new(file)))
         .with_context(|| format!("failed to read stylesheet file: {}", file_path.display()))?;
 
-    let stylesheet = match parse_stylesheet(&stylesheet_src) {
+    let stylesheet = match parse_stylesheet_with(&stylesheet_src, duplicates) {
         Ok(stylesheet) => stylesheet,
         Err(err) => {
+            let rule = match &err.rule {
+                Some(rule) => format!(" in rule '{}'", rule),
+                None => String::new(),
+            };
             return Err(anyhow::anyhow!(
-                "failed to parse stylesheet file: {}\n{}",
+                "failed to parse stylesheet file {}:{}:{}{}: {}",
                 file_path.display(),
-                err.to_string()
-            ))
+                err.pos.line,
+                err.pos.column,
+                rule,
+                err.message
+            ));
         }
     };
 
-    Ok((file_path, stylesheet))
+    if deny_warnings {
+        deny_lint_warnings(&file_path, &stylesheet_src, duplicates)?;
+    }
+
+    Ok((file_path, source_hash(&stylesheet_src), stylesheet))
+}
+
+/// Return the identifier of the static generated for each rule of the stylesheet, or an error
+/// naming the file and the rule whose name can't be used.
+fn stylesheet_idents(
+    file_path: &Path,
+    stylesheet: &[(String, StylePropList)],
+    rename_keywords: bool,
+) -> anyhow::Result<Vec<String>> {
+    rule_idents(
+        stylesheet.iter().map(|(name, _)| name.as_str()),
+        rename_keywords,
+    )
+    .map_err(|message| {
+        anyhow::anyhow!(
+            "invalid style name in stylesheet file {}: {}",
+            file_path.display(),
+            message
+        )
+    })
 }
 
 fn format_stylesheet_src(
     mod_name: &String,
     file_path: &Path,
+    source: Option<u64>,
     stylesheet: &[(String, StylePropList)],
+    idents: &[String],
 ) -> TokenStream {
     let mod_name = format_ident!("{}", mod_name);
-    let rules = stylesheet.iter().map(|(name, style)| {
-        let name = format_ident!("{}", name);
+    let rules = stylesheet.iter().zip(idents).map(|((_, style), ident)| {
+        let name = format_ident!("{}", ident);
         let style = style.to_src();
         quote! {
             #[dynamic]
             pub static #name: StyleHandle = StyleHandle::new(#style);
         }
     });
+    let registrations = stylesheet.iter().zip(idents).map(|((name, _), ident)| {
+        let ident = format_ident!("{}", ident);
+        quote! {
+            registry.register(#name, StyleHandle::clone(&#ident));
+        }
+    });
     let path = file_path.to_str().unwrap();
+    let source = match source {
+        // Referencing the file makes cargo rebuild the crate when it changes. The constant is
+        // unused, so the text is dropped at link time, but it still has to be compiled.
+        None => quote! {
+            #[allow(dead_code)]
+            const _: &str = include_str!(#path);
+        },
+        Some(hash) => quote! {
+            /// Hash of the stylesheet source, ignoring comments, which these styles were
+            /// generated from.
+            #[allow(dead_code)]
+            pub const SOURCE_HASH: u64 = #hash;
+        },
+    };
     let output = quote! {
         mod #mod_name {
             use static_init::dynamic;
-            use bevy_peacock::{StyleHandle, StyleProp, StylePropList, Selector};
+            use bevy_peacock::{
+                StyleHandle, StyleProp, StylePropList, Selector, StyleRegistry, TextOverflow,
+            };
             use bevy::{render::color::Color, ui};
-            #[allow(dead_code)]
-            const _: &str = include_str!(#path);
+            #source
             #( #rules )*
+
+            /// Register all of the styles in this stylesheet by name.
+            #[allow(dead_code, unused_variables)]
+            pub fn register_styles(registry: &mut StyleRegistry) {
+                #( #registrations )*
+            }
         }
     };
     // println!("{}", output);
@@ -88,12 +340,61 @@ fn format_stylesheet_src(
 /// Arguments:
 /// mod_name - the name of the module to generate.
 /// path - the path to the stylesheet file.
+/// merge_duplicates - (optional) merge rules with the same name into a single style, rather
+///   than reporting an error.
+/// spans - (optional) keep the source location of each rule, selector and property, so
+///   that they can be looked up with `StyleHandle::source()`.
+/// embed_source = false - (optional) don't reference the stylesheet with `include_str!`, which
+///   makes the compiler read the whole file, comments included, on every build. The module gets
+///   a `SOURCE_HASH` constant instead. Cargo then no longer rebuilds the crate when only the
+///   stylesheet changes, so edits require touching the file which invokes the macro, unless the
+///   `nightly` feature is enabled, which tracks the file with `proc_macro::tracked_path`.
+/// deny_warnings - (optional) fail to compile if `lint_stylesheet` finds likely mistakes in the
+///   stylesheet, such as a property set twice in the same block, so that CI builds catch them.
+/// rename_keywords - (optional) generate the statics of styles whose names are Rust keywords,
+///   such as `loop`, with a `_` suffix (`loop_`), rather than failing to compile. They're still
+///   registered under their own names.
+///
+/// Each option can be given on its own, which turns it on, or as `option = true` or
+/// `option = false`. Only `embed_source` is on by default.
+///
+/// Comments are stripped from the stylesheet as it's read, and don't affect the generated code.
 #[proc_macro]
 pub fn import_stylesheet(input: TokenStream) -> TokenStream {
     let input = parse_macro_input!(input as StylesheetInput);
-    match import_stylesheet_from_path(&input.path) {
-        Ok((file_path, stylesheet)) => {
-            format_stylesheet_src(&input.mod_name.to_string(), &file_path, &stylesheet)
+    match import_stylesheet_from_path(&input.path, input.duplicates, input.deny_warnings) {
+        Ok((file_path, hash, mut stylesheet)) => {
+            #[cfg(feature = "nightly")]
+            if !input.embed_source {
+                proc_macro::tracked_path::path(file_path.to_string_lossy());
+            }
+            for (_, style) in stylesheet.iter_mut() {
+                if let (true, Some(spans)) = (input.spans, style.spans()) {
+                    let spans = SpanTable {
+                        path: Some(input.path.clone()),
+                        ..spans.clone()
+                    };
+                    *style = std::mem::take(style).with_spans(spans);
+                } else {
+                    *style = std::mem::take(style).without_spans();
+                }
+            }
+            let idents = match stylesheet_idents(&file_path, &stylesheet, input.rename_keywords) {
+                Ok(idents) => idents,
+                Err(err) => {
+                    return syn::Error::new_spanned(&input.mod_name, err.to_string())
+                        .to_compile_error()
+                        .into()
+                }
+            };
+            let source = (!input.embed_source).then_some(hash);
+            format_stylesheet_src(
+                &input.mod_name.to_string(),
+                &file_path,
+                source,
+                &stylesheet,
+                &idents,
+            )
         }
         Err(err) => syn::Error::new_spanned(&input.mod_name, err.to_string())
             .to_compile_error()
@@ -111,7 +412,7 @@ impl ToSrc for SelectorEntry {
         let selector = selector.to_src();
         let props = props.iter().map(|prop| prop.to_src()).collect::<Vec<_>>();
         quote! {
-            (Box::new(Selector::parse(#selector).unwrap()), vec![#( #props ),*])
+            (Box::new(#selector.parse::<Selector>().unwrap()), vec![#( #props ),*])
         }
     }
 }
@@ -119,19 +420,81 @@ impl ToSrc for SelectorEntry {
 impl ToSrc for StylePropList {
     fn to_src(&self) -> proc_macro2::TokenStream {
         let props = self
-            .get_props()
+            .props()
             .iter()
             .map(|prop| prop.to_src())
             .collect::<Vec<_>>();
-        let selectors = self
-            .get_selectors()
-            .iter()
-            .map(|selector| selector.to_src());
+        let selectors = self.selectors().iter().map(|selector| selector.to_src());
+        let scopes = (!self.scopes().is_empty()).then(|| {
+            let scopes = self.scopes().iter().map(|(scope, props)| {
+                let props = props.iter().map(|prop| prop.to_src());
+                quote! { (#scope.to_string(), vec![#( #props ),*]) }
+            });
+            quote! { .with_scopes(vec![#( #scopes ),*]) }
+        });
+        let spans = self.spans().map(|spans| {
+            let spans = spans.to_src();
+            quote! { .with_spans(#spans) }
+        });
         quote! {
             StylePropList::from_raw(
                 vec![#( #props ),*],
                 vec![#( #selectors ),*],
             )
+            #scopes
+            #spans
+        }
+    }
+}
+
+impl ToSrc for SourcePos {
+    fn to_src(&self) -> proc_macro2::TokenStream {
+        let SourcePos { line, column } = self;
+        quote! { bevy_peacock::SourcePos { line: #line, column: #column } }
+    }
+}
+
+impl ToSrc for SourceSpan {
+    fn to_src(&self) -> proc_macro2::TokenStream {
+        let start = self.start.to_src();
+        let end = self.end.to_src();
+        quote! { bevy_peacock::SourceSpan { start: #start, end: #end } }
+    }
+}
+
+impl ToSrc for SpanTable {
+    fn to_src(&self) -> proc_macro2::TokenStream {
+        let path = match &self.path {
+            Some(path) => quote! { Some(#path.to_string()) },
+            None => quote! { None },
+        };
+        let rule = self.rule.to_src();
+        let props = self.props.iter().map(|span| span.to_src());
+        let entries = |entries: &[SelectorSpans]| {
+            entries
+                .iter()
+                .map(|SelectorSpans { selector, props }| {
+                    let selector = selector.to_src();
+                    let props = props.iter().map(|span| span.to_src());
+                    quote! {
+                        bevy_peacock::SelectorSpans {
+                            selector: #selector,
+                            props: vec![#( #props ),*],
+                        }
+                    }
+                })
+                .collect::<Vec<_>>()
+        };
+        let selectors = entries(&self.selectors);
+        let scopes = entries(&self.scopes);
+        quote! {
+            bevy_peacock::SpanTable {
+                path: #path,
+                rule: #rule,
+                props: vec![#( #props ),*],
+                selectors: vec![#( #selectors ),*],
+                scopes: vec![#( #scopes ),*],
+            }
         }
     }
 }
@@ -153,6 +516,22 @@ impl ToSrc for StyleProp {
                     StyleProp::BackgroundImage(#path)
                 }
             }
+            StyleProp::BackgroundImageFlipX(flip) => {
+                quote! {
+                    StyleProp::BackgroundImageFlipX(#flip)
+                }
+            }
+            StyleProp::BackgroundImageFlipY(flip) => {
+                quote! {
+                    StyleProp::BackgroundImageFlipY(#flip)
+                }
+            }
+            StyleProp::BackgroundImageTint(color) => {
+                let color = color.to_src();
+                quote! {
+                    StyleProp::BackgroundImageTint(#color)
+                }
+            }
             StyleProp::BackgroundColor(color) => {
                 let color = color.to_src();
                 quote! {
@@ -243,6 +622,11 @@ impl ToSrc for StyleProp {
                 let r = r.to_src();
                 quote! {StyleProp::AspectRatio(#r)}
             }
+            StyleProp::Calc(property, length) => {
+                let property = property.to_src();
+                let length = length.to_src();
+                quote! {StyleProp::Calc(#property, #length)}
+            }
             StyleProp::Margin(length) => {
                 let length = length.to_src();
                 quote! {StyleProp::Margin(#length)}
@@ -329,9 +713,10 @@ impl ToSrc for StyleProp {
                 let length = length.to_src();
                 quote! {StyleProp::ColumnGap(#length)}
             }
-            StyleProp::Gap(length) => {
-                let length = length.to_src();
-                quote! {StyleProp::Gap(#length)}
+            StyleProp::Gap(row, column) => {
+                let row = row.to_src();
+                let column = column.to_src();
+                quote! {StyleProp::Gap(#row, #column)}
             }
             StyleProp::AlignItems(value) => {
                 let value = value.to_src();
@@ -392,9 +777,20 @@ impl ToSrc for StyleProp {
                     StyleProp::Font(#path)
                 }
             }
+            StyleProp::TextOverflow(overflow) => {
+                let overflow = overflow.to_src();
+                quote! {StyleProp::TextOverflow(#overflow)}
+            }
+            StyleProp::TextAlign(align) => {
+                let align = align.to_src();
+                quote! {StyleProp::TextAlign(#align)}
+            }
             StyleProp::FontSize(value) => {
                 quote! {StyleProp::FontSize(#value)}
             }
+            StyleProp::InheritColor => quote! {StyleProp::InheritColor},
+            StyleProp::InheritFont => quote! {StyleProp::InheritFont},
+            StyleProp::InheritFontSize => quote! {StyleProp::InheritFontSize},
             StyleProp::OutlineColor(color) => {
                 let color = color.to_src();
                 quote! {
@@ -424,8 +820,35 @@ impl ToSrc for StyleProp {
             StyleProp::Rotation(value) => {
                 quote! {StyleProp::Rotation(#value)}
             }
-            StyleProp::Translation(_) => todo!(),
-            StyleProp::Transition(_) => todo!(),
+            StyleProp::Translation(value) => {
+                let value = value.to_src();
+                quote! {StyleProp::Translation(#value)}
+            }
+            StyleProp::UiScale(value) => {
+                quote! {StyleProp::UiScale(#value)}
+            }
+            StyleProp::Transition(transitions) => {
+                let transitions = transitions.iter().map(|tr| tr.to_src());
+                quote! {StyleProp::Transition(vec![#( #transitions ),*])}
+            }
+            StyleProp::AnimateIn(anims) => {
+                let anims = anims.iter().map(|anim| anim.to_src());
+                quote! {StyleProp::AnimateIn(vec![#( #anims ),*])}
+            }
+            StyleProp::ElevateWhileAnimating(elevate) => {
+                quote! {StyleProp::ElevateWhileAnimating(#elevate)}
+            }
+            StyleProp::Token(property, token) => {
+                quote! {StyleProp::Token(#property, #token.to_string())}
+            }
+            StyleProp::Rem(property, lengths) => {
+                let lengths = lengths.iter().map(|length| length.to_src());
+                quote! {StyleProp::Rem(#property, vec![#( #lengths ),*])}
+            }
+            StyleProp::Important(prop) => {
+                let prop = prop.to_src();
+                quote! {StyleProp::Important(Box::new(#prop))}
+            }
         }
     }
 }
@@ -451,6 +874,13 @@ impl ToSrc for f32 {
     }
 }
 
+impl ToSrc for bevy::math::Vec3 {
+    fn to_src(&self) -> proc_macro2::TokenStream {
+        let Self { x, y, z } = *self;
+        quote! {bevy::math::Vec3::new(#x, #y, #z)}
+    }
+}
+
 impl ToSrc for Color {
     fn to_src(&self) -> proc_macro2::TokenStream {
         match self {
@@ -559,6 +989,14 @@ impl ToSrc for ui::OverflowAxis {
     }
 }
 
+impl ToSrc for ui::Overflow {
+    fn to_src(&self) -> proc_macro2::TokenStream {
+        let x = self.x.to_src();
+        let y = self.y.to_src();
+        quote! {ui::Overflow { x: #x, y: #y }}
+    }
+}
+
 impl ToSrc for ui::Direction {
     fn to_src(&self) -> proc_macro2::TokenStream {
         match self {
@@ -569,6 +1007,186 @@ impl ToSrc for ui::Direction {
     }
 }
 
+impl ToSrc for TextOverflow {
+    fn to_src(&self) -> proc_macro2::TokenStream {
+        match self {
+            TextOverflow::Clip => quote! {TextOverflow::Clip},
+            TextOverflow::Ellipsis => quote! {TextOverflow::Ellipsis},
+        }
+    }
+}
+
+impl ToSrc for TextAlignment {
+    fn to_src(&self) -> proc_macro2::TokenStream {
+        match self {
+            TextAlignment::Left => quote! {bevy::text::TextAlignment::Left},
+            TextAlignment::Center => quote! {bevy::text::TextAlignment::Center},
+            TextAlignment::Right => quote! {bevy::text::TextAlignment::Right},
+        }
+    }
+}
+
+impl ToSrc for CalcLength {
+    fn to_src(&self) -> proc_macro2::TokenStream {
+        let Self { percent, px } = *self;
+        quote! {bevy_peacock::CalcLength::new(#percent, #px)}
+    }
+}
+
+impl ToSrc for Length {
+    fn to_src(&self) -> proc_macro2::TokenStream {
+        match self {
+            Length::Val(val) => {
+                let val = val.to_src();
+                quote! {bevy_peacock::Length::Val(#val)}
+            }
+            Length::Rem(rem) => quote! {bevy_peacock::Length::Rem(#rem)},
+        }
+    }
+}
+
+impl ToSrc for CalcProperty {
+    fn to_src(&self) -> proc_macro2::TokenStream {
+        match self {
+            CalcProperty::Left => quote! {bevy_peacock::CalcProperty::Left},
+            CalcProperty::Right => quote! {bevy_peacock::CalcProperty::Right},
+            CalcProperty::Top => quote! {bevy_peacock::CalcProperty::Top},
+            CalcProperty::Bottom => quote! {bevy_peacock::CalcProperty::Bottom},
+            CalcProperty::Width => quote! {bevy_peacock::CalcProperty::Width},
+            CalcProperty::Height => quote! {bevy_peacock::CalcProperty::Height},
+            CalcProperty::MinWidth => quote! {bevy_peacock::CalcProperty::MinWidth},
+            CalcProperty::MinHeight => quote! {bevy_peacock::CalcProperty::MinHeight},
+            CalcProperty::MaxWidth => quote! {bevy_peacock::CalcProperty::MaxWidth},
+            CalcProperty::MaxHeight => quote! {bevy_peacock::CalcProperty::MaxHeight},
+        }
+    }
+}
+
+impl ToSrc for Transition {
+    fn to_src(&self) -> proc_macro2::TokenStream {
+        let property = self.property.to_src();
+        let delay = self.delay;
+        let duration = self.duration;
+        let timing = self.timing.to_src();
+        let quantum = match self.quantum {
+            Some(quantum) => quote! {Some(#quantum)},
+            None => quote! {None},
+        };
+        quote! {
+            bevy_peacock::Transition {
+                property: #property,
+                delay: #delay,
+                duration: #duration,
+                timing: #timing,
+                quantum: #quantum,
+            }
+        }
+    }
+}
+
+impl ToSrc for AnimateIn {
+    fn to_src(&self) -> proc_macro2::TokenStream {
+        let from = self.from.to_src();
+        let transition = self.transition.to_src();
+        quote! {
+            bevy_peacock::AnimateIn {
+                from: #from,
+                transition: #transition,
+            }
+        }
+    }
+}
+
+impl ToSrc for TransitionProperty {
+    fn to_src(&self) -> proc_macro2::TokenStream {
+        match self {
+            TransitionProperty::Transform => {
+                quote! {bevy_peacock::TransitionProperty::Transform}
+            }
+            TransitionProperty::Scale => quote! {bevy_peacock::TransitionProperty::Scale},
+            TransitionProperty::Rotation => quote! {bevy_peacock::TransitionProperty::Rotation},
+            TransitionProperty::Translation => {
+                quote! {bevy_peacock::TransitionProperty::Translation}
+            }
+            TransitionProperty::BackgroundColor => {
+                quote! {bevy_peacock::TransitionProperty::BackgroundColor}
+            }
+            TransitionProperty::BorderColor => {
+                quote! {bevy_peacock::TransitionProperty::BorderColor}
+            }
+            TransitionProperty::Left => quote! {bevy_peacock::TransitionProperty::Left},
+            TransitionProperty::Top => quote! {bevy_peacock::TransitionProperty::Top},
+            TransitionProperty::Bottom => quote! {bevy_peacock::TransitionProperty::Bottom},
+            TransitionProperty::Right => quote! {bevy_peacock::TransitionProperty::Right},
+            TransitionProperty::Height => quote! {bevy_peacock::TransitionProperty::Height},
+            TransitionProperty::Width => quote! {bevy_peacock::TransitionProperty::Width},
+            TransitionProperty::BorderLeft => {
+                quote! {bevy_peacock::TransitionProperty::BorderLeft}
+            }
+            TransitionProperty::BorderTop => {
+                quote! {bevy_peacock::TransitionProperty::BorderTop}
+            }
+            TransitionProperty::BorderRight => {
+                quote! {bevy_peacock::TransitionProperty::BorderRight}
+            }
+            TransitionProperty::BorderBottom => {
+                quote! {bevy_peacock::TransitionProperty::BorderBottom}
+            }
+            TransitionProperty::PaddingLeft => {
+                quote! {bevy_peacock::TransitionProperty::PaddingLeft}
+            }
+            TransitionProperty::PaddingRight => {
+                quote! {bevy_peacock::TransitionProperty::PaddingRight}
+            }
+            TransitionProperty::PaddingTop => {
+                quote! {bevy_peacock::TransitionProperty::PaddingTop}
+            }
+            TransitionProperty::PaddingBottom => {
+                quote! {bevy_peacock::TransitionProperty::PaddingBottom}
+            }
+            TransitionProperty::MarginLeft => {
+                quote! {bevy_peacock::TransitionProperty::MarginLeft}
+            }
+            TransitionProperty::MarginRight => {
+                quote! {bevy_peacock::TransitionProperty::MarginRight}
+            }
+            TransitionProperty::MarginTop => {
+                quote! {bevy_peacock::TransitionProperty::MarginTop}
+            }
+            TransitionProperty::MarginBottom => {
+                quote! {bevy_peacock::TransitionProperty::MarginBottom}
+            }
+            TransitionProperty::RowGap => {
+                quote! {bevy_peacock::TransitionProperty::RowGap}
+            }
+            TransitionProperty::ColumnGap => {
+                quote! {bevy_peacock::TransitionProperty::ColumnGap}
+            }
+            TransitionProperty::OutlineWidth => {
+                quote! {bevy_peacock::TransitionProperty::OutlineWidth}
+            }
+            TransitionProperty::OutlineOffset => {
+                quote! {bevy_peacock::TransitionProperty::OutlineOffset}
+            }
+            TransitionProperty::OutlineColor => {
+                quote! {bevy_peacock::TransitionProperty::OutlineColor}
+            }
+            TransitionProperty::FontSize => quote! {bevy_peacock::TransitionProperty::FontSize},
+        }
+    }
+}
+
+impl ToSrc for Timing {
+    fn to_src(&self) -> proc_macro2::TokenStream {
+        match self {
+            Timing::Linear => quote! {bevy_peacock::Timing::Linear},
+            Timing::EaseIn => quote! {bevy_peacock::Timing::EaseIn},
+            Timing::EaseOut => quote! {bevy_peacock::Timing::EaseOut},
+            Timing::EaseInOut => quote! {bevy_peacock::Timing::EaseInOut},
+        }
+    }
+}
+
 impl ToSrc for ui::FlexDirection {
     fn to_src(&self) -> proc_macro2::TokenStream {
         match self {
@@ -690,3 +1308,163 @@ impl ToSrc for ui::GridAutoFlow {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_strip_line_comment() {
+        assert_eq!(strip_line_comment("width: 10px;"), "width: 10px;");
+        assert_eq!(
+            strip_line_comment("width: 10px; // Comment"),
+            "width: 10px;"
+        );
+        assert_eq!(strip_line_comment("  // Comment\n"), "");
+        assert_eq!(strip_line_comment("height: 5px;  \r\n"), "height: 5px;");
+        assert_eq!(strip_line_comment("a / b // c"), "a / b");
+        assert_eq!(
+            strip_line_comment(r#"font: "http://fonts/a.ttf"; // Comment"#),
+            r#"font: "http://fonts/a.ttf";"#
+        );
+        assert_eq!(
+            strip_line_comment(r#"font: "a\"//b.ttf";"#),
+            r#"font: "a\"//b.ttf";"#
+        );
+    }
+
+    #[test]
+    fn test_read_stripped() {
+        let src = "// Header\n\nBUTTON {\n    width: 10px; // Width\n}";
+        let stripped = read_stripped(src.as_bytes()).unwrap();
+        assert_eq!(stripped, "\n\nBUTTON {\n    width: 10px;\n}\n");
+
+        // Stripping keeps line and column numbers the same.
+        let src = "// Header\nBUTTON {\n    width: 10px; // Width\n    height: x;\n}";
+        let err = parse_stylesheet_with(src, DuplicateRules::Error).unwrap_err();
+        let stripped = read_stripped(src.as_bytes()).unwrap();
+        let stripped_err = parse_stylesheet_with(&stripped, DuplicateRules::Error).unwrap_err();
+        assert_eq!(err.pos, stripped_err.pos);
+    }
+
+    #[test]
+    fn test_deny_lint_warnings() {
+        let path = Path::new("styles.pss");
+        let clean = "BUTTON {\n    width: 10px;\n}\n";
+        assert!(deny_lint_warnings(path, clean, DuplicateRules::Error).is_ok());
+        let src = "BUTTON {\n    width: 10px;\n    width: 20px;\n}\nEMPTY {}\n";
+        let err = deny_lint_warnings(path, src, DuplicateRules::Error).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "stylesheet file styles.pss has 2 lint warning(s):\n\
+            styles.pss:3:5 in rule 'BUTTON': 'width' overrides the value set at line 2, \
+            column 5 of the same block\n\
+            styles.pss:5:1 in rule 'EMPTY': rule has no properties"
+        );
+    }
+
+    #[test]
+    fn test_rule_idents() {
+        let idents = |names: &[&str]| rule_idents(names.iter().copied(), false);
+        assert_eq!(
+            idents(&["BUTTON", "button_2", "Self_"]).unwrap(),
+            ["BUTTON", "button_2", "Self_"]
+        );
+        assert_eq!(
+            idents(&["BUTTON", "loop"]).unwrap_err(),
+            "style name 'loop' is a Rust keyword; rename it or use the `rename_keywords` option"
+        );
+        let invalid = "isn't a valid Rust identifier; names must start with an ASCII letter, \
+            followed by ASCII letters, digits or '_'";
+        assert_eq!(
+            idents(&["2COLUMN"]).unwrap_err(),
+            format!("style name '2COLUMN' {}", invalid)
+        );
+        assert_eq!(
+            idents(&["BOUTON_ÉTÉ"]).unwrap_err(),
+            format!("style name 'BOUTON_ÉTÉ' {}", invalid)
+        );
+        assert!(idents(&[""]).is_err());
+        assert_eq!(
+            idents(&["register_styles"]).unwrap_err(),
+            "style name 'register_styles' is reserved for an item of the generated module; \
+            rename it"
+        );
+
+        // Keywords are renamed with a suffix, which mustn't collide with another style.
+        let renamed = |names: &[&str]| rule_idents(names.iter().copied(), true);
+        assert_eq!(
+            renamed(&["loop", "move", "self"]).unwrap(),
+            ["loop_", "move_", "self_"]
+        );
+        assert_eq!(
+            renamed(&["loop_", "PANEL", "loop"]).unwrap_err(),
+            "style names 'loop_' and 'loop' both generate the identifier `loop_`; rename one of \
+            them"
+        );
+    }
+
+    #[test]
+    fn test_stylesheet_idents() {
+        let path = Path::new("styles.pss");
+        let stylesheet = parse_stylesheet_with(
+            "BUTTON { width: 10px; }\nloop { width: 20px; }",
+            DuplicateRules::Error,
+        )
+        .unwrap();
+        let err = stylesheet_idents(path, &stylesheet, false).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "invalid style name in stylesheet file styles.pss: style name 'loop' is a Rust \
+            keyword; rename it or use the `rename_keywords` option"
+        );
+        assert_eq!(
+            stylesheet_idents(path, &stylesheet, true).unwrap(),
+            ["BUTTON", "loop_"]
+        );
+    }
+
+    #[test]
+    fn test_parse_options() {
+        let parse = |src: &str| syn::parse_str::<StylesheetInput>(src).unwrap();
+        let input = parse(r#"styles, "styles.pss""#);
+        assert!(matches!(input.duplicates, DuplicateRules::Error));
+        assert!(!input.spans && input.embed_source && !input.deny_warnings);
+        assert!(!input.rename_keywords);
+
+        let input = parse(
+            r#"styles, "styles.pss", merge_duplicates, spans, embed_source = false,
+            deny_warnings = true, rename_keywords,"#,
+        );
+        assert!(matches!(input.duplicates, DuplicateRules::Merge));
+        assert!(input.spans && !input.embed_source && input.deny_warnings);
+        assert!(input.rename_keywords);
+
+        let input = parse(r#"styles, "styles.pss", merge_duplicates = false, spans = true"#);
+        assert!(matches!(input.duplicates, DuplicateRules::Error));
+        assert!(input.spans);
+
+        let err = syn::parse_str::<StylesheetInput>(r#"styles, "styles.pss", spans = 1"#);
+        assert!(err.is_err());
+    }
+
+    #[test]
+    fn test_source_hash() {
+        // The hash is part of the generated code, so it mustn't change between builds.
+        assert_eq!(source_hash(""), 0xcbf2_9ce4_8422_2325);
+        assert_eq!(source_hash("a"), 0xaf63_dc4c_8601_ec8c);
+        assert_eq!(source_hash("foobar"), 0x8594_4171_f739_67e8);
+
+        let src = "BUTTON {\n    width: 10px;\n}\n";
+        let commented = "// Button\nBUTTON { // Start\n    width: 10px;   \n}";
+        let edited = "BUTTON {\n    width: 11px;\n}\n";
+        let strip = |src: &str| read_stripped(src.as_bytes()).unwrap();
+        assert_eq!(source_hash(&strip(src)), source_hash(&strip(src)));
+        assert_ne!(source_hash(&strip(src)), source_hash(&strip(edited)));
+        assert_ne!(source_hash(&strip(src)), source_hash(&strip(commented)));
+        assert_eq!(
+            source_hash(&strip(commented)),
+            source_hash(&strip("\nBUTTON {\n    width: 10px;\n}"))
+        );
+    }
+}
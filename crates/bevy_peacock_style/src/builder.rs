@@ -6,12 +6,16 @@ use bevy::{
     log::error,
     math::Vec3,
     prelude::Color,
+    text::TextAlignment,
     ui::{self, ZIndex},
 };
 
-use crate::{selector_parser, style::SelectorEntry, PointerEvents, StyleProp};
+use crate::{
+    style::{ScopeEntry, SelectorEntry},
+    CalcLength, CalcProperty, PointerEvents, Selector, StyleProp, StylePropList, TextOverflow,
+};
 
-use super::transition::Transition;
+use super::transition::{AnimateIn, Transition};
 
 /// Trait that represents a CSS color
 pub trait ColorParam {
@@ -111,6 +115,32 @@ impl<H: LengthParam, V: LengthParam> UiRectParam for (H, V) {
     }
 }
 
+/// Trait that represents CSS overflow, either for both axes or for each axis separately.
+pub trait OverflowParam {
+    fn to_overflow(self) -> ui::Overflow;
+}
+
+impl OverflowParam for ui::Overflow {
+    fn to_overflow(self) -> ui::Overflow {
+        self
+    }
+}
+
+impl OverflowParam for ui::OverflowAxis {
+    fn to_overflow(self) -> ui::Overflow {
+        ui::Overflow { x: self, y: self }
+    }
+}
+
+impl OverflowParam for (ui::OverflowAxis, ui::OverflowAxis) {
+    fn to_overflow(self) -> ui::Overflow {
+        ui::Overflow {
+            x: self.0,
+            y: self.1,
+        }
+    }
+}
+
 /// Trait that represents am optional float
 pub trait OptFloatParam {
     fn to_val(self) -> Option<f32>;
@@ -138,6 +168,7 @@ impl OptFloatParam for i32 {
 pub struct StyleBuilder {
     pub(crate) props: Vec<StyleProp>,
     pub(crate) selectors: Vec<SelectorEntry>,
+    pub(crate) scopes: Vec<ScopeEntry>,
 }
 
 impl StyleBuilder {
@@ -146,6 +177,22 @@ impl StyleBuilder {
         self
     }
 
+    pub fn background_image_flip_x(&mut self, flip: bool) -> &mut Self {
+        self.props.push(StyleProp::BackgroundImageFlipX(flip));
+        self
+    }
+
+    pub fn background_image_flip_y(&mut self, flip: bool) -> &mut Self {
+        self.props.push(StyleProp::BackgroundImageFlipY(flip));
+        self
+    }
+
+    pub fn background_image_tint(&mut self, color: impl ColorParam) -> &mut Self {
+        self.props
+            .push(StyleProp::BackgroundImageTint(color.to_val()));
+        self
+    }
+
     pub fn background_color(&mut self, color: impl ColorParam) -> &mut Self {
         self.props.push(StyleProp::BackgroundColor(color.to_val()));
         self
@@ -176,8 +223,8 @@ impl StyleBuilder {
         self
     }
 
-    pub fn overflow(&mut self, ov: ui::OverflowAxis) -> &mut Self {
-        self.props.push(StyleProp::Overflow(ov));
+    pub fn overflow(&mut self, ov: impl OverflowParam) -> &mut Self {
+        self.props.push(StyleProp::Overflow(ov.to_overflow()));
         self
     }
 
@@ -246,6 +293,13 @@ impl StyleBuilder {
         self
     }
 
+    /// Set a layout property to a length relative to the parent's size, such as
+    /// `calc(100% - 32px)`.
+    pub fn calc(&mut self, property: CalcProperty, length: CalcLength) -> &mut Self {
+        self.props.push(StyleProp::Calc(property, length));
+        self
+    }
+
     pub fn aspect_ratio(&mut self, length: impl OptFloatParam) -> &mut Self {
         self.props.push(StyleProp::AspectRatio(length.to_val()));
         self
@@ -364,7 +418,8 @@ impl StyleBuilder {
     }
 
     pub fn gap(&mut self, length: impl LengthParam) -> &mut Self {
-        self.props.push(StyleProp::Gap(length.to_val()));
+        let length = length.to_val();
+        self.props.push(StyleProp::Gap(length, length));
         self
     }
 
@@ -485,6 +540,16 @@ impl StyleBuilder {
         self
     }
 
+    pub fn text_overflow(&mut self, overflow: TextOverflow) -> &mut Self {
+        self.props.push(StyleProp::TextOverflow(overflow));
+        self
+    }
+
+    pub fn text_align(&mut self, align: TextAlignment) -> &mut Self {
+        self.props.push(StyleProp::TextAlign(align));
+        self
+    }
+
     pub fn font(&mut self, path: Option<AssetPath<'static>>) -> &mut Self {
         self.props.push(StyleProp::Font(path));
         self
@@ -495,6 +560,28 @@ impl StyleBuilder {
         self
     }
 
+    pub fn inherit_color(&mut self) -> &mut Self {
+        self.props.push(StyleProp::InheritColor);
+        self
+    }
+
+    pub fn inherit_font(&mut self) -> &mut Self {
+        self.props.push(StyleProp::InheritFont);
+        self
+    }
+
+    pub fn inherit_font_size(&mut self) -> &mut Self {
+        self.props.push(StyleProp::InheritFontSize);
+        self
+    }
+
+    /// Scale the pixel sizes of every element in this UI tree, including font sizes. Only
+    /// valid on root nodes.
+    pub fn ui_scale(&mut self, scale: f32) -> &mut Self {
+        self.props.push(StyleProp::UiScale(scale));
+        self
+    }
+
     pub fn scale_x(&mut self, scale: f32) -> &mut Self {
         self.props.push(StyleProp::ScaleX(scale));
         self
@@ -526,22 +613,92 @@ impl StyleBuilder {
         self
     }
 
+    pub fn animate_in(&mut self, animate_in: &[AnimateIn]) -> &mut Self {
+        self.props.push(StyleProp::AnimateIn(Vec::from(animate_in)));
+        self
+    }
+
+    pub fn elevate_while_animating(&mut self, elevate: bool) -> &mut Self {
+        self.props.push(StyleProp::ElevateWhileAnimating(elevate));
+        self
+    }
+
     /// Add a selector expression to this style declaration.
     pub fn selector(
         &mut self,
-        mut expr: &str,
+        expr: &str,
         builder_fn: impl FnOnce(&mut StyleBuilder) -> &mut StyleBuilder,
     ) -> &mut Self {
         let mut builder = StyleBuilder::default();
         builder_fn(&mut builder);
-        match selector_parser::selector_parser(&mut expr) {
+        match expr.parse::<Selector>() {
             Ok(selector) => {
-                self.selectors.push((selector, builder.props));
+                self.selectors.push((Box::new(selector), builder.props));
             }
             Err(err) => {
-                error!("{}: {}", err, expr)
+                error!("invalid selector '{}': {}", expr, err)
             }
         }
         self
     }
+
+    /// Add properties which only apply when the element's scope is named `scope`.
+    pub fn scope(
+        &mut self,
+        scope: &str,
+        builder_fn: impl FnOnce(&mut StyleBuilder) -> &mut StyleBuilder,
+    ) -> &mut Self {
+        let mut builder = StyleBuilder::default();
+        builder_fn(&mut builder);
+        self.scopes.push((scope.to_owned(), builder.props));
+        self
+    }
+
+    /// Call `builder_fn` to add properties only if `condition` is true. Unlike a selector,
+    /// the condition is decided once, when the style is built.
+    pub fn when(
+        &mut self,
+        condition: bool,
+        builder_fn: impl FnOnce(&mut StyleBuilder) -> &mut StyleBuilder,
+    ) -> &mut Self {
+        if condition {
+            builder_fn(self);
+        }
+        self
+    }
+
+    /// Call `builder_fn` with the value to add properties only if there is one.
+    pub fn when_some<T>(
+        &mut self,
+        value: Option<T>,
+        builder_fn: impl FnOnce(&mut StyleBuilder, T) -> &mut StyleBuilder,
+    ) -> &mut Self {
+        if let Some(value) = value {
+            builder_fn(self, value);
+        }
+        self
+    }
+
+    /// Call `builder_fn` with each item, in order, to add properties for it.
+    pub fn for_each<I: IntoIterator>(
+        &mut self,
+        items: I,
+        mut builder_fn: impl FnMut(&mut StyleBuilder, I::Item) -> &mut StyleBuilder,
+    ) -> &mut Self {
+        for item in items {
+            builder_fn(self, item);
+        }
+        self
+    }
+
+    /// Add properties after the ones already added, so that they take precedence over them.
+    pub fn extend(&mut self, props: impl IntoIterator<Item = StyleProp>) -> &mut Self {
+        self.props.extend(props);
+        self
+    }
+
+    /// Return the properties built so far as a [`StylePropList`], leaving the builder empty.
+    pub fn build(&mut self) -> StylePropList {
+        StylePropList::from_builder(std::mem::take(self))
+    }
 }
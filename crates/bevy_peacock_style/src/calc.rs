@@ -0,0 +1,140 @@
+use bevy::{math::Vec2, ui};
+
+use crate::TransitionProperty;
+
+/// A length computed from a percentage of the parent node's size plus a fixed number of
+/// pixels, such as `calc(100% - 32px)`. Unlike a plain percentage, this can't be expressed as
+/// a single [`ui::Val`], so it's resolved to pixels whenever styles are computed.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct CalcLength {
+    /// Percentage of the parent's size.
+    pub percent: f32,
+    /// Fixed offset in pixels.
+    pub px: f32,
+}
+
+impl CalcLength {
+    /// Construct a new `CalcLength`.
+    pub fn new(percent: f32, px: f32) -> Self {
+        Self { percent, px }
+    }
+
+    /// Return the length in pixels, given the size of the parent along the same axis.
+    pub fn resolve(&self, parent: f32) -> f32 {
+        parent * self.percent / 100. + self.px
+    }
+}
+
+impl std::ops::Add for CalcLength {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        Self::new(self.percent + rhs.percent, self.px + rhs.px)
+    }
+}
+
+impl std::ops::Neg for CalcLength {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Self::new(-self.percent, -self.px)
+    }
+}
+
+impl std::fmt::Display for CalcLength {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let sign = if self.px < 0. { '-' } else { '+' };
+        write!(f, "calc({}% {} {}px)", self.percent, sign, self.px.abs())
+    }
+}
+
+/// The layout properties which can be set to a [`CalcLength`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CalcProperty {
+    /// The `left` property.
+    Left,
+    /// The `right` property.
+    Right,
+    /// The `top` property.
+    Top,
+    /// The `bottom` property.
+    Bottom,
+    /// The `width` property.
+    Width,
+    /// The `height` property.
+    Height,
+    /// The `min_width` property.
+    MinWidth,
+    /// The `min_height` property.
+    MinHeight,
+    /// The `max_width` property.
+    MaxWidth,
+    /// The `max_height` property.
+    MaxHeight,
+}
+
+impl CalcProperty {
+    /// True if percentages are relative to the parent's width, false if they are relative to
+    /// its height.
+    pub fn is_horizontal(&self) -> bool {
+        matches!(
+            self,
+            CalcProperty::Left
+                | CalcProperty::Right
+                | CalcProperty::Width
+                | CalcProperty::MinWidth
+                | CalcProperty::MaxWidth
+        )
+    }
+
+    /// The name of the property in stylesheets.
+    pub fn name(&self) -> &'static str {
+        match self {
+            CalcProperty::Left => "left",
+            CalcProperty::Right => "right",
+            CalcProperty::Top => "top",
+            CalcProperty::Bottom => "bottom",
+            CalcProperty::Width => "width",
+            CalcProperty::Height => "height",
+            CalcProperty::MinWidth => "min_width",
+            CalcProperty::MinHeight => "min_height",
+            CalcProperty::MaxWidth => "max_width",
+            CalcProperty::MaxHeight => "max_height",
+        }
+    }
+
+    /// Return the animatable property corresponding to this one, if there is one.
+    pub fn transition_property(&self) -> Option<TransitionProperty> {
+        match self {
+            CalcProperty::Left => Some(TransitionProperty::Left),
+            CalcProperty::Right => Some(TransitionProperty::Right),
+            CalcProperty::Top => Some(TransitionProperty::Top),
+            CalcProperty::Bottom => Some(TransitionProperty::Bottom),
+            CalcProperty::Width => Some(TransitionProperty::Width),
+            CalcProperty::Height => Some(TransitionProperty::Height),
+            _ => None,
+        }
+    }
+
+    /// Resolve `length` against the size of the parent node, and store it in `style`.
+    pub fn apply(&self, style: &mut ui::Style, length: &CalcLength, parent_size: Vec2) {
+        let basis = if self.is_horizontal() {
+            parent_size.x
+        } else {
+            parent_size.y
+        };
+        let val = ui::Val::Px(length.resolve(basis));
+        match self {
+            CalcProperty::Left => style.left = val,
+            CalcProperty::Right => style.right = val,
+            CalcProperty::Top => style.top = val,
+            CalcProperty::Bottom => style.bottom = val,
+            CalcProperty::Width => style.width = val,
+            CalcProperty::Height => style.height = val,
+            CalcProperty::MinWidth => style.min_width = val,
+            CalcProperty::MinHeight => style.min_height = val,
+            CalcProperty::MaxWidth => style.max_width = val,
+            CalcProperty::MaxHeight => style.max_height = val,
+        }
+    }
+}
@@ -0,0 +1,158 @@
+use smallvec::SmallVec;
+
+use crate::{AppStateKind, CapabilityKind, MediaQuery, Selector};
+
+/// A single instruction of a [`CompiledSelector`].
+#[derive(Debug, PartialEq, Clone)]
+pub enum SelectorOp {
+    /// The element has the class name.
+    Class(String),
+    /// The element has a class name starting with the prefix.
+    ClassPrefix(String),
+    /// The element is being hovered.
+    Hover,
+    /// The element has keyboard focus.
+    Focus,
+    /// The element, or a descendant of it, has keyboard focus.
+    FocusWithin,
+    /// The element has keyboard focus, when focus is shown.
+    FocusVisible,
+    /// The element is the first child of its parent.
+    FirstChild,
+    /// The element is the last child of its parent.
+    LastChild,
+    /// The element is disabled.
+    Disabled,
+    /// The element is checked.
+    Checked,
+    /// The element is being dragged.
+    Dragging,
+    /// The element has something being dragged over it.
+    DragOver,
+    /// The app's color scheme is dark.
+    Dark,
+    /// The app has the capability.
+    Capability(CapabilityKind),
+    /// The app is in the state.
+    AppState(AppStateKind),
+    /// The app's locale matches the language tag.
+    Lang(String),
+    /// The element matches the custom pseudo-class.
+    Custom(String),
+    /// The primary window size matches the media query.
+    Media(MediaQuery),
+    /// The feature flag is enabled.
+    Flag(String),
+    /// Continue matching against the parent of the element.
+    Parent,
+    /// Any of the alternatives match. This is always the last instruction.
+    Either(Vec<CompiledSelector>),
+}
+
+/// A flattened form of a [`Selector`], which can be matched with a simple loop rather than by
+/// following a chain of boxes. The instructions are in the order they're evaluated: tests on
+/// the current element, then [`SelectorOp::Parent`] to move to its parent, and so on. The
+/// selector matches if the end is reached without a test failing.
+///
+/// Selectors are compiled once, when a [`StylePropList`](crate::StylePropList) is constructed,
+/// while the [`Selector`] tree is kept for display and analysis.
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct CompiledSelector(SmallVec<[SelectorOp; 4]>);
+
+impl CompiledSelector {
+    /// Compile a selector.
+    pub fn new(selector: &Selector) -> Self {
+        let mut ops = SmallVec::new();
+        let mut next = selector;
+        loop {
+            let (op, rest) = match next {
+                Selector::Accept => break,
+                Selector::Current(rest) => {
+                    next = rest;
+                    continue;
+                }
+                Selector::Either(opts) => {
+                    ops.push(SelectorOp::Either(
+                        opts.iter().map(|opt| Self::new(opt)).collect(),
+                    ));
+                    break;
+                }
+                Selector::Class(name, rest) => (SelectorOp::Class(name.clone()), rest),
+                Selector::ClassPrefix(prefix, rest) => {
+                    (SelectorOp::ClassPrefix(prefix.clone()), rest)
+                }
+                Selector::Hover(rest) => (SelectorOp::Hover, rest),
+                Selector::Focus(rest) => (SelectorOp::Focus, rest),
+                Selector::FocusWithin(rest) => (SelectorOp::FocusWithin, rest),
+                Selector::FocusVisible(rest) => (SelectorOp::FocusVisible, rest),
+                Selector::FirstChild(rest) => (SelectorOp::FirstChild, rest),
+                Selector::LastChild(rest) => (SelectorOp::LastChild, rest),
+                Selector::Disabled(rest) => (SelectorOp::Disabled, rest),
+                Selector::Checked(rest) => (SelectorOp::Checked, rest),
+                Selector::Dragging(rest) => (SelectorOp::Dragging, rest),
+                Selector::DragOver(rest) => (SelectorOp::DragOver, rest),
+                Selector::Dark(rest) => (SelectorOp::Dark, rest),
+                Selector::Capability(kind, rest) => (SelectorOp::Capability(*kind), rest),
+                Selector::AppState(kind, rest) => (SelectorOp::AppState(*kind), rest),
+                Selector::Lang(tag, rest) => (SelectorOp::Lang(tag.clone()), rest),
+                Selector::Custom(name, rest) => (SelectorOp::Custom(name.clone()), rest),
+                Selector::Media(query, rest) => (SelectorOp::Media(*query), rest),
+                Selector::Flag(name, rest) => (SelectorOp::Flag(name.clone()), rest),
+                Selector::Parent(rest) => (SelectorOp::Parent, rest),
+            };
+            ops.push(op);
+            next = rest;
+        }
+        Self(ops)
+    }
+
+    /// The instructions, in the order they're evaluated.
+    pub fn ops(&self) -> &[SelectorOp] {
+        &self.0
+    }
+}
+
+impl From<&Selector> for CompiledSelector {
+    fn from(selector: &Selector) -> Self {
+        Self::new(selector)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn compile(src: &str) -> Vec<SelectorOp> {
+        CompiledSelector::new(&src.parse::<Selector>().unwrap())
+            .ops()
+            .to_vec()
+    }
+
+    #[test]
+    fn test_compile_selector() {
+        assert_eq!(compile("&"), vec![]);
+        assert_eq!(
+            compile(".a > * > &.b:hover"),
+            vec![
+                SelectorOp::Hover,
+                SelectorOp::Class("b".into()),
+                SelectorOp::Parent,
+                SelectorOp::Parent,
+                SelectorOp::Class("a".into()),
+            ]
+        );
+        assert_eq!(
+            compile("@media (max-width: 800) .x > &, &:--busy"),
+            vec![
+                SelectorOp::Media(MediaQuery {
+                    max_width: Some(800.),
+                    ..Default::default()
+                }),
+                SelectorOp::Either(vec![
+                    CompiledSelector::new(&".x > &".parse::<Selector>().unwrap()),
+                    CompiledSelector::new(&"&:--busy".parse::<Selector>().unwrap()),
+                ]),
+            ]
+        );
+    }
+}
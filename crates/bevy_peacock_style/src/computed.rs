@@ -1,10 +1,10 @@
-use super::style::PointerEvents;
-use super::transition::Transition;
+use super::calc::{CalcLength, CalcProperty};
+use super::style::{PointerEvents, TextOverflow};
+use super::style_field::StyleFieldSet;
+use super::transition::{AnimateIn, Transition, TransitionPropertySet};
 use bevy::asset::AssetPath;
 use bevy::prelude::*;
 use bevy::text::BreakLineOn;
-#[cfg(feature = "bevy_mod_picking")]
-use bevy_mod_picking::prelude::Pickable;
 
 /// A computed style represents the composition of one or more `ElementStyle`s.
 #[derive(Default, Clone, Debug)]
@@ -19,6 +19,11 @@ pub struct ComputedStyle {
     pub font: Option<AssetPath<'static>>,
     pub font_handle: Option<Handle<Font>>,
     pub line_break: Option<BreakLineOn>,
+    pub text_overflow: Option<TextOverflow>,
+
+    // Text properties inherited from the parent, used to resolve `inherit`
+    pub inherited_color: Option<Color>,
+    pub inherited_font_size: Option<f32>,
 
     // pub text_style: TextStyle,
     pub border_color: Option<Color>,
@@ -34,17 +39,42 @@ pub struct ComputedStyle {
     pub rotation: Option<f32>,
     pub translation: Option<Vec3>,
 
+    // Scale factor set by the `ui_scale` property, which is only valid on root nodes.
+    pub ui_scale: Option<f32>,
+
+    // Scale factor applied to pixel sizes as style props are applied, inherited from the root.
+    pub px_scale: Option<f32>,
+
     // Image properties
     pub image: Option<AssetPath<'static>>,
     pub image_handle: Option<Handle<Image>>,
     pub flip_x: bool,
     pub flip_y: bool,
+    pub image_tint: Option<Color>,
 
     // Picking properties
     pub pickable: Option<PointerEvents>,
 
     // Transitiions
     pub transitions: Vec<Transition>,
+
+    // Animations which play once, when the style is first applied to the element.
+    pub animate_in: Vec<AnimateIn>,
+
+    // Whether the z-index is raised while the transform is animating.
+    pub elevate_while_animating: bool,
+
+    // Animatable properties which were explicitly set by an applied style prop, as opposed to
+    // left at their initial values.
+    pub explicit_props: TransitionPropertySet,
+
+    // Fields of `style` which were set by an applied style prop. Only these are written to the
+    // element's `Style`, so that other systems can own the rest.
+    pub style_fields: StyleFieldSet,
+
+    // Layout properties set to a `calc()` length, which are resolved against the size of the
+    // parent node once all styles have been applied.
+    pub calc: Vec<(CalcProperty, CalcLength)>,
 }
 
 impl ComputedStyle {
@@ -52,4 +82,12 @@ impl ComputedStyle {
     pub fn new() -> Self {
         Self { ..default() }
     }
+
+    /// Resolve the `calc()` lengths against the size of the parent node, replacing the
+    /// corresponding layout properties with pixel values.
+    pub fn resolve_calc(&mut self, parent_size: Vec2) {
+        for (property, length) in self.calc.iter() {
+            property.apply(&mut self.style, length, parent_size);
+        }
+    }
 }
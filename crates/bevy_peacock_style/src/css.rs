@@ -0,0 +1,835 @@
+//! Serialization of style properties back into stylesheet syntax.
+
+use std::fmt::{self, Write};
+
+use bevy::{asset::AssetPath, math::Vec3, render::color::Color, text::TextAlignment, ui};
+
+use crate::{
+    AnimateIn, Length, StyleProp, StylePropList, TextOverflow, Timing, Transition,
+    TransitionProperty,
+};
+
+/// A property value which can be written in stylesheet syntax. This is the inverse of the
+/// parser's `CoercePropValue`: parsing the output yields the same value.
+pub(crate) trait CssValue {
+    fn fmt_css(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result;
+}
+
+/// Adapter which formats a [`CssValue`] with `Display`.
+pub(crate) struct Css<'a, T: ?Sized>(pub(crate) &'a T);
+
+impl<'a, T: CssValue + ?Sized> fmt::Display for Css<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt_css(f)
+    }
+}
+
+impl CssValue for f32 {
+    fn fmt_css(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
+impl CssValue for bool {
+    fn fmt_css(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
+impl CssValue for i16 {
+    fn fmt_css(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
+impl CssValue for u16 {
+    fn fmt_css(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self)
+    }
+}
+
+impl CssValue for Option<f32> {
+    fn fmt_css(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Some(value) => write!(f, "{}", value),
+            None => write!(f, "none"),
+        }
+    }
+}
+
+impl CssValue for ui::Val {
+    fn fmt_css(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ui::Val::Auto => write!(f, "auto"),
+            ui::Val::Px(v) => write!(f, "{}px", v),
+            ui::Val::Percent(v) => write!(f, "{}%", v),
+            ui::Val::Vw(v) => write!(f, "{}vw", v),
+            ui::Val::Vh(v) => write!(f, "{}vh", v),
+            ui::Val::VMin(v) => write!(f, "{}vmin", v),
+            ui::Val::VMax(v) => write!(f, "{}vmax", v),
+        }
+    }
+}
+
+impl CssValue for Length {
+    fn fmt_css(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Length::Val(val) => val.fmt_css(f),
+            Length::Rem(rem) => write!(f, "{}rem", rem),
+        }
+    }
+}
+
+/// Lengths of a shorthand such as `padding`, separated by spaces.
+impl CssValue for [Length] {
+    fn fmt_css(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, length) in self.iter().enumerate() {
+            if index > 0 {
+                write!(f, " ")?;
+            }
+            length.fmt_css(f)?;
+        }
+        Ok(())
+    }
+}
+
+/// Written with as few values as will reproduce the rect: one if all sides are equal, two
+/// (horizontal, vertical) if opposite sides are equal, otherwise left, right, top, bottom.
+impl CssValue for ui::UiRect {
+    fn fmt_css(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.left == self.right && self.top == self.bottom {
+            if self.left == self.top {
+                write!(f, "{}", Css(&self.left))
+            } else {
+                write!(f, "{} {}", Css(&self.left), Css(&self.top))
+            }
+        } else {
+            write!(
+                f,
+                "{} {} {} {}",
+                Css(&self.left),
+                Css(&self.right),
+                Css(&self.top),
+                Css(&self.bottom)
+            )
+        }
+    }
+}
+
+impl CssValue for Vec3 {
+    fn fmt_css(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}px {}px {}px", self.x, self.y, self.z)
+    }
+}
+
+/// Colors are written as hex if that's exact, and as a color function otherwise. `None` is
+/// `transparent`.
+impl CssValue for Option<Color> {
+    fn fmt_css(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            None => write!(f, "transparent"),
+            Some(Color::Rgba {
+                red,
+                green,
+                blue,
+                alpha,
+            }) => {
+                let channels = [red, green, blue, alpha];
+                let bytes = channels.map(|c| (c * 255.).round());
+                let exact = channels
+                    .iter()
+                    .zip(bytes)
+                    .all(|(c, b)| (0. ..=255.).contains(&b) && b / 255. == *c);
+                if !exact {
+                    return write!(f, "rgba({}, {}, {}, {})", red, green, blue, alpha);
+                }
+                write!(
+                    f,
+                    "#{:02x}{:02x}{:02x}",
+                    bytes[0] as u8, bytes[1] as u8, bytes[2] as u8
+                )?;
+                if bytes[3] != 255. {
+                    write!(f, "{:02x}", bytes[3] as u8)?;
+                }
+                Ok(())
+            }
+            Some(Color::RgbaLinear {
+                red,
+                green,
+                blue,
+                alpha,
+            }) => write!(f, "rgba_linear({}, {}, {}, {})", red, green, blue, alpha),
+            Some(Color::Hsla {
+                hue,
+                saturation,
+                lightness,
+                alpha,
+            }) => write!(f, "hsla({}, {}, {}, {})", hue, saturation, lightness, alpha),
+            // There's no syntax for LCH colors, so they are converted to sRGB.
+            Some(color @ Color::Lcha { .. }) => Some(color.as_rgba()).fmt_css(f),
+        }
+    }
+}
+
+impl CssValue for Option<AssetPath<'static>> {
+    fn fmt_css(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Some(path) => {
+                f.write_char('"')?;
+                for c in path.to_string().chars() {
+                    match c {
+                        '"' => f.write_str("\\\"")?,
+                        '\\' => f.write_str("\\\\")?,
+                        '\n' => f.write_str("\\n")?,
+                        c => f.write_char(c)?,
+                    }
+                }
+                f.write_char('"')
+            }
+            None => write!(f, "none"),
+        }
+    }
+}
+
+impl CssValue for ui::ZIndex {
+    fn fmt_css(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ui::ZIndex::Local(z) => write!(f, "{}", z),
+            ui::ZIndex::Global(z) => write!(f, "global({})", z),
+        }
+    }
+}
+
+impl CssValue for ui::Overflow {
+    fn fmt_css(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.x == self.y {
+            self.x.fmt_css(f)
+        } else {
+            write!(f, "{} {}", Css(&self.x), Css(&self.y))
+        }
+    }
+}
+
+/// Implement [`CssValue`] for a type which is written as one of a fixed set of identifiers.
+macro_rules! css_ident {
+    ($ty:ty { $($variant:path => $name:literal),* $(,)? }) => {
+        impl CssValue for $ty {
+            fn fmt_css(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str(match self {
+                    $($variant => $name,)*
+                })
+            }
+        }
+    };
+}
+
+css_ident!(ui::Display {
+    ui::Display::Flex => "flex",
+    ui::Display::Grid => "grid",
+    ui::Display::None => "none",
+});
+
+css_ident!(ui::PositionType {
+    ui::PositionType::Relative => "relative",
+    ui::PositionType::Absolute => "absolute",
+});
+
+css_ident!(ui::OverflowAxis {
+    ui::OverflowAxis::Clip => "clip",
+    ui::OverflowAxis::Visible => "visible",
+});
+
+css_ident!(ui::Direction {
+    ui::Direction::Inherit => "inherit",
+    ui::Direction::RightToLeft => "rtl",
+    ui::Direction::LeftToRight => "ltr",
+});
+
+css_ident!(TextAlignment {
+    TextAlignment::Left => "left",
+    TextAlignment::Center => "center",
+    TextAlignment::Right => "right",
+});
+
+css_ident!(TextOverflow {
+    TextOverflow::Clip => "clip",
+    TextOverflow::Ellipsis => "ellipsis",
+});
+
+css_ident!(ui::FlexDirection {
+    ui::FlexDirection::Row => "row",
+    ui::FlexDirection::RowReverse => "row_reverse",
+    ui::FlexDirection::Column => "column",
+    ui::FlexDirection::ColumnReverse => "column_reverse",
+});
+
+css_ident!(ui::FlexWrap {
+    ui::FlexWrap::NoWrap => "nowrap",
+    ui::FlexWrap::Wrap => "wrap",
+    ui::FlexWrap::WrapReverse => "wrap_reverse",
+});
+
+css_ident!(ui::AlignItems {
+    ui::AlignItems::Default => "default",
+    ui::AlignItems::Start => "start",
+    ui::AlignItems::End => "end",
+    ui::AlignItems::FlexStart => "flex_start",
+    ui::AlignItems::FlexEnd => "flex_end",
+    ui::AlignItems::Center => "center",
+    ui::AlignItems::Baseline => "baseline",
+    ui::AlignItems::Stretch => "stretch",
+});
+
+css_ident!(ui::AlignContent {
+    ui::AlignContent::Default => "default",
+    ui::AlignContent::Start => "start",
+    ui::AlignContent::End => "end",
+    ui::AlignContent::FlexStart => "flex_start",
+    ui::AlignContent::FlexEnd => "flex_end",
+    ui::AlignContent::Center => "center",
+    ui::AlignContent::Stretch => "stretch",
+    ui::AlignContent::SpaceBetween => "space_between",
+    ui::AlignContent::SpaceEvenly => "space_evenly",
+    ui::AlignContent::SpaceAround => "space_around",
+});
+
+css_ident!(ui::AlignSelf {
+    ui::AlignSelf::Auto => "auto",
+    ui::AlignSelf::Start => "start",
+    ui::AlignSelf::End => "end",
+    ui::AlignSelf::FlexStart => "flex_start",
+    ui::AlignSelf::FlexEnd => "flex_end",
+    ui::AlignSelf::Center => "center",
+    ui::AlignSelf::Baseline => "baseline",
+    ui::AlignSelf::Stretch => "stretch",
+});
+
+css_ident!(ui::JustifyItems {
+    ui::JustifyItems::Default => "default",
+    ui::JustifyItems::Start => "start",
+    ui::JustifyItems::End => "end",
+    ui::JustifyItems::Center => "center",
+    ui::JustifyItems::Baseline => "baseline",
+    ui::JustifyItems::Stretch => "stretch",
+});
+
+css_ident!(ui::JustifyContent {
+    ui::JustifyContent::Default => "default",
+    ui::JustifyContent::Start => "start",
+    ui::JustifyContent::End => "end",
+    ui::JustifyContent::FlexStart => "flex_start",
+    ui::JustifyContent::FlexEnd => "flex_end",
+    ui::JustifyContent::Center => "center",
+    ui::JustifyContent::Stretch => "stretch",
+    ui::JustifyContent::SpaceBetween => "space_between",
+    ui::JustifyContent::SpaceEvenly => "space_evenly",
+    ui::JustifyContent::SpaceAround => "space_around",
+});
+
+css_ident!(ui::JustifySelf {
+    ui::JustifySelf::Auto => "auto",
+    ui::JustifySelf::Start => "start",
+    ui::JustifySelf::End => "end",
+    ui::JustifySelf::Center => "center",
+    ui::JustifySelf::Baseline => "baseline",
+    ui::JustifySelf::Stretch => "stretch",
+});
+
+css_ident!(ui::GridAutoFlow {
+    ui::GridAutoFlow::Row => "row",
+    ui::GridAutoFlow::RowDense => "row_dense",
+    ui::GridAutoFlow::Column => "column",
+    ui::GridAutoFlow::ColumnDense => "column_dense",
+});
+
+css_ident!(TransitionProperty {
+    TransitionProperty::Transform => "transform",
+    TransitionProperty::Scale => "scale",
+    TransitionProperty::Rotation => "rotation",
+    TransitionProperty::Translation => "translation",
+    TransitionProperty::BackgroundColor => "background_color",
+    TransitionProperty::BorderColor => "border_color",
+    TransitionProperty::Left => "left",
+    TransitionProperty::Top => "top",
+    TransitionProperty::Bottom => "bottom",
+    TransitionProperty::Right => "right",
+    TransitionProperty::Height => "height",
+    TransitionProperty::Width => "width",
+    TransitionProperty::BorderLeft => "border_left",
+    TransitionProperty::BorderTop => "border_top",
+    TransitionProperty::BorderRight => "border_right",
+    TransitionProperty::BorderBottom => "border_bottom",
+    TransitionProperty::PaddingLeft => "padding_left",
+    TransitionProperty::PaddingRight => "padding_right",
+    TransitionProperty::PaddingTop => "padding_top",
+    TransitionProperty::PaddingBottom => "padding_bottom",
+    TransitionProperty::MarginLeft => "margin_left",
+    TransitionProperty::MarginRight => "margin_right",
+    TransitionProperty::MarginTop => "margin_top",
+    TransitionProperty::MarginBottom => "margin_bottom",
+    TransitionProperty::RowGap => "row_gap",
+    TransitionProperty::ColumnGap => "column_gap",
+    TransitionProperty::OutlineWidth => "outline_width",
+    TransitionProperty::OutlineOffset => "outline_offset",
+    TransitionProperty::OutlineColor => "outline_color",
+    TransitionProperty::FontSize => "font_size",
+});
+
+css_ident!(Timing {
+    Timing::Linear => "linear",
+    Timing::EaseIn => "ease_in",
+    Timing::EaseOut => "ease_out",
+    Timing::EaseInOut => "ease_in_out",
+});
+
+/// Options which have their default values are omitted.
+impl CssValue for Transition {
+    fn fmt_css(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}s", Css(&self.property), self.duration)?;
+        if self.timing != Timing::Linear {
+            write!(f, " {}", Css(&self.timing))?;
+        }
+        if self.delay != 0. {
+            write!(f, " {}s", self.delay)?;
+        }
+        if let Some(quantum) = self.quantum {
+            write!(f, " quantize({}px)", quantum)?;
+        }
+        Ok(())
+    }
+}
+
+impl CssValue for Vec<Transition> {
+    fn fmt_css(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, transition) in self.iter().enumerate() {
+            if index > 0 {
+                write!(f, ", ")?;
+            }
+            transition.fmt_css(f)?;
+        }
+        Ok(())
+    }
+}
+
+/// The starting value is written as the value of the `from` declaration.
+impl CssValue for AnimateIn {
+    fn fmt_css(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let from = self.from.to_string();
+        let value = from
+            .split_once(": ")
+            .map_or(from.as_str(), |(_, value)| value);
+        write!(
+            f,
+            "{} {} {}s",
+            Css(&self.transition.property),
+            value,
+            self.transition.duration
+        )?;
+        if self.transition.timing != Timing::Linear {
+            write!(f, " {}", Css(&self.transition.timing))?;
+        }
+        if self.transition.delay != 0. {
+            write!(f, " {}s", self.transition.delay)?;
+        }
+        Ok(())
+    }
+}
+
+impl CssValue for Vec<AnimateIn> {
+    fn fmt_css(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, anim) in self.iter().enumerate() {
+            if index > 0 {
+                write!(f, ", ")?;
+            }
+            anim.fmt_css(f)?;
+        }
+        Ok(())
+    }
+}
+
+impl StyleProp {
+    /// Returns false for properties which have no stylesheet syntax yet. These are written
+    /// with their debug representation as the value.
+    fn has_syntax(&self) -> bool {
+        if let StyleProp::Important(prop) = self {
+            return prop.has_syntax();
+        }
+        !matches!(
+            self,
+            StyleProp::GridTemplateRows(_)
+                | StyleProp::GridTemplateColumns(_)
+                | StyleProp::GridAutoRows(_)
+                | StyleProp::GridAutoColumns(_)
+                | StyleProp::GridRow(_)
+                | StyleProp::GridColumn(_)
+                | StyleProp::PointerEvents(_)
+                | StyleProp::Cursor(_)
+                | StyleProp::CursorImage(_)
+                | StyleProp::CursorOffset(_)
+        )
+    }
+}
+
+/// Writes the property as a stylesheet declaration, such as `width: 10px`, without the
+/// trailing semicolon.
+impl fmt::Display for StyleProp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StyleProp::BackgroundImage(v) => write!(f, "background_image: {}", Css(v)),
+            StyleProp::BackgroundImageFlipX(v) => {
+                write!(f, "background_image_flip_x: {}", Css(v))
+            }
+            StyleProp::BackgroundImageFlipY(v) => {
+                write!(f, "background_image_flip_y: {}", Css(v))
+            }
+            StyleProp::BackgroundImageTint(v) => write!(f, "background_image_tint: {}", Css(v)),
+            StyleProp::BackgroundColor(v) => write!(f, "background_color: {}", Css(v)),
+            StyleProp::BorderColor(v) => write!(f, "border_color: {}", Css(v)),
+            StyleProp::Color(v) => write!(f, "color: {}", Css(v)),
+
+            StyleProp::ZIndex(v) => write!(f, "z_index: {}", Css(v)),
+
+            StyleProp::Display(v) => write!(f, "display: {}", Css(v)),
+            StyleProp::Position(v) => write!(f, "position_type: {}", Css(v)),
+            StyleProp::Overflow(v) => write!(f, "overflow: {}", Css(v)),
+            StyleProp::OverflowX(v) => write!(f, "overflow_x: {}", Css(v)),
+            StyleProp::OverflowY(v) => write!(f, "overflow_y: {}", Css(v)),
+            StyleProp::Direction(v) => write!(f, "direction: {}", Css(v)),
+
+            StyleProp::Left(v) => write!(f, "left: {}", Css(v)),
+            StyleProp::Right(v) => write!(f, "right: {}", Css(v)),
+            StyleProp::Top(v) => write!(f, "top: {}", Css(v)),
+            StyleProp::Bottom(v) => write!(f, "bottom: {}", Css(v)),
+
+            StyleProp::Width(v) => write!(f, "width: {}", Css(v)),
+            StyleProp::Height(v) => write!(f, "height: {}", Css(v)),
+            StyleProp::MinWidth(v) => write!(f, "min_width: {}", Css(v)),
+            StyleProp::MinHeight(v) => write!(f, "min_height: {}", Css(v)),
+            StyleProp::MaxWidth(v) => write!(f, "max_width: {}", Css(v)),
+            StyleProp::MaxHeight(v) => write!(f, "max_height: {}", Css(v)),
+            StyleProp::AspectRatio(v) => write!(f, "aspect_ratio: {}", Css(v)),
+
+            StyleProp::Calc(property, length) => write!(f, "{}: {}", property.name(), length),
+
+            StyleProp::Margin(v) => write!(f, "margin: {}", Css(v)),
+            StyleProp::MarginLeft(v) => write!(f, "margin_left: {}", Css(v)),
+            StyleProp::MarginRight(v) => write!(f, "margin_right: {}", Css(v)),
+            StyleProp::MarginTop(v) => write!(f, "margin_top: {}", Css(v)),
+            StyleProp::MarginBottom(v) => write!(f, "margin_bottom: {}", Css(v)),
+
+            StyleProp::Padding(v) => write!(f, "padding: {}", Css(v)),
+            StyleProp::PaddingLeft(v) => write!(f, "padding_left: {}", Css(v)),
+            StyleProp::PaddingRight(v) => write!(f, "padding_right: {}", Css(v)),
+            StyleProp::PaddingTop(v) => write!(f, "padding_top: {}", Css(v)),
+            StyleProp::PaddingBottom(v) => write!(f, "padding_bottom: {}", Css(v)),
+
+            StyleProp::Border(v) => write!(f, "border: {}", Css(v)),
+            StyleProp::BorderLeft(v) => write!(f, "border_left: {}", Css(v)),
+            StyleProp::BorderRight(v) => write!(f, "border_right: {}", Css(v)),
+            StyleProp::BorderTop(v) => write!(f, "border_top: {}", Css(v)),
+            StyleProp::BorderBottom(v) => write!(f, "border_bottom: {}", Css(v)),
+
+            StyleProp::FlexDirection(v) => write!(f, "flex_direction: {}", Css(v)),
+            StyleProp::FlexWrap(v) => write!(f, "flex_wrap: {}", Css(v)),
+            StyleProp::FlexGrow(v) => write!(f, "flex_grow: {}", Css(v)),
+            StyleProp::FlexShrink(v) => write!(f, "flex_shrink: {}", Css(v)),
+            StyleProp::FlexBasis(v) => write!(f, "flex_basis: {}", Css(v)),
+            StyleProp::RowGap(v) => write!(f, "row_gap: {}", Css(v)),
+            StyleProp::ColumnGap(v) => write!(f, "column_gap: {}", Css(v)),
+            StyleProp::Gap(row, column) if row == column => write!(f, "gap: {}", Css(row)),
+            StyleProp::Gap(row, column) => write!(f, "gap: {} {}", Css(row), Css(column)),
+
+            StyleProp::AlignItems(v) => write!(f, "align_items: {}", Css(v)),
+            StyleProp::AlignSelf(v) => write!(f, "align_self: {}", Css(v)),
+            StyleProp::AlignContent(v) => write!(f, "align_content: {}", Css(v)),
+            StyleProp::JustifyItems(v) => write!(f, "justify_items: {}", Css(v)),
+            StyleProp::JustifySelf(v) => write!(f, "justify_self: {}", Css(v)),
+            StyleProp::JustifyContent(v) => write!(f, "justify_content: {}", Css(v)),
+
+            StyleProp::GridAutoFlow(v) => write!(f, "grid_auto_flow: {}", Css(v)),
+            StyleProp::GridTemplateRows(v) => write!(f, "grid_template_rows: {:?}", v),
+            StyleProp::GridTemplateColumns(v) => write!(f, "grid_template_columns: {:?}", v),
+            StyleProp::GridAutoRows(v) => write!(f, "grid_auto_rows: {:?}", v),
+            StyleProp::GridAutoColumns(v) => write!(f, "grid_auto_columns: {:?}", v),
+            StyleProp::GridRow(v) => write!(f, "grid_row: {:?}", v),
+            StyleProp::GridRowStart(v) => write!(f, "grid_row_start: {}", Css(v)),
+            StyleProp::GridRowSpan(v) => write!(f, "grid_row_span: {}", Css(v)),
+            StyleProp::GridRowEnd(v) => write!(f, "grid_row_end: {}", Css(v)),
+            StyleProp::GridColumn(v) => write!(f, "grid_column: {:?}", v),
+            StyleProp::GridColumnStart(v) => write!(f, "grid_column_start: {}", Css(v)),
+            StyleProp::GridColumnSpan(v) => write!(f, "grid_column_span: {}", Css(v)),
+            StyleProp::GridColumnEnd(v) => write!(f, "grid_column_end: {}", Css(v)),
+
+            StyleProp::PointerEvents(v) => write!(f, "pointer_events: {:?}", v),
+
+            StyleProp::Font(v) => write!(f, "font: {}", Css(v)),
+            StyleProp::FontSize(v) => write!(f, "font_size: {}", Css(v)),
+            StyleProp::TextOverflow(v) => write!(f, "text_overflow: {}", Css(v)),
+            StyleProp::TextAlign(v) => write!(f, "text_align: {}", Css(v)),
+
+            StyleProp::InheritColor => write!(f, "color: inherit"),
+            StyleProp::InheritFont => write!(f, "font: inherit"),
+            StyleProp::InheritFontSize => write!(f, "font_size: inherit"),
+
+            StyleProp::OutlineColor(v) => write!(f, "outline_color: {}", Css(v)),
+            StyleProp::OutlineWidth(v) => write!(f, "outline_width: {}", Css(v)),
+            StyleProp::OutlineOffset(v) => write!(f, "outline_offset: {}", Css(v)),
+
+            StyleProp::Cursor(v) => write!(f, "cursor: {:?}", v),
+            StyleProp::CursorImage(v) => write!(f, "cursor_image: {:?}", v),
+            StyleProp::CursorOffset(v) => write!(f, "cursor_offset: {:?}", v),
+
+            StyleProp::Scale(v) => write!(f, "scale: {}", Css(v)),
+            StyleProp::ScaleX(v) => write!(f, "scale_x: {}", Css(v)),
+            StyleProp::ScaleY(v) => write!(f, "scale_y: {}", Css(v)),
+            StyleProp::Rotation(v) => write!(f, "rotation: {}rad", v),
+            StyleProp::Translation(v) => write!(f, "translation: {}", Css(v)),
+
+            StyleProp::UiScale(v) => write!(f, "ui_scale: {}", Css(v)),
+
+            StyleProp::Transition(v) => write!(f, "transition: {}", Css(v)),
+            StyleProp::AnimateIn(v) => write!(f, "animate_in: {}", Css(v)),
+            StyleProp::ElevateWhileAnimating(v) => {
+                write!(f, "elevate_while_animating: {}", Css(v))
+            }
+            StyleProp::Token(property, token) => write!(f, "{}: token({})", property, token),
+            StyleProp::Rem(property, lengths) => write!(f, "{}: {}", property, Css(&lengths[..])),
+            StyleProp::Important(prop) => write!(f, "{} !important", prop),
+        }
+    }
+}
+
+/// Write a block of declarations, one per line. Properties which have no stylesheet syntax
+/// are commented out, so that the output can still be parsed.
+fn write_props(out: &mut String, props: &[StyleProp], indent: &str) {
+    for prop in props {
+        let comment = if prop.has_syntax() { "" } else { "// " };
+        let _ = writeln!(out, "{}{}{};", indent, comment, prop);
+    }
+}
+
+impl StylePropList {
+    /// Serialize the style as a stylesheet rule named `name`, including its scope and selector blocks.
+    ///
+    /// Parsing the result with [`parse_stylesheet`](crate::parse_stylesheet) reproduces the
+    /// style, except for properties which have no stylesheet syntax (such as grid templates
+    /// and cursors), which are written as comments, and LCH colors, which are converted to
+    /// sRGB.
+    pub fn to_css(&self, name: &str) -> String {
+        let mut out = String::new();
+        let _ = writeln!(out, "{} {{", name);
+        write_props(&mut out, self.props(), "    ");
+        for (scope, props) in self.scopes() {
+            let _ = writeln!(out, "    @scope({}) {{", scope);
+            write_props(&mut out, props, "        ");
+            let _ = writeln!(out, "    }}");
+        }
+        for (selector, props) in self.selectors() {
+            let _ = writeln!(out, "    {} {{", selector);
+            write_props(&mut out, props, "        ");
+            let _ = writeln!(out, "    }}");
+        }
+        out.push_str("}\n");
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cursor::Cursor;
+    use crate::{parse_stylesheet, CalcLength, CalcProperty, PointerEvents};
+
+    /// Serialize `list`, parse the result, and check that it's the same as the original.
+    #[track_caller]
+    fn round_trip(list: &StylePropList) -> String {
+        let css = list.to_css("TEST");
+        let mut parsed = parse_stylesheet(&css).unwrap_or_else(|err| panic!("{}\n{}", err, css));
+        assert_eq!(parsed.len(), 1);
+        let (name, reparsed) = parsed.pop().unwrap();
+        assert_eq!(name, "TEST");
+        assert_eq!(
+            format!(
+                "{:?}",
+                (reparsed.props(), reparsed.selectors(), reparsed.scopes())
+            ),
+            format!("{:?}", (list.props(), list.selectors(), list.scopes())),
+            "{}",
+            css
+        );
+        css
+    }
+
+    #[test]
+    fn test_display_style_prop() {
+        assert_eq!(
+            StyleProp::Width(ui::Val::Px(10.)).to_string(),
+            "width: 10px"
+        );
+        assert_eq!(
+            StyleProp::BackgroundColor(Some(Color::rgba_u8(255, 0, 16, 128))).to_string(),
+            "background_color: #ff001080"
+        );
+        assert_eq!(
+            StyleProp::Color(Some(Color::rgb(0.3, 1., 0.))).to_string(),
+            "color: rgba(0.3, 1, 0, 1)"
+        );
+        assert_eq!(
+            StyleProp::Padding(ui::UiRect::axes(ui::Val::Px(2.), ui::Val::Percent(5.))).to_string(),
+            "padding: 2px 5%"
+        );
+        assert_eq!(
+            StyleProp::Calc(CalcProperty::MaxWidth, CalcLength::new(100., -32.)).to_string(),
+            "max_width: calc(100% - 32px)"
+        );
+        assert_eq!(
+            StyleProp::Font(Some("fonts/a \"b\".ttf".into())).to_string(),
+            "font: \"fonts/a \\\"b\\\".ttf\""
+        );
+    }
+
+    #[test]
+    fn test_round_trip_stylesheet() {
+        let mut parsed = parse_stylesheet(
+            r#"
+            TEST {
+                background_image: "images/bg.png";
+                background_image_flip_x: true;
+                background_image_flip_y: false;
+                background_image_tint: #80808080;
+                background_color: #123456;
+                border_color: transparent;
+                color: #ff000080;
+                z_index: global(-3);
+                display: grid;
+                position_type: absolute;
+                overflow: clip visible;
+                overflow_x: visible;
+                overflow_y: clip;
+                direction: rtl;
+                left: 1px;
+                right: 2%;
+                top: 3vw;
+                bottom: 4vh;
+                width: 5vmin;
+                height: 6vmax;
+                min_width: auto;
+                min_height: calc(50% + 4px);
+                max_width: calc(100% - 0.5px);
+                max_height: 7.25px;
+                aspect_ratio: 1.5;
+                margin: 1px 2px 3px 4px;
+                margin_left: 1px;
+                margin_right: 2px;
+                margin_top: 3px;
+                margin_bottom: 4px;
+                padding: 8px 2px;
+                padding_left: 1px;
+                padding_right: 2px;
+                padding_top: 3px;
+                padding_bottom: 4px;
+                padding: 1rem 2px !important;
+                border: 1px;
+                border_left: 1px;
+                border_right: 2px;
+                border_top: 3px;
+                border_bottom: 4px;
+                flex_direction: column_reverse;
+                flex_wrap: wrap_reverse;
+                flex_grow: 2;
+                flex_shrink: 0.5;
+                flex_basis: 10%;
+                row_gap: 1px;
+                column_gap: 2px;
+                gap: 3px 4%;
+                align_items: baseline;
+                align_self: flex_end;
+                align_content: space_evenly;
+                justify_items: stretch;
+                justify_self: center;
+                justify_content: space_around;
+                grid_auto_flow: column_dense;
+                grid_row_start: -1;
+                grid_row_span: 2;
+                grid_row_end: 3;
+                grid_column_start: 4;
+                grid_column_span: 5;
+                grid_column_end: -6;
+                font: inherit;
+                font_size: 14.5;
+                text_overflow: ellipsis;
+                text_align: center;
+                outline_color: hsla(120, 0.5, 0.25, 1);
+                outline_width: 2px;
+                outline_offset: -1px;
+                scale: 1.25;
+                scale_x: 0.5;
+                scale_y: 2;
+                rotation: 0.75rad;
+                translation: 1px -2px 3px;
+                ui_scale: 2;
+                transition: width 0.3s ease_in_out 0.1s quantize(2px), transform 1s;
+                animate_in: translation 1px -2px 3px 0.5s ease_out 0.1s, rotation 0.5rad 1s,
+                    outline_color #ffffff00 0.2s, width 50% 1s;
+
+                &:hover { color: inherit; font_size: inherit; gap: 4px; }
+                .dark > &.selected:focus-within {
+                    background_color: rgba_linear(0.25, 0.5, 1, 0.75);
+                    font: "fonts/b.ttf";
+                    aspect_ratio: none;
+                    z_index: 2;
+                    overflow: visible;
+                }
+                @media (max-width: 800) { padding: 0; }
+                @media (min-width: 400) and (max-height: 600) &.dense { margin: auto; }
+                @scope(dark) { background_color: #111111; }
+                @scope(high-contrast) { border: 2px; color: #ffffff; }
+            }
+            "#,
+        )
+        .unwrap();
+        let (_, list) = parsed.pop().unwrap();
+        round_trip(&list);
+
+        // Each property's key is the name it's written with.
+        for prop in list.props().iter() {
+            assert!(prop.to_string().starts_with(&format!("{}: ", prop.key())));
+        }
+    }
+
+    #[test]
+    fn test_round_trip_built() {
+        let list = StylePropList::build(|ss| {
+            ss.background_color(Color::rgba(0.1, 0.2, 0.3, 0.4))
+                .border_color(Color::hsl(200., 0.5, 0.5))
+                .color(Color::WHITE)
+                .z_index(ui::ZIndex::Local(-2))
+                .calc(CalcProperty::Left, CalcLength::new(-50., 0.))
+                .transition(&[Transition {
+                    property: TransitionProperty::BorderBottom,
+                    delay: 0.25,
+                    duration: 0.125,
+                    timing: Timing::EaseOut,
+                    quantum: None,
+                }])
+        });
+        round_trip(&list);
+
+        // Properties without stylesheet syntax are commented out.
+        let list = StylePropList::from_raw(
+            vec![
+                StyleProp::Width(ui::Val::Px(1.)),
+                StyleProp::PointerEvents(PointerEvents::None),
+                StyleProp::Cursor(Cursor::Pointer),
+            ],
+            Vec::new(),
+        );
+        let css = list.to_css("TEST");
+        assert_eq!(
+            css,
+            "TEST {\n    width: 1px;\n    // pointer_events: None;\n    // cursor: Pointer;\n}\n"
+        );
+        let (_, reparsed) = parse_stylesheet(&css).unwrap().pop().unwrap();
+        assert_eq!(reparsed.props().len(), 1);
+    }
+}
@@ -0,0 +1,291 @@
+use winnow::error::{ContextError, ParseError, StrContext};
+
+use crate::{span::LineIndex, style_parser::StyleParsingError, SourcePos};
+
+/// The kind of problem found while parsing a stylesheet.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StylesheetErrorKind {
+    /// A selector could not be parsed.
+    Selector,
+    /// A property name was not recognized.
+    PropertyName,
+    /// A property value was malformed, or was of the wrong type for the property.
+    PropertyValue,
+    /// The stylesheet is not structured correctly, for example a missing brace or semicolon,
+    /// or a rule name which is defined twice.
+    Structure,
+}
+
+/// An error found while parsing a stylesheet.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StylesheetError {
+    /// What kind of problem was found.
+    pub kind: StylesheetErrorKind,
+    /// The name of the rule containing the error, if the error is within a rule.
+    pub rule: Option<String>,
+    /// Where in the stylesheet source the error was found.
+    pub pos: SourcePos,
+    /// Byte offset of the error in the stylesheet source.
+    pub offset: usize,
+    /// Description of the error.
+    pub message: String,
+}
+
+impl StylesheetError {
+    /// Convert a winnow parsing error into a `StylesheetError`. `input` is the complete
+    /// stylesheet source.
+    pub(crate) fn from_parse_error(input: &str, err: ParseError<&str, ContextError>) -> Self {
+        Self::from_context_error(input, err.offset(), err.inner())
+    }
+
+    /// Convert the error found at `offset` while parsing part of a stylesheet into a
+    /// `StylesheetError`. `input` is the complete stylesheet source.
+    pub(crate) fn from_context_error(input: &str, offset: usize, inner: &ContextError) -> Self {
+        let lines = LineIndex::new(input);
+        let cause = inner
+            .cause()
+            .and_then(|cause| cause.downcast_ref::<StyleParsingError>());
+        let (kind, message) = match cause {
+            Some(StyleParsingError::InvalidPropertyName(_)) => {
+                (StylesheetErrorKind::PropertyName, inner.to_string())
+            }
+            Some(
+                StyleParsingError::InvalidPropertyType(_)
+                | StyleParsingError::InvalidPropertyValue(_)
+                | StyleParsingError::InvalidHexColor(_),
+            ) => (StylesheetErrorKind::PropertyValue, inner.to_string()),
+            Some(
+                StyleParsingError::ScopeInBlock(_)
+                | StyleParsingError::MissingTransitionProperty(_),
+            ) => (StylesheetErrorKind::Structure, inner.to_string()),
+            Some(StyleParsingError::MisplacedCurrent(_)) => {
+                (StylesheetErrorKind::Selector, inner.to_string())
+            }
+            Some(StyleParsingError::DuplicateRule { name, first, .. }) => {
+                let first = lines.pos(*first);
+                (
+                    StylesheetErrorKind::Structure,
+                    format!(
+                        "duplicate rule '{}' (first defined at line {}, column {})",
+                        name, first.line, first.column
+                    ),
+                )
+            }
+            None => {
+                let kind = inner
+                    .context()
+                    .find_map(|context| match context {
+                        StrContext::Label("property value" | "transition" | "animate_in") => {
+                            Some(StylesheetErrorKind::PropertyValue)
+                        }
+                        StrContext::Label("selector" | "media query") => {
+                            Some(StylesheetErrorKind::Selector)
+                        }
+                        _ => None,
+                    })
+                    .unwrap_or(StylesheetErrorKind::Structure);
+                let message = match inner.to_string() {
+                    message if message.is_empty() => "syntax error".to_string(),
+                    message => message,
+                };
+                (kind, message)
+            }
+        };
+        Self {
+            kind,
+            rule: rule_at(input, offset),
+            pos: lines.pos(offset),
+            offset,
+            message,
+        }
+    }
+
+    /// Convert a winnow error from parsing a selector on its own into a `StylesheetError`.
+    /// `input` is the complete source, and `start` the offset of the selector within it.
+    pub(crate) fn from_selector_error(
+        input: &str,
+        start: usize,
+        err: ParseError<&str, ContextError>,
+    ) -> Self {
+        let offset = start + err.offset();
+        let message = match err.inner().to_string() {
+            message if message.is_empty() => "syntax error".to_string(),
+            message => message,
+        };
+        Self {
+            kind: StylesheetErrorKind::Selector,
+            rule: None,
+            pos: LineIndex::new(input).pos(offset),
+            offset,
+            message,
+        }
+    }
+}
+
+impl std::fmt::Display for StylesheetError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}, column {}", self.pos.line, self.pos.column)?;
+        if let Some(rule) = &self.rule {
+            write!(f, ", in rule '{}'", rule)?;
+        }
+        write!(f, ": {}", self.message)
+    }
+}
+
+impl std::error::Error for StylesheetError {}
+
+/// Find the name of the rule which is open at `offset`, by scanning the braces which precede
+/// it. Rules may be nested one level deep inside an `@media` block.
+fn rule_at(input: &str, offset: usize) -> Option<String> {
+    let mut rule: Option<&str> = None;
+    let mut depth = 0;
+    let mut in_media = false;
+    let mut header_start = 0;
+    let mut chars = input[..offset].char_indices().peekable();
+    while let Some((index, c)) = chars.next() {
+        match c {
+            '/' if chars.peek().map(|(_, c)| *c) == Some('/') => {
+                for (_, c) in chars.by_ref() {
+                    if c == '\n' {
+                        break;
+                    }
+                }
+                header_start = chars.peek().map_or(offset, |(index, _)| *index);
+            }
+            '"' => {
+                while let Some((_, c)) = chars.next() {
+                    match c {
+                        '\\' => {
+                            chars.next();
+                        }
+                        '"' => break,
+                        _ => {}
+                    }
+                }
+            }
+            '{' => {
+                let header = input[header_start..index].trim();
+                match (depth, in_media) {
+                    (0, _) if header.starts_with('@') => in_media = true,
+                    (0, _) | (1, true) => rule = Some(header),
+                    _ => {}
+                }
+                depth += 1;
+                header_start = index + 1;
+            }
+            '}' => {
+                depth -= 1;
+                match (depth, in_media) {
+                    (0, _) => {
+                        rule = None;
+                        in_media = false;
+                    }
+                    (1, true) => rule = None,
+                    _ => {}
+                }
+                header_start = index + 1;
+            }
+            ';' => header_start = index + 1,
+            _ => {}
+        }
+    }
+    rule.filter(|name| !name.is_empty()).map(str::to_owned)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_stylesheet, Selector};
+
+    fn parse_err(src: &str) -> StylesheetError {
+        parse_stylesheet(src).unwrap_err()
+    }
+
+    #[test]
+    fn test_error_kinds() {
+        let err = parse_err("A { width: 10px; }\nB {\n  foo: 1;\n}");
+        assert_eq!(err.kind, StylesheetErrorKind::PropertyName);
+        assert_eq!(err.rule.as_deref(), Some("B"));
+        assert_eq!(err.pos, SourcePos { line: 3, column: 3 });
+        assert_eq!(err.offset, 25);
+        assert_eq!(
+            err.to_string(),
+            "line 3, column 3, in rule 'B': invalid property name: 'foo'"
+        );
+
+        let err = parse_err("A {\n  width: #fff;\n}");
+        assert_eq!(err.kind, StylesheetErrorKind::PropertyValue);
+        assert_eq!(err.pos, SourcePos { line: 2, column: 3 });
+
+        let err = parse_err("A { color: rgb(1, 2; }");
+        assert_eq!(err.kind, StylesheetErrorKind::PropertyValue);
+
+        let err = parse_err("A {\n  .foo[ { width: 1; }\n}");
+        assert_eq!(err.kind, StylesheetErrorKind::Selector);
+        assert_eq!(err.rule.as_deref(), Some("A"));
+        assert_eq!(err.pos, SourcePos { line: 2, column: 7 });
+
+        let err = parse_err("@media (max-wdth: 800) {\n  A { width: 1; }\n}");
+        assert_eq!(err.kind, StylesheetErrorKind::Selector);
+        assert_eq!(err.rule, None);
+
+        let err = parse_err("A {\n  width: 10px\n}");
+        assert_eq!(err.kind, StylesheetErrorKind::Structure);
+        assert_eq!(err.message, "expected semicolon");
+
+        let err = parse_err("A {\n  :hover { width: 1; }\n");
+        assert_eq!(err.kind, StylesheetErrorKind::Structure);
+        assert_eq!(err.rule.as_deref(), Some("A"));
+        assert_eq!(err.message, "expected `}`");
+
+        let err = parse_err("A { width: 1; }\nA { width: 2; }");
+        assert_eq!(err.kind, StylesheetErrorKind::Structure);
+        assert_eq!(err.rule, None);
+        assert_eq!(err.pos, SourcePos { line: 2, column: 1 });
+        assert_eq!(
+            err.message,
+            "duplicate rule 'A' (first defined at line 1, column 1)"
+        );
+    }
+
+    #[test]
+    fn test_selector_errors() {
+        let err = "  .foo > &:hovr".parse::<Selector>().unwrap_err();
+        assert_eq!(err.kind, StylesheetErrorKind::Selector);
+        assert_eq!(err.rule, None);
+        assert_eq!(
+            err.pos,
+            SourcePos {
+                line: 1,
+                column: 11
+            }
+        );
+        assert_eq!(err.offset, 10);
+
+        let err = "&:hovr".parse::<Selector>().unwrap_err();
+        assert_eq!(err.kind, StylesheetErrorKind::Selector);
+        assert_eq!(err.offset, 1);
+
+        #[allow(deprecated)]
+        let message = Selector::parse("&:hovr").unwrap_err();
+        assert_eq!(message, err.to_string());
+    }
+
+    #[test]
+    fn test_error_is_owned() {
+        fn assert_owned<T: std::error::Error + Send + Sync + 'static>() {}
+        assert_owned::<StylesheetError>();
+    }
+
+    #[test]
+    fn test_rule_at() {
+        let src =
+            "// A { B {\nA { width: 1; }\n@media (max-width: 800) {\n  B { x }\n}\nC { &.x { y } }";
+        let at = |s: &str| rule_at(src, src.find(s).unwrap());
+        assert_eq!(at("width"), Some("A".into()));
+        assert_eq!(at("(max"), None);
+        assert_eq!(at("x }"), Some("B".into()));
+        assert_eq!(at("C"), None);
+        assert_eq!(at("y"), Some("C".into()));
+    }
+}
@@ -3,24 +3,73 @@
 #![warn(missing_docs)]
 
 mod builder;
+mod calc;
+mod color_names;
+mod compiled_selector;
 mod computed;
+mod css;
 mod cursor;
+mod error;
+mod lint;
+mod mock_matcher;
+mod rem;
 mod selector;
 mod selector_parser;
+mod span;
 mod style;
+mod style_field;
 mod style_parser;
+mod token;
 mod transition;
 
 pub use builder::StyleBuilder;
+pub use calc::CalcLength;
+pub use calc::CalcProperty;
+pub use color_names::parse_color_name;
+pub use compiled_selector::CompiledSelector;
+pub use compiled_selector::SelectorOp;
 pub use computed::ComputedStyle;
+pub use error::StylesheetError;
+pub use error::StylesheetErrorKind;
+pub use lint::lint_stylesheet;
+pub use lint::lint_stylesheet_with;
+pub use lint::LintKind;
+pub use lint::LintWarning;
+pub use mock_matcher::MockElement;
+pub use mock_matcher::MockSelectorMatcher;
+pub use rem::Length;
+pub use rem::DEFAULT_ROOT_FONT_SIZE;
+pub use selector::AppStateKind;
+pub use selector::CapabilityKind;
+pub use selector::MediaQuery;
 pub use selector::Selector;
 pub use selector::SelectorMatcher;
+pub use span::SelectorSpans;
+pub use span::SourcePos;
+pub use span::SourceSpan;
+pub use span::SpanTable;
 pub use style::PointerEvents;
+pub use style::Priority;
+pub use style::PropSource;
+pub use style::ScopeEntry;
 pub use style::SelectorEntry;
+pub use style::StyleLint;
 pub use style::StyleProp;
 pub use style::StylePropList;
+pub use style::TextOverflow;
+pub use style_field::StyleField;
+pub use style_field::StyleFieldSet;
 pub use style_parser::parse_stylesheet;
+pub use style_parser::parse_stylesheet_recovering;
+pub use style_parser::parse_stylesheet_with;
+pub use style_parser::DuplicateRules;
+pub use token::TokenValue;
 pub use transition::timing;
+pub use transition::unit_name;
+pub use transition::AnimateIn;
+pub use transition::Timing;
+pub use transition::TimingFunction;
 pub use transition::Transition;
 pub use transition::TransitionProperty;
+pub use transition::TransitionPropertySet;
 pub use transition::TransitionState;
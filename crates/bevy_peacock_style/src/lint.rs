@@ -0,0 +1,376 @@
+use crate::{
+    css::Css, parse_stylesheet_with, span::LineIndex, DuplicateRules, SourcePos, SourceSpan,
+    StyleProp, StylePropList, StylesheetError,
+};
+
+/// The kind of likely mistake reported by [`lint_stylesheet`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LintKind {
+    /// A property is set more than once in the same block, so all but the last are ignored.
+    DuplicateProperty,
+    /// A selector is read differently than it's written, which suggests that it doesn't mean
+    /// what its author intended.
+    NonCanonicalSelector,
+    /// A rule, selector or scope has no properties.
+    EmptyBlock,
+    /// A transition is declared for a property which the rule never sets.
+    UnusedTransition,
+    /// A selector or scope sets a property which the rule sets with `!important`, so its value
+    /// never applies.
+    OverriddenByImportant,
+}
+
+/// A likely mistake in a stylesheet, found by [`lint_stylesheet`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LintWarning {
+    /// What kind of mistake was found.
+    pub kind: LintKind,
+    /// The name of the rule containing the mistake.
+    pub rule: String,
+    /// Where in the stylesheet source the mistake was found.
+    pub pos: SourcePos,
+    /// Description of the mistake.
+    pub message: String,
+}
+
+impl std::fmt::Display for LintWarning {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "line {}, column {}, in rule '{}': {}",
+            self.pos.line, self.pos.column, self.rule, self.message
+        )
+    }
+}
+
+/// Check a stylesheet for likely mistakes which don't prevent it from parsing, such as a
+/// property which is set twice in the same block. Warnings are returned in source order.
+/// Defining the same rule name more than once is an error, as for [`parse_stylesheet`].
+///
+/// [`parse_stylesheet`]: crate::parse_stylesheet
+pub fn lint_stylesheet(input: &str) -> Result<Vec<LintWarning>, StylesheetError> {
+    lint_stylesheet_with(input, DuplicateRules::Error)
+}
+
+/// Check a stylesheet for likely mistakes, handling duplicate rule names as specified.
+/// Properties of a merged rule which override those of an earlier block aren't reported.
+pub fn lint_stylesheet_with(
+    input: &str,
+    duplicates: DuplicateRules,
+) -> Result<Vec<LintWarning>, StylesheetError> {
+    let lines = LineIndex::new(input);
+    let rules = parse_stylesheet_with(input, duplicates)?;
+    let mut linter = Linter {
+        lines: &lines,
+        rule: "",
+        warnings: Vec::new(),
+    };
+    for (name, list) in &rules {
+        linter.rule = name;
+        linter.lint_rule(list);
+    }
+    let mut warnings = linter.warnings;
+    warnings.sort_by_key(|warning| (warning.pos.line, warning.pos.column));
+    Ok(warnings)
+}
+
+struct Linter<'a> {
+    lines: &'a LineIndex<'a>,
+    rule: &'a str,
+    warnings: Vec<LintWarning>,
+}
+
+impl<'a> Linter<'a> {
+    fn warn(&mut self, kind: LintKind, pos: SourcePos, message: String) {
+        self.warnings.push(LintWarning {
+            kind,
+            rule: self.rule.to_owned(),
+            pos,
+            message,
+        });
+    }
+
+    fn lint_rule(&mut self, list: &StylePropList) {
+        // The parser always records spans, but styles can't be linted without them.
+        let Some(spans) = list.spans() else {
+            return;
+        };
+        if list.props().is_empty() && list.selectors().is_empty() && list.scopes().is_empty() {
+            self.warn(
+                LintKind::EmptyBlock,
+                spans.rule.start,
+                "rule has no properties".to_string(),
+            );
+        }
+        self.lint_props(list.props(), &spans.props);
+        let important: Vec<&str> = list
+            .props()
+            .iter()
+            .filter(|prop| prop.is_important())
+            .map(StyleProp::key)
+            .collect();
+
+        for ((selector, props), entry) in list.selectors().iter().zip(&spans.selectors) {
+            let start = self.lines.offset(entry.selector.start);
+            let written = self.lines.src()[start..]
+                .split('{')
+                .next()
+                .unwrap_or_default()
+                .trim();
+            // Media queries are normalized heavily, and their units rewritten.
+            let read = selector.to_string();
+            if !selector.uses_media() && without_whitespace(written) != without_whitespace(&read) {
+                self.warn(
+                    LintKind::NonCanonicalSelector,
+                    entry.selector.start,
+                    format!("selector '{}' is read as '{}'", written, read.trim()),
+                );
+            }
+            if props.is_empty() {
+                self.warn(
+                    LintKind::EmptyBlock,
+                    entry.selector.start,
+                    format!("selector '{}' has no properties", written),
+                );
+            }
+            self.lint_props(props, &entry.props);
+            self.lint_overridden(&important, props, &entry.props);
+        }
+
+        for ((name, props), entry) in list.scopes().iter().zip(&spans.scopes) {
+            if props.is_empty() {
+                self.warn(
+                    LintKind::EmptyBlock,
+                    entry.selector.start,
+                    format!("scope '{}' has no properties", name),
+                );
+            }
+            self.lint_props(props, &entry.props);
+            self.lint_overridden(&important, props, &entry.props);
+        }
+
+        let set = list.transition_properties();
+        let transitions = list.all_props().flat_map(|prop| match prop {
+            StyleProp::Transition(trans) => trans.as_slice(),
+            _ => &[],
+        });
+        let mut reported = Vec::new();
+        for tr in transitions {
+            if set.contains(tr.property) || reported.contains(&tr.property) {
+                continue;
+            }
+            reported.push(tr.property);
+            self.warn(
+                LintKind::UnusedTransition,
+                spans.rule.start,
+                format!(
+                    "transition on '{}' has no effect, because the rule never sets it",
+                    Css(&tr.property)
+                ),
+            );
+        }
+    }
+
+    /// Report properties which are set more than once in the same block.
+    fn lint_props(&mut self, props: &[StyleProp], spans: &[SourceSpan]) {
+        for (index, (prop, span)) in props.iter().zip(spans).enumerate() {
+            // Shorthands such as `size` expand to several props with the same span.
+            let earlier = props[..index]
+                .iter()
+                .zip(spans)
+                .rev()
+                .find(|(other, other_span)| {
+                    other.key() == prop.key()
+                        && *other_span != span
+                        && self.same_block(**other_span, *span)
+                });
+            if let Some((_, first)) = earlier {
+                self.warn(
+                    LintKind::DuplicateProperty,
+                    span.start,
+                    format!(
+                        "'{}' overrides the value set at line {}, column {} of the same block",
+                        prop.key(),
+                        first.start.line,
+                        first.start.column
+                    ),
+                );
+            }
+        }
+    }
+
+    /// Report conditional properties which can't take effect, because the rule sets the same
+    /// property with `!important` and they don't.
+    fn lint_overridden(&mut self, important: &[&str], props: &[StyleProp], spans: &[SourceSpan]) {
+        for (prop, span) in props.iter().zip(spans) {
+            if !prop.is_important() && important.contains(&prop.key()) {
+                self.warn(
+                    LintKind::OverriddenByImportant,
+                    span.start,
+                    format!(
+                        "'{}' has no effect, because the rule sets it with !important",
+                        prop.key()
+                    ),
+                );
+            }
+        }
+    }
+
+    /// True if `second` is in the same block as `first`, rather than in a later block of a
+    /// merged rule: the braces between them never close the block containing `first`.
+    fn same_block(&self, first: SourceSpan, second: SourceSpan) -> bool {
+        let (start, end) = (
+            self.lines.offset(first.end),
+            self.lines.offset(second.start),
+        );
+        let mut depth = 0;
+        let mut in_string = false;
+        let mut chars = self.lines.src()[start..end].chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' if in_string => {
+                    chars.next();
+                }
+                '"' => in_string = !in_string,
+                '{' if !in_string => depth += 1,
+                '}' if !in_string => {
+                    depth -= 1;
+                    if depth < 0 {
+                        return false;
+                    }
+                }
+                _ => {}
+            }
+        }
+        true
+    }
+}
+
+fn without_whitespace(s: &str) -> String {
+    s.chars().filter(|c| !c.is_whitespace()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lint(src: &str) -> Vec<(LintKind, u32, u32)> {
+        lint_stylesheet(src)
+            .unwrap()
+            .iter()
+            .map(|warning| (warning.kind, warning.pos.line, warning.pos.column))
+            .collect()
+    }
+
+    #[test]
+    fn test_lint_clean() {
+        let src = "BUTTON {
+            size: 10px 20px;
+            min_width: 30px;
+            transition: width 0.3s;
+            &:hover { width: 40px; }
+            @scope(dark) { background_color: #111; }
+            @media (min-width: 600px) { height: 5px; }
+        }";
+        assert_eq!(lint(src), vec![]);
+        assert!(lint_stylesheet("BUTTON { width: 10px").is_err());
+
+        // A shorthand followed by one of the properties it sets is still a duplicate.
+        assert_eq!(
+            lint("A { size: 1px 2px; height: 3px; }"),
+            vec![(LintKind::DuplicateProperty, 1, 20)]
+        );
+    }
+
+    #[test]
+    fn test_lint_duplicate_property() {
+        let src = "BUTTON {
+    width: 10px;
+    height: 5px;
+    width: 20px;
+    &:hover { color: #f00; color: #0f0; }
+}";
+        assert_eq!(
+            lint(src),
+            vec![
+                (LintKind::DuplicateProperty, 4, 5),
+                (LintKind::DuplicateProperty, 5, 28),
+            ]
+        );
+        let warnings = lint_stylesheet(src).unwrap();
+        assert_eq!(warnings[0].rule, "BUTTON");
+        assert_eq!(
+            warnings[0].to_string(),
+            "line 4, column 5, in rule 'BUTTON': 'width' overrides the value set at line 2, \
+            column 5 of the same block"
+        );
+
+        // Separate blocks of a merged rule may override each other.
+        let src = "BUTTON { width: 10px; }\nBUTTON { width: 20px; width: 30px; }";
+        let warnings = lint_stylesheet_with(src, DuplicateRules::Merge).unwrap();
+        assert_eq!(warnings.len(), 1);
+        assert_eq!(
+            warnings[0].pos,
+            SourcePos {
+                line: 2,
+                column: 23
+            }
+        );
+    }
+
+    #[test]
+    fn test_lint_non_canonical_selector() {
+        let src = "BUTTON {\n    * > & { width: 10px; }\n    .a > & { width: 20px; }\n}";
+        assert_eq!(lint(src), vec![(LintKind::NonCanonicalSelector, 2, 5)]);
+        assert_eq!(
+            lint_stylesheet(src).unwrap()[0].message,
+            "selector '* > &' is read as '> &'"
+        );
+    }
+
+    #[test]
+    fn test_lint_empty_block() {
+        let src = "EMPTY {}\nBUTTON {\n    &:hover {}\n    @scope(dark) { }\n    width: 1px;\n}";
+        assert_eq!(
+            lint(src),
+            vec![
+                (LintKind::EmptyBlock, 1, 1),
+                (LintKind::EmptyBlock, 3, 5),
+                (LintKind::EmptyBlock, 4, 5),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_lint_unused_transition() {
+        let src = "BUTTON {
+            width: 10px;
+            transition: width 0.3s, height 0.3s, transform 0.2s;
+            &:hover { scale: 1.1; }
+        }
+        LABEL { transition: background_color 1s, background_color 2s; }";
+        let warnings = lint_stylesheet(src).unwrap();
+        let messages: Vec<_> = warnings.iter().map(|w| w.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            vec![
+                "transition on 'height' has no effect, because the rule never sets it",
+                "transition on 'background_color' has no effect, because the rule never sets it",
+            ]
+        );
+        assert!(warnings
+            .iter()
+            .all(|warning| warning.kind == LintKind::UnusedTransition));
+    }
+
+    #[test]
+    fn test_lint_overridden_by_important() {
+        let src = "BUTTON {
+    background_color: #f00 !important;
+    width: 10px;
+    &:hover { background_color: #0f0; width: 20px; }
+    @scope(dark) { background_color: #00f !important; }
+}";
+        assert_eq!(lint(src), vec![(LintKind::OverriddenByImportant, 4, 15)]);
+    }
+}
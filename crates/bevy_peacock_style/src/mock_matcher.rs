@@ -0,0 +1,363 @@
+//! A [`SelectorMatcher`] which matches against a description of an element and its ancestors,
+//! for testing styles without an app.
+
+use bevy::{ecs::entity::Entity, math::Vec2, utils::HashMap};
+
+use crate::{
+    AppStateKind, CapabilityKind, ComputedStyle, Selector, SelectorMatcher, StylePropList,
+    TokenValue, DEFAULT_ROOT_FONT_SIZE,
+};
+
+/// The state of one element of a [`MockSelectorMatcher`]'s chain.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct MockElement {
+    /// The element's class names.
+    pub classes: Vec<String>,
+    /// The element matches `:hover`.
+    pub hover: bool,
+    /// The element matches `:focus`, and it and its ancestors match `:focus-within`.
+    pub focus: bool,
+    /// The element matches `:focus-visible`.
+    pub focus_visible: bool,
+    /// The element matches `:first-child`.
+    pub first_child: bool,
+    /// The element matches `:last-child`.
+    pub last_child: bool,
+    /// The element matches `:disabled`.
+    pub disabled: bool,
+    /// The element matches `:checked`.
+    pub checked: bool,
+    /// The element matches `:dragging`.
+    pub dragging: bool,
+    /// The element matches `:drag-over`.
+    pub drag_over: bool,
+    /// The names of the custom pseudo-classes, without the `:--` prefix, which match the
+    /// element.
+    pub custom: Vec<String>,
+    /// The name of the scope the element starts, if any.
+    pub scope: Option<String>,
+}
+
+impl MockElement {
+    /// Construct an element with the given whitespace-separated class names.
+    pub fn new(classes: &str) -> Self {
+        Self {
+            classes: classes.split_whitespace().map(str::to_owned).collect(),
+            ..Default::default()
+        }
+    }
+
+    /// Make the element match `:hover`.
+    pub fn hovered(mut self) -> Self {
+        self.hover = true;
+        self
+    }
+
+    /// Make the element match `:focus`, and `:focus-visible` too if `visible`.
+    pub fn focused(mut self, visible: bool) -> Self {
+        self.focus = true;
+        self.focus_visible = visible;
+        self
+    }
+
+    /// Make the element match `:first-child`.
+    pub fn first_child(mut self) -> Self {
+        self.first_child = true;
+        self
+    }
+
+    /// Make the element match `:last-child`.
+    pub fn last_child(mut self) -> Self {
+        self.last_child = true;
+        self
+    }
+
+    /// Make the element match `:disabled`.
+    pub fn disabled(mut self) -> Self {
+        self.disabled = true;
+        self
+    }
+
+    /// Make the element match `:checked`.
+    pub fn checked(mut self) -> Self {
+        self.checked = true;
+        self
+    }
+
+    /// Make the element match the custom pseudo-class `:--name`.
+    pub fn custom(mut self, name: &str) -> Self {
+        self.custom.push(name.to_owned());
+        self
+    }
+
+    /// Make the element start the scope `name`.
+    pub fn scope(mut self, name: &str) -> Self {
+        self.scope = Some(name.to_owned());
+        self
+    }
+}
+
+/// A [`SelectorMatcher`] which matches selectors against a chain of [`MockElement`]s rather
+/// than entities, so that styles can be computed with [`StylePropList::apply_to`] in unit
+/// tests, with no ECS at all. The chain starts with the element being styled, followed by its
+/// parent, grandparent and so on; the element at index `i` is `Entity::from_raw(i)`.
+///
+/// The state of the app, such as the color scheme or the window size, is held in public
+/// fields, which default to a light color scheme, no capabilities or flags, and no window.
+///
+/// ```
+/// # use bevy_peacock_style::*;
+/// let (_, list) = parse_stylesheet("BUTTON { .panel > &:hover { z_index: 2; } }")
+///     .unwrap()
+///     .pop()
+///     .unwrap();
+/// let matcher = MockSelectorMatcher::new([
+///     MockElement::new("button").hovered(),
+///     MockElement::new("panel"),
+/// ]);
+/// assert!(matches!(matcher.compute(&list).z_index, bevy::ui::ZIndex::Local(2)));
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct MockSelectorMatcher {
+    /// The element being styled, followed by its ancestors.
+    pub chain: Vec<MockElement>,
+    /// The app's color scheme is dark, matching `:dark`.
+    pub dark: bool,
+    /// The capabilities which match, such as `:reduced-motion`.
+    pub capabilities: Vec<CapabilityKind>,
+    /// The app states which match, such as `:paused`.
+    pub app_states: Vec<AppStateKind>,
+    /// The enabled feature flags, which match `@flag(name)`.
+    pub flags: Vec<String>,
+    /// The app's locale, as a language tag such as `de-AT`, which `:lang()` matches.
+    pub locale: Option<String>,
+    /// The logical size of the window, which media queries match. Without one, they never
+    /// match.
+    pub window_size: Option<Vec2>,
+    /// The theme tokens, by name.
+    pub tokens: HashMap<String, TokenValue>,
+    /// The root font size in pixels, which `rem` lengths are multiples of.
+    pub root_font_size: f32,
+}
+
+impl MockSelectorMatcher {
+    /// Construct a matcher for a chain of elements, starting with the element being styled,
+    /// followed by its ancestors.
+    pub fn new(chain: impl IntoIterator<Item = MockElement>) -> Self {
+        Self {
+            chain: chain.into_iter().collect(),
+            root_font_size: DEFAULT_ROOT_FONT_SIZE,
+            ..Default::default()
+        }
+    }
+
+    /// The entity standing for the element at `depth` in the chain, where 0 is the element
+    /// being styled and 1 its parent.
+    pub fn entity(depth: usize) -> Entity {
+        Entity::from_raw(depth as u32)
+    }
+
+    /// Compute the style of the element being styled, from its properties alone.
+    pub fn compute(&self, list: &StylePropList) -> ComputedStyle {
+        let mut computed = ComputedStyle::new();
+        list.apply_to(&mut computed, self, &Self::entity(0));
+        computed
+    }
+
+    /// Return the element which `entity` stands for, and its depth in the chain.
+    fn element(&self, entity: &Entity) -> Option<(usize, &MockElement)> {
+        let depth = entity.index() as usize;
+        self.chain.get(depth).map(|element| (depth, element))
+    }
+
+    /// True if the locale is `tag`, or starts with all of the subtags of `tag`, ignoring case.
+    fn matches_lang(&self, tag: &str) -> bool {
+        self.locale.as_ref().is_some_and(|locale| {
+            let locale = locale.as_bytes();
+            locale.len() >= tag.len()
+                && locale[..tag.len()].eq_ignore_ascii_case(tag.as_bytes())
+                && matches!(locale.get(tag.len()), None | Some(b'-' | b'_'))
+        })
+    }
+
+    /// True if `test` holds for the element, and `next` matches it.
+    fn element_match(
+        &self,
+        entity: &Entity,
+        next: &Selector,
+        test: impl FnOnce(&MockElement) -> bool,
+    ) -> bool {
+        self.element(entity)
+            .is_some_and(|(_, element)| test(element))
+            && self.selector_match(next, entity)
+    }
+}
+
+impl SelectorMatcher for MockSelectorMatcher {
+    fn selector_match(&self, selector: &Selector, entity: &Entity) -> bool {
+        match selector {
+            Selector::Accept => true,
+            Selector::Class(cls, next) => {
+                self.element_match(entity, next, |e| e.classes.iter().any(|c| c == cls))
+            }
+            Selector::ClassPrefix(prefix, next) => self.element_match(entity, next, |e| {
+                e.classes.iter().any(|c| c.starts_with(prefix.as_str()))
+            }),
+            Selector::Hover(next) => self.element_match(entity, next, |e| e.hover),
+            Selector::Focus(next) => self.element_match(entity, next, |e| e.focus),
+            Selector::FocusWithin(next) => {
+                // The element itself, or one of its descendants in the chain, has focus.
+                self.element(entity)
+                    .is_some_and(|(depth, _)| self.chain[..=depth].iter().any(|e| e.focus))
+                    && self.selector_match(next, entity)
+            }
+            Selector::FocusVisible(next) => self.element_match(entity, next, |e| e.focus_visible),
+            Selector::FirstChild(next) => self.element_match(entity, next, |e| e.first_child),
+            Selector::LastChild(next) => self.element_match(entity, next, |e| e.last_child),
+            Selector::Disabled(next) => self.element_match(entity, next, |e| e.disabled),
+            Selector::Checked(next) => self.element_match(entity, next, |e| e.checked),
+            Selector::Dragging(next) => self.element_match(entity, next, |e| e.dragging),
+            Selector::DragOver(next) => self.element_match(entity, next, |e| e.drag_over),
+            Selector::Custom(name, next) => {
+                self.element_match(entity, next, |e| e.custom.iter().any(|c| c == name))
+            }
+            Selector::Dark(next) => self.dark && self.selector_match(next, entity),
+            Selector::Capability(kind, next) => {
+                self.capabilities.contains(kind) && self.selector_match(next, entity)
+            }
+            Selector::AppState(kind, next) => {
+                self.app_states.contains(kind) && self.selector_match(next, entity)
+            }
+            Selector::Lang(tag, next) => {
+                self.matches_lang(tag) && self.selector_match(next, entity)
+            }
+            Selector::Media(query, next) => {
+                self.window_size.is_some_and(|size| query.matches(size))
+                    && self.selector_match(next, entity)
+            }
+            Selector::Flag(name, next) => {
+                self.flags.contains(name) && self.selector_match(next, entity)
+            }
+            Selector::Current(next) => self.selector_match(next, entity),
+            Selector::Parent(next) => match self.element(entity) {
+                Some((depth, _)) if depth + 1 < self.chain.len() => {
+                    self.selector_match(next, &Self::entity(depth + 1))
+                }
+                _ => false,
+            },
+            Selector::Either(opts) => opts.iter().any(|next| self.selector_match(next, entity)),
+        }
+    }
+
+    fn scope_match(&self, scope: &str, entity: &Entity) -> bool {
+        // The nearest enclosing scope, starting with the element itself.
+        self.element(entity).is_some_and(|(depth, _)| {
+            self.chain[depth..]
+                .iter()
+                .find_map(|e| e.scope.as_deref())
+                .is_some_and(|name| name == scope)
+        })
+    }
+
+    fn theme_token(&self, token: &str) -> Option<TokenValue> {
+        self.tokens.get(token).cloned()
+    }
+
+    fn root_font_size(&self) -> f32 {
+        self.root_font_size
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parse_stylesheet;
+    use bevy::{render::color::Color, ui::Val};
+
+    /// Parse a stylesheet with a single rule, returning its properties.
+    fn rule(src: &str) -> StylePropList {
+        parse_stylesheet(&format!("RULE {{ {} }}", src))
+            .unwrap()
+            .pop()
+            .unwrap()
+            .1
+    }
+
+    #[test]
+    fn test_hover_selector() {
+        let list = rule("width: 10px; &:hover { width: 20px; }");
+        let width = |element: MockElement| {
+            MockSelectorMatcher::new([element])
+                .compute(&list)
+                .style
+                .width
+        };
+        assert_eq!(width(MockElement::new("")), Val::Px(10.));
+        assert_eq!(width(MockElement::new("").hovered()), Val::Px(20.));
+    }
+
+    #[test]
+    fn test_parent_chain() {
+        let list = rule(
+            "height: 1px;
+            .panel > & { height: 2px; }
+            .dialog > .panel > &:first-child { height: 3px; }
+            :focus-within > & { width: 5px; }",
+        );
+        let size = |chain: Vec<MockElement>| {
+            let style = MockSelectorMatcher::new(chain).compute(&list).style;
+            (style.width, style.height)
+        };
+        assert_eq!(size(vec![MockElement::new("")]), (Val::Auto, Val::Px(1.)));
+        assert_eq!(
+            size(vec![MockElement::new(""), MockElement::new("panel")]),
+            (Val::Auto, Val::Px(2.))
+        );
+        assert_eq!(
+            size(vec![
+                MockElement::new("").first_child().focused(false),
+                MockElement::new("panel"),
+                MockElement::new("dialog"),
+            ]),
+            (Val::Px(5.), Val::Px(3.))
+        );
+        // A parent selector doesn't match past the end of the chain, and only the parent is
+        // the parent.
+        assert_eq!(
+            size(vec![
+                MockElement::new("").first_child(),
+                MockElement::new("dialog"),
+                MockElement::new("panel"),
+            ]),
+            (Val::Auto, Val::Px(1.))
+        );
+    }
+
+    #[test]
+    fn test_app_state_and_tokens() {
+        let list = rule(
+            "background_color: token(surface);
+            &:dark { background_color: #000000; }
+            :lang(de) > & { width: 8px; }
+            @media (max-width: 400) { height: 4px; }",
+        );
+        let mut matcher =
+            MockSelectorMatcher::new([MockElement::new(""), MockElement::new("").scope("card")]);
+        matcher
+            .tokens
+            .insert("surface".into(), TokenValue::Color(Color::WHITE));
+        let computed = matcher.compute(&list);
+        assert_eq!(computed.background_color, Some(Color::WHITE));
+        assert_eq!(computed.style.height, Val::Auto);
+        assert!(matcher.scope_match("card", &MockSelectorMatcher::entity(0)));
+        assert!(!matcher.scope_match("other", &MockSelectorMatcher::entity(0)));
+
+        matcher.dark = true;
+        matcher.locale = Some("de-AT".into());
+        matcher.window_size = Some(Vec2::new(320., 480.));
+        let computed = matcher.compute(&list);
+        assert_eq!(computed.background_color, Some(Color::BLACK));
+        assert_eq!(computed.style.width, Val::Px(8.));
+        assert_eq!(computed.style.height, Val::Px(4.));
+    }
+}
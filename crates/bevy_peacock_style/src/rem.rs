@@ -0,0 +1,98 @@
+use bevy::ui;
+
+use crate::{style_parser::rem_props, StyleProp};
+
+/// The root font size, in pixels, which `rem` lengths are multiples of when the app doesn't
+/// set one.
+pub const DEFAULT_ROOT_FONT_SIZE: f32 = 16.;
+
+/// A length in a property which may be relative to the root font size, such as each of the
+/// lengths in `padding: 1.5rem 8px`. A [`ui::Val`] can't represent `rem` lengths, so properties
+/// which use them hold a list of these, and are resolved to pixels whenever styles are
+/// computed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Length {
+    /// A length which doesn't depend on the root font size.
+    Val(ui::Val),
+    /// A multiple of the root font size.
+    Rem(f32),
+}
+
+impl Length {
+    /// Resolve the length, given the root font size in pixels.
+    pub fn resolve(&self, root_font_size: f32) -> ui::Val {
+        match self {
+            Length::Val(val) => *val,
+            Length::Rem(rem) => ui::Val::Px(rem * root_font_size),
+        }
+    }
+}
+
+impl From<ui::Val> for Length {
+    fn from(val: ui::Val) -> Self {
+        Length::Val(val)
+    }
+}
+
+/// The properties which can be set to `rem` lengths: those which take one or more lengths.
+const REM_PROPERTIES: &[&str] = &[
+    "left",
+    "right",
+    "top",
+    "bottom",
+    "width",
+    "height",
+    "min_width",
+    "min_height",
+    "max_width",
+    "max_height",
+    "margin",
+    "margin_left",
+    "margin_right",
+    "margin_top",
+    "margin_bottom",
+    "padding",
+    "padding_left",
+    "padding_right",
+    "padding_top",
+    "padding_bottom",
+    "border",
+    "border_left",
+    "border_right",
+    "border_top",
+    "border_bottom",
+    "flex_basis",
+    "row_gap",
+    "column_gap",
+    "gap",
+    "outline_width",
+    "outline_offset",
+    "font_size",
+];
+
+/// Return the name of `property` as a static string, if it can be set to `rem` lengths.
+pub(crate) fn rem_property(property: &str) -> Option<&'static str> {
+    REM_PROPERTIES
+        .iter()
+        .copied()
+        .find(|name| *name == property)
+}
+
+/// Resolve a property set to `lengths`, given the root font size. Values which were valid when
+/// parsed can only fail to resolve if the root font size is negative, which leaves the property
+/// unset.
+pub(crate) fn resolve_rem(
+    property: &str,
+    lengths: &[Length],
+    root_font_size: f32,
+) -> Vec<StyleProp> {
+    rem_props(property, lengths, root_font_size).unwrap_or_else(|err| {
+        bevy::log::warn!(
+            "'{}' can't be resolved with a root font size of {}: {}",
+            property,
+            root_font_size,
+            err
+        );
+        Vec::new()
+    })
+}
@@ -1,19 +1,38 @@
 use bevy::ecs::entity::Entity;
+use bevy::math::Vec2;
 use winnow::Parser;
 
 use std::fmt;
 
-use crate::selector_parser;
+use crate::{
+    selector_parser, CompiledSelector, StylesheetError, TokenValue, DEFAULT_ROOT_FONT_SIZE,
+};
 
 /// Represents a predicate which can be used to conditionally style a node.
 /// Selectors support a subset of CSS grammar:
 ///
 /// * Current element (`&`)
 /// * Classname matching
+/// * Classname prefix matching (`.icon-*`). Only a single trailing `*` is allowed; it matches
+///   any class which starts with the text before the `*`.
 /// * Psuedo-classes: `:hover`, `:focus`, `:focus-within`, `:focus-visible`, `:first-child`,
-///   `:last-child`.
+///   `:last-child`, `:disabled`, `:checked`, `:dragging`, `:drag-over`. The drag pseudo-classes
+///   are driven by `bevy_mod_picking`'s drag events, and never match without it.
+/// * The app's color scheme (`:dark`), which matches while the `ColorScheme` resource is dark.
+/// * Capabilities of the device and the user's preferences (`:reduced-motion`, `:touch`,
+///   `:gamepad`), which match while the `PeacockCapabilities` resource has them.
+/// * The state of the app (`:window-focused`, `:window-unfocused`, `:paused`), which match the
+///   focus of the primary window and the `UiPauseState` resource.
+/// * The app's locale (`:lang(de)`), which matches any locale whose language tag starts with the
+///   given subtags, so `de` matches `de-AT`.
+/// * Custom pseudo-classes defined by the app (`:--in-combat`). The leading dashes keep them
+///   from colliding with built-in pseudo-classes added in the future.
 /// * Parent element (`>`) pattern
 /// * Multiple patterns can be specified by commas.
+/// * Media queries on the size of the primary window (`@media (max-width: 800)`), which apply
+///   to the whole selector.
+/// * Feature flags enabled by the app at runtime (`@flag new_checkout`), which also apply to
+///   the whole selector. Flags the app doesn't know about are simply never enabled.
 ///
 /// Examples:
 /// ```css
@@ -22,12 +41,25 @@ use crate::selector_parser;
 ///   :hover
 ///   .state > &
 ///   .state > * > &.name
+///   .icon-*
+///   &.title:lang(ja)
+///   &:dark
+///   &.spinner:reduced-motion
+///   .hud:window-unfocused > &
+///   .party > &:--in-combat
+///   @media (min-width: 1200) and (max-height: 900) &.name
+///   @flag new_checkout &.cta
 /// ```
 ///
-/// Selectors must target the "current element": this means that the "`&`" selector is
-/// required, and it can only appear on the last term of the selector expression. This means
-/// that parent elements cannot implicitly style their children; child elements must have styles
-/// explicitly specified (although those styles can be conditional on the state of their parents).
+/// Selectors always target the "current element": the last term of the selector expression
+/// matches the element being styled, whether or not it starts with "`&`", so `.state > &.name`
+/// and `.state > .name` are equivalent. `&` can't appear in any other term, and selectors which
+/// put it there are rejected by the parser. This means that parent elements cannot implicitly
+/// style their children; child elements must have styles explicitly specified (although those
+/// styles can be conditional on the state of their parents).
+///
+/// Selectors are displayed as they were written, with or without the `&`, and parsing the
+/// displayed form gives back the same selector.
 #[derive(Debug, PartialEq, Clone)]
 pub enum Selector {
     /// If we reach this state, it means the match was successful
@@ -36,6 +68,9 @@ pub enum Selector {
     /// Match an element with a specific class name.
     Class(String, Box<Selector>),
 
+    /// Match an element with any class name starting with the given prefix.
+    ClassPrefix(String, Box<Selector>),
+
     /// Element that is being hovered.
     Hover(Box<Selector>),
 
@@ -54,6 +89,34 @@ pub enum Selector {
     /// Element is the last child of its parent.
     LastChild(Box<Selector>),
 
+    /// Element is disabled.
+    Disabled(Box<Selector>),
+
+    /// Element is checked.
+    Checked(Box<Selector>),
+
+    /// Element is being dragged.
+    Dragging(Box<Selector>),
+
+    /// Element has something being dragged over it.
+    DragOver(Box<Selector>),
+
+    /// The app's `ColorScheme` resource is dark.
+    Dark(Box<Selector>),
+
+    /// The app's `PeacockCapabilities` resource has a capability.
+    Capability(CapabilityKind, Box<Selector>),
+
+    /// The primary window's focus or the app's `UiPauseState` is in a state.
+    AppState(AppStateKind, Box<Selector>),
+
+    /// The app's `Locale` resource matches a language tag, such as `de` or `zh-Hant`.
+    Lang(String, Box<Selector>),
+
+    /// Element matches a custom pseudo-class registered by the app, named without the leading
+    /// dashes.
+    Custom(String, Box<Selector>),
+
     /// Reference to the current element.
     Current(Box<Selector>),
 
@@ -63,6 +126,132 @@ pub enum Selector {
     /// List of alternate choices.
     #[allow(clippy::vec_box)]
     Either(Vec<Box<Selector>>),
+
+    /// The primary window size matches a media query.
+    Media(MediaQuery, Box<Selector>),
+
+    /// The named feature flag is enabled in the app's `StyleFlags` resource.
+    Flag(String, Box<Selector>),
+}
+
+/// A condition on the logical size of the primary window, in pixels. All of the specified
+/// bounds must be satisfied.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct MediaQuery {
+    /// Minimum window width.
+    pub min_width: Option<f32>,
+    /// Maximum window width.
+    pub max_width: Option<f32>,
+    /// Minimum window height.
+    pub min_height: Option<f32>,
+    /// Maximum window height.
+    pub max_height: Option<f32>,
+}
+
+/// A capability of the device, or a preference of the user, which selectors can test with a
+/// pseudo-class such as `:reduced-motion`, independent of the element.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum CapabilityKind {
+    /// The user prefers less motion: `:reduced-motion`.
+    ReducedMotion,
+    /// The primary input is a touch screen: `:touch`.
+    Touch,
+    /// A gamepad is the primary input: `:gamepad`.
+    Gamepad,
+}
+
+impl CapabilityKind {
+    /// Every capability.
+    pub const ALL: [CapabilityKind; 3] = [
+        CapabilityKind::ReducedMotion,
+        CapabilityKind::Touch,
+        CapabilityKind::Gamepad,
+    ];
+
+    /// The name of the pseudo-class which tests the capability, without the colon.
+    pub fn name(self) -> &'static str {
+        match self {
+            CapabilityKind::ReducedMotion => "reduced-motion",
+            CapabilityKind::Touch => "touch",
+            CapabilityKind::Gamepad => "gamepad",
+        }
+    }
+}
+
+impl fmt::Display for CapabilityKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, ":{}", self.name())
+    }
+}
+
+/// A state of the app, rather than of the element, which selectors can test with a
+/// pseudo-class such as `:paused`.
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum AppStateKind {
+    /// The primary window has focus: `:window-focused`.
+    WindowFocused,
+    /// The primary window doesn't have focus: `:window-unfocused`.
+    WindowUnfocused,
+    /// The app's `UiPauseState` is paused: `:paused`.
+    Paused,
+}
+
+impl AppStateKind {
+    /// The name of the pseudo-class which tests the state, without the colon.
+    pub fn name(self) -> &'static str {
+        match self {
+            AppStateKind::WindowFocused => "window-focused",
+            AppStateKind::WindowUnfocused => "window-unfocused",
+            AppStateKind::Paused => "paused",
+        }
+    }
+
+    /// True for the states which depend on the focus of the primary window.
+    pub fn is_window_focus(self) -> bool {
+        matches!(
+            self,
+            AppStateKind::WindowFocused | AppStateKind::WindowUnfocused
+        )
+    }
+}
+
+impl fmt::Display for AppStateKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, ":{}", self.name())
+    }
+}
+
+impl MediaQuery {
+    /// Returns whether a window of the given size satisfies the query.
+    pub fn matches(&self, size: Vec2) -> bool {
+        self.min_width.is_none_or(|w| size.x >= w)
+            && self.max_width.is_none_or(|w| size.x <= w)
+            && self.min_height.is_none_or(|h| size.y >= h)
+            && self.max_height.is_none_or(|h| size.y <= h)
+    }
+}
+
+impl fmt::Display for MediaQuery {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "@media")?;
+        let features = [
+            ("min-width", self.min_width),
+            ("max-width", self.max_width),
+            ("min-height", self.min_height),
+            ("max-height", self.max_height),
+        ];
+        let mut first = true;
+        for (name, value) in features {
+            if let Some(value) = value {
+                if !first {
+                    write!(f, " and")?;
+                }
+                write!(f, " ({}: {})", name, value)?;
+                first = false;
+            }
+        }
+        Ok(())
+    }
 }
 
 impl Selector {
@@ -71,31 +260,62 @@ impl Selector {
     pub fn depth(&self) -> usize {
         match self {
             Selector::Accept => 1,
-            Selector::Class(_, next) => next.depth(),
+            Selector::Class(_, next) | Selector::ClassPrefix(_, next) => next.depth(),
             Selector::Hover(next)
             | Selector::Focus(next)
             | Selector::FocusWithin(next)
             | Selector::FocusVisible(next)
             | Selector::FirstChild(next)
-            | Selector::LastChild(next) => next.depth(),
+            | Selector::LastChild(next)
+            | Selector::Disabled(next)
+            | Selector::Checked(next)
+            | Selector::Dragging(next)
+            | Selector::DragOver(next)
+            | Selector::Dark(next)
+            | Selector::Capability(_, next)
+            | Selector::AppState(_, next)
+            | Selector::Lang(_, next)
+            | Selector::Custom(_, next) => next.depth(),
             Selector::Current(next) => next.depth(),
             Selector::Parent(next) => next.depth() + 1,
             Selector::Either(opts) => opts.iter().map(|next| next.depth()).max().unwrap_or(0),
+            Selector::Media(_, next) | Selector::Flag(_, next) => next.depth(),
         }
     }
 
+    /// Returns the alternatives of this selector: the items of an `Either`, or just the
+    /// selector itself.
+    pub fn arms(&self) -> impl Iterator<Item = &Selector> {
+        let (this, opts): (Option<&Selector>, &[Box<Selector>]) = match self {
+            Selector::Either(opts) => (None, opts),
+            _ => (Some(self), &[]),
+        };
+        this.into_iter().chain(opts.iter().map(|arm| arm.as_ref()))
+    }
+
     /// Returns whether this selector uses the hover pseudo-class.
     pub fn uses_hover(&self) -> bool {
         match self {
             Selector::Accept => false,
-            Selector::Class(_, next) => next.uses_hover(),
+            Selector::Class(_, next) | Selector::ClassPrefix(_, next) => next.uses_hover(),
             Selector::Hover(_) => true,
             Selector::Focus(next)
             | Selector::FocusWithin(next)
             | Selector::FocusVisible(next)
             | Selector::FirstChild(next)
             | Selector::LastChild(next)
-            | Selector::Current(next) => next.uses_hover(),
+            | Selector::Disabled(next)
+            | Selector::Checked(next)
+            | Selector::Dragging(next)
+            | Selector::DragOver(next)
+            | Selector::Dark(next)
+            | Selector::Capability(_, next)
+            | Selector::AppState(_, next)
+            | Selector::Lang(_, next)
+            | Selector::Custom(_, next)
+            | Selector::Current(next)
+            | Selector::Media(_, next)
+            | Selector::Flag(_, next) => next.uses_hover(),
             Selector::Parent(next) => next.uses_hover(),
             Selector::Either(opts) => opts
                 .iter()
@@ -105,44 +325,226 @@ impl Selector {
         }
     }
 
-    /// Returns whether this selector uses the hover pseudo-class.
+    /// Returns whether this selector uses the focus-within pseudo-class.
     pub fn uses_focus_within(&self) -> bool {
         match self {
             Selector::Accept => false,
-            Selector::Class(_, next) => next.uses_hover(),
+            Selector::Class(_, next) | Selector::ClassPrefix(_, next) => next.uses_focus_within(),
             Selector::FocusWithin(_) => true,
             Selector::Hover(next)
             | Selector::Focus(next)
             | Selector::FocusVisible(next)
             | Selector::FirstChild(next)
             | Selector::LastChild(next)
-            | Selector::Current(next) => next.uses_hover(),
-            Selector::Parent(next) => next.uses_hover(),
+            | Selector::Disabled(next)
+            | Selector::Checked(next)
+            | Selector::Dragging(next)
+            | Selector::DragOver(next)
+            | Selector::Dark(next)
+            | Selector::Capability(_, next)
+            | Selector::AppState(_, next)
+            | Selector::Lang(_, next)
+            | Selector::Custom(_, next)
+            | Selector::Current(next)
+            | Selector::Media(_, next)
+            | Selector::Flag(_, next) => next.uses_focus_within(),
+            Selector::Parent(next) => next.uses_focus_within(),
             Selector::Either(opts) => opts
                 .iter()
-                .map(|next| next.uses_hover())
+                .map(|next| next.uses_focus_within())
                 .max()
                 .unwrap_or(false),
         }
     }
 
-    /// Parse a selector from a string.
+    /// Returns whether this selector depends on the window size.
+    pub fn uses_media(&self) -> bool {
+        match self {
+            Selector::Accept => false,
+            Selector::Media(..) => true,
+            Selector::Class(_, next)
+            | Selector::ClassPrefix(_, next)
+            | Selector::Hover(next)
+            | Selector::Focus(next)
+            | Selector::FocusWithin(next)
+            | Selector::FocusVisible(next)
+            | Selector::FirstChild(next)
+            | Selector::LastChild(next)
+            | Selector::Disabled(next)
+            | Selector::Checked(next)
+            | Selector::Dragging(next)
+            | Selector::DragOver(next)
+            | Selector::Dark(next)
+            | Selector::Capability(_, next)
+            | Selector::AppState(_, next)
+            | Selector::Lang(_, next)
+            | Selector::Custom(_, next)
+            | Selector::Current(next)
+            | Selector::Parent(next)
+            | Selector::Flag(_, next) => next.uses_media(),
+            Selector::Either(opts) => opts.iter().any(|next| next.uses_media()),
+        }
+    }
+
+    /// Returns whether this selector depends on the app's color scheme.
+    pub fn uses_color_scheme(&self) -> bool {
+        match self {
+            Selector::Accept => false,
+            Selector::Dark(_) => true,
+            Selector::Capability(_, next)
+            | Selector::AppState(_, next)
+            | Selector::Class(_, next)
+            | Selector::ClassPrefix(_, next)
+            | Selector::Hover(next)
+            | Selector::Focus(next)
+            | Selector::FocusWithin(next)
+            | Selector::FocusVisible(next)
+            | Selector::FirstChild(next)
+            | Selector::LastChild(next)
+            | Selector::Disabled(next)
+            | Selector::Checked(next)
+            | Selector::Dragging(next)
+            | Selector::DragOver(next)
+            | Selector::Lang(_, next)
+            | Selector::Custom(_, next)
+            | Selector::Current(next)
+            | Selector::Parent(next)
+            | Selector::Media(_, next)
+            | Selector::Flag(_, next) => next.uses_color_scheme(),
+            Selector::Either(opts) => opts.iter().any(|next| next.uses_color_scheme()),
+        }
+    }
+
+    /// Returns whether this selector depends on the app's capabilities.
+    pub fn uses_capabilities(&self) -> bool {
+        match self {
+            Selector::Accept => false,
+            Selector::Capability(..) => true,
+            Selector::AppState(_, next)
+            | Selector::Class(_, next)
+            | Selector::ClassPrefix(_, next)
+            | Selector::Hover(next)
+            | Selector::Focus(next)
+            | Selector::FocusWithin(next)
+            | Selector::FocusVisible(next)
+            | Selector::FirstChild(next)
+            | Selector::LastChild(next)
+            | Selector::Disabled(next)
+            | Selector::Checked(next)
+            | Selector::Dragging(next)
+            | Selector::DragOver(next)
+            | Selector::Dark(next)
+            | Selector::Lang(_, next)
+            | Selector::Custom(_, next)
+            | Selector::Current(next)
+            | Selector::Parent(next)
+            | Selector::Media(_, next)
+            | Selector::Flag(_, next) => next.uses_capabilities(),
+            Selector::Either(opts) => opts.iter().any(|next| next.uses_capabilities()),
+        }
+    }
+
+    /// Returns whether this selector depends on whether the primary window has focus.
+    pub fn uses_window_focus(&self) -> bool {
+        self.uses_app_state(AppStateKind::is_window_focus)
+    }
+
+    /// Returns whether this selector depends on whether the app is paused.
+    pub fn uses_pause(&self) -> bool {
+        self.uses_app_state(|kind| kind == AppStateKind::Paused)
+    }
+
+    /// Returns whether this selector uses any of the app state pseudo-classes accepted by
+    /// `filter`.
+    fn uses_app_state(&self, filter: fn(AppStateKind) -> bool) -> bool {
+        match self {
+            Selector::Accept => false,
+            Selector::AppState(kind, _) if filter(*kind) => true,
+            Selector::AppState(_, next)
+            | Selector::Class(_, next)
+            | Selector::ClassPrefix(_, next)
+            | Selector::Hover(next)
+            | Selector::Focus(next)
+            | Selector::FocusWithin(next)
+            | Selector::FocusVisible(next)
+            | Selector::FirstChild(next)
+            | Selector::LastChild(next)
+            | Selector::Disabled(next)
+            | Selector::Checked(next)
+            | Selector::Dragging(next)
+            | Selector::DragOver(next)
+            | Selector::Dark(next)
+            | Selector::Capability(_, next)
+            | Selector::Lang(_, next)
+            | Selector::Custom(_, next)
+            | Selector::Current(next)
+            | Selector::Parent(next)
+            | Selector::Media(_, next)
+            | Selector::Flag(_, next) => next.uses_app_state(filter),
+            Selector::Either(opts) => opts.iter().any(|next| next.uses_app_state(filter)),
+        }
+    }
+
+    /// Returns the names of the feature flags this selector depends on, in the order they
+    /// appear, which may include duplicates.
+    pub fn feature_flags(&self) -> Vec<&str> {
+        let mut flags = Vec::new();
+        let mut pending = vec![self];
+        while let Some(next) = pending.pop() {
+            match next {
+                Selector::Accept => {}
+                Selector::Flag(name, next) => {
+                    flags.push(name.as_str());
+                    pending.push(next);
+                }
+                Selector::Class(_, next)
+                | Selector::ClassPrefix(_, next)
+                | Selector::Hover(next)
+                | Selector::Focus(next)
+                | Selector::FocusWithin(next)
+                | Selector::FocusVisible(next)
+                | Selector::FirstChild(next)
+                | Selector::LastChild(next)
+                | Selector::Disabled(next)
+                | Selector::Checked(next)
+                | Selector::Dragging(next)
+                | Selector::DragOver(next)
+                | Selector::Dark(next)
+                | Selector::Capability(_, next)
+                | Selector::AppState(_, next)
+                | Selector::Lang(_, next)
+                | Selector::Custom(_, next)
+                | Selector::Current(next)
+                | Selector::Parent(next)
+                | Selector::Media(_, next) => pending.push(next),
+                Selector::Either(opts) => pending.extend(opts.iter().rev().map(|opt| opt.as_ref())),
+            }
+        }
+        flags
+    }
+
+    /// Parse a selector from a string, returning the error as a string.
+    #[deprecated(note = "use `str::parse`, which returns a `StylesheetError`")]
     pub fn parse(input: &str) -> Result<Self, String> {
-        selector_parser::selector_parser
-            .parse(input.trim())
-            .map(|a| *a)
-            .map_err(|e| e.to_string())
+        input
+            .parse()
+            .map_err(|err: StylesheetError| err.to_string())
     }
 }
 
+/// Parse a selector from a string. Errors are of kind [`StylesheetErrorKind::Selector`], with
+/// positions within `input`.
+///
+/// [`StylesheetErrorKind::Selector`]: crate::StylesheetErrorKind::Selector
 impl std::str::FromStr for Selector {
-    type Err = String;
+    type Err = StylesheetError;
 
     fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let start = input.len() - input.trim_start().len();
         selector_parser::selector_parser
             .parse(input.trim())
             .map(|a| *a)
-            .map_err(|e| e.to_string())
+            .map_err(|err| StylesheetError::from_selector_error(input, start, err))
     }
 }
 
@@ -154,26 +556,116 @@ impl fmt::Display for Selector {
                 // Because 'current' comes first, reverse order
                 let mut str = String::with_capacity(64);
                 let mut p = prev.as_ref();
-                while let Selector::Class(name, desc) = p {
-                    str.insert_str(0, name);
-                    str.insert(0, '.');
-                    p = desc.as_ref()
+                loop {
+                    match p {
+                        Selector::Class(name, desc) => {
+                            str.insert_str(0, name);
+                            str.insert(0, '.');
+                            p = desc.as_ref()
+                        }
+                        Selector::ClassPrefix(name, desc) => {
+                            str.insert(0, '*');
+                            str.insert_str(0, name);
+                            str.insert(0, '.');
+                            p = desc.as_ref()
+                        }
+                        Selector::Hover(desc) => {
+                            str.insert_str(0, ":hover");
+                            p = desc.as_ref()
+                        }
+                        Selector::Focus(desc) => {
+                            str.insert_str(0, ":focus");
+                            p = desc.as_ref()
+                        }
+                        Selector::FocusWithin(desc) => {
+                            str.insert_str(0, ":focus-within");
+                            p = desc.as_ref()
+                        }
+                        Selector::FocusVisible(desc) => {
+                            str.insert_str(0, ":focus-visible");
+                            p = desc.as_ref()
+                        }
+                        Selector::FirstChild(desc) => {
+                            str.insert_str(0, ":first-child");
+                            p = desc.as_ref()
+                        }
+                        Selector::LastChild(desc) => {
+                            str.insert_str(0, ":last-child");
+                            p = desc.as_ref()
+                        }
+                        Selector::Disabled(desc) => {
+                            str.insert_str(0, ":disabled");
+                            p = desc.as_ref()
+                        }
+                        Selector::Checked(desc) => {
+                            str.insert_str(0, ":checked");
+                            p = desc.as_ref()
+                        }
+                        Selector::Dragging(desc) => {
+                            str.insert_str(0, ":dragging");
+                            p = desc.as_ref()
+                        }
+                        Selector::DragOver(desc) => {
+                            str.insert_str(0, ":drag-over");
+                            p = desc.as_ref()
+                        }
+                        Selector::Dark(desc) => {
+                            str.insert_str(0, ":dark");
+                            p = desc.as_ref()
+                        }
+                        Selector::Capability(kind, desc) => {
+                            str.insert_str(0, &kind.to_string());
+                            p = desc.as_ref()
+                        }
+                        Selector::AppState(kind, desc) => {
+                            str.insert_str(0, &kind.to_string());
+                            p = desc.as_ref()
+                        }
+                        Selector::Lang(tag, desc) => {
+                            str.insert_str(0, &format!(":lang({})", tag));
+                            p = desc.as_ref()
+                        }
+                        Selector::Custom(name, desc) => {
+                            str.insert_str(0, name);
+                            str.insert_str(0, ":--");
+                            p = desc.as_ref()
+                        }
+                        _ => break,
+                    }
                 }
                 str.insert(0, '&');
                 write!(f, "{}{}", p, str)
             }
 
             Selector::Class(name, prev) => write!(f, "{}.{}", prev, name),
+            Selector::ClassPrefix(name, prev) => write!(f, "{}.{}*", prev, name),
             Selector::Hover(prev) => write!(f, "{}:hover", prev),
             Selector::Focus(prev) => write!(f, "{}:focus", prev),
             Selector::FocusWithin(prev) => write!(f, "{}:focus-within", prev),
             Selector::FocusVisible(prev) => write!(f, "{}:focus-visible", prev),
             Selector::FirstChild(prev) => write!(f, "{}:first-child", prev),
             Selector::LastChild(prev) => write!(f, "{}:last-child", prev),
+            Selector::Disabled(prev) => write!(f, "{}:disabled", prev),
+            Selector::Checked(prev) => write!(f, "{}:checked", prev),
+            Selector::Dragging(prev) => write!(f, "{}:dragging", prev),
+            Selector::DragOver(prev) => write!(f, "{}:drag-over", prev),
+            Selector::Dark(prev) => write!(f, "{}:dark", prev),
+            Selector::Capability(kind, prev) => write!(f, "{}{}", prev, kind),
+            Selector::AppState(kind, prev) => write!(f, "{}{}", prev, kind),
+            Selector::Lang(tag, prev) => write!(f, "{}:lang({})", prev, tag),
+            Selector::Custom(name, prev) => write!(f, "{}:--{}", prev, name),
             Selector::Parent(prev) => match prev.as_ref() {
                 Selector::Parent(_) => write!(f, "{}* > ", prev),
                 _ => write!(f, "{} > ", prev),
             },
+            Selector::Media(query, next) => match next.as_ref() {
+                Selector::Accept => write!(f, "{}", query),
+                _ => write!(f, "{} {}", query, next),
+            },
+            Selector::Flag(name, next) => match next.as_ref() {
+                Selector::Accept => write!(f, "@flag {}", name),
+                _ => write!(f, "@flag {} {}", name, next),
+            },
             Selector::Either(items) => {
                 for (index, item) in items.iter().enumerate() {
                     if index > 0 {
@@ -192,4 +684,34 @@ pub trait SelectorMatcher {
     /// Given an array of match params representing the element's ancestor chain, match the
     /// selector expression with the params.
     fn selector_match(&self, selector: &Selector, entity: &Entity) -> bool;
+
+    /// Match `compiled`, the compiled form of `selector`, against the element. This is what
+    /// styles use to match their selectors; matchers which don't evaluate compiled selectors
+    /// match the tree instead.
+    fn compiled_match(
+        &self,
+        _compiled: &CompiledSelector,
+        selector: &Selector,
+        entity: &Entity,
+    ) -> bool {
+        self.selector_match(selector, entity)
+    }
+
+    /// Return whether the nearest scope enclosing the element is named `scope`. Matchers which
+    /// don't support scopes never match.
+    fn scope_match(&self, _scope: &str, _entity: &Entity) -> bool {
+        false
+    }
+
+    /// Return the value of the theme token named `token` in the current theme, or `None` if
+    /// it's undefined. Matchers which don't support themes never define any tokens.
+    fn theme_token(&self, _token: &str) -> Option<TokenValue> {
+        None
+    }
+
+    /// Return the root font size in pixels, which `rem` lengths are multiples of. Matchers
+    /// which don't support it use [`DEFAULT_ROOT_FONT_SIZE`].
+    fn root_font_size(&self) -> f32 {
+        DEFAULT_ROOT_FONT_SIZE
+    }
 }
@@ -1,21 +1,32 @@
 use winnow::{
-    ascii::space0,
-    combinator::{alt, opt, preceded, repeat, separated},
+    ascii::{float, space0, space1},
+    combinator::{alt, delimited, opt, preceded, repeat, separated, terminated},
+    error::{ErrMode, ErrorKind, FromExternalError},
     stream::AsChar,
     token::{one_of, take_while},
     PResult, Parser,
 };
 
-use crate::Selector;
+use crate::{style_parser::StyleParsingError, AppStateKind, CapabilityKind, MediaQuery, Selector};
 
 enum SelectorToken<'s> {
     Class(&'s str),
+    ClassPrefix(&'s str),
     Hover,
     FirstChild,
     LastChild,
     Focus,
     FocusWithin,
     FocusVisible,
+    Disabled,
+    Checked,
+    Dragging,
+    DragOver,
+    Dark,
+    Capability(CapabilityKind),
+    AppState(AppStateKind),
+    Lang(&'s str),
+    Custom(&'s str),
 }
 
 fn parent(input: &mut &str) -> PResult<()> {
@@ -23,16 +34,22 @@ fn parent(input: &mut &str) -> PResult<()> {
 }
 
 fn class_name<'s>(input: &mut &'s str) -> PResult<SelectorToken<'s>> {
-    preceded(
-        '.',
-        (
-            one_of(AsChar::is_alpha),
-            take_while(0.., (AsChar::is_alphanum, '-', '_')),
+    (
+        preceded(
+            '.',
+            (
+                one_of(AsChar::is_alpha),
+                take_while(0.., (AsChar::is_alphanum, '-', '_')),
+            )
+                .recognize(),
         ),
+        opt('*'),
     )
-    .recognize()
-    .map(|cls: &str| SelectorToken::Class(&cls[1..]))
-    .parse_next(input)
+        .map(|(cls, wildcard): (&str, _)| match wildcard {
+            Some(_) => SelectorToken::ClassPrefix(cls),
+            None => SelectorToken::Class(cls),
+        })
+        .parse_next(input)
 }
 
 fn hover<'s>(input: &mut &'s str) -> PResult<SelectorToken<'s>> {
@@ -77,6 +94,92 @@ fn last_child<'s>(input: &mut &'s str) -> PResult<SelectorToken<'s>> {
         .parse_next(input)
 }
 
+fn disabled<'s>(input: &mut &'s str) -> PResult<SelectorToken<'s>> {
+    ":disabled"
+        .recognize()
+        .map(|_| SelectorToken::Disabled)
+        .parse_next(input)
+}
+
+fn checked<'s>(input: &mut &'s str) -> PResult<SelectorToken<'s>> {
+    ":checked"
+        .recognize()
+        .map(|_| SelectorToken::Checked)
+        .parse_next(input)
+}
+
+fn dragging<'s>(input: &mut &'s str) -> PResult<SelectorToken<'s>> {
+    ":dragging"
+        .recognize()
+        .map(|_| SelectorToken::Dragging)
+        .parse_next(input)
+}
+
+fn drag_over<'s>(input: &mut &'s str) -> PResult<SelectorToken<'s>> {
+    ":drag-over"
+        .recognize()
+        .map(|_| SelectorToken::DragOver)
+        .parse_next(input)
+}
+
+fn dark<'s>(input: &mut &'s str) -> PResult<SelectorToken<'s>> {
+    ":dark"
+        .recognize()
+        .map(|_| SelectorToken::Dark)
+        .parse_next(input)
+}
+
+/// A capability pseudo-class, such as `:reduced-motion`.
+fn capability<'s>(input: &mut &'s str) -> PResult<SelectorToken<'s>> {
+    alt((
+        ":reduced-motion".value(CapabilityKind::ReducedMotion),
+        ":touch".value(CapabilityKind::Touch),
+        ":gamepad".value(CapabilityKind::Gamepad),
+    ))
+    .map(SelectorToken::Capability)
+    .parse_next(input)
+}
+
+/// An app state pseudo-class, such as `:paused`.
+fn app_state<'s>(input: &mut &'s str) -> PResult<SelectorToken<'s>> {
+    alt((
+        ":window-focused".value(AppStateKind::WindowFocused),
+        ":window-unfocused".value(AppStateKind::WindowUnfocused),
+        ":paused".value(AppStateKind::Paused),
+    ))
+    .map(SelectorToken::AppState)
+    .parse_next(input)
+}
+
+/// A locale pseudo-class, such as `:lang(de)` or `:lang(zh-Hant)`.
+fn lang<'s>(input: &mut &'s str) -> PResult<SelectorToken<'s>> {
+    delimited(
+        (":lang(", space0),
+        (
+            one_of(AsChar::is_alpha),
+            take_while(0.., (AsChar::is_alphanum, '-')),
+        )
+            .recognize(),
+        (space0, ')'),
+    )
+    .map(SelectorToken::Lang)
+    .parse_next(input)
+}
+
+/// A custom pseudo-class defined by the app, such as `:--in-combat`.
+fn custom<'s>(input: &mut &'s str) -> PResult<SelectorToken<'s>> {
+    preceded(
+        ":--",
+        (
+            one_of(AsChar::is_alpha),
+            take_while(0.., (AsChar::is_alphanum, '-', '_')),
+        )
+            .recognize(),
+    )
+    .map(SelectorToken::Custom)
+    .parse_next(input)
+}
+
 fn simple_selector<'s>(input: &mut &'s str) -> PResult<(Option<char>, Vec<SelectorToken<'s>>)> {
     (
         opt(alt(('*', '&'))),
@@ -87,9 +190,19 @@ fn simple_selector<'s>(input: &mut &'s str) -> PResult<(Option<char>, Vec<Select
                 hover,
                 first_child,
                 last_child,
-                focus,
+                // Longer names first, since `:focus` is a prefix of the others.
                 focus_within,
                 focus_visible,
+                focus,
+                disabled,
+                checked,
+                dragging,
+                drag_over,
+                dark,
+                capability,
+                app_state,
+                lang,
+                custom,
             )),
         ),
     )
@@ -104,6 +217,9 @@ fn combo_selector(input: &mut &str) -> PResult<Box<Selector>> {
             SelectorToken::Class(cls) => {
                 sel = Box::new(Selector::Class(cls.into(), sel));
             }
+            SelectorToken::ClassPrefix(cls) => {
+                sel = Box::new(Selector::ClassPrefix(cls.into(), sel));
+            }
             SelectorToken::Hover => {
                 sel = Box::new(Selector::Hover(sel));
             }
@@ -122,6 +238,33 @@ fn combo_selector(input: &mut &str) -> PResult<Box<Selector>> {
             SelectorToken::FocusVisible => {
                 sel = Box::new(Selector::FocusVisible(sel));
             }
+            SelectorToken::Disabled => {
+                sel = Box::new(Selector::Disabled(sel));
+            }
+            SelectorToken::Checked => {
+                sel = Box::new(Selector::Checked(sel));
+            }
+            SelectorToken::Dragging => {
+                sel = Box::new(Selector::Dragging(sel));
+            }
+            SelectorToken::DragOver => {
+                sel = Box::new(Selector::DragOver(sel));
+            }
+            SelectorToken::Dark => {
+                sel = Box::new(Selector::Dark(sel));
+            }
+            SelectorToken::Capability(kind) => {
+                sel = Box::new(Selector::Capability(kind, sel));
+            }
+            SelectorToken::AppState(kind) => {
+                sel = Box::new(Selector::AppState(kind, sel));
+            }
+            SelectorToken::Lang(tag) => {
+                sel = Box::new(Selector::Lang(tag.into(), sel));
+            }
+            SelectorToken::Custom(name) => {
+                sel = Box::new(Selector::Custom(name.into(), sel));
+            }
         }
     }
     if let Some(ch) = prefix {
@@ -132,9 +275,14 @@ fn combo_selector(input: &mut &str) -> PResult<Box<Selector>> {
     Ok(sel)
 }
 
+/// One alternative of a selector: a chain of terms separated by `>`. The last term matches the
+/// element being styled, whether or not it starts with `&`, so `&` can't appear in any other.
 fn desc_selector(input: &mut &str) -> PResult<Box<Selector>> {
+    let start = *input;
+    let mut misplaced_current = false;
     let mut sel = combo_selector.parse_next(input)?;
     while parent.parse_next(input).is_ok() {
+        misplaced_current |= matches!(sel.as_ref(), Selector::Current(_));
         sel = Box::new(Selector::Parent(sel));
         let (prefix, classes) = simple_selector.parse_next(input)?;
         for tok in classes {
@@ -142,6 +290,9 @@ fn desc_selector(input: &mut &str) -> PResult<Box<Selector>> {
                 SelectorToken::Class(cls) => {
                     sel = Box::new(Selector::Class(cls.into(), sel));
                 }
+                SelectorToken::ClassPrefix(cls) => {
+                    sel = Box::new(Selector::ClassPrefix(cls.into(), sel));
+                }
                 SelectorToken::Hover => {
                     sel = Box::new(Selector::Hover(sel));
                 }
@@ -160,6 +311,33 @@ fn desc_selector(input: &mut &str) -> PResult<Box<Selector>> {
                 SelectorToken::FocusVisible => {
                     sel = Box::new(Selector::FocusVisible(sel));
                 }
+                SelectorToken::Disabled => {
+                    sel = Box::new(Selector::Disabled(sel));
+                }
+                SelectorToken::Checked => {
+                    sel = Box::new(Selector::Checked(sel));
+                }
+                SelectorToken::Dragging => {
+                    sel = Box::new(Selector::Dragging(sel));
+                }
+                SelectorToken::DragOver => {
+                    sel = Box::new(Selector::DragOver(sel));
+                }
+                SelectorToken::Dark => {
+                    sel = Box::new(Selector::Dark(sel));
+                }
+                SelectorToken::Capability(kind) => {
+                    sel = Box::new(Selector::Capability(kind, sel));
+                }
+                SelectorToken::AppState(kind) => {
+                    sel = Box::new(Selector::AppState(kind, sel));
+                }
+                SelectorToken::Lang(tag) => {
+                    sel = Box::new(Selector::Lang(tag.into(), sel));
+                }
+                SelectorToken::Custom(name) => {
+                    sel = Box::new(Selector::Custom(name.into(), sel));
+                }
             }
         }
         if let Some(ch) = prefix {
@@ -169,6 +347,16 @@ fn desc_selector(input: &mut &str) -> PResult<Box<Selector>> {
         }
     }
 
+    if misplaced_current {
+        let src = start[..start.len() - input.len()].trim_end();
+        *input = start;
+        return Err(ErrMode::from_external_error(
+            input,
+            ErrorKind::Verify,
+            StyleParsingError::MisplacedCurrent(src.to_owned()),
+        )
+        .cut());
+    }
     Ok(sel)
 }
 
@@ -184,13 +372,99 @@ fn either(input: &mut &str) -> PResult<Box<Selector>> {
         .parse_next(input)
 }
 
+/// A single media feature, such as `(max-width: 800)`.
+fn media_feature(input: &mut &str) -> PResult<(&'static str, f32)> {
+    delimited(
+        ('(', space0),
+        (
+            alt((
+                "min-width".value("min-width"),
+                "max-width".value("max-width"),
+                "min-height".value("min-height"),
+                "max-height".value("max-height"),
+            )),
+            (space0, ':', space0),
+            terminated(float, opt("px")),
+        ),
+        (space0, ')'),
+    )
+    .map(|(name, _, value)| (name, value))
+    .parse_next(input)
+}
+
+/// A media query, such as `@media (min-width: 800) and (max-width: 1200)`.
+fn media_query(input: &mut &str) -> PResult<MediaQuery> {
+    preceded(
+        ("@media", space1),
+        separated(1.., media_feature, (space1, "and", space1)),
+    )
+    .map(|features: Vec<(&str, f32)>| {
+        let mut query = MediaQuery::default();
+        for (name, value) in features {
+            match name {
+                "min-width" => query.min_width = Some(value),
+                "max-width" => query.max_width = Some(value),
+                "min-height" => query.min_height = Some(value),
+                _ => query.max_height = Some(value),
+            }
+        }
+        query
+    })
+    .parse_next(input)
+}
+
+/// The name of a feature flag, such as `new_checkout`.
+fn flag_name<'s>(input: &mut &'s str) -> PResult<&'s str> {
+    take_while(1.., (AsChar::is_alphanum, '_', '-')).parse_next(input)
+}
+
+/// A feature flag condition, such as `@flag new_checkout`.
+fn flag(input: &mut &str) -> PResult<String> {
+    preceded(("@flag", space1), flag_name)
+        .map(str::to_owned)
+        .parse_next(input)
+}
+
+/// A condition which applies to the whole selector.
+enum Condition {
+    Media(MediaQuery),
+    Flag(String),
+}
+
 pub fn selector_parser(input: &mut &str) -> PResult<Box<Selector>> {
-    either.parse_next(input)
+    let conditions: Vec<Condition> = repeat(
+        ..,
+        terminated(
+            alt((media_query.map(Condition::Media), flag.map(Condition::Flag))),
+            space0,
+        ),
+    )
+    .parse_next(input)?;
+    // Conditions on their own apply to the current element.
+    let mut sel = either.parse_next(input)?;
+    for condition in conditions.into_iter().rev() {
+        sel = Box::new(match condition {
+            Condition::Media(query) => Selector::Media(query, sel),
+            Condition::Flag(name) => Selector::Flag(name, sel),
+        });
+    }
+    Ok(sel)
+}
+
+/// Parse a media query on its own, such as `@media (max-width: 800)`.
+pub(crate) fn media_query_parser(input: &mut &str) -> PResult<MediaQuery> {
+    media_query.parse_next(input)
+}
+
+/// Parse a feature flag condition on its own, such as `@flag new_checkout`.
+pub(crate) fn flag_parser(input: &mut &str) -> PResult<String> {
+    flag.parse_next(input)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use bevy::math::Vec2;
 
     #[test]
     fn test_parse_current() {
@@ -226,6 +500,13 @@ mod tests {
             ".foo > &.bar.baz".parse::<Selector>().unwrap().to_string(),
             ".foo > &.bar.baz",
         );
+        assert_eq!(
+            ".foo:hover > &.bar:focus-within"
+                .parse::<Selector>()
+                .unwrap()
+                .to_string(),
+            ".foo:hover > &.bar:focus-within",
+        );
         assert_eq!(
             ".a.b.c > .d.e.f > &.g.h.i"
                 .parse::<Selector>()
@@ -239,6 +520,38 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_parse_class_prefix() {
+        assert_eq!(
+            ".icon-*".parse::<Selector>().unwrap(),
+            Selector::ClassPrefix("icon-".into(), Box::new(Selector::Accept))
+        );
+        assert_eq!(
+            ".menu-* > &.icon-*".parse::<Selector>().unwrap(),
+            Selector::Current(Box::new(Selector::ClassPrefix(
+                "icon-".into(),
+                Box::new(Selector::Parent(Box::new(Selector::ClassPrefix(
+                    "menu-".into(),
+                    Box::new(Selector::Accept)
+                ))))
+            )))
+        );
+        assert_eq!(
+            ".menu-* > &.icon-*.big"
+                .parse::<Selector>()
+                .unwrap()
+                .to_string(),
+            ".menu-* > &.icon-*.big",
+        );
+        assert_eq!(
+            ".icon-*".parse::<Selector>().unwrap().to_string(),
+            ".icon-*"
+        );
+        assert!(".icon-**".parse::<Selector>().is_err());
+        assert!(".ic*on".parse::<Selector>().is_err());
+        assert!(".*".parse::<Selector>().is_err());
+    }
+
     #[test]
     fn test_parse_current_class() {
         assert_eq!(
@@ -299,15 +612,178 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_parse_disabled_checked() {
+        assert_eq!(
+            ":disabled".parse::<Selector>().unwrap(),
+            Selector::Disabled(Box::new(Selector::Accept))
+        );
+        assert_eq!(
+            "&.toggle:checked".parse::<Selector>().unwrap(),
+            Selector::Current(Box::new(Selector::Checked(Box::new(Selector::Class(
+                "toggle".into(),
+                Box::new(Selector::Accept)
+            )))))
+        );
+        assert_eq!(
+            ":disabled > &:checked"
+                .parse::<Selector>()
+                .unwrap()
+                .to_string(),
+            ":disabled > &:checked",
+        );
+    }
+
+    #[test]
+    fn test_parse_drag() {
+        assert_eq!(
+            "&:dragging".parse::<Selector>().unwrap(),
+            Selector::Current(Box::new(Selector::Dragging(Box::new(Selector::Accept))))
+        );
+        assert_eq!(
+            ".slot:drag-over".parse::<Selector>().unwrap(),
+            Selector::DragOver(Box::new(Selector::Class(
+                "slot".into(),
+                Box::new(Selector::Accept)
+            )))
+        );
+        assert_eq!(
+            ":drag-over > &.item:dragging"
+                .parse::<Selector>()
+                .unwrap()
+                .to_string(),
+            ":drag-over > &.item:dragging",
+        );
+    }
+
+    #[test]
+    fn test_parse_dark() {
+        assert_eq!(
+            "&:dark".parse::<Selector>().unwrap(),
+            Selector::Current(Box::new(Selector::Dark(Box::new(Selector::Accept))))
+        );
+        // Round trip through `Display`.
+        for src in ["&:dark", ".panel:dark > &.title", ":dark, &:hover:dark"] {
+            let sel = src.parse::<Selector>().unwrap();
+            assert_eq!(sel.to_string(), src);
+            assert!(sel.uses_color_scheme());
+        }
+        assert!(!"&:drag-over"
+            .parse::<Selector>()
+            .unwrap()
+            .uses_color_scheme());
+    }
+
+    #[test]
+    fn test_parse_capability() {
+        assert_eq!(
+            "&.spinner:reduced-motion".parse::<Selector>().unwrap(),
+            Selector::Current(Box::new(Selector::Capability(
+                CapabilityKind::ReducedMotion,
+                Box::new(Selector::Class(
+                    "spinner".into(),
+                    Box::new(Selector::Accept)
+                ))
+            )))
+        );
+        // Round trip through `Display`.
+        for src in [
+            "&:touch",
+            ":gamepad > &.hint",
+            "&:reduced-motion:dark, &:hover",
+        ] {
+            let sel = src.parse::<Selector>().unwrap();
+            assert_eq!(sel.to_string(), src);
+            assert!(sel.uses_capabilities());
+        }
+        assert!(!"&:dark".parse::<Selector>().unwrap().uses_capabilities());
+        assert!(":reduced".parse::<Selector>().is_err());
+    }
+
+    #[test]
+    fn test_parse_app_state() {
+        assert_eq!(
+            ".hud:window-unfocused > &".parse::<Selector>().unwrap(),
+            Selector::Current(Box::new(Selector::Parent(Box::new(Selector::AppState(
+                AppStateKind::WindowUnfocused,
+                Box::new(Selector::Class("hud".into(), Box::new(Selector::Accept)))
+            )))))
+        );
+        // Round trip through `Display`.
+        for src in [
+            "&:window-focused",
+            ":paused > &.menu",
+            "&:paused:dark, &:hover",
+        ] {
+            let sel = src.parse::<Selector>().unwrap();
+            assert_eq!(sel.to_string(), src);
+        }
+        let sel = "&:window-focused".parse::<Selector>().unwrap();
+        assert!(sel.uses_window_focus() && !sel.uses_pause());
+        let sel = ":paused > &".parse::<Selector>().unwrap();
+        assert!(sel.uses_pause() && !sel.uses_window_focus());
+        assert!(!sel.uses_capabilities());
+        assert!(!"&:touch".parse::<Selector>().unwrap().uses_pause());
+        assert!(":window".parse::<Selector>().is_err());
+    }
+
+    #[test]
+    fn test_parse_lang() {
+        assert_eq!(
+            ":lang(de)".parse::<Selector>().unwrap(),
+            Selector::Lang("de".into(), Box::new(Selector::Accept))
+        );
+        assert_eq!(
+            "&.title:lang(zh-Hant)".parse::<Selector>().unwrap(),
+            Selector::Current(Box::new(Selector::Lang(
+                "zh-Hant".into(),
+                Box::new(Selector::Class("title".into(), Box::new(Selector::Accept)))
+            )))
+        );
+
+        // Round trip through `Display`, which is how stylesheets are embedded by
+        // `import_stylesheet!`.
+        for src in [":lang(de)", "&.title:lang(ja)", ":lang(de-AT) > &:hover"] {
+            assert_eq!(src.parse::<Selector>().unwrap().to_string(), src);
+        }
+
+        assert!(":lang()".parse::<Selector>().is_err());
+        assert!(":lang(-de)".parse::<Selector>().is_err());
+        assert!(":lang(de".parse::<Selector>().is_err());
+    }
+
+    #[test]
+    fn test_parse_custom() {
+        assert_eq!(
+            ":--in-combat".parse::<Selector>().unwrap(),
+            Selector::Custom("in-combat".into(), Box::new(Selector::Accept))
+        );
+        assert_eq!(
+            "&.quest:--quest_available".parse::<Selector>().unwrap(),
+            Selector::Current(Box::new(Selector::Custom(
+                "quest_available".into(),
+                Box::new(Selector::Class("quest".into(), Box::new(Selector::Accept)))
+            )))
+        );
+        for src in [":--in-combat", "&.a:--b", ":--party > &:hover:--low-health"] {
+            assert_eq!(src.parse::<Selector>().unwrap().to_string(), src);
+        }
+        assert!(":-in-combat".parse::<Selector>().is_err());
+        assert!(":--".parse::<Selector>().is_err());
+        assert!(":--1st".parse::<Selector>().is_err());
+    }
+
     #[test]
     fn test_parse_parent() {
+        // The last term matches the current element, with or without `&`.
         assert_eq!(
-            "&.foo > .bar".parse::<Selector>().unwrap(),
+            ".foo > .bar".parse::<Selector>().unwrap(),
             Selector::Class(
                 "bar".into(),
-                Box::new(Selector::Parent(Box::new(Selector::Current(Box::new(
-                    Selector::Class("foo".into(), Box::new(Selector::Accept))
-                )))))
+                Box::new(Selector::Parent(Box::new(Selector::Class(
+                    "foo".into(),
+                    Box::new(Selector::Accept)
+                ))))
             )
         );
 
@@ -323,6 +799,36 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_misplaced_current() {
+        for (src, offset, arm) in [
+            ("&.foo > .bar", 0, "&.foo > .bar"),
+            ("& > &", 0, "& > &"),
+            (".a > &.b > .c", 0, ".a > &.b > .c"),
+            ("&.a, &.b > .c", 5, "&.b > .c"),
+            ("@media (max-width: 800) & > .x", 24, "& > .x"),
+        ] {
+            let err = src.parse::<Selector>().unwrap_err();
+            assert_eq!(err.offset, offset, "{}", src);
+            assert!(
+                err.message.ends_with(&format!("'{}'", arm)),
+                "{}: {}",
+                src,
+                err.message
+            );
+        }
+
+        // Stylesheets report the same error, at the same place.
+        let err = crate::parse_stylesheet("A {\n  .a, &.b > .c { width: 1px; }\n}").unwrap_err();
+        assert_eq!(err.kind, crate::StylesheetErrorKind::Selector);
+        assert_eq!(err.rule.as_deref(), Some("A"));
+        assert_eq!((err.pos.line, err.pos.column), (2, 7));
+        assert!(err
+            .message
+            .contains("'&' can only appear in the last term of a selector"));
+        assert!(err.message.ends_with("'&.b > .c'"));
+    }
+
     #[test]
     fn test_either() {
         assert_eq!(
@@ -336,4 +842,75 @@ mod tests {
             ))
         );
     }
+
+    #[test]
+    fn test_media() {
+        let query = MediaQuery {
+            max_width: Some(800.),
+            ..Default::default()
+        };
+        assert_eq!(
+            "@media (max-width: 800px)".parse::<Selector>().unwrap(),
+            Selector::Media(query, Box::new(Selector::Accept))
+        );
+        assert_eq!(
+            "@media (min-width: 400) and (max-height: 300) &:hover"
+                .parse::<Selector>()
+                .unwrap(),
+            Selector::Media(
+                MediaQuery {
+                    min_width: Some(400.),
+                    max_height: Some(300.),
+                    ..Default::default()
+                },
+                Box::new(Selector::Current(Box::new(Selector::Hover(Box::new(
+                    Selector::Accept
+                )))))
+            )
+        );
+
+        // Round trip through `Display`.
+        for src in [
+            "@media (max-width: 800)",
+            "@media (min-width: 400) and (max-height: 300) &.wide",
+            "@media (min-height: 200) .a > &, &.b",
+        ] {
+            let sel = src.parse::<Selector>().unwrap();
+            assert_eq!(sel.to_string(), src);
+            assert!(sel.uses_media());
+        }
+
+        assert!(query.matches(Vec2::new(800., 2000.)));
+        assert!(!query.matches(Vec2::new(801., 2000.)));
+    }
+
+    #[test]
+    fn test_flag() {
+        assert_eq!(
+            "@flag new_checkout &.cta".parse::<Selector>().unwrap(),
+            Selector::Flag(
+                "new_checkout".into(),
+                Box::new(Selector::Current(Box::new(Selector::Class(
+                    "cta".into(),
+                    Box::new(Selector::Accept)
+                ))))
+            )
+        );
+
+        // Round trip through `Display`, including conditions combined with media queries.
+        for (src, flags) in [
+            ("@flag dark-mode", vec!["dark-mode"]),
+            ("@flag a @media (max-width: 800) .x > &", vec!["a"]),
+            (
+                "@media (max-width: 800) @flag a @flag b &:hover",
+                vec!["a", "b"],
+            ),
+        ] {
+            let sel = src.parse::<Selector>().unwrap();
+            assert_eq!(sel.to_string(), src);
+            assert_eq!(sel.feature_flags(), flags);
+        }
+        assert!("@flag".parse::<Selector>().is_err());
+        assert!("@flag &".parse::<Selector>().is_err());
+    }
 }
@@ -0,0 +1,88 @@
+/// A position in a stylesheet source. Lines and columns start at 1; columns count characters,
+/// not bytes.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SourcePos {
+    /// Line number.
+    pub line: u32,
+    /// Column number.
+    pub column: u32,
+}
+
+/// The range of a stylesheet source occupied by a rule, selector or property.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SourceSpan {
+    /// Position of the first character.
+    pub start: SourcePos,
+    /// Position just past the last character.
+    pub end: SourcePos,
+}
+
+/// Source locations of a selector entry, and of each of its properties.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SelectorSpans {
+    /// The selector entry, including its block of properties.
+    pub selector: SourceSpan,
+    /// Each of the entry's properties, in order.
+    pub props: Vec<SourceSpan>,
+}
+
+/// Source locations for a parsed [`StylePropList`](crate::StylePropList). This is stored
+/// separately from the properties, so that styles which weren't parsed don't pay for it.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SpanTable {
+    /// Path of the stylesheet file, if known.
+    pub path: Option<String>,
+    /// The rule, from its name to its closing brace. For rules which are split into several
+    /// blocks, this is the first unconditional block.
+    pub rule: SourceSpan,
+    /// Each of the unconditional properties, in order.
+    pub props: Vec<SourceSpan>,
+    /// Each of the selector entries, in order.
+    pub selectors: Vec<SelectorSpans>,
+    /// Each of the scope entries, in order.
+    pub scopes: Vec<SelectorSpans>,
+}
+
+/// Converts byte offsets in a source string to line and column numbers.
+pub(crate) struct LineIndex<'s> {
+    src: &'s str,
+    line_starts: Vec<usize>,
+}
+
+impl<'s> LineIndex<'s> {
+    pub(crate) fn new(src: &'s str) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(src.match_indices('\n').map(|(index, _)| index + 1))
+            .collect();
+        Self { src, line_starts }
+    }
+
+    pub(crate) fn src(&self) -> &'s str {
+        self.src
+    }
+
+    pub(crate) fn pos(&self, offset: usize) -> SourcePos {
+        let line = self.line_starts.partition_point(|start| *start <= offset) - 1;
+        let line_start = self.line_starts[line];
+        SourcePos {
+            line: line as u32 + 1,
+            column: self.src[line_start..offset].chars().count() as u32 + 1,
+        }
+    }
+
+    /// The byte offset of `pos`; the inverse of [`pos`](Self::pos).
+    pub(crate) fn offset(&self, pos: SourcePos) -> usize {
+        let line_start = self.line_starts[pos.line as usize - 1];
+        self.src[line_start..]
+            .char_indices()
+            .nth(pos.column as usize - 1)
+            .map_or(self.src.len(), |(index, _)| line_start + index)
+    }
+
+    pub(crate) fn span(&self, start: usize, end: usize) -> SourceSpan {
+        SourceSpan {
+            start: self.pos(start),
+            end: self.pos(end),
+        }
+    }
+}
@@ -1,5 +1,7 @@
 #![allow(missing_docs)]
 
+use std::sync::{Arc, OnceLock};
+
 use bevy::{
     asset::AssetPath,
     math::{IVec2, Vec3},
@@ -7,8 +9,21 @@ use bevy::{
     ui,
 };
 
-use super::{selector::Selector, transition::Transition};
-use crate::{cursor::Cursor, selector::SelectorMatcher, ComputedStyle, StyleBuilder};
+use super::{
+    calc::{CalcLength, CalcProperty},
+    compiled_selector::CompiledSelector,
+    selector::Selector,
+    span::{SourceSpan, SpanTable},
+    style_field::StyleFieldSet,
+    transition::{unit_name, AnimateIn, Transition, TransitionProperty, TransitionPropertySet},
+};
+use crate::{
+    cursor::Cursor,
+    rem::{resolve_rem, Length, DEFAULT_ROOT_FONT_SIZE},
+    selector::SelectorMatcher,
+    token::{resolve_token, token_placeholder},
+    ComputedStyle, StyleBuilder,
+};
 
 /// Controls behavior of bevy_mod_picking
 #[derive(Debug, Clone, Copy, PartialEq)]
@@ -19,11 +34,52 @@ pub enum PointerEvents {
     All,
 }
 
+/// How text which overflows its node horizontally is displayed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TextOverflow {
+    /// The text is clipped to the node (or overflows it, depending on the node's `overflow`).
+    Clip,
+    /// The text is truncated, with an ellipsis ("…") appended.
+    Ellipsis,
+}
+
+/// A likely mistake in a style, found by [`StylePropList::validate`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum StyleLint {
+    /// A transition is declared on a layout property which is only ever set to `auto`, which
+    /// can't be resolved to a length, so the transition has no effect.
+    UnanimatableTransition {
+        property: TransitionProperty,
+        /// The unit of the first value set for the property, which is `auto`.
+        unit: &'static str,
+    },
+}
+
+impl std::fmt::Display for StyleLint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            StyleLint::UnanimatableTransition { property, unit } => write!(
+                f,
+                "transition on {:?} has no effect, because {:?} is only set to {} values, \
+                which can't be animated",
+                property, property, unit
+            ),
+        }
+    }
+}
+
 /// The set of all style attributes. This is represented as a list of enums rather than
 /// a map so that attributes can be both strongly typed and represented sparsely.
 #[derive(Debug, Clone)]
 pub enum StyleProp {
     BackgroundImage(Option<AssetPath<'static>>),
+    /// Mirror the background image horizontally.
+    BackgroundImageFlipX(bool),
+    /// Mirror the background image vertically.
+    BackgroundImageFlipY(bool),
+    /// Color the background image is multiplied by. Takes the place of the background color
+    /// when there is an image.
+    BackgroundImageTint(Option<Color>),
     BackgroundColor(Option<Color>),
     BorderColor(Option<Color>),
     Color(Option<Color>),
@@ -32,7 +88,7 @@ pub enum StyleProp {
 
     Display(ui::Display),
     Position(ui::PositionType),
-    Overflow(ui::OverflowAxis),
+    Overflow(ui::Overflow),
     OverflowX(ui::OverflowAxis),
     OverflowY(ui::OverflowAxis),
     Direction(ui::Direction),
@@ -50,6 +106,9 @@ pub enum StyleProp {
     MaxHeight(ui::Val),
     AspectRatio(Option<f32>),
 
+    /// A layout length relative to the parent's size, such as `calc(100% - 32px)`.
+    Calc(CalcProperty, CalcLength),
+
     // Allow margin sides to be set individually
     Margin(ui::UiRect),
     MarginLeft(ui::Val),
@@ -77,7 +136,8 @@ pub enum StyleProp {
     FlexBasis(ui::Val),
     RowGap(ui::Val),
     ColumnGap(ui::Val),
-    Gap(ui::Val),
+    /// Row gap and column gap.
+    Gap(ui::Val, ui::Val),
 
     AlignItems(ui::AlignItems),
     AlignSelf(ui::AlignSelf),
@@ -107,6 +167,13 @@ pub enum StyleProp {
     // Text
     Font(Option<AssetPath<'static>>),
     FontSize(f32),
+    TextOverflow(TextOverflow),
+    TextAlign(TextAlignment),
+
+    // Reset inherited text properties to the parent's value
+    InheritColor,
+    InheritFont,
+    InheritFontSize,
 
     // Outlines
     OutlineColor(Option<Color>),
@@ -125,113 +192,906 @@ pub enum StyleProp {
     Rotation(f32),
     Translation(Vec3),
 
+    // Scale factor for the pixel sizes of a UI tree (root nodes only)
+    UiScale(f32),
+
     // Transitions
     Transition(Vec<Transition>),
+    AnimateIn(Vec<AnimateIn>),
+    /// Raise the element's z-index while its transform is animating, so that it draws over
+    /// its siblings.
+    ElevateWhileAnimating(bool),
+
+    /// A property set to a theme token, such as `background_color: token(surface)`, which is
+    /// resolved against the current theme whenever styles are computed. Holds the name of the
+    /// property and of the token.
+    Token(&'static str, String),
+
+    /// A property set to lengths of which at least one is relative to the root font size, such
+    /// as `padding: 1.5rem 8px`, which is resolved to pixels whenever styles are computed.
+    /// Holds the name of the property and its lengths.
+    Rem(&'static str, Vec<Length>),
+
+    /// A property declared with `!important`, such as `background_color: #f00 !important`. It's
+    /// applied after the normal properties of all of the element's styles, so that it wins
+    /// over them wherever they come in the order.
+    Important(Box<StyleProp>),
+}
+
+/// Which of a style's properties are applied by [`StylePropList::apply_pass`]. All of the
+/// element's styles are applied with `Normal`, then all of them again with `Important`, so
+/// that `!important` properties take precedence over normal ones from any style.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Priority {
+    /// The properties declared without `!important`.
+    Normal,
+    /// The properties declared with `!important`.
+    Important,
 }
 
 pub type SelectorEntry = (Box<Selector>, Vec<StyleProp>);
 
+/// The part of a [`StylePropList`] which a group of properties passed to the trace callback of
+/// [`StylePropList::apply_pass_traced`] came from.
+#[derive(Debug, Clone, Copy)]
+pub enum PropSource<'a> {
+    /// The style's unconditional properties.
+    Unconditional,
+    /// An `@scope(name)` block.
+    Scope(&'a str),
+    /// A selector entry.
+    Selector(&'a Selector),
+}
+
+/// Properties which only apply when the element's scope has the given name, written as
+/// `@scope(name) { ... }` inside a rule.
+pub type ScopeEntry = (String, Vec<StyleProp>);
+
+impl StyleProp {
+    /// Return the set of animatable properties which this style prop sets.
+    pub fn transition_properties(&self) -> TransitionPropertySet {
+        match self {
+            StyleProp::BackgroundColor(_) => TransitionProperty::BackgroundColor.into(),
+            StyleProp::BorderColor(_) => TransitionProperty::BorderColor.into(),
+            StyleProp::Left(_) => TransitionProperty::Left.into(),
+            StyleProp::Right(_) => TransitionProperty::Right.into(),
+            StyleProp::Top(_) => TransitionProperty::Top.into(),
+            StyleProp::Bottom(_) => TransitionProperty::Bottom.into(),
+            StyleProp::Width(_) => TransitionProperty::Width.into(),
+            StyleProp::Height(_) => TransitionProperty::Height.into(),
+            StyleProp::Calc(property, _) => property
+                .transition_property()
+                .map(TransitionPropertySet::from)
+                .unwrap_or_default(),
+            StyleProp::Border(_) => [
+                TransitionProperty::BorderLeft,
+                TransitionProperty::BorderRight,
+                TransitionProperty::BorderTop,
+                TransitionProperty::BorderBottom,
+            ]
+            .into_iter()
+            .collect(),
+            StyleProp::BorderLeft(_) => TransitionProperty::BorderLeft.into(),
+            StyleProp::BorderRight(_) => TransitionProperty::BorderRight.into(),
+            StyleProp::BorderTop(_) => TransitionProperty::BorderTop.into(),
+            StyleProp::BorderBottom(_) => TransitionProperty::BorderBottom.into(),
+            StyleProp::Padding(_) => [
+                TransitionProperty::PaddingLeft,
+                TransitionProperty::PaddingRight,
+                TransitionProperty::PaddingTop,
+                TransitionProperty::PaddingBottom,
+            ]
+            .into_iter()
+            .collect(),
+            StyleProp::PaddingLeft(_) => TransitionProperty::PaddingLeft.into(),
+            StyleProp::PaddingRight(_) => TransitionProperty::PaddingRight.into(),
+            StyleProp::PaddingTop(_) => TransitionProperty::PaddingTop.into(),
+            StyleProp::PaddingBottom(_) => TransitionProperty::PaddingBottom.into(),
+            StyleProp::Margin(_) => [
+                TransitionProperty::MarginLeft,
+                TransitionProperty::MarginRight,
+                TransitionProperty::MarginTop,
+                TransitionProperty::MarginBottom,
+            ]
+            .into_iter()
+            .collect(),
+            StyleProp::MarginLeft(_) => TransitionProperty::MarginLeft.into(),
+            StyleProp::MarginRight(_) => TransitionProperty::MarginRight.into(),
+            StyleProp::MarginTop(_) => TransitionProperty::MarginTop.into(),
+            StyleProp::MarginBottom(_) => TransitionProperty::MarginBottom.into(),
+            StyleProp::Gap(_, _) => [TransitionProperty::RowGap, TransitionProperty::ColumnGap]
+                .into_iter()
+                .collect(),
+            StyleProp::RowGap(_) => TransitionProperty::RowGap.into(),
+            StyleProp::ColumnGap(_) => TransitionProperty::ColumnGap.into(),
+            StyleProp::OutlineWidth(_) => TransitionProperty::OutlineWidth.into(),
+            StyleProp::OutlineOffset(_) => TransitionProperty::OutlineOffset.into(),
+            StyleProp::OutlineColor(_) => TransitionProperty::OutlineColor.into(),
+            StyleProp::FontSize(_) | StyleProp::InheritFontSize => {
+                TransitionProperty::FontSize.into()
+            }
+            StyleProp::Scale(_) | StyleProp::ScaleX(_) | StyleProp::ScaleY(_) => {
+                [TransitionProperty::Transform, TransitionProperty::Scale]
+                    .into_iter()
+                    .collect()
+            }
+            StyleProp::Rotation(_) => [TransitionProperty::Transform, TransitionProperty::Rotation]
+                .into_iter()
+                .collect(),
+            StyleProp::Translation(_) => [
+                TransitionProperty::Transform,
+                TransitionProperty::Translation,
+            ]
+            .into_iter()
+            .collect(),
+            StyleProp::Token(property, _) => token_placeholder(property)
+                .iter()
+                .fold(TransitionPropertySet::default(), |set, prop| {
+                    set | prop.transition_properties()
+                }),
+            StyleProp::Rem(property, lengths) => {
+                resolve_rem(property, lengths, DEFAULT_ROOT_FONT_SIZE)
+                    .iter()
+                    .fold(TransitionPropertySet::default(), |set, prop| {
+                        set | prop.transition_properties()
+                    })
+            }
+            StyleProp::Important(prop) => prop.transition_properties(),
+            _ => TransitionPropertySet::default(),
+        }
+    }
+
+    /// Return the value which this style prop sets for the layout property `property`, if any.
+    /// `calc()` and `rem` lengths are resolved to pixels, so they're returned as a pixel value.
+    pub fn layout_val(&self, property: TransitionProperty) -> Option<ui::Val> {
+        match (self, property) {
+            (StyleProp::Left(val), TransitionProperty::Left)
+            | (StyleProp::Right(val), TransitionProperty::Right)
+            | (StyleProp::Top(val), TransitionProperty::Top)
+            | (StyleProp::Bottom(val), TransitionProperty::Bottom)
+            | (StyleProp::Width(val), TransitionProperty::Width)
+            | (StyleProp::Height(val), TransitionProperty::Height)
+            | (StyleProp::BorderLeft(val), TransitionProperty::BorderLeft)
+            | (StyleProp::BorderRight(val), TransitionProperty::BorderRight)
+            | (StyleProp::BorderTop(val), TransitionProperty::BorderTop)
+            | (StyleProp::BorderBottom(val), TransitionProperty::BorderBottom)
+            | (StyleProp::PaddingLeft(val), TransitionProperty::PaddingLeft)
+            | (StyleProp::PaddingRight(val), TransitionProperty::PaddingRight)
+            | (StyleProp::PaddingTop(val), TransitionProperty::PaddingTop)
+            | (StyleProp::PaddingBottom(val), TransitionProperty::PaddingBottom)
+            | (StyleProp::MarginLeft(val), TransitionProperty::MarginLeft)
+            | (StyleProp::MarginRight(val), TransitionProperty::MarginRight)
+            | (StyleProp::MarginTop(val), TransitionProperty::MarginTop)
+            | (StyleProp::MarginBottom(val), TransitionProperty::MarginBottom)
+            | (StyleProp::RowGap(val), TransitionProperty::RowGap)
+            | (StyleProp::ColumnGap(val), TransitionProperty::ColumnGap)
+            | (StyleProp::Gap(val, _), TransitionProperty::RowGap)
+            | (StyleProp::Gap(_, val), TransitionProperty::ColumnGap) => Some(*val),
+            (StyleProp::Border(rect), TransitionProperty::BorderLeft)
+            | (StyleProp::Padding(rect), TransitionProperty::PaddingLeft)
+            | (StyleProp::Margin(rect), TransitionProperty::MarginLeft) => Some(rect.left),
+            (StyleProp::Border(rect), TransitionProperty::BorderRight)
+            | (StyleProp::Padding(rect), TransitionProperty::PaddingRight)
+            | (StyleProp::Margin(rect), TransitionProperty::MarginRight) => Some(rect.right),
+            (StyleProp::Border(rect), TransitionProperty::BorderTop)
+            | (StyleProp::Padding(rect), TransitionProperty::PaddingTop)
+            | (StyleProp::Margin(rect), TransitionProperty::MarginTop) => Some(rect.top),
+            (StyleProp::Border(rect), TransitionProperty::BorderBottom)
+            | (StyleProp::Padding(rect), TransitionProperty::PaddingBottom)
+            | (StyleProp::Margin(rect), TransitionProperty::MarginBottom) => Some(rect.bottom),
+            (StyleProp::Calc(calc, length), _) if calc.transition_property() == Some(property) => {
+                Some(ui::Val::Px(length.px))
+            }
+            (StyleProp::Rem(name, lengths), _) => {
+                resolve_rem(name, lengths, DEFAULT_ROOT_FONT_SIZE)
+                    .iter()
+                    .find_map(|prop| prop.layout_val(property))
+            }
+            (StyleProp::Important(prop), _) => prop.layout_val(property),
+            _ => None,
+        }
+    }
+
+    /// Return the layout property which this style prop sets, if it's one which can also be
+    /// set to a `calc()` length.
+    pub fn calc_property(&self) -> Option<CalcProperty> {
+        match self {
+            StyleProp::Left(_) => Some(CalcProperty::Left),
+            StyleProp::Right(_) => Some(CalcProperty::Right),
+            StyleProp::Top(_) => Some(CalcProperty::Top),
+            StyleProp::Bottom(_) => Some(CalcProperty::Bottom),
+            StyleProp::Width(_) => Some(CalcProperty::Width),
+            StyleProp::Height(_) => Some(CalcProperty::Height),
+            StyleProp::MinWidth(_) => Some(CalcProperty::MinWidth),
+            StyleProp::MinHeight(_) => Some(CalcProperty::MinHeight),
+            StyleProp::MaxWidth(_) => Some(CalcProperty::MaxWidth),
+            StyleProp::MaxHeight(_) => Some(CalcProperty::MaxHeight),
+            StyleProp::Calc(property, _) => Some(*property),
+            StyleProp::Important(prop) => prop.calc_property(),
+            _ => None,
+        }
+    }
+
+    /// Return the name of the property this style prop sets, as written in stylesheets, such
+    /// as `"width"`. Props which set the same property, such as `Color` and `InheritColor`,
+    /// have the same key.
+    pub fn key(&self) -> &'static str {
+        match self {
+            StyleProp::BackgroundImage(_) => "background_image",
+            StyleProp::BackgroundImageFlipX(_) => "background_image_flip_x",
+            StyleProp::BackgroundImageFlipY(_) => "background_image_flip_y",
+            StyleProp::BackgroundImageTint(_) => "background_image_tint",
+            StyleProp::BackgroundColor(_) => "background_color",
+            StyleProp::BorderColor(_) => "border_color",
+            StyleProp::Color(_) | StyleProp::InheritColor => "color",
+            StyleProp::ZIndex(_) => "z_index",
+            StyleProp::Display(_) => "display",
+            StyleProp::Position(_) => "position_type",
+            StyleProp::Overflow(_) => "overflow",
+            StyleProp::OverflowX(_) => "overflow_x",
+            StyleProp::OverflowY(_) => "overflow_y",
+            StyleProp::Direction(_) => "direction",
+            StyleProp::Left(_) => "left",
+            StyleProp::Right(_) => "right",
+            StyleProp::Top(_) => "top",
+            StyleProp::Bottom(_) => "bottom",
+            StyleProp::Width(_) => "width",
+            StyleProp::Height(_) => "height",
+            StyleProp::MinWidth(_) => "min_width",
+            StyleProp::MinHeight(_) => "min_height",
+            StyleProp::MaxWidth(_) => "max_width",
+            StyleProp::MaxHeight(_) => "max_height",
+            StyleProp::AspectRatio(_) => "aspect_ratio",
+            StyleProp::Calc(property, _) => property.name(),
+            StyleProp::Margin(_) => "margin",
+            StyleProp::MarginLeft(_) => "margin_left",
+            StyleProp::MarginRight(_) => "margin_right",
+            StyleProp::MarginTop(_) => "margin_top",
+            StyleProp::MarginBottom(_) => "margin_bottom",
+            StyleProp::Padding(_) => "padding",
+            StyleProp::PaddingLeft(_) => "padding_left",
+            StyleProp::PaddingRight(_) => "padding_right",
+            StyleProp::PaddingTop(_) => "padding_top",
+            StyleProp::PaddingBottom(_) => "padding_bottom",
+            StyleProp::Border(_) => "border",
+            StyleProp::BorderLeft(_) => "border_left",
+            StyleProp::BorderRight(_) => "border_right",
+            StyleProp::BorderTop(_) => "border_top",
+            StyleProp::BorderBottom(_) => "border_bottom",
+            StyleProp::FlexDirection(_) => "flex_direction",
+            StyleProp::FlexWrap(_) => "flex_wrap",
+            StyleProp::FlexGrow(_) => "flex_grow",
+            StyleProp::FlexShrink(_) => "flex_shrink",
+            StyleProp::FlexBasis(_) => "flex_basis",
+            StyleProp::RowGap(_) => "row_gap",
+            StyleProp::ColumnGap(_) => "column_gap",
+            StyleProp::Gap(_, _) => "gap",
+            StyleProp::AlignItems(_) => "align_items",
+            StyleProp::AlignSelf(_) => "align_self",
+            StyleProp::AlignContent(_) => "align_content",
+            StyleProp::JustifyItems(_) => "justify_items",
+            StyleProp::JustifySelf(_) => "justify_self",
+            StyleProp::JustifyContent(_) => "justify_content",
+            StyleProp::GridAutoFlow(_) => "grid_auto_flow",
+            StyleProp::GridTemplateRows(_) => "grid_template_rows",
+            StyleProp::GridTemplateColumns(_) => "grid_template_columns",
+            StyleProp::GridAutoRows(_) => "grid_auto_rows",
+            StyleProp::GridAutoColumns(_) => "grid_auto_columns",
+            StyleProp::GridRow(_) => "grid_row",
+            StyleProp::GridRowStart(_) => "grid_row_start",
+            StyleProp::GridRowSpan(_) => "grid_row_span",
+            StyleProp::GridRowEnd(_) => "grid_row_end",
+            StyleProp::GridColumn(_) => "grid_column",
+            StyleProp::GridColumnStart(_) => "grid_column_start",
+            StyleProp::GridColumnSpan(_) => "grid_column_span",
+            StyleProp::GridColumnEnd(_) => "grid_column_end",
+            StyleProp::PointerEvents(_) => "pointer_events",
+            StyleProp::Font(_) | StyleProp::InheritFont => "font",
+            StyleProp::FontSize(_) | StyleProp::InheritFontSize => "font_size",
+            StyleProp::TextOverflow(_) => "text_overflow",
+            StyleProp::TextAlign(_) => "text_align",
+            StyleProp::OutlineColor(_) => "outline_color",
+            StyleProp::OutlineWidth(_) => "outline_width",
+            StyleProp::OutlineOffset(_) => "outline_offset",
+            StyleProp::Cursor(_) => "cursor",
+            StyleProp::CursorImage(_) => "cursor_image",
+            StyleProp::CursorOffset(_) => "cursor_offset",
+            StyleProp::Scale(_) => "scale",
+            StyleProp::ScaleX(_) => "scale_x",
+            StyleProp::ScaleY(_) => "scale_y",
+            StyleProp::Rotation(_) => "rotation",
+            StyleProp::Translation(_) => "translation",
+            StyleProp::UiScale(_) => "ui_scale",
+            StyleProp::Transition(_) => "transition",
+            StyleProp::AnimateIn(_) => "animate_in",
+            StyleProp::ElevateWhileAnimating(_) => "elevate_while_animating",
+            StyleProp::Token(property, _) | StyleProp::Rem(property, _) => property,
+            StyleProp::Important(prop) => prop.key(),
+        }
+    }
+
+    /// Return true if the property was declared with `!important`.
+    pub fn is_important(&self) -> bool {
+        matches!(self, StyleProp::Important(_))
+    }
+
+    /// Return the set of `Style` fields which this style prop sets.
+    pub fn style_fields(&self) -> StyleFieldSet {
+        StyleFieldSet::for_key(self.key())
+    }
+}
+
 /// A collection of style attributes which can be merged to create a `ComputedStyle`.
+///
+/// The unconditional properties are applied first, then the scope entries whose scope matches
+/// the element, then the selector entries which match it. Later properties override earlier
+/// ones, so selectors such as `:hover` take precedence over scope entries.
+///
+/// Matching entries are always applied in the order they were declared, however the list was
+/// built. Selectors have no specificity, so when two matching entries set the same property,
+/// the one declared last wins, as it does in CSS for selectors of equal specificity.
+///
+/// The fields are private, so that the representation can change without breaking users. A
+/// list is built with one of these constructors:
+///
+/// * [`StylePropList::build`] or [`StylePropList::from_builder`], from a [`StyleBuilder`];
+/// * [`StylePropList::from_raw`], from the properties and selector entries, followed by
+///   [`with_scopes`](StylePropList::with_scopes) and [`with_spans`](StylePropList::with_spans)
+///   if needed;
+/// * [`parse_stylesheet`](crate::parse_stylesheet), for each rule of a stylesheet.
+///
+/// Lists are combined with [`merge`](StylePropList::merge), and read with
+/// [`props`](StylePropList::props), [`selectors`](StylePropList::selectors),
+/// [`scopes`](StylePropList::scopes) and [`all_props`](StylePropList::all_props):
+///
+/// ```
+/// use bevy::ui::Val;
+/// use bevy_peacock_style::{Selector, StyleProp, StylePropList};
+///
+/// let base = StylePropList::build(|ss| ss.width(10.).selector(":hover", |ss| ss.width(20.)));
+/// let wide = StylePropList::from_raw(vec![StyleProp::Width(Val::Px(30.))], Vec::new());
+/// let list = base.merge(wide);
+///
+/// assert_eq!(list.props().len(), 2);
+/// assert_eq!(*list.selectors()[0].0, ":hover".parse::<Selector>().unwrap());
+/// assert_eq!(list.all_props().count(), 3);
+/// ```
 #[derive(Debug, Default, Clone)]
 pub struct StylePropList {
+    /// The segments of the list, applied in order as though they were concatenated.
+    ///
+    /// A list derived from another with [`merge`](StylePropList::merge) shares the other's
+    /// layers rather than copying them, so that many small variations of a large base style
+    /// don't each hold a copy of it. Only the last layer is ever modified in place, and only
+    /// when it isn't shared.
+    layers: Vec<Arc<PropLayer>>,
+
+    /// The layers concatenated into one, built the first time a slice accessor is called on a
+    /// list with more than one layer.
+    flat: OnceLock<Arc<PropLayer>>,
+
+    /// Where the style was defined, for styles parsed from a stylesheet.
+    spans: Option<Box<SpanTable>>,
+}
+
+/// One segment of a [`StylePropList`].
+#[derive(Debug, Default, Clone)]
+struct PropLayer {
     /// List of style attributes.
     ///
     /// Rather than storing the attributes in a struct full of optional fields, we store a flat
     /// vector of enums, each of which stores a single style attribute. This "sparse" representation
     /// allows for fast merging of styles, particularly for styles which have few or no attributes.
-    pub(crate) props: Vec<StyleProp>,
+    props: Vec<StyleProp>,
 
     /// List of conditional styles
-    pub(crate) selectors: Vec<SelectorEntry>,
+    selectors: Vec<SelectorEntry>,
+
+    /// The selectors of the conditional styles, compiled for matching.
+    compiled: Vec<CompiledSelector>,
+
+    /// List of styles which depend on the element's scope
+    scopes: Vec<ScopeEntry>,
+
+    /// Whether any of the properties are `!important`, so that the important pass can skip
+    /// layers which have none.
+    important: bool,
+}
+
+impl PropLayer {
+    fn is_empty(&self) -> bool {
+        self.props.is_empty() && self.selectors.is_empty() && self.scopes.is_empty()
+    }
+
+    fn append(&mut self, other: PropLayer) {
+        self.props.extend(other.props);
+        self.selectors.extend(other.selectors);
+        self.compiled.extend(other.compiled);
+        self.scopes.extend(other.scopes);
+        self.important |= other.important;
+    }
+
+    fn append_shared(&mut self, other: &PropLayer) {
+        self.props.extend_from_slice(&other.props);
+        self.selectors.extend_from_slice(&other.selectors);
+        self.compiled.extend_from_slice(&other.compiled);
+        self.scopes.extend_from_slice(&other.scopes);
+        self.important |= other.important;
+    }
+}
+
+fn any_important<'a>(mut props: impl Iterator<Item = &'a StyleProp>) -> bool {
+    props.any(StyleProp::is_important)
 }
 
 impl StylePropList {
     pub fn new() -> Self {
-        Self {
-            props: Vec::new(),
-            selectors: Vec::new(),
-        }
+        Self::default()
     }
 
     pub fn from_builder(builder: StyleBuilder) -> Self {
+        Self::from_raw(builder.props, builder.selectors).with_scopes(builder.scopes)
+    }
+
+    /// Construct a list from its unconditional properties and selector entries.
+    pub fn from_raw(props: Vec<StyleProp>, selectors: Vec<SelectorEntry>) -> Self {
+        let important = any_important(
+            props
+                .iter()
+                .chain(selectors.iter().flat_map(|(_, props)| props)),
+        );
+        let layer = PropLayer {
+            props,
+            compiled: selectors
+                .iter()
+                .map(|(sel, _)| sel.as_ref().into())
+                .collect(),
+            selectors,
+            scopes: Vec::new(),
+            important,
+        };
         Self {
-            props: builder.props,
-            selectors: builder.selectors,
+            layers: vec![Arc::new(layer)],
+            flat: OnceLock::new(),
+            spans: None,
         }
     }
 
-    pub fn from_raw(props: Vec<StyleProp>, selectors: Vec<SelectorEntry>) -> Self {
-        Self { props, selectors }
+    /// Add scope entries to the style.
+    pub fn with_scopes(mut self, scopes: Vec<ScopeEntry>) -> Self {
+        if !scopes.is_empty() {
+            let layer = self.top_layer();
+            layer.important |= any_important(scopes.iter().flat_map(|(_, props)| props));
+            layer.scopes.extend(scopes);
+        }
+        self
     }
 
     /// Build a [`StylePropList`] using a builder callback.
     pub fn build(builder_fn: impl FnOnce(&mut StyleBuilder) -> &mut StyleBuilder) -> Self {
         let mut builder = StyleBuilder::default();
         builder_fn(&mut builder);
-        Self {
-            props: builder.props,
-            selectors: builder.selectors,
-        }
+        Self::from_builder(builder)
     }
 
     pub fn with_props(&mut self, props: &[StyleProp]) -> Self {
-        Self {
-            props: Vec::from(props),
-            selectors: Vec::new(),
+        Self::from_raw(Vec::from(props), Vec::new())
+    }
+
+    /// Append the properties and selectors of `other`, so that they take precedence over
+    /// those already in the list.
+    ///
+    /// Layers of `other` which are shared with other lists are appended by reference rather
+    /// than copied.
+    pub fn extend(&mut self, other: StylePropList) {
+        for layer in other.layers {
+            if layer.is_empty() {
+                continue;
+            }
+            match (
+                self.layers.last_mut().and_then(Arc::get_mut),
+                Arc::try_unwrap(layer),
+            ) {
+                (Some(top), Ok(layer)) => top.append(layer),
+                (_, Ok(layer)) => self.layers.push(Arc::new(layer)),
+                (_, Err(shared)) => self.layers.push(shared),
+            }
+        }
+        self.flat = OnceLock::new();
+        // Spans from different sources can't be combined into a single table.
+        self.spans = None;
+    }
+
+    /// Return the list with the properties and selectors of `other` appended, so that they
+    /// take precedence over those of `self`.
+    pub fn merge(mut self, other: StylePropList) -> Self {
+        self.extend(other);
+        self
+    }
+
+    /// Take the list apart into its unconditional properties, selector entries and scope
+    /// entries, discarding any source locations.
+    pub fn into_raw(self) -> (Vec<StyleProp>, Vec<SelectorEntry>, Vec<ScopeEntry>) {
+        let mut flat = PropLayer::default();
+        for layer in self.layers {
+            match Arc::try_unwrap(layer) {
+                Ok(layer) => flat.append(layer),
+                Err(shared) => flat.append_shared(&shared),
+            }
+        }
+        (flat.props, flat.selectors, flat.scopes)
+    }
+
+    /// Return the last layer for modification, adding a new one if it's shared with another
+    /// list. Clears the flattened copy, which it invalidates.
+    fn top_layer(&mut self) -> &mut PropLayer {
+        self.flat = OnceLock::new();
+        if self.layers.last_mut().and_then(Arc::get_mut).is_none() {
+            self.layers.push(Arc::default());
         }
+        Arc::get_mut(self.layers.last_mut().unwrap()).unwrap()
     }
 
+    /// Return the layers concatenated into one, flattening them on first use.
+    fn flat(&self) -> &PropLayer {
+        match self.layers.as_slice() {
+            [layer] => layer,
+            layers => self.flat.get_or_init(|| {
+                let mut flat = PropLayer::default();
+                for layer in layers {
+                    flat.append_shared(layer);
+                }
+                Arc::new(flat)
+            }),
+        }
+    }
+
+    /// Attach source locations to the style.
+    pub fn with_spans(mut self, spans: SpanTable) -> Self {
+        self.spans = Some(Box::new(spans));
+        self
+    }
+
+    /// Discard any source locations attached to the style.
+    pub fn without_spans(mut self) -> Self {
+        self.spans = None;
+        self
+    }
+
+    /// Return where the style was defined, if it was parsed from a stylesheet.
+    pub fn spans(&self) -> Option<&SpanTable> {
+        self.spans.as_deref()
+    }
+
+    /// Return where the unconditional property at `index` was defined, if known.
+    pub fn span_of_prop(&self, index: usize) -> Option<SourceSpan> {
+        self.spans.as_ref()?.props.get(index).copied()
+    }
+
+    /// Return where the selector entry at `index` was defined, if known.
+    pub fn span_of_selector(&self, index: usize) -> Option<SourceSpan> {
+        Some(self.spans.as_ref()?.selectors.get(index)?.selector)
+    }
+
+    /// The unconditional properties.
+    pub fn props(&self) -> &[StyleProp] {
+        self.flat().props.as_slice()
+    }
+
+    /// The conditional properties, each with the selector which must match for them to apply.
+    pub fn selectors(&self) -> &[SelectorEntry] {
+        self.flat().selectors.as_slice()
+    }
+
+    /// The compiled forms of the selectors of the conditional properties, in the same order.
+    pub fn compiled_selectors(&self) -> &[CompiledSelector] {
+        self.flat().compiled.as_slice()
+    }
+
+    /// The properties which depend on the element's scope, each with the scope name.
+    pub fn scopes(&self) -> &[ScopeEntry] {
+        self.flat().scopes.as_slice()
+    }
+
+    /// The unconditional properties. The same as [`props`](Self::props).
     pub fn get_props(&self) -> &[StyleProp] {
-        self.props.as_slice()
+        self.props()
     }
 
+    /// The conditional properties. The same as [`selectors`](Self::selectors).
     pub fn get_selectors(&self) -> &[SelectorEntry] {
-        self.selectors.as_slice()
+        self.selectors()
+    }
+
+    /// The properties which depend on the element's scope. The same as [`scopes`](Self::scopes).
+    pub fn get_scopes(&self) -> &[ScopeEntry] {
+        self.scopes()
+    }
+
+    /// Iterate over the selector entries of every layer, without flattening them.
+    fn selector_entries(&self) -> impl Iterator<Item = &SelectorEntry> {
+        self.layers.iter().flat_map(|layer| layer.selectors.iter())
+    }
+
+    /// Return whether any of the style props depend on the element's scope.
+    pub fn uses_scopes(&self) -> bool {
+        self.layers.iter().any(|layer| !layer.scopes.is_empty())
     }
 
     /// Return the number of UiNode levels referenced by selectors.
     pub fn depth(&self) -> usize {
-        self.selectors
-            .iter()
+        self.selector_entries()
             .map(|s| s.0.depth())
             .max()
             .unwrap_or(0)
     }
 
+    /// Return the selector alternatives whose depth exceeds `max_depth`.
+    pub fn deep_selectors(&self, max_depth: usize) -> impl Iterator<Item = &Selector> {
+        self.selector_entries()
+            .flat_map(|s| s.0.arms())
+            .filter(move |arm| arm.depth() > max_depth)
+    }
+
     /// Return whether any of the selectors use the ':hover' pseudo-class.
     pub fn uses_hover(&self) -> bool {
-        self.selectors.iter().any(|s| s.0.uses_hover())
+        self.selector_entries().any(|s| s.0.uses_hover())
     }
 
     /// Return whether any of the selectors use the ':focus-within' pseudo-class.
     pub fn uses_focus_within(&self) -> bool {
-        self.selectors.iter().any(|s| s.0.uses_focus_within())
+        self.selector_entries().any(|s| s.0.uses_focus_within())
+    }
+
+    /// Return whether any of the selectors depend on the window size.
+    pub fn uses_media(&self) -> bool {
+        self.selector_entries().any(|s| s.0.uses_media())
     }
 
-    /// Merge the style properties into a computed `Style` object.
+    /// Return whether any of the selectors depend on the app's color scheme.
+    pub fn uses_color_scheme(&self) -> bool {
+        self.selector_entries().any(|s| s.0.uses_color_scheme())
+    }
+
+    /// Return whether any of the selectors depend on the app's capabilities.
+    pub fn uses_capabilities(&self) -> bool {
+        self.selector_entries().any(|s| s.0.uses_capabilities())
+    }
+
+    /// Return whether any of the selectors depend on whether the primary window has focus.
+    pub fn uses_window_focus(&self) -> bool {
+        self.selector_entries().any(|s| s.0.uses_window_focus())
+    }
+
+    /// Return whether any of the selectors depend on whether the app is paused.
+    pub fn uses_pause(&self) -> bool {
+        self.selector_entries().any(|s| s.0.uses_pause())
+    }
+
+    /// Return the names of the feature flags which any of the selectors depend on, sorted and
+    /// without duplicates.
+    pub fn feature_flags(&self) -> Vec<&str> {
+        let mut flags: Vec<&str> = self
+            .selector_entries()
+            .flat_map(|s| s.0.feature_flags())
+            .collect();
+        flags.sort_unstable();
+        flags.dedup();
+        flags
+    }
+
+    /// Return whether any of the style props, including those in conditional styles, are
+    /// `calc()` lengths which depend on the size of the parent node.
+    pub fn uses_calc(&self) -> bool {
+        self.all_props()
+            .any(|prop| matches!(prop, StyleProp::Calc(_, _)))
+    }
+
+    /// Return the set of animatable properties set by any of the style props, including
+    /// those in conditional styles.
+    pub fn transition_properties(&self) -> TransitionPropertySet {
+        self.all_props()
+            .fold(TransitionPropertySet::default(), |set, prop| {
+                set | prop.transition_properties()
+            })
+    }
+
+    /// Return whether any of the style props, including those in conditional styles, declare
+    /// a transition for `property`.
+    pub fn declares_transition(&self, property: TransitionProperty) -> bool {
+        self.all_props().any(|prop| match prop {
+            StyleProp::Transition(trans) => trans.iter().any(|tr| tr.property == property),
+            _ => false,
+        })
+    }
+
+    /// Check the style for likely mistakes which can be found without applying it, such as a
+    /// transition on a property which is only set to values that can't be animated.
+    pub fn validate(&self) -> Vec<StyleLint> {
+        let mut lints = Vec::new();
+        let transitions = self.all_props().flat_map(|prop| match prop {
+            StyleProp::Transition(trans) => trans.as_slice(),
+            _ => &[],
+        });
+        for tr in transitions {
+            let mut vals = self
+                .all_props()
+                .filter_map(|prop| prop.layout_val(tr.property))
+                .peekable();
+            let Some(first) = vals.peek().copied() else {
+                continue;
+            };
+            if vals.any(|val| val != ui::Val::Auto) {
+                continue;
+            }
+            let lint = StyleLint::UnanimatableTransition {
+                property: tr.property,
+                unit: unit_name(first),
+            };
+            if !lints.contains(&lint) {
+                lints.push(lint);
+            }
+        }
+        lints
+    }
+
+    /// Iterate over every property: the unconditional ones, then those of the scope entries,
+    /// then those of the selector entries.
+    pub fn all_props(&self) -> impl Iterator<Item = &StyleProp> {
+        let layers = self.layers.iter();
+        layers
+            .clone()
+            .flat_map(|layer| layer.props.iter())
+            .chain(
+                layers
+                    .clone()
+                    .flat_map(|layer| layer.scopes.iter())
+                    .flat_map(|(_, props)| props.iter()),
+            )
+            .chain(self.selector_entries().flat_map(|(_, props)| props.iter()))
+    }
+
+    /// Merge the style properties into a computed `Style` object: the normal properties,
+    /// then the `!important` ones.
     pub fn apply_to(
         &self,
         computed: &mut ComputedStyle,
         matcher: &dyn SelectorMatcher,
         entity: &Entity,
     ) {
+        self.apply_pass(computed, matcher, entity, Priority::Normal);
+        self.apply_pass(computed, matcher, entity, Priority::Important);
+    }
+
+    /// Merge the style properties with the given priority into a computed `Style` object.
+    /// When an element has several styles, each pass is made over all of them in turn.
+    pub fn apply_pass(
+        &self,
+        computed: &mut ComputedStyle,
+        matcher: &dyn SelectorMatcher,
+        entity: &Entity,
+        priority: Priority,
+    ) {
+        let layers = self
+            .layers
+            .iter()
+            .filter(|layer| priority == Priority::Normal || layer.important);
+        // Each phase walks every layer, so that the result is the same as for the flattened
+        // list: all the unconditional properties, then the scopes, then the selectors.
+        for layer in layers.clone() {
+            self.apply_attrs_with(&layer.props, computed, Some(matcher), priority);
+        }
+        for layer in layers.clone() {
+            for (scope, props) in layer.scopes.iter() {
+                if matcher.scope_match(scope, entity) {
+                    self.apply_attrs_with(props, computed, Some(matcher), priority);
+                }
+            }
+        }
+        for layer in layers {
+            for ((selector, props), compiled) in layer.selectors.iter().zip(&layer.compiled) {
+                if matcher.compiled_match(compiled, selector, entity) {
+                    self.apply_attrs_with(props, computed, Some(matcher), priority);
+                }
+            }
+        }
+    }
+
+    /// Merge the style properties with the given priority into a computed `Style` object, as
+    /// [`apply_pass`](StylePropList::apply_pass) does, calling `trace` with each non-empty
+    /// group of properties as it's applied, so that tools can show where each of the
+    /// element's properties came from. A list with several layers is flattened first, so that
+    /// the groups are the same as those of the equivalent single list.
+    pub fn apply_pass_traced(
+        &self,
+        computed: &mut ComputedStyle,
+        matcher: &dyn SelectorMatcher,
+        entity: &Entity,
+        priority: Priority,
+        mut trace: impl FnMut(PropSource, &[StyleProp]),
+    ) {
+        let flat = self.flat();
+        if priority == Priority::Important && !flat.important {
+            return;
+        }
+        let mut apply = |source: PropSource, props: &[StyleProp], computed: &mut ComputedStyle| {
+            self.apply_attrs_with(props, computed, Some(matcher), priority);
+            // Only report the properties which this pass applied.
+            let applied: Vec<StyleProp> = props
+                .iter()
+                .filter(|prop| prop.is_important() == (priority == Priority::Important))
+                .cloned()
+                .collect();
+            if !applied.is_empty() {
+                trace(source, &applied);
+            }
+        };
+
         // Apply unconditional styles
-        self.apply_attrs_to(&self.props, computed);
+        apply(PropSource::Unconditional, &flat.props, computed);
+
+        // Apply styles for the element's scope
+        for (scope, props) in flat.scopes.iter() {
+            if matcher.scope_match(scope, entity) {
+                apply(PropSource::Scope(scope), props, computed);
+            }
+        }
 
         // Apply conditional styles
-        for (selector, props) in self.selectors.iter() {
-            if matcher.selector_match(selector, entity) {
-                self.apply_attrs_to(props, computed);
+        for ((selector, props), compiled) in flat.selectors.iter().zip(&flat.compiled) {
+            if matcher.compiled_match(compiled, selector, entity) {
+                apply(PropSource::Selector(selector), props, computed);
             }
         }
     }
 
+    /// Merge `attrs` into a computed `Style` object, the normal properties and then the
+    /// `!important` ones. There's no theme, so any theme tokens are treated as undefined.
     pub fn apply_attrs_to(&self, attrs: &[StyleProp], computed: &mut ComputedStyle) {
+        self.apply_attrs_with(attrs, computed, None, Priority::Normal);
+        self.apply_attrs_with(attrs, computed, None, Priority::Important);
+    }
+
+    /// Merge those of `attrs` which have the given priority into a computed `Style` object,
+    /// resolving theme tokens with `matcher`.
+    fn apply_attrs_with(
+        &self,
+        attrs: &[StyleProp],
+        computed: &mut ComputedStyle,
+        matcher: Option<&dyn SelectorMatcher>,
+        priority: Priority,
+    ) {
+        // Pixel sizes are scaled by the UI scale of the tree the element belongs to.
+        let px = computed.px_scale.unwrap_or(1.);
         for attr in attrs.iter() {
+            let attr = match (attr, priority) {
+                (StyleProp::Important(attr), Priority::Important) => attr.as_ref(),
+                (attr, Priority::Normal) if !attr.is_important() => attr,
+                _ => continue,
+            };
+            // Theme tokens and rem lengths mark the properties they resolve to as explicit, and
+            // the fields they set, when they're applied.
+            if !matches!(attr, StyleProp::Token(..) | StyleProp::Rem(..)) {
+                computed.explicit_props |= attr.transition_properties();
+                computed.style_fields |= attr.style_fields();
+            }
+            // A later value for a layout property replaces any earlier `calc()` length.
+            if let Some(property) = attr.calc_property() {
+                computed.calc.retain(|(p, _)| *p != property);
+            }
             match attr {
                 StyleProp::BackgroundImage(image) => {
                     computed.image = image.clone();
                 }
+                StyleProp::BackgroundImageFlipX(flip) => {
+                    computed.flip_x = *flip;
+                }
+                StyleProp::BackgroundImageFlipY(flip) => {
+                    computed.flip_y = *flip;
+                }
+                StyleProp::BackgroundImageTint(expr) => {
+                    computed.image_tint = *expr;
+                }
                 StyleProp::BackgroundColor(expr) => {
                     computed.background_color = *expr;
                 }
@@ -257,96 +1117,100 @@ impl StylePropList {
                     computed.style.overflow.y = *expr;
                 }
                 StyleProp::Overflow(expr) => {
-                    computed.style.overflow.x = *expr;
-                    computed.style.overflow.y = *expr;
+                    computed.style.overflow = *expr;
                 }
 
                 StyleProp::Direction(expr) => {
                     computed.style.direction = *expr;
                 }
                 StyleProp::Left(expr) => {
-                    computed.style.left = *expr;
+                    computed.style.left = scale_val(*expr, px);
                 }
                 StyleProp::Right(expr) => {
-                    computed.style.right = *expr;
+                    computed.style.right = scale_val(*expr, px);
                 }
                 StyleProp::Top(expr) => {
-                    computed.style.top = *expr;
+                    computed.style.top = scale_val(*expr, px);
                 }
                 StyleProp::Bottom(expr) => {
-                    computed.style.bottom = *expr;
+                    computed.style.bottom = scale_val(*expr, px);
                 }
                 StyleProp::Width(expr) => {
-                    computed.style.width = *expr;
+                    computed.style.width = scale_val(*expr, px);
                 }
                 StyleProp::Height(expr) => {
-                    computed.style.height = *expr;
+                    computed.style.height = scale_val(*expr, px);
                 }
                 StyleProp::MinWidth(expr) => {
-                    computed.style.min_width = *expr;
+                    computed.style.min_width = scale_val(*expr, px);
                 }
                 StyleProp::MinHeight(expr) => {
-                    computed.style.min_height = *expr;
+                    computed.style.min_height = scale_val(*expr, px);
                 }
                 StyleProp::MaxWidth(expr) => {
-                    computed.style.max_width = *expr;
+                    computed.style.max_width = scale_val(*expr, px);
                 }
                 StyleProp::MaxHeight(expr) => {
-                    computed.style.max_height = *expr;
+                    computed.style.max_height = scale_val(*expr, px);
                 }
                 StyleProp::AspectRatio(expr) => {
                     computed.style.aspect_ratio = *expr;
                 }
+                StyleProp::Calc(property, length) => {
+                    computed
+                        .calc
+                        .push((*property, CalcLength::new(length.percent, length.px * px)));
+                }
 
                 // Margins
                 StyleProp::Margin(expr) => {
-                    computed.style.margin = *expr;
+                    computed.style.margin = scale_rect(*expr, px);
                 }
                 StyleProp::MarginLeft(expr) => {
-                    computed.style.margin.left = *expr;
+                    computed.style.margin.left = scale_val(*expr, px);
                 }
                 StyleProp::MarginRight(expr) => {
-                    computed.style.margin.right = *expr;
+                    computed.style.margin.right = scale_val(*expr, px);
                 }
                 StyleProp::MarginTop(expr) => {
-                    computed.style.margin.top = *expr;
+                    computed.style.margin.top = scale_val(*expr, px);
                 }
                 StyleProp::MarginBottom(expr) => {
-                    computed.style.margin.bottom = *expr;
+                    computed.style.margin.bottom = scale_val(*expr, px);
                 }
 
                 // Padding
                 StyleProp::Padding(expr) => {
-                    computed.style.padding = *expr;
+                    computed.style.padding = scale_rect(*expr, px);
                 }
                 StyleProp::PaddingLeft(expr) => {
-                    computed.style.padding.left = *expr;
+                    computed.style.padding.left = scale_val(*expr, px);
                 }
                 StyleProp::PaddingRight(expr) => {
-                    computed.style.padding.right = *expr;
+                    computed.style.padding.right = scale_val(*expr, px);
                 }
                 StyleProp::PaddingTop(expr) => {
-                    computed.style.padding.top = *expr;
+                    computed.style.padding.top = scale_val(*expr, px);
                 }
                 StyleProp::PaddingBottom(expr) => {
-                    computed.style.padding.bottom = *expr;
+                    computed.style.padding.bottom = scale_val(*expr, px);
                 }
 
                 // Border
                 StyleProp::Border(expr) => {
-                    computed.style.border = *expr;
+                    computed.style.border = scale_rect(*expr, px);
                 }
                 StyleProp::BorderLeft(expr) => {
-                    computed.style.border.left = *expr;
+                    computed.style.border.left = scale_val(*expr, px);
                 }
                 StyleProp::BorderRight(expr) => {
-                    computed.style.border.right = *expr;
+                    computed.style.border.right = scale_val(*expr, px);
                 }
                 StyleProp::BorderTop(expr) => {
-                    computed.style.border.top = *expr;
+                    computed.style.border.top = scale_val(*expr, px);
                 }
                 StyleProp::BorderBottom(expr) => {
-                    computed.style.border.bottom = *expr;
+                    computed.style.border.bottom = scale_val(*expr, px);
                 }
 
                 // Flex
@@ -363,17 +1227,17 @@ impl StylePropList {
                     computed.style.flex_shrink = *expr;
                 }
                 StyleProp::FlexBasis(expr) => {
-                    computed.style.flex_basis = *expr;
+                    computed.style.flex_basis = scale_val(*expr, px);
                 }
                 StyleProp::ColumnGap(expr) => {
-                    computed.style.column_gap = *expr;
+                    computed.style.column_gap = scale_val(*expr, px);
                 }
                 StyleProp::RowGap(expr) => {
-                    computed.style.row_gap = *expr;
+                    computed.style.row_gap = scale_val(*expr, px);
                 }
-                StyleProp::Gap(expr) => {
-                    computed.style.column_gap = *expr;
-                    computed.style.row_gap = *expr;
+                StyleProp::Gap(row, column) => {
+                    computed.style.row_gap = scale_val(*row, px);
+                    computed.style.column_gap = scale_val(*column, px);
                 }
 
                 // Align
@@ -416,25 +1280,25 @@ impl StylePropList {
                     computed.style.grid_row = *expr;
                 }
                 StyleProp::GridRowStart(expr) => {
-                    computed.style.grid_row.set_start(*expr);
+                    computed.style.grid_row = computed.style.grid_row.set_start(*expr);
                 }
                 StyleProp::GridRowSpan(expr) => {
-                    computed.style.grid_row.set_span(*expr);
+                    computed.style.grid_row = computed.style.grid_row.set_span(*expr);
                 }
                 StyleProp::GridRowEnd(expr) => {
-                    computed.style.grid_row.set_end(*expr);
+                    computed.style.grid_row = computed.style.grid_row.set_end(*expr);
                 }
                 StyleProp::GridColumn(expr) => {
                     computed.style.grid_column = *expr;
                 }
                 StyleProp::GridColumnStart(expr) => {
-                    computed.style.grid_column.set_start(*expr);
+                    computed.style.grid_column = computed.style.grid_column.set_start(*expr);
                 }
                 StyleProp::GridColumnSpan(expr) => {
-                    computed.style.grid_column.set_span(*expr);
+                    computed.style.grid_column = computed.style.grid_column.set_span(*expr);
                 }
                 StyleProp::GridColumnEnd(expr) => {
-                    computed.style.grid_column.set_end(*expr);
+                    computed.style.grid_column = computed.style.grid_column.set_end(*expr);
                 }
 
                 // Outline
@@ -442,22 +1306,40 @@ impl StylePropList {
                     computed.outline_color = *expr;
                 }
                 StyleProp::OutlineWidth(expr) => {
-                    computed.outline_width = *expr;
+                    computed.outline_width = scale_val(*expr, px);
                 }
                 StyleProp::OutlineOffset(expr) => {
-                    computed.outline_offset = *expr;
+                    computed.outline_offset = scale_val(*expr, px);
                 }
 
                 StyleProp::PointerEvents(expr) => {
                     computed.pickable = Some(*expr);
                 }
 
+                StyleProp::TextOverflow(expr) => {
+                    computed.text_overflow = Some(*expr);
+                }
+                StyleProp::TextAlign(expr) => {
+                    computed.alignment = Some(*expr);
+                }
                 StyleProp::Font(expr) => {
                     computed.font = expr.clone();
                 }
 
                 StyleProp::FontSize(expr) => {
-                    computed.font_size = Some(*expr);
+                    computed.font_size = Some(*expr * px);
+                }
+
+                StyleProp::InheritColor => {
+                    computed.color = computed.inherited_color;
+                }
+                StyleProp::InheritFont => {
+                    // The font handle is already the inherited one; the element's own font
+                    // path is only resolved after all styles have been applied.
+                    computed.font = None;
+                }
+                StyleProp::InheritFontSize => {
+                    computed.font_size = computed.inherited_font_size;
                 }
 
                 StyleProp::Cursor(_) => todo!(),
@@ -481,8 +1363,327 @@ impl StylePropList {
                     computed.translation = Some(*expr);
                 }
 
+                StyleProp::UiScale(expr) => {
+                    computed.ui_scale = Some(*expr);
+                }
+
                 StyleProp::Transition(trans) => computed.transitions.clone_from(trans),
+                StyleProp::AnimateIn(anims) => computed.animate_in.clone_from(anims),
+                StyleProp::ElevateWhileAnimating(elevate) => {
+                    computed.elevate_while_animating = *elevate;
+                }
+
+                StyleProp::Token(property, token) => {
+                    let value = matcher.and_then(|matcher| matcher.theme_token(token));
+                    let props = resolve_token(property, token, value);
+                    self.apply_attrs_with(&props, computed, None, Priority::Normal);
+                }
+
+                StyleProp::Rem(property, lengths) => {
+                    let root_font_size =
+                        matcher.map_or(DEFAULT_ROOT_FONT_SIZE, |matcher| matcher.root_font_size());
+                    let props = resolve_rem(property, lengths, root_font_size);
+                    self.apply_attrs_with(&props, computed, None, Priority::Normal);
+                }
+
+                // `!important` has no further effect on a property which is already important.
+                StyleProp::Important(attr) => {
+                    self.apply_attrs_with(
+                        std::slice::from_ref(attr.as_ref()),
+                        computed,
+                        matcher,
+                        Priority::Normal,
+                    );
+                }
+            }
+        }
+    }
+}
+
+impl ComputedStyle {
+    /// Return the style which the element's entrance animations start from: this style, with
+    /// each animated property set to its starting value.
+    pub fn animate_in_origin(&self) -> ComputedStyle {
+        let mut origin = self.clone();
+        let from: Vec<StyleProp> = self
+            .animate_in
+            .iter()
+            .map(|anim| anim.from.clone())
+            .collect();
+        StylePropList::default().apply_attrs_to(&from, &mut origin);
+        origin
+    }
+}
+
+fn scale_val(val: ui::Val, scale: f32) -> ui::Val {
+    match val {
+        ui::Val::Px(px) => ui::Val::Px(px * scale),
+        _ => val,
+    }
+}
+
+fn scale_rect(rect: ui::UiRect, scale: f32) -> ui::UiRect {
+    ui::UiRect {
+        left: scale_val(rect.left, scale),
+        right: scale_val(rect.right, scale),
+        top: scale_val(rect.top, scale),
+        bottom: scale_val(rect.bottom, scale),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Matches the selectors and scope it names.
+    #[derive(Default)]
+    struct NamedMatcher {
+        selectors: &'static [&'static str],
+        scope: &'static str,
+    }
+
+    impl SelectorMatcher for NamedMatcher {
+        fn selector_match(&self, selector: &Selector, _entity: &Entity) -> bool {
+            self.selectors.contains(&selector.to_string().as_str())
+        }
+
+        fn scope_match(&self, scope: &str, _entity: &Entity) -> bool {
+            scope == self.scope
+        }
+    }
+
+    /// Apply `list` for each of a few matcher states, returning the results as strings.
+    fn apply_all(list: &StylePropList) -> Vec<String> {
+        let matchers = [
+            NamedMatcher {
+                selectors: &[],
+                scope: "",
+            },
+            NamedMatcher {
+                selectors: &[":hover"],
+                scope: "dark",
+            },
+            NamedMatcher {
+                selectors: &[":hover", ":focus"],
+                scope: "light",
+            },
+        ];
+        matchers
+            .iter()
+            .map(|matcher| {
+                let mut computed = ComputedStyle::default();
+                list.apply_to(&mut computed, matcher, &Entity::PLACEHOLDER);
+                format!("{:?}", computed)
+            })
+            .collect()
+    }
+
+    /// Concatenate the layers of `list` into a single one.
+    fn flattened(list: &StylePropList) -> StylePropList {
+        let (props, selectors, scopes) = list.clone().into_raw();
+        StylePropList::from_raw(props, selectors).with_scopes(scopes)
+    }
+
+    #[test]
+    fn test_builder_combinators() {
+        let build = |compact: bool, accent: Option<Color>| {
+            let mut builder = StyleBuilder::default();
+            builder
+                .width(10.)
+                .padding(8.)
+                .when(compact, |ss| ss.padding(2.).width(5.))
+                .when_some(accent, |ss, color| ss.background_color(color))
+                .for_each(1..=3, |ss, i| ss.height(i as f32 * 10.))
+                .extend([StyleProp::Width(ui::Val::Px(20.))])
+                .selector(":hover", |ss| ss.when(compact, |ss| ss.width(30.)));
+            builder.build()
+        };
+        let keys =
+            |list: &StylePropList| list.props().iter().map(StyleProp::key).collect::<Vec<_>>();
+
+        let list = build(false, None);
+        assert_eq!(
+            keys(&list),
+            ["width", "padding", "height", "height", "height", "width"]
+        );
+        assert!(list.selectors()[0].1.is_empty());
+        let list = build(true, Some(Color::RED));
+        assert_eq!(
+            keys(&list),
+            [
+                "width",
+                "padding",
+                "padding",
+                "width",
+                "background_color",
+                "height",
+                "height",
+                "height",
+                "width"
+            ]
+        );
+
+        // Later properties override earlier ones.
+        let mut computed = ComputedStyle::default();
+        list.apply_to(
+            &mut computed,
+            &NamedMatcher::default(),
+            &Entity::PLACEHOLDER,
+        );
+        assert_eq!(computed.style.width, ui::Val::Px(20.));
+        assert_eq!(computed.style.height, ui::Val::Px(30.));
+        assert_eq!(computed.style.padding, ui::UiRect::all(ui::Val::Px(2.)));
+        assert_eq!(computed.background_color, Some(Color::RED));
+        let hovered = NamedMatcher {
+            selectors: &[":hover"],
+            ..default()
+        };
+        let mut computed = ComputedStyle::default();
+        list.apply_to(&mut computed, &hovered, &Entity::PLACEHOLDER);
+        assert_eq!(computed.style.width, ui::Val::Px(30.));
+
+        // Building leaves the builder empty.
+        let mut builder = StyleBuilder::default();
+        builder.width(10.);
+        assert_eq!(builder.build().props().len(), 1);
+        assert!(builder.build().props().is_empty());
+    }
+
+    #[test]
+    fn test_derived_lists_share_base() {
+        let base = StylePropList::build(|ss| {
+            for i in 0..200 {
+                ss.width(i as f32);
             }
+            ss
+        });
+        let derived: Vec<_> = (0..100)
+            .map(|i| {
+                base.clone()
+                    .merge(StylePropList::build(|ss| ss.height(i as f32)))
+            })
+            .collect();
+        assert_eq!(Arc::strong_count(&base.layers[0]), 101);
+        for (i, list) in derived.iter().enumerate() {
+            assert_eq!(list.layers.len(), 2);
+            assert!(Arc::ptr_eq(&list.layers[0], &base.layers[0]));
+            let mut computed = ComputedStyle::default();
+            list.apply_to(
+                &mut computed,
+                &NamedMatcher::default(),
+                &Entity::PLACEHOLDER,
+            );
+            assert_eq!(computed.style.width, ui::Val::Px(199.));
+            assert_eq!(computed.style.height, ui::Val::Px(i as f32));
+            // Applying the list doesn't flatten it.
+            assert!(list.flat.get().is_none());
+        }
+    }
+
+    /// Matches the selectors whose text is in the list.
+    struct ListMatcher(Vec<String>);
+
+    impl SelectorMatcher for ListMatcher {
+        fn selector_match(&self, selector: &Selector, _entity: &Entity) -> bool {
+            self.0.contains(&selector.to_string())
+        }
+
+        fn scope_match(&self, _scope: &str, _entity: &Entity) -> bool {
+            false
+        }
+    }
+
+    #[test]
+    fn test_equal_selectors_apply_in_declaration_order() {
+        // Each entry sets the width to its own number. Every third one doesn't match.
+        let selector = |i: usize| format!(".c{} > &", i);
+        let matches = |i: usize| i % 3 != 1;
+        let matcher = ListMatcher((0..12).filter(|i| matches(*i)).map(selector).collect());
+        let entry = |i: usize| -> SelectorEntry {
+            (
+                Box::new(selector(i).parse().unwrap()),
+                vec![StyleProp::Width(ui::Val::Px(i as f32))],
+            )
+        };
+
+        let mut order: Vec<usize> = (0..12).collect();
+        let mut seed = 0x2545_f491u32;
+        for _ in 0..50 {
+            // Shuffle with a fixed seed, so that any failure can be reproduced.
+            for i in (1..order.len()).rev() {
+                seed = seed.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+                order.swap(i, (seed >> 16) as usize % (i + 1));
+            }
+            let expected = *order.iter().rev().find(|i| matches(**i)).unwrap();
+
+            let src = order
+                .iter()
+                .map(|i| format!("{} {{ width: {}px; }}", selector(*i), i))
+                .collect::<Vec<_>>()
+                .join("\n");
+            let (_, parsed) = crate::parse_stylesheet(&format!("A {{ {} }}", src))
+                .unwrap()
+                .pop()
+                .unwrap();
+            let raw =
+                StylePropList::from_raw(Vec::new(), order.iter().map(|i| entry(*i)).collect());
+            let (first, second) = order.split_at(5);
+            let layered =
+                StylePropList::from_raw(Vec::new(), first.iter().map(|i| entry(*i)).collect())
+                    .merge(StylePropList::from_raw(
+                        Vec::new(),
+                        second.iter().map(|i| entry(*i)).collect(),
+                    ));
+
+            for list in [&parsed, &raw, &layered] {
+                for _ in 0..2 {
+                    let mut computed = ComputedStyle::default();
+                    list.apply_to(&mut computed, &matcher, &Entity::PLACEHOLDER);
+                    assert_eq!(
+                        computed.style.width,
+                        ui::Val::Px(expected as f32),
+                        "order {:?}",
+                        order
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_layered_matches_flattened() {
+        let base = StylePropList::build(|ss| {
+            ss.width(10.)
+                .height(10.)
+                .selector(":hover", |ss| ss.width(20.).z_index(1))
+                .scope("dark", |ss| ss.height(30.))
+        });
+        let button = base.clone().merge(StylePropList::build(|ss| {
+            ss.width(15.)
+                .selector(":focus", |ss| ss.height(40.))
+                .scope("light", |ss| ss.width(50.))
+        }));
+        let primary = button.clone().merge(StylePropList::build(|ss| {
+            ss.z_index(2).selector(":hover", |ss| ss.height(60.))
+        }));
+        let mut extended = StylePropList::new();
+        extended.extend(primary.clone());
+        extended.extend(base.clone());
+
+        for list in [&base, &button, &primary, &extended] {
+            let flat = flattened(list);
+            assert_eq!(apply_all(list), apply_all(&flat));
+            assert_eq!(
+                format!("{:?}", list.all_props().collect::<Vec<_>>()),
+                format!("{:?}", flat.all_props().collect::<Vec<_>>())
+            );
+            assert_eq!(format!("{:?}", list.props()), format!("{:?}", flat.props()));
+            assert_eq!(list.selectors().len(), flat.selectors().len());
+            assert_eq!(list.scopes().len(), flat.scopes().len());
+            assert_eq!(list.depth(), flat.depth());
+            assert_eq!(list.uses_scopes(), flat.uses_scopes());
         }
+        assert_eq!(primary.layers.len(), 3);
+        assert_eq!(Arc::strong_count(&base.layers[0]), 5);
     }
 }
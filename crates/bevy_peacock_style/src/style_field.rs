@@ -0,0 +1,266 @@
+use std::sync::OnceLock;
+
+use bevy::{ui, utils::HashMap};
+
+use crate::StyleProp;
+
+/// The [`StyleProp`] variant which sets a field: the one with the same name as the field,
+/// unless another is given.
+macro_rules! field_prop {
+    ($variant:ident) => {
+        StyleProp::$variant
+    };
+    ($variant:ident, $prop:ident) => {
+        StyleProp::$prop
+    };
+}
+
+macro_rules! style_fields {
+    ($($variant:ident $(($prop:ident))?: $field:ident $(.$part:ident)? => [$($key:literal),+]),* $(,)?) => {
+        /// A field of the `Style` component, or a side or axis of one, which style properties
+        /// can set. Peacock only writes the fields which an element's styles set, so that other
+        /// systems can own the rest.
+        #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+        #[allow(missing_docs)]
+        pub enum StyleField {
+            $($variant),*
+        }
+
+        impl StyleField {
+            /// Every field, in declaration order.
+            pub const ALL: &'static [StyleField] = &[$(StyleField::$variant),*];
+
+            /// The keys of the properties which set the field, such as `"margin_left"` and
+            /// `"margin"` for the left margin.
+            pub fn keys(self) -> &'static [&'static str] {
+                match self {
+                    $(StyleField::$variant => &[$($key),+]),*
+                }
+            }
+
+            /// True if the field has the same value in both styles.
+            pub fn eq(self, a: &ui::Style, b: &ui::Style) -> bool {
+                match self {
+                    $(StyleField::$variant => a.$field$(.$part)? == b.$field$(.$part)?),*
+                }
+            }
+
+            /// Copy the field from one style to another.
+            pub fn copy(self, from: &ui::Style, to: &mut ui::Style) {
+                match self {
+                    $(StyleField::$variant => to.$field$(.$part)?.clone_from(&from.$field$(.$part)?)),*
+                }
+            }
+
+            /// The property which sets the field to its value in `style`, such as
+            /// `margin_left: 4px`, for showing a computed style in stylesheet syntax.
+            pub fn prop(self, style: &ui::Style) -> StyleProp {
+                match self {
+                    $(StyleField::$variant => {
+                        field_prop!($variant $(, $prop)?)(style.$field$(.$part)?.clone())
+                    }),*
+                }
+            }
+        }
+    };
+}
+
+style_fields!(
+    Display: display => ["display"],
+    PositionType(Position): position_type => ["position_type"],
+    OverflowX: overflow.x => ["overflow_x", "overflow"],
+    OverflowY: overflow.y => ["overflow_y", "overflow"],
+    Direction: direction => ["direction"],
+    Left: left => ["left"],
+    Right: right => ["right"],
+    Top: top => ["top"],
+    Bottom: bottom => ["bottom"],
+    Width: width => ["width"],
+    Height: height => ["height"],
+    MinWidth: min_width => ["min_width"],
+    MinHeight: min_height => ["min_height"],
+    MaxWidth: max_width => ["max_width"],
+    MaxHeight: max_height => ["max_height"],
+    AspectRatio: aspect_ratio => ["aspect_ratio"],
+    MarginLeft: margin.left => ["margin_left", "margin"],
+    MarginRight: margin.right => ["margin_right", "margin"],
+    MarginTop: margin.top => ["margin_top", "margin"],
+    MarginBottom: margin.bottom => ["margin_bottom", "margin"],
+    PaddingLeft: padding.left => ["padding_left", "padding"],
+    PaddingRight: padding.right => ["padding_right", "padding"],
+    PaddingTop: padding.top => ["padding_top", "padding"],
+    PaddingBottom: padding.bottom => ["padding_bottom", "padding"],
+    BorderLeft: border.left => ["border_left", "border"],
+    BorderRight: border.right => ["border_right", "border"],
+    BorderTop: border.top => ["border_top", "border"],
+    BorderBottom: border.bottom => ["border_bottom", "border"],
+    FlexDirection: flex_direction => ["flex_direction"],
+    FlexWrap: flex_wrap => ["flex_wrap"],
+    FlexGrow: flex_grow => ["flex_grow"],
+    FlexShrink: flex_shrink => ["flex_shrink"],
+    FlexBasis: flex_basis => ["flex_basis"],
+    RowGap: row_gap => ["row_gap", "gap"],
+    ColumnGap: column_gap => ["column_gap", "gap"],
+    AlignItems: align_items => ["align_items"],
+    AlignSelf: align_self => ["align_self"],
+    AlignContent: align_content => ["align_content"],
+    JustifyItems: justify_items => ["justify_items"],
+    JustifySelf: justify_self => ["justify_self"],
+    JustifyContent: justify_content => ["justify_content"],
+    GridAutoFlow: grid_auto_flow => ["grid_auto_flow"],
+    GridTemplateRows: grid_template_rows => ["grid_template_rows"],
+    GridTemplateColumns: grid_template_columns => ["grid_template_columns"],
+    GridAutoRows: grid_auto_rows => ["grid_auto_rows"],
+    GridAutoColumns: grid_auto_columns => ["grid_auto_columns"],
+    GridRow: grid_row => ["grid_row", "grid_row_start", "grid_row_span", "grid_row_end"],
+    GridColumn: grid_column => [
+        "grid_column",
+        "grid_column_start",
+        "grid_column_span",
+        "grid_column_end"
+    ],
+);
+
+/// A set of [`StyleField`]s, stored as a bitmask.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct StyleFieldSet(u64);
+
+impl StyleFieldSet {
+    /// The fields set by the property named `key`, such as the four sides of the margin for
+    /// `"margin"`.
+    pub fn for_key(key: &str) -> Self {
+        static BY_KEY: OnceLock<HashMap<&'static str, StyleFieldSet>> = OnceLock::new();
+        let by_key = BY_KEY.get_or_init(|| {
+            let mut by_key: HashMap<&'static str, StyleFieldSet> = HashMap::new();
+            for field in StyleField::ALL {
+                for key in field.keys() {
+                    by_key.entry(key).or_default().insert(*field);
+                }
+            }
+            by_key
+        });
+        by_key.get(key).copied().unwrap_or_default()
+    }
+
+    /// Add a field to the set.
+    pub fn insert(&mut self, field: StyleField) {
+        self.0 |= Self::bit(field);
+    }
+
+    /// True if the set contains the given field.
+    pub fn contains(&self, field: StyleField) -> bool {
+        self.0 & Self::bit(field) != 0
+    }
+
+    /// True if the set is empty.
+    pub fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    /// Iterate over the fields in the set.
+    pub fn iter(&self) -> impl Iterator<Item = StyleField> + '_ {
+        StyleField::ALL
+            .iter()
+            .copied()
+            .filter(|field| self.contains(*field))
+    }
+
+    /// True if every field in the set has the same value in both styles.
+    pub fn eq(&self, a: &ui::Style, b: &ui::Style) -> bool {
+        self.iter().all(|field| field.eq(a, b))
+    }
+
+    /// Copy the fields in the set from one style to another, leaving the others unchanged.
+    pub fn copy(&self, from: &ui::Style, to: &mut ui::Style) {
+        self.iter().for_each(|field| field.copy(from, to));
+    }
+
+    fn bit(field: StyleField) -> u64 {
+        1 << field as u64
+    }
+}
+
+impl From<StyleField> for StyleFieldSet {
+    fn from(field: StyleField) -> Self {
+        Self(Self::bit(field))
+    }
+}
+
+impl FromIterator<StyleField> for StyleFieldSet {
+    fn from_iter<I: IntoIterator<Item = StyleField>>(iter: I) -> Self {
+        let mut set = Self::default();
+        iter.into_iter().for_each(|field| set.insert(field));
+        set
+    }
+}
+
+impl std::ops::BitOr for StyleFieldSet {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+impl std::ops::BitOrAssign for StyleFieldSet {
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.0 |= rhs.0;
+    }
+}
+
+impl std::ops::Sub for StyleFieldSet {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self {
+        Self(self.0 & !rhs.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_style_field_set() {
+        let margin = StyleFieldSet::for_key("margin");
+        assert_eq!(
+            margin.iter().collect::<Vec<_>>(),
+            [
+                StyleField::MarginLeft,
+                StyleField::MarginRight,
+                StyleField::MarginTop,
+                StyleField::MarginBottom
+            ]
+        );
+        assert_eq!(
+            StyleFieldSet::for_key("grid_row_span"),
+            StyleField::GridRow.into()
+        );
+        assert!(StyleFieldSet::for_key("background_color").is_empty());
+
+        let fields = (margin - StyleField::MarginTop.into()) | StyleField::Width.into();
+        let from = ui::Style {
+            width: ui::Val::Px(10.),
+            height: ui::Val::Px(20.),
+            margin: ui::UiRect::all(ui::Val::Px(4.)),
+            ..Default::default()
+        };
+        let mut to = ui::Style::default();
+        assert!(!fields.eq(&from, &to));
+        fields.copy(&from, &mut to);
+        assert!(fields.eq(&from, &to));
+        assert_eq!(to.width, ui::Val::Px(10.));
+        assert_eq!(to.height, ui::Val::Auto);
+        assert_eq!(to.margin.left, ui::Val::Px(4.));
+        assert_eq!(to.margin.top, ui::Val::Px(0.));
+
+        // Each field is shown as the property which sets it alone.
+        for field in StyleField::ALL {
+            assert_eq!(field.prop(&from).key(), field.keys()[0]);
+        }
+        assert_eq!(
+            StyleField::MarginLeft.prop(&from).to_string(),
+            "margin_left: 4px"
+        );
+    }
+}
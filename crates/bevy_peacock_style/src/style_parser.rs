@@ -1,18 +1,29 @@
-use bevy::{asset::AssetPath, render::color::Color, ui};
+use bevy::{asset::AssetPath, math::Vec3, render::color::Color, text::TextAlignment, ui};
 use winnow::{
-    ascii::{escaped_transform, multispace0},
-    ascii::{float, space1},
-    combinator::{alt, cut_err, delimited, eof, opt, preceded, repeat, separated, terminated},
-    error::{
-        ContextError, ErrMode, ErrorKind, FromExternalError, ParseError, StrContext,
-        StrContextValue,
+    ascii::{escaped_transform, float},
+    combinator::{
+        alt, cut_err, delimited, eof, opt, peek, preceded, repeat, separated, terminated,
     },
-    stream::AsChar,
+    error::{ContextError, ErrMode, ErrorKind, FromExternalError, StrContext, StrContextValue},
+    stream::{AsChar, Offset},
     token::{none_of, one_of, take_while},
     PResult, Parser,
 };
 
-use crate::{selector_parser, style::SelectorEntry, StyleProp, StylePropList};
+use crate::{
+    calc::{CalcLength, CalcProperty},
+    color_names::parse_color_name,
+    css::Css,
+    error::StylesheetError,
+    rem::{rem_property, Length, DEFAULT_ROOT_FONT_SIZE},
+    selector::Selector,
+    selector_parser,
+    span::{LineIndex, SelectorSpans, SpanTable},
+    style::{ScopeEntry, SelectorEntry},
+    token::token_property,
+    AnimateIn, StyleProp, StylePropList, TextOverflow, Timing, TokenValue, Transition,
+    TransitionProperty,
+};
 
 #[derive(Debug, PartialEq, Eq)]
 #[allow(clippy::enum_variant_names)]
@@ -20,6 +31,19 @@ pub(crate) enum StyleParsingError {
     InvalidPropertyName(String),
     InvalidPropertyType(String),
     InvalidPropertyValue(String),
+    DuplicateRule {
+        name: String,
+        first: usize,
+        second: usize,
+    },
+    /// A scope entry within a block which makes its contents conditional, such as `@media`.
+    ScopeInBlock(&'static str),
+    MissingTransitionProperty(&'static str),
+    /// A selector with `&` in a term other than the last, which always matches the element
+    /// being styled.
+    MisplacedCurrent(String),
+    /// A `#` color which doesn't have 3, 4, 6 or 8 hex digits.
+    InvalidHexColor(String),
 }
 
 impl std::fmt::Display for StyleParsingError {
@@ -34,6 +58,34 @@ impl std::fmt::Display for StyleParsingError {
             StyleParsingError::InvalidPropertyValue(val) => {
                 write!(f, "invalid property value: {}", val)
             }
+            StyleParsingError::DuplicateRule {
+                name,
+                first,
+                second,
+            } => write!(
+                f,
+                "duplicate rule '{}' at byte offset {} (first defined at byte offset {})",
+                name, second, first
+            ),
+            StyleParsingError::ScopeInBlock(block) => {
+                write!(f, "@scope blocks can't be used within {} blocks", block)
+            }
+            StyleParsingError::MissingTransitionProperty(longhand) => write!(
+                f,
+                "'{}' has no effect without a transition_property in the same block",
+                longhand
+            ),
+            StyleParsingError::MisplacedCurrent(selector) => write!(
+                f,
+                "'&' can only appear in the last term of a selector, which is the element being \
+                 styled: '{}'",
+                selector
+            ),
+            StyleParsingError::InvalidHexColor(literal) => write!(
+                f,
+                "invalid color '{}' (expected 3, 4, 6 or 8 hex digits)",
+                literal
+            ),
         }
     }
 }
@@ -45,14 +97,64 @@ enum PropValue<'s> {
     Number(f32),
     String(String),
     Length(ui::Val),
+    Angle(f32),
     List(Vec<PropValue<'s>>),
     Color(Color),
+    Function(&'s str, Vec<PropValue<'s>>),
+    Calc(CalcLength),
+    Token(&'s str),
+    Rem(f32),
+}
+
+#[derive(Debug)]
+enum StylePropOrSelector<'s> {
+    StyleProps(Vec<StyleProp>, &'s str),
+    TransitionLonghand(TransitionLonghand, &'s str),
+    Selector(SelectorEntry, SelectorSrc<'s>),
+    Scope(ScopeEntry, SelectorSrc<'s>),
+    /// The selector entries of a flag block, already made conditional on the flag.
+    Flag(Vec<(SelectorEntry, SelectorSrc<'s>)>),
 }
 
+/// The source text of a selector entry, and of each of its properties.
 #[derive(Debug)]
-enum StylePropOrSelector {
-    StyleProp(StyleProp),
-    Selector(SelectorEntry),
+struct SelectorSrc<'s> {
+    selector: &'s str,
+    props: Vec<&'s str>,
+}
+
+/// The source text of a rule and its parts, used to compute a [`SpanTable`].
+struct RuleSrc<'s> {
+    rule: &'s str,
+    props: Vec<&'s str>,
+    selectors: Vec<SelectorSrc<'s>>,
+    scopes: Vec<SelectorSrc<'s>>,
+}
+
+impl<'s> RuleSrc<'s> {
+    fn to_spans(&self, lines: &LineIndex) -> SpanTable {
+        let start = lines.src();
+        let span = |src: &str| {
+            let offset = src.offset_from(&start);
+            lines.span(offset, offset + src.len())
+        };
+        let entry_spans = |entries: &[SelectorSrc]| {
+            entries
+                .iter()
+                .map(|sel| SelectorSpans {
+                    selector: span(sel.selector),
+                    props: sel.props.iter().map(|src| span(src)).collect(),
+                })
+                .collect()
+        };
+        SpanTable {
+            path: None,
+            rule: span(self.rule),
+            props: self.props.iter().map(|src| span(src)).collect(),
+            selectors: entry_spans(&self.selectors),
+            scopes: entry_spans(&self.scopes),
+        }
+    }
 }
 
 impl<'s> PropValue<'s> {
@@ -62,8 +164,27 @@ impl<'s> PropValue<'s> {
             PropValue::Number(_) => "number".to_string(),
             PropValue::String(_) => "string".to_string(),
             PropValue::Length(_) => "length".to_string(),
+            PropValue::Angle(_) => "angle".to_string(),
             PropValue::List(_) => "list".to_string(),
             PropValue::Color(_) => "color".to_string(),
+            PropValue::Function(_, _) => "function".to_string(),
+            PropValue::Calc(_) => "calc expression".to_string(),
+            PropValue::Token(_) => "token".to_string(),
+            PropValue::Rem(_) => "rem length".to_string(),
+        }
+    }
+
+    /// Coerce to an angle in radians. Bare numbers are treated as radians.
+    fn coerce_angle(&self) -> Result<f32, StyleParsingError> {
+        match self {
+            PropValue::Angle(a) | PropValue::Number(a) => Ok(*a),
+            PropValue::Ident(id) => Err(StyleParsingError::InvalidPropertyValue(format!(
+                "\"{}\"",
+                id
+            ))),
+            _ => Err(StyleParsingError::InvalidPropertyType(
+                PropValue::type_name(self),
+            )),
         }
     }
 }
@@ -79,6 +200,33 @@ fn whitespace(input: &mut &str) -> PResult<()> {
     .parse_next(input)
 }
 
+/// Whitespace or comments separating the elements of a list, such as the lengths in
+/// `padding: 1px 2px`. Unlike [`whitespace`], at least one character is required.
+fn whitespace1(input: &mut &str) -> PResult<()> {
+    whitespace
+        .recognize()
+        .verify(|sep: &str| !sep.is_empty())
+        .void()
+        .parse_next(input)
+}
+
+/// A comma separating function arguments or transitions, with any whitespace around it.
+fn comma(input: &mut &str) -> PResult<()> {
+    (whitespace, ',', whitespace).void().parse_next(input)
+}
+
+/// The end of a function's arguments: an optional trailing comma, and the closing parenthesis.
+fn close_args(input: &mut &str) -> PResult<()> {
+    (whitespace, opt((',', whitespace)), ')')
+        .void()
+        .parse_next(input)
+}
+
+fn close_brace(input: &mut &str) -> PResult<char> {
+    '}'.context(StrContext::Expected(StrContextValue::CharLiteral('}')))
+        .parse_next(input)
+}
+
 fn prop_name<'s>(input: &mut &'s str) -> PResult<&'s str> {
     (
         one_of(AsChar::is_alpha),
@@ -98,6 +246,12 @@ fn ident<'s>(input: &mut &'s str) -> PResult<PropValue<'s>> {
         .parse_next(input)
 }
 
+fn ident_list<'s>(input: &mut &'s str) -> PResult<PropValue<'s>> {
+    separated(2..=4, ident, whitespace1)
+        .map(|vals: Vec<PropValue<'s>>| PropValue::List(vals))
+        .parse_next(input)
+}
+
 fn string_chars(input: &mut &str) -> PResult<String> {
     escaped_transform(
         none_of(['"', '\\']).recognize(),
@@ -114,13 +268,13 @@ fn string<'s>(input: &mut &'s str) -> PResult<PropValue<'s>> {
 }
 
 fn color_arg_sep(input: &mut &str) -> PResult<()> {
-    (multispace0, opt((',', multispace0)))
+    (whitespace, opt((',', whitespace)))
         .void()
         .parse_next(input)
 }
 
 fn alpha_sep(input: &mut &str) -> PResult<()> {
-    (multispace0, opt((one_of([',', '/']), multispace0)))
+    (whitespace, opt((one_of([',', '/']), whitespace)))
         .void()
         .parse_next(input)
 }
@@ -132,42 +286,54 @@ fn f32_arg(input: &mut &str) -> PResult<f32> {
 fn length<'s>(input: &mut &'s str) -> PResult<PropValue<'s>> {
     alt((
         "auto".map(|_| PropValue::Length(ui::Val::Auto)),
-        (f32_arg, alt(("%", "px", "vh", "vw", "vmin", "vmax"))).map(|(num, unit)| match unit {
-            "%" => PropValue::Length(ui::Val::Percent(num)),
-            "px" => PropValue::Length(ui::Val::Px(num)),
-            "vh" => PropValue::Length(ui::Val::Vh(num)),
-            "vw" => PropValue::Length(ui::Val::Vw(num)),
-            "vmin" => PropValue::Length(ui::Val::VMin(num)),
-            "vmax" => PropValue::Length(ui::Val::VMax(num)),
-            _ => unreachable!(),
-        }),
+        (f32_arg, alt(("%", "px", "vh", "vw", "vmin", "vmax", "rem"))).map(
+            |(num, unit)| match unit {
+                "%" => PropValue::Length(ui::Val::Percent(num)),
+                "px" => PropValue::Length(ui::Val::Px(num)),
+                "vh" => PropValue::Length(ui::Val::Vh(num)),
+                "vw" => PropValue::Length(ui::Val::Vw(num)),
+                "vmin" => PropValue::Length(ui::Val::VMin(num)),
+                "vmax" => PropValue::Length(ui::Val::VMax(num)),
+                "rem" => PropValue::Rem(num),
+                _ => unreachable!(),
+            },
+        ),
         f32_arg.map(PropValue::Number),
     ))
     .parse_next(input)
 }
 
+fn angle<'s>(input: &mut &'s str) -> PResult<PropValue<'s>> {
+    (f32_arg, alt(("deg", "rad")))
+        .map(|(num, unit)| match unit {
+            "deg" => PropValue::Angle(num.to_radians()),
+            "rad" => PropValue::Angle(num),
+            _ => unreachable!(),
+        })
+        .parse_next(input)
+}
+
 fn length_list<'s>(input: &mut &'s str) -> PResult<PropValue<'s>> {
-    separated(2..4, length, space1)
+    separated(2..=4, length, whitespace1)
         .map(|vals: Vec<PropValue<'s>>| PropValue::List(vals))
         .parse_next(input)
 }
 
 fn color_fn<'s>(input: &mut &'s str) -> PResult<PropValue<'s>> {
     (
-        alt(("rgb", "rgba", "rgb_linear", "rgba_linear", "hsl", "hsla")),
-        multispace0,
+        alt(("rgba_linear", "rgb_linear", "rgba", "rgb", "hsla", "hsl")),
+        whitespace,
         '(',
         cut_err((
-            multispace0,
+            whitespace,
             terminated(f32_arg, color_arg_sep),
             terminated(f32_arg, color_arg_sep),
             f32_arg,
             opt(preceded(alpha_sep, f32_arg)),
-            multispace0,
-            ')',
+            close_args,
         )),
     )
-        .map(|(f, _, _, (_, a1, a2, a3, a4, _, _))| match f {
+        .map(|(f, _, _, (_, a1, a2, a3, a4, _))| match f {
             "rgb" | "rgba" => {
                 if let Some(alpha) = a4 {
                     PropValue::Color(Color::rgba(a1, a2, a3, alpha))
@@ -194,23 +360,677 @@ fn color_fn<'s>(input: &mut &'s str) -> PResult<PropValue<'s>> {
         .parse_next(input)
 }
 
+/// A `#` color with 3, 4, 6 or 8 hex digits. The 3 and 4 digit forms are shorthands which
+/// repeat each digit, as in CSS, so `#abcd` is `#aabbccdd`.
+fn hex_color<'s>(input: &mut &'s str) -> PResult<PropValue<'s>> {
+    let start = *input;
+    // Take any letters which follow the digits too, so that they're reported as part of it.
+    let literal = ('#', take_while(0.., AsChar::is_alphanum))
+        .recognize()
+        .parse_next(input)?;
+    let digits = &literal[1..];
+    match digits.len() {
+        3 | 4 | 6 | 8 if digits.chars().all(|c| c.is_ascii_hexdigit()) => {
+            Ok(PropValue::Color(Color::hex(digits).unwrap()))
+        }
+        _ => {
+            *input = start;
+            Err(ErrMode::from_external_error(
+                input,
+                ErrorKind::Verify,
+                StyleParsingError::InvalidHexColor(literal.to_owned()),
+            )
+            .cut())
+        }
+    }
+}
+
 fn color<'s>(input: &mut &'s str) -> PResult<PropValue<'s>> {
+    alt((hex_color, color_fn)).parse_next(input)
+}
+
+fn function_call<'s>(input: &mut &'s str) -> PResult<PropValue<'s>> {
+    (
+        prop_name,
+        '(',
+        whitespace,
+        alt((
+            terminated(separated(1.., length, comma), close_args),
+            ')'.map(|_| Vec::new()),
+        )),
+    )
+        .map(|(name, _, _, args)| PropValue::Function(name, args))
+        .parse_next(input)
+}
+
+/// A single term of a `calc()` expression. Bare numbers are treated as pixels.
+fn calc_term(input: &mut &str) -> PResult<CalcLength> {
+    (f32_arg, opt(alt(("%", "px"))))
+        .map(|(num, unit)| match unit {
+            Some("%") => CalcLength::new(num, 0.),
+            _ => CalcLength::new(0., num),
+        })
+        .parse_next(input)
+}
+
+/// A `calc()` expression: a sum or difference of pixel and percentage lengths, such as
+/// `calc(100% - 32px)`.
+fn calc<'s>(input: &mut &'s str) -> PResult<PropValue<'s>> {
+    preceded(
+        ("calc", whitespace, '('),
+        cut_err((
+            whitespace,
+            calc_term,
+            repeat(
+                ..,
+                (whitespace, one_of(['+', '-']), whitespace, calc_term).map(|(_, op, _, term)| {
+                    if op == '-' {
+                        -term
+                    } else {
+                        term
+                    }
+                }),
+            )
+            .fold(CalcLength::default, |sum, term| sum + term),
+            whitespace,
+            ')'.context(StrContext::Expected(StrContextValue::Description(
+                "px or % length",
+            ))),
+        )),
+    )
+    .map(|(_, first, rest, _, _)| PropValue::Calc(first + rest))
+    .parse_next(input)
+}
+
+/// A reference to a theme token, such as `token(surface)`, which is resolved whenever styles
+/// are computed.
+fn token<'s>(input: &mut &'s str) -> PResult<PropValue<'s>> {
+    delimited(
+        ("token", whitespace, '(', whitespace),
+        cut_err(
+            take_while(1.., (AsChar::is_alphanum, '_', '-')).context(StrContext::Expected(
+                StrContextValue::Description("token name"),
+            )),
+        ),
+        cut_err(close_args),
+    )
+    .map(PropValue::Token)
+    .parse_next(input)
+}
+
+/// Create a layout length property, which may also be set to a `calc()` expression.
+fn length_prop(
+    value: &PropValue,
+    property: CalcProperty,
+    prop: fn(ui::Val) -> StyleProp,
+) -> Result<StyleProp, StyleParsingError> {
+    match value {
+        PropValue::Calc(length) => Ok(StyleProp::Calc(property, *length)),
+        _ => Ok(prop(value.coerce()?)),
+    }
+}
+
+/// A duration in seconds, with units of `s` or `ms`.
+fn time(input: &mut &str) -> PResult<f32> {
+    (f32_arg, alt(("ms", "s")))
+        .map(|(num, unit)| if unit == "ms" { num / 1000. } else { num })
+        .parse_next(input)
+}
+
+fn transition_property(input: &mut &str) -> PResult<TransitionProperty> {
+    prop_name
+        .try_map(|name| match name {
+            "transform" => Ok(TransitionProperty::Transform),
+            "scale" => Ok(TransitionProperty::Scale),
+            "rotation" => Ok(TransitionProperty::Rotation),
+            "translation" => Ok(TransitionProperty::Translation),
+            "background_color" => Ok(TransitionProperty::BackgroundColor),
+            "border_color" => Ok(TransitionProperty::BorderColor),
+            "left" => Ok(TransitionProperty::Left),
+            "top" => Ok(TransitionProperty::Top),
+            "bottom" => Ok(TransitionProperty::Bottom),
+            "right" => Ok(TransitionProperty::Right),
+            "height" => Ok(TransitionProperty::Height),
+            "width" => Ok(TransitionProperty::Width),
+            "border_left" => Ok(TransitionProperty::BorderLeft),
+            "border_top" => Ok(TransitionProperty::BorderTop),
+            "border_right" => Ok(TransitionProperty::BorderRight),
+            "border_bottom" => Ok(TransitionProperty::BorderBottom),
+            "padding_left" => Ok(TransitionProperty::PaddingLeft),
+            "padding_right" => Ok(TransitionProperty::PaddingRight),
+            "padding_top" => Ok(TransitionProperty::PaddingTop),
+            "padding_bottom" => Ok(TransitionProperty::PaddingBottom),
+            "margin_left" => Ok(TransitionProperty::MarginLeft),
+            "margin_right" => Ok(TransitionProperty::MarginRight),
+            "margin_top" => Ok(TransitionProperty::MarginTop),
+            "margin_bottom" => Ok(TransitionProperty::MarginBottom),
+            "row_gap" => Ok(TransitionProperty::RowGap),
+            "column_gap" => Ok(TransitionProperty::ColumnGap),
+            "outline_width" => Ok(TransitionProperty::OutlineWidth),
+            "outline_offset" => Ok(TransitionProperty::OutlineOffset),
+            "outline_color" => Ok(TransitionProperty::OutlineColor),
+            "font_size" => Ok(TransitionProperty::FontSize),
+            _ => Err(StyleParsingError::InvalidPropertyValue(format!(
+                "\"{}\" (expected an animatable property)",
+                name
+            ))),
+        })
+        .parse_next(input)
+}
+
+fn timing_fn(input: &mut &str) -> PResult<Timing> {
     alt((
-        ('#', take_while(1..8, AsChar::is_hex_digit))
-            .recognize()
-            .try_map(|s| Color::hex(s).map(PropValue::Color)),
-        color_fn,
+        "linear".value(Timing::Linear),
+        "ease_in_out".value(Timing::EaseInOut),
+        "ease_in".value(Timing::EaseIn),
+        "ease_out".value(Timing::EaseOut),
     ))
     .parse_next(input)
 }
 
+fn quantize(input: &mut &str) -> PResult<f32> {
+    delimited(
+        ("quantize", whitespace, '(', whitespace),
+        terminated(f32_arg, opt("px")),
+        close_args,
+    )
+    .parse_next(input)
+}
+
+enum TransitionOption {
+    Timing(Timing),
+    Delay(f32),
+    Quantum(f32),
+}
+
+/// A single transition: `<property> <duration> [<timing>] [<delay>] [quantize(<px>)]`.
+fn transition(input: &mut &str) -> PResult<Transition> {
+    let property = terminated(transition_property, whitespace1).parse_next(input)?;
+    transition_timing(property).parse_next(input)
+}
+
+/// The part of a transition which follows the property: its duration and options.
+fn transition_timing(property: TransitionProperty) -> impl FnMut(&mut &str) -> PResult<Transition> {
+    move |input: &mut &str| {
+        let duration = time.parse_next(input)?;
+        let options: Vec<TransitionOption> = repeat(
+            ..,
+            preceded(
+                whitespace1,
+                alt((
+                    quantize.map(TransitionOption::Quantum),
+                    timing_fn.map(TransitionOption::Timing),
+                    time.map(TransitionOption::Delay),
+                )),
+            ),
+        )
+        .parse_next(input)?;
+        let mut transition = Transition {
+            property,
+            duration,
+            ..Default::default()
+        };
+        for option in options {
+            match option {
+                TransitionOption::Timing(timing) => transition.timing = timing,
+                TransitionOption::Delay(delay) => transition.delay = delay,
+                TransitionOption::Quantum(quantum) => transition.quantum = Some(quantum),
+            }
+        }
+        Ok(transition)
+    }
+}
+
+/// A single entrance animation: `<property> <from> <duration> [<timing>] [<delay>]`, where
+/// `<from>` is a value of the property, such as `scale 0.8 0.2s ease_out`.
+fn animate_in(input: &mut &str) -> PResult<AnimateIn> {
+    let (property, name) = terminated(transition_property.with_recognized(), whitespace1)
+        .try_map(|(property, name)| match property {
+            TransitionProperty::Transform
+            | TransitionProperty::BackgroundColor
+            | TransitionProperty::BorderColor => {
+                Err(StyleParsingError::InvalidPropertyValue(format!(
+                    "\"{}\" (expected a property which can be animated in)",
+                    name
+                )))
+            }
+            _ => Ok((property, name)),
+        })
+        .parse_next(input)?;
+    // Each part of the value is followed by whitespace, which tells it apart from the duration.
+    let mut values: Vec<PropValue> =
+        repeat(1..=3, terminated(alt((color, angle, length)), whitespace1)).parse_next(input)?;
+    let value = match values.len() {
+        1 => values.remove(0),
+        _ => PropValue::List(values),
+    };
+    let from = create_prop(name, &value)
+        .map_err(|err| ErrMode::from_external_error(input, ErrorKind::Verify, err).cut())?;
+    let transition = transition_timing(property).parse_next(input)?;
+    Ok(AnimateIn { from, transition })
+}
+
+/// A comma-separated list, allowing a trailing comma. Unlike `separated`, an item which fails
+/// to parse after a comma is reported, rather than ending the list.
+fn comma_list<'s, T>(
+    mut item: impl Parser<&'s str, T, ContextError>,
+) -> impl FnMut(&mut &'s str) -> PResult<Vec<T>> {
+    move |input: &mut &'s str| {
+        let mut items = vec![item.parse_next(input)?];
+        while opt(comma).parse_next(input)?.is_some() {
+            if opt(peek(';')).parse_next(input)?.is_some() {
+                break;
+            }
+            items.push(cut_err(item.by_ref()).parse_next(input)?);
+        }
+        Ok(items)
+    }
+}
+
+fn transition_list(input: &mut &str) -> PResult<Vec<Transition>> {
+    comma_list(transition).parse_next(input)
+}
+
+/// A `transition_*` longhand declaration. The longhands of a block are merged into a single
+/// transition property when the block closes.
+#[derive(Debug)]
+enum TransitionLonghand {
+    Property(Vec<TransitionProperty>),
+    Duration(Vec<f32>),
+    Timing(Vec<Timing>),
+    Delay(Vec<f32>),
+}
+
+impl TransitionLonghand {
+    fn name(&self) -> &'static str {
+        match self {
+            TransitionLonghand::Property(_) => "transition_property",
+            TransitionLonghand::Duration(_) => "transition_duration",
+            TransitionLonghand::Timing(_) => "transition_timing_function",
+            TransitionLonghand::Delay(_) => "transition_delay",
+        }
+    }
+}
+
+/// A longhand declaration: `transition_property: width, height;`, `transition_duration: 0.2s;`,
+/// `transition_timing_function: ease_out;` or `transition_delay: 0.1s;`.
+fn transition_longhand(input: &mut &str) -> PResult<TransitionLonghand> {
+    let name = terminated(
+        alt((
+            "transition_property",
+            "transition_duration",
+            "transition_timing_function",
+            "transition_delay",
+        )),
+        (whitespace, ':', whitespace),
+    )
+    .parse_next(input)?;
+    let value = move |input: &mut &str| match name {
+        "transition_property" => comma_list(transition_property)
+            .map(TransitionLonghand::Property)
+            .parse_next(input),
+        "transition_duration" => comma_list(time)
+            .map(TransitionLonghand::Duration)
+            .parse_next(input),
+        "transition_timing_function" => comma_list(timing_fn)
+            .map(TransitionLonghand::Timing)
+            .parse_next(input),
+        _ => comma_list(time)
+            .map(TransitionLonghand::Delay)
+            .parse_next(input),
+    };
+    terminated(
+        cut_err(value.context(StrContext::Label("transition"))),
+        (
+            whitespace,
+            cut_err(
+                ';'.context(StrContext::Expected(StrContextValue::Description(
+                    "semicolon",
+                ))),
+            ),
+        ),
+    )
+    .parse_next(input)
+}
+
+/// A longhand declaration and its source text, not including trailing whitespace.
+fn transition_longhand_with_src<'s>(input: &mut &'s str) -> PResult<(TransitionLonghand, &'s str)> {
+    (transition_longhand.with_recognized(), whitespace)
+        .map(|(longhand, _)| longhand)
+        .parse_next(input)
+}
+
+/// The transitions described by the longhands of a block, starting from those of the last
+/// `transition` shorthand before them, if any.
+#[derive(Default)]
+struct TransitionLonghands {
+    properties: Option<Vec<TransitionProperty>>,
+    durations: Vec<f32>,
+    timings: Vec<Timing>,
+    delays: Vec<f32>,
+    quanta: Vec<Option<f32>>,
+}
+
+impl TransitionLonghands {
+    /// Start again from the transitions of a shorthand.
+    fn reset(&mut self, transitions: &[Transition]) {
+        *self = Self {
+            properties: Some(transitions.iter().map(|tr| tr.property).collect()),
+            durations: transitions.iter().map(|tr| tr.duration).collect(),
+            timings: transitions.iter().map(|tr| tr.timing).collect(),
+            delays: transitions.iter().map(|tr| tr.delay).collect(),
+            quanta: transitions.iter().map(|tr| tr.quantum).collect(),
+        };
+    }
+
+    /// Apply a longhand, replacing any earlier declaration of it.
+    fn set(&mut self, longhand: TransitionLonghand) {
+        match longhand {
+            TransitionLonghand::Property(properties) => self.properties = Some(properties),
+            TransitionLonghand::Duration(durations) => self.durations = durations,
+            TransitionLonghand::Timing(timings) => self.timings = timings,
+            TransitionLonghand::Delay(delays) => self.delays = delays,
+        }
+    }
+
+    /// One transition per property, with shorter lists repeated to match, as in CSS.
+    fn transitions(&self) -> Option<Vec<Transition>> {
+        fn nth<T: Copy>(values: &[T], index: usize) -> Option<T> {
+            (!values.is_empty()).then(|| values[index % values.len()])
+        }
+        let defaults = Transition::default();
+        let properties = self.properties.as_ref()?;
+        Some(
+            properties
+                .iter()
+                .enumerate()
+                .map(|(index, &property)| Transition {
+                    property,
+                    duration: nth(&self.durations, index).unwrap_or(defaults.duration),
+                    timing: nth(&self.timings, index).unwrap_or(defaults.timing),
+                    delay: nth(&self.delays, index).unwrap_or(defaults.delay),
+                    quantum: self.quanta.get(index).copied().flatten(),
+                })
+                .collect(),
+        )
+    }
+}
+
+/// Merge the transition longhands among the items of a block into a single transition
+/// property, which follows the block's other properties. Longhands modify the transitions of
+/// an earlier `transition` shorthand, which the merged property replaces, and a later
+/// shorthand replaces them. `start` is the
+/// input at the start of the block's items, used to report errors at the offending longhand.
+fn merge_transition_longhands<'s>(
+    input: &mut &'s str,
+    start: &'s str,
+    items: Vec<StylePropOrSelector<'s>>,
+) -> PResult<Vec<StylePropOrSelector<'s>>> {
+    let mut longhands = TransitionLonghands::default();
+    // The first and last longhands since the last shorthand, and the source of the last
+    // `transition_property`.
+    let mut first: Option<(&'static str, &'s str)> = None;
+    let mut last: Option<&'s str> = None;
+    let mut property_src: Option<&'s str> = None;
+    // The index of the last shorthand, which is replaced if longhands follow it.
+    let mut shorthand: Option<usize> = None;
+    let mut merged = Vec::with_capacity(items.len());
+    for item in items {
+        match item {
+            StylePropOrSelector::TransitionLonghand(longhand, src) => {
+                first.get_or_insert((longhand.name(), src));
+                last = Some(src);
+                if matches!(longhand, TransitionLonghand::Property(_)) {
+                    property_src = Some(src);
+                }
+                longhands.set(longhand);
+            }
+            StylePropOrSelector::StyleProps(props, src) => {
+                for prop in &props {
+                    if let StyleProp::Transition(transitions) = prop {
+                        longhands.reset(transitions);
+                        (first, last, property_src) = (None, None, None);
+                        shorthand = Some(merged.len());
+                    }
+                }
+                merged.push(StylePropOrSelector::StyleProps(props, src));
+            }
+            item => merged.push(item),
+        }
+    }
+    let Some((name, first_src)) = first else {
+        return Ok(merged);
+    };
+    let Some(transitions) = longhands.transitions() else {
+        *input = &start[first_src.offset_from(&start)..];
+        return Err(ErrMode::from_external_error(
+            input,
+            ErrorKind::Verify,
+            StyleParsingError::MissingTransitionProperty(name),
+        )
+        .cut());
+    };
+    if let Some(index) = shorthand {
+        merged.remove(index);
+    }
+    merged.push(StylePropOrSelector::StyleProps(
+        vec![StyleProp::Transition(transitions)],
+        property_src.or(last).unwrap_or(first_src),
+    ));
+    Ok(merged)
+}
+
+fn non_negative(value: f32) -> Result<f32, StyleParsingError> {
+    if value >= 0. {
+        Ok(value)
+    } else {
+        Err(StyleParsingError::InvalidPropertyValue(format!(
+            "{} (expected a non-negative number)",
+            value
+        )))
+    }
+}
+
+/// Create the width and height properties set by a size shorthand, from either a single length
+/// for both, or two lengths.
+fn size_props(
+    value: &PropValue,
+    width: (CalcProperty, fn(ui::Val) -> StyleProp),
+    height: (CalcProperty, fn(ui::Val) -> StyleProp),
+) -> Result<Vec<StyleProp>, StyleParsingError> {
+    let (width_value, height_value) = match value {
+        PropValue::List(vals) => match vals.as_slice() {
+            [width, height] => (width, height),
+            _ => {
+                return Err(StyleParsingError::InvalidPropertyValue(format!(
+                    "{} values (expected 1 or 2)",
+                    vals.len()
+                )))
+            }
+        },
+        _ => (value, value),
+    };
+    Ok(vec![
+        length_prop(width_value, width.0, width.1)?,
+        length_prop(height_value, height.0, height.1)?,
+    ])
+}
+
+/// Returns the first negative length of a property which can't meaningfully be negative, such
+/// as a size or padding. Bevy would clamp these, which is rarely what was intended.
+fn negative_length(prop: &StyleProp) -> Option<ui::Val> {
+    let is_negative = |val: &ui::Val| match *val {
+        ui::Val::Auto => false,
+        ui::Val::Px(n)
+        | ui::Val::Percent(n)
+        | ui::Val::Vw(n)
+        | ui::Val::Vh(n)
+        | ui::Val::VMin(n)
+        | ui::Val::VMax(n) => n < 0.,
+    };
+    match prop {
+        StyleProp::Width(val)
+        | StyleProp::Height(val)
+        | StyleProp::MinWidth(val)
+        | StyleProp::MinHeight(val)
+        | StyleProp::MaxWidth(val)
+        | StyleProp::MaxHeight(val)
+        | StyleProp::PaddingLeft(val)
+        | StyleProp::PaddingRight(val)
+        | StyleProp::PaddingTop(val)
+        | StyleProp::PaddingBottom(val)
+        | StyleProp::BorderLeft(val)
+        | StyleProp::BorderRight(val)
+        | StyleProp::BorderTop(val)
+        | StyleProp::BorderBottom(val) => Some(*val).filter(is_negative),
+        StyleProp::Padding(rect) | StyleProp::Border(rect) => {
+            [rect.left, rect.right, rect.top, rect.bottom]
+                .into_iter()
+                .find(is_negative)
+        }
+        StyleProp::FontSize(size) => Some(ui::Val::Px(*size)).filter(is_negative),
+        _ => None,
+    }
+}
+
+/// Create the properties set by a property declaration. Shorthands set more than one.
+fn create_props(name: &str, value: &PropValue) -> Result<Vec<StyleProp>, StyleParsingError> {
+    if let PropValue::Token(token) = value {
+        if let Some(property) = token_property(name) {
+            return Ok(vec![StyleProp::Token(property, token.to_string())]);
+        }
+        // Report unknown properties as such, rather than as unable to take a token.
+        let unknown = matches!(
+            create_props_unchecked(name, &PropValue::Number(0.)),
+            Err(StyleParsingError::InvalidPropertyName(_))
+        );
+        return match unknown {
+            true => Err(StyleParsingError::InvalidPropertyName(name.to_owned())),
+            false => Err(StyleParsingError::InvalidPropertyValue(format!(
+                "token({}) ('{}' can't be set to a theme token)",
+                token, name
+            ))),
+        };
+    }
+    if let Some(lengths) = rem_lengths(value)? {
+        let Some(property) = rem_property(name) else {
+            return Err(StyleParsingError::InvalidPropertyValue(format!(
+                "rem length ('{}' can't be set to rem lengths)",
+                name
+            )));
+        };
+        // Check the value now, so that errors are reported when the stylesheet is parsed.
+        rem_props(property, &lengths, DEFAULT_ROOT_FONT_SIZE)?;
+        return Ok(vec![StyleProp::Rem(property, lengths)]);
+    }
+    let props = create_props_unchecked(name, value)?;
+    if let Some(val) = props.iter().find_map(negative_length) {
+        return Err(StyleParsingError::InvalidPropertyValue(format!(
+            "{} (expected a non-negative length for '{}')",
+            Css(&val),
+            name
+        )));
+    }
+    // A scale of zero or less would collapse or flip the whole UI tree.
+    if let Some(StyleProp::UiScale(scale)) = props.first() {
+        if !(scale.is_finite() && *scale > 0.) {
+            return Err(StyleParsingError::InvalidPropertyValue(format!(
+                "{} (expected a positive number for 'ui_scale')",
+                scale
+            )));
+        }
+    }
+    Ok(props)
+}
+
+/// The lengths of a value which contains any `rem` lengths, such as `1.5rem 8px`, or `None` if
+/// it contains none.
+fn rem_lengths(value: &PropValue) -> Result<Option<Vec<Length>>, StyleParsingError> {
+    let values = match value {
+        PropValue::List(vals) => vals.as_slice(),
+        _ => std::slice::from_ref(value),
+    };
+    if !values.iter().any(|val| matches!(val, PropValue::Rem(_))) {
+        return Ok(None);
+    }
+    values
+        .iter()
+        .map(|val| match val {
+            PropValue::Rem(rem) => Ok(Length::Rem(*rem)),
+            _ => Ok(Length::Val(val.coerce()?)),
+        })
+        .collect::<Result<_, _>>()
+        .map(Some)
+}
+
+/// Create the properties set by `property` when it's set to `lengths`, resolving `rem` lengths
+/// against the root font size.
+pub(crate) fn rem_props(
+    property: &str,
+    lengths: &[Length],
+    root_font_size: f32,
+) -> Result<Vec<StyleProp>, StyleParsingError> {
+    // Pixels are passed as plain numbers, which every length property accepts, including
+    // `font_size`.
+    let value = |length: &Length| match length.resolve(root_font_size) {
+        ui::Val::Px(px) => PropValue::Number(px),
+        val => PropValue::Length(val),
+    };
+    let value = match lengths {
+        [length] => value(length),
+        _ => PropValue::List(lengths.iter().map(value).collect()),
+    };
+    create_props(property, &value)
+}
+
+/// Create the properties set by `property` when it's set to the value of a theme token.
+pub(crate) fn token_props(property: &str, value: TokenValue) -> Result<Vec<StyleProp>, String> {
+    let value = match value {
+        TokenValue::Color(color) => PropValue::Color(color),
+        TokenValue::Length(length) => PropValue::Length(length),
+        TokenValue::Number(number) => PropValue::Number(number),
+    };
+    create_props(property, &value).map_err(|err| err.to_string())
+}
+
+fn create_props_unchecked(
+    name: &str,
+    value: &PropValue,
+) -> Result<Vec<StyleProp>, StyleParsingError> {
+    match name {
+        "size" => size_props(
+            value,
+            (CalcProperty::Width, StyleProp::Width),
+            (CalcProperty::Height, StyleProp::Height),
+        ),
+        "min_size" => size_props(
+            value,
+            (CalcProperty::MinWidth, StyleProp::MinWidth),
+            (CalcProperty::MinHeight, StyleProp::MinHeight),
+        ),
+        "max_size" => size_props(
+            value,
+            (CalcProperty::MaxWidth, StyleProp::MaxWidth),
+            (CalcProperty::MaxHeight, StyleProp::MaxHeight),
+        ),
+        _ => Ok(vec![create_prop(name, value)?]),
+    }
+}
+
 fn create_prop(name: &str, value: &PropValue) -> Result<StyleProp, StyleParsingError> {
     match name {
         "background_image" => Ok(StyleProp::BackgroundImage(value.coerce()?)),
+        "background_image_flip_x" => Ok(StyleProp::BackgroundImageFlipX(value.coerce()?)),
+        "background_image_flip_y" => Ok(StyleProp::BackgroundImageFlipY(value.coerce()?)),
+        "background_image_tint" => Ok(StyleProp::BackgroundImageTint(value.coerce()?)),
         "background_color" => Ok(StyleProp::BackgroundColor(value.coerce()?)),
         "border_color" => Ok(StyleProp::BorderColor(value.coerce()?)),
-        "color" => Ok(StyleProp::Color(value.coerce()?)),
+        "color" => match value {
+            PropValue::Ident("inherit") => Ok(StyleProp::InheritColor),
+            _ => Ok(StyleProp::Color(value.coerce()?)),
+        },
         "z_index" => Ok(StyleProp::ZIndex(value.coerce()?)),
+        "elevate_while_animating" => Ok(StyleProp::ElevateWhileAnimating(value.coerce()?)),
 
         "display" => Ok(StyleProp::Display(value.coerce()?)),
         "position_type" => Ok(StyleProp::Position(value.coerce()?)),
@@ -219,17 +1039,17 @@ fn create_prop(name: &str, value: &PropValue) -> Result<StyleProp, StyleParsingE
         "overflow_y" => Ok(StyleProp::OverflowY(value.coerce()?)),
         "direction" => Ok(StyleProp::Direction(value.coerce()?)),
 
-        "left" => Ok(StyleProp::Left(value.coerce()?)),
-        "right" => Ok(StyleProp::Right(value.coerce()?)),
-        "top" => Ok(StyleProp::Top(value.coerce()?)),
-        "bottom" => Ok(StyleProp::Bottom(value.coerce()?)),
-
-        "width" => Ok(StyleProp::Width(value.coerce()?)),
-        "height" => Ok(StyleProp::Height(value.coerce()?)),
-        "min_width" => Ok(StyleProp::MinWidth(value.coerce()?)),
-        "min_height" => Ok(StyleProp::MinHeight(value.coerce()?)),
-        "max_width" => Ok(StyleProp::MaxWidth(value.coerce()?)),
-        "max_height" => Ok(StyleProp::MaxHeight(value.coerce()?)),
+        "left" => length_prop(value, CalcProperty::Left, StyleProp::Left),
+        "right" => length_prop(value, CalcProperty::Right, StyleProp::Right),
+        "top" => length_prop(value, CalcProperty::Top, StyleProp::Top),
+        "bottom" => length_prop(value, CalcProperty::Bottom, StyleProp::Bottom),
+
+        "width" => length_prop(value, CalcProperty::Width, StyleProp::Width),
+        "height" => length_prop(value, CalcProperty::Height, StyleProp::Height),
+        "min_width" => length_prop(value, CalcProperty::MinWidth, StyleProp::MinWidth),
+        "min_height" => length_prop(value, CalcProperty::MinHeight, StyleProp::MinHeight),
+        "max_width" => length_prop(value, CalcProperty::MaxWidth, StyleProp::MaxWidth),
+        "max_height" => length_prop(value, CalcProperty::MaxHeight, StyleProp::MaxHeight),
         "aspect_ratio" => Ok(StyleProp::AspectRatio(value.coerce()?)),
 
         "border" => Ok(StyleProp::Border(value.coerce()?)),
@@ -251,12 +1071,16 @@ fn create_prop(name: &str, value: &PropValue) -> Result<StyleProp, StyleParsingE
         "margin_bottom" => Ok(StyleProp::MarginBottom(value.coerce()?)),
 
         "flex_direction" => Ok(StyleProp::FlexDirection(value.coerce()?)),
-        "flex_grow" => Ok(StyleProp::FlexGrow(value.coerce()?)),
-        "flex_shrink" => Ok(StyleProp::FlexShrink(value.coerce()?)),
+        "flex_wrap" => Ok(StyleProp::FlexWrap(value.coerce()?)),
+        "flex_grow" => Ok(StyleProp::FlexGrow(non_negative(value.coerce()?)?)),
+        "flex_shrink" => Ok(StyleProp::FlexShrink(non_negative(value.coerce()?)?)),
         "flex_basis" => Ok(StyleProp::FlexBasis(value.coerce()?)),
         "row_gap" => Ok(StyleProp::RowGap(value.coerce()?)),
         "column_gap" => Ok(StyleProp::ColumnGap(value.coerce()?)),
-        "gap" => Ok(StyleProp::Gap(value.coerce()?)),
+        "gap" => {
+            let (row, column) = value.coerce()?;
+            Ok(StyleProp::Gap(row, column))
+        }
 
         "align_items" => Ok(StyleProp::AlignItems(value.coerce()?)),
         "align_content" => Ok(StyleProp::AlignContent(value.coerce()?)),
@@ -276,15 +1100,23 @@ fn create_prop(name: &str, value: &PropValue) -> Result<StyleProp, StyleParsingE
         "grid_row_span" => Ok(StyleProp::GridRowSpan(value.coerce()?)),
         "grid_row_end" => Ok(StyleProp::GridRowEnd(value.coerce()?)),
         // GridColumn(ui::GridPlacement),
-        "grid_column_start" => Ok(StyleProp::GridRowStart(value.coerce()?)),
-        "grid_column_span" => Ok(StyleProp::GridRowSpan(value.coerce()?)),
-        "grid_column_end" => Ok(StyleProp::GridRowEnd(value.coerce()?)),
+        "grid_column_start" => Ok(StyleProp::GridColumnStart(value.coerce()?)),
+        "grid_column_span" => Ok(StyleProp::GridColumnSpan(value.coerce()?)),
+        "grid_column_end" => Ok(StyleProp::GridColumnEnd(value.coerce()?)),
 
         // PointerEvents(PointerEvents),
 
         // // Text
-        "font" => Ok(StyleProp::Font(value.coerce()?)),
-        // FontSize(f32),
+        "font" => match value {
+            PropValue::Ident("inherit") => Ok(StyleProp::InheritFont),
+            _ => Ok(StyleProp::Font(value.coerce()?)),
+        },
+        "font_size" => match value {
+            PropValue::Ident("inherit") => Ok(StyleProp::InheritFontSize),
+            _ => Ok(StyleProp::FontSize(value.coerce()?)),
+        },
+        "text_overflow" => Ok(StyleProp::TextOverflow(value.coerce()?)),
+        "text_align" => Ok(StyleProp::TextAlign(value.coerce()?)),
 
         // Outlines
         "outline_color" => Ok(StyleProp::OutlineColor(value.coerce()?)),
@@ -300,24 +1132,81 @@ fn create_prop(name: &str, value: &PropValue) -> Result<StyleProp, StyleParsingE
         "scale" => Ok(StyleProp::Scale(value.coerce()?)),
         "scale_x" => Ok(StyleProp::ScaleX(value.coerce()?)),
         "scale_y" => Ok(StyleProp::ScaleY(value.coerce()?)),
-        "rotation" => Ok(StyleProp::Rotation(value.coerce()?)),
-        // Translation(Vec3),
+        "rotation" => Ok(StyleProp::Rotation(value.coerce_angle()?)),
+        "translation" => Ok(StyleProp::Translation(value.coerce()?)),
+
+        "ui_scale" => Ok(StyleProp::UiScale(value.coerce()?)),
 
-        // // Transitions
-        // Transition(Vec<Transition>),
         _ => Err(StyleParsingError::InvalidPropertyName(name.to_owned())),
     }
 }
 
-fn style_prop_inner(input: &mut &str) -> PResult<StyleProp> {
-    let (name, _, _, _, value, _, _) = (
+fn style_prop_inner(input: &mut &str) -> PResult<Vec<StyleProp>> {
+    // Transitions have their own syntax, which doesn't fit the general property value grammar.
+    if let Some(transitions) = opt(delimited(
+        ("transition", whitespace, ':', whitespace),
+        cut_err(transition_list.context(StrContext::Label("transition"))),
+        (
+            whitespace,
+            cut_err(
+                ';'.context(StrContext::Expected(StrContextValue::Description(
+                    "semicolon",
+                ))),
+            ),
+        ),
+    ))
+    .parse_next(input)?
+    {
+        return Ok(vec![StyleProp::Transition(transitions)]);
+    }
+
+    if let Some(anims) = opt(delimited(
+        ("animate_in", whitespace, ':', whitespace),
+        cut_err(comma_list(animate_in).context(StrContext::Label("animate_in"))),
+        (
+            whitespace,
+            cut_err(
+                ';'.context(StrContext::Expected(StrContextValue::Description(
+                    "semicolon",
+                ))),
+            ),
+        ),
+    ))
+    .parse_next(input)?
+    {
+        return Ok(vec![StyleProp::AnimateIn(anims)]);
+    }
+
+    let start = *input;
+    let (name, _, _, _, value, _, important, _) = (
         prop_name,
-        multispace0,
+        whitespace,
         ':',
-        multispace0,
-        alt((color, ident, length_list, length, string))
-            .context(StrContext::Label("property value")),
-        multispace0,
+        whitespace,
+        alt((
+            color,
+            calc,
+            token,
+            function_call,
+            ident_list,
+            ident,
+            angle,
+            length_list,
+            length,
+            string,
+        ))
+        .context(StrContext::Label("property value")),
+        whitespace,
+        opt((
+            '!',
+            whitespace,
+            cut_err(
+                "important".context(StrContext::Expected(StrContextValue::StringLiteral(
+                    "important",
+                ))),
+            ),
+            whitespace,
+        )),
         cut_err(
             ';'.context(StrContext::Expected(StrContextValue::Description(
                 "semicolon",
@@ -326,85 +1215,595 @@ fn style_prop_inner(input: &mut &str) -> PResult<StyleProp> {
     )
         .parse_next(input)?;
 
-    create_prop(name, &value)
-        .map_err(|err| ErrMode::from_external_error(input, ErrorKind::Fail, err).cut())
+    // Report invalid properties at the start of the property, rather than after it.
+    let props = create_props(name, &value).map_err(|err| {
+        *input = start;
+        ErrMode::from_external_error(input, ErrorKind::Fail, err).cut()
+    })?;
+    Ok(match important {
+        Some(_) => props
+            .into_iter()
+            .map(|prop| StyleProp::Important(Box::new(prop)))
+            .collect(),
+        None => props,
+    })
 }
 
-fn style_prop(input: &mut &str) -> PResult<StyleProp> {
-    (style_prop_inner, whitespace)
-        .map(|(prop, _)| Ok(prop))
-        .parse_next(input)?
+/// The style properties set by a property declaration, and its source text, not including
+/// trailing whitespace.
+fn style_prop_with_src<'s>(input: &mut &'s str) -> PResult<(Vec<StyleProp>, &'s str)> {
+    (style_prop_inner.with_recognized(), whitespace)
+        .map(|(prop, _)| prop)
+        .parse_next(input)
 }
 
-fn selector_prop_list_items(input: &mut &str) -> PResult<Vec<StyleProp>> {
-    repeat(.., style_prop).parse_next(input)
+#[cfg(test)]
+fn style_props(input: &mut &str) -> PResult<Vec<StyleProp>> {
+    style_prop_with_src
+        .map(|(props, _)| props)
+        .parse_next(input)
 }
 
-fn selector(input: &mut &str) -> PResult<SelectorEntry> {
-    (
-        selector_parser::selector_parser,
-        whitespace,
-        '{',
-        cut_err((whitespace, selector_prop_list_items, '}', whitespace)),
-    )
-        .map(|(sel, _, _, (_, props, _, _))| (sel, props))
-        .context(StrContext::Expected(StrContextValue::Description(
-            "selector",
-        )))
+/// A declaration which sets a single style property.
+#[cfg(test)]
+fn style_prop(input: &mut &str) -> PResult<StyleProp> {
+    style_props
+        .verify_map(|mut props| (props.len() == 1).then(|| props.remove(0)))
         .parse_next(input)
 }
 
-fn style_prop_list_items(input: &mut &str) -> PResult<Vec<StylePropOrSelector>> {
-    repeat(
+/// The style properties of a selector or scope entry, each paired with the source text of the
+/// declaration which set it.
+fn selector_prop_list_items<'s>(input: &mut &'s str) -> PResult<Vec<(StyleProp, &'s str)>> {
+    let start = *input;
+    let items = repeat(
         ..,
         alt((
-            style_prop.map(StylePropOrSelector::StyleProp),
-            selector.map(StylePropOrSelector::Selector),
+            transition_longhand_with_src
+                .map(|(longhand, src)| StylePropOrSelector::TransitionLonghand(longhand, src)),
+            style_prop_with_src.map(|(props, src)| StylePropOrSelector::StyleProps(props, src)),
         )),
     )
-    .parse_next(input)
+    .parse_next(input)?;
+    let mut decls = Vec::new();
+    for item in merge_transition_longhands(input, start, items)? {
+        if let StylePropOrSelector::StyleProps(props, src) = item {
+            decls.extend(props.into_iter().map(|prop| (prop, src)));
+        }
+    }
+    Ok(decls)
 }
 
-fn style_prop_list(input: &mut &str) -> PResult<(String, StylePropList)> {
+/// A selector entry and its source text, not including trailing whitespace.
+fn selector_with_src<'s>(input: &mut &'s str) -> PResult<(SelectorEntry, SelectorSrc<'s>)> {
     (
-        ident.context(StrContext::Label("style name")).recognize(),
+        (
+            // Properties start with a letter, and selectors never do.
+            peek(one_of(['&', '*', '.', ':', '>', '@'])),
+            cut_err(
+                (selector_parser::selector_parser, whitespace, '{')
+                    .context(StrContext::Label("selector")),
+            ),
+            cut_err((whitespace, selector_prop_list_items, close_brace)),
+        )
+            .with_recognized(),
         whitespace,
-        '{',
-        cut_err((whitespace, style_prop_list_items, '}')),
     )
-        .map(|(name, _, _, (_, mut items, _))| {
-            let mut props: Vec<StyleProp> = Vec::new();
-            let mut selectors: Vec<SelectorEntry> = Vec::new();
-            for item in items.drain(..) {
-                match item {
-                    StylePropOrSelector::StyleProp(prop) => {
-                        props.push(prop);
-                    }
-                    StylePropOrSelector::Selector(sel) => {
-                        selectors.push(sel);
-                    }
-                }
-            }
-            (name.to_owned(), StylePropList { props, selectors })
+        .map(|(((_, (sel, _, _), (_, props, _)), selector), _)| {
+            let (props, props_src) = props.into_iter().unzip();
+            (
+                (sel, props),
+                SelectorSrc {
+                    selector,
+                    props: props_src,
+                },
+            )
         })
         .parse_next(input)
 }
 
-fn stylesheet(input: &mut &str) -> PResult<Vec<(String, StylePropList)>> {
+/// A block of properties which only apply within a named scope, and its source text, not
+/// including trailing whitespace: `@scope(dark) { background_color: #111; }`.
+fn scope_with_src<'s>(input: &mut &'s str) -> PResult<(ScopeEntry, SelectorSrc<'s>)> {
     (
-        repeat(.., (whitespace, style_prop_list).map(|(_, l)| l)),
+        (
+            "@scope",
+            cut_err(
+                (
+                    whitespace,
+                    '(',
+                    whitespace,
+                    take_while(1.., (AsChar::is_alphanum, '_', '-')),
+                    whitespace,
+                    ')',
+                    whitespace,
+                    '{',
+                )
+                    .context(StrContext::Label("scope")),
+            ),
+            cut_err((whitespace, selector_prop_list_items, close_brace)),
+        )
+            .with_recognized(),
         whitespace,
-        eof,
     )
-        .map(|(entries, _, _)| entries)
+        .map(
+            |(((_, (_, _, _, name, _, _, _, _), (_, props, _)), scope), _)| {
+                let (props, props_src): (Vec<StyleProp>, _) = props.into_iter().unzip();
+                (
+                    (name.to_owned(), props),
+                    SelectorSrc {
+                        selector: scope,
+                        props: props_src,
+                    },
+                )
+            },
+        )
         .parse_next(input)
 }
 
-/// Parse a stylesheet from a string.
-pub fn parse_stylesheet(
-    input: &str,
-) -> Result<Vec<(String, StylePropList)>, ParseError<&str, ContextError>> {
-    stylesheet.parse(input)
+/// A block of properties and selector entries which only apply when a feature flag is
+/// enabled, not including trailing whitespace:
+/// `@flag new_checkout { &.cta { background_color: #0a0; } }`. Properties directly within the
+/// block become a selector entry of their own, before the block's other entries.
+fn flag_with_src<'s>(input: &mut &'s str) -> PResult<Vec<(SelectorEntry, SelectorSrc<'s>)>> {
+    // Without a brace after the name, this is a selector entry such as `@flag beta &.cta`.
+    let start = *input;
+    let ((name, _, _), (_, items, _)) = (
+        (selector_parser::flag_parser, whitespace, '{'),
+        cut_err((whitespace, style_prop_list_items, close_brace)),
+    )
+        .parse_next(input)?;
+    let block = &start[..input.offset_from(&start)];
+    let flagged = |sel: Box<Selector>| Box::new(Selector::Flag(name.clone(), sel));
+
+    let mut props: Vec<StyleProp> = Vec::new();
+    let mut props_src = Vec::new();
+    let mut entries = Vec::new();
+    for item in items {
+        match item {
+            StylePropOrSelector::StyleProps(decl_props, prop_src) => {
+                props_src.extend(decl_props.iter().map(|_| prop_src));
+                props.extend(decl_props);
+            }
+            StylePropOrSelector::Selector((sel, sel_props), sel_src) => {
+                entries.push(((flagged(sel), sel_props), sel_src));
+            }
+            StylePropOrSelector::Flag(inner) => entries.extend(
+                inner
+                    .into_iter()
+                    .map(|((sel, sel_props), sel_src)| ((flagged(sel), sel_props), sel_src)),
+            ),
+            StylePropOrSelector::Scope(_, scope_src) => {
+                *input = &start[scope_src.selector.offset_from(&start)..];
+                return Err(ErrMode::from_external_error(
+                    input,
+                    ErrorKind::Verify,
+                    StyleParsingError::ScopeInBlock("@flag"),
+                )
+                .cut());
+            }
+            // Merged into a transition property by `merge_transition_longhands`.
+            StylePropOrSelector::TransitionLonghand(..) => {}
+        }
+    }
+    if !props.is_empty() {
+        entries.insert(
+            0,
+            (
+                (flagged(Box::new(Selector::Accept)), props),
+                SelectorSrc {
+                    selector: block,
+                    props: props_src,
+                },
+            ),
+        );
+    }
+    whitespace.parse_next(input)?;
+    Ok(entries)
+}
+
+fn style_prop_list_items<'s>(input: &mut &'s str) -> PResult<Vec<StylePropOrSelector<'s>>> {
+    let start = *input;
+    let items = repeat(
+        ..,
+        alt((
+            transition_longhand_with_src
+                .map(|(longhand, src)| StylePropOrSelector::TransitionLonghand(longhand, src)),
+            style_prop_with_src.map(|(props, src)| StylePropOrSelector::StyleProps(props, src)),
+            scope_with_src.map(|(scope, src)| StylePropOrSelector::Scope(scope, src)),
+            flag_with_src.map(StylePropOrSelector::Flag),
+            selector_with_src.map(|(sel, src)| StylePropOrSelector::Selector(sel, src)),
+        )),
+    )
+    .parse_next(input)?;
+    merge_transition_longhands(input, start, items)
+}
+
+fn style_prop_list<'s>(input: &mut &'s str) -> PResult<(String, StylePropList, RuleSrc<'s>)> {
+    (
+        ident.context(StrContext::Label("style name")).recognize(),
+        whitespace,
+        '{',
+        cut_err((whitespace, style_prop_list_items, close_brace)),
+    )
+        .with_recognized()
+        .map(|((name, _, _, (_, items, _)), rule)| {
+            let mut props: Vec<StyleProp> = Vec::new();
+            let mut selectors: Vec<SelectorEntry> = Vec::new();
+            let mut scopes: Vec<ScopeEntry> = Vec::new();
+            let mut src = RuleSrc {
+                rule,
+                props: Vec::new(),
+                selectors: Vec::new(),
+                scopes: Vec::new(),
+            };
+            for item in items {
+                match item {
+                    StylePropOrSelector::StyleProps(decl_props, prop_src) => {
+                        // Each property set by a shorthand shares the shorthand's source.
+                        src.props.extend(decl_props.iter().map(|_| prop_src));
+                        props.extend(decl_props);
+                    }
+                    StylePropOrSelector::Selector(sel, sel_src) => {
+                        selectors.push(sel);
+                        src.selectors.push(sel_src);
+                    }
+                    StylePropOrSelector::Scope(scope, scope_src) => {
+                        scopes.push(scope);
+                        src.scopes.push(scope_src);
+                    }
+                    StylePropOrSelector::Flag(entries) => {
+                        for (sel, sel_src) in entries {
+                            selectors.push(sel);
+                            src.selectors.push(sel_src);
+                        }
+                    }
+                    // Merged into a transition property by `merge_transition_longhands`.
+                    StylePropOrSelector::TransitionLonghand(..) => {}
+                }
+            }
+            (
+                name.to_owned(),
+                StylePropList::from_raw(props, selectors).with_scopes(scopes),
+                src,
+            )
+        })
+        .parse_next(input)
+}
+
+/// A rule within a media or flag block, named by `block`. Scope entries can't be made
+/// conditional, so they aren't allowed.
+fn conditional_rule<'s>(
+    block: &'static str,
+) -> impl FnMut(&mut &'s str) -> PResult<(String, StylePropList, RuleSrc<'s>)> {
+    move |input: &mut &'s str| {
+        let start = *input;
+        let rule = style_prop_list.parse_next(input)?;
+        if let Some(scope) = rule.2.scopes.first() {
+            *input = &start[scope.selector.offset_from(&start)..];
+            return Err(ErrMode::from_external_error(
+                input,
+                ErrorKind::Verify,
+                StyleParsingError::ScopeInBlock(block),
+            )
+            .cut());
+        }
+        Ok(rule)
+    }
+}
+
+/// Make the properties and selectors of the rules within a media or flag block conditional,
+/// by applying `condition` to their selectors.
+fn conditional_rules<'s>(
+    rules: Vec<(String, StylePropList, RuleSrc<'s>)>,
+    condition: impl Fn(Box<Selector>) -> Selector,
+) -> Vec<(String, StylePropList, RuleSrc<'s>)> {
+    rules
+        .into_iter()
+        .map(|(name, list, src)| {
+            let (props, list_selectors, _) = list.into_raw();
+            let mut selectors: Vec<SelectorEntry> = Vec::with_capacity(list_selectors.len() + 1);
+            let mut selectors_src = Vec::with_capacity(list_selectors.len() + 1);
+            if !props.is_empty() {
+                selectors.push((Box::new(condition(Box::new(Selector::Accept))), props));
+                selectors_src.push(SelectorSrc {
+                    selector: src.rule,
+                    props: src.props,
+                });
+            }
+            selectors.extend(
+                list_selectors
+                    .into_iter()
+                    .map(|(sel, props)| (Box::new(condition(sel)), props)),
+            );
+            selectors_src.extend(src.selectors);
+            (
+                name,
+                StylePropList::from_raw(Vec::new(), selectors),
+                RuleSrc {
+                    rule: src.rule,
+                    props: Vec::new(),
+                    selectors: selectors_src,
+                    scopes: Vec::new(),
+                },
+            )
+        })
+        .collect()
+}
+
+/// A block of rules which only apply when the window size matches a media query:
+/// `@media (max-width: 800) { MAIN { padding: 4; } }`. The rules' properties and selectors
+/// are made conditional on the query.
+fn media_block<'s>(input: &mut &'s str) -> PResult<Vec<(String, StylePropList, RuleSrc<'s>)>> {
+    (
+        peek('@'),
+        cut_err(
+            (selector_parser::media_query_parser, whitespace, '{')
+                .context(StrContext::Label("media query")),
+        ),
+        cut_err((
+            repeat(.., preceded(whitespace, conditional_rule("@media"))),
+            whitespace,
+            '}',
+        )),
+    )
+        .map(|(_, (query, _, _), (rules, _, _))| {
+            conditional_rules(rules, |sel| Selector::Media(query, sel))
+        })
+        .parse_next(input)
+}
+
+/// A block of rules which only apply when a feature flag is enabled:
+/// `@flag new_checkout { CTA { background_color: #0a0; } }`. The rules' properties and
+/// selectors are made conditional on the flag.
+fn flag_block<'s>(input: &mut &'s str) -> PResult<Vec<(String, StylePropList, RuleSrc<'s>)>> {
+    (
+        selector_parser::flag_parser,
+        cut_err((whitespace, '{').context(StrContext::Label("flag block"))),
+        cut_err((
+            repeat(.., preceded(whitespace, conditional_rule("@flag"))),
+            whitespace,
+            '}',
+        )),
+    )
+        .map(|(name, _, (rules, _, _))| {
+            conditional_rules(rules, |sel| Selector::Flag(name.clone(), sel))
+        })
+        .parse_next(input)
+}
+
+enum StylesheetItem<'s> {
+    Rule((String, StylePropList, RuleSrc<'s>)),
+    /// The rules of a media or flag block.
+    Conditional(Vec<(String, StylePropList, RuleSrc<'s>)>),
+}
+
+/// A stylesheet entry which is being assembled from one or more rules.
+struct Entry<'s> {
+    /// Offset of the first unconditional rule with this name.
+    first: Option<usize>,
+    name: String,
+    list: StylePropList,
+    src: RuleSrc<'s>,
+}
+
+impl<'s> Entry<'s> {
+    fn merge(&mut self, list: StylePropList, src: RuleSrc<'s>) {
+        self.list.extend(list);
+        self.src.props.extend(src.props);
+        self.src.selectors.extend(src.selectors);
+        self.src.scopes.extend(src.scopes);
+    }
+}
+
+/// How [`parse_stylesheet_with`] handles a stylesheet which defines the same rule name more
+/// than once.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DuplicateRules {
+    /// Duplicate rule names are a parsing error.
+    #[default]
+    Error,
+
+    /// Later rules are merged into the first rule with the same name. Properties from later
+    /// rules override those from earlier ones, as in CSS.
+    Merge,
+}
+
+/// One top-level item of a stylesheet: a rule, or a media or flag block of rules.
+fn stylesheet_item<'s>(input: &mut &'s str) -> PResult<StylesheetItem<'s>> {
+    alt((
+        style_prop_list.map(StylesheetItem::Rule),
+        flag_block.map(StylesheetItem::Conditional),
+        media_block.map(StylesheetItem::Conditional),
+    ))
+    .parse_next(input)
+}
+
+fn stylesheet(
+    duplicates: DuplicateRules,
+) -> impl FnMut(&mut &str) -> PResult<Vec<(String, StylePropList)>> {
+    move |input: &mut &str| {
+        let start = *input;
+        let (items, _, _): (Vec<(&str, StylesheetItem)>, _, _) = (
+            repeat(
+                ..,
+                (whitespace, stylesheet_item.with_recognized()).map(|(_, (item, src))| (src, item)),
+            ),
+            whitespace,
+            eof,
+        )
+            .parse_next(input)?;
+
+        let (rules, mut errors) = assemble_stylesheet(start, items, duplicates);
+        if errors.is_empty() {
+            return Ok(rules);
+        }
+        let (offset, err) = errors.remove(0);
+        *input = &start[offset..];
+        Err(ErrMode::from_external_error(input, ErrorKind::Verify, err).cut())
+    }
+}
+
+/// Assemble the items parsed from `start` into rules, merging the rules with the same name.
+/// Rules which can't be merged because they're duplicates are skipped, and returned as
+/// errors with their offsets.
+#[allow(clippy::type_complexity)]
+fn assemble_stylesheet<'s>(
+    start: &'s str,
+    items: Vec<(&'s str, StylesheetItem<'s>)>,
+    duplicates: DuplicateRules,
+) -> (
+    Vec<(String, StylePropList)>,
+    Vec<(usize, StyleParsingError)>,
+) {
+    // Rules from media and flag blocks are always merged into the rule with the same name.
+    let mut entries: Vec<Entry> = Vec::with_capacity(items.len());
+    let mut errors = Vec::new();
+    for (item_src, item) in items {
+        let offset = item_src.offset_from(&start);
+        match item {
+            StylesheetItem::Rule((name, list, src)) => {
+                match entries.iter_mut().find(|entry| entry.name == name) {
+                    None => entries.push(Entry {
+                        first: Some(offset),
+                        name,
+                        list,
+                        src,
+                    }),
+                    Some(entry) if entry.first.is_none() => {
+                        entry.first = Some(offset);
+                        entry.src.rule = src.rule;
+                        entry.merge(list, src);
+                    }
+                    Some(entry) if duplicates == DuplicateRules::Merge => {
+                        entry.merge(list, src);
+                    }
+                    Some(entry) => errors.push((
+                        offset,
+                        StyleParsingError::DuplicateRule {
+                            name,
+                            first: entry.first.unwrap_or_default(),
+                            second: offset,
+                        },
+                    )),
+                }
+            }
+            StylesheetItem::Conditional(rules) => {
+                for (name, list, src) in rules {
+                    match entries.iter_mut().find(|entry| entry.name == name) {
+                        None => entries.push(Entry {
+                            first: None,
+                            name,
+                            list,
+                            src,
+                        }),
+                        Some(entry) => entry.merge(list, src),
+                    }
+                }
+            }
+        }
+    }
+
+    let lines = LineIndex::new(start);
+    let rules = entries
+        .into_iter()
+        .map(|entry| {
+            let spans = entry.src.to_spans(&lines);
+            (entry.name, entry.list.with_spans(spans))
+        })
+        .collect();
+    (rules, errors)
+}
+
+/// The length of the top-level item at the start of `input`, up to and including the brace
+/// which closes it, or a semicolon outside of any braces. This is found by counting braces,
+/// skipping strings and comments, so that parsing can resume after an item which is
+/// malformed. Always at least one character, unless `input` is empty.
+fn item_len(input: &str) -> usize {
+    let mut depth = 0usize;
+    let mut chars = input.char_indices().peekable();
+    while let Some((index, c)) = chars.next() {
+        match c {
+            '{' => depth += 1,
+            '}' if depth <= 1 => return index + 1,
+            '}' => depth -= 1,
+            ';' if depth == 0 => return index + 1,
+            '"' => {
+                while let Some((_, c)) = chars.next() {
+                    match c {
+                        '\\' => {
+                            chars.next();
+                        }
+                        '"' => break,
+                        _ => {}
+                    }
+                }
+            }
+            '/' if chars.peek().is_some_and(|(_, c)| *c == '/') => {
+                while chars.next_if(|(_, c)| *c != '\n').is_some() {}
+            }
+            _ => {}
+        }
+    }
+    input.len()
+}
+
+/// Parse a stylesheet from a string. Defining the same rule name more than once is an error.
+pub fn parse_stylesheet(input: &str) -> Result<Vec<(String, StylePropList)>, StylesheetError> {
+    parse_stylesheet_with(input, DuplicateRules::Error)
+}
+
+/// Parse a stylesheet from a string, handling duplicate rule names as specified.
+pub fn parse_stylesheet_with(
+    input: &str,
+    duplicates: DuplicateRules,
+) -> Result<Vec<(String, StylePropList)>, StylesheetError> {
+    stylesheet(duplicates)
+        .parse(input)
+        .map_err(|err| StylesheetError::from_parse_error(input, err))
+}
+
+/// Parse a stylesheet from a string, skipping any rule or block which can't be parsed rather
+/// than failing as a whole, for sources such as user-edited files where the rest should still
+/// apply. Returns the rules which were parsed, and an error for each item which was skipped.
+/// Duplicate rule names are handled as specified; with [`DuplicateRules::Error`], the later
+/// rules are skipped.
+pub fn parse_stylesheet_recovering(
+    input: &str,
+    duplicates: DuplicateRules,
+) -> (Vec<(String, StylePropList)>, Vec<StylesheetError>) {
+    let mut items = Vec::new();
+    let mut errors = Vec::new();
+    let mut rest = input;
+    loop {
+        // Whitespace and comments always parse.
+        let _ = whitespace.parse_next(&mut rest);
+        if rest.is_empty() {
+            break;
+        }
+        let item_start = rest;
+        match stylesheet_item.with_recognized().parse_next(&mut rest) {
+            Ok((item, src)) => items.push((src, item)),
+            Err(err) => {
+                // An item which doesn't start like any kind of item is reported where it
+                // starts, as `parse_stylesheet` would.
+                let (offset, inner) = match err {
+                    ErrMode::Cut(inner) => (rest.offset_from(&input), inner),
+                    _ => (item_start.offset_from(&input), ContextError::new()),
+                };
+                errors.push(StylesheetError::from_context_error(input, offset, &inner));
+                rest = &item_start[item_len(item_start)..];
+            }
+        }
+    }
+
+    let (rules, duplicates) = assemble_stylesheet(input, items, duplicates);
+    for (offset, err) in duplicates {
+        let inner = ContextError::from_external_error(&input, ErrorKind::Verify, err);
+        errors.push(StylesheetError::from_context_error(input, offset, &inner));
+    }
+    errors.sort_by_key(|err| err.offset);
+    (rules, errors)
 }
 
 trait CoercePropValue<T> {
@@ -432,6 +1831,30 @@ impl<'s> CoercePropValue<i16> for PropValue<'s> {
     }
 }
 
+impl<'s> CoercePropValue<i32> for PropValue<'s> {
+    fn coerce(&self) -> Result<i32, StyleParsingError> {
+        match self {
+            PropValue::Number(p) => {
+                if p.fract() == 0.0 && *p >= i32::MIN as f32 && *p <= i32::MAX as f32 {
+                    Ok(*p as i32)
+                } else {
+                    Err(StyleParsingError::InvalidPropertyValue(format!(
+                        "{} (expected an integer)",
+                        p
+                    )))
+                }
+            }
+            PropValue::Ident(id) => Err(StyleParsingError::InvalidPropertyValue(format!(
+                "\"{}\"",
+                id
+            ))),
+            _ => Err(StyleParsingError::InvalidPropertyType(
+                PropValue::type_name(self),
+            )),
+        }
+    }
+}
+
 impl<'s> CoercePropValue<u16> for PropValue<'s> {
     fn coerce(&self) -> Result<u16, StyleParsingError> {
         match self {
@@ -453,6 +1876,22 @@ impl<'s> CoercePropValue<u16> for PropValue<'s> {
     }
 }
 
+impl<'s> CoercePropValue<bool> for PropValue<'s> {
+    fn coerce(&self) -> Result<bool, StyleParsingError> {
+        match self {
+            PropValue::Ident("true") => Ok(true),
+            PropValue::Ident("false") => Ok(false),
+            PropValue::Ident(id) => Err(StyleParsingError::InvalidPropertyValue(format!(
+                "\"{}\" (expected true or false)",
+                id
+            ))),
+            _ => Err(StyleParsingError::InvalidPropertyType(
+                PropValue::type_name(self),
+            )),
+        }
+    }
+}
+
 impl<'s> CoercePropValue<f32> for PropValue<'s> {
     fn coerce(&self) -> Result<f32, StyleParsingError> {
         match self {
@@ -488,11 +1927,14 @@ impl<'s> CoercePropValue<Option<Color>> for PropValue<'s> {
     fn coerce(&self) -> Result<Option<Color>, StyleParsingError> {
         match self {
             PropValue::Color(color) => Ok(Some(*color)),
-            PropValue::Ident("transparent") => Ok(None),
-            PropValue::Ident(id) => Err(StyleParsingError::InvalidPropertyType(format!(
-                "\"{}\"",
-                id
-            ))),
+            PropValue::Ident(id) if id.eq_ignore_ascii_case("transparent") => Ok(None),
+            PropValue::Ident(id) => match parse_color_name(id) {
+                Some(color) => Ok(Some(color)),
+                None => Err(StyleParsingError::InvalidPropertyType(format!(
+                    "\"{}\"",
+                    id
+                ))),
+            },
             _ => Err(StyleParsingError::InvalidPropertyType(
                 PropValue::type_name(self),
             )),
@@ -533,6 +1975,31 @@ impl<'s> CoercePropValue<ui::Val> for PropValue<'s> {
     }
 }
 
+impl<'s> CoercePropValue<Vec3> for PropValue<'s> {
+    fn coerce(&self) -> Result<Vec3, StyleParsingError> {
+        match self {
+            PropValue::List(vals) => {
+                let mut coords = [0.; 3];
+                for (coord, val) in coords.iter_mut().zip(vals.iter()) {
+                    *coord = match val.coerce()? {
+                        ui::Val::Px(px) => px,
+                        other => {
+                            return Err(StyleParsingError::InvalidPropertyValue(format!(
+                                "{:?}",
+                                other
+                            )))
+                        }
+                    };
+                }
+                Ok(Vec3::from_array(coords))
+            }
+            _ => Err(StyleParsingError::InvalidPropertyType(
+                PropValue::type_name(self),
+            )),
+        }
+    }
+}
+
 impl<'s> CoercePropValue<ui::UiRect> for PropValue<'s> {
     fn coerce(&self) -> Result<ui::UiRect, StyleParsingError> {
         match self {
@@ -563,10 +2030,47 @@ impl<'s> CoercePropValue<ui::UiRect> for PropValue<'s> {
     }
 }
 
+/// A row and column gap: either a single length for both, or two lengths.
+impl<'s> CoercePropValue<(ui::Val, ui::Val)> for PropValue<'s> {
+    fn coerce(&self) -> Result<(ui::Val, ui::Val), StyleParsingError> {
+        match self {
+            PropValue::List(vals) => match vals.as_slice() {
+                [row, column] => Ok((row.coerce()?, column.coerce()?)),
+                _ => Err(StyleParsingError::InvalidPropertyValue(format!(
+                    "{} values (expected 1 or 2)",
+                    vals.len()
+                ))),
+            },
+            _ => {
+                let gap: ui::Val = self.coerce()?;
+                Ok((gap, gap))
+            }
+        }
+    }
+}
+
 impl<'s> CoercePropValue<ui::ZIndex> for PropValue<'s> {
     fn coerce(&self) -> Result<ui::ZIndex, StyleParsingError> {
         match self {
-            PropValue::Number(n) => Ok(ui::ZIndex::Local(*n as i32)),
+            PropValue::Number(_) => Ok(ui::ZIndex::Local(self.coerce()?)),
+            PropValue::Function(name @ ("local" | "global"), args) => {
+                if args.len() != 1 {
+                    return Err(StyleParsingError::InvalidPropertyValue(format!(
+                        "{}() takes 1 argument, got {}",
+                        name,
+                        args.len()
+                    )));
+                }
+                if *name == "global" {
+                    Ok(ui::ZIndex::Global(args[0].coerce()?))
+                } else {
+                    Ok(ui::ZIndex::Local(args[0].coerce()?))
+                }
+            }
+            PropValue::Function(name, _) => Err(StyleParsingError::InvalidPropertyValue(format!(
+                "{}()",
+                name
+            ))),
             PropValue::Ident(id) => Err(StyleParsingError::InvalidPropertyValue(format!(
                 "\"{}\"",
                 id
@@ -627,6 +2131,28 @@ impl<'s> CoercePropValue<ui::OverflowAxis> for PropValue<'s> {
     }
 }
 
+impl<'s> CoercePropValue<ui::Overflow> for PropValue<'s> {
+    fn coerce(&self) -> Result<ui::Overflow, StyleParsingError> {
+        match self {
+            PropValue::Ident(_) => {
+                let axis: ui::OverflowAxis = self.coerce()?;
+                Ok(ui::Overflow { x: axis, y: axis })
+            }
+            PropValue::List(vals) if vals.len() == 2 => Ok(ui::Overflow {
+                x: vals[0].coerce()?,
+                y: vals[1].coerce()?,
+            }),
+            PropValue::List(vals) => Err(StyleParsingError::InvalidPropertyValue(format!(
+                "{} values",
+                vals.len()
+            ))),
+            _ => Err(StyleParsingError::InvalidPropertyType(
+                PropValue::type_name(self),
+            )),
+        }
+    }
+}
+
 impl<'s> CoercePropValue<ui::Direction> for PropValue<'s> {
     fn coerce(&self) -> Result<ui::Direction, StyleParsingError> {
         match self {
@@ -644,6 +2170,39 @@ impl<'s> CoercePropValue<ui::Direction> for PropValue<'s> {
     }
 }
 
+impl<'s> CoercePropValue<TextAlignment> for PropValue<'s> {
+    fn coerce(&self) -> Result<TextAlignment, StyleParsingError> {
+        match self {
+            PropValue::Ident("left") => Ok(TextAlignment::Left),
+            PropValue::Ident("center") => Ok(TextAlignment::Center),
+            PropValue::Ident("right") => Ok(TextAlignment::Right),
+            PropValue::Ident(id) => Err(StyleParsingError::InvalidPropertyValue(format!(
+                "\"{}\"",
+                id
+            ))),
+            _ => Err(StyleParsingError::InvalidPropertyType(
+                PropValue::type_name(self),
+            )),
+        }
+    }
+}
+
+impl<'s> CoercePropValue<TextOverflow> for PropValue<'s> {
+    fn coerce(&self) -> Result<TextOverflow, StyleParsingError> {
+        match self {
+            PropValue::Ident("clip") => Ok(TextOverflow::Clip),
+            PropValue::Ident("ellipsis") => Ok(TextOverflow::Ellipsis),
+            PropValue::Ident(id) => Err(StyleParsingError::InvalidPropertyValue(format!(
+                "\"{}\"",
+                id
+            ))),
+            _ => Err(StyleParsingError::InvalidPropertyType(
+                PropValue::type_name(self),
+            )),
+        }
+    }
+}
+
 impl<'s> CoercePropValue<ui::FlexDirection> for PropValue<'s> {
     fn coerce(&self) -> Result<ui::FlexDirection, StyleParsingError> {
         match self {
@@ -669,7 +2228,7 @@ impl<'s> CoercePropValue<ui::FlexWrap> for PropValue<'s> {
             PropValue::Ident("wrap") => Ok(ui::FlexWrap::Wrap),
             PropValue::Ident("wrap_reverse") => Ok(ui::FlexWrap::WrapReverse),
             PropValue::Ident(id) => Err(StyleParsingError::InvalidPropertyValue(format!(
-                "\"{}\"",
+                "\"{}\" (expected nowrap, wrap or wrap_reverse)",
                 id
             ))),
             _ => Err(StyleParsingError::InvalidPropertyType(
@@ -834,6 +2393,8 @@ mod tests {
     use winnow::error::ContextError;
 
     use super::*;
+    use crate::span::{SourcePos, SourceSpan};
+    use crate::{StyleLint, StylesheetErrorKind};
 
     #[track_caller]
     fn run_parser<'s, T>(mut parser: impl Parser<&'s str, T, ContextError>, input: &'s str) -> T {
@@ -889,6 +2450,78 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_parse_named_color() {
+        assert!(matches!(
+            run_parser(style_prop, "color: white;"),
+            StyleProp::Color(Some(color)) if color == Color::WHITE
+        ));
+        assert!(matches!(
+            run_parser(style_prop, "background_color: CornflowerBlue;"),
+            StyleProp::BackgroundColor(Some(color)) if color == Color::rgb_u8(0x64, 0x95, 0xed)
+        ));
+        assert!(matches!(
+            run_parser(style_prop, "border_color: REBECCAPURPLE;"),
+            StyleProp::BorderColor(Some(color)) if color == Color::rgb_u8(0x66, 0x33, 0x99)
+        ));
+        assert!(matches!(
+            run_parser(style_prop, "outline_color: lightGrey;"),
+            StyleProp::OutlineColor(Some(color)) if color == Color::rgb_u8(0xd3, 0xd3, 0xd3)
+        ));
+        // `transparent` still means no color, in any case.
+        assert!(matches!(
+            run_parser(style_prop, "background_color: Transparent;"),
+            StyleProp::BackgroundColor(None)
+        ));
+
+        let err = run_parser_err(style_prop, "color: whitish;");
+        assert!(err.contains("\"whitish\""), "{err}");
+        let err = parse_stylesheet("MAIN {\n    border_color: reddish;\n}").unwrap_err();
+        assert_eq!(err.kind, StylesheetErrorKind::PropertyValue);
+    }
+
+    #[test]
+    fn test_parse_hex_color() {
+        let color = |src: &str| match run_parser(style_prop, &format!("color: {};", src)) {
+            StyleProp::Color(Some(color)) => color,
+            result => panic!("incorrect result: {:?}", result),
+        };
+        assert_eq!(color("#abc"), Color::rgb_u8(0xaa, 0xbb, 0xcc));
+        assert_eq!(color("#abcd"), Color::rgba_u8(0xaa, 0xbb, 0xcc, 0xdd));
+        assert_eq!(color("#aabbcc"), Color::rgb_u8(0xaa, 0xbb, 0xcc));
+        assert_eq!(color("#aabbccdd"), Color::rgba_u8(0xaa, 0xbb, 0xcc, 0xdd));
+        assert_eq!(color("#AbC"), color("#aabbcc"));
+
+        for literal in [
+            "#",
+            "#f",
+            "#ff",
+            "#fffff",
+            "#fffffff",
+            "#fffffffff",
+            "#ffg",
+            "#12z456",
+        ] {
+            let err = run_parser_err(style_prop, &format!("color: {};", literal));
+            assert!(
+                err.contains(&format!(
+                    "invalid color '{}' (expected 3, 4, 6 or 8 hex digits)",
+                    literal
+                )),
+                "{err}"
+            );
+        }
+        let err = parse_stylesheet("MAIN {\n    color: #fffff;\n}").unwrap_err();
+        assert_eq!(err.kind, StylesheetErrorKind::PropertyValue);
+        assert_eq!(
+            err.pos,
+            SourcePos {
+                line: 2,
+                column: 12
+            }
+        );
+    }
+
     #[test]
     fn test_style_parser_background_color() {
         let result = run_parser(style_prop, "background_color: #fff;");
@@ -900,6 +2533,41 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_style_parser_background_image() {
+        let result = run_parser(style_prop, "background_image: \"textures/panel.png\";");
+        assert!(
+            matches!(result, StyleProp::BackgroundImage(Some(ref path)) if path.path().to_str() == Some("textures/panel.png")),
+            "{:?}",
+            result
+        );
+        let result = run_parser(style_prop, "background_image_flip_x: true;");
+        assert!(
+            matches!(result, StyleProp::BackgroundImageFlipX(true)),
+            "{:?}",
+            result
+        );
+        let result = run_parser(style_prop, "background_image_flip_y: false;");
+        assert!(
+            matches!(result, StyleProp::BackgroundImageFlipY(false)),
+            "{:?}",
+            result
+        );
+        let result = run_parser(style_prop, "background_image_tint: #ff000080;");
+        assert!(
+            matches!(result, StyleProp::BackgroundImageTint(Some(color)) if color == Color::rgba_u8(255, 0, 0, 128)),
+            "{:?}",
+            result
+        );
+        let err = run_parser_err(style_prop, "background_image_flip_x: yes;");
+        assert!(
+            err.contains("invalid property value: \"yes\" (expected true or false)"),
+            "{err}"
+        );
+        let err = run_parser_err(style_prop, "background_image_flip_y: 1;");
+        assert!(err.contains("invalid property type: number"), "{err}");
+    }
+
     #[test]
     fn test_style_parser_length() {
         let result = run_parser(style_prop, "width: 10;");
@@ -943,11 +2611,496 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_style_parser_aspect_ratio() {
+        let result = run_parser(style_prop, "aspect_ratio: 1.5;");
+        match result {
+            StyleProp::AspectRatio(val) => {
+                assert_eq!(val, Some(1.5));
+            }
+            _ => panic!("incorrect result: {:?}", result),
+        }
+
+        let result = run_parser(style_prop, "aspect_ratio: none;");
+        match result {
+            StyleProp::AspectRatio(val) => {
+                assert_eq!(val, None);
+            }
+            _ => panic!("incorrect result: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_style_parser_overflow() {
+        let result = run_parser(style_prop, "overflow: clip;");
+        match result {
+            StyleProp::Overflow(val) => {
+                assert_eq!(val, ui::Overflow::clip());
+            }
+            _ => panic!("incorrect result: {:?}", result),
+        }
+
+        let result = run_parser(style_prop, "overflow: clip visible;");
+        match result {
+            StyleProp::Overflow(val) => {
+                assert_eq!(val, ui::Overflow::clip_x());
+            }
+            _ => panic!("incorrect result: {:?}", result),
+        }
+
+        let result = run_parser(style_prop, "overflow_x: visible;");
+        match result {
+            StyleProp::OverflowX(val) => {
+                assert_eq!(val, ui::OverflowAxis::Visible);
+            }
+            _ => panic!("incorrect result: {:?}", result),
+        }
+
+        let result = run_parser(style_prop, "overflow_y: clip;");
+        match result {
+            StyleProp::OverflowY(val) => {
+                assert_eq!(val, ui::OverflowAxis::Clip);
+            }
+            _ => panic!("incorrect result: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_style_parser_transforms() {
+        let result = run_parser(style_prop, "scale: 1.2;");
+        assert!(
+            matches!(result, StyleProp::Scale(v) if v == 1.2),
+            "{:?}",
+            result
+        );
+        let result = run_parser(style_prop, "scale_x: 0.5;");
+        assert!(
+            matches!(result, StyleProp::ScaleX(v) if v == 0.5),
+            "{:?}",
+            result
+        );
+        let result = run_parser(style_prop, "scale_y: 2;");
+        assert!(
+            matches!(result, StyleProp::ScaleY(v) if v == 2.),
+            "{:?}",
+            result
+        );
+        let result = run_parser(style_prop, "ui_scale: 0.75;");
+        assert!(
+            matches!(result, StyleProp::UiScale(v) if v == 0.75),
+            "{:?}",
+            result
+        );
+        for (src, scale) in [("0", "0"), ("-2", "-2"), ("1e39", "inf")] {
+            let err = run_parser_err(style_props, &format!("ui_scale: {src};"));
+            assert!(
+                err.contains(&format!(
+                    "invalid property value: {scale} (expected a positive number for 'ui_scale')"
+                )),
+                "{err}"
+            );
+        }
+
+        let result = run_parser(style_prop, "rotation: 45deg;");
+        match result {
+            StyleProp::Rotation(val) => {
+                assert!((val - std::f32::consts::FRAC_PI_4).abs() < 1e-6);
+            }
+            _ => panic!("incorrect result: {:?}", result),
+        }
+        let result = run_parser(style_prop, "rotation: 1.5;");
+        assert!(
+            matches!(result, StyleProp::Rotation(v) if v == 1.5),
+            "{:?}",
+            result
+        );
+        let result = run_parser(style_prop, "rotation: 0.5rad;");
+        assert!(
+            matches!(result, StyleProp::Rotation(v) if v == 0.5),
+            "{:?}",
+            result
+        );
+
+        let result = run_parser(style_prop, "translation: 10px 20px;");
+        match result {
+            StyleProp::Translation(val) => assert_eq!(val, Vec3::new(10., 20., 0.)),
+            _ => panic!("incorrect result: {:?}", result),
+        }
+        let result = run_parser(style_prop, "translation: 10px 20px 5;");
+        match result {
+            StyleProp::Translation(val) => assert_eq!(val, Vec3::new(10., 20., 5.)),
+            _ => panic!("incorrect result: {:?}", result),
+        }
+
+        let err = run_parser_err(style_prop, "translation: 10% 20px;");
+        assert!(
+            err.contains("invalid property value: Percent(10.0)"),
+            "{err}"
+        );
+        let err = run_parser_err(style_prop, "translation: 10px;");
+        assert!(err.contains("invalid property type: length"), "{err}");
+        let err = run_parser_err(style_prop, "scale: 45deg;");
+        assert!(err.contains("invalid property type: angle"), "{err}");
+    }
+
+    #[test]
+    fn test_style_parser_z_index() {
+        let result = run_parser(style_prop, "z_index: 3;");
+        assert!(
+            matches!(result, StyleProp::ZIndex(ui::ZIndex::Local(3))),
+            "{:?}",
+            result
+        );
+        let result = run_parser(style_prop, "z_index: -2;");
+        assert!(
+            matches!(result, StyleProp::ZIndex(ui::ZIndex::Local(-2))),
+            "{:?}",
+            result
+        );
+        let result = run_parser(style_prop, "z_index: global(10);");
+        assert!(
+            matches!(result, StyleProp::ZIndex(ui::ZIndex::Global(10))),
+            "{:?}",
+            result
+        );
+        let result = run_parser(style_prop, "z_index: global( -5 );");
+        assert!(
+            matches!(result, StyleProp::ZIndex(ui::ZIndex::Global(-5))),
+            "{:?}",
+            result
+        );
+        let result = run_parser(style_prop, "z_index: local(4);");
+        assert!(
+            matches!(result, StyleProp::ZIndex(ui::ZIndex::Local(4))),
+            "{:?}",
+            result
+        );
+        let result = run_parser(style_prop, "elevate_while_animating: true;");
+        assert!(
+            matches!(result, StyleProp::ElevateWhileAnimating(true)),
+            "{:?}",
+            result
+        );
+        assert_eq!(result.to_string(), "elevate_while_animating: true");
+    }
+
+    #[test]
+    fn test_style_parser_z_index_err() {
+        let err = run_parser_err(style_prop, "z_index: 1.5;");
+        assert!(
+            err.contains("invalid property value: 1.5 (expected an integer)"),
+            "{err}"
+        );
+        let err = run_parser_err(style_prop, "z_index: global(2.5);");
+        assert!(
+            err.contains("invalid property value: 2.5 (expected an integer)"),
+            "{err}"
+        );
+        let err = run_parser_err(style_prop, "z_index: global();");
+        assert!(
+            err.contains("invalid property value: global() takes 1 argument, got 0"),
+            "{err}"
+        );
+        let err = run_parser_err(style_prop, "z_index: global(1, 2);");
+        assert!(
+            err.contains("invalid property value: global() takes 1 argument, got 2"),
+            "{err}"
+        );
+        let err = run_parser_err(style_prop, "z_index: front(1);");
+        assert!(err.contains("invalid property value: front()"), "{err}");
+        let err = run_parser_err(style_prop, "z_index: 10px;");
+        assert!(err.contains("invalid property type: length"), "{err}");
+    }
+
+    #[test]
+    fn test_style_parser_inherit() {
+        let result = run_parser(style_prop, "color: inherit;");
+        assert!(matches!(result, StyleProp::InheritColor), "{:?}", result);
+        let result = run_parser(style_prop, "font: inherit;");
+        assert!(matches!(result, StyleProp::InheritFont), "{:?}", result);
+        let result = run_parser(style_prop, "font_size: inherit;");
+        assert!(matches!(result, StyleProp::InheritFontSize), "{:?}", result);
+        let result = run_parser(style_prop, "font_size: 14;");
+        assert!(
+            matches!(result, StyleProp::FontSize(v) if v == 14.),
+            "{:?}",
+            result
+        );
+    }
+
+    #[test]
+    fn test_style_parser_text_overflow() {
+        let result = run_parser(style_prop, "text_overflow: ellipsis;");
+        assert!(
+            matches!(result, StyleProp::TextOverflow(TextOverflow::Ellipsis)),
+            "{:?}",
+            result
+        );
+        let result = run_parser(style_prop, "text_overflow: clip;");
+        assert!(
+            matches!(result, StyleProp::TextOverflow(TextOverflow::Clip)),
+            "{:?}",
+            result
+        );
+        let err = run_parser_err(style_prop, "text_overflow: fade;");
+        assert!(err.contains("invalid property value: \"fade\""), "{err}");
+    }
+
+    #[test]
+    fn test_style_parser_text_align() {
+        for (src, align) in [
+            ("text_align: left;", TextAlignment::Left),
+            ("text_align: center;", TextAlignment::Center),
+            ("text_align: right;", TextAlignment::Right),
+        ] {
+            let result = run_parser(style_prop, src);
+            assert!(
+                matches!(result, StyleProp::TextAlign(a) if a == align),
+                "{:?}",
+                result
+            );
+        }
+        let err = run_parser_err(style_prop, "text_align: justify;");
+        assert!(err.contains("invalid property value: \"justify\""), "{err}");
+    }
+
+    #[test]
+    fn test_style_parser_direction() {
+        let result = run_parser(style_prop, "direction: rtl;");
+        match result {
+            StyleProp::Direction(val) => {
+                assert_eq!(val, ui::Direction::RightToLeft);
+            }
+            _ => panic!("incorrect result: {:?}", result),
+        }
+
+        let result = run_parser(style_prop, "direction: inherit;");
+        match result {
+            StyleProp::Direction(val) => {
+                assert_eq!(val, ui::Direction::Inherit);
+            }
+            _ => panic!("incorrect result: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_style_parser_flex() {
+        let result = run_parser(style_prop, "flex_grow: 2;");
+        assert!(matches!(result, StyleProp::FlexGrow(v) if v == 2.));
+        let result = run_parser(style_prop, "flex_shrink: 0.5;");
+        assert!(matches!(result, StyleProp::FlexShrink(v) if v == 0.5));
+        let result = run_parser(style_prop, "flex_wrap: wrap_reverse;");
+        assert!(matches!(
+            result,
+            StyleProp::FlexWrap(ui::FlexWrap::WrapReverse)
+        ));
+
+        let err = run_parser_err(style_prop, "flex_grow: -1;");
+        assert!(
+            err.contains("invalid property value: -1 (expected a non-negative number)"),
+            "{err}"
+        );
+        let err = run_parser_err(style_prop, "flex_wrap: wrapped;");
+        assert!(
+            err.contains(
+                "invalid property value: \"wrapped\" (expected nowrap, wrap or wrap_reverse)"
+            ),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn test_style_parser_gap() {
+        let result = run_parser(style_prop, "gap: 4;");
+        assert!(matches!(
+            result,
+            StyleProp::Gap(ui::Val::Px(r), ui::Val::Px(c)) if r == 4. && c == 4.
+        ));
+
+        // Two values set the row and column gaps separately.
+        let result = run_parser(style_prop, "gap: 4 8%;");
+        assert!(matches!(
+            result,
+            StyleProp::Gap(ui::Val::Px(r), ui::Val::Percent(c)) if r == 4. && c == 8.
+        ));
+
+        let err = run_parser_err(style_prop, "gap: 1 2 3;");
+        assert!(
+            err.contains("invalid property value: 3 values (expected 1 or 2)"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn test_style_parser_size() {
+        let result = run_parser(style_props, "size: 32;");
+        assert!(matches!(
+            result.as_slice(),
+            [
+                StyleProp::Width(ui::Val::Px(w)),
+                StyleProp::Height(ui::Val::Px(h))
+            ] if *w == 32. && *h == 32.
+        ));
+
+        let result = run_parser(style_props, "min_size: 100px 50%;");
+        assert!(matches!(
+            result.as_slice(),
+            [
+                StyleProp::MinWidth(ui::Val::Px(w)),
+                StyleProp::MinHeight(ui::Val::Percent(h))
+            ] if *w == 100. && *h == 50.
+        ));
+
+        let result = run_parser(style_props, "max_size: calc(100% - 8px);");
+        assert!(matches!(
+            result.as_slice(),
+            [
+                StyleProp::Calc(CalcProperty::MaxWidth, _),
+                StyleProp::Calc(CalcProperty::MaxHeight, _)
+            ]
+        ));
+
+        let err = run_parser_err(style_props, "size: 1 2 3;");
+        assert!(
+            err.contains("invalid property value: 3 values (expected 1 or 2)"),
+            "{err}"
+        );
+
+        // Errors point at the shorthand, not the properties it expands into.
+        let err = parse_stylesheet("A {\n    width: 1px;\n    size: 1px 2px 3px;\n}").unwrap_err();
+        assert_eq!(err.pos, SourcePos { line: 3, column: 5 });
+        assert_eq!(err.kind, StylesheetErrorKind::PropertyValue);
+
+        // Each expanded property has the span of the shorthand.
+        let result =
+            parse_stylesheet("A {\n    size: 4px;\n    :hover { max_size: 8px; }\n}").unwrap();
+        let style = &result[0].1;
+        assert_eq!(style.props().len(), 2);
+        let spans = style.spans().unwrap();
+        assert_eq!(spans.props.len(), 2);
+        assert_eq!(spans.props[0], spans.props[1]);
+        assert_eq!(spans.props[0].start, SourcePos { line: 2, column: 5 });
+        assert_eq!(spans.selectors[0].props.len(), 2);
+    }
+
+    #[test]
+    fn test_style_parser_negative_lengths() {
+        // Offsets and margins can be negative, in any unit.
+        let result = run_parser(style_prop, "margin_left: -8px;");
+        assert!(matches!(result, StyleProp::MarginLeft(ui::Val::Px(v)) if v == -8.));
+        let result = run_parser(style_prop, "top: -50%;");
+        assert!(matches!(result, StyleProp::Top(ui::Val::Percent(v)) if v == -50.));
+        let result = run_parser(style_prop, "left: -.5vw;");
+        assert!(matches!(result, StyleProp::Left(ui::Val::Vw(v)) if v == -0.5));
+        let result = run_parser(style_prop, "margin: -4px 2vmin;");
+        assert!(matches!(
+            result,
+            StyleProp::Margin(ui::UiRect { left: ui::Val::Px(h), top: ui::Val::VMin(v), .. })
+                if h == -4. && v == 2.
+        ));
+
+        // Sizes, padding, borders and font sizes can't.
+        for (src, message) in [
+            (
+                "width: -10px;",
+                "-10px (expected a non-negative length for 'width')",
+            ),
+            (
+                "max_height: -5%;",
+                "-5% (expected a non-negative length for 'max_height')",
+            ),
+            (
+                "padding: 4px -2px;",
+                "-2px (expected a non-negative length for 'padding')",
+            ),
+            (
+                "border_top: -1px;",
+                "-1px (expected a non-negative length for 'border_top')",
+            ),
+            (
+                "font_size: -12;",
+                "-12px (expected a non-negative length for 'font_size')",
+            ),
+            (
+                "min_size: 4px -1vh;",
+                "-1vh (expected a non-negative length for 'min_size')",
+            ),
+        ] {
+            let err = run_parser_err(style_props, src);
+            assert!(
+                err.contains(&format!("invalid property value: {message}")),
+                "{err}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_style_parser_calc() {
+        let result = run_parser(style_prop, "width: calc(100% - 32px);");
+        assert!(matches!(
+            result,
+            StyleProp::Calc(CalcProperty::Width, CalcLength { percent, px })
+                if percent == 100. && px == -32.
+        ));
+
+        let result = run_parser(style_prop, "top: calc( 50% + 10px - 4 );");
+        assert!(matches!(
+            result,
+            StyleProp::Calc(CalcProperty::Top, CalcLength { percent, px })
+                if percent == 50. && px == 6.
+        ));
+
+        let err = run_parser_err(style_prop, "width: calc(100% - 2vh);");
+        assert!(err.contains("expected px or % length"), "{err}");
+
+        let err = run_parser_err(style_prop, "padding: calc(100% - 2px);");
+        assert!(
+            err.contains("invalid property type: calc expression"),
+            "{err}"
+        );
+    }
+
+    #[test]
+    fn test_validate_transitions() {
+        let lints = |src: &str| parse_stylesheet(src).unwrap().pop().unwrap().1.validate();
+        assert_eq!(
+            lints("A { width: auto; transition: width 0.3s, height 0.3s; height: 10px; }"),
+            vec![StyleLint::UnanimatableTransition {
+                property: TransitionProperty::Width,
+                unit: "auto",
+            }]
+        );
+
+        // Relative lengths are resolved to pixels, so they can be animated too.
+        assert!(lints("A { border: 2px 4vw; transition: border_top 0.3s; }").is_empty());
+        assert!(lints("A { width: 50%; transition: width 0.3s; }").is_empty());
+
+        // A length anywhere in the style, including a conditional one, can be animated.
+        assert!(
+            lints("A { width: auto; transition: width 0.3s; :hover { width: 10px; } }").is_empty()
+        );
+        assert!(lints("A { width: calc(50% - 10px); transition: width 0.3s; }").is_empty());
+        // Transitions on properties which aren't set at all aren't reported.
+        assert!(lints("A { transition: width 0.3s; }").is_empty());
+    }
+
+    #[test]
+    fn test_style_parser_err_bad_ident() {
+        let err = run_parser_err(style_prop, "overflow: scroll;");
+        assert!(err.contains("invalid property value: \"scroll\""), "{err}");
+
+        let err = run_parser_err(style_prop, "overflow: clip scroll;");
+        assert!(err.contains("invalid property value: \"scroll\""), "{err}");
+
+        let err = run_parser_err(style_prop, "direction: up;");
+        assert!(err.contains("invalid property value: \"up\""), "{err}");
+    }
+
     #[test]
     fn test_style_list_parser_empty() {
         let result = run_parser(style_prop_list, "MAIN {}");
         assert_eq!(result.0, "MAIN");
-        assert_eq!(result.1.props.len(), 0);
+        assert_eq!(result.1.props().len(), 0);
     }
 
     #[test]
@@ -960,19 +3113,19 @@ mod tests {
              }",
         );
         assert_eq!(result.0, "MAIN");
-        assert_eq!(result.1.props.len(), 2);
+        assert_eq!(result.1.props().len(), 2);
     }
 
     #[test]
     fn test_stylesheet_parser_empty() {
-        let result = run_parser(stylesheet, "");
+        let result = run_parser(stylesheet(DuplicateRules::Error), "");
         assert_eq!(result.len(), 0);
     }
 
     #[test]
     fn test_stylesheet_parser_single() {
         let result = run_parser(
-            stylesheet,
+            stylesheet(DuplicateRules::Error),
             "MAIN {
                 width: 10px;
                 height: 10px;
@@ -984,7 +3137,7 @@ mod tests {
     #[test]
     fn test_stylesheet_parser_with_selector() {
         let result = run_parser(
-            stylesheet,
+            stylesheet(DuplicateRules::Error),
             "MAIN {
                 width: 10px;
                 height: 10px;
@@ -996,14 +3149,14 @@ mod tests {
              }",
         );
         assert_eq!(result.len(), 1);
-        assert_eq!(result[0].1.props.len(), 2);
-        assert_eq!(result[0].1.selectors.len(), 1);
+        assert_eq!(result[0].1.props().len(), 2);
+        assert_eq!(result[0].1.selectors().len(), 1);
     }
 
     #[test]
     fn test_stylesheet_parser_with_space() {
         let result = run_parser(
-            stylesheet,
+            stylesheet(DuplicateRules::Error),
             "
             MAIN {
                 width: 10px;
@@ -1021,7 +3174,7 @@ mod tests {
     #[test]
     fn test_stylesheet_comment() {
         let result = run_parser(
-            stylesheet,
+            stylesheet(DuplicateRules::Error),
             "
             // Comment1
             MAIN {
@@ -1044,7 +3197,7 @@ mod tests {
     #[test]
     fn test_stylesheet_parser_err_bad_prop() {
         let err = run_parser_err(
-            stylesheet,
+            stylesheet(DuplicateRules::Error),
             "MAIN {
                 bad: 10px;
                 height: 10px;
@@ -1056,7 +3209,7 @@ mod tests {
     #[test]
     fn test_stylesheet_parser_err_bad_value() {
         let err = run_parser_err(
-            stylesheet,
+            stylesheet(DuplicateRules::Error),
             "MAIN {
                 height: #fff;
              }",
@@ -1067,7 +3220,7 @@ mod tests {
     #[test]
     fn test_stylesheet_parser_err_bad_value2() {
         let err = run_parser_err(
-            stylesheet,
+            stylesheet(DuplicateRules::Error),
             "MAIN {
                 color: #fff#;
              }",
@@ -1083,4 +3236,630 @@ mod tests {
         let result = run_parser_err(style_prop_list_items, "colorr: fff;");
         assert!(result.contains("invalid property name:"), "{err}");
     }
+
+    #[test]
+    fn test_style_parser_multi_line_values() {
+        // Values may span lines, with comments between their elements, and parse the same as
+        // when written on one line.
+        let same = |multi_line: &str, one_line: &str| {
+            assert_eq!(
+                format!("{:?}", run_parser(style_props, multi_line)),
+                format!("{:?}", run_parser(style_props, one_line)),
+                "{multi_line}"
+            );
+        };
+        same(
+            "padding:\n    1px // top\n    2px\n    3px // bottom\n    4px\n;",
+            "padding: 1px 2px 3px 4px;",
+        );
+        same("margin: 10%\n\tauto;", "margin: 10% auto;");
+        same(
+            "flex_direction: // main axis\n  row;",
+            "flex_direction: row;",
+        );
+        same(
+            "transition:\n    width 0.3s\n        ease_in,\n    left 200ms // slide\n        0.1s,\n;",
+            "transition: width 0.3s ease_in, left 200ms 0.1s;",
+        );
+        same(
+            "width: calc(\n    100% // of the parent\n    - 32px\n);",
+            "width: calc(100% - 32px);",
+        );
+
+        // A single trailing comma is allowed before the closing parenthesis.
+        same(
+            "background_color: rgba(\n    1,\n    0.5,\n    0, // blue\n    0.5,\n);",
+            "background_color: rgba(1, 0.5, 0, 0.5);",
+        );
+        same("color: rgb(1, 0.5, 0,);", "color: rgb(1, 0.5, 0);");
+        same(
+            "transition: width 0.3s quantize(\n    0.5px,\n);",
+            "transition: width 0.3s quantize(0.5px);",
+        );
+        run_parser_err(style_props, "color: rgb(1, 0.5, 0,,);");
+        run_parser_err(style_props, "transition: width 0.3s,,;");
+        run_parser_err(style_props, "padding: 1px 2px,;");
+    }
+
+    #[test]
+    fn test_style_parser_important() {
+        let result = run_parser(style_prop, "background_color: #f00 !important;");
+        assert!(matches!(
+            result,
+            StyleProp::Important(ref prop) if matches!(**prop, StyleProp::BackgroundColor(_))
+        ));
+        assert_eq!(result.key(), "background_color");
+        assert_eq!(result.to_string(), "background_color: #ff0000 !important");
+
+        // Each of the props set by a shorthand is important.
+        let props = run_parser(style_props, "size: 10px 20px ! important ;");
+        assert_eq!(props.len(), 2);
+        assert!(props.iter().all(StyleProp::is_important));
+        assert!(run_parser(style_prop, "padding: token(gap) !important;").is_important());
+
+        let err = run_parser_err(style_props, "width: 10px !importent;");
+        assert!(err.contains("expected `important`"), "{err}");
+        run_parser_err(style_props, "width: 10px important;");
+    }
+
+    #[test]
+    fn test_style_parser_token() {
+        let result = run_parser(style_prop, "background_color: token(surface);");
+        assert!(matches!(
+            result,
+            StyleProp::Token("background_color", ref token) if token == "surface"
+        ));
+        assert_eq!(result.to_string(), "background_color: token(surface)");
+        assert_eq!(result.key(), "background_color");
+        assert!(result
+            .transition_properties()
+            .contains(TransitionProperty::BackgroundColor));
+        let result = run_parser(style_prop, "padding: token( gap-small );");
+        assert!(matches!(result, StyleProp::Token("padding", ref token) if token == "gap-small"));
+
+        let err = run_parser_err(style_props, "display: token(layout);");
+        assert!(
+            err.contains("token(layout) ('display' can't be set to a theme token)"),
+            "{err}"
+        );
+        let err = run_parser_err(style_props, "colour: token(surface);");
+        assert!(err.contains("invalid property name: 'colour'"), "{err}");
+        run_parser_err(style_props, "color: token();");
+
+        // Resolving the token with its value in a theme gives the props set by that value.
+        assert_eq!(
+            format!(
+                "{:?}",
+                token_props("padding", TokenValue::Length(ui::Val::Px(4.))).unwrap()
+            ),
+            format!("{:?}", run_parser(style_props, "padding: 4px;"))
+        );
+        assert!(token_props("padding", TokenValue::Color(Color::RED)).is_err());
+        assert!(token_props("padding", TokenValue::Length(ui::Val::Px(-4.))).is_err());
+    }
+
+    #[test]
+    fn test_style_parser_rem() {
+        let result = run_parser(style_prop, "padding: 1.5rem 8px;");
+        assert!(matches!(
+            result,
+            StyleProp::Rem("padding", ref lengths)
+                if lengths == &[Length::Rem(1.5), Length::Val(ui::Val::Px(8.))]
+        ));
+        assert_eq!(result.to_string(), "padding: 1.5rem 8px");
+        assert_eq!(result.key(), "padding");
+        let result = run_parser(style_prop, "width: 2rem;");
+        assert!(result
+            .transition_properties()
+            .contains(TransitionProperty::Width));
+        assert_eq!(
+            result.layout_val(TransitionProperty::Width),
+            Some(ui::Val::Px(32.))
+        );
+
+        // Resolving the lengths gives the props set by the equivalent pixel lengths.
+        assert_eq!(
+            format!(
+                "{:?}",
+                rem_props("padding", &[Length::Rem(1.5), Length::Rem(2.)], 10.).unwrap()
+            ),
+            format!("{:?}", run_parser(style_props, "padding: 15px 20px;"))
+        );
+        assert_eq!(
+            format!(
+                "{:?}",
+                rem_props("font_size", &[Length::Rem(0.5)], 32.).unwrap()
+            ),
+            format!("{:?}", run_parser(style_props, "font_size: 16;"))
+        );
+
+        let err = run_parser_err(style_props, "flex_grow: 1rem;");
+        assert!(
+            err.contains("rem length ('flex_grow' can't be set to rem lengths)"),
+            "{err}"
+        );
+        let err = run_parser_err(style_props, "width: -1rem;");
+        assert!(
+            err.contains("expected a non-negative length for 'width'"),
+            "{err}"
+        );
+        run_parser_err(style_props, "padding: 1rem 2rem 3rem;");
+    }
+
+    #[test]
+    fn test_transition() {
+        let StyleProp::Transition(transitions) = run_parser(
+            style_prop,
+            "transition: width 0.3s ease_in quantize(0.5px), left 200ms 0.1s;",
+        ) else {
+            panic!("expected transition");
+        };
+        assert_eq!(transitions.len(), 2);
+        assert_eq!(transitions[0].property, TransitionProperty::Width);
+        assert_eq!(transitions[0].duration, 0.3);
+        assert_eq!(transitions[0].timing, Timing::EaseIn);
+        assert_eq!(transitions[0].quantum, Some(0.5));
+        assert_eq!(transitions[1].property, TransitionProperty::Left);
+        assert_eq!(transitions[1].duration, 0.2);
+        assert_eq!(transitions[1].delay, 0.1);
+        assert_eq!(transitions[1].timing, Timing::Linear);
+        assert_eq!(transitions[1].quantum, None);
+
+        let StyleProp::Transition(transitions) = run_parser(
+            style_prop,
+            "transition: scale 0.1s, rotation 0.2s, translation 1s ease_out;",
+        ) else {
+            panic!("expected transition");
+        };
+        let properties: Vec<_> = transitions.iter().map(|tr| tr.property).collect();
+        assert_eq!(
+            properties,
+            [
+                TransitionProperty::Scale,
+                TransitionProperty::Rotation,
+                TransitionProperty::Translation
+            ]
+        );
+
+        let StyleProp::Transition(transitions) = run_parser(
+            style_prop,
+            "transition: outline_width 0.2s, outline_offset 0.2s, outline_color 0.2s, font_size 1s;",
+        ) else {
+            panic!("expected transition");
+        };
+        let properties: Vec<_> = transitions.iter().map(|tr| tr.property).collect();
+        assert_eq!(
+            properties,
+            [
+                TransitionProperty::OutlineWidth,
+                TransitionProperty::OutlineOffset,
+                TransitionProperty::OutlineColor,
+                TransitionProperty::FontSize
+            ]
+        );
+
+        let err = run_parser_err(style_prop, "transition: color 1s;");
+        assert!(err.contains("expected an animatable property"), "{err}");
+        // Later transitions in the list are checked as well.
+        let err = run_parser_err(style_prop, "transition: width 1s, color 1s;");
+        assert!(err.contains("expected an animatable property"), "{err}");
+    }
+
+    #[test]
+    fn test_animate_in() {
+        let StyleProp::AnimateIn(anims) = run_parser(
+            style_prop,
+            "animate_in: scale 0.8 0.2s ease_out, translation 0 20px 0 300ms 0.1s, \
+                outline_color rgba(1, 1, 1, 0) 1s;",
+        ) else {
+            panic!("expected animate_in");
+        };
+        assert_eq!(anims.len(), 3);
+        assert!(matches!(anims[0].from, StyleProp::Scale(scale) if scale == 0.8));
+        assert_eq!(anims[0].transition.property, TransitionProperty::Scale);
+        assert_eq!(anims[0].transition.duration, 0.2);
+        assert_eq!(anims[0].transition.timing, Timing::EaseOut);
+        assert!(matches!(anims[1].from, StyleProp::Translation(v) if v == Vec3::new(0., 20., 0.)));
+        assert_eq!(anims[1].transition.duration, 0.3);
+        assert_eq!(anims[1].transition.delay, 0.1);
+        assert!(matches!(
+            anims[2].from,
+            StyleProp::OutlineColor(Some(color)) if color == Color::rgba(1., 1., 1., 0.)
+        ));
+
+        let err = run_parser_err(style_prop, "animate_in: transform 1 1s;");
+        assert!(
+            err.contains("expected a property which can be animated in"),
+            "{err}"
+        );
+        let err = run_parser_err(style_prop, "animate_in: width 1s;");
+        assert!(err.contains("animate_in"), "{err}");
+        let err = run_parser_err(style_prop, "animate_in: scale #fff 1s;");
+        assert!(err.contains("invalid property type"), "{err}");
+    }
+
+    fn transitions(src: &str) -> Vec<(TransitionProperty, f32, Timing, f32, Option<f32>)> {
+        let sheet = parse_stylesheet(src).unwrap();
+        let props = sheet[0].1.props();
+        let [StyleProp::Transition(transitions)] = props else {
+            panic!("expected a single transition, got {props:?}");
+        };
+        transitions
+            .iter()
+            .map(|tr| (tr.property, tr.duration, tr.timing, tr.delay, tr.quantum))
+            .collect()
+    }
+
+    #[test]
+    fn test_transition_longhands() {
+        use TransitionProperty::*;
+
+        // Shorter lists repeat to match the properties.
+        assert_eq!(
+            transitions(
+                "A {
+                    transition_property: width, height, left;
+                    transition_duration: 0.25s, 100ms;
+                    transition_timing_function: ease_out;
+                    transition_delay: 0.05s;
+                }"
+            ),
+            [
+                (Width, 0.25, Timing::EaseOut, 0.05, None),
+                (Height, 0.1, Timing::EaseOut, 0.05, None),
+                (Left, 0.25, Timing::EaseOut, 0.05, None),
+            ]
+        );
+
+        // Missing longhands take their defaults, and the last declaration of each wins.
+        assert_eq!(
+            transitions(
+                "A {
+                    transition_duration: 1s;
+                    transition_property: top;
+                    transition_duration: 0.5s,;
+                }"
+            ),
+            [(Top, 0.5, Timing::Linear, 0., None)]
+        );
+
+        // Longhands modify an earlier shorthand, and a later shorthand replaces them.
+        assert_eq!(
+            transitions(
+                "A {
+                    transition: width 1s quantize(1px), height 2s;
+                    transition_delay: 0.1s;
+                }"
+            ),
+            [
+                (Width, 1., Timing::Linear, 0.1, Some(1.)),
+                (Height, 2., Timing::Linear, 0.1, None),
+            ]
+        );
+        assert_eq!(
+            transitions(
+                "A {
+                    transition_property: width;
+                    transition_duration: 1s;
+                    transition: height 2s;
+                }"
+            ),
+            [(Height, 2., Timing::Linear, 0., None)]
+        );
+
+        // Longhands are merged within selector and scope blocks too.
+        let sheet = parse_stylesheet(
+            "A {
+                :hover { transition_property: scale; transition_duration: 0.1s; }
+                @scope(dark) { transition_property: background_color; }
+            }",
+        )
+        .unwrap();
+        let (_, hover) = &sheet[0].1.selectors()[0];
+        assert!(matches!(
+            &hover[..],
+            [StyleProp::Transition(tr)] if tr[0].property == Scale && tr[0].duration == 0.1
+        ));
+        let (_, dark) = &sheet[0].1.scopes()[0];
+        assert!(matches!(
+            &dark[..],
+            [StyleProp::Transition(tr)] if tr[0].property == BackgroundColor
+        ));
+    }
+
+    #[test]
+    fn test_transition_longhand_errors() {
+        let err = parse_stylesheet("A {\n  transition_property: width, display;\n}")
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.contains("line 2, column 31")
+                && err.contains("\"display\" (expected an animatable property)"),
+            "{err}"
+        );
+
+        let err = parse_stylesheet("A {\n  width: 1px;\n  transition_delay: 1s;\n}").unwrap_err();
+        assert_eq!(err.pos.line, 3);
+        assert_eq!(err.kind, StylesheetErrorKind::Structure);
+        assert!(
+            err.message
+                .contains("'transition_delay' has no effect without a transition_property"),
+            "{err}"
+        );
+
+        let err = parse_stylesheet("A { transition_timing_function: bounce; }").unwrap_err();
+        assert_eq!(err.kind, StylesheetErrorKind::PropertyValue, "{err}");
+        assert!(
+            parse_stylesheet("A { transition_property: width; transition_duration: 1s,,; }")
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_stylesheet_duplicate_rules() {
+        let src = "BUTTON { width: 10; }\nPANEL { height: 5; }\nBUTTON { height: 20; }\n";
+        let err = parse_stylesheet(src).unwrap_err().to_string();
+        assert!(
+            err.contains(
+                "line 3, column 1: duplicate rule 'BUTTON' (first defined at line 1, column 1)"
+            ),
+            "{err}"
+        );
+
+        let result = parse_stylesheet_with(src, DuplicateRules::Merge).unwrap();
+        assert_eq!(result.len(), 2);
+        assert_eq!(result[0].0, "BUTTON");
+        assert_eq!(
+            format!("{:?}", result[0].1.props()),
+            "[Width(Px(10.0)), Height(Px(20.0))]"
+        );
+        assert_eq!(result[1].0, "PANEL");
+    }
+
+    #[test]
+    fn test_stylesheet_recovering() {
+        let src = "BUTTON { width: 10; }
+            PANEL { width: ; height: 5; }
+            // A comment with a brace }
+            LABEL { font: \"a}b.ttf\"; color: #fff; }
+            stray;
+            BUTTON { height: 20; }
+            @media (max-width: 800) { BUTTON { width: 5; } }
+            ICON { size:";
+        let (rules, errors) = parse_stylesheet_recovering(src, DuplicateRules::Error);
+        let names: Vec<&str> = rules.iter().map(|(name, _)| name.as_str()).collect();
+        assert_eq!(names, ["BUTTON", "LABEL"]);
+        assert_eq!(
+            format!("{:?}", rules[0].1.props()),
+            "[Width(Px(10.0))]",
+            "the duplicate BUTTON rule is skipped"
+        );
+        assert_eq!(rules[0].1.selectors().len(), 1);
+
+        // Each skipped item is reported where parse_stylesheet would report it.
+        let lines: Vec<(u32, StylesheetErrorKind)> =
+            errors.iter().map(|err| (err.pos.line, err.kind)).collect();
+        assert_eq!(
+            lines,
+            [
+                (2, StylesheetErrorKind::Structure),
+                (5, StylesheetErrorKind::Structure),
+                (6, StylesheetErrorKind::Structure),
+                (8, StylesheetErrorKind::Structure),
+            ],
+            "{errors:?}"
+        );
+        assert_eq!(errors[0].rule.as_deref(), Some("PANEL"));
+        assert!(
+            errors[2].message.contains("duplicate rule 'BUTTON'"),
+            "{}",
+            errors[2]
+        );
+        assert_eq!(
+            parse_stylesheet(src).unwrap_err(),
+            errors[0],
+            "the first error is the same"
+        );
+
+        let (rules, errors) = parse_stylesheet_recovering(src, DuplicateRules::Merge);
+        assert_eq!(errors.len(), 3);
+        assert_eq!(
+            format!("{:?}", rules[0].1.props()),
+            "[Width(Px(10.0)), Height(Px(20.0))]"
+        );
+    }
+
+    #[test]
+    fn test_stylesheet_media() {
+        let result = parse_stylesheet(
+            "@media (max-width: 800) {
+                MAIN { padding: 4; :hover { padding: 2; } }
+                SMALL { width: 10; }
+             }
+             MAIN {
+                padding: 8;
+                @media (min-width: 1200) and (min-height: 900) { padding: 16; }
+             }",
+        )
+        .unwrap();
+        assert_eq!(result.len(), 2);
+        let (name, main) = &result[0];
+        assert_eq!(name, "MAIN");
+        assert_eq!(
+            format!("{:?}", main.props()),
+            "[Padding(UiRect { left: Px(8.0), right: Px(8.0), top: Px(8.0), bottom: Px(8.0) })]"
+        );
+        let selectors: Vec<String> = main
+            .selectors()
+            .iter()
+            .map(|(s, _)| s.to_string())
+            .collect();
+        assert_eq!(
+            selectors,
+            [
+                "@media (max-width: 800)",
+                "@media (max-width: 800) :hover",
+                "@media (min-width: 1200) and (min-height: 900)",
+            ]
+        );
+        assert_eq!(result[1].0, "SMALL");
+        assert!(result[1].1.props().is_empty());
+        assert!(result[1].1.uses_media());
+
+        // Rules in media blocks are always merged; only unconditional rules can be duplicates.
+        let err = parse_stylesheet("@media (max-width: 800) { MAIN { padding: 4; } MAIN { padding: 2; } } MAIN { padding: 8; } MAIN { padding: 9; }")
+            .unwrap_err()
+            .to_string();
+        assert!(err.contains("duplicate rule 'MAIN'"), "{err}");
+    }
+
+    #[test]
+    fn test_stylesheet_flags() {
+        let result = parse_stylesheet(
+            "@flag compact { MAIN { padding: 4; } }
+             MAIN {
+                padding: 8;
+                @flag new_checkout {
+                    width: 100;
+                    &.cta { background_color: #0a0; }
+                    @flag beta { :hover { width: 120; } }
+                }
+                @flag beta &.cta { width: 80; }
+             }",
+        )
+        .unwrap();
+        assert_eq!(result.len(), 1);
+        let (_, main) = &result[0];
+        assert_eq!(main.props().len(), 1);
+        let selectors: Vec<String> = main
+            .selectors()
+            .iter()
+            .map(|(s, _)| s.to_string())
+            .collect();
+        assert_eq!(
+            selectors,
+            [
+                "@flag compact",
+                "@flag new_checkout",
+                "@flag new_checkout &.cta",
+                "@flag new_checkout @flag beta :hover",
+                "@flag beta &.cta",
+            ]
+        );
+        assert_eq!(main.feature_flags(), ["beta", "compact", "new_checkout"]);
+        let spans = main.spans().unwrap();
+        assert_eq!(spans.selectors.len(), 5);
+        assert_eq!(spans.selectors[1].props.len(), 1);
+
+        let err =
+            parse_stylesheet("MAIN { @flag beta { @scope(dark) { width: 1; } } }").unwrap_err();
+        assert_eq!(err.kind, StylesheetErrorKind::Structure);
+        assert!(
+            err.message
+                .contains("@scope blocks can't be used within @flag blocks"),
+            "{}",
+            err
+        );
+        assert_eq!(err.offset, 20);
+    }
+
+    #[test]
+    fn test_stylesheet_scopes() {
+        let result = parse_stylesheet(
+            "PANEL {
+                color: #fff;
+                @scope(dark) { background_color: #111; }
+                :hover { color: #ff0; }
+                @scope ( high-contrast ) { border: 2px; color: #000; }
+             }",
+        )
+        .unwrap();
+        let (_, panel) = &result[0];
+        assert!(panel.uses_scopes());
+        assert_eq!(panel.selectors().len(), 1);
+        let scopes: Vec<(&str, usize)> = panel
+            .scopes()
+            .iter()
+            .map(|(name, props)| (name.as_str(), props.len()))
+            .collect();
+        assert_eq!(scopes, [("dark", 1), ("high-contrast", 2)]);
+        let spans = panel.spans().unwrap();
+        assert_eq!(spans.scopes.len(), 2);
+        assert_eq!(spans.scopes[1].props.len(), 2);
+
+        let err = parse_stylesheet("PANEL { @scope(dark) color: #fff; }").unwrap_err();
+        assert_eq!(
+            err.pos,
+            SourcePos {
+                line: 1,
+                column: 22
+            }
+        );
+
+        let err =
+            parse_stylesheet("@media (max-width: 800) { PANEL { @scope(dark) { } } }").unwrap_err();
+        assert_eq!(err.kind, StylesheetErrorKind::Structure);
+        assert!(
+            err.message.contains("@scope blocks can't be used"),
+            "{}",
+            err
+        );
+        assert_eq!(
+            err.pos,
+            SourcePos {
+                line: 1,
+                column: 35
+            }
+        );
+    }
+
+    #[test]
+    fn test_stylesheet_spans() {
+        let src = "\
+BUTTON {
+    width: 10px;
+    :hover {
+        color: #f00;
+        width: 12px;
+    }
+    height: 5px;
+}
+
+@media (max-width: 800) {
+    BUTTON { width: 4px; }
+}
+LABEL { color: #fff; }
+";
+        let pos = |line, column| SourcePos { line, column };
+        let span = |start: (u32, u32), end: (u32, u32)| SourceSpan {
+            start: pos(start.0, start.1),
+            end: pos(end.0, end.1),
+        };
+
+        let result = parse_stylesheet(src).unwrap();
+        let button = result[0].1.spans().unwrap();
+        assert_eq!(button.path, None);
+        assert_eq!(button.rule, span((1, 1), (8, 2)));
+        assert_eq!(button.props, [span((2, 5), (2, 17)), span((7, 5), (7, 17))]);
+        assert_eq!(button.selectors.len(), 2);
+        assert_eq!(button.selectors[0].selector, span((3, 5), (6, 6)));
+        assert_eq!(
+            button.selectors[0].props,
+            [span((4, 9), (4, 21)), span((5, 9), (5, 21))]
+        );
+        // Unconditional properties in a media block span the whole rule.
+        assert_eq!(button.selectors[1].selector, span((11, 5), (11, 27)));
+        assert_eq!(button.selectors[1].props, [span((11, 14), (11, 25))]);
+        assert_eq!(result[0].1.span_of_prop(1), Some(span((7, 5), (7, 17))));
+        assert_eq!(result[0].1.span_of_prop(2), None);
+        assert_eq!(result[0].1.span_of_selector(0), Some(span((3, 5), (6, 6))));
+
+        let label = result[1].1.spans().unwrap();
+        assert_eq!(label.rule, span((13, 1), (13, 23)));
+        assert_eq!(label.props, [span((13, 9), (13, 21))]);
+        assert!(label.selectors.is_empty());
+
+        // Styles which weren't parsed have no spans.
+        assert!(StylePropList::default().spans().is_none());
+    }
 }
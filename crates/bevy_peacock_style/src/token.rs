@@ -0,0 +1,125 @@
+use bevy::{render::color::Color, ui};
+
+use crate::{style_parser::token_props, StyleProp};
+
+/// The value of a theme token, which a property written as `token(name)` takes when styles
+/// are computed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TokenValue {
+    /// A color, for properties such as `background_color`.
+    Color(Color),
+    /// A length, for properties such as `width` and `padding`.
+    Length(ui::Val),
+    /// A plain number, for properties such as `flex_grow` and `scale`.
+    Number(f32),
+}
+
+impl From<Color> for TokenValue {
+    fn from(color: Color) -> Self {
+        TokenValue::Color(color)
+    }
+}
+
+impl From<ui::Val> for TokenValue {
+    fn from(length: ui::Val) -> Self {
+        TokenValue::Length(length)
+    }
+}
+
+impl From<f32> for TokenValue {
+    fn from(number: f32) -> Self {
+        TokenValue::Number(number)
+    }
+}
+
+/// The properties which can be set to a theme token: those which take a single color, length
+/// or number.
+const TOKEN_PROPERTIES: &[&str] = &[
+    "background_color",
+    "background_image_tint",
+    "border_color",
+    "color",
+    "outline_color",
+    "left",
+    "right",
+    "top",
+    "bottom",
+    "width",
+    "height",
+    "min_width",
+    "min_height",
+    "max_width",
+    "max_height",
+    "margin",
+    "margin_left",
+    "margin_right",
+    "margin_top",
+    "margin_bottom",
+    "padding",
+    "padding_left",
+    "padding_right",
+    "padding_top",
+    "padding_bottom",
+    "border",
+    "border_left",
+    "border_right",
+    "border_top",
+    "border_bottom",
+    "flex_basis",
+    "row_gap",
+    "column_gap",
+    "gap",
+    "outline_width",
+    "outline_offset",
+    "font_size",
+    "aspect_ratio",
+    "flex_grow",
+    "flex_shrink",
+    "scale",
+    "scale_x",
+    "scale_y",
+    "rotation",
+];
+
+/// Return the name of `property` as a static string, if it can be set to a theme token.
+pub(crate) fn token_property(property: &str) -> Option<&'static str> {
+    TOKEN_PROPERTIES
+        .iter()
+        .copied()
+        .find(|name| *name == property)
+}
+
+/// Resolve a property set to the theme token `token`, given the token's value in the current
+/// theme. Tokens which the theme doesn't define, or whose value is of the wrong type for the
+/// property, make color properties a visible magenta, and leave other properties unset.
+pub(crate) fn resolve_token(
+    property: &str,
+    token: &str,
+    value: Option<TokenValue>,
+) -> Vec<StyleProp> {
+    if let Some(value) = value {
+        match token_props(property, value) {
+            Ok(props) => return props,
+            Err(err) => bevy::log::warn!(
+                "Theme token '{}' can't be used for '{}': {}",
+                token,
+                property,
+                err
+            ),
+        }
+    }
+    token_props(property, TokenValue::Color(Color::FUCHSIA)).unwrap_or_default()
+}
+
+/// The props which `property` would be resolved to by a token of whichever type it accepts,
+/// used to find out which properties it sets without a theme.
+pub(crate) fn token_placeholder(property: &str) -> Vec<StyleProp> {
+    [
+        TokenValue::Color(Color::NONE),
+        TokenValue::Length(ui::Val::Px(0.)),
+        TokenValue::Number(1.),
+    ]
+    .into_iter()
+    .find_map(|value| token_props(property, value).ok())
+    .unwrap_or_default()
+}
@@ -1,10 +1,14 @@
+use bevy::{reflect::Reflect, ui};
 use std::fmt::Debug;
 
+use crate::StyleProp;
+
 /// Represents an animation timing function such as 'ease-in'.
 pub trait TimingFunction
 where
     Self: Send + Sync + Debug,
 {
+    /// Map a linear clock value in the range `[0, 1]` to an eased value.
     fn eval(&self, t: f32) -> f32;
 }
 
@@ -12,7 +16,7 @@ where
 pub mod timing {
     use std::{f32::consts::PI, fmt::Debug};
 
-    use super::TimingFunction;
+    use super::{Timing, TimingFunction};
 
     /// Linear easing function
     pub struct Linear {}
@@ -75,24 +79,62 @@ pub mod timing {
     }
 
     /// Linear easing function
-    pub const LINEAR: &Linear = &Linear {};
+    pub const LINEAR: Timing = Timing::Linear;
 
     /// "ease-in" animation function
-    pub const EASE_IN: &EaseIn = &EaseIn {};
+    pub const EASE_IN: Timing = Timing::EaseIn;
 
     /// "ease-out" animation function
-    pub const EASE_OUT: &EaseOut = &EaseOut {};
+    pub const EASE_OUT: Timing = Timing::EaseOut;
 
     /// "ease-in-out" animation function
-    pub const EASE_IN_OUT: &EaseInOut = &EaseInOut {};
+    pub const EASE_IN_OUT: Timing = Timing::EaseInOut;
+}
+
+/// Serializable identifier for one of the built-in timing functions.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Reflect)]
+pub enum Timing {
+    /// Linear easing function
+    #[default]
+    Linear,
+
+    /// Cubic ease-in function
+    EaseIn,
+
+    /// Cubic ease-out function
+    EaseOut,
+
+    /// Sinusoidal ease-in-out function
+    EaseInOut,
+}
+
+impl TimingFunction for Timing {
+    fn eval(&self, t: f32) -> f32 {
+        match self {
+            Timing::Linear => timing::Linear {}.eval(t),
+            Timing::EaseIn => timing::EaseIn {}.eval(t),
+            Timing::EaseOut => timing::EaseOut {}.eval(t),
+            Timing::EaseInOut => timing::EaseInOut {}.eval(t),
+        }
+    }
 }
 
 /// Specifies which property is being animated.
-#[derive(Clone, Debug, PartialEq, Eq, Copy, Hash)]
+#[derive(Clone, Debug, PartialEq, Eq, Copy, Hash, Reflect)]
 pub enum TransitionProperty {
-    /// Animate the element's transform
+    /// Animate the element's transform. This is shorthand for animating its scale, rotation
+    /// and translation together, sharing a clock.
     Transform,
 
+    /// Animate the scale of the element's transform
+    Scale,
+
+    /// Animate the rotation of the element's transform
+    Rotation,
+
+    /// Animate the translation of the element's transform
+    Translation,
+
     /// Animate the element's background color
     BackgroundColor,
 
@@ -128,10 +170,157 @@ pub enum TransitionProperty {
 
     /// Animate border bottom
     BorderBottom,
+
+    /// Animate padding left
+    PaddingLeft,
+
+    /// Animate padding right
+    PaddingRight,
+
+    /// Animate padding top
+    PaddingTop,
+
+    /// Animate padding bottom
+    PaddingBottom,
+
+    /// Animate margin left
+    MarginLeft,
+
+    /// Animate margin right
+    MarginRight,
+
+    /// Animate margin top
+    MarginTop,
+
+    /// Animate margin bottom
+    MarginBottom,
+
+    /// Animate row gap
+    RowGap,
+
+    /// Animate column gap
+    ColumnGap,
+
+    /// Animate the width of the element's outline
+    OutlineWidth,
+
+    /// Animate the offset of the element's outline
+    OutlineOffset,
+
+    /// Animate the color of the element's outline
+    OutlineColor,
+
+    /// Animate the font size of the element's text
+    FontSize,
+}
+
+impl TransitionProperty {
+    /// Return the value of this property in `style`, or `None` if it isn't a layout property.
+    pub fn layout_val(&self, style: &ui::Style) -> Option<ui::Val> {
+        match self {
+            TransitionProperty::Left => Some(style.left),
+            TransitionProperty::Top => Some(style.top),
+            TransitionProperty::Bottom => Some(style.bottom),
+            TransitionProperty::Right => Some(style.right),
+            TransitionProperty::Height => Some(style.height),
+            TransitionProperty::Width => Some(style.width),
+            TransitionProperty::BorderLeft => Some(style.border.left),
+            TransitionProperty::BorderTop => Some(style.border.top),
+            TransitionProperty::BorderRight => Some(style.border.right),
+            TransitionProperty::BorderBottom => Some(style.border.bottom),
+            TransitionProperty::PaddingLeft => Some(style.padding.left),
+            TransitionProperty::PaddingRight => Some(style.padding.right),
+            TransitionProperty::PaddingTop => Some(style.padding.top),
+            TransitionProperty::PaddingBottom => Some(style.padding.bottom),
+            TransitionProperty::MarginLeft => Some(style.margin.left),
+            TransitionProperty::MarginRight => Some(style.margin.right),
+            TransitionProperty::MarginTop => Some(style.margin.top),
+            TransitionProperty::MarginBottom => Some(style.margin.bottom),
+            TransitionProperty::RowGap => Some(style.row_gap),
+            TransitionProperty::ColumnGap => Some(style.column_gap),
+            TransitionProperty::Transform
+            | TransitionProperty::Scale
+            | TransitionProperty::Rotation
+            | TransitionProperty::Translation
+            | TransitionProperty::BackgroundColor
+            | TransitionProperty::BorderColor
+            | TransitionProperty::OutlineWidth
+            | TransitionProperty::OutlineOffset
+            | TransitionProperty::OutlineColor
+            | TransitionProperty::FontSize => None,
+        }
+    }
+}
+
+/// Return the name of the unit of a length, as written in a stylesheet. Used in messages about
+/// lengths which can't be animated.
+pub fn unit_name(val: ui::Val) -> &'static str {
+    match val {
+        ui::Val::Auto => "auto",
+        ui::Val::Px(_) => "px",
+        ui::Val::Percent(_) => "%",
+        ui::Val::Vw(_) => "vw",
+        ui::Val::Vh(_) => "vh",
+        ui::Val::VMin(_) => "vmin",
+        ui::Val::VMax(_) => "vmax",
+    }
+}
+
+/// A set of [`TransitionProperty`]s, stored as a bitmask.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TransitionPropertySet(u32);
+
+impl TransitionPropertySet {
+    /// Add a property to the set.
+    pub fn insert(&mut self, prop: TransitionProperty) {
+        self.0 |= Self::bit(prop);
+    }
+
+    /// True if the set contains the given property.
+    pub fn contains(&self, prop: TransitionProperty) -> bool {
+        self.0 & Self::bit(prop) != 0
+    }
+
+    /// True if the set is empty.
+    pub fn is_empty(&self) -> bool {
+        self.0 == 0
+    }
+
+    fn bit(prop: TransitionProperty) -> u32 {
+        1 << prop as u32
+    }
+}
+
+impl From<TransitionProperty> for TransitionPropertySet {
+    fn from(prop: TransitionProperty) -> Self {
+        Self(Self::bit(prop))
+    }
+}
+
+impl FromIterator<TransitionProperty> for TransitionPropertySet {
+    fn from_iter<I: IntoIterator<Item = TransitionProperty>>(iter: I) -> Self {
+        let mut set = Self::default();
+        iter.into_iter().for_each(|prop| set.insert(prop));
+        set
+    }
+}
+
+impl std::ops::BitOr for TransitionPropertySet {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+impl std::ops::BitOrAssign for TransitionPropertySet {
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.0 |= rhs.0;
+    }
 }
 
 /// Defines a CSS-like animated transition
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Reflect)]
 pub struct Transition {
     /// Which property is to be animated.
     pub property: TransitionProperty,
@@ -143,7 +332,11 @@ pub struct Transition {
     pub duration: f32,
 
     /// Easing function
-    pub timing: &'static dyn TimingFunction,
+    pub timing: Timing,
+
+    /// For length properties, the minimum change in pixels before a new animated value is
+    /// written. Overrides the global setting if present.
+    pub quantum: Option<f32>,
 }
 
 impl Default for Transition {
@@ -153,11 +346,26 @@ impl Default for Transition {
             delay: 0.,
             duration: 0.,
             timing: timing::LINEAR,
+            quantum: None,
         }
     }
 }
 
+/// An animation which plays once, when a style declaring it is first applied to an element,
+/// rather than whenever a property changes. The property starts at the value set by `from`,
+/// and animates to its computed value as described by `transition`.
+#[derive(Clone, Debug)]
+pub struct AnimateIn {
+    /// A style prop which sets the starting value of the animated property, such as
+    /// `StyleProp::Scale(0.8)`.
+    pub from: StyleProp,
+
+    /// The property to animate, and the duration, timing and delay of the animation.
+    pub transition: Transition,
+}
+
 #[doc(hidden)]
+#[derive(Default, Reflect)]
 pub struct TransitionState {
     pub transition: Transition,
     // pub(crate) direction: f32,
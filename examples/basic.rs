@@ -4,7 +4,7 @@ use bevy::prelude::*;
 use bevy_peacock::*;
 use std::f32::consts::PI;
 
-import_stylesheet!(test_styles, "examples/styles.pss");
+import_stylesheet!(test_styles, "examples/styles.pss", deny_warnings);
 
 fn main() {
     App::new()
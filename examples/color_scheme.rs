@@ -0,0 +1,71 @@
+//! Example of styling for a dark color scheme with the `:dark` pseudo-class. Press D to switch
+//! between the light and dark schemes.
+
+use bevy::prelude::*;
+use bevy_peacock::*;
+
+const STYLES: &str = "
+ROOT {
+    width: 100%;
+    height: 100%;
+    align_items: center;
+    justify_content: center;
+    background_color: #f4f4f4;
+    &:dark { background_color: #202020; }
+}
+CARD {
+    padding: 24;
+    border: 2;
+    border_color: #c0c0c0;
+    background_color: #ffffff;
+    transition: background_color 0.3s ease_in_out;
+    &:dark {
+        border_color: #505050;
+        background_color: #303030;
+    }
+}
+LABEL {
+    color: #202020;
+    :dark { color: #f0f0f0; }
+}
+";
+
+fn main() {
+    App::new()
+        .add_plugins(DefaultPlugins)
+        .add_plugins(PeacockPlugin)
+        .add_systems(Startup, (register_styles, setup_view_root).chain())
+        .add_systems(Update, (bevy::window::close_on_esc, toggle_color_scheme))
+        .run();
+}
+
+fn register_styles(mut registry: ResMut<StyleRegistry>) {
+    registry.register_sheet(parse_stylesheet(STYLES).unwrap());
+}
+
+fn setup_view_root(mut commands: Commands, registry: Res<StyleRegistry>) {
+    commands.spawn(Camera2dBundle::default());
+    commands
+        .spawn(NodeBundle::default())
+        .with_styles(registry.get("ROOT").unwrap())
+        .with_children(|parent| {
+            parent
+                .spawn(NodeBundle::default())
+                .with_styles(registry.get("CARD").unwrap())
+                .with_children(|parent| {
+                    parent
+                        .spawn(TextBundle::from_section(
+                            "Press D to switch color schemes",
+                            TextStyle::default(),
+                        ))
+                        .with_styles(registry.get("LABEL").unwrap());
+                });
+        });
+}
+
+fn toggle_color_scheme(keys: Res<Input<KeyCode>>, mut scheme: ResMut<ColorScheme>) {
+    if keys.just_pressed(KeyCode::D) {
+        *scheme = scheme.toggled();
+        info!("Color scheme: {:?}", *scheme);
+    }
+}
@@ -0,0 +1,182 @@
+//! A panel with a row for each pseudo-class, labelled with what makes it apply. Each row is
+//! highlighted while its pseudo-class matches it.
+
+use bevy::{a11y::Focus, prelude::*};
+use bevy_peacock::*;
+use static_init::dynamic;
+
+/// Background of a row whose pseudo-class doesn't match.
+pub const IDLE: Color = Color::rgb(0.2, 0.2, 0.25);
+
+/// Background of a row whose pseudo-class matches.
+pub const HIGHLIGHT: Color = Color::rgb(0.9, 0.6, 0.1);
+
+/// The rows of the gallery, in order: the name of each pseudo-class, the selector which
+/// highlights its row, and how to make it apply.
+pub const ROWS: &[(&str, &str, &str)] = &[
+    ("first-child", "&:first-child", "always, as the first row"),
+    ("hover", "&:hover", "point at this row"),
+    ("focus", "&:focus", "press Tab until this row has focus"),
+    (
+        "focus-visible",
+        "&:focus-visible",
+        "press Tab until this row has focus",
+    ),
+    (
+        "focus-within",
+        "&:focus-within",
+        "press Tab until the square has focus",
+    ),
+    ("checked", "&:checked", "press Space"),
+    ("disabled", "&:disabled", "press D"),
+    ("dragging", "&:dragging", "drag this row"),
+    (
+        "drag-over",
+        "&:drag-over",
+        "drag the row above onto this one",
+    ),
+    (
+        "lang",
+        "&:lang(de)",
+        "press L to switch the locale to German",
+    ),
+    ("--blink", "&:--blink", "during even seconds"),
+    ("last-child", "&:last-child", "always, as the last row"),
+];
+
+/// Marks each row of the gallery with the name of the pseudo-class it shows.
+#[derive(Component, Debug)]
+pub struct GalleryRow(pub &'static str);
+
+/// Marks the elements which Tab moves the focus between.
+#[derive(Component, Debug)]
+pub struct Focusable;
+
+#[dynamic]
+static PANEL: StyleHandle = StyleHandle::build(|ss| {
+    ss.display(Display::Flex)
+        .flex_direction(FlexDirection::Column)
+        .gap(4.)
+        .padding(16.)
+});
+
+#[dynamic]
+static ROW: StyleHandle = StyleHandle::build(|ss| {
+    ss.display(Display::Flex)
+        .flex_direction(FlexDirection::Row)
+        .align_items(AlignItems::Center)
+        .gap(8.)
+        .width(480.)
+        .height(32.)
+        .padding(UiRect::horizontal(Val::Px(8.)))
+        .background_color(IDLE)
+});
+
+#[dynamic]
+static SQUARE: StyleHandle = StyleHandle::build(|ss| {
+    ss.width(16.)
+        .height(16.)
+        .background_color(Color::GRAY)
+        .selector(":focus", |ss| ss.background_color(Color::WHITE))
+});
+
+/// Adds the gallery, and the keyboard controls which change the state of its rows.
+pub struct GalleryPlugin;
+
+impl Plugin for GalleryPlugin {
+    fn build(&self, app: &mut App) {
+        app.world
+            .resource_mut::<CustomPseudoClassRegistry>()
+            .register("blink", |world: &World, _: Entity| {
+                (world.resource::<Time>().elapsed_seconds() as u32).is_multiple_of(2)
+            });
+        app.add_systems(Startup, setup_gallery).add_systems(
+            Update,
+            (handle_keys, apply_deferred)
+                .chain()
+                .before(PeacockSystemSet),
+        );
+    }
+}
+
+/// Spawn the panel, with a row for each of the [`ROWS`].
+pub fn setup_gallery(mut commands: Commands) {
+    commands
+        .spawn(NodeBundle::default())
+        .with_styles(PANEL.clone())
+        .with_children(|parent| {
+            for (name, selector, hint) in ROWS {
+                let highlight = StyleHandle::build(|ss| {
+                    ss.selector(selector, |ss| ss.background_color(HIGHLIGHT))
+                });
+                // Hover and focus are only tracked for elements with classes.
+                let mut row = parent.spawn((NodeBundle::default(), GalleryRow(name)));
+                row.with_styles((ROW.clone(), highlight))
+                    .class_names("row")
+                    .with_children(|row| {
+                        row.spawn(TextBundle::from_section(
+                            format!(":{} - {}", name, hint),
+                            TextStyle {
+                                font_size: 16.,
+                                color: Color::WHITE,
+                                ..default()
+                            },
+                        ));
+                        if *name == "focus-within" {
+                            row.spawn((NodeBundle::default(), Focusable))
+                                .with_styles(SQUARE.clone())
+                                .class_names("square");
+                        }
+                    });
+                if matches!(*name, "focus" | "focus-visible") {
+                    row.insert(Focusable);
+                }
+            }
+        });
+}
+
+/// Tab moves the focus to the next focusable element, Space and D toggle the checked and
+/// disabled rows, and L switches the locale between English and German.
+pub fn handle_keys(
+    mut commands: Commands,
+    keys: Res<Input<KeyCode>>,
+    mut focus: ResMut<Focus>,
+    focusable: Query<Entity, With<Focusable>>,
+    rows: Query<(Entity, &GalleryRow, Has<Checked>, Has<Disabled>)>,
+    locale: Option<Res<Locale>>,
+) {
+    if keys.just_pressed(KeyCode::Tab) {
+        let mut order: Vec<Entity> = focusable.iter().collect();
+        order.sort();
+        // Tabbing past the last element clears the focus.
+        focus.0 = match focus.0.and_then(|f| order.iter().position(|e| *e == f)) {
+            Some(index) => order.get(index + 1).copied(),
+            None => order.first().copied(),
+        };
+    }
+    for (entity, GalleryRow(name), checked, disabled) in rows.iter() {
+        match *name {
+            "checked" if keys.just_pressed(KeyCode::Space) => {
+                if checked {
+                    commands.entity(entity).remove::<Checked>();
+                } else {
+                    commands.entity(entity).insert(Checked);
+                }
+            }
+            "disabled" if keys.just_pressed(KeyCode::D) => {
+                if disabled {
+                    commands.entity(entity).remove::<Disabled>();
+                } else {
+                    commands.entity(entity).insert(Disabled);
+                }
+            }
+            _ => {}
+        }
+    }
+    if keys.just_pressed(KeyCode::L) {
+        match locale {
+            Some(locale) if locale.matches("de") => commands.insert_resource(Locale::new("en")),
+            _ => commands.insert_resource(Locale::new("de")),
+        }
+    }
+}
@@ -0,0 +1,92 @@
+//! Setup code shared by the gallery, transitions, theming and stylesheet examples. Each example
+//! is a plugin, which `main` adds to a windowed app, and which `tests/examples.rs` adds to a
+//! headless app to check the styles it computes.
+
+// Each example only uses its own module.
+#![allow(dead_code)]
+
+use bevy::{
+    a11y::Focus,
+    input::{keyboard::KeyboardInput, ButtonState, InputPlugin},
+    prelude::*,
+    time::TimeUpdateStrategy,
+    utils::Duration,
+};
+use bevy_peacock::PeacockPlugin;
+
+pub mod gallery;
+pub mod stylesheet;
+pub mod theming;
+pub mod transitions;
+
+/// The time step of each update of a headless app, in seconds.
+pub const FRAME: f32 = 0.1;
+
+/// Build an app which shows an example in a window. With the `bevy_mod_picking` feature, the
+/// picking plugins are added too, so that `:hover` and the drag pseudo-classes follow the mouse.
+pub fn windowed_app(title: &str) -> App {
+    let mut app = App::new();
+    app.add_plugins(DefaultPlugins.set(WindowPlugin {
+        primary_window: Some(Window {
+            title: title.into(),
+            ..default()
+        }),
+        ..default()
+    }))
+    .add_plugins(PeacockPlugin)
+    .add_systems(Startup, spawn_camera)
+    .add_systems(Update, bevy::window::close_on_esc);
+    #[cfg(feature = "bevy_mod_picking")]
+    app.add_plugins(bevy_mod_picking::DefaultPickingPlugins);
+    app
+}
+
+/// Build an app which runs the style pass without a window or a renderer, advancing time by
+/// [`FRAME`] each update.
+pub fn headless_app() -> App {
+    let mut app = App::new();
+    app.add_plugins((
+        MinimalPlugins,
+        AssetPlugin::default(),
+        InputPlugin,
+        PeacockPlugin,
+    ))
+    .init_asset::<Image>()
+    .init_asset::<Font>()
+    .init_resource::<Focus>()
+    .insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_secs_f32(
+        FRAME,
+    )));
+    app
+}
+
+/// Press and release `key`, and run one update, which sees it as just pressed.
+pub fn press_key(app: &mut App, key: KeyCode) {
+    for state in [ButtonState::Pressed, ButtonState::Released] {
+        app.world.send_event(KeyboardInput {
+            scan_code: 0,
+            key_code: Some(key),
+            state,
+            window: Entity::PLACEHOLDER,
+        });
+    }
+    app.update();
+}
+
+fn spawn_camera(mut commands: Commands) {
+    commands.spawn(Camera2dBundle::default());
+}
+
+/// Find the entity with the component `C` for which `filter` is true.
+pub fn find<C: Component>(app: &mut App, filter: impl Fn(&C) -> bool) -> Entity {
+    let mut query = app.world.query::<(Entity, &C)>();
+    query
+        .iter(&app.world)
+        .find_map(|(entity, c)| filter(c).then_some(entity))
+        .expect("no matching entity")
+}
+
+/// The background color of `entity`, if it has one.
+pub fn bg_color(app: &App, entity: Entity) -> Option<Color> {
+    app.world.get::<BackgroundColor>(entity).map(|bg| bg.0)
+}
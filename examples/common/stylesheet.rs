@@ -0,0 +1,77 @@
+//! A menu whose appearance comes entirely from `examples/stylesheet.pcss`. Up and Down move the
+//! `selected` class between its items, and the menu narrows when the window does.
+
+use bevy::prelude::*;
+use bevy_peacock::*;
+
+import_stylesheet!(menu_styles, "examples/stylesheet.pcss", deny_warnings);
+
+/// The labels of the menu items.
+pub const ITEMS: &[&str] = &["New Game", "Continue", "Settings", "Credits", "Quit"];
+
+/// The index of the selected menu item.
+#[derive(Resource, Default, Debug, PartialEq, Eq)]
+pub struct Selection(pub usize);
+
+/// Marks each menu item with its index.
+#[derive(Component, Debug)]
+pub struct MenuItem(pub usize);
+
+/// Adds the menu, and the keyboard controls which move its selection.
+pub struct StylesheetPlugin;
+
+impl Plugin for StylesheetPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Selection>()
+            .add_systems(Startup, setup_menu)
+            .add_systems(
+                Update,
+                (handle_keys, select_items).chain().before(PeacockSystemSet),
+            );
+    }
+}
+
+/// Spawn the title and the menu, with the first item selected.
+pub fn setup_menu(mut commands: Commands) {
+    commands
+        .spawn(NodeBundle::default())
+        .with_styles(menu_styles::ROOT.clone())
+        .with_children(|parent| {
+            parent
+                .spawn(TextBundle::from_section("Peacock", TextStyle::default()))
+                .with_styles(menu_styles::TITLE.clone());
+            parent
+                .spawn(NodeBundle::default())
+                .with_styles(menu_styles::MENU.clone())
+                .with_children(|menu| {
+                    for (index, label) in ITEMS.iter().enumerate() {
+                        menu.spawn((NodeBundle::default(), MenuItem(index)))
+                            .with_styles(menu_styles::ITEM.clone())
+                            .class_names("selected".if_true(index == 0))
+                            .with_children(|item| {
+                                item.spawn(TextBundle::from_section(*label, TextStyle::default()))
+                                    .with_styles(menu_styles::ITEM_LABEL.clone());
+                            });
+                    }
+                });
+        });
+}
+
+/// Up and Down move the selection, wrapping around at either end.
+pub fn handle_keys(keys: Res<Input<KeyCode>>, mut selection: ResMut<Selection>) {
+    if keys.just_pressed(KeyCode::Down) {
+        selection.0 = (selection.0 + 1) % ITEMS.len();
+    } else if keys.just_pressed(KeyCode::Up) {
+        selection.0 = (selection.0 + ITEMS.len() - 1) % ITEMS.len();
+    }
+}
+
+/// Give the `selected` class to the selected item, and only to it.
+pub fn select_items(selection: Res<Selection>, mut items: Query<(&MenuItem, &mut ElementClasses)>) {
+    if !selection.is_changed() {
+        return;
+    }
+    for (item, mut classes) in items.iter_mut() {
+        classes.set_class("selected", item.0 == selection.0);
+    }
+}
@@ -0,0 +1,146 @@
+//! A grid of cards whose colors come from theme tokens. T swaps between the light and dark
+//! themes, C toggles the `compact` class of every card, and Right moves the `selected` class to
+//! the next card.
+
+use bevy::prelude::*;
+use bevy_peacock::*;
+
+/// Number of cards in the grid.
+pub const CARD_COUNT: usize = 6;
+
+/// Styles of the grid and its cards, parsed at startup. The colors and spacing are theme
+/// tokens, so swapping the theme restyles everything without touching the styles.
+pub const THEMING_STYLES: &str = "
+GRID {
+    display: flex;
+    flex_wrap: wrap;
+    align_content: flex_start;
+    width: 100%;
+    height: 100%;
+    gap: token(gap);
+    padding: token(gap);
+    background_color: token(backdrop);
+}
+
+CARD {
+    width: 160px;
+    height: 120px;
+    padding: token(gap);
+    border: 3px;
+    background_color: token(surface);
+    border_color: token(surface);
+    .selected { border_color: token(accent); }
+    .compact { width: 80px; height: 60px; }
+}
+
+CARD_LABEL {
+    color: token(text);
+}
+";
+
+/// The light theme, which the example starts with.
+pub fn light_theme() -> Theme {
+    Theme::new()
+        .with("backdrop", Color::rgb(0.85, 0.85, 0.9))
+        .with("surface", Color::WHITE)
+        .with("text", Color::BLACK)
+        .with("accent", Color::rgb(0.1, 0.4, 0.9))
+        .with("gap", Val::Px(12.))
+}
+
+/// The dark theme, which has the same spacing as the light one.
+pub fn dark_theme() -> Theme {
+    light_theme()
+        .with("backdrop", Color::rgb(0.05, 0.05, 0.08))
+        .with("surface", Color::rgb(0.2, 0.2, 0.25))
+        .with("text", Color::WHITE)
+        .with("accent", Color::rgb(0.9, 0.6, 0.1))
+}
+
+/// Which of the two themes is in use.
+#[derive(Resource, Default, Debug, PartialEq, Eq)]
+pub struct DarkMode(pub bool);
+
+/// Marks each card with its position in the grid.
+#[derive(Component, Debug)]
+pub struct Card(pub usize);
+
+/// Adds the grid of cards, the themes, and the keyboard controls which swap them.
+pub struct ThemingPlugin;
+
+impl Plugin for ThemingPlugin {
+    fn build(&self, app: &mut App) {
+        app.world
+            .resource_mut::<StyleRegistry>()
+            .register_sheet(parse_stylesheet(THEMING_STYLES).unwrap());
+        app.init_resource::<DarkMode>()
+            .insert_resource(light_theme())
+            .add_systems(Startup, setup_theming)
+            .add_systems(
+                Update,
+                (handle_keys, apply_theme).chain().before(PeacockSystemSet),
+            );
+    }
+}
+
+/// Spawn the grid of cards, with the first one selected.
+pub fn setup_theming(mut commands: Commands, registry: Res<StyleRegistry>) {
+    let style = |name| registry.get(name).unwrap();
+    commands
+        .spawn(NodeBundle::default())
+        .with_styles(style("GRID"))
+        .with_children(|parent| {
+            for index in 0..CARD_COUNT {
+                parent
+                    .spawn((NodeBundle::default(), Card(index)))
+                    .with_styles(style("CARD"))
+                    .class_names("selected".if_true(index == 0))
+                    .with_children(|card| {
+                        card.spawn(TextBundle::from_section(
+                            format!("Card {}", index + 1),
+                            TextStyle::default(),
+                        ))
+                        .with_styles(style("CARD_LABEL"));
+                    });
+            }
+        });
+}
+
+/// T toggles dark mode, C toggles the `compact` class of every card, and Right moves the
+/// `selected` class to the next card.
+pub fn handle_keys(
+    keys: Res<Input<KeyCode>>,
+    mut dark_mode: ResMut<DarkMode>,
+    mut cards: Query<(&Card, &mut ElementClasses)>,
+) {
+    if keys.just_pressed(KeyCode::T) {
+        dark_mode.0 = !dark_mode.0;
+    }
+    let compact = keys.just_pressed(KeyCode::C);
+    let selected = keys.just_pressed(KeyCode::Right).then(|| {
+        cards
+            .iter()
+            .find(|(_, classes)| classes.has_class("selected"))
+            .map_or(0, |(card, _)| (card.0 + 1) % CARD_COUNT)
+    });
+    for (card, mut classes) in cards.iter_mut() {
+        if compact {
+            let was_compact = classes.has_class("compact");
+            classes.set_class("compact", !was_compact);
+        }
+        if let Some(selected) = selected {
+            classes.set_class("selected", card.0 == selected);
+        }
+    }
+}
+
+/// Replace the theme with the one chosen by [`DarkMode`] when it changes, which restyles
+/// every element.
+pub fn apply_theme(dark_mode: Res<DarkMode>, mut theme: ResMut<Theme>) {
+    if dark_mode.is_changed() && !dark_mode.is_added() {
+        *theme = match dark_mode.0 {
+            true => dark_theme(),
+            false => light_theme(),
+        };
+    }
+}
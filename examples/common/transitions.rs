@@ -0,0 +1,153 @@
+//! A box which grows, turns and changes the color of its outline when Space toggles its `active` class. Up and
+//! Down change how long the transitions take, by replacing the contents of the box's style
+//! handle.
+
+use std::f32::consts::PI;
+
+use bevy::prelude::*;
+use bevy_peacock::*;
+
+/// Duration of the transitions when the example starts, in seconds.
+pub const INITIAL_DURATION: f32 = 1.;
+
+/// Amount by which Up and Down change the duration, which is kept between zero and
+/// [`MAX_DURATION`].
+pub const DURATION_STEP: f32 = 0.25;
+
+/// The longest duration which Up can set.
+pub const MAX_DURATION: f32 = 3.;
+
+/// Color of the box's outline while it's inactive.
+pub const INACTIVE_COLOR: Color = Color::rgb(0.2, 0.4, 0.9);
+
+/// Color of the box's outline while it's active.
+pub const ACTIVE_COLOR: Color = Color::rgb(0.9, 0.5, 0.1);
+
+/// The current duration of the transitions, and the style handle of the box, which is rebuilt
+/// whenever the duration changes.
+#[derive(Resource)]
+pub struct TransitionDemo {
+    /// Duration of each transition, in seconds.
+    pub duration: f32,
+    /// Style of the animated box.
+    pub style: StyleHandle,
+}
+
+/// Marks the animated box.
+#[derive(Component, Debug)]
+pub struct AnimatedBox;
+
+/// Marks the label showing the duration and the keyboard controls.
+#[derive(Component, Debug)]
+pub struct DurationLabel;
+
+/// Build the style of the box, whose width, transform and outline color transition over
+/// `duration` seconds.
+pub fn box_style(duration: f32) -> StylePropList {
+    let transition = |property| Transition {
+        property,
+        duration,
+        timing: timing::EASE_IN_OUT,
+        ..default()
+    };
+    StylePropList::build(|ss| {
+        ss.width(100.)
+            .height(100.)
+            .background_color(Color::DARK_GRAY)
+            .outline_width(4.)
+            .outline_color(INACTIVE_COLOR)
+            .transition(&[
+                transition(TransitionProperty::Width),
+                transition(TransitionProperty::Transform),
+                transition(TransitionProperty::OutlineColor),
+            ])
+            .selector(".active", |ss| {
+                ss.width(300.)
+                    .rotation(PI / 4.)
+                    .scale(1.5)
+                    .outline_color(ACTIVE_COLOR)
+            })
+    })
+}
+
+/// Adds the box, and the keyboard controls which animate it.
+pub struct TransitionsPlugin;
+
+impl Plugin for TransitionsPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(TransitionDemo {
+            duration: INITIAL_DURATION,
+            style: StyleHandle::new(box_style(INITIAL_DURATION)),
+        })
+        .add_systems(Startup, setup_transitions)
+        .add_systems(
+            Update,
+            (handle_keys, update_label).chain().before(PeacockSystemSet),
+        );
+    }
+}
+
+/// Spawn the label, and the box with its style.
+pub fn setup_transitions(mut commands: Commands, demo: Res<TransitionDemo>) {
+    commands
+        .spawn(NodeBundle {
+            style: Style {
+                flex_direction: FlexDirection::Column,
+                row_gap: Val::Px(48.),
+                padding: UiRect::all(Val::Px(32.)),
+                ..default()
+            },
+            ..default()
+        })
+        .with_children(|parent| {
+            parent.spawn((
+                TextBundle::from_section("", TextStyle::default()),
+                DurationLabel,
+            ));
+            parent
+                .spawn((
+                    NodeBundle::default(),
+                    ElementClasses::default(),
+                    AnimatedBox,
+                ))
+                .with_styles(demo.style.clone());
+        });
+}
+
+/// Space toggles the box's `active` class, and Up and Down change the duration.
+pub fn handle_keys(
+    keys: Res<Input<KeyCode>>,
+    mut demo: ResMut<TransitionDemo>,
+    mut boxes: Query<&mut ElementClasses, With<AnimatedBox>>,
+) {
+    if keys.just_pressed(KeyCode::Space) {
+        for mut classes in boxes.iter_mut() {
+            let active = classes.has_class("active");
+            classes.set_class("active", !active);
+        }
+    }
+    let duration = if keys.just_pressed(KeyCode::Up) {
+        (demo.duration + DURATION_STEP).min(MAX_DURATION)
+    } else if keys.just_pressed(KeyCode::Down) {
+        (demo.duration - DURATION_STEP).max(0.)
+    } else {
+        return;
+    };
+    if duration != demo.duration {
+        demo.duration = duration;
+        // Every element using the handle is restyled with the new transitions.
+        demo.style.replace(box_style(duration));
+    }
+}
+
+fn update_label(demo: Res<TransitionDemo>, mut labels: Query<&mut Text, With<DurationLabel>>) {
+    if !demo.is_changed() {
+        return;
+    }
+    for mut text in labels.iter_mut() {
+        text.sections[0].value = format!(
+            "Duration: {:.2}s. Space toggles the box, Up and Down change the duration.",
+            demo.duration
+        );
+    }
+}
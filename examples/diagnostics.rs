@@ -0,0 +1,68 @@
+//! Example of the style pass diagnostics. A grid of cells is restyled every half second, and
+//! the diagnostics are printed to the log each second.
+
+use bevy::{diagnostic::LogDiagnosticsPlugin, prelude::*};
+use bevy_peacock::*;
+use static_init::dynamic;
+
+#[dynamic]
+static GRID: StyleHandle = StyleHandle::build(|ss| {
+    ss.display(Display::Flex)
+        .flex_wrap(FlexWrap::Wrap)
+        .width(Val::Percent(100.))
+        .height(Val::Percent(100.))
+});
+
+#[dynamic]
+static CELL: StyleHandle = StyleHandle::build(|ss| {
+    ss.width(40.)
+        .height(40.)
+        .margin(2.)
+        .background_color(Color::DARK_GRAY)
+        .selector(".lit > &", |ss| ss.background_color(Color::GOLD))
+        .selector(".lit > &.even", |ss| ss.background_color(Color::ORANGE))
+});
+
+fn main() {
+    App::new()
+        .add_plugins(DefaultPlugins)
+        .add_plugins((
+            PeacockPlugin,
+            PeacockDiagnosticsPlugin,
+            LogDiagnosticsPlugin::default(),
+        ))
+        .add_systems(Startup, setup_view_root)
+        .add_systems(Update, (bevy::window::close_on_esc, toggle_lit))
+        .run();
+}
+
+fn setup_view_root(mut commands: Commands) {
+    commands.spawn(Camera2dBundle::default());
+    commands
+        .spawn((NodeBundle::default(), ElementClasses::default()))
+        .with_styles(GRID.clone())
+        .with_children(|parent| {
+            for index in 0..400 {
+                parent
+                    .spawn(NodeBundle::default())
+                    .with_styles(CELL.clone())
+                    .class_names("even".if_true(index % 2 == 0));
+            }
+        });
+}
+
+/// Toggle the `lit` class of the grid, which restyles every cell.
+fn toggle_lit(
+    time: Res<Time>,
+    mut timer: Local<Option<Timer>>,
+    mut query: Query<&mut ElementClasses, Without<Parent>>,
+) {
+    let timer = timer.get_or_insert_with(|| Timer::from_seconds(0.5, TimerMode::Repeating));
+    if !timer.tick(time.delta()).just_finished() {
+        return;
+    }
+    for mut classes in query.iter_mut() {
+        let lit = classes.has_class("lit");
+        classes.set_class("lit", !lit);
+    }
+}
@@ -0,0 +1,15 @@
+//! Example of every pseudo-class. Each row is highlighted while its pseudo-class matches it: Tab
+//! moves the focus, Space and D toggle the checked and disabled rows, and L switches the locale.
+//! Run with `--features bevy_mod_picking` to hover over and drag the rows.
+//!
+//! The setup lives in `common/gallery.rs`, which `tests/examples.rs` also runs headlessly.
+
+mod common;
+
+use common::gallery::GalleryPlugin;
+
+fn main() {
+    common::windowed_app("Peacock gallery")
+        .add_plugins(GalleryPlugin)
+        .run();
+}
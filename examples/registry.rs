@@ -0,0 +1,68 @@
+//! Example of looking up styles by name, as when the style names come from a data file.
+
+use bevy::prelude::*;
+use bevy_peacock::*;
+
+import_stylesheet!(test_styles, "examples/styles.pss", spans = true);
+
+/// Styles defined at runtime, e.g. loaded from a file.
+const RUNTIME_STYLES: &str = "
+PANEL {
+    display: flex;
+    flex_direction: column;
+    padding: 8;
+    border: 2;
+    border_color: #ff00ff;
+}
+";
+
+/// Stand-in for a UI description loaded from a config file: each line is a style name,
+/// followed by the text of the label.
+const LAYOUT: &str = "
+PANEL: Loaded from a runtime stylesheet
+BUTTON: Loaded from a compile-time stylesheet
+";
+
+fn main() {
+    App::new()
+        .add_plugins(DefaultPlugins)
+        .add_plugins(PeacockPlugin)
+        .add_systems(Startup, (register_styles, setup_view_root).chain())
+        .add_systems(Update, bevy::window::close_on_esc)
+        .run();
+}
+
+fn register_styles(mut registry: ResMut<StyleRegistry>) {
+    test_styles::register_styles(&mut registry);
+    registry.register_sheet(parse_stylesheet(RUNTIME_STYLES).unwrap());
+}
+
+fn setup_view_root(mut commands: Commands, registry: Res<StyleRegistry>) {
+    commands.spawn(Camera2dBundle::default());
+    commands
+        .spawn(NodeBundle::default())
+        .with_styles(test_styles::ROOT.clone())
+        .with_children(|parent| {
+            for line in LAYOUT.lines().filter(|line| !line.is_empty()) {
+                let (style_name, label) = line.split_once(':').unwrap();
+                let Some(style) = registry.get(style_name) else {
+                    warn!("Unknown style: {}", style_name);
+                    continue;
+                };
+                if let Some(source) = style.source() {
+                    info!(
+                        "Style {} defined at {}:{}",
+                        style_name,
+                        source.path.as_deref().unwrap_or("<runtime>"),
+                        source.rule.start.line
+                    );
+                }
+                parent
+                    .spawn(NodeBundle::default())
+                    .with_styles(style)
+                    .with_children(|parent| {
+                        parent.spawn(TextBundle::from_section(label.trim(), TextStyle::default()));
+                    });
+            }
+        });
+}
@@ -0,0 +1,14 @@
+//! Example of a UI styled entirely by an imported stylesheet, `examples/stylesheet.pcss`. Up and
+//! Down move the selection, and the menu narrows when the window is narrower than 600 pixels.
+//!
+//! The setup lives in `common/stylesheet.rs`, which `tests/examples.rs` also runs headlessly.
+
+mod common;
+
+use common::stylesheet::StylesheetPlugin;
+
+fn main() {
+    common::windowed_app("Peacock stylesheet")
+        .add_plugins(StylesheetPlugin)
+        .run();
+}
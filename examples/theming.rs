@@ -0,0 +1,14 @@
+//! Example of swapping themes and class names at runtime. T toggles the dark theme, C toggles
+//! compact cards, and Right moves the selection.
+//!
+//! The setup lives in `common/theming.rs`, which `tests/examples.rs` also runs headlessly.
+
+mod common;
+
+use common::theming::ThemingPlugin;
+
+fn main() {
+    common::windowed_app("Peacock theming")
+        .add_plugins(ThemingPlugin)
+        .run();
+}
@@ -0,0 +1,63 @@
+//! Example of inspecting where an element's properties came from. Press F12 to print the style
+//! trace of the element under the cursor.
+
+use bevy::prelude::*;
+use bevy_peacock::*;
+
+import_stylesheet!(test_styles, "examples/styles.pss");
+
+fn main() {
+    App::new()
+        .add_plugins(DefaultPlugins)
+        .add_plugins(PeacockPlugin)
+        .insert_resource(PeacockConfig {
+            trace_styles: true,
+            ..default()
+        })
+        .add_systems(Startup, (register_styles, setup_view_root).chain())
+        .add_systems(Update, (bevy::window::close_on_esc, print_trace))
+        .run();
+}
+
+fn register_styles(mut registry: ResMut<StyleRegistry>) {
+    test_styles::register_styles(&mut registry);
+}
+
+fn setup_view_root(mut commands: Commands) {
+    commands.spawn(Camera2dBundle::default());
+    commands
+        .spawn(NodeBundle::default())
+        .with_styles(test_styles::ROOT.clone())
+        .with_children(|parent| {
+            for label in ["Button1", "Button2"] {
+                parent
+                    .spawn((
+                        NodeBundle::default(),
+                        Interaction::default(),
+                        Name::new(label),
+                    ))
+                    .with_styles(test_styles::BUTTON.clone())
+                    .with_children(|parent| {
+                        parent.spawn(TextBundle::from_section(label, TextStyle::default()));
+                    });
+            }
+        });
+}
+
+fn print_trace(
+    keys: Res<Input<KeyCode>>,
+    query: Query<(Entity, &Interaction, &DebugStyleTrace, Option<&Name>)>,
+) {
+    if !keys.just_pressed(KeyCode::F12) {
+        return;
+    }
+    for (entity, interaction, trace, name) in query.iter() {
+        if *interaction == Interaction::None {
+            continue;
+        }
+        match name {
+            Some(name) => info!("Styles of '{}' ({:?}):\n{}", name, entity, trace),
+            None => info!("Styles of {:?}:\n{}", entity, trace),
+        }
+    }
+}
@@ -0,0 +1,14 @@
+//! Example of transitions of layout, transform and color properties. Space toggles the box, and
+//! Up and Down change how long its transitions take.
+//!
+//! The setup lives in `common/transitions.rs`, which `tests/examples.rs` also runs headlessly.
+
+mod common;
+
+use common::transitions::TransitionsPlugin;
+
+fn main() {
+    common::windowed_app("Peacock transitions")
+        .add_plugins(TransitionsPlugin)
+        .run();
+}
@@ -1,36 +1,385 @@
 use bevy::{prelude::*, ui, utils::HashMap};
-use bevy_peacock_style::TransitionState;
+use bevy_peacock_style::{TimingFunction, Transition, TransitionState};
 
-use crate::TransitionProperty;
+use crate::{PeacockPropertyMask, TransitionProperty};
 
-#[derive(Component)]
+/// Animation of an element's transform. The scale, rotation and translation each have their
+/// own clock, so that they can be animated with different timings, and retargeting one doesn't
+/// restart the others. Parts without a transition are set to the target immediately.
+#[derive(Component, Default, Reflect)]
+#[reflect(Component)]
 #[doc(hidden)]
 pub struct AnimatedTransform {
-    pub(crate) state: TransitionState,
+    pub(crate) scale: Option<TransitionState>,
+    pub(crate) rotation: Option<TransitionState>,
+    pub(crate) translation: Option<TransitionState>,
+    /// The value each animated part started from.
     pub(crate) origin: Transform,
     pub(crate) target: Transform,
 }
 
-#[derive(Component)]
+/// The parts of a [`Transform`] which can be animated independently.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum TransformPart {
+    Scale,
+    Rotation,
+    Translation,
+}
+
+impl TransformPart {
+    pub(crate) const ALL: [TransformPart; 3] = [
+        TransformPart::Scale,
+        TransformPart::Rotation,
+        TransformPart::Translation,
+    ];
+
+    /// The transition property which animates only this part.
+    fn property(self) -> TransitionProperty {
+        match self {
+            TransformPart::Scale => TransitionProperty::Scale,
+            TransformPart::Rotation => TransitionProperty::Rotation,
+            TransformPart::Translation => TransitionProperty::Translation,
+        }
+    }
+
+    /// Return the transition which animates this part: either one for the part itself, or
+    /// the `transform` shorthand.
+    pub(crate) fn transition(self, transitions: &[Transition]) -> Option<&Transition> {
+        transitions
+            .iter()
+            .find(|tr| tr.property == self.property())
+            .or_else(|| {
+                transitions
+                    .iter()
+                    .find(|tr| tr.property == TransitionProperty::Transform)
+            })
+    }
+
+    fn differs(self, a: &Transform, b: &Transform) -> bool {
+        match self {
+            TransformPart::Scale => a.scale != b.scale,
+            TransformPart::Rotation => a.rotation != b.rotation,
+            TransformPart::Translation => a.translation != b.translation,
+        }
+    }
+
+    fn copy(self, from: &Transform, to: &mut Transform) {
+        match self {
+            TransformPart::Scale => to.scale = from.scale,
+            TransformPart::Rotation => to.rotation = from.rotation,
+            TransformPart::Translation => to.translation = from.translation,
+        }
+    }
+
+    fn lerp(self, origin: &Transform, target: &Transform, t: f32, to: &mut Transform) {
+        match self {
+            TransformPart::Scale => to.scale = origin.scale.lerp(target.scale, t),
+            TransformPart::Rotation => to.rotation = origin.rotation.lerp(target.rotation, t),
+            TransformPart::Translation => {
+                to.translation = origin.translation.lerp(target.translation, t)
+            }
+        }
+    }
+}
+
+impl AnimatedTransform {
+    /// Create an animation which starts at `target`, with a clock for each part which has a
+    /// transition.
+    pub(crate) fn new(target: Transform, transitions: &[Transition]) -> Self {
+        let mut at = Self {
+            origin: target,
+            target,
+            ..default()
+        };
+        for part in TransformPart::ALL {
+            *at.state_mut(part) = part.transition(transitions).map(|tr| TransitionState {
+                transition: tr.clone(),
+                clock: 0.,
+            });
+        }
+        at
+    }
+
+    pub(crate) fn state(&self, part: TransformPart) -> Option<&TransitionState> {
+        match part {
+            TransformPart::Scale => self.scale.as_ref(),
+            TransformPart::Rotation => self.rotation.as_ref(),
+            TransformPart::Translation => self.translation.as_ref(),
+        }
+    }
+
+    fn state_mut(&mut self, part: TransformPart) -> &mut Option<TransitionState> {
+        match part {
+            TransformPart::Scale => &mut self.scale,
+            TransformPart::Rotation => &mut self.rotation,
+            TransformPart::Translation => &mut self.translation,
+        }
+    }
+
+    /// Return whether [`retarget`](Self::retarget) would change anything.
+    pub(crate) fn needs_retarget(&self, target: &Transform, transitions: &[Transition]) -> bool {
+        TransformPart::ALL.into_iter().any(|part| {
+            part.differs(&self.target, target)
+                || self.state(part).is_some() != part.transition(transitions).is_some()
+        })
+    }
+
+    /// Animate towards a new target, starting from `current`. Only the parts whose target
+    /// changed are restarted, except that parts animated by the `transform` shorthand share a
+    /// clock, so they restart together.
+    pub(crate) fn retarget(
+        &mut self,
+        current: &Transform,
+        target: Transform,
+        transitions: &[Transition],
+    ) {
+        let is_shorthand = |part: TransformPart| {
+            part.transition(transitions)
+                .is_some_and(|tr| tr.property == TransitionProperty::Transform)
+        };
+        let shorthand_changed = TransformPart::ALL
+            .into_iter()
+            .any(|part| is_shorthand(part) && part.differs(&self.target, &target));
+        for part in TransformPart::ALL {
+            let restart =
+                part.differs(&self.target, &target) || (shorthand_changed && is_shorthand(part));
+            let transition = part.transition(transitions);
+            let state = self.state_mut(part);
+            match transition {
+                Some(tr) if restart || state.is_none() => {
+                    *state = Some(TransitionState {
+                        transition: tr.clone(),
+                        clock: 0.,
+                    });
+                    part.copy(current, &mut self.origin);
+                }
+                Some(_) => {}
+                None => *state = None,
+            }
+        }
+        self.target = target;
+    }
+
+    /// Return whether any part of the transform is still animating. A new animation starts at
+    /// its target, so it isn't running until it's retargeted.
+    pub(crate) fn is_running(&self) -> bool {
+        TransformPart::ALL.into_iter().any(|part| {
+            part.differs(&self.origin, &self.target)
+                && self.state(part).is_some_and(|state| state.clock < 1.)
+        })
+    }
+
+    /// Return the current value of the animation, advancing each clock by `delta`.
+    fn advance(&mut self, delta: f32) -> Transform {
+        let mut value = self.target;
+        let AnimatedTransform {
+            scale,
+            rotation,
+            translation,
+            origin,
+            target,
+        } = self;
+        for (part, state) in [
+            (TransformPart::Scale, scale),
+            (TransformPart::Rotation, rotation),
+            (TransformPart::Translation, translation),
+        ] {
+            if let Some(state) = state {
+                state.advance(delta);
+                let t = state.transition.timing.eval(state.clock);
+                part.lerp(origin, target, t, &mut value);
+            }
+        }
+        value
+    }
+}
+
+/// Animation of an element's [`BackgroundColor`], which is the fill color of a node without
+/// an image, and the tint of one with an image.
+#[derive(Component, Default, Reflect)]
+#[reflect(Component)]
 #[doc(hidden)]
-pub struct AnimatedBackgroundColor {
-    pub(crate) state: TransitionState,
-    pub(crate) origin: Color,
-    pub(crate) target: Color,
+pub struct AnimatedBackgroundColor(pub(crate) AnimatedColor);
+
+/// Animation of an element's [`BorderColor`].
+#[derive(Component, Default, Reflect)]
+#[reflect(Component)]
+#[doc(hidden)]
+pub struct AnimatedBorderColor(pub(crate) AnimatedColor);
+
+/// Global settings for style animations.
+#[derive(Resource, Debug, Clone)]
+pub struct PeacockAnimationConfig {
+    /// The minimum change, in pixels, before a new animated layout value is written to the
+    /// [`Style`]. Writing to `Style` invalidates the layout of the subtree, so skipping
+    /// imperceptible changes can save a lot of layout passes when many elements are animating.
+    /// The final value of an animation is always written exactly. Defaults to 0 (off); can be
+    /// overridden for individual transitions with `quantize(<px>)`.
+    pub layout_quantum: f32,
+
+    /// The amount added to the [`ZIndex`] of an element with `elevate_while_animating: true`
+    /// while its transform is animating, so that it draws over its siblings. An element
+    /// without a `ZIndex` is raised to `ZIndex::Local` of this amount. Defaults to 1000.
+    pub elevation: i32,
 }
 
+impl Default for PeacockAnimationConfig {
+    fn default() -> Self {
+        Self {
+            layout_quantum: 0.,
+            elevation: 1000,
+        }
+    }
+}
+
+/// Marks an element whose styles set `elevate_while_animating: true`.
 #[derive(Component)]
-#[doc(hidden)]
-pub struct AnimatedBorderColor {
-    pub(crate) state: TransitionState,
-    pub(crate) origin: Color,
-    pub(crate) target: Color,
+pub(crate) struct ElevateWhileAnimating;
+
+/// The [`ZIndex`] which an element had before it was raised for a transform animation, which
+/// is restored when the animation finishes. Styles which change the z-index while the element
+/// is raised update this instead.
+#[derive(Component, Clone, Copy, Debug)]
+pub(crate) struct ElevatedZIndex {
+    /// The z-index to restore, or `None` to remove it.
+    pub(crate) prior: Option<ZIndex>,
+    /// The amount the z-index was raised by.
+    pub(crate) elevation: i32,
+}
+
+impl ElevatedZIndex {
+    /// The z-index to use while raised.
+    pub(crate) fn raised(&self) -> ZIndex {
+        match self.prior {
+            Some(ZIndex::Global(z)) => ZIndex::Global(z.saturating_add(self.elevation)),
+            Some(ZIndex::Local(z)) => ZIndex::Local(z.saturating_add(self.elevation)),
+            None => ZIndex::Local(self.elevation),
+        }
+    }
+}
+
+/// A component with lengths which can be animated by an [`AnimatedLayoutProp`].
+pub trait AnimatedLengths {
+    /// Return the current value of `prop`, or `None` if this component doesn't hold it.
+    fn length(&self, prop: TransitionProperty) -> Option<ui::Val>;
+
+    /// Set `prop` to a length in pixels.
+    fn set_length(&mut self, prop: TransitionProperty, value: f32);
+
+    /// Set `prop` to a length in any unit. Components which only hold pixel lengths ignore
+    /// other units.
+    fn set_val(&mut self, prop: TransitionProperty, val: ui::Val) {
+        if let ui::Val::Px(value) = val {
+            self.set_length(prop, value);
+        }
+    }
+}
+
+impl AnimatedLengths for Style {
+    fn length(&self, prop: TransitionProperty) -> Option<ui::Val> {
+        prop.layout_val(self)
+    }
+
+    fn set_length(&mut self, prop: TransitionProperty, value: f32) {
+        self.set_val(prop, ui::Val::Px(value));
+    }
+
+    fn set_val(&mut self, prop: TransitionProperty, val: ui::Val) {
+        match prop {
+            TransitionProperty::Width => self.width = val,
+            TransitionProperty::Height => self.height = val,
+            TransitionProperty::Left => self.left = val,
+            TransitionProperty::Top => self.top = val,
+            TransitionProperty::Bottom => self.bottom = val,
+            TransitionProperty::Right => self.right = val,
+            TransitionProperty::BorderLeft => self.border.left = val,
+            TransitionProperty::BorderTop => self.border.top = val,
+            TransitionProperty::BorderRight => self.border.right = val,
+            TransitionProperty::BorderBottom => self.border.bottom = val,
+            TransitionProperty::PaddingLeft => self.padding.left = val,
+            TransitionProperty::PaddingRight => self.padding.right = val,
+            TransitionProperty::PaddingTop => self.padding.top = val,
+            TransitionProperty::PaddingBottom => self.padding.bottom = val,
+            TransitionProperty::MarginLeft => self.margin.left = val,
+            TransitionProperty::MarginRight => self.margin.right = val,
+            TransitionProperty::MarginTop => self.margin.top = val,
+            TransitionProperty::MarginBottom => self.margin.bottom = val,
+            TransitionProperty::RowGap => self.row_gap = val,
+            TransitionProperty::ColumnGap => self.column_gap = val,
+            _ => panic!("Invalid style transition prop"),
+        }
+    }
+}
+
+/// Return the length which percentages of a layout property are relative to: the parent's
+/// height for vertical properties, and its width for the rest, including all of the borders,
+/// padding and margins. Gaps are relative to the element's own size, which isn't known until
+/// layout, so they're approximated with the parent's; the declared value is written exactly
+/// once the animation finishes.
+pub(crate) fn percent_basis(prop: TransitionProperty, parent_size: Vec2) -> f32 {
+    match prop {
+        TransitionProperty::Height
+        | TransitionProperty::Top
+        | TransitionProperty::Bottom
+        | TransitionProperty::RowGap => parent_size.y,
+        _ => parent_size.x,
+    }
+}
+
+impl AnimatedLengths for Outline {
+    fn length(&self, prop: TransitionProperty) -> Option<ui::Val> {
+        match prop {
+            TransitionProperty::OutlineWidth => Some(self.width),
+            TransitionProperty::OutlineOffset => Some(self.offset),
+            _ => None,
+        }
+    }
+
+    fn set_length(&mut self, prop: TransitionProperty, value: f32) {
+        match prop {
+            TransitionProperty::OutlineWidth => self.width = ui::Val::Px(value),
+            TransitionProperty::OutlineOffset => self.offset = ui::Val::Px(value),
+            _ => panic!("Invalid outline transition prop"),
+        }
+    }
 }
 
+/// The font size of every section of a text is animated together, starting from the size of
+/// the first section.
+impl AnimatedLengths for Text {
+    fn length(&self, prop: TransitionProperty) -> Option<ui::Val> {
+        match prop {
+            TransitionProperty::FontSize => self
+                .sections
+                .first()
+                .map(|section| ui::Val::Px(section.style.font_size)),
+            _ => None,
+        }
+    }
+
+    fn set_length(&mut self, prop: TransitionProperty, value: f32) {
+        match prop {
+            TransitionProperty::FontSize => {
+                for section in self.sections.iter_mut() {
+                    section.style.font_size = value;
+                }
+            }
+            _ => panic!("Invalid text transition prop"),
+        }
+    }
+}
+
+#[derive(Default, Reflect)]
 pub struct AnimatedLayoutProp {
     pub(crate) state: TransitionState,
     pub(crate) origin: f32,
     pub(crate) target: f32,
+    /// The target as declared in the style, if it's in units other than pixels. The animation
+    /// runs in pixels, but this is written once it finishes, so that the layout goes on
+    /// following the size of the parent or viewport.
+    pub(crate) target_val: Option<ui::Val>,
+    #[reflect(ignore)]
+    pub(crate) last_written: Option<f32>,
 }
 
 impl AnimatedLayoutProp {
@@ -39,133 +388,639 @@ impl AnimatedLayoutProp {
             state,
             origin: 0.,
             target: 0.,
+            target_val: None,
+            last_written: None,
         }
     }
 
-    /// Update the [`Style`] component with the current animation value.
-    pub fn update(&mut self, prop: TransitionProperty, style: &mut Style, delta: f32, force: bool) {
+    /// Create a finished animation which holds `value`, so that retargeting it animates from
+    /// `value`.
+    pub(crate) fn starting_at(transition: Transition, value: f32) -> Self {
+        Self {
+            state: TransitionState {
+                transition,
+                clock: 1.,
+            },
+            origin: value,
+            target: value,
+            target_val: None,
+            last_written: None,
+        }
+    }
+
+    /// The current value of the animation.
+    pub(crate) fn value(&self) -> f32 {
+        let t = self.state.t();
+        self.target * t + self.origin * (1. - t)
+    }
+
+    /// Update the component holding the property with the current animation value.
+    pub fn update(
+        &mut self,
+        prop: TransitionProperty,
+        target: &mut impl AnimatedLengths,
+        delta: f32,
+        force: bool,
+    ) {
+        if let Some(value) = self.advance(delta, 0., force) {
+            self.write(prop, target, value);
+        }
+    }
+
+    /// Write a value returned by [`advance`](Self::advance): the declared target once the
+    /// animation has finished, otherwise the value in pixels.
+    fn write(&self, prop: TransitionProperty, target: &mut impl AnimatedLengths, value: f32) {
+        match self.target_val {
+            Some(val) if self.state.clock >= 1. => target.set_val(prop, val),
+            _ => target.set_length(prop, value),
+        }
+    }
+
+    /// Advance the animation clock, and return the value which should be written to the style,
+    /// if any. Values which differ from the last written value by less than `quantum` are
+    /// skipped, unless `force` is set or the animation has finished.
+    pub(crate) fn advance(&mut self, delta: f32, quantum: f32, force: bool) -> Option<f32> {
         let t_old = self.state.clock;
         self.state.advance(delta);
         let t = self.state.transition.timing.eval(self.state.clock);
-        if t != t_old || force {
-            let value = self.target * t + self.origin * (1. - t);
-            match prop {
-                TransitionProperty::Width => style.width = ui::Val::Px(value),
-                TransitionProperty::Height => style.height = ui::Val::Px(value),
-                TransitionProperty::Left => style.left = ui::Val::Px(value),
-                TransitionProperty::Top => style.top = ui::Val::Px(value),
-                TransitionProperty::Bottom => style.bottom = ui::Val::Px(value),
-                TransitionProperty::Right => style.right = ui::Val::Px(value),
-                TransitionProperty::BorderLeft => style.border.left = ui::Val::Px(value),
-                TransitionProperty::BorderTop => style.border.top = ui::Val::Px(value),
-                TransitionProperty::BorderRight => style.border.right = ui::Val::Px(value),
-                TransitionProperty::BorderBottom => style.border.bottom = ui::Val::Px(value),
-                TransitionProperty::Transform
-                | TransitionProperty::BackgroundColor
-                | TransitionProperty::BorderColor => panic!("Invalid style transition prop"),
+        if t == t_old && !force {
+            return None;
+        }
+        let value = self.value();
+        let quantum = self.state.transition.quantum.unwrap_or(quantum);
+        if !force && self.state.clock < 1. {
+            if let Some(last) = self.last_written {
+                if (value - last).abs() < quantum {
+                    return None;
+                }
             }
         }
+        self.last_written = Some(value);
+        Some(value)
     }
 
     /// Restart the animation with a new target if the target changed.
-    pub fn restart_if_changed(
+    pub fn restart_if_changed<T: AnimatedLengths>(
         &mut self,
         prop: TransitionProperty,
-        prev_style: &Style, // The current style values
-        next_style: &Style, // The targets we are going for
+        prev: &T, // The current values
+        next: &T, // The targets we are going for
     ) {
-        let (next, prev) = match prop {
-            TransitionProperty::Width => (next_style.width, prev_style.width),
-            TransitionProperty::Height => (next_style.height, prev_style.height),
-            TransitionProperty::Left => (next_style.left, prev_style.left),
-            TransitionProperty::Top => (next_style.top, prev_style.top),
-            TransitionProperty::Bottom => (next_style.bottom, prev_style.bottom),
-            TransitionProperty::Right => (next_style.right, prev_style.right),
-            TransitionProperty::BorderLeft => (next_style.border.left, prev_style.border.left),
-            TransitionProperty::BorderTop => (next_style.border.top, prev_style.border.top),
-            TransitionProperty::BorderRight => (next_style.border.right, prev_style.border.right),
-            TransitionProperty::BorderBottom => {
-                (next_style.border.bottom, prev_style.border.bottom)
-            }
-            TransitionProperty::Transform
-            | TransitionProperty::BackgroundColor
-            | TransitionProperty::BorderColor => panic!("Invalid style transition prop"),
+        // Assume that all values are in pixels, we don't try and animate in other units.
+        if let (Some(ui::Val::Px(next_value)), Some(ui::Val::Px(prev_value))) =
+            (next.length(prop), prev.length(prop))
+        {
+            self.retarget(prev_value, next_value);
+        }
+    }
+
+    /// Animate from `current` towards `target`, unless `target` is already the target.
+    pub fn retarget(&mut self, current: f32, target: f32) {
+        if self.target != target {
+            self.origin = current;
+            self.target = target;
+            self.state.clock = 0.;
+        }
+    }
+
+    /// Animate from `current` towards `target`, both resolved to pixels, unless `declared`
+    /// (the target as written in the style) is already the target. A percentage target
+    /// whose parent was resized in the meantime isn't restarted, since the declared value is
+    /// written when the animation finishes anyway.
+    pub(crate) fn retarget_resolved(&mut self, current: f32, target: f32, declared: ui::Val) {
+        let declared = (!matches!(declared, ui::Val::Px(_))).then_some(declared);
+        let unchanged = match (declared, self.target_val) {
+            (None, None) => self.target == target,
+            (declared, target_val) => declared == target_val,
         };
+        self.target_val = declared;
+        if !unchanged {
+            self.origin = current;
+            self.target = target;
+            self.state.clock = 0.;
+        }
+    }
+}
 
-        // Assume that all values are in pixels, we don't try and animate in other units.
-        if let (ui::Val::Px(next_value), ui::Val::Px(prev_value)) = (next, prev) {
-            if self.target != next_value {
-                self.origin = prev_value;
-                self.target = next_value;
-                self.state.clock = 0.;
+#[derive(Component, Default, Reflect)]
+#[reflect(Component)]
+#[doc(hidden)]
+pub struct AnimatedLayout(pub HashMap<TransitionProperty, AnimatedLayoutProp>);
+
+/// Interpolate between two colors in linear RGB space. The ends are returned exactly, so that
+/// a finished animation leaves the target color as it was specified.
+pub(crate) fn lerp_color(origin: Color, target: Color, t: f32) -> Color {
+    if t <= 0. {
+        return origin;
+    } else if t >= 1. {
+        return target;
+    }
+    let [r0, g0, b0, a0] = origin.as_linear_rgba_f32();
+    let [r1, g1, b1, a1] = target.as_linear_rgba_f32();
+    Color::rgba_linear(
+        r0 + (r1 - r0) * t,
+        g0 + (g1 - g0) * t,
+        b0 + (b1 - b0) * t,
+        a0 + (a1 - a0) * t,
+    )
+}
+
+#[derive(Default, Reflect)]
+pub struct AnimatedColor {
+    pub(crate) state: TransitionState,
+    pub(crate) origin: Color,
+    pub(crate) target: Color,
+}
+
+impl AnimatedColor {
+    /// Create a finished animation which holds `color`.
+    pub(crate) fn starting_at(transition: Transition, color: Color) -> Self {
+        Self {
+            state: TransitionState {
+                transition,
+                clock: 1.,
+            },
+            origin: color,
+            target: color,
+        }
+    }
+
+    /// Animate from `current` towards `target`, unless `target` is already the target. Colors
+    /// are compared by value, since the same color can be written in different color spaces.
+    pub(crate) fn retarget(&mut self, current: Color, target: Color) {
+        if self.target.as_linear_rgba_f32() != target.as_linear_rgba_f32() {
+            self.origin = current;
+            self.target = target;
+            self.state.clock = 0.;
+        }
+    }
+
+    /// The current value of the animation.
+    pub(crate) fn value(&self) -> Color {
+        lerp_color(self.origin, self.target, self.state.t())
+    }
+
+    /// Advance the animation clock, returning whether the value changed.
+    fn advance(&mut self, delta: f32) -> bool {
+        let t_old = self.state.clock;
+        self.state.advance(delta);
+        self.state.clock != t_old
+    }
+}
+
+/// Animation of an element's [`Outline`]. The width and offset are animated like layout
+/// lengths, and the color is interpolated in linear RGB.
+#[derive(Component, Default, Reflect)]
+#[reflect(Component)]
+#[doc(hidden)]
+pub struct AnimatedOutline {
+    pub(crate) lengths: HashMap<TransitionProperty, AnimatedLayoutProp>,
+    pub(crate) color: Option<AnimatedColor>,
+}
+
+impl AnimatedOutline {
+    /// Animate from the `current` outline towards `next`, restarting the parts whose target
+    /// changed, and then set `next` to the current value of the animation. Parts without a
+    /// transition, and lengths which aren't in pixels, are left at their targets.
+    pub(crate) fn retarget(
+        &mut self,
+        current: &Outline,
+        next: &mut Outline,
+        transitions: &[Transition],
+    ) {
+        let transition = |prop| transitions.iter().find(|tr| tr.property == prop);
+        for prop in [
+            TransitionProperty::OutlineWidth,
+            TransitionProperty::OutlineOffset,
+        ] {
+            match (transition(prop), current.length(prop), next.length(prop)) {
+                (Some(tr), Some(ui::Val::Px(from)), Some(ui::Val::Px(to))) => {
+                    let anim = self
+                        .lengths
+                        .entry(prop)
+                        .or_insert_with(|| AnimatedLayoutProp::starting_at(tr.clone(), from));
+                    anim.retarget(from, to);
+                    adopt_transition(&mut anim.state, tr);
+                    anim.update(prop, next, 0., true);
+                }
+                _ => {
+                    self.lengths.remove(&prop);
+                }
+            }
+        }
+        match transition(TransitionProperty::OutlineColor) {
+            Some(tr) => {
+                let anim = self
+                    .color
+                    .get_or_insert_with(|| AnimatedColor::starting_at(tr.clone(), current.color));
+                anim.retarget(current.color, next.color);
+                adopt_transition(&mut anim.state, tr);
+                next.color = anim.value();
             }
+            None => self.color = None,
         }
     }
 }
 
-#[derive(Component)]
+/// Records that an element's entrance animations have started, so that they're only played
+/// once. Until they finish, at `until` seconds of elapsed time, restyling the element goes on
+/// animating their properties.
+#[derive(Component, Default, Reflect)]
+#[reflect(Component)]
 #[doc(hidden)]
-pub struct AnimatedLayout(pub HashMap<TransitionProperty, AnimatedLayoutProp>);
+pub struct AnimatedIn {
+    pub(crate) until: f32,
+}
+
+/// Animation of the font size of an element's text.
+#[derive(Component, Default, Reflect)]
+#[reflect(Component)]
+#[doc(hidden)]
+pub struct AnimatedFontSize(pub(crate) AnimatedLayoutProp);
+
+/// Validates animation state which was restored from a saved scene, clamping clocks to the
+/// valid range. Entries for properties which no longer have a transition are dropped when
+/// the element is next restyled.
+#[doc(hidden)]
+#[allow(clippy::type_complexity)]
+pub fn fix_restored_animations(
+    mut query: Query<
+        (
+            Option<&mut AnimatedTransform>,
+            Option<&mut AnimatedBackgroundColor>,
+            Option<&mut AnimatedBorderColor>,
+            Option<&mut AnimatedLayout>,
+            Option<&mut AnimatedOutline>,
+            Option<&mut AnimatedFontSize>,
+        ),
+        Or<(
+            Added<AnimatedTransform>,
+            Added<AnimatedBackgroundColor>,
+            Added<AnimatedBorderColor>,
+            Added<AnimatedLayout>,
+            Added<AnimatedOutline>,
+            Added<AnimatedFontSize>,
+        )>,
+    >,
+) {
+    for (transform, bg, border, layout, outline, font_size) in query.iter_mut() {
+        if let Some(mut at) = transform {
+            for part in TransformPart::ALL {
+                if let Some(state) = at.state_mut(part) {
+                    clamp_clock(state);
+                }
+            }
+        }
+        if let Some(mut at) = bg {
+            clamp_clock(&mut at.0.state);
+        }
+        if let Some(mut at) = border {
+            clamp_clock(&mut at.0.state);
+        }
+        if let Some(mut anim) = layout {
+            for trans in anim.0.values_mut() {
+                clamp_clock(&mut trans.state);
+            }
+        }
+        if let Some(mut anim) = outline {
+            let anim = anim.as_mut();
+            for trans in anim.lengths.values_mut() {
+                clamp_clock(&mut trans.state);
+            }
+            if let Some(color) = &mut anim.color {
+                clamp_clock(&mut color.state);
+            }
+        }
+        if let Some(mut anim) = font_size {
+            clamp_clock(&mut anim.0.state);
+        }
+    }
+}
+
+/// Use `transition` for an animation which was just restarted, so that replacing an element's
+/// transitions, for example with a different duration, applies to the next animation. Running
+/// animations keep the transition they started with.
+pub(crate) fn adopt_transition(state: &mut TransitionState, transition: &Transition) {
+    if state.clock == 0. {
+        state.transition = transition.clone();
+    }
+}
+
+fn clamp_clock(state: &mut TransitionState) {
+    if !(0. ..=1.).contains(&state.clock) {
+        // NaN also ends up here; treat it as a finished animation.
+        state.clock = if state.clock < 0. { 0. } else { 1. };
+    }
+}
 
 #[doc(hidden)]
 pub fn animate_transforms(
-    mut query: Query<(&mut Transform, &mut AnimatedTransform)>,
+    mut query: Query<(
+        &mut Transform,
+        &mut AnimatedTransform,
+        Option<&PeacockPropertyMask>,
+    )>,
     time: Res<Time>,
 ) {
-    for (mut trans, mut at) in query.iter_mut() {
-        let t_old = at.state.clock;
-        at.state.advance(time.delta_seconds());
-        let t = at.state.transition.timing.eval(at.state.clock);
-        if t != t_old {
-            trans.scale = at.origin.scale.lerp(at.target.scale, t);
-            trans.translation = at.origin.translation.lerp(at.target.translation, t);
-            trans.rotation = at.origin.rotation.lerp(at.target.rotation, t);
+    for (mut trans, mut at, mask) in query.iter_mut() {
+        // Compose the independently animated parts, leaving those which another system owns,
+        // and only write if the result changed.
+        let mut value = at.advance(time.delta_seconds());
+        if let Some(mask) = mask {
+            mask.keep_masked_transform(&trans, &mut value);
+        }
+        if *trans != value {
+            *trans = value;
+        }
+    }
+}
+
+/// Raise the z-index of elements with `elevate_while_animating: true` while their transform is
+/// animating, and restore it once the animation finishes or is removed.
+#[allow(clippy::type_complexity)]
+pub(crate) fn elevate_animated_transforms(
+    mut commands: Commands,
+    query: Query<
+        (
+            Entity,
+            Option<&AnimatedTransform>,
+            Has<ElevateWhileAnimating>,
+            Option<&ZIndex>,
+            Option<&ElevatedZIndex>,
+        ),
+        Or<(With<ElevateWhileAnimating>, With<ElevatedZIndex>)>,
+    >,
+    config: Res<PeacockAnimationConfig>,
+) {
+    for (entity, at, elevate, z_index, elevated) in query.iter() {
+        let running = elevate && at.is_some_and(|at| at.is_running());
+        match (running, elevated) {
+            (true, None) => {
+                let elevated = ElevatedZIndex {
+                    prior: z_index.copied(),
+                    elevation: config.elevation,
+                };
+                commands
+                    .entity(entity)
+                    .insert((elevated, elevated.raised()));
+            }
+            (false, Some(elevated)) => {
+                let mut e = commands.entity(entity);
+                e.remove::<ElevatedZIndex>();
+                match elevated.prior {
+                    Some(prior) => e.insert(prior),
+                    None => e.remove::<ZIndex>(),
+                };
+            }
+            _ => {}
         }
     }
 }
 
 #[doc(hidden)]
 pub fn animate_bg_colors(
-    mut query: Query<(
-        Entity,
-        Option<&mut BackgroundColor>,
-        &mut AnimatedBackgroundColor,
-    )>,
+    mut query: Query<(&mut BackgroundColor, &mut AnimatedBackgroundColor)>,
     time: Res<Time>,
 ) {
-    #![allow(unused)]
-    for (e, mut bg, mut at) in query.iter_mut() {
-        let t_old = at.state.clock;
-        at.state.advance(time.delta_seconds());
-        let t = at.state.transition.timing.eval(at.state.clock);
-        let origin = at.origin.as_rgba_linear();
-        let target = at.target.as_rgba_linear();
-        todo!("Finish color space interpolation!");
+    let delta = time.delta_seconds();
+    for (mut bg, mut anim) in query.iter_mut() {
+        if anim.0.advance(delta) {
+            bg.0 = anim.0.value();
+        }
     }
 }
 
 #[doc(hidden)]
 pub fn animate_border_colors(
-    mut query: Query<(Entity, Option<&mut BorderColor>, &mut AnimatedBorderColor)>,
+    mut query: Query<(&mut BorderColor, &mut AnimatedBorderColor)>,
     time: Res<Time>,
 ) {
-    #![allow(unused)]
-    for (e, mut bg, mut at) in query.iter_mut() {
-        let t_old = at.state.clock;
-        at.state.advance(time.delta_seconds());
-        let t = at.state.transition.timing.eval(at.state.clock);
-        let origin = at.origin.as_rgba_linear();
-        let target = at.target.as_rgba_linear();
-        todo!("Finish color space interpolation!");
+    let delta = time.delta_seconds();
+    for (mut border, mut anim) in query.iter_mut() {
+        if anim.0.advance(delta) {
+            border.0 = anim.0.value();
+        }
     }
 }
 
 #[doc(hidden)]
-pub fn animate_layout(mut query: Query<(&mut Style, &mut AnimatedLayout)>, time: Res<Time>) {
+pub fn animate_layout(
+    mut query: Query<(&mut Style, &mut AnimatedLayout)>,
+    time: Res<Time>,
+    config: Res<PeacockAnimationConfig>,
+) {
     let delta = time.delta_seconds();
+    let quantum = config.layout_quantum;
     for (mut style, mut anim) in query.iter_mut() {
         for (prop, trans) in anim.0.iter_mut() {
-            trans.update(*prop, &mut style, delta, false);
+            // Only mark the style as changed if a new value is written.
+            if let Some(value) = trans.advance(delta, quantum, false) {
+                trans.write(*prop, style.as_mut(), value);
+            }
         }
     }
 }
+
+#[doc(hidden)]
+pub fn animate_outlines(mut query: Query<(&mut Outline, &mut AnimatedOutline)>, time: Res<Time>) {
+    let delta = time.delta_seconds();
+    for (mut outline, mut anim) in query.iter_mut() {
+        // Outlines don't affect layout, so only the transition's own quantum applies.
+        let anim = anim.as_mut();
+        for (prop, trans) in anim.lengths.iter_mut() {
+            if let Some(value) = trans.advance(delta, 0., false) {
+                outline.set_length(*prop, value);
+            }
+        }
+        if let Some(color) = &mut anim.color {
+            if color.advance(delta) {
+                outline.color = color.value();
+            }
+        }
+    }
+}
+
+#[doc(hidden)]
+pub fn animate_font_sizes(
+    mut query: Query<(&mut Text, &mut AnimatedFontSize)>,
+    time: Res<Time>,
+    config: Res<PeacockAnimationConfig>,
+) {
+    let delta = time.delta_seconds();
+    for (mut text, mut anim) in query.iter_mut() {
+        // Resizing text relayouts it, so it's quantized like layout lengths.
+        if let Some(value) = anim.0.advance(delta, config.layout_quantum, false) {
+            text.set_length(TransitionProperty::FontSize, value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::PeacockPlugin;
+    use crate::{timing, Transition};
+    use bevy::ecs::system::RunSystemOnce;
+    use bevy::scene::serde::SceneDeserializer;
+    use bevy::utils::Duration;
+    use serde::de::DeserializeSeed;
+
+    fn test_app() -> App {
+        let mut app = crate::test_utils::test_app();
+        app.add_plugins((TransformPlugin, PeacockPlugin));
+        app
+    }
+
+    fn state(property: TransitionProperty, clock: f32) -> TransitionState {
+        TransitionState {
+            transition: Transition {
+                property,
+                duration: 1.,
+                timing: timing::LINEAR,
+                ..default()
+            },
+            clock,
+        }
+    }
+
+    #[test]
+    fn test_scene_round_trip() {
+        let mut app = test_app();
+        let mut layout = AnimatedLayout::default();
+        layout.0.insert(
+            TransitionProperty::Width,
+            AnimatedLayoutProp {
+                state: state(TransitionProperty::Width, 1.5),
+                origin: 10.,
+                target: 20.,
+                target_val: None,
+                last_written: None,
+            },
+        );
+        let entity = app
+            .world
+            .spawn((
+                Transform::default(),
+                AnimatedTransform {
+                    scale: Some(state(TransitionProperty::Scale, 0.5)),
+                    rotation: None,
+                    translation: Some(state(TransitionProperty::Translation, 1.)),
+                    origin: Transform::default(),
+                    target: Transform::from_scale(Vec3::splat(2.)),
+                },
+                layout,
+            ))
+            .id();
+
+        let scene = DynamicSceneBuilder::from_world(&app.world)
+            .extract_entity(entity)
+            .build();
+        let saved = scene
+            .serialize_ron(app.world.resource::<AppTypeRegistry>())
+            .unwrap();
+
+        // Load into a fresh app.
+        let mut app = test_app();
+        let world = &mut app.world;
+        let scene = {
+            let registry = world.resource::<AppTypeRegistry>().read();
+            let mut deserializer = ron::de::Deserializer::from_str(&saved).unwrap();
+            SceneDeserializer {
+                type_registry: &registry,
+            }
+            .deserialize(&mut deserializer)
+            .unwrap()
+        };
+        scene.write_to_world(world, &mut default()).unwrap();
+        world.run_system_once(fix_restored_animations);
+
+        let (_, at, layout) = world
+            .query::<(Entity, &AnimatedTransform, &AnimatedLayout)>()
+            .single(world);
+        let scale = at.state(TransformPart::Scale).unwrap();
+        assert_eq!(scale.clock, 0.5);
+        assert_eq!(scale.transition.property, TransitionProperty::Scale);
+        assert!(at.state(TransformPart::Rotation).is_none());
+        assert_eq!(at.target.scale, Vec3::splat(2.));
+        let width = layout.0.get(&TransitionProperty::Width).unwrap();
+        assert_eq!(width.state.clock, 1.);
+        assert_eq!(width.target, 20.);
+
+        // The animation continues from the saved clock.
+        let mut time = Time::<()>::default();
+        time.advance_by(Duration::from_millis(250));
+        world.insert_resource(time);
+        world.run_system_once(animate_transforms);
+
+        let (transform, at) = world
+            .query::<(&Transform, &AnimatedTransform)>()
+            .single(world);
+        assert_eq!(at.state(TransformPart::Scale).unwrap().clock, 0.75);
+        assert_eq!(transform.scale, Vec3::splat(1.75));
+    }
+
+    /// Run a 5-second linear width animation from 0 to 100px at 60fps, returning the number
+    /// of frames in which the style was changed, and the final width.
+    fn count_layout_writes(layout_quantum: f32, quantum: Option<f32>) -> (usize, ui::Val) {
+        let mut world = World::new();
+        world.insert_resource(PeacockAnimationConfig {
+            layout_quantum,
+            ..default()
+        });
+        let mut prop = AnimatedLayoutProp::new(TransitionState {
+            transition: Transition {
+                property: TransitionProperty::Width,
+                duration: 5.,
+                timing: timing::LINEAR,
+                quantum,
+                ..default()
+            },
+            clock: 0.,
+        });
+        prop.target = 100.;
+        let mut layout = AnimatedLayout::default();
+        layout.0.insert(TransitionProperty::Width, prop);
+        let entity = world.spawn((Style::default(), layout)).id();
+
+        let mut writes = 0;
+        let mut last_changed = world
+            .entity(entity)
+            .get_change_ticks::<Style>()
+            .unwrap()
+            .last_changed_tick();
+        for _ in 0..360 {
+            let mut time = Time::<()>::default();
+            time.advance_by(Duration::from_secs_f64(1. / 60.));
+            world.insert_resource(time);
+            world.run_system_once(animate_layout);
+            let changed = world
+                .entity(entity)
+                .get_change_ticks::<Style>()
+                .unwrap()
+                .last_changed_tick();
+            if changed != last_changed {
+                writes += 1;
+                last_changed = changed;
+            }
+        }
+        (writes, world.get::<Style>(entity).unwrap().width)
+    }
+
+    #[test]
+    fn test_layout_quantum() {
+        // Without quantization, every frame of the animation is written.
+        let (writes, width) = count_layout_writes(0., None);
+        assert!((299..=301).contains(&writes), "{} writes", writes);
+        assert_eq!(width, ui::Val::Px(100.));
+
+        // With a 1px quantum, a 100px animation is written at most about 100 times.
+        let (writes, width) = count_layout_writes(1., None);
+        assert!((75..=101).contains(&writes), "{} writes", writes);
+        assert_eq!(width, ui::Val::Px(100.));
+
+        // The transition's own quantum overrides the global setting.
+        let (writes, width) = count_layout_writes(0., Some(10.));
+        assert!((8..=11).contains(&writes), "{} writes", writes);
+        assert_eq!(width, ui::Val::Px(100.));
+    }
+}
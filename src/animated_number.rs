@@ -0,0 +1,309 @@
+//! Animation of numbers displayed as text, such as scores and health counters.
+
+use bevy::{ecs::system::EntityCommands, prelude::*};
+use bevy_peacock_style::{Transition, TransitionState};
+
+/// How an [`AnimatedNumber`] is displayed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Reflect)]
+pub struct NumberFormat {
+    /// Number of digits after the decimal point. Zero displays whole numbers.
+    pub decimals: u8,
+
+    /// Character inserted between each group of three digits before the decimal point, if any.
+    pub thousands_separator: Option<char>,
+}
+
+impl NumberFormat {
+    /// Display whole numbers, without separators.
+    pub const INTEGER: Self = Self {
+        decimals: 0,
+        thousands_separator: None,
+    };
+
+    /// Display numbers with a fixed number of digits after the decimal point.
+    pub const fn fixed(decimals: u8) -> Self {
+        Self {
+            decimals,
+            thousands_separator: None,
+        }
+    }
+
+    /// Separate groups of three digits before the decimal point with `separator`.
+    pub const fn with_thousands_separator(mut self, separator: char) -> Self {
+        self.thousands_separator = Some(separator);
+        self
+    }
+
+    /// Format `value`, rounded to the number of decimals.
+    pub fn format(&self, value: f64) -> String {
+        let mut text = format!("{:.*}", self.decimals as usize, value);
+        // Don't display values which round to zero as "-0".
+        if text.starts_with('-') && text.bytes().all(|b| matches!(b, b'-' | b'0' | b'.')) {
+            text.remove(0);
+        }
+        let Some(separator) = self.thousands_separator else {
+            return text;
+        };
+        let digits_start = if text.starts_with('-') { 1 } else { 0 };
+        let digits_end = text.find('.').unwrap_or(text.len());
+        let mut result = String::with_capacity(text.len() + (digits_end - digits_start) / 3);
+        result.push_str(&text[..digits_start]);
+        for (i, digit) in text[digits_start..digits_end].chars().enumerate() {
+            if i > 0 && (digits_end - digits_start - i) % 3 == 0 {
+                result.push(separator);
+            }
+            result.push(digit);
+        }
+        result.push_str(&text[digits_end..]);
+        result
+    }
+}
+
+/// Component which displays a number in the first section of the entity's [`Text`],
+/// animating between values when it changes. Only the section's value is written, so text
+/// styles applied by Peacock are unaffected. Use
+/// [`AnimateNumber::animate_number_to`] to change the value.
+#[derive(Component, Default, Reflect)]
+#[reflect(Component)]
+pub struct AnimatedNumber {
+    /// The value being animated towards.
+    pub target: f64,
+
+    /// How the value is displayed.
+    pub format: NumberFormat,
+
+    /// The transition towards the target, and how far through it the animation is.
+    pub(crate) state: TransitionState,
+
+    /// The value displayed when the transition started.
+    pub(crate) origin: f64,
+}
+
+impl AnimatedNumber {
+    /// Construct a number which displays `value`, without animating.
+    pub fn new(value: f64, format: NumberFormat) -> Self {
+        Self {
+            target: value,
+            format,
+            state: TransitionState {
+                transition: Transition::default(),
+                clock: 1.,
+            },
+            origin: value,
+        }
+    }
+
+    /// The value currently displayed.
+    pub fn value(&self) -> f64 {
+        let t = self.state.t() as f64;
+        self.target * t + self.origin * (1. - t)
+    }
+
+    /// True if the animation has reached its target.
+    pub fn is_finished(&self) -> bool {
+        self.state.clock >= 1.
+    }
+
+    /// The transition used for the current animation.
+    pub fn transition(&self) -> &Transition {
+        &self.state.transition
+    }
+
+    /// Animate from the value currently displayed towards `target`.
+    pub fn retarget(&mut self, target: f64, transition: Transition) {
+        self.origin = self.value();
+        self.target = target;
+        self.state = TransitionState {
+            transition,
+            clock: 0.,
+        };
+    }
+}
+
+/// Event sent when an [`AnimatedNumber`] reaches its target.
+#[derive(Event, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AnimatedNumberFinished(pub Entity);
+
+/// Trait for animating the number displayed by an entity.
+pub trait AnimateNumber {
+    /// Animate the entity's [`AnimatedNumber`] from the value currently displayed to `value`.
+    /// If the entity doesn't have one, a whole number is animated from zero.
+    fn animate_number_to(&mut self, value: f64, transition: Transition) -> &mut Self;
+}
+
+impl<'w, 's, 'a> AnimateNumber for EntityCommands<'w, 's, 'a> {
+    fn animate_number_to(&mut self, value: f64, transition: Transition) -> &mut Self {
+        self.add(move |mut entity: EntityWorldMut| {
+            entity.animate_number_to(value, transition);
+        });
+        self
+    }
+}
+
+impl<'w> AnimateNumber for EntityWorldMut<'w> {
+    fn animate_number_to(&mut self, value: f64, transition: Transition) -> &mut Self {
+        if let Some(mut number) = self.get_mut::<AnimatedNumber>() {
+            number.retarget(value, transition);
+        } else {
+            let mut number = AnimatedNumber::new(0., NumberFormat::INTEGER);
+            number.retarget(value, transition);
+            self.insert(number);
+        }
+        self
+    }
+}
+
+/// Advance number animations, and write the displayed values into the text of their entities.
+#[doc(hidden)]
+pub fn animate_numbers(
+    mut query: Query<(Entity, &mut AnimatedNumber, &mut Text)>,
+    time: Res<Time>,
+    mut finished: EventWriter<AnimatedNumberFinished>,
+) {
+    let delta = time.delta_seconds();
+    for (entity, mut number, mut text) in query.iter_mut() {
+        if number.is_finished() && !number.is_changed() && !text.is_changed() {
+            continue;
+        }
+        let was_finished = number.is_finished();
+        number.bypass_change_detection().state.advance(delta);
+        let value = number.format.format(number.value());
+        match text.sections.first() {
+            Some(section) if section.value == value => {}
+            Some(_) => text.sections[0].value = value,
+            None => text.sections.push(TextSection::new(value, default())),
+        }
+        if !was_finished && number.is_finished() {
+            finished.send(AnimatedNumberFinished(entity));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::timing;
+    use bevy::{ecs::system::RunSystemOnce, utils::Duration};
+
+    fn transition(duration: f32) -> Transition {
+        Transition {
+            duration,
+            timing: timing::LINEAR,
+            ..default()
+        }
+    }
+
+    fn test_world() -> World {
+        let mut world = World::new();
+        world.init_resource::<Events<AnimatedNumberFinished>>();
+        world.init_resource::<Time>();
+        world
+    }
+
+    /// Run the animation system for `frames` frames of `seconds` each, returning the text of
+    /// `entity` after each one.
+    fn run_frames(world: &mut World, entity: Entity, frames: usize, seconds: f32) -> Vec<String> {
+        (0..frames)
+            .map(|_| {
+                let mut time = Time::<()>::default();
+                time.advance_by(Duration::from_secs_f32(seconds));
+                world.insert_resource(time);
+                world.run_system_once(animate_numbers);
+                world.get::<Text>(entity).unwrap().sections[0].value.clone()
+            })
+            .collect()
+    }
+
+    fn finished_events(world: &mut World) -> Vec<AnimatedNumberFinished> {
+        world
+            .resource_mut::<Events<AnimatedNumberFinished>>()
+            .drain()
+            .collect()
+    }
+
+    #[test]
+    fn test_number_format() {
+        assert_eq!(NumberFormat::INTEGER.format(41.6), "42");
+        assert_eq!(NumberFormat::INTEGER.format(-0.4), "0");
+        assert_eq!(NumberFormat::fixed(2).format(1.23456), "1.23");
+        assert_eq!(NumberFormat::fixed(1).format(-0.04), "0.0");
+        let grouped = NumberFormat::INTEGER.with_thousands_separator(',');
+        assert_eq!(grouped.format(999.), "999");
+        assert_eq!(grouped.format(1000.), "1,000");
+        assert_eq!(grouped.format(-1234567.), "-1,234,567");
+        let grouped = NumberFormat::fixed(2).with_thousands_separator(' ');
+        assert_eq!(grouped.format(12345.678), "12 345.68");
+    }
+
+    #[test]
+    fn test_animate_number() {
+        let mut world = test_world();
+        let entity = world
+            .spawn((
+                Text::from_section("", TextStyle::default()),
+                AnimatedNumber::new(0., NumberFormat::INTEGER),
+            ))
+            .id();
+        run_frames(&mut world, entity, 1, 0.1);
+        assert_eq!(world.get::<Text>(entity).unwrap().sections[0].value, "0");
+
+        // The value rises steadily, and the event is sent once it reaches the target.
+        world
+            .entity_mut(entity)
+            .animate_number_to(100., transition(1.));
+        let values = run_frames(&mut world, entity, 10, 0.1);
+        let values: Vec<i32> = values.iter().map(|v| v.parse().unwrap()).collect();
+        assert!(values.windows(2).all(|pair| pair[0] < pair[1]));
+        assert_eq!(values[9], 100);
+        assert_eq!(
+            finished_events(&mut world),
+            vec![AnimatedNumberFinished(entity)]
+        );
+        run_frames(&mut world, entity, 2, 0.1);
+        assert!(finished_events(&mut world).is_empty());
+
+        // Retargeting mid-animation continues from the displayed value.
+        world
+            .entity_mut(entity)
+            .animate_number_to(200., transition(1.));
+        assert_eq!(run_frames(&mut world, entity, 5, 0.1)[4], "150");
+        world
+            .entity_mut(entity)
+            .animate_number_to(0., transition(1.));
+        assert_eq!(run_frames(&mut world, entity, 1, 0.5)[0], "75");
+        assert!(finished_events(&mut world).is_empty());
+        assert_eq!(run_frames(&mut world, entity, 1, 0.5)[0], "0");
+        assert_eq!(
+            finished_events(&mut world),
+            vec![AnimatedNumberFinished(entity)]
+        );
+    }
+
+    #[test]
+    fn test_animate_number_keeps_style() {
+        let mut world = test_world();
+        let style = TextStyle {
+            font_size: 30.,
+            color: Color::RED,
+            ..default()
+        };
+        let entity = world
+            .spawn(Text::from_sections([
+                TextSection::new("", style.clone()),
+                TextSection::new(" points", style),
+            ]))
+            .id();
+        let mut commands_queue = bevy::ecs::system::CommandQueue::default();
+        Commands::new(&mut commands_queue, &world)
+            .entity(entity)
+            .animate_number_to(1234., transition(0.));
+        commands_queue.apply(&mut world);
+
+        run_frames(&mut world, entity, 1, 0.1);
+        let text = world.get::<Text>(entity).unwrap();
+        assert_eq!(text.sections[0].value, "1234");
+        assert_eq!(text.sections[0].style.color, Color::RED);
+        assert_eq!(text.sections[0].style.font_size, 30.);
+        assert_eq!(text.sections[1].value, " points");
+    }
+}
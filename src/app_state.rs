@@ -0,0 +1,205 @@
+//! State of the app, rather than of any element, which selects the pseudo-classes
+//! `:window-focused`, `:window-unfocused` and `:paused`.
+
+use bevy::{prelude::*, window::PrimaryWindow};
+
+use crate::restyle::RestyleDependents;
+
+/// Resource holding whether the app's UI is paused, which styles can test with the `:paused`
+/// pseudo-class, so that rules such as `:paused > &.hud { background_color: #0008; }` dim the
+/// UI behind a pause menu. [`PeacockPlugin`](crate::PeacockPlugin) starts the app unpaused;
+/// without the resource, `:paused` never matches. Changing it restyles the elements whose
+/// styles use `:paused`.
+#[derive(Resource, Reflect, Default, Clone, Copy, Debug, PartialEq, Eq)]
+#[reflect(Resource)]
+pub struct UiPauseState(pub bool);
+
+impl UiPauseState {
+    /// True if the UI is paused.
+    pub fn is_paused(&self) -> bool {
+        self.0
+    }
+}
+
+/// Restyle the elements and trees whose styles use the focus of the primary window, or the
+/// pause state, when the one they use changes. The previous values are compared so that writes which don't
+/// change them are ignored.
+pub(crate) fn restyle_changed_app_state(
+    window_query: Query<&Window, With<PrimaryWindow>>,
+    pause: Option<Res<UiPauseState>>,
+    mut focused_prev: Local<Option<bool>>,
+    mut paused_prev: Local<bool>,
+    mut restyle: RestyleDependents,
+) {
+    // Without a primary window, neither of the window focus pseudo-classes match.
+    let focused = window_query.get_single().ok().map(|window| window.focused);
+    let paused = pause.is_some_and(|pause| pause.is_paused());
+    let focus_changed = focused != *focused_prev;
+    let pause_changed = paused != *paused_prev;
+    if !focus_changed && !pause_changed {
+        return;
+    }
+    *focused_prev = focused;
+    *paused_prev = paused;
+    restyle.restyle(|styles| {
+        (focus_changed && styles.uses_window_focus()) || (pause_changed && styles.uses_pause())
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ElementStyles, RestyleEntity, StyleRegistry, StyleRoot};
+    use bevy_peacock_style::parse_stylesheet;
+
+    #[test]
+    fn test_app_state_selectors() {
+        let mut app = crate::test_utils::plugin_test_app();
+        assert_eq!(*app.world.resource::<UiPauseState>(), UiPauseState(false));
+
+        let mut registry = StyleRegistry::default();
+        registry.register_sheet(
+            parse_stylesheet(
+                "HUD {
+                    width: 10px;
+                    height: 10px;
+                    &:window-unfocused { width: 20px; }
+                    :paused > & { height: 30px; }
+                }
+                MENU {
+                    width: 5px;
+                    &:paused { width: 50px; }
+                }",
+            )
+            .unwrap(),
+        );
+        let hud = registry.get("HUD").unwrap();
+        let menu = registry.get("MENU").unwrap();
+        app.world.insert_resource(registry);
+        let window = app
+            .world
+            .spawn((
+                Window {
+                    focused: true,
+                    ..default()
+                },
+                PrimaryWindow,
+            ))
+            .id();
+        let child = app
+            .world
+            .spawn((NodeBundle::default(), ElementStyles::new([hud])))
+            .id();
+        let other = app
+            .world
+            .spawn((NodeBundle::default(), ElementStyles::new([menu])))
+            .id();
+        app.world.spawn(NodeBundle::default()).add_child(child);
+        let size = |app: &App, entity| {
+            let style = app.world.get::<Style>(entity).unwrap();
+            (style.width, style.height)
+        };
+        // The entities restyled because of a change to the app state since the last call.
+        let mut reader = app.world.resource::<Events<RestyleEntity>>().get_reader();
+        let mut restyled = |app: &App| {
+            let mut entities: Vec<_> = reader
+                .read(app.world.resource::<Events<RestyleEntity>>())
+                .map(|event| event.0)
+                .collect();
+            entities.sort();
+            entities
+        };
+
+        // The first frame sees the window appear, which restyles the elements using its focus.
+        app.update();
+        assert_eq!(restyled(&app), [child]);
+        assert_eq!(size(&app, child), (Val::Px(10.), Val::Px(10.)));
+        assert_eq!(size(&app, other), (Val::Px(5.), Val::Auto));
+
+        // Only the elements whose styles use the window focus are restyled when it changes.
+        app.world.get_mut::<Window>(window).unwrap().focused = false;
+        app.update();
+        assert_eq!(restyled(&app), [child]);
+        assert_eq!(size(&app, child), (Val::Px(20.), Val::Px(10.)));
+        assert_eq!(size(&app, other), (Val::Px(5.), Val::Auto));
+
+        // Pausing restyles the elements using `:paused`, which matches on ancestors too.
+        app.world.resource_mut::<UiPauseState>().0 = true;
+        app.update();
+        assert_eq!(restyled(&app), [child, other]);
+        assert_eq!(size(&app, child), (Val::Px(20.), Val::Px(30.)));
+        assert_eq!(size(&app, other), (Val::Px(50.), Val::Auto));
+
+        // Writing the same state changes nothing.
+        app.world.resource_mut::<UiPauseState>().0 = true;
+        app.world.get_mut::<Window>(window).unwrap().focused = false;
+        app.update();
+        assert_eq!(restyled(&app), []);
+
+        app.world.get_mut::<Window>(window).unwrap().focused = true;
+        app.world.remove_resource::<UiPauseState>();
+        app.update();
+        assert_eq!(restyled(&app), [child, other]);
+        assert_eq!(size(&app, child), (Val::Px(10.), Val::Px(10.)));
+        assert_eq!(size(&app, other), (Val::Px(5.), Val::Auto));
+    }
+
+    #[test]
+    fn test_app_state_root_styles() {
+        let mut app = crate::test_utils::plugin_test_app();
+        let mut registry = StyleRegistry::default();
+        registry.register_sheet(
+            parse_stylesheet(
+                "THEME {
+                    width: 10px;
+                    height: 10px;
+                    &:window-unfocused { width: 20px; }
+                    &:paused { height: 30px; }
+                }
+                PANEL { left: 10px; }",
+            )
+            .unwrap(),
+        );
+        let theme = registry.get("THEME").unwrap();
+        let panel = registry.get("PANEL").unwrap();
+        app.world.insert_resource(registry);
+        let window = app
+            .world
+            .spawn((
+                Window {
+                    focused: true,
+                    ..default()
+                },
+                PrimaryWindow,
+            ))
+            .id();
+
+        // Only the root's default styles use the app state.
+        let root = app
+            .world
+            .spawn((NodeBundle::default(), StyleRoot::new(&[theme])))
+            .id();
+        let child = app
+            .world
+            .spawn((NodeBundle::default(), ElementStyles::new([panel])))
+            .set_parent(root)
+            .id();
+        let size = |app: &App, entity| {
+            let style = app.world.get::<Style>(entity).unwrap();
+            (style.width, style.height)
+        };
+        app.update();
+        assert_eq!(size(&app, root), (Val::Px(10.), Val::Px(10.)));
+        assert_eq!(size(&app, child), (Val::Px(10.), Val::Px(10.)));
+
+        app.world.get_mut::<Window>(window).unwrap().focused = false;
+        app.update();
+        assert_eq!(size(&app, root), (Val::Px(20.), Val::Px(10.)));
+        assert_eq!(size(&app, child), (Val::Px(20.), Val::Px(10.)));
+
+        app.world.resource_mut::<UiPauseState>().0 = true;
+        app.update();
+        assert_eq!(size(&app, root), (Val::Px(20.), Val::Px(30.)));
+        assert_eq!(size(&app, child), (Val::Px(20.), Val::Px(30.)));
+    }
+}
@@ -0,0 +1,211 @@
+//! Warnings for fonts and images referenced by styles which fail to load.
+//!
+//! The asset server returns a handle for any path, whether or not the file exists, so a style
+//! naming a missing font would otherwise render text in the default font with no hint why.
+
+use bevy::{
+    asset::{AssetPath, LoadState, UntypedAssetId},
+    prelude::*,
+    utils::HashSet,
+};
+use bevy_peacock_style::StyleProp;
+#[cfg(test)]
+use std::sync::atomic::Ordering;
+
+use crate::{ElementStyles, PeacockConfig, RestyleSubtree, StyleHandle, StyleRegistry};
+
+/// A font or image loaded by the style pass which hasn't finished loading yet.
+struct PendingLoad {
+    entity: Entity,
+    path: AssetPath<'static>,
+    id: UntypedAssetId,
+    is_font: bool,
+}
+
+/// The fonts and images loaded by the style pass which are still loading, and the paths of
+/// those which failed to load.
+#[derive(Resource, Default)]
+pub(crate) struct AssetLoadChecks {
+    pending: Vec<PendingLoad>,
+    failed: HashSet<AssetPath<'static>>,
+}
+
+impl AssetLoadChecks {
+    /// Check that the font or image which `entity`'s styles loaded from `path` loads.
+    pub(crate) fn watch(
+        &mut self,
+        entity: Entity,
+        path: &AssetPath<'static>,
+        id: impl Into<UntypedAssetId>,
+        is_font: bool,
+    ) {
+        if !self.failed.contains(path) {
+            self.pending.push(PendingLoad {
+                entity,
+                path: path.clone(),
+                id: id.into(),
+                is_font,
+            });
+        }
+    }
+
+    /// True if the asset at `path` is known to have failed to load.
+    pub(crate) fn has_failed(&self, path: &AssetPath<'static>) -> bool {
+        self.failed.contains(path)
+    }
+}
+
+/// Number of asset load failures reported. This is global rather than thread local, since the
+/// check runs on the app's task pool.
+#[cfg(test)]
+static ASSET_WARNINGS: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+
+/// Describe the style of `entity` which references `path`: its registered name or its index,
+/// followed by ` (override)` if the path comes from a user override.
+fn style_referencing(
+    styles: Option<&ElementStyles>,
+    registry: &StyleRegistry,
+    path: &AssetPath<'static>,
+) -> Option<String> {
+    let references = |props: &[StyleProp]| {
+        props.iter().any(|prop| match prop {
+            StyleProp::Font(Some(p)) | StyleProp::BackgroundImage(Some(p)) => p == path,
+            _ => false,
+        })
+    };
+    let style_references = |ss: &StyleHandle| {
+        let props = ss.props();
+        references(props.props())
+            || props.selectors().iter().any(|(_, p)| references(p))
+            || props.scopes().iter().any(|(_, p)| references(p))
+    };
+    let name = |index: usize, ss| match registry.name_of(ss) {
+        Some(name) => name.to_owned(),
+        None => format!("#{}", index),
+    };
+    // Later styles take precedence, so search from the end.
+    for (index, ss) in styles?.styles.iter().enumerate().rev() {
+        if registry.override_for(ss).is_some_and(style_references) {
+            return Some(format!("{} (override)", name(index, ss)));
+        }
+        if style_references(ss) {
+            return Some(name(index, ss));
+        }
+    }
+    None
+}
+
+/// Log a warning, once per path, for each font or image loaded by the style pass which failed
+/// to load. Elements whose font failed are restyled so that the fallback font in
+/// [`PeacockConfig`] replaces it, if there is one.
+pub(crate) fn check_asset_loads(
+    checks: Option<ResMut<AssetLoadChecks>>,
+    assets: Res<AssetServer>,
+    config: Option<Res<PeacockConfig>>,
+    registry: Res<StyleRegistry>,
+    query: Query<&ElementStyles>,
+    mut restyle: EventWriter<RestyleSubtree>,
+) {
+    let Some(mut checks) = checks else {
+        return;
+    };
+    if checks.pending.is_empty() {
+        return;
+    }
+    let has_fallback = config.is_some_and(|config| config.fallback_font.is_some());
+    let AssetLoadChecks { pending, failed } = checks.as_mut();
+    pending.retain(|load| match assets.get_load_state(load.id) {
+        Some(LoadState::Failed) => {
+            if failed.insert(load.path.clone()) {
+                #[cfg(test)]
+                ASSET_WARNINGS.fetch_add(1, Ordering::Relaxed);
+                let kind = if load.is_font { "font" } else { "image" };
+                let style = style_referencing(query.get(load.entity).ok(), &registry, &load.path)
+                    .unwrap_or_else(|| "<unknown>".to_owned());
+                warn!(
+                    "Failed to load {} \"{}\", used by style {} of {:?}{}",
+                    kind,
+                    load.path,
+                    style,
+                    load.entity,
+                    if load.is_font && has_fallback {
+                        "; using the fallback font instead"
+                    } else {
+                        ""
+                    }
+                );
+            }
+            if load.is_font && has_fallback {
+                restyle.send(RestyleSubtree(load.entity));
+            }
+            false
+        }
+        // Loaded, or no longer referenced by anything.
+        Some(LoadState::Loaded) | None => false,
+        Some(_) => true,
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::WithStyles;
+    use bevy_peacock_style::parse_stylesheet;
+
+    #[test]
+    fn test_missing_assets() {
+        ASSET_WARNINGS.store(0, Ordering::Relaxed);
+        let mut app = crate::test_utils::plugin_test_app();
+
+        let fallback = Handle::<Font>::weak_from_u128(0x7ea_c0c4);
+        app.world.resource_mut::<PeacockConfig>().fallback_font = Some(fallback.clone());
+        let mut registry = app.world.resource_mut::<StyleRegistry>();
+        registry.register_sheet(
+            parse_stylesheet(
+                r#"LABEL {
+                    font: "fonts/Missing.ttf";
+                    background_image: "textures/Missing.png";
+                }"#,
+            )
+            .unwrap(),
+        );
+        let label = registry.get("LABEL").unwrap();
+        let spawn_label = |app: &mut App| {
+            app.world
+                .spawn(TextBundle::from_section("Text", TextStyle::default()))
+                .with_styles(label.clone())
+                .id()
+        };
+        let first = spawn_label(&mut app);
+        let second = spawn_label(&mut app);
+
+        let font = |app: &App, entity| {
+            app.world.get::<Text>(entity).unwrap().sections[0]
+                .style
+                .font
+                .clone()
+        };
+        for _ in 0..500 {
+            app.update();
+            let checks = app.world.resource::<AssetLoadChecks>();
+            if checks.has_failed(&"textures/Missing.png".into()) && font(&app, first) == fallback {
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(2));
+        }
+
+        // Each path is reported once, and the failed font is replaced by the fallback.
+        assert_eq!(font(&app, first), fallback);
+        assert_eq!(font(&app, second), fallback);
+        assert_eq!(ASSET_WARNINGS.load(Ordering::Relaxed), 2);
+        let checks = app.world.resource::<AssetLoadChecks>();
+        assert!(checks.has_failed(&"fonts/Missing.ttf".into()));
+
+        // Elements using a path which is already known to have failed get the fallback
+        // straight away, and aren't reported again.
+        let third = spawn_label(&mut app);
+        app.update();
+        assert_eq!(font(&app, third), fallback);
+        assert_eq!(ASSET_WARNINGS.load(Ordering::Relaxed), 2);
+    }
+}
@@ -0,0 +1,315 @@
+//! Budgets and occupancy metrics for Peacock's internal caches.
+
+use std::collections::BTreeMap;
+use std::hash::Hash;
+
+use bevy::{prelude::*, utils::HashMap};
+
+/// The occupancy of one of Peacock's internal caches, as reported to
+/// [`PeacockCacheRegistry`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct CacheStats {
+    /// Number of entries in the cache.
+    pub entries: usize,
+    /// Approximate memory allocated for the entries, in bytes, which is kept when the cache is
+    /// emptied. Memory owned by the values, such as the contents of a style, isn't counted.
+    pub bytes: usize,
+    /// The most entries the cache may hold, if it's limited.
+    pub max_entries: Option<usize>,
+    /// Number of entries evicted to stay within the limit, since the app started.
+    pub evictions: u64,
+}
+
+/// Resource which collects the occupancy of Peacock's internal caches, and holds their
+/// budgets. Each cache is known by name:
+///
+/// * [`SELECTOR_MATCHES`](Self::SELECTOR_MATCHES): the results of matching parts of
+///   selectors against ancestors, which are reused by siblings during a style pass. The
+///   cache is emptied at the start of each pass, so its size is that of the last pass.
+/// * [`PRESET_GAPS`](Self::PRESET_GAPS): the shared handles returned by
+///   `presets::row_with_gap()` and `presets::column_with_gap()`.
+///
+/// Caches are unlimited unless given a budget with [`set_max_entries`](Self::set_max_entries).
+/// When a cache would grow past its budget, the least recently used quarter of its entries
+/// are evicted. Evicted entries are rebuilt the next time they're needed, so budgets only
+/// trade memory for time. The numbers are updated by the systems which use the caches, and
+/// are published as diagnostics by [`PeacockDiagnosticsPlugin`](crate::PeacockDiagnosticsPlugin).
+#[derive(Resource, Default, Debug)]
+pub struct PeacockCacheRegistry {
+    caches: BTreeMap<&'static str, CacheStats>,
+    /// Incremented by `clear_all`, so that caches owned by systems can tell they're to be
+    /// emptied.
+    generation: u32,
+}
+
+impl PeacockCacheRegistry {
+    /// The name of the selector match cache.
+    pub const SELECTOR_MATCHES: &'static str = "selector_matches";
+    /// The name of the cache of preset handles with gaps.
+    pub const PRESET_GAPS: &'static str = "preset_gaps";
+
+    /// The occupancy of the named cache, if it has reported.
+    pub fn stats(&self, name: &str) -> Option<CacheStats> {
+        self.caches.get(name).copied()
+    }
+
+    /// The occupancy of every cache which has reported, by name.
+    pub fn iter(&self) -> impl Iterator<Item = (&'static str, CacheStats)> + '_ {
+        self.caches.iter().map(|(name, stats)| (*name, *stats))
+    }
+
+    /// The number of entries in all of the caches.
+    pub fn total_entries(&self) -> usize {
+        self.caches.values().map(|stats| stats.entries).sum()
+    }
+
+    /// The approximate memory allocated by all of the caches, in bytes.
+    pub fn total_bytes(&self) -> usize {
+        self.caches.values().map(|stats| stats.bytes).sum()
+    }
+
+    /// The most entries the named cache may hold, if it's limited.
+    pub fn max_entries(&self, name: &str) -> Option<usize> {
+        self.caches.get(name).and_then(|stats| stats.max_entries)
+    }
+
+    /// Limit the number of entries the named cache may hold, or remove the limit with `None`.
+    /// A limit of zero is treated as one. The cache applies the limit the next time it's
+    /// used.
+    pub fn set_max_entries(&mut self, name: &'static str, max_entries: Option<usize>) {
+        self.caches.entry(name).or_default().max_entries = max_entries;
+    }
+
+    /// Empty every cache. Caches are emptied the next time they're used.
+    pub fn clear_all(&mut self) {
+        self.generation = self.generation.wrapping_add(1);
+        for stats in self.caches.values_mut() {
+            stats.entries = 0;
+            stats.bytes = 0;
+        }
+    }
+
+    /// Incremented by each call to `clear_all`.
+    #[cfg(feature = "presets")]
+    pub(crate) fn generation(&self) -> u32 {
+        self.generation
+    }
+
+    /// Record the occupancy of a cache, keeping its budget.
+    pub(crate) fn report<K, V>(&mut self, name: &'static str, cache: &LruCache<K, V>) {
+        let stats = self.caches.entry(name).or_default();
+        stats.entries = cache.len();
+        stats.bytes = cache.approx_bytes();
+        stats.evictions = cache.evictions();
+    }
+}
+
+/// A map which can be limited to a number of entries, evicting the least recently used ones to
+/// stay within it.
+pub(crate) struct LruCache<K, V> {
+    /// Each value, and the tick when it was last used.
+    entries: HashMap<K, (V, u64)>,
+    tick: u64,
+    max_entries: Option<usize>,
+    evictions: u64,
+}
+
+impl<K, V> Default for LruCache<K, V> {
+    fn default() -> Self {
+        Self {
+            entries: HashMap::default(),
+            tick: 0,
+            max_entries: None,
+            evictions: 0,
+        }
+    }
+}
+
+impl<K, V> LruCache<K, V> {
+    /// Number of entries in the cache.
+    pub(crate) fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Number of entries evicted since the cache was created.
+    pub(crate) fn evictions(&self) -> u64 {
+        self.evictions
+    }
+
+    /// Approximate memory allocated for the entries, in bytes.
+    pub(crate) fn approx_bytes(&self) -> usize {
+        self.entries.capacity() * std::mem::size_of::<(K, (V, u64))>()
+    }
+
+    /// Remove every entry. This doesn't count as eviction.
+    pub(crate) fn clear(&mut self) {
+        self.entries.clear();
+    }
+}
+
+impl<K: Eq + Hash + Clone, V> LruCache<K, V> {
+    /// Limit the number of entries, evicting the least recently used ones if there are more.
+    pub(crate) fn set_max_entries(&mut self, max_entries: Option<usize>) {
+        self.max_entries = max_entries.map(|max| max.max(1));
+        if let Some(max) = self.max_entries {
+            if self.entries.len() > max {
+                self.evict(self.entries.len() - max);
+            }
+        }
+    }
+
+    /// Return the value for `key`, marking it as recently used.
+    pub(crate) fn get(&mut self, key: &K) -> Option<&V> {
+        self.tick += 1;
+        let tick = self.tick;
+        self.entries.get_mut(key).map(|(value, used)| {
+            *used = tick;
+            &*value
+        })
+    }
+
+    /// Insert the value for `key`, first evicting the least recently used quarter of the
+    /// entries if the cache is full.
+    pub(crate) fn insert(&mut self, key: K, value: V) {
+        if let Some(max) = self.max_entries {
+            if self.entries.len() >= max && !self.entries.contains_key(&key) {
+                self.evict((max / 4).max(self.entries.len() + 1 - max));
+            }
+        }
+        self.tick += 1;
+        self.entries.insert(key, (value, self.tick));
+    }
+
+    /// Return the value for `key`, building and inserting it if it isn't cached.
+    #[cfg(any(test, feature = "presets"))]
+    pub(crate) fn get_or_insert_with(&mut self, key: K, build: impl FnOnce() -> V) -> &V {
+        if self.get(&key).is_none() {
+            self.insert(key.clone(), build());
+        }
+        &self.entries[&key].0
+    }
+
+    /// Remove the `count` least recently used entries. Evicting in batches keeps the cost of
+    /// finding them low when the cache is full.
+    fn evict(&mut self, count: usize) {
+        let mut ticks: Vec<u64> = self.entries.values().map(|(_, used)| *used).collect();
+        let count = count.min(ticks.len());
+        if count == 0 {
+            return;
+        }
+        // Ticks are unique, so exactly `count` entries are at or below the threshold.
+        let (_, threshold, _) = ticks.select_nth_unstable(count - 1);
+        let threshold = *threshold;
+        self.entries.retain(|_, (_, used)| *used > threshold);
+        self.evictions += count as u64;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ElementClasses, ElementStyles, StyleHandle};
+
+    #[test]
+    fn test_lru_eviction_order() {
+        let mut cache: LruCache<&str, u32> = LruCache::default();
+        cache.set_max_entries(Some(4));
+        for (index, key) in ["a", "b", "c", "d"].into_iter().enumerate() {
+            cache.insert(key, index as u32);
+        }
+        // Using "a" makes "b" the least recently used.
+        assert_eq!(cache.get(&"a"), Some(&0));
+        cache.insert("e", 4);
+        assert_eq!(cache.len(), 4);
+        assert_eq!(cache.evictions(), 1);
+        assert_eq!(cache.get(&"b"), None);
+        // Replacing an entry doesn't evict anything.
+        cache.insert("c", 5);
+        assert_eq!(cache.evictions(), 1);
+        assert_eq!(cache.get(&"c"), Some(&5));
+
+        // Evicted entries are rebuilt when they're needed.
+        let mut builds = 0;
+        assert_eq!(
+            *cache.get_or_insert_with("b", || {
+                builds += 1;
+                1
+            }),
+            1
+        );
+        assert_eq!(builds, 1);
+        assert_eq!(cache.get(&"d"), None);
+
+        // Lowering the limit evicts the least recently used entries straight away.
+        cache.set_max_entries(Some(2));
+        let mut keys: Vec<_> = cache.entries.keys().copied().collect();
+        keys.sort();
+        assert_eq!(keys, ["b", "c"]);
+        assert_eq!(cache.evictions(), 4);
+
+        // Larger caches evict a quarter of their entries at a time.
+        let mut cache: LruCache<u32, ()> = LruCache::default();
+        cache.set_max_entries(Some(8));
+        (0..9).for_each(|key| cache.insert(key, ()));
+        assert_eq!(cache.len(), 7);
+        assert!((2..9).all(|key| cache.get(&key).is_some()));
+    }
+
+    #[test]
+    fn test_selector_match_budget() {
+        let mut app = crate::test_utils::plugin_test_app();
+
+        // Siblings share the results of matching their parent, so each parent has an entry for
+        // each of the two ancestor selectors.
+        let style = StyleHandle::build(|ss| {
+            ss.width(10.)
+                .selector(".wide > &", |ss| ss.width(20.))
+                .selector(".tall > &", |ss| ss.height(20.))
+        });
+        let mut leaves = Vec::new();
+        for index in 0..10 {
+            let children: Vec<Entity> = (0..3)
+                .map(|_| {
+                    app.world
+                        .spawn((NodeBundle::default(), ElementStyles::new([style.clone()])))
+                        .id()
+                })
+                .collect();
+            leaves.push(children[0]);
+            app.world
+                .spawn((
+                    NodeBundle::default(),
+                    ElementClasses::new(if index % 2 == 0 { "wide" } else { "tall" }),
+                ))
+                .push_children(&children);
+        }
+        app.world
+            .resource_mut::<PeacockCacheRegistry>()
+            .set_max_entries(PeacockCacheRegistry::SELECTOR_MATCHES, Some(8));
+        app.update();
+
+        // The cache stays within its budget, and the evicted results are recomputed.
+        let stats = app
+            .world
+            .resource::<PeacockCacheRegistry>()
+            .stats(PeacockCacheRegistry::SELECTOR_MATCHES)
+            .unwrap();
+        assert!(stats.entries <= 8, "{:?}", stats);
+        assert!(stats.evictions > 0);
+        assert!(stats.bytes > 0);
+        assert_eq!(stats.max_entries, Some(8));
+        for (index, leaf) in leaves.iter().enumerate() {
+            let style = app.world.get::<Style>(*leaf).unwrap();
+            if index % 2 == 0 {
+                assert_eq!((style.width, style.height), (Val::Px(20.), Val::Auto));
+            } else {
+                assert_eq!((style.width, style.height), (Val::Px(10.), Val::Px(20.)));
+            }
+        }
+
+        let mut registry = app.world.resource_mut::<PeacockCacheRegistry>();
+        registry.clear_all();
+        assert_eq!(registry.total_entries(), 0);
+        assert_eq!(registry.total_bytes(), 0);
+    }
+}
@@ -0,0 +1,187 @@
+//! Capabilities of the device and preferences of the user, which select capability
+//! pseudo-classes such as `:reduced-motion`.
+
+use bevy::prelude::*;
+use bevy_peacock_style::CapabilityKind;
+
+use crate::restyle::RestyleDependents;
+
+/// Resource holding the capabilities of the device and the preferences of the user, which the
+/// app detects or lets the user choose. Styles can use the pseudo-classes `:reduced-motion`,
+/// `:touch` and `:gamepad` to make properties conditional on them, independent of the element,
+/// so that rules such as `&.spinner:reduced-motion { rotation: 0; }` replace an animation with
+/// static styling. [`PeacockPlugin`](crate::PeacockPlugin) starts the app with none of them;
+/// without the resource, the pseudo-classes never match. Changing it restyles the elements
+/// whose styles use them.
+#[derive(Resource, Reflect, Default, Clone, Copy, Debug, PartialEq, Eq)]
+#[reflect(Resource)]
+pub struct PeacockCapabilities {
+    /// The user prefers less motion.
+    pub reduced_motion: bool,
+    /// The primary input is a touch screen.
+    pub touch: bool,
+    /// A gamepad is the primary input.
+    pub gamepad: bool,
+}
+
+impl PeacockCapabilities {
+    /// True if the capability is present.
+    pub fn has(&self, kind: CapabilityKind) -> bool {
+        match kind {
+            CapabilityKind::ReducedMotion => self.reduced_motion,
+            CapabilityKind::Touch => self.touch,
+            CapabilityKind::Gamepad => self.gamepad,
+        }
+    }
+
+    /// Set whether the capability is present.
+    pub fn set(&mut self, kind: CapabilityKind, enabled: bool) {
+        let field = match kind {
+            CapabilityKind::ReducedMotion => &mut self.reduced_motion,
+            CapabilityKind::Touch => &mut self.touch,
+            CapabilityKind::Gamepad => &mut self.gamepad,
+        };
+        *field = enabled;
+    }
+}
+
+/// Restyle the elements and trees whose styles use capability pseudo-classes when the
+/// capabilities are set, changed or removed. The previous value is compared so that writes which don't change
+/// any capability are ignored.
+pub(crate) fn restyle_changed_capabilities(
+    capabilities: Option<Res<PeacockCapabilities>>,
+    mut capabilities_prev: Local<PeacockCapabilities>,
+    mut restyle: RestyleDependents,
+) {
+    let capabilities = capabilities.map(|c| *c).unwrap_or_default();
+    if capabilities == *capabilities_prev {
+        return;
+    }
+    *capabilities_prev = capabilities;
+    restyle.restyle(|styles| styles.uses_capabilities());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ElementClasses, ElementStyles, RestyleEntity, StyleRegistry, StyleRoot};
+    use bevy_peacock_style::parse_stylesheet;
+
+    #[test]
+    fn test_capability_selectors() {
+        let mut app = crate::test_utils::plugin_test_app();
+        assert_eq!(
+            *app.world.resource::<PeacockCapabilities>(),
+            PeacockCapabilities::default()
+        );
+
+        let mut registry = StyleRegistry::default();
+        registry.register_sheet(
+            parse_stylesheet(
+                "SPINNER {
+                    width: 10px;
+                    &.spinner:reduced-motion { width: 20px; }
+                    :touch > & { width: 30px; }
+                }
+                PLAIN { width: 5px; }",
+            )
+            .unwrap(),
+        );
+        let spinner = registry.get("SPINNER").unwrap();
+        let plain = registry.get("PLAIN").unwrap();
+        app.world.insert_resource(registry);
+        let child = app
+            .world
+            .spawn((
+                NodeBundle::default(),
+                ElementClasses::new("spinner"),
+                ElementStyles::new([spinner]),
+            ))
+            .id();
+        let other = app
+            .world
+            .spawn((NodeBundle::default(), ElementStyles::new([plain])))
+            .id();
+        app.world.spawn(NodeBundle::default()).add_child(child);
+        let width = |app: &App, entity| app.world.get::<Style>(entity).unwrap().width;
+        // The entities restyled because of a change to the capabilities since the last call.
+        let mut reader = app.world.resource::<Events<RestyleEntity>>().get_reader();
+        let mut restyled = |app: &App| {
+            reader
+                .read(app.world.resource::<Events<RestyleEntity>>())
+                .map(|event| event.0)
+                .collect::<Vec<_>>()
+        };
+
+        app.update();
+        assert_eq!(restyled(&app), []);
+        assert_eq!(width(&app, child), Val::Px(10.));
+
+        // Only the elements whose styles use capabilities are restyled.
+        app.world
+            .resource_mut::<PeacockCapabilities>()
+            .reduced_motion = true;
+        app.update();
+        assert_eq!(restyled(&app), [child]);
+        assert_eq!(width(&app, child), Val::Px(20.));
+        assert_eq!(width(&app, other), Val::Px(5.));
+
+        // Capabilities are global, so they match on ancestors too.
+        app.world
+            .resource_mut::<PeacockCapabilities>()
+            .set(CapabilityKind::Touch, true);
+        app.update();
+        assert_eq!(restyled(&app), [child]);
+        assert_eq!(width(&app, child), Val::Px(30.));
+
+        // Writing the same capabilities changes nothing.
+        app.world.resource_mut::<PeacockCapabilities>().touch = true;
+        app.update();
+        assert_eq!(restyled(&app), []);
+
+        // Without the resource, the pseudo-classes never match.
+        app.world.remove_resource::<PeacockCapabilities>();
+        app.update();
+        assert_eq!(restyled(&app), [child]);
+        assert_eq!(width(&app, child), Val::Px(10.));
+    }
+
+    #[test]
+    fn test_capability_root_styles() {
+        let mut app = crate::test_utils::plugin_test_app();
+        let mut registry = StyleRegistry::default();
+        registry.register_sheet(
+            parse_stylesheet(
+                "THEME {
+                    height: 10px;
+                    &:touch { height: 40px; }
+                }
+                PANEL { width: 10px; }",
+            )
+            .unwrap(),
+        );
+        let theme = registry.get("THEME").unwrap();
+        let panel = registry.get("PANEL").unwrap();
+        app.world.insert_resource(registry);
+
+        // Only the root's default styles use `:touch`.
+        let root = app
+            .world
+            .spawn((NodeBundle::default(), StyleRoot::new(&[theme])))
+            .id();
+        let child = app
+            .world
+            .spawn((NodeBundle::default(), ElementStyles::new([panel])))
+            .set_parent(root)
+            .id();
+        let height = |app: &App, entity| app.world.get::<Style>(entity).unwrap().height;
+        app.update();
+        assert_eq!(height(&app, root), Val::Px(10.));
+        assert_eq!(height(&app, child), Val::Px(10.));
+
+        app.world.resource_mut::<PeacockCapabilities>().touch = true;
+        app.update();
+        assert_eq!(height(&app, root), Val::Px(40.));
+        assert_eq!(height(&app, child), Val::Px(40.));
+    }
+}
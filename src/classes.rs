@@ -1,30 +1,331 @@
-use bevy::ecs::system::EntityCommands;
+use bevy::ecs::system::{EntityCommands, SystemParam};
 use bevy::prelude::*;
 // use bevy::utils::all_tuples;
-use bevy::utils::HashSet;
+use bevy::utils::{HashMap, HashSet};
 use impl_trait_for_tuples::*;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::BTreeSet;
+use std::hash::{Hash, Hasher};
+use std::ops::Bound;
+
+use crate::plugin::check_installed;
 
 /// List of class names which are attached to a given UiNode. Style selectors can use these
 /// class names to conditionally apply styles.
-#[derive(Component, Default)]
-pub struct ElementClasses(pub HashSet<String>);
+///
+/// Class names are stored in a list without duplicates, in the order they were added, so that
+/// the component can be reflected (and thus loaded from scenes). Alongside the list is a sorted
+/// index of the same names, which isn't reflected, and which selector matching uses for class
+/// and prefix lookups. The methods of this type keep the index up to date. Editing the list
+/// through [`names_mut`](Self::names_mut), or loading it from a scene, leaves it without an
+/// index, so lookups scan the list until [`reindex`](Self::reindex) is called; the plugin also
+/// reindexes changed components at the start of each style pass.
+#[derive(Component, Reflect, Default, Clone)]
+#[reflect(Component)]
+pub struct ElementClasses(Vec<String>, #[reflect(ignore)] Option<BTreeSet<String>>);
 
 impl ElementClasses {
-    /// Construct a new [`ElementClasses`] component from a tuple of class names.
+    /// Construct a new [`ElementClasses`] component from a tuple of class names. Duplicate
+    /// names are ignored, keeping the first.
     pub fn new<'a, C: ClassNames<'a>>(classes: C) -> Self {
-        Self(classes.to_set())
+        let mut result = Self(Vec::with_capacity(classes.len()), Some(BTreeSet::new()));
+        classes.extend_classes(&mut result);
+        result
+    }
+
+    /// Iterate over the class names, in the order they were added.
+    pub fn iter(&self) -> impl Iterator<Item = &str> {
+        self.0.iter().map(String::as_str)
+    }
+
+    /// The list of class names, for editing it directly. The index is dropped, so lookups scan
+    /// the list until it is reindexed. The edited list may contain duplicates, which
+    /// [`reindex`](Self::reindex) removes.
+    pub fn names_mut(&mut self) -> &mut Vec<String> {
+        self.1 = None;
+        &mut self.0
+    }
+
+    /// The number of class names.
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// True if there are no class names.
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// True if this element has the given classname. The same as
+    /// [`has_class`](Self::has_class).
+    pub fn contains(&self, cls: &str) -> bool {
+        self.has_class(cls)
     }
 
     /// Add a classname to this element. Be careful using this method with `.class_names()`,
     /// because the latter will overwrite any changes you make with this method.
     pub fn add_class(&mut self, cls: &str) {
-        self.0.insert(cls.to_string());
+        self.set_class(cls, true);
     }
 
     /// Remove a classname from this element. Be careful using this method with `.class_names()`,
     /// because the latter will overwrite any changes you make with this method.
     pub fn remove_class(&mut self, cls: &str) {
-        self.0.remove(cls);
+        self.set_class(cls, false);
+    }
+
+    /// True if this element has the given classname.
+    pub fn has_class(&self, cls: &str) -> bool {
+        match &self.1 {
+            Some(index) => index.contains(cls),
+            None => self.0.iter().any(|c| c == cls),
+        }
+    }
+
+    /// True if this element has any classname beginning with `prefix`.
+    pub fn has_class_prefix(&self, prefix: &str) -> bool {
+        match &self.1 {
+            // The first name not less than the prefix is the only one which can start with it.
+            Some(index) => index
+                .range::<str, _>((Bound::Included(prefix), Bound::Unbounded))
+                .next()
+                .is_some_and(|cls| cls.starts_with(prefix)),
+            None => self.0.iter().any(|cls| cls.starts_with(prefix)),
+        }
+    }
+
+    /// Add or remove a classname depending on `enabled`. Returns true if the class set changed.
+    pub fn set_class(&mut self, cls: &str, enabled: bool) -> bool {
+        if !self.is_indexed() {
+            self.reindex();
+        }
+        let index = self.1.as_mut().unwrap();
+        if enabled {
+            if !index.insert(cls.to_string()) {
+                return false;
+            }
+            self.0.push(cls.to_string());
+        } else {
+            if !index.remove(cls) {
+                return false;
+            }
+            self.0.retain(|c| c != cls);
+        }
+        true
+    }
+
+    /// Rebuild the lookup index from the list of class names, removing any duplicate names
+    /// from the list. Only needed after [`names_mut`](Self::names_mut) or loading from a scene.
+    pub fn reindex(&mut self) {
+        let mut index = BTreeSet::new();
+        self.0.retain(|cls| index.insert(cls.clone()));
+        self.1 = Some(index);
+    }
+
+    /// True if lookups use the index rather than scanning the list.
+    fn is_indexed(&self) -> bool {
+        self.1.is_some()
+    }
+}
+
+/// Compares the class names, in order. The index is derived from them, so it's ignored.
+impl PartialEq for ElementClasses {
+    fn eq(&self, other: &Self) -> bool {
+        self.0 == other.0
+    }
+}
+
+impl std::fmt::Debug for ElementClasses {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("ElementClasses").field(&self.0).finish()
+    }
+}
+
+/// Rebuild the index of class lists which were edited directly or loaded from a scene. Runs at
+/// the start of [`PeacockSystemSet`](crate::PeacockSystemSet), before anything matches
+/// selectors against them. This doesn't mark the component as changed again.
+pub(crate) fn reindex_classes(mut query: Query<&mut ElementClasses, Changed<ElementClasses>>) {
+    for mut classes in query.iter_mut() {
+        if !classes.is_indexed() {
+            classes.bypass_change_detection().reindex();
+        }
+    }
+}
+
+impl ElementClasses {
+    /// A hash of the class set, which doesn't depend on the order of the names, so that a
+    /// class which was removed and added back hashes the same as before.
+    pub(crate) fn set_hash(&self) -> u64 {
+        self.0.iter().fold(0, |hash, cls| {
+            let mut hasher = DefaultHasher::new();
+            cls.hash(&mut hasher);
+            hash ^ hasher.finish()
+        })
+    }
+}
+
+/// The hash of each element's class set when styles were last updated. Systems which add a
+/// class and remove it again in the same frame leave the component marked as changed, so the
+/// style pass compares hashes to only restyle elements whose classes changed overall.
+#[derive(Resource, Default)]
+pub(crate) struct PreviousClasses(pub(crate) HashMap<Entity, u64>);
+
+impl PreviousClasses {
+    /// True if the class set of `entity` differs from the one recorded, or none was recorded.
+    pub(crate) fn changed(&self, entity: Entity, classes: &ElementClasses) -> bool {
+        self.0.get(&entity) != Some(&classes.set_hash())
+    }
+}
+
+/// The hashes of the class sets which the most recent style pass matched against, for the
+/// elements whose classes were written or removed since the pass before. `None` means the
+/// component was removed.
+#[derive(Resource, Default)]
+pub(crate) struct StyledClasses(pub(crate) HashMap<Entity, Option<u64>>);
+
+/// The class sets which were written or removed since the previous style pass. The style pass
+/// records them into [`StyledClasses`] as it runs, so that classes written after it are still
+/// seen as changed by the next one.
+#[derive(SystemParam)]
+pub(crate) struct ClassChanges<'w, 's> {
+    changed: Query<'w, 's, (Entity, &'static ElementClasses), Changed<ElementClasses>>,
+    removed: RemovedComponents<'w, 's, ElementClasses>,
+    styled: Option<ResMut<'w, StyledClasses>>,
+}
+
+impl<'w, 's> ClassChanges<'w, 's> {
+    /// Record the class sets seen by the style pass which is running.
+    pub(crate) fn record(&mut self) {
+        let Some(styled) = self.styled.as_mut() else {
+            return;
+        };
+        for entity in self.removed.read() {
+            styled.0.insert(entity, None);
+        }
+        for (entity, classes) in self.changed.iter() {
+            styled.0.insert(entity, Some(classes.set_hash()));
+        }
+    }
+}
+
+/// Record the class sets seen by the previous style pass as the previous ones. Like
+/// [`update_focus`](crate::focus::update_focus), this runs at the start of
+/// [`PeacockSystemSet`](crate::PeacockSystemSet), rather than hashing the classes after the
+/// pass, which would pick up classes written in between.
+pub(crate) fn update_previous_classes(
+    mut styled: ResMut<StyledClasses>,
+    mut prev: ResMut<PreviousClasses>,
+) {
+    for (entity, hash) in styled.0.drain() {
+        match hash {
+            Some(hash) => prev.0.insert(entity, hash),
+            None => prev.0.remove(&entity),
+        };
+    }
+}
+
+/// Construct from a list of class names. Duplicate names are ignored.
+impl FromIterator<String> for ElementClasses {
+    fn from_iter<I: IntoIterator<Item = String>>(iter: I) -> Self {
+        let mut classes = Self(iter.into_iter().collect(), None);
+        classes.reindex();
+        classes
+    }
+}
+
+impl From<&[&str]> for ElementClasses {
+    fn from(classes: &[&str]) -> Self {
+        classes.iter().map(|cls| cls.to_string()).collect()
+    }
+}
+
+/// Apply a list of class toggles to a change-detected [`ElementClasses`]. The component is
+/// only mutably dereferenced (and thus marked as changed) if the toggles would actually alter
+/// the class set. If the same class name appears more than once, the last toggle wins.
+///
+/// Returns true if the class set changed.
+fn apply_toggles(classes: &mut Mut<ElementClasses>, toggles: &[(&str, bool)]) -> bool {
+    let changed = toggles.iter().enumerate().any(|(index, (cls, enabled))| {
+        classes.has_class(cls) != *enabled && !toggles[index + 1..].iter().any(|(c, _)| c == cls)
+    });
+    if changed {
+        for (cls, enabled) in toggles {
+            classes.set_class(cls, *enabled);
+        }
+    }
+    changed
+}
+
+/// System parameter for efficiently updating the class names of many entities at once.
+///
+/// Writes which would not alter an entity's class set are skipped entirely, so they don't
+/// trigger change detection (and hence don't cause the entity's styles to be recomputed).
+/// Entities which don't have an [`ElementClasses`] component are ignored.
+#[derive(SystemParam)]
+pub struct ClassWriter<'w, 's> {
+    query: Query<'w, 's, &'static mut ElementClasses>,
+}
+
+impl<'w, 's> ClassWriter<'w, 's> {
+    /// Add or remove a single class on an entity. Returns true if the class set changed.
+    pub fn set(&mut self, entity: Entity, cls: &str, enabled: bool) -> bool {
+        match self.query.get_mut(entity) {
+            Ok(mut classes) => apply_toggles(&mut classes, &[(cls, enabled)]),
+            _ => false,
+        }
+    }
+
+    /// Apply a batch of `(entity, class, enabled)` toggles. Toggles are grouped by entity so
+    /// that each entity's component is accessed only once; within an entity, toggles are
+    /// applied in the order given.
+    ///
+    /// Returns the number of entities whose class set actually changed.
+    pub fn set_many<'a>(
+        &mut self,
+        toggles: impl IntoIterator<Item = (Entity, &'a str, bool)>,
+    ) -> usize {
+        let mut toggles: Vec<(Entity, &'a str, bool)> = toggles.into_iter().collect();
+        // Stable sort, so that the order of toggles for the same entity is preserved.
+        toggles.sort_by_key(|(entity, _, _)| *entity);
+
+        let mut count = 0;
+        let mut batch: Vec<(&str, bool)> = Vec::new();
+        let mut start = 0;
+        while start < toggles.len() {
+            let entity = toggles[start].0;
+            let end = start
+                + toggles[start..]
+                    .iter()
+                    .take_while(|(e, _, _)| *e == entity)
+                    .count();
+            if let Ok(mut classes) = self.query.get_mut(entity) {
+                batch.clear();
+                batch.extend(toggles[start..end].iter().map(|(_, cls, en)| (*cls, *en)));
+                if apply_toggles(&mut classes, &batch) {
+                    count += 1;
+                }
+            }
+            start = end;
+        }
+        count
+    }
+
+    /// Add and remove classes on a single entity. Classes in `remove` are processed after
+    /// classes in `add`, so a class listed in both will be removed.
+    ///
+    /// Returns true if the class set changed.
+    pub fn apply_diff(&mut self, entity: Entity, add: &[&str], remove: &[&str]) -> bool {
+        match self.query.get_mut(entity) {
+            Ok(mut classes) => {
+                let toggles: Vec<(&str, bool)> = add
+                    .iter()
+                    .map(|cls| (*cls, true))
+                    .chain(remove.iter().map(|cls| (*cls, false)))
+                    .collect();
+                apply_toggles(&mut classes, &toggles)
+            }
+            _ => false,
+        }
     }
 }
 
@@ -40,6 +341,22 @@ impl<'a, C: ClassNames<'a> + PartialEq> PartialEq for ConditionalClassNames<'a,
     }
 }
 
+/// A choice between two sets of class names, created by [`ClassNames::if_else`].
+pub struct AlternativeClassNames<'a, C: ClassNames<'a>, D: ClassNames<'a>> {
+    pub(crate) inner: C,
+    pub(crate) other: D,
+    pub(crate) enabled: bool,
+    pub(crate) marker: std::marker::PhantomData<&'a ()>,
+}
+
+impl<'a, C: ClassNames<'a> + PartialEq, D: ClassNames<'a> + PartialEq> PartialEq
+    for AlternativeClassNames<'a, C, D>
+{
+    fn eq(&self, other: &Self) -> bool {
+        self.inner == other.inner && self.other == other.other && self.enabled == other.enabled
+    }
+}
+
 /// Tuple of class names, possibly conditional, possibly nested.
 pub trait ClassNames<'a>: Send + Clone {
     /// Return the number of class names.
@@ -51,6 +368,19 @@ pub trait ClassNames<'a>: Send + Clone {
     /// Add all of the enabled class names to a set.
     fn add_classes(&self, classes: &mut HashSet<String>);
 
+    /// Add all of the enabled class names to `classes`, in order, skipping those it already
+    /// has. The default implementation goes through [`add_classes`](Self::add_classes), and
+    /// so adds the names in sorted order, since the set has none of its own.
+    fn extend_classes(&self, classes: &mut ElementClasses) {
+        let mut set = HashSet::<String>::with_capacity(self.len());
+        self.add_classes(&mut set);
+        let mut names: Vec<String> = set.into_iter().collect();
+        names.sort_unstable();
+        for cls in names {
+            classes.add_class(&cls);
+        }
+    }
+
     /// Make this set of class names conditional; if the condition is false, then the
     /// class names will not be added to the set.
     fn if_true(self, enabled: bool) -> ConditionalClassNames<'a, Self>
@@ -64,6 +394,25 @@ pub trait ClassNames<'a>: Send + Clone {
         }
     }
 
+    /// Choose between this set of class names and `other`: if the condition is true, this
+    /// set's class names are added, and otherwise `other`'s are. For example,
+    /// `"open".if_else(is_open, "closed")`.
+    fn if_else<D: ClassNames<'a>>(
+        self,
+        enabled: bool,
+        other: D,
+    ) -> AlternativeClassNames<'a, Self, D>
+    where
+        Self: Sized,
+    {
+        AlternativeClassNames {
+            inner: self,
+            other,
+            enabled,
+            marker: std::marker::PhantomData,
+        }
+    }
+
     /// Convert this set of class names into a HashSet.
     fn to_set(&self) -> HashSet<String>
     where
@@ -85,6 +434,8 @@ impl<'a> ClassNames<'a> for () {
     }
 
     fn add_classes(&self, _classes: &mut HashSet<String>) {}
+
+    fn extend_classes(&self, _classes: &mut ElementClasses) {}
 }
 
 impl<'a> ClassNames<'a> for String {
@@ -99,6 +450,10 @@ impl<'a> ClassNames<'a> for String {
     fn add_classes(&self, classes: &mut HashSet<String>) {
         classes.insert(self.clone());
     }
+
+    fn extend_classes(&self, classes: &mut ElementClasses) {
+        classes.add_class(self);
+    }
 }
 
 impl<'a> ClassNames<'a> for &str {
@@ -113,6 +468,10 @@ impl<'a> ClassNames<'a> for &str {
     fn add_classes(&self, classes: &mut HashSet<String>) {
         classes.insert(self.to_string());
     }
+
+    fn extend_classes(&self, classes: &mut ElementClasses) {
+        classes.add_class(self);
+    }
 }
 
 impl<'a, C: ClassNames<'a>> ClassNames<'a> for ConditionalClassNames<'a, C> {
@@ -137,6 +496,12 @@ impl<'a, C: ClassNames<'a>> ClassNames<'a> for ConditionalClassNames<'a, C> {
             self.inner.add_classes(classes);
         }
     }
+
+    fn extend_classes(&self, classes: &mut ElementClasses) {
+        if self.enabled {
+            self.inner.extend_classes(classes);
+        }
+    }
 }
 
 impl<'a, C: ClassNames<'a>> Clone for ConditionalClassNames<'a, C> {
@@ -149,6 +514,111 @@ impl<'a, C: ClassNames<'a>> Clone for ConditionalClassNames<'a, C> {
     }
 }
 
+impl<'a, C: ClassNames<'a>, D: ClassNames<'a>> ClassNames<'a> for AlternativeClassNames<'a, C, D> {
+    fn len(&self) -> usize {
+        if self.enabled {
+            self.inner.len()
+        } else {
+            self.other.len()
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        if self.enabled {
+            self.inner.is_empty()
+        } else {
+            self.other.is_empty()
+        }
+    }
+
+    fn add_classes(&self, classes: &mut HashSet<String>) {
+        if self.enabled {
+            self.inner.add_classes(classes);
+        } else {
+            self.other.add_classes(classes);
+        }
+    }
+
+    fn extend_classes(&self, classes: &mut ElementClasses) {
+        if self.enabled {
+            self.inner.extend_classes(classes);
+        } else {
+            self.other.extend_classes(classes);
+        }
+    }
+}
+
+impl<'a, C: ClassNames<'a>, D: ClassNames<'a>> Clone for AlternativeClassNames<'a, C, D> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            other: self.other.clone(),
+            enabled: self.enabled,
+            marker: self.marker,
+        }
+    }
+}
+
+/// Class names paired with whether each is enabled, such as a class set computed by game
+/// logic. Only the enabled names are added.
+impl<'a> ClassNames<'a> for Vec<(&str, bool)> {
+    fn len(&self) -> usize {
+        self.iter().filter(|(_, enabled)| *enabled).count()
+    }
+
+    fn is_empty(&self) -> bool {
+        !self.iter().any(|(_, enabled)| *enabled)
+    }
+
+    fn add_classes(&self, classes: &mut HashSet<String>) {
+        for (cls, enabled) in self {
+            if *enabled {
+                classes.insert(cls.to_string());
+            }
+        }
+    }
+
+    fn extend_classes(&self, classes: &mut ElementClasses) {
+        for (cls, enabled) in self {
+            if *enabled {
+                classes.add_class(cls);
+            }
+        }
+    }
+}
+
+/// Class names mapped to whether each is enabled. Only the enabled names are added, in sorted
+/// order, since the map has none of its own.
+impl<'a> ClassNames<'a> for HashMap<&str, bool> {
+    fn len(&self) -> usize {
+        self.values().filter(|enabled| **enabled).count()
+    }
+
+    fn is_empty(&self) -> bool {
+        !self.values().any(|enabled| *enabled)
+    }
+
+    fn add_classes(&self, classes: &mut HashSet<String>) {
+        for (cls, enabled) in self {
+            if *enabled {
+                classes.insert(cls.to_string());
+            }
+        }
+    }
+
+    fn extend_classes(&self, classes: &mut ElementClasses) {
+        let mut enabled: Vec<&str> = self
+            .iter()
+            .filter(|(_, enabled)| **enabled)
+            .map(|(cls, _)| *cls)
+            .collect();
+        enabled.sort_unstable();
+        for cls in enabled {
+            classes.add_class(cls);
+        }
+    }
+}
+
 // macro_rules! impl_class_names {
 //     ($($T:ident),*) => {
 //         impl<$($T: ConditionalClassName),*> ClassNamesTuple for ($($T,)*) {
@@ -170,7 +640,7 @@ impl<'a> ClassNames<'a> for Tuple {
     }
 
     fn is_empty(&self) -> bool {
-        false
+        for_tuples!( #( self.Tuple.is_empty() )&* );
     }
 
     fn add_classes(&self, classes: &mut HashSet<String>) {
@@ -178,6 +648,12 @@ impl<'a> ClassNames<'a> for Tuple {
             self.Tuple.add_classes(classes);
         )* );
     }
+
+    fn extend_classes(&self, classes: &mut ElementClasses) {
+        for_tuples!( #(
+            self.Tuple.extend_classes(classes);
+        )* );
+    }
 }
 
 /// Trait that permits adding a tuple of [`ClassName`]s to an object.
@@ -189,7 +665,11 @@ pub trait WithClasses {
 /// Insert class names using commands.
 impl<'w, 's, 'a> WithClasses for EntityCommands<'w, 's, 'a> {
     fn class_names<'c, C: ClassNames<'c>>(&mut self, classes: C) -> &mut Self {
-        self.insert(ElementClasses(classes.to_set()));
+        let classes = ElementClasses::new(classes);
+        self.add(|mut entity: EntityWorldMut| {
+            entity.world_scope(check_installed);
+            entity.insert(classes);
+        });
         self
     }
 }
@@ -197,10 +677,11 @@ impl<'w, 's, 'a> WithClasses for EntityCommands<'w, 's, 'a> {
 /// Update or insert class names into an [`EntityWorldMut`].
 impl<'w> WithClasses for EntityWorldMut<'w> {
     fn class_names<'a, C: ClassNames<'a>>(&mut self, classes: C) -> &mut Self {
+        self.world_scope(check_installed);
         if let Some(mut ec) = self.get_mut::<ElementClasses>() {
-            ec.0 = classes.to_set();
+            *ec = ElementClasses::new(classes);
         } else {
-            self.insert(ElementClasses(classes.to_set()));
+            self.insert(ElementClasses::new(classes));
         }
         self
     }
@@ -208,34 +689,500 @@ impl<'w> WithClasses for EntityWorldMut<'w> {
 
 #[cfg(test)]
 mod tests {
-    use bevy::utils::hashbrown::HashSet;
+    use bevy::ecs::{component::Tick, system::RunSystemOnce};
 
     use super::*;
 
-    fn get_names<'a, CN: ClassNames<'a>>(class_names: CN) -> HashSet<String> {
-        let mut classes = HashSet::with_capacity(class_names.len());
-        class_names.add_classes(&mut classes);
-        classes
+    const TOGGLED: [&str; 3] = ["visible", "culled", "selected"];
+
+    fn spawn_classes(world: &mut World, count: usize) -> Vec<Entity> {
+        (0..count)
+            .map(|_| world.spawn(ElementClasses::new("visible")).id())
+            .collect()
+    }
+
+    /// Count the number of [`ElementClasses`] components which changed after `since`.
+    fn count_changed(world: &mut World, since: Tick) -> usize {
+        let this_run = world.change_tick();
+        world
+            .query::<Ref<ElementClasses>>()
+            .iter(world)
+            .filter(|classes| classes.last_changed().is_newer_than(since, this_run))
+            .count()
+    }
+
+    /// Every tenth entity flips from visible to culled, the rest are no-ops.
+    fn toggles(entities: &[Entity]) -> Vec<(Entity, &'static str, bool)> {
+        entities
+            .iter()
+            .enumerate()
+            .flat_map(|(index, e)| {
+                let culled = index % 10 == 0;
+                [
+                    (*e, TOGGLED[0], !culled),
+                    (*e, TOGGLED[1], culled),
+                    (*e, TOGGLED[2], false),
+                ]
+            })
+            .collect()
+    }
+
+    fn get_names<'a, CN: ClassNames<'a>>(class_names: CN) -> Vec<String> {
+        names(&ElementClasses::new(class_names))
+    }
+
+    fn names(classes: &ElementClasses) -> Vec<String> {
+        classes.iter().map(str::to_owned).collect()
     }
 
     #[test]
     fn test_class_names() {
         let cl = get_names(());
-        assert_eq!(cl, HashSet::new());
+        assert!(cl.is_empty());
 
         let cl = get_names("test");
-        assert_eq!(cl, ["test".to_owned()].into());
+        assert_eq!(cl, ["test"]);
 
         let cl = get_names(("one", "two"));
-        assert_eq!(cl, ["one".to_owned(), "two".to_owned()].into());
+        assert_eq!(cl, ["one", "two"]);
 
         let cl = get_names(("one".if_true(true), "two"));
-        assert_eq!(cl, ["one".to_owned(), "two".to_owned()].into());
+        assert_eq!(cl, ["one", "two"]);
 
         let cl = get_names(("one".if_true(false), "two"));
-        assert_eq!(cl, ["two".to_owned()].into());
+        assert_eq!(cl, ["two"]);
 
         let cl = get_names(("one".if_true(true).if_true(false), "two"));
-        assert_eq!(cl, ["two".to_owned()].into());
+        assert_eq!(cl, ["two"]);
+
+        // A tuple is only empty if everything in it is.
+        assert!(("one".if_true(false), ()).is_empty());
+        assert!(!("one".if_true(false), "two").is_empty());
+    }
+
+    #[test]
+    fn test_new_keeps_order() {
+        let classes = ElementClasses::new(("b", "a", "b".if_true(true), "c".if_else(false, "a")));
+        assert_eq!(names(&classes), ["b", "a"]);
+        assert_eq!(classes.len(), 2);
+        assert!(classes.contains("a"));
+        assert!(!classes.contains("c"));
+        assert!(ElementClasses::new(()).is_empty());
+    }
+
+    #[test]
+    fn test_if_else() {
+        let cl = get_names("open".if_else(true, "closed"));
+        assert_eq!(cl, ["open"]);
+
+        let cl = get_names("open".if_else(false, "closed"));
+        assert_eq!(cl, ["closed"]);
+
+        // Alternatives can be tuples, and nest inside tuples and conditionals.
+        let names = (
+            "panel",
+            ("open", "wide").if_else(false, ("closed", "narrow".if_true(false))),
+            "a".if_else(true, "b").if_true(false),
+        );
+        assert_eq!(names.len(), 2);
+        let cl = get_names(names);
+        assert_eq!(cl, ["panel", "closed"]);
+
+        assert!("one".if_true(false).if_else(true, "two").is_empty());
+        assert!(!"one".if_true(false).if_else(false, "two").is_empty());
+    }
+
+    #[test]
+    fn test_class_maps() {
+        let toggles = vec![("selected", true), ("disabled", false), ("hot", true)];
+        assert_eq!(ClassNames::len(&toggles), 2);
+        let classes = ElementClasses::new(("item", toggles));
+        assert!(classes.has_class("item"));
+        assert!(classes.has_class("selected"));
+        assert!(classes.has_class("hot"));
+        assert!(!classes.has_class("disabled"));
+
+        let map: HashMap<&str, bool> = [("selected", false), ("disabled", true)].into();
+        assert_eq!(ClassNames::len(&map), 1);
+        let cl = get_names(("item".if_else(false, "other"), map));
+        assert_eq!(cl, ["other", "disabled"]);
+
+        let none: Vec<(&str, bool)> = vec![("selected", false)];
+        assert!(ClassNames::is_empty(&none));
+        assert!(get_names(none).is_empty());
+    }
+
+    #[test]
+    fn test_set_class() {
+        let mut classes = ElementClasses::new("one");
+        assert!(!classes.set_class("one", true));
+        assert!(classes.set_class("two", true));
+        assert!(classes.set_class("one", false));
+        assert!(!classes.set_class("one", false));
+        assert!(classes.has_class("two"));
+        assert!(!classes.has_class("one"));
+    }
+
+    #[test]
+    fn test_class_prefix() {
+        let classes = ElementClasses::new(("menu-file", "icons", "icon-save"));
+        assert!(classes.has_class_prefix("icon-"));
+        assert!(classes.has_class_prefix("menu"));
+        assert!(classes.has_class_prefix(""));
+        assert!(!classes.has_class_prefix("icons-"));
+        assert!(!classes.has_class_prefix("menus"));
+        assert!(!ElementClasses::default().has_class_prefix(""));
+    }
+
+    #[test]
+    fn test_direct_edits() {
+        let mut classes = ElementClasses::new(("a", "b"));
+        // Edits to the list are seen straight away, including ones which keep its length.
+        classes.names_mut().push("c".to_owned());
+        classes.names_mut().push("a".to_owned());
+        assert!(classes.has_class("c"));
+        assert!(classes.has_class_prefix("c"));
+        classes.names_mut()[2] = "d".to_owned();
+        assert!(classes.has_class("d"));
+        assert!(!classes.has_class("c"));
+        assert!(!classes.has_class_prefix("c"));
+
+        // Reindexing removes the duplicate.
+        classes.reindex();
+        assert_eq!(names(&classes), ["a", "b", "d"]);
+        assert!(classes.has_class("d"));
+        assert!(!classes.has_class("c"));
+        assert!(classes.set_class("a", false));
+        assert_eq!(names(&classes), ["b", "d"]);
+        assert_eq!(classes, ElementClasses::new(("b", "d")));
+    }
+
+    #[test]
+    fn test_reindex_changed_classes() {
+        let mut world = World::new();
+        let entity = world.spawn(ElementClasses::new(("a", "b"))).id();
+        world.get_mut::<ElementClasses>(entity).unwrap().names_mut()[1] = "c".to_owned();
+        world.run_system_once(reindex_classes);
+        let classes = world.get::<ElementClasses>(entity).unwrap();
+        assert!(classes.is_indexed());
+        assert!(classes.has_class("c"));
+        assert!(!classes.has_class("b"));
+    }
+
+    #[derive(Clone)]
+    struct Legacy(&'static str);
+
+    /// An implementation written against the set-based API.
+    impl<'a> ClassNames<'a> for Legacy {
+        fn len(&self) -> usize {
+            2
+        }
+
+        fn is_empty(&self) -> bool {
+            false
+        }
+
+        fn add_classes(&self, classes: &mut HashSet<String>) {
+            classes.insert(self.0.to_owned());
+            classes.insert(format!("{}-legacy", self.0));
+        }
+    }
+
+    #[test]
+    fn test_set_based_class_names() {
+        assert_eq!(
+            ("one", "two".if_true(false), Legacy("x")).to_set(),
+            ["one".to_owned(), "x".to_owned(), "x-legacy".to_owned()].into()
+        );
+        let classes = ElementClasses::new(("one", Legacy("x"), "two"));
+        assert_eq!(names(&classes), ["one", "x", "x-legacy", "two"]);
+    }
+
+    #[test]
+    fn test_class_writer_change_detection() {
+        // Naive per-call mutation marks every entity as changed.
+        let mut world = World::new();
+        let entities = spawn_classes(&mut world, 1000);
+        let batch = toggles(&entities);
+        let since = world.increment_change_tick();
+        world.run_system_once(move |mut query: Query<&mut ElementClasses>| {
+            for (e, cls, enabled) in batch.iter() {
+                let mut classes = query.get_mut(*e).unwrap();
+                if *enabled {
+                    classes.add_class(cls);
+                } else {
+                    classes.remove_class(cls);
+                }
+            }
+        });
+        assert_eq!(count_changed(&mut world, since), 1000);
+
+        // Batched writes only mark entities whose class set actually changed.
+        let mut world = World::new();
+        let entities = spawn_classes(&mut world, 1000);
+        let batch = toggles(&entities);
+        let since = world.increment_change_tick();
+        let count =
+            world.run_system_once(move |mut writer: ClassWriter| writer.set_many(batch.clone()));
+        assert_eq!(count, 100);
+        assert_eq!(count_changed(&mut world, since), 100);
+
+        let classes = world.get::<ElementClasses>(entities[0]).unwrap();
+        assert_eq!(names(classes), ["culled"]);
+        let classes = world.get::<ElementClasses>(entities[1]).unwrap();
+        assert_eq!(names(classes), ["visible"]);
+    }
+
+    #[test]
+    fn test_set_hash_ignores_order() {
+        let classes = ElementClasses::new(("a", "b"));
+        let mut flapped = classes.clone();
+        flapped.remove_class("a");
+        flapped.add_class("a");
+        assert_eq!(names(&flapped), ["b", "a"]);
+        assert_eq!(flapped.set_hash(), classes.set_hash());
+        assert_ne!(ElementClasses::new("a").set_hash(), classes.set_hash());
+        assert_ne!(ElementClasses::default().set_hash(), classes.set_hash());
+    }
+
+    #[test]
+    fn test_class_flapping_in_one_frame() {
+        use crate::diagnostics::StyleUpdateCounts;
+        use crate::{ElementStyles, PeacockSystemSet, StyleHandle};
+
+        /// Class toggles for the frame. The first system applies the first, and a second one,
+        /// standing in for unrelated game logic, applies the rest.
+        #[derive(Resource, Default)]
+        struct Flap(Vec<(Entity, &'static str, bool)>);
+
+        fn toggle(toggles: &[(Entity, &str, bool)], query: &mut Query<&mut ElementClasses>) {
+            for (entity, cls, enabled) in toggles {
+                query.get_mut(*entity).unwrap().set_class(cls, *enabled);
+            }
+        }
+
+        fn input_system(flap: Res<Flap>, mut query: Query<&mut ElementClasses>) {
+            toggle(&flap.0[..flap.0.len().min(1)], &mut query);
+        }
+
+        fn game_system(flap: Res<Flap>, mut query: Query<&mut ElementClasses>) {
+            toggle(flap.0.get(1..).unwrap_or_default(), &mut query);
+        }
+
+        let mut app = crate::test_utils::plugin_test_app();
+        app.init_resource::<Flap>()
+            .init_resource::<StyleUpdateCounts>()
+            .add_systems(
+                Update,
+                (input_system, game_system).chain().before(PeacockSystemSet),
+            );
+
+        let style = StyleHandle::build(|ss| {
+            ss.width(10.)
+                .selector(".open > &", |ss| ss.width(20.))
+                .selector("&.wide", |ss| ss.width(30.))
+        });
+        let child = app
+            .world
+            .spawn((
+                NodeBundle::default(),
+                ElementClasses::default(),
+                ElementStyles::new([style]),
+            ))
+            .id();
+        let parent = app
+            .world
+            .spawn((NodeBundle::default(), ElementClasses::new("panel")))
+            .add_child(child)
+            .id();
+        // Run a frame with the given toggle, returning the number of elements recomputed.
+        let run = |app: &mut App, flap: &[(Entity, &'static str, bool)]| {
+            app.world.resource_mut::<Flap>().0 = flap.to_vec();
+            app.update();
+            let counts = std::mem::take(app.world.resource_mut::<StyleUpdateCounts>().as_mut());
+            counts.entities_recomputed
+        };
+        let width = |app: &App| app.world.get::<Style>(child).unwrap().width;
+
+        assert_eq!(run(&mut app, &[]), 1);
+        assert_eq!(run(&mut app, &[]), 0);
+
+        // Adding and removing a class in the same frame is a net no-op, on the element itself
+        // or on an ancestor.
+        assert_eq!(
+            run(&mut app, &[(child, "wide", true), (child, "wide", false)]),
+            0
+        );
+        assert_eq!(
+            run(&mut app, &[(parent, "open", true), (parent, "open", false)]),
+            0
+        );
+        // So is removing and adding back one which was already there, which reorders them.
+        assert_eq!(
+            run(
+                &mut app,
+                &[(parent, "panel", false), (parent, "panel", true)]
+            ),
+            0
+        );
+        assert_eq!(width(&app), Val::Px(10.));
+
+        // Net changes still restyle, including ones made by the second system.
+        assert_eq!(run(&mut app, &[(parent, "open", true)]), 1);
+        assert_eq!(width(&app), Val::Px(20.));
+        assert_eq!(
+            run(&mut app, &[(child, "wide", false), (child, "wide", true)]),
+            1
+        );
+        assert_eq!(width(&app), Val::Px(30.));
+        assert_eq!(
+            run(&mut app, &[(child, "wide", false), (parent, "open", false)]),
+            1
+        );
+        assert_eq!(width(&app), Val::Px(10.));
+    }
+
+    #[test]
+    fn test_classes_written_after_style_pass() {
+        use crate::{ElementStyles, PeacockStage, StyleHandle};
+
+        // A system with no ordering against the style pass, which happens to run after it.
+        #[derive(Resource, Default)]
+        struct Late(Option<(Entity, &'static str)>);
+
+        fn late_system(mut late: ResMut<Late>, mut query: Query<&mut ElementClasses>) {
+            if let Some((entity, cls)) = late.0.take() {
+                query.get_mut(entity).unwrap().add_class(cls);
+            }
+        }
+
+        let mut app = crate::test_utils::plugin_test_app();
+        app.init_resource::<Late>().add_systems(
+            Update,
+            late_system
+                .after(PeacockStage::ComputeStyles)
+                .before(PeacockStage::Flush),
+        );
+
+        let style =
+            StyleHandle::build(|ss| ss.width(10.).selector(".open > &", |ss| ss.width(20.)));
+        let child = app
+            .world
+            .spawn((NodeBundle::default(), ElementStyles::new([style])))
+            .id();
+        let parent = app
+            .world
+            .spawn((NodeBundle::default(), ElementClasses::default()))
+            .add_child(child)
+            .id();
+        let width = |app: &App| app.world.get::<Style>(child).unwrap().width;
+
+        app.update();
+        assert_eq!(width(&app), Val::Px(10.));
+
+        // The class is styled on the next frame, rather than being lost.
+        app.world.resource_mut::<Late>().0 = Some((parent, "open"));
+        app.update();
+        assert_eq!(width(&app), Val::Px(10.));
+        app.update();
+        assert_eq!(width(&app), Val::Px(20.));
+    }
+
+    #[test]
+    fn test_class_writer_mixed_batch() {
+        let mut world = World::new();
+        let entities = spawn_classes(&mut world, 3);
+        let (a, b, c) = (entities[0], entities[1], entities[2]);
+        let since = world.increment_change_tick();
+        let count = world.run_system_once(move |mut writer: ClassWriter| {
+            writer.set_many([
+                (a, "open", true),
+                (b, "visible", false),
+                (a, "visible", false),
+                // Net no-op: added then removed again.
+                (c, "open", true),
+                (b, "open", true),
+                (c, "open", false),
+            ])
+        });
+        assert_eq!(count, 2);
+        assert_eq!(count_changed(&mut world, since), 2);
+        assert_eq!(names(world.get::<ElementClasses>(a).unwrap()), ["open"]);
+        assert_eq!(names(world.get::<ElementClasses>(b).unwrap()), ["open"]);
+        assert_eq!(names(world.get::<ElementClasses>(c).unwrap()), ["visible"]);
+    }
+
+    #[test]
+    fn test_class_writer_apply_diff() {
+        let mut world = World::new();
+        let e = world.spawn(ElementClasses::new(("a", "b"))).id();
+
+        let since = world.increment_change_tick();
+        let changed = world
+            .run_system_once(move |mut writer: ClassWriter| writer.apply_diff(e, &["a"], &["c"]));
+        assert!(!changed);
+        assert_eq!(count_changed(&mut world, since), 0);
+
+        let since = world.increment_change_tick();
+        let changed = world.run_system_once(move |mut writer: ClassWriter| {
+            writer.apply_diff(e, &["c", "d"], &["a", "d"])
+        });
+        assert!(changed);
+        assert_eq!(count_changed(&mut world, since), 1);
+        assert_eq!(names(world.get::<ElementClasses>(e).unwrap()), ["b", "c"]);
+    }
+
+    #[test]
+    fn test_from_list() {
+        let classes = ElementClasses::from(["one", "two", "one"].as_slice());
+        assert_eq!(names(&classes), ["one", "two"]);
+        assert!(classes.has_class("two"));
+
+        let mut classes: ElementClasses = ["b".to_owned(), "a".to_owned(), "b".to_owned()]
+            .into_iter()
+            .collect();
+        assert_eq!(names(&classes), ["b", "a"]);
+        classes.add_class("a");
+        classes.add_class("c");
+        classes.remove_class("b");
+        assert_eq!(names(&classes), ["a", "c"]);
+    }
+
+    #[test]
+    fn test_scene_round_trip() {
+        use bevy::scene::{serde::SceneDeserializer, DynamicSceneBuilder};
+        use serde::de::DeserializeSeed;
+
+        let mut world = World::new();
+        let registry = AppTypeRegistry::default();
+        registry.write().register::<ElementClasses>();
+        registry.write().register::<Vec<String>>();
+        world.insert_resource(registry.clone());
+        let entity = world
+            .spawn(ElementClasses::from(["button", "primary"].as_slice()))
+            .id();
+        let saved = DynamicSceneBuilder::from_world(&world)
+            .extract_entity(entity)
+            .build()
+            .serialize_ron(&registry)
+            .unwrap();
+
+        let mut world = World::new();
+        world.insert_resource(registry.clone());
+        let scene = {
+            let registry = registry.read();
+            let mut deserializer = ron::de::Deserializer::from_str(&saved).unwrap();
+            SceneDeserializer {
+                type_registry: &registry,
+            }
+            .deserialize(&mut deserializer)
+            .unwrap()
+        };
+        scene.write_to_world(&mut world, &mut default()).unwrap();
+        let classes = world.query::<&ElementClasses>().single(&world);
+        assert_eq!(names(classes), ["button", "primary"]);
+        // The index isn't saved, so lookups search the list until it is rebuilt.
+        assert!(classes.has_class("primary"));
+        assert!(!classes.has_class("secondary"));
     }
 }
@@ -0,0 +1,168 @@
+//! The app's color scheme, which selects `:dark` selectors.
+
+use bevy::prelude::*;
+
+use crate::restyle::RestyleDependents;
+
+/// Resource holding the app's color scheme. Styles can use the `:dark` pseudo-class to make
+/// properties conditional on it, so that rules such as `&:dark { background_color: #222; }`
+/// override the light colors. [`PeacockPlugin`](crate::PeacockPlugin) starts the app in
+/// [`ColorScheme::Light`]; without the resource, `:dark` never matches. Changing it restyles
+/// the elements whose styles use `:dark`.
+#[derive(Resource, Reflect, Default, Clone, Copy, Debug, PartialEq, Eq)]
+#[reflect(Resource)]
+pub enum ColorScheme {
+    /// Light backgrounds with dark text.
+    #[default]
+    Light,
+    /// Dark backgrounds with light text.
+    Dark,
+}
+
+impl ColorScheme {
+    /// True if this is the dark scheme.
+    pub fn is_dark(self) -> bool {
+        self == ColorScheme::Dark
+    }
+
+    /// Return the other scheme.
+    pub fn toggled(self) -> Self {
+        match self {
+            ColorScheme::Light => ColorScheme::Dark,
+            ColorScheme::Dark => ColorScheme::Light,
+        }
+    }
+}
+
+/// Restyle the elements and trees whose styles use `:dark` when the color scheme is set,
+/// changed or removed. The previous value is compared so that writes which don't change the
+/// scheme are ignored.
+pub(crate) fn restyle_changed_color_scheme(
+    scheme: Option<Res<ColorScheme>>,
+    mut dark_prev: Local<bool>,
+    mut restyle: RestyleDependents,
+) {
+    let dark = scheme.is_some_and(|scheme| scheme.is_dark());
+    if dark == *dark_prev {
+        return;
+    }
+    *dark_prev = dark;
+    restyle.restyle(|styles| styles.uses_color_scheme());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ElementStyles, RestyleEntity, StyleRegistry, StyleRoot};
+    use bevy_peacock_style::parse_stylesheet;
+
+    #[test]
+    fn test_dark_selector() {
+        let mut app = crate::test_utils::plugin_test_app();
+        assert_eq!(*app.world.resource::<ColorScheme>(), ColorScheme::Light);
+
+        let mut registry = StyleRegistry::default();
+        registry.register_sheet(
+            parse_stylesheet(
+                "PANEL {
+                    background_color: #eee;
+                    &:dark { background_color: #222; }
+                    .inverted > &:dark { background_color: #fff; }
+                }
+                PLAIN { background_color: #888; }",
+            )
+            .unwrap(),
+        );
+        let panel = registry.get("PANEL").unwrap();
+        let plain = registry.get("PLAIN").unwrap();
+        app.world.insert_resource(registry);
+        let panel = app
+            .world
+            .spawn((NodeBundle::default(), ElementStyles::new([panel])))
+            .id();
+        let other = app
+            .world
+            .spawn((NodeBundle::default(), ElementStyles::new([plain])))
+            .id();
+        let color = |app: &App, entity| app.world.get::<BackgroundColor>(entity).unwrap().0;
+        // The entities restyled because of a change to the scheme since the last call.
+        let mut reader = app.world.resource::<Events<RestyleEntity>>().get_reader();
+        let mut restyled = |app: &App| {
+            reader
+                .read(app.world.resource::<Events<RestyleEntity>>())
+                .map(|event| event.0)
+                .collect::<Vec<_>>()
+        };
+        let light = Color::hex("eee").unwrap();
+        let dark = Color::hex("222").unwrap();
+
+        app.update();
+        assert_eq!(restyled(&app), []);
+        assert_eq!(color(&app, panel), light);
+
+        // Only the elements whose styles use `:dark` are restyled.
+        *app.world.resource_mut::<ColorScheme>() = ColorScheme::Dark;
+        app.update();
+        assert_eq!(restyled(&app), [panel]);
+        assert_eq!(color(&app, panel), dark);
+        assert_eq!(color(&app, other), Color::hex("888").unwrap());
+
+        // Writing the same scheme changes nothing.
+        *app.world.resource_mut::<ColorScheme>() = ColorScheme::Dark;
+        app.update();
+        assert_eq!(restyled(&app), []);
+
+        let toggled = app.world.resource::<ColorScheme>().toggled();
+        app.world.insert_resource(toggled);
+        app.update();
+        assert_eq!(restyled(&app), [panel]);
+        assert_eq!(color(&app, panel), light);
+
+        // Without the resource, `:dark` never matches.
+        app.world.insert_resource(ColorScheme::Dark);
+        app.update();
+        assert_eq!(color(&app, panel), dark);
+        app.world.remove_resource::<ColorScheme>();
+        app.update();
+        assert_eq!(color(&app, panel), light);
+    }
+
+    #[test]
+    fn test_dark_root_styles() {
+        let mut app = crate::test_utils::plugin_test_app();
+        let mut registry = StyleRegistry::default();
+        registry.register_sheet(
+            parse_stylesheet(
+                "THEME {
+                    background_color: #eee;
+                    &:dark { background_color: #222; }
+                }
+                PANEL { width: 10px; }",
+            )
+            .unwrap(),
+        );
+        let theme = registry.get("THEME").unwrap();
+        let panel = registry.get("PANEL").unwrap();
+        app.world.insert_resource(registry);
+
+        // Only the root's default styles use `:dark`.
+        let root = app
+            .world
+            .spawn((NodeBundle::default(), StyleRoot::new(&[theme])))
+            .id();
+        let child = app
+            .world
+            .spawn((NodeBundle::default(), ElementStyles::new([panel])))
+            .set_parent(root)
+            .id();
+        let color = |app: &App, entity| app.world.get::<BackgroundColor>(entity).unwrap().0;
+        app.update();
+        assert_eq!(color(&app, root), Color::hex("eee").unwrap());
+        assert_eq!(color(&app, child), Color::hex("eee").unwrap());
+
+        *app.world.resource_mut::<ColorScheme>() = ColorScheme::Dark;
+        app.update();
+        assert_eq!(color(&app, root), Color::hex("222").unwrap());
+        assert_eq!(color(&app, child), Color::hex("222").unwrap());
+    }
+}
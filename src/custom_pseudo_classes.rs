@@ -0,0 +1,218 @@
+//! Pseudo-classes defined by the app, such as `:--in-combat`, which are backed by arbitrary
+//! game state rather than by the UI.
+
+use std::cell::RefCell;
+
+use bevy::{
+    prelude::*,
+    utils::{HashMap, HashSet},
+};
+
+/// A custom pseudo-class, which decides whether it applies to an entity by looking at the
+/// world. It's evaluated for every UI node once per frame, before styles are updated, so
+/// [`matches`](Self::matches) should be cheap.
+///
+/// Closures taking `(&World, Entity)` implement this trait, so a pseudo-class backed by a
+/// marker component can be registered with
+/// `registry.register("in-combat", |world: &World, e| world.get::<InCombat>(e).is_some())`.
+pub trait CustomPseudoClass: Send + Sync + 'static {
+    /// True if the pseudo-class applies to `entity`.
+    fn matches(&self, world: &World, entity: Entity) -> bool;
+
+    /// Return false if the pseudo-class can't have changed for any entity since it was last
+    /// evaluated, so that evaluating it again can be skipped. The default conservatively
+    /// returns true, so that every node is rechecked every frame.
+    fn may_have_changed(&mut self, _world: &World) -> bool {
+        true
+    }
+}
+
+impl<F: Fn(&World, Entity) -> bool + Send + Sync + 'static> CustomPseudoClass for F {
+    fn matches(&self, world: &World, entity: Entity) -> bool {
+        self(world, entity)
+    }
+}
+
+/// A registered pseudo-class, and the results of its most recent evaluation.
+struct CustomClassEntry {
+    class: Box<dyn CustomPseudoClass>,
+    /// The UI nodes which the pseudo-class applies to.
+    active: HashSet<Entity>,
+    /// The UI nodes which the pseudo-class started or stopped applying to in the most recent
+    /// evaluation.
+    changed: HashSet<Entity>,
+}
+
+/// Resource holding the custom pseudo-classes which selectors can use, named without the
+/// leading dashes, so `:--in-combat` is registered as `in-combat`. Selectors naming a
+/// pseudo-class which isn't registered never match.
+#[derive(Resource, Default)]
+pub struct CustomPseudoClassRegistry {
+    classes: HashMap<String, CustomClassEntry>,
+}
+
+impl CustomPseudoClassRegistry {
+    /// Register a custom pseudo-class, replacing any existing one with the same name.
+    pub fn register(&mut self, name: impl Into<String>, class: impl CustomPseudoClass) {
+        self.classes.insert(
+            name.into(),
+            CustomClassEntry {
+                class: Box::new(class),
+                active: HashSet::default(),
+                changed: HashSet::default(),
+            },
+        );
+    }
+
+    /// Remove a custom pseudo-class. Returns whether it was registered.
+    pub fn unregister(&mut self, name: &str) -> bool {
+        self.classes.remove(name).is_some()
+    }
+
+    /// True if a pseudo-class named `name` is registered.
+    pub fn contains(&self, name: &str) -> bool {
+        self.classes.contains_key(name)
+    }
+
+    /// True if the pseudo-class `name` applied to `entity` when it was last evaluated.
+    /// Returns `None` if the pseudo-class isn't registered.
+    pub fn matches(&self, name: &str, entity: Entity) -> Option<bool> {
+        self.classes
+            .get(name)
+            .map(|entry| entry.active.contains(&entity))
+    }
+
+    /// True if any pseudo-class started or stopped applying to `entity` in the most recent
+    /// evaluation.
+    pub(crate) fn changed(&self, entity: Entity) -> bool {
+        self.classes
+            .values()
+            .any(|entry| entry.changed.contains(&entity))
+    }
+}
+
+/// Names of unregistered pseudo-classes which have already been reported, so that each is only
+/// reported once.
+#[derive(Default)]
+pub(crate) struct UnknownPseudoClasses(RefCell<HashSet<String>>);
+
+impl UnknownPseudoClasses {
+    /// Log a warning the first time an unregistered pseudo-class is used.
+    pub(crate) fn report(&self, name: &str) {
+        if !self.0.borrow().contains(name) {
+            self.0.borrow_mut().insert(name.to_owned());
+            warn!(
+                "Unknown pseudo-class ':--{}'; register it with the \
+                CustomPseudoClassRegistry resource",
+                name
+            );
+        }
+    }
+}
+
+/// Evaluate each custom pseudo-class for every UI node, recording which nodes it started or
+/// stopped applying to since it was last evaluated so that their styles can be updated.
+pub(crate) fn update_custom_pseudo_classes(
+    world: &mut World,
+    nodes: &mut QueryState<Entity, With<Node>>,
+) {
+    if !world.contains_resource::<CustomPseudoClassRegistry>() {
+        return;
+    }
+    world.resource_scope(|world, mut registry: Mut<CustomPseudoClassRegistry>| {
+        for entry in registry.classes.values_mut() {
+            entry.changed.clear();
+            if !entry.class.may_have_changed(world) {
+                continue;
+            }
+            let active: HashSet<Entity> = nodes
+                .iter(world)
+                .filter(|entity| entry.class.matches(world, *entity))
+                .collect();
+            entry.changed = active
+                .symmetric_difference(&entry.active)
+                .copied()
+                .collect();
+            entry.active = active;
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ElementStyles, StyleHandle};
+
+    #[derive(Component)]
+    struct InCombat;
+
+    #[derive(Resource, Default)]
+    struct Frozen(bool);
+
+    /// A pseudo-class backed by a marker component, which can be frozen to test
+    /// `may_have_changed`.
+    struct InCombatClass;
+
+    impl CustomPseudoClass for InCombatClass {
+        fn matches(&self, world: &World, entity: Entity) -> bool {
+            world.get::<InCombat>(entity).is_some()
+        }
+
+        fn may_have_changed(&mut self, world: &World) -> bool {
+            !world.resource::<Frozen>().0
+        }
+    }
+
+    #[test]
+    fn test_custom_pseudo_class() {
+        let mut app = crate::test_utils::plugin_test_app();
+        app.init_resource::<Frozen>();
+        app.world
+            .resource_mut::<CustomPseudoClassRegistry>()
+            .register("in-combat", InCombatClass);
+
+        let style = StyleHandle::build(|ss| {
+            ss.width(10.)
+                .selector(":--in-combat > &", |ss| ss.width(20.))
+                .selector(":--unknown", |ss| ss.width(30.))
+        });
+        let parent = app.world.spawn(NodeBundle::default()).id();
+        let child = app
+            .world
+            .spawn((NodeBundle::default(), ElementStyles::new(&[style])))
+            .set_parent(parent)
+            .id();
+        let width = |app: &App| app.world.get::<Style>(child).unwrap().width;
+
+        app.update();
+        assert_eq!(width(&app), Val::Px(10.));
+
+        // Entering combat restyles the descendants whose selectors depend on it.
+        app.world.entity_mut(parent).insert(InCombat);
+        app.update();
+        assert_eq!(width(&app), Val::Px(20.));
+        let registry = app.world.resource::<CustomPseudoClassRegistry>();
+        assert_eq!(registry.matches("in-combat", parent), Some(true));
+        assert_eq!(registry.matches("in-combat", child), Some(false));
+        assert_eq!(registry.matches("unknown", child), None);
+
+        // Nothing is rechecked while the pseudo-class reports that it can't have changed.
+        app.world.resource_mut::<Frozen>().0 = true;
+        app.world.entity_mut(parent).remove::<InCombat>();
+        app.update();
+        assert_eq!(width(&app), Val::Px(20.));
+
+        app.world.resource_mut::<Frozen>().0 = false;
+        app.update();
+        assert_eq!(width(&app), Val::Px(10.));
+
+        // Closures can be registered too.
+        app.world
+            .resource_mut::<CustomPseudoClassRegistry>()
+            .register("unknown", |world: &World, entity| {
+                world.get::<Node>(entity).is_some()
+            });
+        app.update();
+        assert_eq!(width(&app), Val::Px(30.));
+    }
+}
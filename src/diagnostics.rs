@@ -0,0 +1,200 @@
+//! Counters describing the work done by the style pass each frame, published as Bevy
+//! diagnostics.
+
+use bevy::{
+    diagnostic::{Diagnostic, DiagnosticId, Diagnostics, RegisterDiagnostic},
+    prelude::*,
+};
+
+use crate::{PeacockCacheRegistry, PeacockSystemSet};
+
+/// Counts of the work done by the style pass since they were last published. The style pass
+/// only counts when this resource exists, which [`PeacockDiagnosticsPlugin`] inserts.
+#[derive(Resource, Default, Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct StyleUpdateCounts {
+    /// Entities visited by the traversal of the UI hierarchy.
+    pub(crate) entities_visited: usize,
+    /// Entities whose styles were computed.
+    pub(crate) entities_recomputed: usize,
+    /// Selectors matched against an element, and the parts of them matched against its
+    /// ancestors, not including cached results.
+    pub(crate) selector_evaluations: usize,
+}
+
+impl std::ops::AddAssign for StyleUpdateCounts {
+    fn add_assign(&mut self, rhs: Self) {
+        self.entities_visited += rhs.entities_visited;
+        self.entities_recomputed += rhs.entities_recomputed;
+        self.selector_evaluations += rhs.selector_evaluations;
+    }
+}
+
+/// Plugin which publishes the work done by the style pass each frame as diagnostics, which can
+/// be read from the [`DiagnosticsStore`](bevy::diagnostic::DiagnosticsStore) or printed by
+/// [`LogDiagnosticsPlugin`](bevy::diagnostic::LogDiagnosticsPlugin):
+///
+/// * `peacock/entities_visited`: UI entities visited by the style pass.
+/// * `peacock/entities_recomputed`: entities whose styles were computed.
+/// * `peacock/selector_evaluations`: selectors matched against an element, and the parts of
+///   them matched against its ancestors, not including results reused within the pass.
+/// * `peacock/cache_entries` and `peacock/cache_bytes`: the total entries and approximate
+///   memory of Peacock's internal caches. See [`PeacockCacheRegistry`] for each cache.
+///
+/// The style pass also emits tracing spans for each UI tree, for computing each element's
+/// styles, for loading assets and for applying the computed styles, which show up in
+/// profilers such as Tracy when Bevy's `trace` feature is enabled.
+#[derive(Default)]
+pub struct PeacockDiagnosticsPlugin;
+
+impl PeacockDiagnosticsPlugin {
+    /// UI entities visited by the style pass.
+    pub const ENTITIES_VISITED: DiagnosticId =
+        DiagnosticId::from_u128(161907070830538505438447441080993589909);
+    /// Entities whose styles were computed.
+    pub const ENTITIES_RECOMPUTED: DiagnosticId =
+        DiagnosticId::from_u128(264413578857623516740005234214124547910);
+    /// Selectors, and the parts of them matched against ancestors, evaluated by the style
+    /// pass.
+    pub const SELECTOR_EVALUATIONS: DiagnosticId =
+        DiagnosticId::from_u128(223070831294226394394254399733850983147);
+    /// Entries in Peacock's internal caches.
+    pub const CACHE_ENTRIES: DiagnosticId =
+        DiagnosticId::from_u128(167727879350530655312182681741258587698);
+    /// Approximate memory allocated by Peacock's internal caches, in bytes.
+    pub const CACHE_BYTES: DiagnosticId =
+        DiagnosticId::from_u128(96972139348447388892946444284628562958);
+
+    /// Publish the counts since the previous frame, and reset them.
+    pub(crate) fn diagnostic_system(
+        mut diagnostics: Diagnostics,
+        mut counts: ResMut<StyleUpdateCounts>,
+        caches: Option<Res<PeacockCacheRegistry>>,
+    ) {
+        let counts = std::mem::take(counts.as_mut());
+        diagnostics.add_measurement(Self::ENTITIES_VISITED, || counts.entities_visited as f64);
+        diagnostics.add_measurement(Self::ENTITIES_RECOMPUTED, || {
+            counts.entities_recomputed as f64
+        });
+        diagnostics.add_measurement(Self::SELECTOR_EVALUATIONS, || {
+            counts.selector_evaluations as f64
+        });
+        if let Some(caches) = caches {
+            diagnostics.add_measurement(Self::CACHE_ENTRIES, || caches.total_entries() as f64);
+            diagnostics.add_measurement(Self::CACHE_BYTES, || caches.total_bytes() as f64);
+        }
+    }
+}
+
+impl Plugin for PeacockDiagnosticsPlugin {
+    fn build(&self, app: &mut App) {
+        app.register_diagnostic(Diagnostic::new(
+            Self::ENTITIES_VISITED,
+            "peacock/entities_visited",
+            20,
+        ))
+        .register_diagnostic(Diagnostic::new(
+            Self::ENTITIES_RECOMPUTED,
+            "peacock/entities_recomputed",
+            20,
+        ))
+        .register_diagnostic(Diagnostic::new(
+            Self::SELECTOR_EVALUATIONS,
+            "peacock/selector_evaluations",
+            20,
+        ))
+        .register_diagnostic(Diagnostic::new(
+            Self::CACHE_ENTRIES,
+            "peacock/cache_entries",
+            20,
+        ))
+        .register_diagnostic(Diagnostic::new(
+            Self::CACHE_BYTES,
+            "peacock/cache_bytes",
+            20,
+        ))
+        .init_resource::<StyleUpdateCounts>()
+        .add_systems(Update, Self::diagnostic_system.after(PeacockSystemSet));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ElementClasses, ElementStyles, StyleHandle};
+    use bevy::diagnostic::DiagnosticsStore;
+
+    #[test]
+    fn test_style_diagnostics() {
+        let mut app = crate::test_utils::plugin_test_app();
+        app.add_plugins(PeacockDiagnosticsPlugin);
+
+        let style = StyleHandle::build(|ss| {
+            ss.width(10.)
+                .selector(".panel > &", |ss| ss.width(20.))
+                .selector(".panel > * > &", |ss| ss.width(30.))
+        });
+        let leaf = app
+            .world
+            .spawn((NodeBundle::default(), ElementStyles::new([style.clone()])))
+            .id();
+        let child = app
+            .world
+            .spawn((NodeBundle::default(), ElementStyles::new([style])))
+            .add_child(leaf)
+            .id();
+        // The root has no styles, so it's visited but not recomputed.
+        let root = app
+            .world
+            .spawn((NodeBundle::default(), ElementClasses::new("panel")))
+            .add_child(child)
+            .id();
+
+        let value = |app: &App, id| {
+            app.world
+                .resource::<DiagnosticsStore>()
+                .get(id)
+                .and_then(|diagnostic| diagnostic.value())
+        };
+        app.update();
+        assert_eq!(
+            value(&app, PeacockDiagnosticsPlugin::ENTITIES_VISITED),
+            Some(3.)
+        );
+        assert_eq!(
+            value(&app, PeacockDiagnosticsPlugin::ENTITIES_RECOMPUTED),
+            Some(2.)
+        );
+        // Both selectors are matched against both styled elements, and then against each
+        // ancestor they reach: two for the child, and three for the leaf.
+        assert_eq!(
+            value(&app, PeacockDiagnosticsPlugin::SELECTOR_EVALUATIONS),
+            Some(9.)
+        );
+        // The results of matching ancestors are cached.
+        assert!(value(&app, PeacockDiagnosticsPlugin::CACHE_ENTRIES).is_some_and(|n| n > 0.));
+        assert!(value(&app, PeacockDiagnosticsPlugin::CACHE_BYTES).is_some_and(|n| n > 0.));
+
+        // Nothing is recomputed when nothing changes.
+        app.update();
+        assert_eq!(
+            value(&app, PeacockDiagnosticsPlugin::ENTITIES_VISITED),
+            Some(3.)
+        );
+        assert_eq!(
+            value(&app, PeacockDiagnosticsPlugin::ENTITIES_RECOMPUTED),
+            Some(0.)
+        );
+        assert_eq!(
+            value(&app, PeacockDiagnosticsPlugin::SELECTOR_EVALUATIONS),
+            Some(0.)
+        );
+
+        // Changing the root's classes restyles its descendants.
+        app.world.entity_mut(root).insert(ElementClasses::new(""));
+        app.update();
+        assert_eq!(
+            value(&app, PeacockDiagnosticsPlugin::ENTITIES_RECOMPUTED),
+            Some(2.)
+        );
+    }
+}
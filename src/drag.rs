@@ -0,0 +1,264 @@
+//! Drag state for the `:dragging` and `:drag-over` pseudo-classes, which follow the drag events
+//! of `bevy_mod_picking`. Without the `bevy_mod_picking` feature, both pseudo-classes parse but
+//! never match.
+
+#[cfg(not(feature = "bevy_mod_picking"))]
+use std::cell::Cell;
+
+use bevy::prelude::*;
+#[cfg(feature = "bevy_mod_picking")]
+use bevy::utils::HashSet;
+#[cfg(feature = "bevy_mod_picking")]
+use bevy_mod_picking::events::{DragEnd, DragEnter, DragLeave, DragStart, Pointer};
+
+/// The entities being dragged, and those being dragged over, both currently and when styles
+/// were last updated.
+#[cfg(feature = "bevy_mod_picking")]
+#[derive(Resource, Default)]
+pub(crate) struct DragStates {
+    dragging: HashSet<Entity>,
+    /// Pairs of drop targets and the entities being dragged over them.
+    drag_over: HashSet<(Entity, Entity)>,
+    dragging_prev: HashSet<Entity>,
+    drag_over_prev: HashSet<Entity>,
+}
+
+#[cfg(feature = "bevy_mod_picking")]
+impl DragStates {
+    /// True if the entity is being dragged.
+    pub(crate) fn is_dragging(&self, e: Entity) -> bool {
+        self.dragging.contains(&e)
+    }
+
+    /// True if anything is being dragged over the entity.
+    pub(crate) fn is_drag_over(&self, e: Entity) -> bool {
+        self.drag_over.iter().any(|(target, _)| *target == e)
+    }
+
+    /// True if the entity was being dragged when styles were last updated.
+    pub(crate) fn was_dragging(&self, e: Entity) -> bool {
+        self.dragging_prev.contains(&e)
+    }
+
+    /// True if anything was being dragged over the entity when styles were last updated.
+    pub(crate) fn was_drag_over(&self, e: Entity) -> bool {
+        self.drag_over_prev.contains(&e)
+    }
+
+    /// Record the current state as the previous one.
+    pub(crate) fn save(&mut self) {
+        self.dragging_prev.clone_from(&self.dragging);
+        self.drag_over_prev = self.drag_over.iter().map(|(target, _)| *target).collect();
+    }
+}
+
+/// Update the drag states from this frame's drag events, before styles are updated, so that
+/// drag styles apply and revert on the frames the events are sent.
+#[cfg(feature = "bevy_mod_picking")]
+pub(crate) fn update_drag_states(
+    mut drag_start: EventReader<Pointer<DragStart>>,
+    mut drag_end: EventReader<Pointer<DragEnd>>,
+    mut drag_enter: EventReader<Pointer<DragEnter>>,
+    mut drag_leave: EventReader<Pointer<DragLeave>>,
+    mut states: ResMut<DragStates>,
+) {
+    for event in drag_start.read() {
+        states.dragging.insert(event.target);
+    }
+    for event in drag_enter.read() {
+        states.drag_over.insert((event.target, event.dragged));
+    }
+    for event in drag_leave.read() {
+        states.drag_over.remove(&(event.target, event.dragged));
+    }
+    for event in drag_end.read() {
+        let dragged = event.target;
+        states.dragging.remove(&dragged);
+        // Targets are left when a drag ends, but not if they've been despawned meanwhile.
+        states.drag_over.retain(|(_, other)| *other != dragged);
+    }
+}
+
+/// Record the drag states as the previous ones, once styles have been updated.
+#[cfg(feature = "bevy_mod_picking")]
+pub(crate) fn save_drag_states(mut states: ResMut<DragStates>) {
+    states.save();
+}
+
+/// Flag recording that the drag pseudo-classes have been used without the `bevy_mod_picking`
+/// feature, so that it's only reported once.
+#[cfg(not(feature = "bevy_mod_picking"))]
+#[derive(Default)]
+pub(crate) struct UnsupportedDragClasses(Cell<bool>);
+
+#[cfg(not(feature = "bevy_mod_picking"))]
+impl UnsupportedDragClasses {
+    /// Log a warning the first time a drag pseudo-class is matched.
+    pub(crate) fn report(&self) {
+        if !self.0.replace(true) {
+            #[cfg(test)]
+            tests::UNSUPPORTED_REPORTS.with(|n| n.set(n.get() + 1));
+            warn!(
+                "The :dragging and :drag-over pseudo-classes never match without the \
+                bevy_mod_picking feature"
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::{ElementClasses, ElementStyles, PeacockPlugin, StyleHandle};
+    #[cfg(not(feature = "bevy_mod_picking"))]
+    use bevy::ecs::schedule::ExecutorKind;
+    #[cfg(feature = "bevy_mod_picking")]
+    use bevy::render::camera::NormalizedRenderTarget;
+    #[cfg(feature = "bevy_mod_picking")]
+    use bevy_mod_picking::{
+        backend::HitData,
+        focus::{HoverMap, PreviousHoverMap},
+        pointer::{Location, PointerButton, PointerId},
+    };
+    #[cfg(not(feature = "bevy_mod_picking"))]
+    use std::cell::Cell;
+
+    #[cfg(not(feature = "bevy_mod_picking"))]
+    thread_local! {
+        /// Number of times the missing feature has been reported.
+        pub(crate) static UNSUPPORTED_REPORTS: Cell<usize> = const { Cell::new(0) };
+    }
+
+    /// Spawn an item and a slot which it can be dropped on, styled with the drag
+    /// pseudo-classes.
+    fn spawn_item_and_slot(app: &mut App) -> (Entity, Entity) {
+        let style = StyleHandle::build(|ss| {
+            ss.background_color(Color::WHITE)
+                .selector("&:dragging", |ss| ss.background_color(Color::BLUE))
+                .selector("&:drag-over", |ss| ss.background_color(Color::GREEN))
+        });
+        let mut spawn = || {
+            app.world
+                .spawn((
+                    NodeBundle::default(),
+                    ElementStyles::new(std::slice::from_ref(&style)),
+                    ElementClasses::default(),
+                ))
+                .id()
+        };
+        (spawn(), spawn())
+    }
+
+    fn bg(app: &App, entity: Entity) -> Color {
+        app.world.get::<BackgroundColor>(entity).unwrap().0
+    }
+
+    fn test_app() -> App {
+        let mut app = crate::test_utils::test_app();
+        app.add_plugins(PeacockPlugin);
+        #[cfg(feature = "bevy_mod_picking")]
+        app.add_event::<Pointer<DragStart>>()
+            .add_event::<Pointer<DragEnd>>()
+            .add_event::<Pointer<DragEnter>>()
+            .add_event::<Pointer<DragLeave>>();
+        app
+    }
+
+    #[cfg(feature = "bevy_mod_picking")]
+    fn send<E>(app: &mut App, target: Entity, event: E)
+    where
+        E: std::fmt::Debug + Clone + Reflect,
+        Pointer<E>: Event,
+    {
+        let location = Location {
+            target: NormalizedRenderTarget::Image(Handle::default()),
+            position: Vec2::ZERO,
+        };
+        app.world
+            .send_event(Pointer::new(PointerId::Mouse, location, target, event));
+    }
+
+    #[cfg(feature = "bevy_mod_picking")]
+    #[test]
+    fn test_drag_timeline() {
+        let mut app = test_app();
+        let (item, slot) = spawn_item_and_slot(&mut app);
+        let button = PointerButton::Primary;
+        let hit = HitData::new(Entity::PLACEHOLDER, 0., None, None);
+        app.update();
+        assert_eq!(bg(&app, item), Color::WHITE);
+
+        send(
+            &mut app,
+            item,
+            DragStart {
+                button,
+                hit: hit.clone(),
+            },
+        );
+        app.update();
+        assert_eq!(bg(&app, item), Color::BLUE);
+        assert_eq!(bg(&app, slot), Color::WHITE);
+
+        let dragged = item;
+        send(
+            &mut app,
+            slot,
+            DragEnter {
+                button,
+                dragged,
+                hit: hit.clone(),
+            },
+        );
+        app.update();
+        assert_eq!(bg(&app, slot), Color::GREEN);
+
+        send(
+            &mut app,
+            slot,
+            DragLeave {
+                button,
+                dragged,
+                hit: hit.clone(),
+            },
+        );
+        app.update();
+        assert_eq!(bg(&app, slot), Color::WHITE);
+        assert_eq!(bg(&app, item), Color::BLUE);
+
+        // Ending the drag over a target leaves both.
+        send(
+            &mut app,
+            slot,
+            DragEnter {
+                button,
+                dragged,
+                hit,
+            },
+        );
+        app.update();
+        assert_eq!(bg(&app, slot), Color::GREEN);
+        let distance = Vec2::ZERO;
+        send(&mut app, item, DragEnd { button, distance });
+        app.update();
+        assert_eq!(bg(&app, item), Color::WHITE);
+        assert_eq!(bg(&app, slot), Color::WHITE);
+    }
+
+    #[cfg(not(feature = "bevy_mod_picking"))]
+    #[test]
+    fn test_drag_without_picking() {
+        UNSUPPORTED_REPORTS.with(|n| n.set(0));
+        let mut app = test_app();
+        // Styles are matched on this thread, so that the reports can be counted.
+        app.edit_schedule(Update, |schedule| {
+            schedule.set_executor_kind(ExecutorKind::SingleThreaded);
+        });
+        let (item, slot) = spawn_item_and_slot(&mut app);
+        app.update();
+        app.update();
+        assert_eq!(bg(&app, item), Color::WHITE);
+        assert_eq!(bg(&app, slot), Color::WHITE);
+        assert_eq!(UNSUPPORTED_REPORTS.with(|n| n.get()), 1);
+    }
+}
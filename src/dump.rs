@@ -0,0 +1,505 @@
+//! A one-shot description of everything that went into an element's styles, for debugging a
+//! single element without enabling traces for the whole app.
+
+use std::fmt::{self, Write};
+
+use bevy::{ecs::event::ManualEventReader, ecs::system::SystemState, prelude::*};
+use bevy_peacock_style::{ComputedStyle, StyleProp, TransitionState};
+
+use crate::{
+    animate::{
+        AnimatedBackgroundColor, AnimatedBorderColor, AnimatedFontSize, AnimatedIn, AnimatedLayout,
+        AnimatedOutline, AnimatedTransform, ElevatedZIndex, TransformPart,
+    },
+    selector_matcher::init_matcher_resources,
+    style_handle::TextStyles,
+    update::compute_element_style,
+    ElementClasses, ElementStyles, LayoutMeasures, SelectorMatcher, StyleHandle, StyleRegistry,
+    StyleRoot,
+};
+
+/// Event which logs the [`dump_entity_style`] of an entity, at the info level, once the styles
+/// of the frame have been computed. Handled by [`PeacockPlugin`](crate::PeacockPlugin).
+#[derive(Event, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DebugDumpStyle(pub Entity);
+
+/// Describe how the styles of `entity` cascade, as they would be computed right now: its
+/// classes, its styles, whether each of their selector entries matches, the groups of
+/// properties applied in order, the resulting values by category, the text styles it inherits
+/// from each ancestor, and its running animations.
+///
+/// The styles are recomputed for the description, so it doesn't need
+/// [`PeacockConfig::trace_styles`](crate::PeacockConfig), and reflects the current classes and
+/// pseudo-class state even if the element hasn't been restyled since they changed. Nothing is
+/// written to the element. Missing entities and broken hierarchies are described rather than
+/// panicking on.
+pub fn dump_entity_style(world: &mut World, entity: Entity) -> String {
+    let mut out = String::new();
+    if world.get_entity(entity).is_none() {
+        writeln!(out, "Style of {:?}: no such entity", entity).ok();
+        return out;
+    }
+    init_matcher_resources(world);
+    let mut state = SystemState::<(SelectorMatcher, LayoutMeasures)>::new(world);
+    let (matcher, measures) = state.get(world);
+    write_dump(&mut out, world, &matcher, &measures, entity).ok();
+    out
+}
+
+/// Log the dumps requested by [`DebugDumpStyle`] events.
+pub(crate) fn log_style_dumps(
+    world: &mut World,
+    mut reader: Local<ManualEventReader<DebugDumpStyle>>,
+) {
+    let Some(events) = world.get_resource::<Events<DebugDumpStyle>>() else {
+        return;
+    };
+    let entities: Vec<Entity> = reader.read(events).map(|event| event.0).collect();
+    for entity in entities {
+        info!("{}", dump_entity_style(world, entity));
+    }
+}
+
+fn write_dump(
+    out: &mut String,
+    world: &World,
+    matcher: &SelectorMatcher,
+    measures: &LayoutMeasures,
+    entity: Entity,
+) -> fmt::Result {
+    let entity_ref = world.entity(entity);
+    write!(out, "Style of {:?}", entity)?;
+    if let Some(name) = entity_ref.get::<Name>() {
+        write!(out, " \"{}\"", name)?;
+    }
+    writeln!(out)?;
+
+    // The ancestors, nearest first, stopping at a parent which was despawned.
+    let mut ancestors = Vec::new();
+    let mut next = entity_ref.get::<Parent>().map(Parent::get);
+    while let Some(parent) = next {
+        let Some(parent_ref) = world.get_entity(parent) else {
+            writeln!(out, "Parent {:?} is missing", parent)?;
+            break;
+        };
+        ancestors.push(parent);
+        next = parent_ref.get::<Parent>().map(Parent::get);
+    }
+
+    writeln!(out, "Classes:")?;
+    match entity_ref.get::<ElementClasses>() {
+        Some(classes) if !classes.is_empty() => {
+            writeln!(out, "  {}", classes.iter().collect::<Vec<_>>().join(" "))?
+        }
+        _ => writeln!(out, "  (none)")?,
+    }
+
+    // The tree's default styles apply to the style root, and to elements with styles.
+    let element_styles = entity_ref.get::<ElementStyles>();
+    let style_root = std::iter::once(entity)
+        .chain(ancestors.iter().copied())
+        .find_map(|e| world.get::<StyleRoot>(e).map(|root| (e, root)))
+        .filter(|(root, _)| *root == entity || element_styles.is_some());
+    let registry = world.get_resource::<StyleRegistry>();
+    let mut handles: Vec<(String, &StyleHandle, bool)> = Vec::new();
+    if let Some((_, root)) = style_root {
+        for (index, ss) in root.styles.styles.iter().enumerate() {
+            let name = registry.and_then(|registry| registry.name_of(ss));
+            let name = name.map_or_else(|| format!("root #{}", index), str::to_owned);
+            handles.push((name, ss, root.styles.is_enabled_at(index)));
+        }
+    }
+    if let Some(element_styles) = element_styles {
+        for (index, ss) in element_styles.styles.iter().enumerate() {
+            let name = registry.and_then(|registry| registry.name_of(ss));
+            let name = name.map_or_else(|| format!("#{}", index), str::to_owned);
+            handles.push((name, ss, element_styles.is_enabled_at(index)));
+        }
+    }
+    // Each style is followed by its user override, if it has one.
+    let handles: Vec<(String, &StyleHandle, bool)> = handles
+        .into_iter()
+        .flat_map(|(name, ss, enabled)| {
+            let ovr = registry
+                .and_then(|registry| registry.override_for(ss))
+                .map(|ovr| (format!("{} (override)", name), ovr, enabled));
+            std::iter::once((name, ss, enabled)).chain(ovr)
+        })
+        .collect();
+
+    writeln!(out, "Styles:")?;
+    if handles.is_empty() {
+        writeln!(out, "  (none)")?;
+    }
+    for (name, _, enabled) in handles.iter() {
+        match enabled {
+            true => writeln!(out, "  {}", name)?,
+            false => writeln!(out, "  {} (disabled)", name)?,
+        }
+    }
+    if let Some((root, _)) = style_root {
+        writeln!(out, "  (root styles from {:?})", root)?;
+    }
+
+    writeln!(out, "Selectors:")?;
+    for (name, ss, _) in handles.iter().filter(|(_, _, enabled)| *enabled) {
+        let props = ss.props();
+        for (scope, _) in props.scopes() {
+            let matched = matcher.scope_match(scope, &entity);
+            writeln!(
+                out,
+                "  {} @scope({}): {}",
+                name,
+                scope,
+                match_result(matched)
+            )?;
+        }
+        for ((selector, _), compiled) in props.selectors().iter().zip(props.compiled_selectors()) {
+            let matched = matcher.compiled_match(compiled, &entity);
+            writeln!(out, "  {} {}: {}", name, selector, match_result(matched))?;
+        }
+    }
+
+    // The text styles this element inherits are those of its nearest ancestor which has them.
+    // Root nodes set the UI scale for their tree, so theirs is recomputed if it changed, as in
+    // the style pass.
+    let inherited = ancestors
+        .iter()
+        .find_map(|e| world.get::<TextStyles>(*e))
+        .cloned()
+        .unwrap_or_default();
+    let px_scale = match ancestors.is_empty() {
+        true => entity_ref.get::<TextStyles>().and_then(|ts| ts.ui_scale),
+        false => inherited.ui_scale,
+    };
+    let default_style = Style::default();
+    let default_registry = StyleRegistry::default();
+    let compute = |px_scale| {
+        compute_element_style(
+            matcher,
+            registry.unwrap_or(&default_registry),
+            entity,
+            style_root.map(|(_, root)| &root.styles),
+            element_styles,
+            entity_ref.get::<Style>().unwrap_or(&default_style),
+            &inherited,
+            px_scale,
+            measures.parent_size_of(entity).unwrap_or_default(),
+            true,
+        )
+    };
+    let (mut computed, mut trace) = compute(px_scale);
+    if ancestors.is_empty() && computed.ui_scale != px_scale {
+        (computed, trace) = compute(computed.ui_scale);
+    }
+    let trace = trace.unwrap_or_default();
+
+    writeln!(out, "Applied:")?;
+    for line in trace.to_string().lines() {
+        writeln!(out, "  {}", line)?;
+    }
+
+    writeln!(out, "Computed:")?;
+    for (category, props) in computed_categories(&computed) {
+        if props.is_empty() {
+            continue;
+        }
+        writeln!(out, "  {}:", category)?;
+        for prop in props {
+            writeln!(out, "    {}", prop)?;
+        }
+    }
+
+    writeln!(out, "Text styles:")?;
+    for e in std::iter::once(entity).chain(ancestors.iter().copied()) {
+        let Some(text_styles) = world.get::<TextStyles>(e) else {
+            continue;
+        };
+        let mut parts = Vec::new();
+        if let Some((ref path, _)) = text_styles.loaded_font {
+            parts.push(format!("font: \"{}\"", path));
+        }
+        if let Some(font_size) = text_styles.font_size {
+            parts.push(StyleProp::FontSize(font_size).to_string());
+        }
+        if text_styles.color.is_some() {
+            parts.push(StyleProp::Color(text_styles.color).to_string());
+        }
+        if let Some(ui_scale) = text_styles.ui_scale {
+            parts.push(StyleProp::UiScale(ui_scale).to_string());
+        }
+        if parts.is_empty() {
+            parts.push("(none)".to_owned());
+        }
+        let own = if e == entity { " (self)" } else { "" };
+        writeln!(out, "  {:?}{}: {}", e, own, parts.join(", "))?;
+    }
+
+    writeln!(out, "Animations:")?;
+    for line in animation_states(world, entity) {
+        writeln!(out, "  {}", line)?;
+    }
+    Ok(())
+}
+
+fn match_result(matched: bool) -> &'static str {
+    if matched {
+        "matched"
+    } else {
+        "not matched"
+    }
+}
+
+/// The values which a computed style sets, as properties grouped by category.
+fn computed_categories(computed: &ComputedStyle) -> Vec<(&'static str, Vec<StyleProp>)> {
+    let layout = computed
+        .style_fields
+        .iter()
+        .map(|field| field.prop(&computed.style))
+        .collect();
+
+    let mut text = Vec::new();
+    if let Some(ref font) = computed.font {
+        text.push(StyleProp::Font(Some(font.clone())));
+    }
+    if let Some(font_size) = computed.font_size {
+        text.push(StyleProp::FontSize(font_size));
+    }
+    if computed.color.is_some() {
+        text.push(StyleProp::Color(computed.color));
+    }
+    if let Some(alignment) = computed.alignment {
+        text.push(StyleProp::TextAlign(alignment));
+    }
+    if let Some(text_overflow) = computed.text_overflow {
+        text.push(StyleProp::TextOverflow(text_overflow));
+    }
+
+    let mut visual = Vec::new();
+    if computed.background_color.is_some() {
+        visual.push(StyleProp::BackgroundColor(computed.background_color));
+    }
+    if computed.border_color.is_some() {
+        visual.push(StyleProp::BorderColor(computed.border_color));
+    }
+    if computed.outline_color.is_some() {
+        visual.push(StyleProp::OutlineColor(computed.outline_color));
+        visual.push(StyleProp::OutlineWidth(computed.outline_width));
+        visual.push(StyleProp::OutlineOffset(computed.outline_offset));
+    }
+    if !matches!(computed.z_index, ZIndex::Local(0)) {
+        visual.push(StyleProp::ZIndex(computed.z_index));
+    }
+    if let Some(pickable) = computed.pickable {
+        visual.push(StyleProp::PointerEvents(pickable));
+    }
+
+    let mut transform = Vec::new();
+    if let Some(scale_x) = computed.scale_x {
+        transform.push(StyleProp::ScaleX(scale_x));
+    }
+    if let Some(scale_y) = computed.scale_y {
+        transform.push(StyleProp::ScaleY(scale_y));
+    }
+    if let Some(rotation) = computed.rotation {
+        transform.push(StyleProp::Rotation(rotation));
+    }
+    if let Some(translation) = computed.translation {
+        transform.push(StyleProp::Translation(translation));
+    }
+
+    let mut image = Vec::new();
+    if computed.image.is_some() {
+        image.push(StyleProp::BackgroundImage(computed.image.clone()));
+        image.push(StyleProp::BackgroundImageFlipX(computed.flip_x));
+        image.push(StyleProp::BackgroundImageFlipY(computed.flip_y));
+        if computed.image_tint.is_some() {
+            image.push(StyleProp::BackgroundImageTint(computed.image_tint));
+        }
+    }
+
+    let mut animation = Vec::new();
+    if !computed.transitions.is_empty() {
+        animation.push(StyleProp::Transition(computed.transitions.clone()));
+    }
+    if !computed.animate_in.is_empty() {
+        animation.push(StyleProp::AnimateIn(computed.animate_in.clone()));
+    }
+    if computed.elevate_while_animating {
+        animation.push(StyleProp::ElevateWhileAnimating(true));
+    }
+
+    vec![
+        ("layout", layout),
+        ("text", text),
+        ("visual", visual),
+        ("transform", transform),
+        ("image", image),
+        ("animation", animation),
+    ]
+}
+
+/// One line for each of the element's animations, with its clock.
+fn animation_states(world: &World, entity: Entity) -> Vec<String> {
+    fn describe(name: impl fmt::Display, state: &TransitionState) -> String {
+        let status = if state.clock < 1. {
+            "running"
+        } else {
+            "finished"
+        };
+        format!("{}: clock {:.2} ({})", name, state.clock, status)
+    }
+
+    let mut lines = Vec::new();
+    if let Some(anim) = world.get::<AnimatedTransform>(entity) {
+        for part in TransformPart::ALL {
+            if let Some(state) = anim.state(part) {
+                lines.push(describe(format_args!("{:?}", part), state));
+            }
+        }
+    }
+    if let Some(anim) = world.get::<AnimatedBackgroundColor>(entity) {
+        lines.push(describe("BackgroundColor", &anim.0.state));
+    }
+    if let Some(anim) = world.get::<AnimatedBorderColor>(entity) {
+        lines.push(describe("BorderColor", &anim.0.state));
+    }
+    if let Some(anim) = world.get::<AnimatedLayout>(entity) {
+        let mut layout: Vec<String> = anim
+            .0
+            .iter()
+            .map(|(prop, anim)| describe(format_args!("{:?}", prop), &anim.state))
+            .collect();
+        layout.sort();
+        lines.extend(layout);
+    }
+    if let Some(anim) = world.get::<AnimatedOutline>(entity) {
+        let mut lengths: Vec<String> = anim
+            .lengths
+            .iter()
+            .map(|(prop, anim)| describe(format_args!("{:?}", prop), &anim.state))
+            .collect();
+        lengths.sort();
+        lines.extend(lengths);
+        if let Some(ref color) = anim.color {
+            lines.push(describe("OutlineColor", &color.state));
+        }
+    }
+    if let Some(anim) = world.get::<AnimatedFontSize>(entity) {
+        lines.push(describe("FontSize", &anim.0.state));
+    }
+    if let Some(anim) = world.get::<AnimatedIn>(entity) {
+        lines.push(format!("entrance: until {:.2}s", anim.until));
+    }
+    if world.get::<ElevatedZIndex>(entity).is_some() {
+        lines.push("z-index elevated while animating".to_owned());
+    }
+    if lines.is_empty() {
+        lines.push("(none)".to_owned());
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::set_hovered;
+    use bevy_peacock_style::parse_stylesheet;
+
+    #[test]
+    fn test_dump_entity_style() {
+        let mut app = crate::test_utils::plugin_test_app();
+
+        let mut registry = StyleRegistry::default();
+        registry.register_sheet(
+            parse_stylesheet(
+                "PANEL { font_size: 20; color: #fff; }
+                BUTTON {
+                    width: 10px;
+                    transition: width 1s;
+                    :hover { background_color: #0f0; }
+                    .panel > & { height: 5px; }
+                    .selected { width: 30px; }
+                }",
+            )
+            .unwrap(),
+        );
+        let panel_style = registry.get("PANEL").unwrap();
+        let button_style = registry.get("BUTTON").unwrap();
+        app.world.insert_resource(registry);
+        let button = app
+            .world
+            .spawn((
+                NodeBundle::default(),
+                Name::new("ok"),
+                ElementClasses::new("button"),
+                ElementStyles::new([button_style]),
+            ))
+            .id();
+        let panel = app
+            .world
+            .spawn((
+                NodeBundle::default(),
+                ElementClasses::new("panel"),
+                ElementStyles::new([panel_style]),
+            ))
+            .add_child(button)
+            .id();
+        set_hovered(&mut app.world, Some(button));
+        app.world.send_event(DebugDumpStyle(button));
+        app.update();
+
+        let dump = dump_entity_style(&mut app.world, button);
+        for expected in [
+            &format!("Style of {:?} \"ok\"\n", button),
+            "Classes:\n  button\n",
+            "Styles:\n  BUTTON\n",
+            "  BUTTON :hover: matched\n",
+            "  BUTTON .panel > &: matched\n",
+            "  BUTTON .selected: not matched\n",
+            "Applied:\n  BUTTON: width, transition\n  BUTTON :hover: background_color\n",
+            "    width: 10px\n    height: 5px\n",
+            "  text:\n    font_size: 20\n",
+            "  visual:\n    background_color: ",
+            &format!("  {:?}: font_size: 20, color: ", panel),
+            "Animations:\n  Width: clock 1.00 (finished)\n",
+        ] {
+            assert!(dump.contains(expected), "{:?} not in:\n{}", expected, dump);
+        }
+
+        // Moving off the element and selecting it changes the matches, without restyling it.
+        set_hovered(&mut app.world, None);
+        app.world
+            .get_mut::<ElementClasses>(button)
+            .unwrap()
+            .add_class("selected");
+        let dump = dump_entity_style(&mut app.world, button);
+        assert!(dump.contains("  BUTTON :hover: not matched\n"), "{}", dump);
+        assert!(dump.contains("  BUTTON .selected: matched\n"), "{}", dump);
+        assert!(dump.contains("    width: 30px\n"), "{}", dump);
+        assert_eq!(app.world.get::<Style>(button).unwrap().width, Val::Px(10.));
+
+        // Once restyled, the width animates towards the new value.
+        app.update();
+        let dump = dump_entity_style(&mut app.world, button);
+        assert!(dump.contains("(running)\n"), "{}", dump);
+
+        // Despawning the parent alone leaves a broken hierarchy, which is described.
+        app.world.entity_mut(panel).despawn();
+        let dump = dump_entity_style(&mut app.world, button);
+        assert!(
+            dump.contains(&format!("Parent {:?} is missing\n", panel)),
+            "{}",
+            dump
+        );
+        assert!(
+            dump.contains("  BUTTON .panel > &: not matched\n"),
+            "{}",
+            dump
+        );
+        assert_eq!(
+            dump_entity_style(&mut app.world, panel),
+            format!("Style of {:?}: no such entity\n", panel)
+        );
+    }
+}
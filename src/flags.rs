@@ -0,0 +1,190 @@
+//! Feature flags, which select the `@flag name { ... }` blocks of styles.
+
+use bevy::{prelude::*, utils::HashSet};
+
+use crate::restyle::RestyleDependents;
+
+/// Resource holding the feature flags which are enabled, for example for a UI experiment.
+/// Selectors and blocks written `@flag name` only apply while the flag is enabled. Flags which
+/// aren't in the set, including ones the app has never heard of, are disabled, so stylesheets
+/// can refer to flags ahead of the code which enables them. Without this resource, no flags are
+/// enabled.
+#[derive(Resource, Default, Clone, Debug, PartialEq, Eq)]
+pub struct StyleFlags(pub HashSet<String>);
+
+impl StyleFlags {
+    /// Construct a new `StyleFlags` with the given flags enabled.
+    pub fn new<I>(flags: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Into<String>,
+    {
+        Self(flags.into_iter().map(Into::into).collect())
+    }
+
+    /// True if the flag is enabled.
+    pub fn is_enabled(&self, name: &str) -> bool {
+        self.0.contains(name)
+    }
+
+    /// Enable or disable a flag.
+    pub fn set(&mut self, name: impl Into<String>, enabled: bool) {
+        let name = name.into();
+        if enabled {
+            self.0.insert(name);
+        } else {
+            self.0.remove(&name);
+        }
+    }
+}
+
+/// Restyle the elements and trees whose styles depend on a flag which was enabled or disabled
+/// since the last update. The previous flags are compared, so that writes which don't change them are
+/// ignored.
+pub(crate) fn restyle_changed_flags(
+    flags: Option<Res<StyleFlags>>,
+    mut flags_prev: Local<HashSet<String>>,
+    mut restyle: RestyleDependents,
+) {
+    let current = match flags {
+        Some(ref flags) if !flags.is_changed() => return,
+        Some(ref flags) => flags.0.clone(),
+        None if flags_prev.is_empty() => return,
+        None => HashSet::new(),
+    };
+    let changed: HashSet<String> = current.symmetric_difference(&flags_prev).cloned().collect();
+    *flags_prev = current;
+    if changed.is_empty() {
+        return;
+    }
+    restyle.restyle(|styles| {
+        styles
+            .feature_flags()
+            .iter()
+            .any(|flag| changed.contains(flag))
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ElementStyles, RestyleEntity, StyleRegistry, StyleRoot};
+    use bevy_peacock_style::parse_stylesheet;
+
+    #[test]
+    fn test_style_flags() {
+        let mut app = crate::test_utils::plugin_test_app();
+
+        let mut registry = StyleRegistry::default();
+        registry.register_sheet(
+            parse_stylesheet(
+                "BUTTON {
+                    width: 10px;
+                    height: 10px;
+                    @flag new_checkout {
+                        &.cta { width: 20px; }
+                    }
+                    @flag unknown { height: 99px; }
+                }
+                PLAIN { width: 5px; }",
+            )
+            .unwrap(),
+        );
+        let button = registry.get("BUTTON").unwrap();
+        let plain = registry.get("PLAIN").unwrap();
+        app.world.insert_resource(registry);
+        let cta = app
+            .world
+            .spawn((
+                NodeBundle::default(),
+                crate::ElementClasses::new("cta"),
+                ElementStyles::new([button]),
+            ))
+            .id();
+        let other = app
+            .world
+            .spawn((NodeBundle::default(), ElementStyles::new([plain])))
+            .id();
+        let style = |app: &App, entity| {
+            let style = app.world.get::<Style>(entity).unwrap();
+            (style.width, style.height)
+        };
+        // The entities restyled because of a change to the flags since the last call.
+        let mut reader = app.world.resource::<Events<RestyleEntity>>().get_reader();
+        let mut restyled = |app: &App| {
+            reader
+                .read(app.world.resource::<Events<RestyleEntity>>())
+                .map(|event| event.0)
+                .collect::<Vec<_>>()
+        };
+
+        // Without the resource, no flags are enabled.
+        app.update();
+        assert_eq!(style(&app, cta), (Val::Px(10.), Val::Px(10.)));
+
+        // Enabling a flag only changes the entries which depend on it, and only restyles the
+        // elements using them.
+        app.world.insert_resource(StyleFlags::new(["new_checkout"]));
+        app.update();
+        assert_eq!(restyled(&app), [cta]);
+        assert_eq!(style(&app, cta), (Val::Px(20.), Val::Px(10.)));
+        app.world.resource_mut::<StyleFlags>().set("beta", true);
+        app.update();
+        assert_eq!(restyled(&app), []);
+        assert_eq!(style(&app, other), (Val::Px(5.), Val::Auto));
+
+        // Disabling it again, or removing the resource, restores the other properties.
+        app.world
+            .resource_mut::<StyleFlags>()
+            .set("new_checkout", false);
+        app.update();
+        assert_eq!(style(&app, cta), (Val::Px(10.), Val::Px(10.)));
+        app.world
+            .resource_mut::<StyleFlags>()
+            .set("new_checkout", true);
+        app.update();
+        assert_eq!(style(&app, cta), (Val::Px(20.), Val::Px(10.)));
+        app.world.remove_resource::<StyleFlags>();
+        app.update();
+        assert_eq!(style(&app, cta), (Val::Px(10.), Val::Px(10.)));
+    }
+
+    #[test]
+    fn test_flags_in_root_styles() {
+        let mut app = crate::test_utils::plugin_test_app();
+        let mut registry = StyleRegistry::default();
+        registry.register_sheet(
+            parse_stylesheet(
+                "THEME {
+                    height: 10px;
+                    @flag tall { height: 40px; }
+                }
+                PANEL { width: 10px; }",
+            )
+            .unwrap(),
+        );
+        let theme = registry.get("THEME").unwrap();
+        let panel = registry.get("PANEL").unwrap();
+        app.world.insert_resource(registry);
+
+        // Only the root's default styles use the flag.
+        let root = app
+            .world
+            .spawn((NodeBundle::default(), StyleRoot::new(&[theme])))
+            .id();
+        let child = app
+            .world
+            .spawn((NodeBundle::default(), ElementStyles::new([panel])))
+            .set_parent(root)
+            .id();
+        let height = |app: &App, entity| app.world.get::<Style>(entity).unwrap().height;
+        app.update();
+        assert_eq!(height(&app, root), Val::Px(10.));
+        assert_eq!(height(&app, child), Val::Px(10.));
+
+        app.world.insert_resource(StyleFlags::new(["tall"]));
+        app.update();
+        assert_eq!(height(&app, root), Val::Px(40.));
+        assert_eq!(height(&app, child), Val::Px(40.));
+    }
+}
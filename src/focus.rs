@@ -0,0 +1,305 @@
+//! The source of keyboard focus for the `:focus`, `:focus-within` and `:focus-visible`
+//! pseudo-classes.
+
+use bevy::{a11y::Focus, prelude::*};
+
+use crate::PeacockConfig;
+
+/// Resource holding the entity which has focus, as far as styles are concerned. By default
+/// [`PeacockPlugin`](crate::PeacockPlugin) mirrors Bevy's accessibility [`Focus`] resource into
+/// it. Apps which manage focus themselves, for example with gamepad navigation, can disable
+/// that by setting [`PeacockConfig::custom_focus`](crate::PeacockConfig::custom_focus), and
+/// write this resource directly, before [`PeacockSystemSet`](crate::PeacockSystemSet) runs.
+///
+/// Focus written after the set runs is styled on the next frame, and a warning is logged the
+/// first time that happens, since it's usually a missing ordering constraint.
+#[derive(Resource, Default, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PeacockFocusSource(pub Option<Entity>);
+
+/// Resource holding the entity which had focus when styles were computed on the previous frame,
+/// which the style pass compares with [`PeacockFocusSource`] to find the elements whose `:focus`
+/// states changed. It's captured from the focus which the previous style pass actually matched
+/// against, so it doesn't matter when, or how often, the focus is written during a frame.
+#[derive(Resource, Default, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PreviousFocus(pub(crate) Option<Entity>);
+
+impl PreviousFocus {
+    /// The entity which had focus when styles were computed on the previous frame.
+    pub fn get(&self) -> Option<Entity> {
+        self.0
+    }
+}
+
+/// The focus which the most recent style pass matched against.
+#[derive(Resource, Default, Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct StyledFocus(pub(crate) Option<Entity>);
+
+/// Record the focus seen by the previous style pass as the previous focus. This runs at the
+/// start of [`PeacockSystemSet`](crate::PeacockSystemSet), rather than snapshotting
+/// [`PeacockFocusSource`] after the pass, which would pick up focus written in between.
+pub(crate) fn update_focus(styled: Res<StyledFocus>, mut focus_prev: ResMut<PreviousFocus>) {
+    focus_prev.set_if_neq(PreviousFocus(styled.0));
+}
+
+/// Warn, once, if [`PeacockFocusSource`] was written after the style pass ran this frame.
+pub(crate) fn check_focus_order(
+    focus: Res<PeacockFocusSource>,
+    styled: Res<StyledFocus>,
+    mut warned: Local<bool>,
+) {
+    if *warned || focus.0 == styled.0 {
+        return;
+    }
+    *warned = true;
+    #[cfg(test)]
+    tests::FOCUS_ORDER_WARNINGS.with(|n| n.set(n.get() + 1));
+    warn!(
+        "PeacockFocusSource was changed after PeacockSystemSet ran, so the change won't be \
+        styled until the next frame. Order the system which sets the focus \
+        `.before(PeacockSystemSet)`."
+    );
+}
+
+/// Copy the accessibility focus into [`PeacockFocusSource`] when it changes, unless the app
+/// writes it itself. Without a [`Focus`] resource, nothing has focus.
+pub(crate) fn mirror_a11y_focus(
+    focus: Option<Res<Focus>>,
+    config: Option<Res<PeacockConfig>>,
+    mut source: ResMut<PeacockFocusSource>,
+) {
+    if config.is_some_and(|config| config.custom_focus) {
+        return;
+    }
+    let focused = focus.map(|focus| focus.0).unwrap_or_default();
+    source.set_if_neq(PeacockFocusSource(focused));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::plugin_test_app;
+    use crate::{ElementClasses, ElementStyles, PeacockSystemSet, StyleHandle};
+    use bevy::ecs::schedule::ExecutorKind;
+    use std::cell::Cell;
+
+    thread_local! {
+        /// Number of times focus written after the style pass has been reported.
+        pub(crate) static FOCUS_ORDER_WARNINGS: Cell<usize> = const { Cell::new(0) };
+    }
+
+    fn bg(app: &App, entity: Entity) -> Color {
+        app.world.get::<BackgroundColor>(entity).unwrap().0
+    }
+
+    #[test]
+    fn test_custom_focus() {
+        let mut app = plugin_test_app();
+        app.insert_resource(PeacockConfig {
+            custom_focus: true,
+            ..default()
+        });
+
+        let style = StyleHandle::build(|ss| {
+            ss.background_color(Color::RED)
+                .selector(":focus", |ss| ss.background_color(Color::BLUE))
+                .selector(":focus-within", |ss| ss.border_color(Color::GREEN))
+        });
+        let child = app.world.spawn(NodeBundle::default()).id();
+        let button = app
+            .world
+            .spawn((
+                NodeBundle::default(),
+                ElementStyles::new(&[style]),
+                ElementClasses::default(),
+            ))
+            .push_children(&[child])
+            .id();
+
+        // A stand-in for a gamepad navigation system, which focuses whatever it's told to.
+        #[derive(Resource, Default)]
+        struct Navigation(Option<Entity>);
+        app.init_resource::<Navigation>().add_systems(
+            Update,
+            (|nav: Res<Navigation>, mut source: ResMut<PeacockFocusSource>| {
+                source.0 = nav.0;
+            })
+            .before(PeacockSystemSet),
+        );
+
+        app.update();
+        assert_eq!(bg(&app, button), Color::RED);
+
+        app.world.resource_mut::<Navigation>().0 = Some(button);
+        app.update();
+        assert_eq!(bg(&app, button), Color::BLUE);
+
+        // Focusing a descendant is tracked through the custom source too.
+        app.world.resource_mut::<Navigation>().0 = Some(child);
+        app.update();
+        assert_eq!(bg(&app, button), Color::RED);
+        assert_eq!(
+            app.world.get::<BorderColor>(button).unwrap().0,
+            Color::GREEN
+        );
+
+        // The accessibility focus is ignored when a custom source is used.
+        app.world.resource_mut::<Navigation>().0 = None;
+        app.world.resource_mut::<Focus>().0 = Some(button);
+        app.update();
+        assert_eq!(bg(&app, button), Color::RED);
+    }
+
+    #[test]
+    fn test_focus_write_timing() {
+        FOCUS_ORDER_WARNINGS.with(|n| n.set(0));
+        let mut app = plugin_test_app();
+        app.insert_resource(PeacockConfig {
+            custom_focus: true,
+            ..default()
+        });
+
+        let style = StyleHandle::build(|ss| {
+            ss.background_color(Color::RED)
+                .selector(":focus", |ss| ss.background_color(Color::BLUE))
+        });
+        let button = app
+            .world
+            .spawn((
+                NodeBundle::default(),
+                ElementStyles::new(&[style]),
+                ElementClasses::default(),
+            ))
+            .id();
+
+        // Focus writes scripted before the style pass, after it but within the Peacock set,
+        // and after the set.
+        #[derive(Resource, Default)]
+        struct Script {
+            before: Option<Option<Entity>>,
+            within: Option<Option<Entity>>,
+            after: Option<Option<Entity>>,
+        }
+        fn write(write: Option<Option<Entity>>, source: &mut PeacockFocusSource) {
+            if let Some(focus) = write {
+                source.0 = focus;
+            }
+        }
+        // Count the frames in which the background changed.
+        #[derive(Resource, Default)]
+        struct Changes(usize);
+        app.init_resource::<Script>()
+            .init_resource::<Changes>()
+            .add_systems(
+                Update,
+                (
+                    (|mut s: ResMut<Script>, mut f: ResMut<PeacockFocusSource>| {
+                        write(s.before.take(), &mut f)
+                    })
+                    .before(PeacockSystemSet),
+                    (|mut s: ResMut<Script>, mut f: ResMut<PeacockFocusSource>| {
+                        write(s.within.take(), &mut f)
+                    })
+                    .after(crate::update::update_styles)
+                    .before(crate::PeacockStage::Flush),
+                    (|mut s: ResMut<Script>, mut f: ResMut<PeacockFocusSource>| {
+                        write(s.after.take(), &mut f)
+                    })
+                    .after(PeacockSystemSet),
+                ),
+            )
+            .add_systems(
+                Last,
+                |query: Query<Ref<BackgroundColor>>, mut changes: ResMut<Changes>| {
+                    if query.iter().any(|bg| bg.is_changed()) {
+                        changes.0 += 1;
+                    }
+                },
+            )
+            // Warnings are counted per thread.
+            .edit_schedule(Last, |schedule| {
+                schedule.set_executor_kind(ExecutorKind::SingleThreaded);
+            });
+        let step = |app: &mut App, script: Script| {
+            app.world.insert_resource(script);
+            app.world.resource_mut::<Changes>().0 = 0;
+            app.update();
+            let frame1 = (bg(app, button), app.world.resource::<Changes>().0);
+            app.world.resource_mut::<Changes>().0 = 0;
+            app.update();
+            let frame2 = (bg(app, button), app.world.resource::<Changes>().0);
+            (frame1, frame2)
+        };
+        app.update();
+        assert_eq!(bg(&app, button), Color::RED);
+
+        // Focus written before the set is styled on the same frame.
+        let script = Script {
+            before: Some(Some(button)),
+            ..default()
+        };
+        assert_eq!(step(&mut app, script), ((Color::BLUE, 1), (Color::BLUE, 0)));
+        assert_eq!(app.world.resource::<PreviousFocus>().get(), Some(button));
+
+        // Focus written after the style pass has run is styled on the next frame, rather than
+        // being lost.
+        let script = Script {
+            within: Some(None),
+            ..default()
+        };
+        assert_eq!(step(&mut app, script), ((Color::BLUE, 0), (Color::RED, 1)));
+        let script = Script {
+            after: Some(Some(button)),
+            ..default()
+        };
+        assert_eq!(step(&mut app, script), ((Color::RED, 0), (Color::BLUE, 1)));
+
+        // Several writes in a frame only change the style when the focus seen by the pass
+        // changes.
+        let script = Script {
+            before: Some(None),
+            within: Some(Some(button)),
+            after: Some(None),
+        };
+        assert_eq!(step(&mut app, script), ((Color::RED, 1), (Color::RED, 0)));
+        let script = Script {
+            before: Some(Some(button)),
+            within: Some(None),
+            after: Some(Some(button)),
+        };
+        assert_eq!(step(&mut app, script), ((Color::BLUE, 1), (Color::BLUE, 0)));
+
+        // Writes after the pass are reported once.
+        assert_eq!(FOCUS_ORDER_WARNINGS.with(|n| n.get()), 1);
+    }
+
+    #[test]
+    fn test_mirror_a11y_focus() {
+        let mut app = plugin_test_app();
+
+        let style = StyleHandle::build(|ss| {
+            ss.background_color(Color::RED)
+                .selector(":focus", |ss| ss.background_color(Color::BLUE))
+        });
+        let button = app
+            .world
+            .spawn((
+                NodeBundle::default(),
+                ElementStyles::new(&[style]),
+                ElementClasses::default(),
+            ))
+            .id();
+
+        // Works without the accessibility resource.
+        app.update();
+        assert_eq!(bg(&app, button), Color::RED);
+
+        app.insert_resource(Focus(Some(button)));
+        app.update();
+        assert_eq!(bg(&app, button), Color::BLUE);
+        assert_eq!(app.world.resource::<PeacockFocusSource>().0, Some(button));
+
+        app.world.remove_resource::<Focus>();
+        app.update();
+        assert_eq!(bg(&app, button), Color::RED);
+    }
+}
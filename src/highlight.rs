@@ -0,0 +1,229 @@
+//! A debug facility which outlines the entities matching a selector, for tuning selectors
+//! while the app runs.
+
+use bevy::prelude::*;
+use bevy_peacock_style::{Selector, StylesheetError};
+
+use crate::{
+    update::update_styles, ElementStyles, LayoutMeasures, PeacockSystemSet, SelectorMatcher,
+};
+
+/// Resource holding the selector whose matches [`PeacockSelectorHighlightPlugin`] outlines.
+/// Nothing is outlined while it's `None`.
+#[derive(Resource, Default, Clone, Debug, PartialEq)]
+pub struct DebugSelectorHighlight(pub Option<Selector>);
+
+/// Event which sets [`DebugSelectorHighlight`] to the selector parsed from the text, as typed
+/// into a debug UI. Empty text clears the highlight. If the text doesn't parse, the highlight
+/// is cleared and the error is reported in [`DebugSelectorResult::error`].
+#[derive(Event, Clone, Debug, PartialEq, Eq)]
+pub struct HighlightSelector(pub String);
+
+/// Resource holding the outcome of matching the [`DebugSelectorHighlight`], updated each frame,
+/// for debug UIs to display.
+#[derive(Resource, Default, Clone, Debug, PartialEq)]
+pub struct DebugSelectorResult {
+    /// The styled entities which the selector matches, in order of their ids.
+    pub matches: Vec<Entity>,
+    /// The error from parsing the text of the last [`HighlightSelector`] event, if it failed.
+    pub error: Option<StylesheetError>,
+}
+
+impl DebugSelectorResult {
+    /// The number of entities which the selector matches.
+    pub fn count(&self) -> usize {
+        self.matches.len()
+    }
+}
+
+/// Plugin which outlines the styled entities matching the [`DebugSelectorHighlight`] with
+/// gizmos, and reports them in [`DebugSelectorResult`]. The selector is matched against every
+/// entity with [`ElementStyles`], as if it were one of the entity's style selectors, after
+/// the styles are updated each frame, and the number of matches is logged when it changes.
+///
+/// The outlines are drawn in world coordinates, with the origin at the center of the primary
+/// window, which lines up with the UI when it's viewed by a 2D camera at the origin with the
+/// default scale. Requires Bevy's gizmos, and the `debug-highlight` feature.
+#[derive(Default)]
+pub struct PeacockSelectorHighlightPlugin;
+
+impl PeacockSelectorHighlightPlugin {
+    /// The color of the outlines.
+    pub const COLOR: Color = Color::FUCHSIA;
+}
+
+impl Plugin for PeacockSelectorHighlightPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<DebugSelectorHighlight>()
+            .init_resource::<DebugSelectorResult>()
+            .add_event::<HighlightSelector>()
+            .add_systems(
+                Update,
+                (
+                    parse_highlight_selector.before(update_styles),
+                    (update_selector_highlight, draw_selector_highlight)
+                        .chain()
+                        .after(update_styles),
+                )
+                    .in_set(PeacockSystemSet),
+            );
+    }
+}
+
+fn parse_highlight_selector(
+    mut events: EventReader<HighlightSelector>,
+    mut highlight: ResMut<DebugSelectorHighlight>,
+    mut result: ResMut<DebugSelectorResult>,
+) {
+    let Some(HighlightSelector(src)) = events.read().last() else {
+        return;
+    };
+    let parsed = match src.trim() {
+        "" => Ok(None),
+        src => src.parse::<Selector>().map(Some),
+    };
+    match parsed {
+        Ok(selector) => {
+            highlight.0 = selector;
+            result.error = None;
+        }
+        Err(err) => {
+            warn!("Can't highlight selector: {}", err);
+            highlight.0 = None;
+            result.error = Some(err);
+        }
+    }
+}
+
+fn update_selector_highlight(
+    highlight: Res<DebugSelectorHighlight>,
+    mut result: ResMut<DebugSelectorResult>,
+    matcher: SelectorMatcher,
+    query: Query<Entity, With<ElementStyles>>,
+) {
+    let matches = match highlight.0 {
+        Some(ref selector) => matching_entities(&matcher, selector, query.iter()),
+        None => Vec::new(),
+    };
+    if highlight.is_changed() || matches.len() != result.count() {
+        if let Some(ref selector) = highlight.0 {
+            info!("Selector '{}' matches {} entities", selector, matches.len());
+        }
+    }
+    if matches != result.matches {
+        result.matches = matches;
+    }
+}
+
+/// Return the entities which `selector` matches, sorted.
+fn matching_entities(
+    matcher: &SelectorMatcher,
+    selector: &Selector,
+    entities: impl Iterator<Item = Entity>,
+) -> Vec<Entity> {
+    // Cached results are keyed by the address of the selector, which may have been reused.
+    matcher.clear_cache();
+    let mut matches: Vec<Entity> = entities
+        .filter(|entity| matcher.selector_match(selector, entity))
+        .collect();
+    matches.sort_unstable();
+    matches
+}
+
+fn draw_selector_highlight(
+    result: Res<DebugSelectorResult>,
+    measures: LayoutMeasures,
+    mut gizmos: Gizmos,
+) {
+    let Some(viewport) = measures.viewport_size() else {
+        return;
+    };
+    for entity in result.matches.iter() {
+        let Some(rect) = measures.rect_of(*entity) else {
+            continue;
+        };
+        // Window coordinates have y pointing down, from the top-left corner.
+        let center = rect.center();
+        let position = Vec2::new(center.x - viewport.x / 2., viewport.y / 2. - center.y);
+        gizmos.rect_2d(
+            position,
+            0.,
+            rect.size(),
+            PeacockSelectorHighlightPlugin::COLOR,
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Disabled, ElementClasses, StyleHandle};
+    use bevy_peacock_style::StylesheetErrorKind;
+
+    #[test]
+    fn test_selector_matches() {
+        let mut app = crate::test_utils::plugin_test_app();
+        app.init_resource::<DebugSelectorHighlight>()
+            .init_resource::<DebugSelectorResult>()
+            .add_event::<HighlightSelector>()
+            .add_systems(
+                Update,
+                (
+                    parse_highlight_selector.before(update_styles),
+                    update_selector_highlight.after(update_styles),
+                ),
+            );
+
+        let style = StyleHandle::build(|ss| ss.width(10.));
+        let spawn = |world: &mut World, classes: &str| {
+            world
+                .spawn((
+                    NodeBundle::default(),
+                    ElementClasses::new(classes),
+                    ElementStyles::new([style.clone()]),
+                ))
+                .id()
+        };
+        let leaf = spawn(&mut app.world, "item");
+        let child = spawn(&mut app.world, "item");
+        app.world.entity_mut(child).insert(Disabled).add_child(leaf);
+        let other = spawn(&mut app.world, "other");
+        // The root has no styles, so it's never matched itself.
+        app.world
+            .spawn((NodeBundle::default(), ElementClasses::new("panel")))
+            .push_children(&[child, other]);
+
+        let highlight = |app: &mut App, src: &str| {
+            app.world.send_event(HighlightSelector(src.into()));
+            app.update();
+            app.world.resource::<DebugSelectorResult>().clone()
+        };
+
+        let result = highlight(&mut app, ".item");
+        assert_eq!(result.matches, [leaf, child]);
+        assert_eq!(result.count(), 2);
+        assert_eq!(highlight(&mut app, ".panel > &").matches, [child, other]);
+        assert_eq!(highlight(&mut app, ":disabled > .item").matches, [leaf]);
+        assert_eq!(highlight(&mut app, ".panel").matches, []);
+
+        // The matches follow changes to the tree while the selector stays set.
+        app.world.entity_mut(child).remove::<Disabled>();
+        highlight(&mut app, ":disabled > .item");
+        assert_eq!(app.world.resource::<DebugSelectorResult>().count(), 0);
+        app.world.entity_mut(child).insert(Disabled);
+        app.update();
+        assert_eq!(app.world.resource::<DebugSelectorResult>().matches, [leaf]);
+
+        // Errors are reported rather than panicking, and clear the highlight.
+        let result = highlight(&mut app, ".item > &.panel > .x");
+        assert_eq!(result.matches, []);
+        assert_eq!(
+            result.error.map(|err| err.kind),
+            Some(StylesheetErrorKind::Selector)
+        );
+        assert_eq!(app.world.resource::<DebugSelectorHighlight>().0, None);
+
+        let result = highlight(&mut app, "");
+        assert_eq!((result.matches, result.error), (vec![], None));
+    }
+}
@@ -0,0 +1,135 @@
+use bevy::prelude::*;
+
+use crate::{update::update_styles, ElementClasses, PeacockStage};
+
+/// Plugin which mirrors the [`Interaction`] of each element which has [`ElementClasses`] as the
+/// classes `hovered` and `pressed`, so that widgets built on Bevy's buttons can style their
+/// states without a system of their own. `hovered` is present while the state is
+/// `Interaction::Hovered`, and `pressed` while it's `Interaction::Pressed`. The element's other
+/// classes are left alone, and the classes are only written when the state changes or the
+/// element's classes are added.
+///
+/// When the `bevy_mod_picking` feature is enabled, the `:hover` pseudo-class is preferred for
+/// hovering, since it follows the picking backends rather than Bevy's own UI focus system.
+#[derive(Default)]
+pub struct PeacockInteractionClassesPlugin;
+
+impl PeacockInteractionClassesPlugin {
+    /// The class present while the element's `Interaction` is `Hovered`.
+    pub const HOVERED: &'static str = "hovered";
+    /// The class present while the element's `Interaction` is `Pressed`.
+    pub const PRESSED: &'static str = "pressed";
+}
+
+impl Plugin for PeacockInteractionClassesPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            update_interaction_classes
+                .before(update_styles)
+                .in_set(PeacockStage::ComputeStyles),
+        );
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn update_interaction_classes(
+    mut query: Query<
+        (&Interaction, &mut ElementClasses),
+        Or<(Changed<Interaction>, Added<ElementClasses>)>,
+    >,
+) {
+    for (interaction, mut classes) in query.iter_mut() {
+        for (class, enabled) in [
+            (
+                PeacockInteractionClassesPlugin::HOVERED,
+                *interaction == Interaction::Hovered,
+            ),
+            (
+                PeacockInteractionClassesPlugin::PRESSED,
+                *interaction == Interaction::Pressed,
+            ),
+        ] {
+            // Check first, so that the classes are only marked as changed if they are.
+            if classes.has_class(class) != enabled {
+                classes.set_class(class, enabled);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Resource, Default)]
+    struct ClassChanges(usize);
+
+    fn count_class_changes(
+        query: Query<(), Changed<ElementClasses>>,
+        mut changes: ResMut<ClassChanges>,
+    ) {
+        changes.0 += query.iter().count();
+    }
+
+    #[test]
+    fn test_interaction_classes() {
+        let mut app = App::new();
+        app.add_plugins(PeacockInteractionClassesPlugin)
+            .init_resource::<ClassChanges>()
+            .add_systems(
+                Update,
+                count_class_changes.after(update_interaction_classes),
+            );
+        let button = app
+            .world
+            .spawn((Interaction::None, ElementClasses::new("primary")))
+            .id();
+        let mut step = |interaction: Interaction| {
+            *app.world.get_mut::<Interaction>(button).unwrap() = interaction;
+            app.world.resource_mut::<ClassChanges>().0 = 0;
+            app.update();
+            let classes = app.world.get::<ElementClasses>(button).unwrap();
+            let mut classes: Vec<String> = classes.iter().map(str::to_owned).collect();
+            classes.sort();
+            (classes, app.world.resource::<ClassChanges>().0)
+        };
+
+        assert_eq!(step(Interaction::None), (vec!["primary".into()], 1));
+        assert_eq!(
+            step(Interaction::Hovered),
+            (vec!["hovered".into(), "primary".into()], 1)
+        );
+        assert_eq!(
+            step(Interaction::Pressed),
+            (vec!["pressed".into(), "primary".into()], 1)
+        );
+        // Writing the same state doesn't touch the classes.
+        assert_eq!(
+            step(Interaction::Pressed),
+            (vec!["pressed".into(), "primary".into()], 0)
+        );
+        assert_eq!(
+            step(Interaction::Hovered),
+            (vec!["hovered".into(), "primary".into()], 1)
+        );
+        assert_eq!(step(Interaction::None), (vec!["primary".into()], 1));
+    }
+
+    #[test]
+    fn test_classes_added_later() {
+        let mut app = App::new();
+        app.add_plugins(PeacockInteractionClassesPlugin);
+        let button = app.world.spawn(Interaction::Pressed).id();
+        app.update();
+
+        // The interaction hasn't changed since, but the new classes still mirror it.
+        app.world
+            .entity_mut(button)
+            .insert(ElementClasses::new("primary"));
+        app.update();
+        let classes = app.world.get::<ElementClasses>(button).unwrap();
+        assert!(classes.has_class(PeacockInteractionClassesPlugin::PRESSED));
+        assert!(classes.has_class("primary"));
+    }
+}
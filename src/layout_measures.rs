@@ -0,0 +1,163 @@
+//! Measurement of the laid-out size and position of UI nodes, shared by the features which
+//! resolve relative lengths, such as `calc()` and layout transitions.
+
+use bevy::{ecs::system::SystemParam, prelude::*, window::PrimaryWindow};
+
+use crate::selector_matcher::logical_size;
+
+/// Resolve a length to pixels. Percentages are relative to `basis`, which is usually the width
+/// or height of the parent node, and viewport units to `viewport`. Returns `None` for `Auto`,
+/// whose size only the layout knows.
+pub fn resolve_val(val: Val, basis: f32, viewport: Vec2) -> Option<f32> {
+    match val {
+        Val::Auto => None,
+        Val::Px(px) => Some(px),
+        Val::Percent(pct) => Some(basis * pct / 100.),
+        Val::Vw(vw) => Some(viewport.x * vw / 100.),
+        Val::Vh(vh) => Some(viewport.y * vh / 100.),
+        Val::VMin(vmin) => Some(viewport.min_element() * vmin / 100.),
+        Val::VMax(vmax) => Some(viewport.max_element() * vmax / 100.),
+    }
+}
+
+/// System parameter which measures the laid-out size and position of UI nodes.
+///
+/// Measurements come from the most recent layout pass. Styles are applied before layout, so
+/// when read during the style pass they describe the previous frame: a node which was just
+/// resized, or just spawned, is measured at its old size (or zero) until the next frame.
+/// Features built on this should restyle when a measurement they depend on changes, as
+/// `calc()` lengths do with [`size_changed`](Self::size_changed).
+#[derive(SystemParam)]
+pub struct LayoutMeasures<'w, 's> {
+    nodes: Query<'w, 's, (Ref<'static, Node>, Option<&'static GlobalTransform>)>,
+    styles: Query<'w, 's, Ref<'static, Style>>,
+    parents: Query<'w, 's, &'static Parent>,
+    windows: Query<'w, 's, &'static Window, With<PrimaryWindow>>,
+}
+
+impl<'w, 's> LayoutMeasures<'w, 's> {
+    /// The logical size of `entity`, including its padding and border, or `None` if it isn't a
+    /// UI node.
+    pub fn size_of(&self, entity: Entity) -> Option<Vec2> {
+        self.nodes.get(entity).ok().map(|(node, ..)| node.size())
+    }
+
+    /// The logical size of the content box of `entity`: its size less its padding and border.
+    /// Percentage padding and borders are relative to `basis`, the width of the content box of
+    /// its own parent, as in the layout.
+    pub fn content_size_of(&self, entity: Entity, basis: f32) -> Option<Vec2> {
+        let (node, _) = self.nodes.get(entity).ok()?;
+        let Ok(style) = self.styles.get(entity) else {
+            return Some(node.size());
+        };
+        let viewport = self.viewport_size().unwrap_or_default();
+        let resolve = |val| resolve_val(val, basis, viewport).unwrap_or(0.);
+        let inset = |rect: UiRect| {
+            Vec2::new(
+                resolve(rect.left) + resolve(rect.right),
+                resolve(rect.top) + resolve(rect.bottom),
+            )
+        };
+        Some((node.size() - inset(style.padding) - inset(style.border)).max(Vec2::ZERO))
+    }
+
+    /// True if the content size of `entity` may have changed since the calling system last
+    /// ran, either because it was laid out at a new size or because its style changed.
+    pub fn size_changed(&self, entity: Entity) -> bool {
+        self.nodes
+            .get(entity)
+            .is_ok_and(|(node, _)| node.is_changed())
+            || self
+                .styles
+                .get(entity)
+                .is_ok_and(|style| style.is_changed())
+    }
+
+    /// The size which percentages in the layout of `entity` are relative to: that of the
+    /// content box of its parent node, or of the primary window for root nodes.
+    pub fn parent_size_of(&self, entity: Entity) -> Option<Vec2> {
+        match self.parents.get(entity) {
+            Ok(parent) if self.nodes.contains(parent.get()) => {
+                let basis = self.parent_size_of(parent.get()).unwrap_or_default().x;
+                self.content_size_of(parent.get(), basis)
+            }
+            _ => self.viewport_size(),
+        }
+    }
+
+    /// The logical rectangle occupied by `entity`, in window coordinates, or `None` if it
+    /// isn't a UI node or hasn't been positioned.
+    pub fn rect_of(&self, entity: Entity) -> Option<Rect> {
+        let (node, transform) = self.nodes.get(entity).ok()?;
+        Some(node.logical_rect(transform?))
+    }
+
+    /// The logical size of the primary window, which viewport units are relative to.
+    pub fn viewport_size(&self) -> Option<Vec2> {
+        self.windows.get_single().ok().map(logical_size)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{run_layout, set_window_size};
+    use bevy::ecs::system::RunSystemOnce;
+
+    #[test]
+    fn test_resolve_val() {
+        let viewport = Vec2::new(800., 600.);
+        assert_eq!(resolve_val(Val::Auto, 200., viewport), None);
+        assert_eq!(resolve_val(Val::Px(-12.), 200., viewport), Some(-12.));
+        assert_eq!(resolve_val(Val::Percent(25.), 200., viewport), Some(50.));
+        assert_eq!(resolve_val(Val::Vw(10.), 200., viewport), Some(80.));
+        assert_eq!(resolve_val(Val::Vh(10.), 200., viewport), Some(60.));
+        assert_eq!(resolve_val(Val::VMin(50.), 200., viewport), Some(300.));
+        assert_eq!(resolve_val(Val::VMax(50.), 200., viewport), Some(400.));
+        assert_eq!(resolve_val(Val::Percent(50.), 0., Vec2::ZERO), Some(0.));
+    }
+
+    #[test]
+    fn test_layout_measures() {
+        let mut world = World::new();
+        set_window_size(&mut world, 800., 600.);
+        let child = world.spawn(NodeBundle::default()).id();
+        let root = world
+            .spawn(NodeBundle {
+                style: Style {
+                    width: Val::Px(200.),
+                    height: Val::Px(100.),
+                    padding: UiRect::axes(Val::Px(10.), Val::Percent(1.)),
+                    border: UiRect::left(Val::Px(4.)),
+                    ..default()
+                },
+                ..default()
+            })
+            .push_children(&[child])
+            .id();
+        run_layout(&mut world);
+        // Global transforms are normally propagated from the local ones after layout.
+        world
+            .entity_mut(root)
+            .insert(GlobalTransform::from_xyz(100., 50., 0.));
+        let other = world.spawn_empty().id();
+
+        world.run_system_once(move |measures: LayoutMeasures| {
+            let size = Vec2::new(200., 100.);
+            assert_eq!(measures.viewport_size(), Some(Vec2::new(800., 600.)));
+            assert_eq!(measures.size_of(root), Some(size));
+            assert_eq!(measures.size_of(other), None);
+            assert_eq!(
+                measures.content_size_of(root, 800.),
+                Some(Vec2::new(176., 84.))
+            );
+            assert_eq!(measures.parent_size_of(child), Some(Vec2::new(176., 84.)));
+            assert_eq!(measures.parent_size_of(root), Some(Vec2::new(800., 600.)));
+            assert_eq!(
+                measures.rect_of(root),
+                Some(Rect::from_center_size(Vec2::new(100., 50.), size))
+            );
+            assert!(measures.size_changed(root));
+        });
+    }
+}
@@ -3,36 +3,157 @@
 #![warn(missing_docs)]
 
 mod animate;
+mod animated_number;
+mod app_state;
+mod asset_checks;
+mod cache;
+mod capabilities;
 mod classes;
+mod color_scheme;
+mod custom_pseudo_classes;
+mod diagnostics;
+mod drag;
+mod dump;
+mod flags;
+mod focus;
+#[cfg(any(test, feature = "debug-highlight"))]
+mod highlight;
+mod interaction;
+mod layout_measures;
+mod locale;
+mod mask;
+mod orphans;
+mod overrides;
 mod plugin;
+#[cfg(feature = "presets")]
+pub mod presets;
+mod pseudo_classes;
+mod registry;
+mod rem;
+mod restyle;
+mod scope;
 mod selector_matcher;
 mod style_handle;
+mod style_root;
 mod style_tuple;
+#[cfg(any(test, feature = "test-utils"))]
+pub mod test_utils;
+mod text_overflow;
+mod theme;
+mod trace;
 pub(crate) mod update;
 mod update_computed;
+mod watch;
 
 pub use animate::animate_bg_colors;
 pub use animate::animate_border_colors;
+pub use animate::animate_font_sizes;
 pub use animate::animate_layout;
+pub use animate::animate_outlines;
 pub use animate::animate_transforms;
+pub use animate::fix_restored_animations;
+pub use animate::PeacockAnimationConfig;
+pub use animated_number::animate_numbers;
+pub use animated_number::AnimateNumber;
+pub use animated_number::AnimatedNumber;
+pub use animated_number::AnimatedNumberFinished;
+pub use animated_number::NumberFormat;
+pub use app_state::UiPauseState;
+pub use bevy_peacock_style::parse_color_name;
+pub use bevy_peacock_style::parse_stylesheet;
 pub use bevy_peacock_style::timing;
+pub use bevy_peacock_style::AnimateIn;
+pub use bevy_peacock_style::AppStateKind;
+pub use bevy_peacock_style::CalcLength;
+pub use bevy_peacock_style::CalcProperty;
+pub use bevy_peacock_style::CapabilityKind;
 pub use bevy_peacock_style::ComputedStyle;
+pub use bevy_peacock_style::Length;
+pub use bevy_peacock_style::MediaQuery;
 pub use bevy_peacock_style::PointerEvents;
+pub use bevy_peacock_style::Priority;
 pub use bevy_peacock_style::Selector;
+pub use bevy_peacock_style::SelectorSpans;
+pub use bevy_peacock_style::SourcePos;
+pub use bevy_peacock_style::SourceSpan;
+pub use bevy_peacock_style::SpanTable;
+pub use bevy_peacock_style::StyleField;
+pub use bevy_peacock_style::StyleFieldSet;
 pub use bevy_peacock_style::StyleProp;
 pub use bevy_peacock_style::StylePropList;
+pub use bevy_peacock_style::StylesheetError;
+pub use bevy_peacock_style::StylesheetErrorKind;
+pub use bevy_peacock_style::TextOverflow;
+pub use bevy_peacock_style::Timing;
+pub use bevy_peacock_style::TokenValue;
 pub use bevy_peacock_style::Transition;
 pub use bevy_peacock_style::TransitionProperty;
+pub use cache::CacheStats;
+pub use cache::PeacockCacheRegistry;
+pub use capabilities::PeacockCapabilities;
 pub use classes::ClassNames;
+pub use classes::ClassWriter;
 pub use classes::ElementClasses;
+pub use classes::WithClasses;
+pub use color_scheme::ColorScheme;
+pub use custom_pseudo_classes::CustomPseudoClass;
+pub use custom_pseudo_classes::CustomPseudoClassRegistry;
+pub use diagnostics::PeacockDiagnosticsPlugin;
+pub use dump::dump_entity_style;
+pub use dump::DebugDumpStyle;
+pub use flags::StyleFlags;
+pub use focus::PeacockFocusSource;
+pub use focus::PreviousFocus;
+#[cfg(any(test, feature = "debug-highlight"))]
+pub use highlight::DebugSelectorHighlight;
+#[cfg(any(test, feature = "debug-highlight"))]
+pub use highlight::DebugSelectorResult;
+#[cfg(any(test, feature = "debug-highlight"))]
+pub use highlight::HighlightSelector;
+#[cfg(any(test, feature = "debug-highlight"))]
+pub use highlight::PeacockSelectorHighlightPlugin;
+pub use interaction::PeacockInteractionClassesPlugin;
+pub use layout_measures::resolve_val;
+pub use layout_measures::LayoutMeasures;
+pub use locale::Locale;
+pub use mask::PeacockPropertyMask;
+pub use orphans::OrphanedStylesConfig;
+pub use overrides::reload_user_overrides;
+pub use overrides::UserOverrides;
+pub use plugin::PeacockConfig;
+pub use plugin::PeacockInitialized;
 pub use plugin::PeacockPlugin;
-pub use plugin::PeacockSystemSet;
+pub use plugin::SkipHidden;
+pub use plugin::{PeacockStage, PeacockSystemSet};
+pub use pseudo_classes::Checked;
+pub use pseudo_classes::Disabled;
+pub use registry::StyleRegistry;
+pub use rem::RootFontSize;
+pub use restyle::restyle_now;
+pub use restyle::Restyle;
+pub use restyle::RestyleAll;
+pub use restyle::RestyleEntity;
+pub use restyle::RestyleSubtree;
+pub use scope::StyleScope;
 pub(crate) use selector_matcher::SelectorMatcher;
 pub use style_handle::ElementStyles;
 pub use style_handle::StyleHandle;
 pub use style_handle::StyleSheet;
+pub use style_handle::DEFAULT_MAX_SELECTOR_DEPTH;
+pub use style_root::StyleRoot;
 pub use style_tuple::StyleTuple;
 pub use style_tuple::WithStyles;
+pub use text_overflow::FullText;
+pub use text_overflow::TextEllipsis;
+pub use theme::Theme;
+pub use trace::DebugStyleTrace;
+pub use trace::StyleTraceEntry;
+pub use update_computed::ApplyStyleUpdates;
+pub use update_computed::PendingStyleUpdates;
 pub use update_computed::UpdateComputedStyle;
+pub use update_computed::UpdateTextStyle;
+pub use watch::ComputedPropChanged;
+pub use watch::ComputedValue;
+pub use watch::WatchComputedProp;
 
 pub use bevy_peacock_derive::import_stylesheet;
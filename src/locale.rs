@@ -0,0 +1,133 @@
+//! The app's locale, which selects `:lang(tag)` selectors.
+
+use bevy::prelude::*;
+
+use crate::RestyleAll;
+
+/// Resource holding the app's current locale, as a BCP 47 language tag such as `de-AT` or
+/// `ja`. Styles can use the `:lang(tag)` pseudo-class to make properties conditional on it,
+/// for example to shrink the text of languages with long words. Without this resource,
+/// `:lang()` never matches. Changing it restyles every element.
+#[derive(Resource, Reflect, Default, Clone, Debug, PartialEq, Eq)]
+#[reflect(Resource)]
+pub struct Locale(pub String);
+
+impl Locale {
+    /// Construct a new `Locale` with the given language tag.
+    pub fn new(tag: impl Into<String>) -> Self {
+        Self(tag.into())
+    }
+
+    /// True if the locale is `tag`, or starts with all of the subtags of `tag`, ignoring case.
+    /// So `de` matches `de-AT`, but not `den`. `_` is accepted as a separator, for locales
+    /// taken from POSIX environment variables.
+    pub fn matches(&self, tag: &str) -> bool {
+        let locale = self.0.as_bytes();
+        locale.len() >= tag.len()
+            && locale[..tag.len()].eq_ignore_ascii_case(tag.as_bytes())
+            && matches!(locale.get(tag.len()), None | Some(b'-' | b'_'))
+    }
+}
+
+/// Restyle everything when the locale is set, changed or removed. The previous value is
+/// compared so that writes which don't change the locale are ignored.
+pub(crate) fn restyle_changed_locale(
+    locale: Option<Res<Locale>>,
+    mut locale_prev: Local<Option<String>>,
+    mut restyle: EventWriter<RestyleAll>,
+) {
+    let current = locale.as_ref().map(|locale| &locale.0);
+    if current != locale_prev.as_ref() {
+        *locale_prev = current.cloned();
+        restyle.send(RestyleAll);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::restyle::collect_restyles;
+    use crate::update::update_styles;
+    use crate::{ElementStyles, StyleHandle, StyleRegistry};
+    use bevy_peacock_style::parse_stylesheet;
+
+    #[test]
+    fn test_locale_matches() {
+        let locale = Locale::new("de-AT");
+        assert!(locale.matches("de"));
+        assert!(locale.matches("DE-at"));
+        assert!(locale.matches("de-AT"));
+        assert!(!locale.matches("de-CH"));
+        assert!(!locale.matches("d"));
+        assert!(!locale.matches("en"));
+        assert!(Locale::new("pt_BR").matches("pt"));
+        assert!(!Locale::new("den").matches("de"));
+        assert!(!Locale::default().matches("de"));
+    }
+
+    #[test]
+    fn test_lang_selector() {
+        let mut app = crate::test_utils::test_app();
+        app.add_systems(
+            Update,
+            (restyle_changed_locale, collect_restyles, update_styles).chain(),
+        );
+
+        let mut registry = StyleRegistry::default();
+        registry.register_sheet(
+            parse_stylesheet(
+                r#"LABEL {
+                    font_size: 16;
+                    :lang(de) { font_size: 12; }
+                    :lang(ja) { font: "fonts/NotoJP.ttf"; }
+                }
+                TITLE {
+                    font_size: 24;
+                }"#,
+            )
+            .unwrap(),
+        );
+        let label_style = registry.get("LABEL").unwrap();
+        let title_style = registry.get("TITLE").unwrap();
+        app.insert_resource(registry);
+
+        let world = &mut app.world;
+        let spawn_text = |world: &mut World, style: StyleHandle| {
+            world
+                .spawn((
+                    TextBundle::from_section("Text", TextStyle::default()),
+                    ElementStyles::new(&[style]),
+                ))
+                .id()
+        };
+        let label = spawn_text(world, label_style);
+        let title = spawn_text(world, title_style);
+        let text_style = |world: &World, entity: Entity| {
+            let style = &world.get::<Text>(entity).unwrap().sections[0].style;
+            let font = style.font.path().map(|path| path.to_string());
+            (style.font_size, font)
+        };
+
+        app.update();
+        assert_eq!(text_style(&app.world, label), (16., None));
+        assert_eq!(text_style(&app.world, title), (24., None));
+
+        // Only the rules for the new locale apply.
+        app.insert_resource(Locale::new("de-AT"));
+        app.update();
+        assert_eq!(text_style(&app.world, label), (12., None));
+        assert_eq!(text_style(&app.world, title), (24., None));
+
+        app.insert_resource(Locale::new("ja"));
+        app.update();
+        assert_eq!(
+            text_style(&app.world, label),
+            (16., Some("fonts/NotoJP.ttf".to_owned()))
+        );
+        assert_eq!(text_style(&app.world, title), (24., None));
+
+        app.world.remove_resource::<Locale>();
+        app.update();
+        assert_eq!(text_style(&app.world, label).0, 16.);
+    }
+}
@@ -0,0 +1,422 @@
+//! Masks which stop the style pass writing some of an entity's properties, so that other
+//! systems, such as a tweening library, can own them.
+
+use bevy::{prelude::*, utils::HashSet};
+use bevy_peacock_style::{StyleField, StyleFieldSet, Transition, TransitionProperty};
+
+use crate::RestyleEntity;
+
+/// Component which stops Peacock writing some of an entity's style properties to its
+/// components, so that other systems can set them instead. Properties are named by their
+/// [`StyleProp::key`](crate::StyleProp::key), which is their name in stylesheets, such as
+/// `"rotation"` or `"background_color"`.
+///
+/// Masked properties are still computed, so that descendants inherit them as usual; only the
+/// writes to this entity's components are skipped. Masking a shorthand such as `"margin"` masks
+/// all of its parts, and masking a part such as `"margin_left"` masks just that part. Parts of a
+/// grid placement, such as `"grid_row_start"`, mask the whole placement.
+///
+/// Transitions on masked properties are dropped, with a warning, since animating them would
+/// overwrite them every frame. The scale, rotation and translation are masked separately, so
+/// masking the rotation drops a `rotation` transition but leaves a `scale` one running. A
+/// `transform` transition keeps animating the parts which aren't masked, and is only dropped if
+/// all of them are. The scale is animated as a whole, so masking either axis drops a `scale`
+/// transition.
+#[derive(Component, Clone, Debug, Default, PartialEq, Eq)]
+pub struct PeacockPropertyMask {
+    keys: HashSet<String>,
+    /// Whether `keys` lists the properties which are written, rather than those which aren't.
+    only: bool,
+}
+
+impl PeacockPropertyMask {
+    /// Construct a mask which stops Peacock writing the given properties.
+    pub fn new<K: Into<String>>(keys: impl IntoIterator<Item = K>) -> Self {
+        Self {
+            keys: keys.into_iter().map(Into::into).collect(),
+            only: false,
+        }
+    }
+
+    /// Construct a mask which stops Peacock writing any properties except the given ones.
+    pub fn only<K: Into<String>>(keys: impl IntoIterator<Item = K>) -> Self {
+        Self {
+            keys: keys.into_iter().map(Into::into).collect(),
+            only: true,
+        }
+    }
+
+    /// Return whether Peacock is stopped from writing the property named `key`.
+    pub fn masks(&self, key: &str) -> bool {
+        self.masks_any(&[key])
+    }
+
+    /// Return whether a field which can be set by any of the properties named `keys`, such as
+    /// a margin side which is set by both `"margin_left"` and `"margin"`, is masked.
+    pub(crate) fn masks_any(&self, keys: &[&str]) -> bool {
+        keys.iter().any(|key| self.keys.contains(*key)) != self.only
+    }
+
+    /// Return whether the property animated by a transition is masked. Each part of the
+    /// transform is animated separately, so a part's transition is masked along with that part,
+    /// and the `transform` shorthand only once all of the parts are.
+    pub(crate) fn masks_transition(&self, property: TransitionProperty) -> bool {
+        match property {
+            TransitionProperty::Transform => [
+                TransitionProperty::Scale,
+                TransitionProperty::Rotation,
+                TransitionProperty::Translation,
+            ]
+            .into_iter()
+            .all(|part| self.masks_transition(part)),
+            TransitionProperty::Scale => {
+                self.masks_any(&["scale_x", "scale"]) || self.masks_any(&["scale_y", "scale"])
+            }
+            TransitionProperty::Rotation => self.masks("rotation"),
+            TransitionProperty::Translation => self.masks("translation"),
+            TransitionProperty::BackgroundColor => self.masks("background_color"),
+            TransitionProperty::BorderColor => self.masks("border_color"),
+            TransitionProperty::Left => self.masks("left"),
+            TransitionProperty::Top => self.masks("top"),
+            TransitionProperty::Right => self.masks("right"),
+            TransitionProperty::Bottom => self.masks("bottom"),
+            TransitionProperty::Height => self.masks("height"),
+            TransitionProperty::Width => self.masks("width"),
+            TransitionProperty::BorderLeft => self.masks_any(&["border_left", "border"]),
+            TransitionProperty::BorderTop => self.masks_any(&["border_top", "border"]),
+            TransitionProperty::BorderRight => self.masks_any(&["border_right", "border"]),
+            TransitionProperty::BorderBottom => self.masks_any(&["border_bottom", "border"]),
+            TransitionProperty::PaddingLeft => self.masks_any(&["padding_left", "padding"]),
+            TransitionProperty::PaddingRight => self.masks_any(&["padding_right", "padding"]),
+            TransitionProperty::PaddingTop => self.masks_any(&["padding_top", "padding"]),
+            TransitionProperty::PaddingBottom => self.masks_any(&["padding_bottom", "padding"]),
+            TransitionProperty::MarginLeft => self.masks_any(&["margin_left", "margin"]),
+            TransitionProperty::MarginRight => self.masks_any(&["margin_right", "margin"]),
+            TransitionProperty::MarginTop => self.masks_any(&["margin_top", "margin"]),
+            TransitionProperty::MarginBottom => self.masks_any(&["margin_bottom", "margin"]),
+            TransitionProperty::RowGap => self.masks_any(&["row_gap", "gap"]),
+            TransitionProperty::ColumnGap => self.masks_any(&["column_gap", "gap"]),
+            TransitionProperty::OutlineWidth => self.masks("outline_width"),
+            TransitionProperty::OutlineOffset => self.masks("outline_offset"),
+            TransitionProperty::OutlineColor => self.masks("outline_color"),
+            TransitionProperty::FontSize => self.masks("font_size"),
+        }
+    }
+
+    /// Return the fields of the entity's `Style` which are masked, and so left unchanged when
+    /// the computed style is written.
+    pub(crate) fn masked_style_fields(&self) -> StyleFieldSet {
+        StyleField::ALL
+            .iter()
+            .copied()
+            .filter(|field| self.masks_any(field.keys()))
+            .collect()
+    }
+
+    /// Copy the masked parts of the entity's current `Transform` into the transform which is
+    /// about to replace it, whether it was computed from the styles or animated.
+    pub(crate) fn keep_masked_transform(&self, current: &Transform, next: &mut Transform) {
+        if self.masks_any(&["scale_x", "scale"]) {
+            next.scale.x = current.scale.x;
+        }
+        if self.masks_any(&["scale_y", "scale"]) {
+            next.scale.y = current.scale.y;
+        }
+        if self.masks("rotation") {
+            next.rotation = current.rotation;
+        }
+        if self.masks("translation") {
+            next.translation = current.translation;
+        }
+    }
+}
+
+/// Set of `(entity, property)` pairs which have already been reported by
+/// [`drop_masked_transitions`], so that each is only reported once.
+#[derive(Resource, Default)]
+pub(crate) struct MaskedTransitionWarnings(pub(crate) HashSet<(Entity, TransitionProperty)>);
+
+/// Remove the transitions on properties which the entity's mask stops Peacock writing, warning
+/// about each.
+pub(crate) fn drop_masked_transitions(
+    world: &mut World,
+    entity: Entity,
+    mask: &PeacockPropertyMask,
+    transitions: &mut Vec<Transition>,
+) {
+    transitions.retain(|tr| {
+        if !mask.masks_transition(tr.property) {
+            return true;
+        }
+        if world
+            .get_resource_or_insert_with(MaskedTransitionWarnings::default)
+            .0
+            .insert((entity, tr.property))
+        {
+            warn!(
+                "Entity {:?}: transition on {:?} is ignored, because its PeacockPropertyMask \
+                stops Peacock writing {:?}",
+                entity, tr.property, tr.property
+            );
+        }
+        false
+    });
+}
+
+/// Restyle entities whose mask was added, changed or removed, so that properties which are no
+/// longer masked are written again. The warnings about entities whose mask was removed, or
+/// which were despawned, are forgotten.
+pub(crate) fn restyle_changed_masks(
+    query: Query<Entity, Changed<PeacockPropertyMask>>,
+    mut removed: RemovedComponents<PeacockPropertyMask>,
+    mut restyle: EventWriter<RestyleEntity>,
+    warnings: Option<ResMut<MaskedTransitionWarnings>>,
+) {
+    let removed: HashSet<Entity> = removed.read().collect();
+    if let Some(mut warnings) = warnings.filter(|_| !removed.is_empty()) {
+        warnings.0.retain(|(entity, _)| !removed.contains(entity));
+    }
+    restyle.send_batch(query.iter().chain(removed).map(RestyleEntity));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::animate::{animate_transforms, AnimatedTransform, TransformPart};
+    use crate::restyle::collect_restyles;
+    use crate::update::update_styles;
+    use crate::{ElementClasses, ElementStyles, RestyleAll, StyleHandle};
+
+    /// Stand-in for an external tweening system, which spins the entity's rotation each frame.
+    #[derive(Resource, Default)]
+    struct Spin(f32);
+
+    fn spin(mut spin: ResMut<Spin>, mut query: Query<&mut Transform, With<PeacockPropertyMask>>) {
+        spin.0 += 0.1;
+        for mut transform in query.iter_mut() {
+            transform.rotation = Quat::from_rotation_z(spin.0);
+        }
+    }
+
+    fn test_app() -> App {
+        let mut app = crate::test_utils::test_app();
+        app.init_resource::<Spin>().add_systems(
+            Update,
+            (spin, restyle_changed_masks, collect_restyles, update_styles).chain(),
+        );
+        app
+    }
+
+    fn style() -> StyleHandle {
+        StyleHandle::build(|ss| {
+            ss.background_color("#ff0000")
+                .rotation(1.)
+                .width(10.)
+                .height(10.)
+                .selector(".on", |ss| {
+                    ss.background_color("#0000ff")
+                        .rotation(2.)
+                        .width(20.)
+                        .height(20.)
+                })
+        })
+    }
+
+    fn spawn(world: &mut World, mask: PeacockPropertyMask) -> Entity {
+        world
+            .spawn((
+                NodeBundle::default(),
+                ElementStyles::new(&[style()]),
+                ElementClasses::default(),
+                mask,
+            ))
+            .id()
+    }
+
+    fn bg_color(world: &World, entity: Entity) -> Color {
+        world.get::<BackgroundColor>(entity).unwrap().0
+    }
+
+    #[test]
+    fn test_mask_rotation() {
+        let mut app = test_app();
+        let entity = spawn(
+            &mut app.world,
+            PeacockPropertyMask::new(["rotation", "width"]),
+        );
+
+        for frame in 0..6 {
+            let on = frame % 2 == 1;
+            let mut classes = app.world.get_mut::<ElementClasses>(entity).unwrap();
+            if on {
+                classes.add_class("on");
+            } else {
+                classes.remove_class("on");
+            }
+            app.world.send_event(RestyleAll);
+            app.update();
+
+            let world = &app.world;
+            let spin = world.resource::<Spin>().0;
+            let transform = world.get::<Transform>(entity).unwrap();
+            assert_eq!(transform.rotation, Quat::from_rotation_z(spin));
+            let style = world.get::<Style>(entity).unwrap();
+            assert_eq!(style.width, Val::Auto);
+            if on {
+                assert_eq!(bg_color(world, entity), Color::hex("#0000ff").unwrap());
+                assert_eq!(style.height, Val::Px(20.));
+            } else {
+                assert_eq!(bg_color(world, entity), Color::hex("#ff0000").unwrap());
+                assert_eq!(style.height, Val::Px(10.));
+            }
+        }
+
+        // Once the mask is removed, Peacock writes the rotation and width again.
+        app.world.entity_mut(entity).remove::<PeacockPropertyMask>();
+        app.update();
+        let world = &app.world;
+        assert_eq!(
+            world.get::<Transform>(entity).unwrap().rotation,
+            Quat::from_rotation_z(2.)
+        );
+        assert_eq!(world.get::<Style>(entity).unwrap().width, Val::Px(20.));
+    }
+
+    #[test]
+    fn test_mask_animated_rotation() {
+        let mut app = test_app();
+        app.add_systems(Update, animate_transforms.after(update_styles));
+        let style = StyleHandle::build(|ss| {
+            ss.scale(1.)
+                .rotation(1.)
+                .transition(&[Transition {
+                    property: TransitionProperty::Transform,
+                    duration: 10.,
+                    ..default()
+                }])
+                .selector(".on", |ss| ss.scale(2.).rotation(2.))
+        });
+        let entity = app
+            .world
+            .spawn((
+                NodeBundle::default(),
+                ElementStyles::new(&[style]),
+                ElementClasses::default(),
+                PeacockPropertyMask::new(["rotation"]),
+            ))
+            .id();
+        app.update();
+        app.world
+            .get_mut::<ElementClasses>(entity)
+            .unwrap()
+            .add_class("on");
+
+        // The transform transition still animates the scale, while the rotation is left to
+        // the other system.
+        for _ in 0..3 {
+            app.update();
+            let world = &app.world;
+            let at = world.get::<AnimatedTransform>(entity).unwrap();
+            assert!(at.state(TransformPart::Scale).is_some());
+            assert_eq!(at.target.scale, Vec3::new(2., 2., 1.));
+            let spin = world.resource::<Spin>().0;
+            let transform = world.get::<Transform>(entity).unwrap();
+            assert_eq!(transform.rotation, Quat::from_rotation_z(spin));
+        }
+        assert!(app
+            .world
+            .get_resource::<MaskedTransitionWarnings>()
+            .is_none_or(|warnings| warnings.0.is_empty()));
+    }
+
+    #[test]
+    fn test_forget_warnings() {
+        let mut app = test_app();
+        let style = StyleHandle::build(|ss| {
+            ss.rotation(1.).transition(&[Transition {
+                property: TransitionProperty::Rotation,
+                duration: 1.,
+                ..default()
+            }])
+        });
+        let spawn = |world: &mut World| {
+            world
+                .spawn((
+                    NodeBundle::default(),
+                    ElementStyles::new(std::slice::from_ref(&style)),
+                    PeacockPropertyMask::new(["rotation"]),
+                ))
+                .id()
+        };
+        let a = spawn(&mut app.world);
+        let b = spawn(&mut app.world);
+        app.update();
+        let warned = |app: &App| app.world.resource::<MaskedTransitionWarnings>().0.len();
+        assert_eq!(warned(&app), 2);
+
+        // Despawned entities and removed masks are forgotten.
+        app.world.despawn(a);
+        app.update();
+        assert_eq!(warned(&app), 1);
+        app.world.entity_mut(b).remove::<PeacockPropertyMask>();
+        app.update();
+        assert_eq!(warned(&app), 0);
+    }
+
+    #[test]
+    fn test_only_mask() {
+        let mut app = test_app();
+        let entity = spawn(
+            &mut app.world,
+            PeacockPropertyMask::only(["background_color"]),
+        );
+        app.update();
+        let world = &app.world;
+        assert_eq!(bg_color(world, entity), Color::hex("#ff0000").unwrap());
+        assert_eq!(world.get::<Style>(entity).unwrap().width, Val::Auto);
+        let spin = world.resource::<Spin>().0;
+        assert_eq!(
+            world.get::<Transform>(entity).unwrap().rotation,
+            Quat::from_rotation_z(spin)
+        );
+    }
+
+    #[test]
+    fn test_masked_transitions() {
+        let mask = PeacockPropertyMask::new(["rotation", "border"]);
+        assert!(mask.masks_transition(TransitionProperty::Rotation));
+        assert!(!mask.masks_transition(TransitionProperty::Scale));
+        assert!(!mask.masks_transition(TransitionProperty::Translation));
+        assert!(!mask.masks_transition(TransitionProperty::Transform));
+        assert!(mask.masks_transition(TransitionProperty::BorderTop));
+        assert!(!mask.masks_transition(TransitionProperty::Width));
+        let mask = PeacockPropertyMask::new(["scale_y"]);
+        assert!(mask.masks_transition(TransitionProperty::Scale));
+        assert!(!mask.masks_transition(TransitionProperty::Transform));
+        let mask = PeacockPropertyMask::new(["scale", "rotation", "translation"]);
+        assert!(mask.masks_transition(TransitionProperty::Transform));
+        let mask = PeacockPropertyMask::only(["scale_x", "rotation", "translation"]);
+        assert!(mask.masks_transition(TransitionProperty::Scale));
+        assert!(!mask.masks_transition(TransitionProperty::Transform));
+        assert!(mask.masks_transition(TransitionProperty::BackgroundColor));
+
+        let mut world = World::new();
+        let entity = world.spawn_empty().id();
+        let transition = |property| Transition {
+            property,
+            duration: 0.3,
+            ..default()
+        };
+        let mask = PeacockPropertyMask::new(["width"]);
+        for _ in 0..2 {
+            let mut transitions = vec![
+                transition(TransitionProperty::Width),
+                transition(TransitionProperty::Height),
+            ];
+            drop_masked_transitions(&mut world, entity, &mask, &mut transitions);
+            assert_eq!(transitions.len(), 1);
+            assert_eq!(transitions[0].property, TransitionProperty::Height);
+        }
+        assert_eq!(world.resource::<MaskedTransitionWarnings>().0.len(), 1);
+    }
+}
@@ -0,0 +1,235 @@
+//! Diagnostics for styled entities which aren't part of any UI tree.
+//!
+//! The style pass starts from the UI root nodes and walks down the hierarchy, so an entity
+//! which isn't connected to a root (for example, a child whose parent was despawned without
+//! its children, or one parented to an entity which isn't a UI node) is never visited, and
+//! keeps whatever styles it had last.
+
+use bevy::{prelude::*, utils::Duration, utils::HashMap};
+
+use crate::ElementStyles;
+
+/// The maximum number of example entities listed in a warning.
+const MAX_EXAMPLES: usize = 5;
+
+/// Settings for detecting entities with [`ElementStyles`] which aren't visited by the style
+/// pass. Insert this resource to enable the check.
+#[derive(Resource, Debug, Clone)]
+pub struct OrphanedStylesConfig {
+    /// How often to look for styled entities which weren't visited by the most recent style
+    /// pass, and log a warning if there are any. `None` disables the check. Defaults to every
+    /// 5 seconds.
+    pub check_interval: Option<Duration>,
+
+    /// Style the top node of each disconnected subtree as though it were a root, so that
+    /// entities which are deliberately kept out of the UI tree, such as templates, are styled
+    /// too. Defaults to false.
+    pub style_as_roots: bool,
+}
+
+impl Default for OrphanedStylesConfig {
+    fn default() -> Self {
+        Self {
+            check_interval: Some(Duration::from_secs(5)),
+            style_as_roots: false,
+        }
+    }
+}
+
+/// Records which entities were visited by each style pass, while the orphan check is enabled.
+#[derive(Resource, Default)]
+pub(crate) struct StyleVisits {
+    /// Number of style passes which recorded visits.
+    pass: u32,
+    /// The pass in which each entity was last styled.
+    last_styled: HashMap<Entity, u32>,
+    /// When the last check was made.
+    last_check: Option<Duration>,
+    /// The orphaned entities found by the last check.
+    pub(crate) orphaned: Vec<Entity>,
+}
+
+impl StyleVisits {
+    /// Start recording a new style pass.
+    pub(crate) fn begin_pass(&mut self) {
+        self.pass = self.pass.wrapping_add(1);
+    }
+
+    /// Record that `entity` was visited by the current style pass.
+    pub(crate) fn visit(&mut self, entity: Entity) {
+        self.last_styled.insert(entity, self.pass);
+    }
+}
+
+/// Periodically log a warning listing the entities with [`ElementStyles`] which weren't
+/// visited by the most recent style pass.
+pub(crate) fn warn_orphaned_styles(
+    config: Option<Res<OrphanedStylesConfig>>,
+    visits: Option<ResMut<StyleVisits>>,
+    time: Res<Time>,
+    query: Query<(Entity, Option<&Name>), With<ElementStyles>>,
+) {
+    let (Some(interval), Some(mut visits)) = (config.and_then(|c| c.check_interval), visits) else {
+        return;
+    };
+    let now = time.elapsed();
+    if visits.last_check.is_some_and(|last| now < last + interval) {
+        return;
+    }
+    visits.last_check = Some(now);
+
+    // Forget entities which weren't visited by the last pass, including despawned ones.
+    let pass = visits.pass;
+    visits.last_styled.retain(|_, last| *last == pass);
+    let orphaned: Vec<(Entity, Option<&Name>)> = query
+        .iter()
+        .filter(|(entity, _)| !visits.last_styled.contains_key(entity))
+        .collect();
+    visits.orphaned = orphaned.iter().map(|(entity, _)| *entity).collect();
+    if orphaned.is_empty() {
+        return;
+    }
+
+    let mut examples = orphaned
+        .iter()
+        .take(MAX_EXAMPLES)
+        .map(|(entity, name)| match name {
+            Some(name) => format!("'{}' ({:?})", name, entity),
+            None => format!("{:?}", entity),
+        })
+        .collect::<Vec<_>>()
+        .join(", ");
+    if orphaned.len() > MAX_EXAMPLES {
+        examples.push_str(&format!(" and {} more", orphaned.len() - MAX_EXAMPLES));
+    }
+    warn!(
+        "{} entities with ElementStyles aren't part of any UI tree, so their styles aren't \
+        being updated: {}",
+        orphaned.len(),
+        examples
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::update::update_styles;
+    use crate::StyleHandle;
+    use bevy::ecs::system::RunSystemOnce;
+
+    fn test_app(config: OrphanedStylesConfig) -> App {
+        let mut app = crate::test_utils::test_app();
+        app.init_resource::<StyleVisits>().insert_resource(config);
+        app
+    }
+
+    fn red() -> StyleHandle {
+        StyleHandle::build(|ss| ss.background_color("#ff0000"))
+    }
+
+    fn is_styled(world: &World, entity: Entity) -> bool {
+        world.get::<BackgroundColor>(entity).unwrap().0 == Color::hex("#ff0000").unwrap()
+    }
+
+    /// Spawn a styled root with a styled child, a styled node parented to an entity which
+    /// isn't a UI node, and a styled node whose parent has been despawned.
+    fn spawn_nodes(world: &mut World) -> (Entity, Entity, Entity) {
+        let child = world
+            .spawn((NodeBundle::default(), ElementStyles::new(&[red()])))
+            .id();
+        world
+            .spawn((NodeBundle::default(), ElementStyles::new(&[red()])))
+            .add_child(child);
+
+        let template = world
+            .spawn((
+                NodeBundle::default(),
+                ElementStyles::new(&[red()]),
+                Name::new("template"),
+            ))
+            .id();
+        world.spawn(SpatialBundle::default()).add_child(template);
+
+        let orphan = world
+            .spawn((NodeBundle::default(), ElementStyles::new(&[red()])))
+            .id();
+        let parent = world.spawn(NodeBundle::default()).add_child(orphan).id();
+        world.despawn(parent);
+
+        (child, template, orphan)
+    }
+
+    #[test]
+    fn test_detect_orphaned_styles() {
+        let mut app = test_app(OrphanedStylesConfig {
+            check_interval: Some(Duration::ZERO),
+            style_as_roots: false,
+        });
+        let world = &mut app.world;
+        let (child, template, orphan) = spawn_nodes(world);
+
+        world.run_system_once(update_styles);
+        world.run_system_once(warn_orphaned_styles);
+
+        let mut orphaned = world.resource::<StyleVisits>().orphaned.clone();
+        orphaned.sort();
+        assert_eq!(orphaned, vec![template, orphan]);
+        assert!(is_styled(world, child));
+        assert!(!is_styled(world, template));
+        assert!(!is_styled(world, orphan));
+
+        // Once an orphan is reattached to a UI tree, it's no longer reported.
+        world.entity_mut(orphan).set_parent(child);
+        world.run_system_once(update_styles);
+        world.run_system_once(warn_orphaned_styles);
+        assert_eq!(world.resource::<StyleVisits>().orphaned, vec![template]);
+    }
+
+    #[test]
+    fn test_check_interval() {
+        let mut app = test_app(OrphanedStylesConfig {
+            check_interval: Some(Duration::from_secs(10)),
+            style_as_roots: false,
+        });
+        let world = &mut app.world;
+        let (_, _, orphan) = spawn_nodes(world);
+
+        world.run_system_once(update_styles);
+        world.run_system_once(warn_orphaned_styles);
+        assert_eq!(world.resource::<StyleVisits>().orphaned.len(), 2);
+
+        // Not checked again until the interval has passed.
+        world.despawn(orphan);
+        world.run_system_once(update_styles);
+        world.run_system_once(warn_orphaned_styles);
+        assert_eq!(world.resource::<StyleVisits>().orphaned.len(), 2);
+
+        world
+            .resource_mut::<Time>()
+            .advance_by(Duration::from_secs(10));
+        world.run_system_once(warn_orphaned_styles);
+        assert_eq!(world.resource::<StyleVisits>().orphaned.len(), 1);
+    }
+
+    #[test]
+    fn test_style_as_roots() {
+        let mut app = test_app(OrphanedStylesConfig {
+            check_interval: Some(Duration::ZERO),
+            style_as_roots: true,
+        });
+        let world = &mut app.world;
+        let (child, template, orphan) = spawn_nodes(world);
+        let grandchild = world
+            .spawn((NodeBundle::default(), ElementStyles::new(&[red()])))
+            .set_parent(template)
+            .id();
+
+        world.run_system_once(update_styles);
+        world.run_system_once(warn_orphaned_styles);
+
+        for entity in [child, template, orphan, grandchild] {
+            assert!(is_styled(world, entity));
+        }
+        assert!(world.resource::<StyleVisits>().orphaned.is_empty());
+    }
+}
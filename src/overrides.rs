@@ -0,0 +1,250 @@
+//! User override stylesheets, which let players adjust the UI (for example, larger fonts or
+//! higher-contrast colors) by placing stylesheet files in a config directory.
+
+use std::path::{Path, PathBuf};
+
+use bevy::prelude::*;
+use bevy_peacock_style::{parse_stylesheet_recovering, DuplicateRules, StylePropList};
+
+use crate::{ElementStyles, StyleRegistry, StyleRoot};
+
+/// File extensions which are read from the user override directory.
+const EXTENSIONS: [&str; 2] = ["pcss", "pss"];
+
+/// Resource holding the directory which user override stylesheets are read from.
+#[derive(Resource, Clone, Debug)]
+pub struct UserOverrides {
+    /// The directory to read. This is outside of the asset root, so it's read directly from the
+    /// filesystem.
+    pub dir: PathBuf,
+}
+
+/// Re-read the user override stylesheets, replacing any overrides loaded previously, and
+/// restyle the elements which use the affected styles.
+///
+/// Each rule in the override files whose name matches a style in the [`StyleRegistry`] is
+/// applied after that style, wherever it is used. Files which can't be read are logged and
+/// skipped, as are rules which can't be parsed, leaving the rest of their file to apply.
+/// Rules which don't match a registered style are logged and ignored.
+/// Does nothing if there is no [`UserOverrides`] resource.
+pub fn reload_user_overrides(world: &mut World) {
+    let Some(overrides) = world.get_resource::<UserOverrides>() else {
+        return;
+    };
+    let parsed = read_override_dir(&overrides.dir);
+
+    let mut registry = world.get_resource_or_insert_with(StyleRegistry::default);
+    let mut affected = registry.overridden_styles();
+    registry.set_overrides(parsed);
+    affected.extend(registry.overridden_styles());
+
+    // Restyle elements which use a style whose overrides may have changed, either directly or
+    // as the default styles of their tree.
+    let mut query = world.query::<&mut ElementStyles>();
+    for mut element_styles in query.iter_mut(world) {
+        if element_styles
+            .styles
+            .iter()
+            .any(|style| affected.contains(style))
+        {
+            element_styles.set_changed();
+        }
+    }
+    let mut query = world.query::<&mut StyleRoot>();
+    for mut root in query.iter_mut(world) {
+        if root
+            .styles
+            .styles
+            .iter()
+            .any(|style| affected.contains(style))
+        {
+            root.set_changed();
+        }
+    }
+}
+
+/// Read and parse every override stylesheet in `dir`, in file name order. Rules with the same
+/// name, in the same or different files, are merged.
+fn read_override_dir(dir: &Path) -> Vec<(String, StylePropList)> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(err) => {
+            info!(
+                "No user style overrides loaded from {}: {}",
+                dir.display(),
+                err
+            );
+            return Vec::new();
+        }
+    };
+    let mut paths: Vec<PathBuf> = entries
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            path.extension()
+                .and_then(|ext| ext.to_str())
+                .is_some_and(|ext| EXTENSIONS.contains(&ext))
+        })
+        .collect();
+    paths.sort();
+
+    let mut rules: Vec<(String, StylePropList)> = Vec::new();
+    for path in paths {
+        let src = match std::fs::read_to_string(&path) {
+            Ok(src) => src,
+            Err(err) => {
+                warn!("Failed to read style overrides {}: {}", path.display(), err);
+                continue;
+            }
+        };
+        let (parsed, errors) = parse_stylesheet_recovering(&src, DuplicateRules::Merge);
+        for err in errors {
+            warn!("Skipping style override in {}: {}", path.display(), err);
+        }
+        for (name, list) in parsed {
+            match rules.iter_mut().find(|(n, _)| *n == name) {
+                Some((_, prev)) => prev.extend(list),
+                None => rules.push((name, list)),
+            }
+        }
+    }
+    rules
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{apply_styles_once, test_app};
+    use crate::{PeacockPlugin, StyleHandle};
+
+    /// Create an empty directory for a test's override files.
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("bevy_peacock_{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn button_style() -> StyleHandle {
+        StyleHandle::build(|ss| ss.width(10.).height(20.))
+    }
+
+    #[test]
+    fn test_reload_user_overrides() {
+        let dir = temp_dir("reload");
+        let mut app = test_app();
+        let world = &mut app.world;
+        world.insert_resource(UserOverrides { dir: dir.clone() });
+
+        let button = button_style();
+        let mut registry = StyleRegistry::default();
+        registry.register("BUTTON", button.clone());
+        world.insert_resource(registry);
+        let entity = world
+            .spawn((NodeBundle::default(), ElementStyles::new(&[button])))
+            .id();
+
+        // No override files yet.
+        reload_user_overrides(world);
+        apply_styles_once(world);
+        assert_eq!(world.get::<Style>(entity).unwrap().width, Val::Px(10.));
+
+        // A malformed rule is skipped, leaving the rest of its file and the other files to
+        // load, and files with other extensions are ignored.
+        std::fs::write(dir.join("a.pcss"), "BUTTON { width: 30px; }").unwrap();
+        std::fs::write(
+            dir.join("b.pcss"),
+            "BUTTON { width: ; }\nBUTTON { height: 25px; }\nBUTTON { width: ",
+        )
+        .unwrap();
+        std::fs::write(dir.join("c.txt"), "BUTTON { width: 50px; }").unwrap();
+        std::fs::write(dir.join("d.pcss"), "UNKNOWN { width: 1px; }").unwrap();
+        reload_user_overrides(world);
+        apply_styles_once(world);
+        let style = world.get::<Style>(entity).unwrap();
+        assert_eq!(style.width, Val::Px(30.));
+        assert_eq!(style.height, Val::Px(25.));
+
+        // Removing the overrides restores the original style.
+        std::fs::remove_file(dir.join("a.pcss")).unwrap();
+        std::fs::remove_file(dir.join("b.pcss")).unwrap();
+        reload_user_overrides(world);
+        apply_styles_once(world);
+        assert_eq!(world.get::<Style>(entity).unwrap().width, Val::Px(10.));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_reload_style_root_overrides() {
+        let dir = temp_dir("reload_root");
+        let mut app = test_app();
+        let world = &mut app.world;
+        world.insert_resource(UserOverrides { dir: dir.clone() });
+
+        let button = button_style();
+        let mut registry = StyleRegistry::default();
+        registry.register("BUTTON", button.clone());
+        world.insert_resource(registry);
+        let entity = world
+            .spawn((NodeBundle::default(), StyleRoot::new(&[button])))
+            .id();
+
+        reload_user_overrides(world);
+        apply_styles_once(world);
+        assert_eq!(world.get::<Style>(entity).unwrap().width, Val::Px(10.));
+
+        // The tree's default styles pick up the overrides too.
+        std::fs::write(dir.join("a.pcss"), "BUTTON { width: 30px; }").unwrap();
+        reload_user_overrides(world);
+        apply_styles_once(world);
+        assert_eq!(world.get::<Style>(entity).unwrap().width, Val::Px(30.));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_plugin_user_overrides() {
+        let dir = temp_dir("plugin");
+        std::fs::write(
+            dir.join("overrides.pss"),
+            "BUTTON { height: 40px; }\nBUTTON { width: 15px; }",
+        )
+        .unwrap();
+
+        let mut app = test_app();
+        app.add_plugins(PeacockPlugin)
+            .insert_resource(UserOverrides { dir: dir.clone() })
+            .add_systems(
+                Startup,
+                |mut commands: Commands, mut registry: ResMut<StyleRegistry>| {
+                    let button = button_style();
+                    registry.register("BUTTON", button.clone());
+                    commands.spawn((NodeBundle::default(), ElementStyles::new(&[button])));
+                },
+            );
+        app.update();
+
+        let style = app
+            .world
+            .query_filtered::<&Style, With<ElementStyles>>()
+            .single(&app.world);
+        assert_eq!(style.width, Val::Px(15.));
+        assert_eq!(style.height, Val::Px(40.));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_missing_dir() {
+        let mut world = World::new();
+        world.insert_resource(UserOverrides {
+            dir: std::env::temp_dir().join("bevy_peacock_missing_overrides"),
+        });
+        reload_user_overrides(&mut world);
+        assert!(world
+            .resource::<StyleRegistry>()
+            .overridden_styles()
+            .is_empty());
+    }
+}
@@ -1,34 +1,430 @@
-use bevy::prelude::*;
+use bevy::{prelude::*, utils::HashMap};
+use bevy_peacock_style::{Timing, Transition, TransitionProperty, TransitionState};
 
+#[cfg(feature = "bevy_mod_picking")]
+use crate::drag::{save_drag_states, update_drag_states, DragStates};
 use crate::{
-    animate_bg_colors, animate_border_colors, animate_layout, animate_transforms,
-    update::{update_focus, update_styles, PreviousFocus},
+    animate::{
+        elevate_animated_transforms, AnimatedBackgroundColor, AnimatedBorderColor, AnimatedColor,
+        AnimatedFontSize, AnimatedIn, AnimatedLayout, AnimatedLayoutProp, AnimatedOutline,
+        AnimatedTransform, PeacockAnimationConfig,
+    },
+    animate_bg_colors, animate_border_colors, animate_font_sizes, animate_layout, animate_numbers,
+    animate_outlines, animate_transforms,
+    animated_number::{AnimatedNumber, AnimatedNumberFinished, NumberFormat},
+    app_state::restyle_changed_app_state,
+    asset_checks::{check_asset_loads, AssetLoadChecks},
+    capabilities::restyle_changed_capabilities,
+    classes::{reindex_classes, update_previous_classes, PreviousClasses, StyledClasses},
+    color_scheme::restyle_changed_color_scheme,
+    custom_pseudo_classes::{update_custom_pseudo_classes, CustomPseudoClassRegistry},
+    dump::{log_style_dumps, DebugDumpStyle},
+    fix_restored_animations,
+    flags::restyle_changed_flags,
+    focus::{
+        check_focus_order, mirror_a11y_focus, update_focus, PeacockFocusSource, PreviousFocus,
+        StyledFocus,
+    },
+    locale::restyle_changed_locale,
+    mask::restyle_changed_masks,
+    orphans::{warn_orphaned_styles, StyleVisits},
+    overrides::reload_user_overrides,
+    pseudo_classes::{update_pseudo_classes, PreviousPseudoClasses, StyledPseudoClasses},
+    rem::restyle_changed_root_font_size,
+    restyle::{collect_restyles, ForcedRestyles, RestyleAll, RestyleEntity, RestyleSubtree},
+    scope::restyle_changed_scopes,
+    text_overflow::update_text_ellipsis,
+    theme::restyle_changed_theme,
+    update::{
+        forget_ui_scale_warnings, restyle_changed_text, update_styles, update_text_sections,
+        update_window_size, PreviousWindowSize,
+    },
+    update_computed::{forget_transition_warnings, PendingStyleUpdates},
+    watch::ComputedPropChanged,
+    Checked, ColorScheme, Disabled, ElementClasses, Locale, PeacockCacheRegistry,
+    PeacockCapabilities, StyleRegistry, StyleScope, UiPauseState,
 };
 
 /// Plugin which initializes the Quill library.
+///
+/// Optional behaviour is configured with resources, which can be inserted before or after the
+/// plugin is added: [`PeacockConfig`] for the style pass,
+/// [`UserOverrides`](crate::UserOverrides) to read user override stylesheets at startup, and
+/// [`OrphanedStylesConfig`](crate::OrphanedStylesConfig) to check for styled entities which
+/// aren't part of any UI tree.
 pub struct PeacockPlugin;
 
+/// Marker resource inserted by [`PeacockPlugin`], so that code which adds styles can tell
+/// whether anything will process them.
+#[derive(Resource, Default, Debug)]
+pub struct PeacockInitialized;
+
+/// Flag recording that the missing plugin has already been reported for this world.
+#[derive(Resource, Default)]
+struct MissingPluginReported;
+
+impl PeacockPlugin {
+    /// Panic unless the [`PeacockPlugin`] has been added to `app`. Libraries which style their
+    /// widgets with Peacock can call this when building their own plugin, which must then be
+    /// added after this one.
+    pub fn assert_installed(app: &App) {
+        assert!(
+            app.world.contains_resource::<PeacockInitialized>(),
+            "bevy_peacock::PeacockPlugin has not been added to the app, so styles won't be \
+            applied. Add it with `app.add_plugins(PeacockPlugin)`, before any \
+            plugins which use styles."
+        );
+    }
+}
+
+/// Log an error, once per world, if styles or class names are being added to a world without
+/// the [`PeacockPlugin`], since nothing will ever apply them.
+pub(crate) fn check_installed(world: &mut World) {
+    if world.contains_resource::<PeacockInitialized>()
+        || world.contains_resource::<MissingPluginReported>()
+    {
+        return;
+    }
+    world.init_resource::<MissingPluginReported>();
+    error!(
+        "Styles were added to an entity, but bevy_peacock::PeacockPlugin has not been added \
+        to the app, so they will never be applied. Add it with \
+        `app.add_plugins(PeacockPlugin)`."
+    );
+}
+
+/// Runtime settings for the style pass.
+#[derive(Resource, Default, Debug, Clone)]
+pub struct PeacockConfig {
+    /// Record which styles and selector entries set each element's properties, in a
+    /// [`DebugStyleTrace`](crate::DebugStyleTrace) component which is updated whenever the
+    /// element's styles are recomputed. This slows down the style pass, so it's meant for
+    /// debugging tools. Defaults to false.
+    pub trace_styles: bool,
+
+    /// Don't mirror Bevy's accessibility `Focus` into [`PeacockFocusSource`], because the app
+    /// writes that resource itself. Defaults to false.
+    pub custom_focus: bool,
+
+    /// Font substituted for fonts named by styles which fail to load, so that text doesn't
+    /// vanish or silently change to the default font. Failures are logged either way.
+    pub fallback_font: Option<Handle<Font>>,
+
+    /// Which hidden elements the style pass skips the descendants of. Defaults to
+    /// [`SkipHidden::Never`].
+    pub skip_hidden: SkipHidden,
+}
+
+/// Which elements' descendants the style pass skips because they can't be seen, set by
+/// [`PeacockConfig::skip_hidden`].
+///
+/// The hidden element itself is still styled, since its own styles may be what reveals it. Its
+/// descendants are skipped until it's visible again, and then all of them are restyled in that
+/// same pass, whatever has changed in the meantime.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SkipHidden {
+    /// Style every element, whether it's visible or not.
+    #[default]
+    Never,
+
+    /// Skip the descendants of elements whose [`Visibility`] is [`Visibility::Hidden`]. This
+    /// includes descendants which override it with [`Visibility::Visible`].
+    Hidden,
+
+    /// Skip the descendants of elements whose [`ViewVisibility`] is false. This also covers
+    /// elements hidden by their ancestors, but `ViewVisibility` is computed after the style
+    /// pass, so elements which are revealed are restyled a frame late.
+    NotVisible,
+}
+
 /// System set which runs the Peacock style updates. Run this after the UI framework has updated
-/// styles and class names.
+/// styles and class names. For finer ordering, it's divided into the [`PeacockStage`]s.
 #[derive(SystemSet, Debug, Default, Copy, Clone, Hash, Eq, PartialEq)]
 pub struct PeacockSystemSet;
 
+/// The parts of [`PeacockSystemSet`], which run one after the other in the order listed here.
+/// Systems can be ordered against a stage to run between them, such as reading the animated
+/// `Style` after [`Animate`](Self::Animate).
+#[derive(SystemSet, Debug, Copy, Clone, Hash, Eq, PartialEq)]
+pub enum PeacockStage {
+    /// Work out which elements need restyling, and compute and write their styles, including
+    /// the targets of any transitions. Systems which change class names, styles or the
+    /// resources which selectors depend on should run before this.
+    ComputeStyles,
+
+    /// Advance the transitions and animated numbers, writing the animated values.
+    Animate,
+
+    /// Record the state which the next frame's changes are detected against, such as the
+    /// window size. Changes made after this are styled on the next frame.
+    Flush,
+}
+
 impl Plugin for PeacockPlugin {
     fn build(&self, app: &mut App) {
-        app.init_resource::<PreviousFocus>().add_systems(
-            Update,
-            (
-                update_styles,
+        app.init_resource::<PeacockFocusSource>()
+            .init_resource::<PreviousFocus>()
+            .init_resource::<StyledFocus>()
+            .init_resource::<PreviousWindowSize>()
+            .init_resource::<PreviousPseudoClasses>()
+            .init_resource::<StyledPseudoClasses>()
+            .init_resource::<PreviousClasses>()
+            .init_resource::<StyledClasses>()
+            .init_resource::<PendingStyleUpdates>()
+            .init_resource::<StyleRegistry>()
+            .init_resource::<StyleVisits>()
+            .init_resource::<ForcedRestyles>()
+            .add_event::<RestyleEntity>()
+            .add_event::<RestyleSubtree>()
+            .add_event::<RestyleAll>()
+            .add_event::<AnimatedNumberFinished>()
+            .add_event::<ComputedPropChanged>()
+            .add_event::<DebugDumpStyle>()
+            .init_resource::<PeacockAnimationConfig>()
+            .init_resource::<PeacockInitialized>()
+            .init_resource::<AssetLoadChecks>()
+            .init_resource::<CustomPseudoClassRegistry>()
+            .init_resource::<ColorScheme>()
+            .init_resource::<PeacockCapabilities>()
+            .init_resource::<UiPauseState>()
+            .init_resource::<PeacockCacheRegistry>()
+            .init_resource::<PeacockConfig>()
+            .register_type::<ElementClasses>()
+            .register_type::<Disabled>()
+            .register_type::<Checked>()
+            .register_type::<StyleScope>()
+            .register_type::<Locale>()
+            .register_type::<ColorScheme>()
+            .register_type::<PeacockCapabilities>()
+            .register_type::<UiPauseState>()
+            .register_type::<Vec<String>>()
+            .register_type::<AnimatedTransform>()
+            .register_type::<AnimatedBackgroundColor>()
+            .register_type::<AnimatedBorderColor>()
+            .register_type::<AnimatedLayout>()
+            .register_type::<AnimatedLayoutProp>()
+            .register_type::<Option<Val>>()
+            .register_type::<AnimatedOutline>()
+            .register_type::<AnimatedColor>()
+            .register_type::<Option<AnimatedColor>>()
+            .register_type::<AnimatedFontSize>()
+            .register_type::<AnimatedIn>()
+            .register_type::<AnimatedNumber>()
+            .register_type::<NumberFormat>()
+            .register_type::<Option<char>>()
+            .register_type::<HashMap<TransitionProperty, AnimatedLayoutProp>>()
+            .register_type::<TransitionState>()
+            .register_type::<Option<TransitionState>>()
+            .register_type::<Transition>()
+            .register_type::<Option<f32>>()
+            .register_type::<TransitionProperty>()
+            .register_type::<Timing>()
+            .add_systems(
+                Update,
                 (
                     update_focus,
-                    animate_transforms,
+                    reindex_classes,
+                    update_previous_classes,
+                    update_pseudo_classes,
+                    restyle_changed_scopes,
+                    restyle_changed_masks,
+                    forget_transition_warnings,
+                    forget_ui_scale_warnings,
+                    restyle_changed_text,
+                    (
+                        restyle_changed_locale,
+                        restyle_changed_color_scheme,
+                        restyle_changed_capabilities,
+                        restyle_changed_app_state,
+                        restyle_changed_flags,
+                        restyle_changed_theme,
+                        restyle_changed_root_font_size,
+                    )
+                        .chain(),
+                    update_custom_pseudo_classes,
+                    collect_restyles,
+                    update_styles,
+                    check_asset_loads,
+                    warn_orphaned_styles,
+                    update_text_sections,
+                    update_text_ellipsis,
+                    fix_restored_animations,
+                    // Write the computed styles, so that the later stages see them.
+                    apply_deferred,
+                )
+                    .chain()
+                    .in_set(PeacockStage::ComputeStyles),
+            )
+            .add_systems(
+                Update,
+                (
+                    (animate_transforms, elevate_animated_transforms).chain(),
                     animate_bg_colors,
                     animate_border_colors,
                     animate_layout,
-                ),
+                    animate_outlines,
+                    animate_font_sizes,
+                    animate_numbers,
+                )
+                    .in_set(PeacockStage::Animate),
+            )
+            .add_systems(
+                Update,
+                (log_style_dumps, update_window_size).in_set(PeacockStage::Flush),
             )
-                .chain()
-                .in_set(PeacockSystemSet),
+            .configure_sets(
+                Update,
+                (
+                    PeacockStage::ComputeStyles,
+                    PeacockStage::Animate,
+                    PeacockStage::Flush,
+                )
+                    .chain()
+                    .in_set(PeacockSystemSet),
+            )
+            .add_systems(Last, check_focus_order);
+        #[cfg(feature = "presets")]
+        app.add_systems(
+            Update,
+            crate::presets::report_preset_caches.in_set(PeacockStage::Flush),
+        );
+        #[cfg(feature = "bevy_mod_picking")]
+        app.init_resource::<DragStates>().add_systems(
+            Update,
+            (
+                update_drag_states
+                    .before(update_styles)
+                    .in_set(PeacockStage::ComputeStyles),
+                save_drag_states.in_set(PeacockStage::Flush),
+            ),
+        );
+        app.add_systems(
+            Update,
+            mirror_a11y_focus
+                .before(update_styles)
+                .in_set(PeacockStage::ComputeStyles),
+        )
+        // Styles are registered during `Startup`, so load the overrides afterwards.
+        .add_systems(PostStartup, reload_user_overrides);
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::test_utils::plugin_test_app;
+    use crate::{StyleHandle, WithClasses, WithStyles};
+    use bevy::time::TimeUpdateStrategy;
+    use std::time::Duration;
+
+    /// Add styles and class names to a few entities, through both commands and the world.
+    fn add_styles(app: &mut App) {
+        let style = StyleHandle::build(|ss| ss.width(10.));
+        let world = &mut app.world;
+        for _ in 0..3 {
+            world
+                .spawn(NodeBundle::default())
+                .with_styles(style.clone())
+                .class_names("a");
+        }
+        app.add_systems(Update, move |mut commands: Commands| {
+            commands
+                .spawn(NodeBundle::default())
+                .with_styles(style.clone())
+                .class_names("b");
+        });
+        app.update();
+        app.update();
+    }
+
+    #[test]
+    fn test_missing_plugin() {
+        let mut app = App::new();
+        app.add_plugins(MinimalPlugins);
+        add_styles(&mut app);
+        assert!(app.world.contains_resource::<MissingPluginReported>());
+
+        let mut app = plugin_test_app();
+        PeacockPlugin::assert_installed(&app);
+        add_styles(&mut app);
+        assert!(!app.world.contains_resource::<MissingPluginReported>());
+    }
+
+    /// What the systems ordered against the stages saw, in the order they ran.
+    #[derive(Resource, Default)]
+    struct StageLog(Vec<(&'static str, Val, f32)>);
+
+    fn log_stage(name: &'static str) -> impl Fn(ResMut<StageLog>, Query<(&Style, &Transform)>) {
+        move |mut log: ResMut<StageLog>, query: Query<(&Style, &Transform)>| {
+            let (style, transform) = query.single();
+            log.0.push((name, style.width, transform.scale.x));
+        }
+    }
+
+    #[test]
+    fn test_stage_order() {
+        let mut app = plugin_test_app();
+        app.init_resource::<StageLog>();
+        // Advance the clock by a fixed frame time, so that the transition's progress doesn't
+        // depend on how long the frames take to run.
+        app.insert_resource(TimeUpdateStrategy::ManualDuration(Duration::from_millis(
+            16,
+        )));
+
+        // A class added before the styles are computed is styled in the same frame, and the
+        // transition it starts has advanced by the time the animations have run.
+        let add_class = |mut query: Query<&mut ElementClasses>, mut frame: Local<u32>| {
+            *frame += 1;
+            if *frame == 2 {
+                query.single_mut().add_class("wide");
+            }
+        };
+        app.add_systems(
+            Update,
+            (
+                (add_class, log_stage("before"))
+                    .chain()
+                    .before(PeacockStage::ComputeStyles),
+                log_stage("computed")
+                    .after(PeacockStage::ComputeStyles)
+                    .before(PeacockStage::Animate),
+                log_stage("animated")
+                    .after(PeacockStage::Animate)
+                    .before(PeacockStage::Flush),
+                log_stage("after").after(PeacockSystemSet),
+            ),
         );
+        let style = StyleHandle::build(|ss| {
+            ss.width(10.)
+                .scale(1.)
+                .transition(&[Transition {
+                    property: TransitionProperty::Scale,
+                    duration: 1000.,
+                    ..default()
+                }])
+                .selector(".wide", |ss| ss.width(20.).scale(2.))
+        });
+        app.world
+            .spawn(NodeBundle::default())
+            .with_styles(style)
+            .class_names("");
+        app.update();
+        app.world.resource_mut::<StageLog>().0.clear();
+        app.update();
+
+        let log = &app.world.resource::<StageLog>().0;
+        let names: Vec<_> = log.iter().map(|(name, ..)| *name).collect();
+        assert_eq!(names, ["before", "computed", "animated", "after"]);
+        assert_eq!(log[0].1, Val::Px(10.));
+        assert_eq!(log[1].1, Val::Px(20.));
+        assert_eq!(log[1].2, 1.);
+        assert!(log[2].2 > 1. && log[2].2 < 2., "{:?}", log);
+        assert_eq!(log[3].2, log[2].2);
+    }
+
+    #[test]
+    #[should_panic(expected = "PeacockPlugin has not been added")]
+    fn test_assert_installed() {
+        PeacockPlugin::assert_installed(&App::new());
     }
 }
@@ -0,0 +1,242 @@
+//! Ready-made styles for common layout patterns.
+//!
+//! Presets are ordinary [`StyleHandle`]s, so they compose with other styles in the usual way:
+//! properties from styles later in an element's [`ElementStyles`](crate::ElementStyles) list
+//! override those from earlier ones.
+
+use crate::cache::LruCache;
+use crate::{PeacockCacheRegistry, StyleHandle};
+use bevy::{prelude::*, ui};
+use static_init::dynamic;
+use std::sync::Mutex;
+
+pub use statics::*;
+
+// `#[dynamic]` generates an undocumented wrapper type for each static.
+#[allow(missing_docs)]
+mod statics {
+    use crate::{PointerEvents, StyleHandle};
+    use bevy::ui;
+    use static_init::dynamic;
+
+    /// Stretches an element to cover its parent.
+    ///
+    /// `position: absolute; left: 0; top: 0; right: 0; bottom: 0;`
+    #[dynamic]
+    pub static FILL_PARENT: StyleHandle = StyleHandle::build(|ss| {
+        ss.position(ui::PositionType::Absolute)
+            .left(0.)
+            .top(0.)
+            .right(0.)
+            .bottom(0.)
+    });
+
+    /// Centers an element's children along both axes.
+    ///
+    /// `display: flex; justify_content: center; align_items: center;`
+    #[dynamic]
+    pub static CENTER_CONTENT: StyleHandle = StyleHandle::build(|ss| {
+        ss.display(ui::Display::Flex)
+            .justify_content(ui::JustifyContent::Center)
+            .align_items(ui::AlignItems::Center)
+    });
+
+    /// Lays out an element's children horizontally.
+    ///
+    /// `display: flex; flex_direction: row;`
+    #[dynamic]
+    pub static ROW: StyleHandle = StyleHandle::build(|ss| {
+        ss.display(ui::Display::Flex)
+            .flex_direction(ui::FlexDirection::Row)
+    });
+
+    /// Lays out an element's children vertically.
+    ///
+    /// `display: flex; flex_direction: column;`
+    #[dynamic]
+    pub static COLUMN: StyleHandle = StyleHandle::build(|ss| {
+        ss.display(ui::Display::Flex)
+            .flex_direction(ui::FlexDirection::Column)
+    });
+
+    /// Hides an element without removing it from the hierarchy.
+    ///
+    /// `position: absolute; width: 0; height: 0; overflow: clip;` and no pointer events.
+    #[dynamic]
+    pub static VISUALLY_HIDDEN: StyleHandle = StyleHandle::build(|ss| {
+        ss.position(ui::PositionType::Absolute)
+            .width(0.)
+            .height(0.)
+            .overflow(ui::Overflow::clip())
+            .pointer_events(PointerEvents::None)
+    });
+}
+
+/// Gaps up to this many pixels, in whole pixels, are cached by the `*_with_gap` constructors.
+const MAX_CACHED_GAP: f32 = 32.;
+
+/// The direction of the gap of a cached handle.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+enum GapAxis {
+    Row,
+    Column,
+}
+
+/// The shared handles of both constructors, reported to [`PeacockCacheRegistry`] as
+/// [`PeacockCacheRegistry::PRESET_GAPS`].
+#[dynamic]
+static GAPS: Mutex<LruCache<(GapAxis, u32), StyleHandle>> = Mutex::new(LruCache::default());
+
+/// Like [`ROW`], with `column_gap` set to `px` pixels.
+///
+/// Whole-pixel gaps up to 32px return a shared handle; other values allocate a new one.
+pub fn row_with_gap(px: f32) -> StyleHandle {
+    cached_gap(&GAPS, GapAxis::Row, px, |px| {
+        StyleHandle::build(|ss| {
+            ss.display(ui::Display::Flex)
+                .flex_direction(ui::FlexDirection::Row)
+                .column_gap(px)
+        })
+    })
+}
+
+/// Like [`COLUMN`], with `row_gap` set to `px` pixels.
+///
+/// Whole-pixel gaps up to 32px return a shared handle; other values allocate a new one.
+pub fn column_with_gap(px: f32) -> StyleHandle {
+    cached_gap(&GAPS, GapAxis::Column, px, |px| {
+        StyleHandle::build(|ss| {
+            ss.display(ui::Display::Flex)
+                .flex_direction(ui::FlexDirection::Column)
+                .row_gap(px)
+        })
+    })
+}
+
+fn cached_gap(
+    cache: &Mutex<LruCache<(GapAxis, u32), StyleHandle>>,
+    axis: GapAxis,
+    px: f32,
+    build: impl FnOnce(f32) -> StyleHandle,
+) -> StyleHandle {
+    if px.fract() == 0. && (0. ..=MAX_CACHED_GAP).contains(&px) {
+        cache
+            .lock()
+            .unwrap()
+            .get_or_insert_with((axis, px as u32), || build(px))
+            .clone()
+    } else {
+        build(px)
+    }
+}
+
+/// Apply the budget of the gap cache from the [`PeacockCacheRegistry`], empty it if the
+/// registry was cleared, and report its occupancy.
+pub(crate) fn report_preset_caches(
+    mut registry: ResMut<PeacockCacheRegistry>,
+    mut generation: Local<u32>,
+) {
+    let mut cache = GAPS.lock().unwrap();
+    if registry.generation() != *generation {
+        *generation = registry.generation();
+        cache.clear();
+    }
+    cache.set_max_entries(registry.max_entries(PeacockCacheRegistry::PRESET_GAPS));
+    // Only mark the registry as changed when the numbers do.
+    let prev = registry.stats(PeacockCacheRegistry::PRESET_GAPS);
+    registry
+        .bypass_change_detection()
+        .report(PeacockCacheRegistry::PRESET_GAPS, &cache);
+    if registry.stats(PeacockCacheRegistry::PRESET_GAPS) != prev {
+        registry.set_changed();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::apply_styles_once;
+    use crate::ElementStyles;
+
+    fn props(style: &StyleHandle) -> String {
+        format!("{:?}", style.props().props())
+    }
+
+    #[test]
+    fn test_preset_props() {
+        assert_eq!(
+            props(&FILL_PARENT),
+            "[Position(Absolute), Left(Px(0.0)), Top(Px(0.0)), Right(Px(0.0)), Bottom(Px(0.0))]"
+        );
+        assert_eq!(
+            props(&CENTER_CONTENT),
+            "[Display(Flex), JustifyContent(Center), AlignItems(Center)]"
+        );
+        assert_eq!(props(&ROW), "[Display(Flex), FlexDirection(Row)]");
+        assert_eq!(props(&COLUMN), "[Display(Flex), FlexDirection(Column)]");
+        assert_eq!(
+            props(&VISUALLY_HIDDEN),
+            "[Position(Absolute), Width(Px(0.0)), Height(Px(0.0)), \
+             Overflow(Overflow { x: Clip, y: Clip }), PointerEvents(None)]"
+        );
+        assert_eq!(
+            props(&row_with_gap(4.)),
+            "[Display(Flex), FlexDirection(Row), ColumnGap(Px(4.0))]"
+        );
+        assert_eq!(
+            props(&column_with_gap(2.5)),
+            "[Display(Flex), FlexDirection(Column), RowGap(Px(2.5))]"
+        );
+    }
+
+    #[test]
+    fn test_gap_cache() {
+        assert!(row_with_gap(8.) == row_with_gap(8.));
+        assert!(row_with_gap(8.) != column_with_gap(8.));
+        assert!(row_with_gap(8.5) != row_with_gap(8.5));
+        assert!(row_with_gap(100.) != row_with_gap(100.));
+    }
+
+    #[test]
+    fn test_gap_cache_budget() {
+        // A cache of its own, so that the shared one isn't affected.
+        let cache = Mutex::new(LruCache::default());
+        cache.lock().unwrap().set_max_entries(Some(2));
+        let row = |px| {
+            cached_gap(&cache, GapAxis::Row, px, |px| {
+                StyleHandle::build(|ss| ss.column_gap(px))
+            })
+        };
+        let (two, four) = (row(2.), row(4.));
+        assert!(row(2.) == two);
+        // The least recently used handle is evicted first.
+        let six = row(6.);
+        assert_eq!(cache.lock().unwrap().evictions(), 1);
+        assert!(row(2.) == two);
+        // Evicted handles are rebuilt when they're needed, with the same properties.
+        let rebuilt = row(4.);
+        assert!(rebuilt != four);
+        assert_eq!(props(&rebuilt), props(&four));
+        assert!(row(6.) != six);
+    }
+
+    #[test]
+    fn test_compose_with_override() {
+        let mut app = crate::test_utils::test_app();
+        let world = &mut app.world;
+
+        let user = StyleHandle::build(|ss| ss.column_gap(12.));
+        let row = world
+            .spawn((
+                NodeBundle::default(),
+                ElementStyles::new(&[ROW.clone(), user]),
+            ))
+            .id();
+        apply_styles_once(world);
+
+        let style = world.get::<Style>(row).unwrap();
+        assert_eq!(style.display, ui::Display::Flex);
+        assert_eq!(style.flex_direction, ui::FlexDirection::Row);
+        assert_eq!(style.column_gap, ui::Val::Px(12.));
+    }
+}
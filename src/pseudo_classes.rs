@@ -0,0 +1,190 @@
+use bevy::{
+    ecs::system::SystemParam,
+    prelude::*,
+    utils::{HashMap, HashSet},
+};
+
+/// Marker component for a widget which is disabled, matched by the `:disabled` pseudo-class.
+/// Widget libraries insert and remove this as the widget's state changes.
+#[derive(Component, Reflect, Default, Clone, Copy, Debug, PartialEq, Eq)]
+#[reflect(Component)]
+pub struct Disabled;
+
+/// Marker component for a widget which is checked (or selected, or toggled on), matched by the
+/// `:checked` pseudo-class.
+#[derive(Component, Reflect, Default, Clone, Copy, Debug, PartialEq, Eq)]
+#[reflect(Component)]
+pub struct Checked;
+
+/// The entities which were disabled or checked when styles were last updated, used to detect
+/// changes which affect the `:disabled` and `:checked` pseudo-classes.
+#[derive(Resource, Default)]
+pub(crate) struct PreviousPseudoClasses {
+    pub(crate) disabled: HashSet<Entity>,
+    pub(crate) checked: HashSet<Entity>,
+}
+
+/// Whether each entity whose [`Disabled`] or [`Checked`] marker was inserted or removed since
+/// the pass before was disabled or checked when the most recent style pass ran.
+#[derive(Resource, Default)]
+pub(crate) struct StyledPseudoClasses {
+    pub(crate) disabled: HashMap<Entity, bool>,
+    pub(crate) checked: HashMap<Entity, bool>,
+}
+
+/// The [`Disabled`] and [`Checked`] markers which were inserted or removed since the previous
+/// style pass. The style pass records them into [`StyledPseudoClasses`] as it runs, so that
+/// markers changed after it are still seen as changed by the next one.
+#[derive(SystemParam)]
+pub(crate) struct PseudoClassChanges<'w, 's> {
+    added_disabled: Query<'w, 's, Entity, Added<Disabled>>,
+    removed_disabled: RemovedComponents<'w, 's, Disabled>,
+    added_checked: Query<'w, 's, Entity, Added<Checked>>,
+    removed_checked: RemovedComponents<'w, 's, Checked>,
+    styled: Option<ResMut<'w, StyledPseudoClasses>>,
+}
+
+impl<'w, 's> PseudoClassChanges<'w, 's> {
+    /// Record the marker states seen by the style pass which is running.
+    pub(crate) fn record(&mut self) {
+        let Some(styled) = self.styled.as_mut() else {
+            return;
+        };
+        let styled = styled.as_mut();
+        // A marker which was removed and inserted again is both removed and added.
+        styled
+            .disabled
+            .extend(self.removed_disabled.read().map(|entity| (entity, false)));
+        styled
+            .disabled
+            .extend(self.added_disabled.iter().map(|entity| (entity, true)));
+        styled
+            .checked
+            .extend(self.removed_checked.read().map(|entity| (entity, false)));
+        styled
+            .checked
+            .extend(self.added_checked.iter().map(|entity| (entity, true)));
+    }
+}
+
+/// Record the marker states seen by the previous style pass as the previous ones. Like
+/// [`update_focus`](crate::focus::update_focus), this runs at the start of
+/// [`PeacockSystemSet`](crate::PeacockSystemSet), so that markers changed after the pass aren't
+/// taken as already styled.
+pub(crate) fn update_pseudo_classes(
+    mut styled: ResMut<StyledPseudoClasses>,
+    mut prev: ResMut<PreviousPseudoClasses>,
+) {
+    let (styled, prev) = (styled.as_mut(), prev.as_mut());
+    for (states, prev) in [
+        (&mut styled.disabled, &mut prev.disabled),
+        (&mut styled.checked, &mut prev.checked),
+    ] {
+        for (entity, state) in states.drain() {
+            if state {
+                prev.insert(entity);
+            } else {
+                prev.remove(&entity);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{apply_styles_once, plugin_test_app, test_app};
+    use crate::{ElementStyles, PeacockStage, StyleHandle};
+
+    #[test]
+    fn test_disabled_checked() {
+        let mut app = test_app();
+        let world = &mut app.world;
+        let bg_color = |world: &World, entity| world.get::<BackgroundColor>(entity).map(|bg| bg.0);
+
+        let style = StyleHandle::build(|ss| {
+            ss.background_color("#ff0000")
+                .selector(":checked", |ss| ss.background_color("#00ff00"))
+                .selector(":disabled", |ss| ss.background_color("#0000ff"))
+        });
+        let label_style = StyleHandle::build(|ss| {
+            ss.background_color("#ff0000")
+                .selector(":disabled > &", |ss| ss.background_color("#0000ff"))
+        });
+        // The marker components don't require the element to have classes.
+        let label = world
+            .spawn((NodeBundle::default(), ElementStyles::new(&[label_style])))
+            .id();
+        let checkbox = world
+            .spawn((NodeBundle::default(), ElementStyles::new(&[style])))
+            .push_children(&[label])
+            .id();
+
+        apply_styles_once(world);
+        assert_eq!(bg_color(world, checkbox), Color::hex("#ff0000").ok());
+
+        world.entity_mut(checkbox).insert(Checked);
+        apply_styles_once(world);
+        assert_eq!(bg_color(world, checkbox), Color::hex("#00ff00").ok());
+
+        world.entity_mut(checkbox).insert(Disabled);
+        apply_styles_once(world);
+        assert_eq!(bg_color(world, checkbox), Color::hex("#0000ff").ok());
+        assert_eq!(bg_color(world, label), Color::hex("#0000ff").ok());
+
+        world.entity_mut(checkbox).remove::<(Checked, Disabled)>();
+        apply_styles_once(world);
+        assert_eq!(bg_color(world, checkbox), Color::hex("#ff0000").ok());
+        assert_eq!(bg_color(world, label), Color::hex("#ff0000").ok());
+    }
+
+    #[test]
+    fn test_markers_changed_after_style_pass() {
+        // A system with no ordering against the style pass, which happens to run after it.
+        #[derive(Resource, Default)]
+        struct Late(Option<(Entity, bool)>);
+
+        fn late_system(world: &mut World) {
+            match world.resource_mut::<Late>().0.take() {
+                Some((entity, true)) => {
+                    world.entity_mut(entity).insert(Disabled);
+                }
+                Some((entity, false)) => {
+                    world.entity_mut(entity).remove::<Disabled>();
+                }
+                None => {}
+            }
+        }
+
+        let mut app = plugin_test_app();
+        app.init_resource::<Late>().add_systems(
+            Update,
+            late_system
+                .after(PeacockStage::ComputeStyles)
+                .before(PeacockStage::Flush),
+        );
+
+        let style =
+            StyleHandle::build(|ss| ss.width(10.).selector(":disabled", |ss| ss.width(20.)));
+        let button = app
+            .world
+            .spawn((NodeBundle::default(), ElementStyles::new([style])))
+            .id();
+        let width = |app: &App| app.world.get::<Style>(button).unwrap().width;
+
+        app.update();
+        assert_eq!(width(&app), Val::Px(10.));
+
+        // Markers changed after the pass are styled on the next frame, rather than being lost.
+        app.world.resource_mut::<Late>().0 = Some((button, true));
+        app.update();
+        assert_eq!(width(&app), Val::Px(10.));
+        app.update();
+        assert_eq!(width(&app), Val::Px(20.));
+
+        app.world.resource_mut::<Late>().0 = Some((button, false));
+        app.update();
+        app.update();
+        assert_eq!(width(&app), Val::Px(10.));
+    }
+}
@@ -0,0 +1,158 @@
+use std::sync::Arc;
+
+use bevy::{prelude::*, utils::HashMap};
+use bevy_peacock_style::StylePropList;
+
+use crate::{StyleHandle, StyleSheet};
+
+/// Resource which maps names to [`StyleHandle`]s, so that styles can be looked up at runtime,
+/// for example when the style name comes from a data file.
+///
+/// The registry also holds user overrides (see
+/// [`reload_user_overrides`](crate::reload_user_overrides)): styles which are applied after
+/// the registered style with the same name, wherever it is used.
+#[derive(Resource, Default)]
+pub struct StyleRegistry {
+    styles: StyleSheet,
+    /// Override styles, by the name of the style they apply to.
+    overrides: StyleSheet,
+    /// Override styles, by the address of the registered style they apply to.
+    overrides_by_style: HashMap<usize, StyleHandle>,
+}
+
+impl StyleRegistry {
+    /// Register a style under the given name, replacing any style previously registered under
+    /// that name.
+    pub fn register(&mut self, name: &str, handle: StyleHandle) {
+        self.insert_style(name.to_string(), handle);
+    }
+
+    /// Register every rule in a parsed stylesheet (as returned by
+    /// [`parse_stylesheet`](bevy_peacock_style::parse_stylesheet)), using the rule names as
+    /// style names.
+    pub fn register_sheet(&mut self, parsed: Vec<(String, StylePropList)>) {
+        for (name, props) in parsed {
+            self.insert_style(name, StyleHandle::new(props));
+        }
+    }
+
+    /// Look up a style by name.
+    pub fn get(&self, name: &str) -> Option<StyleHandle> {
+        self.styles.get(name).cloned()
+    }
+
+    /// Return the name under which a style is registered, if any. This searches every
+    /// registered style, so it's meant for diagnostics rather than regular use.
+    pub fn name_of(&self, style: &StyleHandle) -> Option<&str> {
+        self.styles
+            .iter()
+            .find(|(_, handle)| *handle == style)
+            .map(|(name, _)| name.as_str())
+    }
+
+    /// Replace the user overrides with the rules of a parsed stylesheet. Rules whose names
+    /// don't match a registered style are logged and ignored.
+    pub fn set_overrides(&mut self, parsed: Vec<(String, StylePropList)>) {
+        self.overrides.clear();
+        for (name, props) in parsed {
+            if self.styles.contains_key(&name) {
+                self.overrides.insert(name, StyleHandle::new(props));
+            } else {
+                info!("Ignoring override for unknown style '{}'", name);
+            }
+        }
+        self.index_overrides();
+    }
+
+    /// Return the user override for a registered style, if there is one.
+    pub fn override_for(&self, style: &StyleHandle) -> Option<&StyleHandle> {
+        if self.overrides_by_style.is_empty() {
+            return None;
+        }
+        self.overrides_by_style.get(&style_key(style))
+    }
+
+    /// Return the registered styles which currently have overrides.
+    pub(crate) fn overridden_styles(&self) -> Vec<StyleHandle> {
+        self.overrides
+            .keys()
+            .filter_map(|name| self.styles.get(name).cloned())
+            .collect()
+    }
+
+    /// Register a style, moving the override for its name, if there is one, from the style it
+    /// replaces to the new one.
+    fn insert_style(&mut self, name: String, handle: StyleHandle) {
+        let ovr = self.overrides.get(&name).cloned();
+        if let Some(prev) = self.styles.insert(name, handle.clone()) {
+            self.overrides_by_style.remove(&style_key(&prev));
+        }
+        if let Some(ovr) = ovr {
+            self.overrides_by_style.insert(style_key(&handle), ovr);
+        }
+    }
+
+    fn index_overrides(&mut self) {
+        self.overrides_by_style = self
+            .overrides
+            .iter()
+            .filter_map(|(name, ovr)| Some((style_key(self.styles.get(name)?), ovr.clone())))
+            .collect();
+    }
+}
+
+/// Styles are identified by address, since the registry holds a reference to each of them.
+fn style_key(style: &StyleHandle) -> usize {
+    Arc::as_ptr(&style.0) as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy_peacock_style::parse_stylesheet;
+
+    #[test]
+    fn test_register() {
+        let mut registry = StyleRegistry::default();
+        let button = StyleHandle::build(|ss| ss.width(10.));
+        registry.register("button", button.clone());
+        assert!(registry.get("button") == Some(button));
+        assert!(registry.get("panel").is_none());
+
+        registry.register_sheet(
+            parse_stylesheet(
+                "button { height: 10; }
+                panel { width: 20; }",
+            )
+            .unwrap(),
+        );
+        assert_eq!(
+            format!("{:?}", registry.get("button").unwrap().props().props()),
+            "[Height(Px(10.0))]"
+        );
+        assert_eq!(
+            format!("{:?}", registry.get("panel").unwrap().props().props()),
+            "[Width(Px(20.0))]"
+        );
+    }
+
+    #[test]
+    fn test_override_follows_registration() {
+        let mut registry = StyleRegistry::default();
+        let button = StyleHandle::build(|ss| ss.width(10.));
+        registry.register("button", button.clone());
+        registry.set_overrides(parse_stylesheet("button { width: 20; }").unwrap());
+        let ovr = registry.override_for(&button).unwrap().clone();
+
+        // Replacing the style moves its override to the new one.
+        let replacement = StyleHandle::build(|ss| ss.width(30.));
+        registry.register("button", replacement.clone());
+        assert!(registry.override_for(&button).is_none());
+        assert!(registry.override_for(&replacement) == Some(&ovr));
+
+        registry.register_sheet(parse_stylesheet("button { height: 5; }").unwrap());
+        assert!(registry.override_for(&replacement).is_none());
+        let button = registry.get("button").unwrap();
+        assert!(registry.override_for(&button) == Some(&ovr));
+    }
+}
@@ -0,0 +1,95 @@
+//! The root font size, which `rem` lengths such as `padding: 1.5rem` are multiples of.
+
+use bevy::prelude::*;
+use bevy_peacock_style::DEFAULT_ROOT_FONT_SIZE;
+
+use crate::RestyleAll;
+
+/// Resource holding the root font size in pixels, which `rem` lengths are multiples of. Lengths
+/// are resolved whenever styles are computed, so changing it, for example from a UI scale
+/// setting, restyles the whole UI. Without this resource, the root font size is 16 pixels.
+///
+/// `rem` lengths resolve to pixels before transitions see them, so transitions between them
+/// animate as usual.
+#[derive(Resource, Clone, Copy, Debug, PartialEq)]
+pub struct RootFontSize(pub f32);
+
+impl Default for RootFontSize {
+    fn default() -> Self {
+        Self(DEFAULT_ROOT_FONT_SIZE)
+    }
+}
+
+/// Restyle everything when the root font size is inserted, changed or removed.
+pub(crate) fn restyle_changed_root_font_size(
+    root_font_size: Option<Res<RootFontSize>>,
+    mut had_root_font_size: Local<bool>,
+    mut restyle: EventWriter<RestyleAll>,
+) {
+    let changed = match root_font_size {
+        Some(ref root_font_size) => root_font_size.is_changed(),
+        None => *had_root_font_size,
+    };
+    *had_root_font_size = root_font_size.is_some();
+    if changed {
+        restyle.send(RestyleAll);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ElementStyles, StyleRegistry};
+    use bevy_peacock_style::parse_stylesheet;
+
+    #[test]
+    fn test_root_font_size() {
+        let mut app = crate::test_utils::plugin_test_app();
+
+        let mut registry = StyleRegistry::default();
+        registry.register_sheet(
+            parse_stylesheet(
+                "PANEL {
+                    padding: 1.5rem 8px;
+                    width: 10rem;
+                    font_size: 0.75rem;
+                }",
+            )
+            .unwrap(),
+        );
+        let panel = registry.get("PANEL").unwrap();
+        app.world.insert_resource(registry);
+        let entity = app
+            .world
+            .spawn((
+                NodeBundle::default(),
+                Text::from_section("Label", TextStyle::default()),
+                ElementStyles::new([panel]),
+            ))
+            .id();
+        let style = |app: &App| app.world.get::<Style>(entity).unwrap().clone();
+        let font_size = |app: &App| {
+            app.world.get::<Text>(entity).unwrap().sections[0]
+                .style
+                .font_size
+        };
+
+        // Without the resource, lengths are multiples of 16px.
+        app.update();
+        assert_eq!(style(&app).padding, UiRect::axes(Val::Px(24.), Val::Px(8.)));
+        assert_eq!(style(&app).width, Val::Px(160.));
+        assert_eq!(font_size(&app), 12.);
+
+        // Doubling the root font size doubles the rem lengths, and only those.
+        app.world.insert_resource(RootFontSize(32.));
+        app.update();
+        assert_eq!(style(&app).padding, UiRect::axes(Val::Px(48.), Val::Px(8.)));
+        assert_eq!(style(&app).width, Val::Px(320.));
+        assert_eq!(font_size(&app), 24.);
+
+        // Removing the resource goes back to the default.
+        app.world.remove_resource::<RootFontSize>();
+        app.update();
+        assert_eq!(style(&app).padding, UiRect::axes(Val::Px(24.), Val::Px(8.)));
+    }
+}
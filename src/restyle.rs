@@ -0,0 +1,279 @@
+//! Forcing elements to be restyled on demand.
+//!
+//! The style pass normally only recomputes the styles of elements whose styles, classes or
+//! pseudo-class states have changed. Styles which depend on state the style pass can't see
+//! need to be recomputed explicitly, which these events and commands do.
+
+use bevy::{
+    ecs::{
+        system::{EntityCommands, SystemParam},
+        world::EntityWorldMut,
+    },
+    prelude::*,
+    utils::HashSet,
+};
+
+use crate::{update::update_styles, ElementStyles, StyleRoot};
+
+/// Event which forces the styles of an entity to be recomputed by the next style pass, even if
+/// nothing it depends on has changed. Its children are only restyled if the text styles they
+/// inherit change as a result.
+#[derive(Event, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RestyleEntity(pub Entity);
+
+/// Event which forces the styles of an entity and all of its descendants to be recomputed by
+/// the next style pass.
+#[derive(Event, Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RestyleSubtree(pub Entity);
+
+/// Event which forces the styles of every element to be recomputed by the next style pass.
+#[derive(Event, Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct RestyleAll;
+
+/// The entities which the next style pass must restyle, collected from the restyle events.
+#[derive(Resource, Default)]
+pub(crate) struct ForcedRestyles {
+    /// Entities to restyle.
+    pub(crate) entities: HashSet<Entity>,
+    /// Entities to restyle along with all of their descendants.
+    pub(crate) subtrees: HashSet<Entity>,
+    /// Restyle everything.
+    pub(crate) all: bool,
+}
+
+impl ForcedRestyles {
+    pub(crate) fn is_empty(&self) -> bool {
+        !self.all && self.entities.is_empty() && self.subtrees.is_empty()
+    }
+}
+
+/// Gather the restyle events sent since the last style pass. Events for entities which have
+/// since been despawned are harmless, since the style pass never reaches them.
+pub(crate) fn collect_restyles(
+    mut entity_events: EventReader<RestyleEntity>,
+    mut subtree_events: EventReader<RestyleSubtree>,
+    mut all_events: EventReader<RestyleAll>,
+    mut forced: ResMut<ForcedRestyles>,
+) {
+    if entity_events.is_empty() && subtree_events.is_empty() && all_events.is_empty() {
+        return;
+    }
+    let forced = forced.as_mut();
+    forced
+        .entities
+        .extend(entity_events.read().map(|event| event.0));
+    forced
+        .subtrees
+        .extend(subtree_events.read().map(|event| event.0));
+    if all_events.read().count() > 0 {
+        forced.all = true;
+    }
+}
+
+/// System parameter for restyling the elements whose styles depend on some state outside the
+/// element, such as a resource, when that state changes. Elements whose own styles depend on it
+/// are restyled alone; a [`StyleRoot`] whose default styles depend on it is restyled along with
+/// its whole subtree, since the defaults apply to every styled element in it.
+#[derive(SystemParam)]
+pub(crate) struct RestyleDependents<'w, 's> {
+    elements: Query<'w, 's, (Entity, &'static ElementStyles)>,
+    roots: Query<'w, 's, (Entity, &'static StyleRoot)>,
+    entity_events: EventWriter<'w, RestyleEntity>,
+    subtree_events: EventWriter<'w, RestyleSubtree>,
+}
+
+impl<'w, 's> RestyleDependents<'w, 's> {
+    /// Restyle the elements and trees whose styles `depends` returns true for.
+    pub(crate) fn restyle(&mut self, depends: impl Fn(&ElementStyles) -> bool) {
+        self.entity_events.send_batch(
+            self.elements
+                .iter()
+                .filter(|(_, styles)| depends(styles))
+                .map(|(entity, _)| RestyleEntity(entity)),
+        );
+        self.subtree_events.send_batch(
+            self.roots
+                .iter()
+                .filter(|(_, root)| depends(&root.styles))
+                .map(|(entity, _)| RestyleSubtree(entity)),
+        );
+    }
+}
+
+/// Restyle an entity and all of its descendants immediately, from an exclusive context such as
+/// an exclusive system or a [`Command`]. This runs the style pass directly, ignoring any other
+/// changes since the last pass, which are left for the next scheduled pass to handle. Requires
+/// the [`PeacockPlugin`](crate::PeacockPlugin).
+pub fn restyle_now(world: &mut World, entity: Entity) {
+    if world.get_entity(entity).is_none() {
+        return;
+    }
+    world
+        .get_resource_or_insert_with(ForcedRestyles::default)
+        .subtrees
+        .insert(entity);
+    let mut system = IntoSystem::into_system(update_styles);
+    system.initialize(world);
+    system.set_last_run(world.change_tick());
+    system.run((), world);
+    system.apply_deferred(world);
+}
+
+/// Trait which adds methods to force an entity to be restyled by the next style pass.
+pub trait Restyle {
+    /// Send a [`RestyleEntity`] event for this entity.
+    fn restyle(&mut self) -> &mut Self;
+
+    /// Send a [`RestyleSubtree`] event for this entity.
+    fn restyle_subtree(&mut self) -> &mut Self;
+}
+
+/// Send restyle events using commands.
+impl<'w, 's, 'a> Restyle for EntityCommands<'w, 's, 'a> {
+    fn restyle(&mut self) -> &mut Self {
+        self.add(|mut entity: EntityWorldMut| {
+            entity.restyle();
+        });
+        self
+    }
+
+    fn restyle_subtree(&mut self) -> &mut Self {
+        self.add(|mut entity: EntityWorldMut| {
+            entity.restyle_subtree();
+        });
+        self
+    }
+}
+
+/// Send restyle events from an [`EntityWorldMut`].
+impl<'w> Restyle for EntityWorldMut<'w> {
+    fn restyle(&mut self) -> &mut Self {
+        let entity = self.id();
+        self.world_scope(|world| world.send_event(RestyleEntity(entity)));
+        self
+    }
+
+    fn restyle_subtree(&mut self) -> &mut Self {
+        let entity = self.id();
+        self.world_scope(|world| world.send_event(RestyleSubtree(entity)));
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::update_computed::PendingStyleUpdates;
+    use crate::{ElementStyles, StyleHandle};
+
+    fn test_app() -> App {
+        let mut app = crate::test_utils::test_app();
+        app.add_systems(Update, (collect_restyles, update_styles).chain());
+        app
+    }
+
+    /// Spawn a parent with two children, all styled.
+    fn spawn_tree(world: &mut World) -> (Entity, [Entity; 2]) {
+        let style = StyleHandle::build(|ss| ss.height(20.));
+        let children = [(); 2].map(|_| {
+            world
+                .spawn((
+                    NodeBundle::default(),
+                    ElementStyles::new(std::slice::from_ref(&style)),
+                ))
+                .id()
+        });
+        let parent = world
+            .spawn((NodeBundle::default(), ElementStyles::new(&[style])))
+            .push_children(&children)
+            .id();
+        (parent, children)
+    }
+
+    /// Change the height of an entity without triggering change detection, as a stand-in for
+    /// state which styles depend on but the style pass can't see.
+    fn set_height_unseen(world: &mut World, entity: Entity, height: f32) {
+        world
+            .get_mut::<Style>(entity)
+            .unwrap()
+            .bypass_change_detection()
+            .height = Val::Px(height);
+    }
+
+    fn height(world: &World, entity: Entity) -> Val {
+        world.get::<Style>(entity).unwrap().height
+    }
+
+    #[test]
+    fn test_restyle_subtree() {
+        let mut app = test_app();
+        let (parent, children) = spawn_tree(&mut app.world);
+        app.update();
+        assert_eq!(height(&app.world, children[0]), Val::Px(20.));
+
+        for entity in [parent, children[0], children[1]] {
+            set_height_unseen(&mut app.world, entity, 5.);
+        }
+        app.update();
+        assert_eq!(height(&app.world, children[0]), Val::Px(5.));
+
+        // Children which are forced more than once are still only computed once.
+        let despawned = app.world.spawn_empty().id();
+        app.world.despawn(despawned);
+        app.world.send_event(RestyleSubtree(parent));
+        app.world.send_event(RestyleEntity(children[0]));
+        app.world.send_event(RestyleEntity(despawned));
+        app.update();
+        for entity in [parent, children[0], children[1]] {
+            assert_eq!(height(&app.world, entity), Val::Px(20.));
+        }
+        assert_eq!(
+            app.world
+                .resource::<PendingStyleUpdates>()
+                .redundant_count(),
+            0
+        );
+
+        // The forced restyle only applies to one pass.
+        set_height_unseen(&mut app.world, children[1], 5.);
+        app.update();
+        assert_eq!(height(&app.world, children[1]), Val::Px(5.));
+    }
+
+    #[test]
+    fn test_restyle_entity() {
+        let mut app = test_app();
+        let (parent, children) = spawn_tree(&mut app.world);
+        app.update();
+
+        for entity in [parent, children[0], children[1]] {
+            set_height_unseen(&mut app.world, entity, 5.);
+        }
+        app.world.entity_mut(children[0]).restyle();
+        app.update();
+        assert_eq!(height(&app.world, parent), Val::Px(5.));
+        assert_eq!(height(&app.world, children[0]), Val::Px(20.));
+        assert_eq!(height(&app.world, children[1]), Val::Px(5.));
+
+        app.world.send_event(RestyleAll);
+        app.update();
+        assert_eq!(height(&app.world, parent), Val::Px(20.));
+        assert_eq!(height(&app.world, children[1]), Val::Px(20.));
+    }
+
+    #[test]
+    fn test_restyle_now() {
+        let mut app = test_app();
+        let (parent, children) = spawn_tree(&mut app.world);
+        app.update();
+
+        for entity in [parent, children[0], children[1]] {
+            set_height_unseen(&mut app.world, entity, 5.);
+        }
+        restyle_now(&mut app.world, parent);
+        for entity in [parent, children[0], children[1]] {
+            assert_eq!(height(&app.world, entity), Val::Px(20.));
+        }
+        assert!(app.world.resource::<ForcedRestyles>().is_empty());
+    }
+}
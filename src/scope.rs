@@ -0,0 +1,112 @@
+//! Named scopes, which select the `@scope(name) { ... }` blocks of styles.
+
+use bevy::prelude::*;
+
+use crate::RestyleSubtree;
+
+/// Component which names the scope of an entity and its descendants, such as a theme. Styles
+/// used within the scope apply their `@scope(name)` blocks for the nearest enclosing scope
+/// only.
+#[derive(Component, Reflect, Default, Clone, Debug, PartialEq, Eq)]
+#[reflect(Component)]
+pub struct StyleScope(pub String);
+
+impl StyleScope {
+    /// Construct a new `StyleScope` with the given name.
+    pub fn new(name: impl Into<String>) -> Self {
+        Self(name.into())
+    }
+}
+
+/// Restyle the subtrees of entities whose scope was added, changed or removed.
+pub(crate) fn restyle_changed_scopes(
+    query: Query<Entity, Changed<StyleScope>>,
+    mut removed: RemovedComponents<StyleScope>,
+    mut restyle: EventWriter<RestyleSubtree>,
+) {
+    restyle.send_batch(query.iter().chain(removed.read()).map(RestyleSubtree));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::restyle::collect_restyles;
+    use crate::test_utils::set_hovered;
+    use crate::update::update_styles;
+    use crate::{ElementClasses, ElementStyles, StyleHandle};
+
+    fn test_app() -> App {
+        let mut app = crate::test_utils::test_app();
+        app.add_systems(
+            Update,
+            (restyle_changed_scopes, collect_restyles, update_styles).chain(),
+        );
+        app
+    }
+
+    fn bg_color(world: &World, entity: Entity) -> Option<Color> {
+        world.get::<BackgroundColor>(entity).map(|bg| bg.0)
+    }
+
+    #[test]
+    fn test_scope_blocks() {
+        let mut app = test_app();
+        let world = &mut app.world;
+        let style = StyleHandle::build(|ss| {
+            ss.background_color("#ff0000")
+                .selector(":hover", |ss| ss.background_color("#ffffff"))
+                .scope("dark", |ss| ss.background_color("#111111"))
+                .scope("light", |ss| ss.background_color("#eeeeee"))
+        });
+        let spawn_panel = |world: &mut World, scope: Option<&str>| {
+            let panel = world
+                .spawn((
+                    NodeBundle::default(),
+                    ElementStyles::new(std::slice::from_ref(&style)),
+                    ElementClasses::default(),
+                ))
+                .id();
+            let mut root = world.spawn(NodeBundle::default());
+            if let Some(scope) = scope {
+                root.insert(StyleScope::new(scope));
+            }
+            root.add_child(panel);
+            panel
+        };
+        let dark = spawn_panel(world, Some("dark"));
+        let light = spawn_panel(world, Some("light"));
+        let unscoped = spawn_panel(world, None);
+
+        // Only the block for the nearest scope applies.
+        let nested = world
+            .spawn((
+                NodeBundle::default(),
+                ElementStyles::new(std::slice::from_ref(&style)),
+                StyleScope::new("light"),
+            ))
+            .set_parent(dark)
+            .id();
+
+        app.update();
+        let world = &mut app.world;
+        assert_eq!(bg_color(world, dark), Color::hex("#111111").ok());
+        assert_eq!(bg_color(world, light), Color::hex("#eeeeee").ok());
+        assert_eq!(bg_color(world, unscoped), Color::hex("#ff0000").ok());
+        assert_eq!(bg_color(world, nested), Color::hex("#eeeeee").ok());
+
+        // Selectors take precedence over scope blocks.
+        set_hovered(world, Some(dark));
+        app.update();
+        assert_eq!(bg_color(&app.world, dark), Color::hex("#ffffff").ok());
+
+        // Renaming or removing a scope restyles the entities within it.
+        let world = &mut app.world;
+        set_hovered(world, None);
+        world.entity_mut(nested).insert(StyleScope::new("dark"));
+        let light_root = world.get::<Parent>(light).unwrap().get();
+        world.entity_mut(light_root).remove::<StyleScope>();
+        app.update();
+        assert_eq!(bg_color(&app.world, nested), Color::hex("#111111").ok());
+        assert_eq!(bg_color(&app.world, light), Color::hex("#ff0000").ok());
+    }
+}
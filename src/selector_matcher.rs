@@ -1,17 +1,74 @@
-use bevy::a11y::Focus;
+use crate::cache::{LruCache, PeacockCacheRegistry};
+use crate::classes::PreviousClasses;
+use crate::custom_pseudo_classes::{CustomPseudoClassRegistry, UnknownPseudoClasses};
+#[cfg(feature = "bevy_mod_picking")]
+use crate::drag::DragStates;
+#[cfg(not(feature = "bevy_mod_picking"))]
+use crate::drag::UnsupportedDragClasses;
+use crate::flags::StyleFlags;
+use crate::focus::{PeacockFocusSource, PreviousFocus};
+use crate::pseudo_classes::PreviousPseudoClasses;
+use crate::rem::RootFontSize;
+use crate::style_handle::replacement_count;
+use crate::theme::{Theme, UnknownTokens};
+use crate::update::PreviousWindowSize;
+use crate::{
+    AppStateKind, CapabilityKind, Checked, ColorScheme, Disabled, ElementClasses, ElementStyles,
+    Locale, PeacockCapabilities, Selector, StyleScope, UiPauseState,
+};
 use bevy::ecs::entity::Entity;
 use bevy::ecs::system::SystemParam;
 use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
 #[cfg(feature = "bevy_mod_picking")]
 use bevy_mod_picking::{
     focus::{HoverMap, PreviousHoverMap},
     pointer::PointerId,
 };
+use bevy_peacock_style::{
+    CompiledSelector, MediaQuery, SelectorOp, TokenValue, DEFAULT_ROOT_FONT_SIZE,
+};
+use std::cell::Cell;
+
+/// Hover state used when `bevy_mod_picking` is not enabled. Nothing in the library writes to
+/// this resource; it exists so that tests can simulate hovering over an entity.
 #[cfg(not(feature = "bevy_mod_picking"))]
-use std::marker::PhantomData;
+#[derive(Resource, Default)]
+pub(crate) struct FallbackHoverMap {
+    pub(crate) hovered: Option<Entity>,
+    pub(crate) hovered_prev: Option<Entity>,
+}
 
-use crate::update::PreviousFocus;
-use crate::{ElementClasses, ElementStyles, Selector};
+/// Results of matching the remainder of a selector against an ancestor, keyed by the ancestor
+/// and the address of the selector. Siblings share ancestors, so the same pair is often
+/// evaluated many times in a single style pass. Cleared at the start of each pass, so that the
+/// results reflect the current state. Replacing the contents of a [`StyleHandle`] frees its
+/// selectors, whose addresses can then be reused by new ones, so the key also holds the
+/// replacement count, and results cached before a replacement during the pass are never used.
+///
+/// [`StyleHandle`]: crate::StyleHandle
+#[derive(Default)]
+pub(crate) struct MatchCache(std::cell::RefCell<LruCache<(Entity, usize, u32), bool>>);
+
+/// Insert the resources which [`SelectorMatcher`] requires, where they're missing, so that it
+/// can be used from exclusive functions in worlds without the plugin.
+pub(crate) fn init_matcher_resources(world: &mut World) {
+    world.init_resource::<PeacockFocusSource>();
+    world.init_resource::<PreviousFocus>();
+    world.init_resource::<PreviousWindowSize>();
+    world.init_resource::<PreviousPseudoClasses>();
+    #[cfg(feature = "bevy_mod_picking")]
+    {
+        world.init_resource::<HoverMap>();
+        world.init_resource::<PreviousHoverMap>();
+        world.init_resource::<DragStates>();
+    }
+}
+
+/// The logical size of a window, which is what media queries are evaluated against.
+pub(crate) fn logical_size(window: &Window) -> Vec2 {
+    Vec2::new(window.width(), window.height())
+}
 
 #[derive(SystemParam)]
 pub struct SelectorMatcher<'w, 's> {
@@ -25,15 +82,102 @@ pub struct SelectorMatcher<'w, 's> {
     hover_map_prev: Res<'w, PreviousHoverMap>,
 
     #[cfg(not(feature = "bevy_mod_picking"))]
-    hover_map: PhantomData<i32>,
-    #[cfg(not(feature = "bevy_mod_picking"))]
-    hover_map_prev: PhantomData<i32>,
+    hover_map: Option<Res<'w, FallbackHoverMap>>,
 
-    focus: Res<'w, Focus>,
+    focus: Res<'w, PeacockFocusSource>,
     focus_prev: Res<'w, PreviousFocus>,
+
+    window_query: Query<'w, 's, &'static Window, With<PrimaryWindow>>,
+    window_size_prev: Res<'w, PreviousWindowSize>,
+
+    disabled_query: Query<'w, 's, (), With<Disabled>>,
+    checked_query: Query<'w, 's, (), With<Checked>>,
+    pseudo_classes_prev: Res<'w, PreviousPseudoClasses>,
+    classes_prev: Option<Res<'w, PreviousClasses>>,
+
+    #[cfg(feature = "bevy_mod_picking")]
+    drag_states: Res<'w, DragStates>,
+    #[cfg(not(feature = "bevy_mod_picking"))]
+    unsupported_drag: Local<'s, UnsupportedDragClasses>,
+
+    scope_query: Query<'w, 's, &'static StyleScope>,
+
+    locale: Option<Res<'w, Locale>>,
+
+    color_scheme: Option<Res<'w, ColorScheme>>,
+
+    capabilities: Option<Res<'w, PeacockCapabilities>>,
+
+    pause: Option<Res<'w, UiPauseState>>,
+
+    flags: Option<Res<'w, StyleFlags>>,
+
+    custom_classes: Option<Res<'w, CustomPseudoClassRegistry>>,
+    unknown_classes: Local<'s, UnknownPseudoClasses>,
+
+    theme: Option<Res<'w, Theme>>,
+    unknown_tokens: Local<'s, UnknownTokens>,
+
+    root_font_size: Option<Res<'w, RootFontSize>>,
+
+    match_cache: Local<'s, MatchCache>,
+
+    /// Number of selectors matched since the count was last taken, for diagnostics.
+    evaluations: Local<'s, Cell<usize>>,
 }
 
 impl<'w, 's> SelectorMatcher<'w, 's> {
+    /// Discard the results cached by the previous style pass.
+    pub(crate) fn clear_cache(&self) {
+        self.match_cache.0.borrow_mut().clear();
+    }
+
+    /// Apply the budget of the cache from the [`PeacockCacheRegistry`].
+    pub(crate) fn set_cache_budget(&self, registry: &PeacockCacheRegistry) {
+        self.match_cache
+            .0
+            .borrow_mut()
+            .set_max_entries(registry.max_entries(PeacockCacheRegistry::SELECTOR_MATCHES));
+    }
+
+    /// Report the occupancy of the cache to the [`PeacockCacheRegistry`].
+    pub(crate) fn report_cache(&self, registry: &mut PeacockCacheRegistry) {
+        registry.report(
+            PeacockCacheRegistry::SELECTOR_MATCHES,
+            &self.match_cache.0.borrow(),
+        );
+    }
+
+    /// Return the number of selectors, or the parts of them matched against ancestors, which
+    /// have been evaluated since the last call, not including cached results.
+    pub(crate) fn take_evaluations(&self) -> usize {
+        self.evaluations.take()
+    }
+
+    fn count_evaluation(&self) {
+        self.evaluations.set(self.evaluations.get() + 1);
+    }
+
+    /// Match the selector against an ancestor of the element being styled, reusing the result
+    /// if the same pair has already been matched in this style pass.
+    fn cached_match(&self, selector: &Selector, entity: &Entity) -> bool {
+        let key = (
+            *entity,
+            selector as *const Selector as usize,
+            replacement_count(),
+        );
+        let cached = self.match_cache.0.borrow_mut().get(&key).copied();
+        if let Some(result) = cached {
+            return result;
+        }
+        #[cfg(test)]
+        tests::SELECTOR_EVALUATIONS.with(|n| n.set(n.get() + 1));
+        self.count_evaluation();
+        let result = self.selector_match(selector, entity);
+        self.match_cache.0.borrow_mut().insert(key, result);
+        result
+    }
+
     /// Detects whether the given entity's styles have changed, or whether any of its ancestors
     /// have changed in a way that would affect the computation of styles (either because
     /// of class list changes or hovering).
@@ -45,11 +189,34 @@ impl<'w, 's> SelectorMatcher<'w, 's> {
         // Style changes only affect current element, not children.
         let mut changed = element_styles.is_changed();
 
+        // Media queries depend on the window size, rather than on the element's ancestors.
+        if !changed && element_styles.uses_media && self.window_resized() {
+            changed = true;
+        }
+
         // Search ancestors to see if any have changed.
         // We want to know if either the class list or the hover state has changed.
         if !changed && element_styles.selector_depth > 0 {
+            let mut reach = None;
             let mut e = entity;
-            for _ in 0..element_styles.selector_depth {
+            for level in 0..element_styles.selector_depth {
+                // Marker components don't require the element to have classes.
+                if self.is_disabled(&e) != self.was_disabled(&e)
+                    || self.is_checked(&e) != self.was_checked(&e)
+                    || self.custom_changed(&e)
+                {
+                    changed = true;
+                    break;
+                }
+
+                #[cfg(feature = "bevy_mod_picking")]
+                if self.is_dragging(&e) != self.was_dragging(&e)
+                    || self.is_drag_over(&e) != self.was_drag_over(&e)
+                {
+                    changed = true;
+                    break;
+                }
+
                 if let Ok(a_classes) = self.classes_query.get(e) {
                     if element_styles.uses_hover && self.is_hovering(&e) != self.was_hovering(&e) {
                         changed = true;
@@ -73,12 +240,21 @@ impl<'w, 's> SelectorMatcher<'w, 's> {
                         break;
                     }
 
-                    if a_classes.is_changed() {
+                    if a_classes.is_changed() && self.classes_changed(&e, &a_classes) {
                         changed = true;
                         break;
                     }
                 }
 
+                // Nothing changed at this level, so stop once no alternative reaches past it.
+                let reach =
+                    *reach.get_or_insert_with(|| self.selector_reach(entity, element_styles));
+                if level + 1 >= reach {
+                    break;
+                }
+
+                #[cfg(test)]
+                tests::ANCESTOR_LOOKUPS.with(|n| n.set(n.get() + 1));
                 match self.parent_query.get(e) {
                     Ok(parent) => e = **parent,
                     _ => break,
@@ -88,6 +264,56 @@ impl<'w, 's> SelectorMatcher<'w, 's> {
         changed
     }
 
+    /// How many levels of the hierarchy, starting at `entity`, changes can affect its styles
+    /// from. Each selector alternative is matched a level at a time, and an alternative which
+    /// doesn't match at a level can't be affected by changes further up. The alternatives are
+    /// sorted deepest first, so the search stops once none of those left could reach further.
+    fn selector_reach(&self, entity: Entity, element_styles: &ElementStyles) -> usize {
+        let mut reach = 0;
+        for arm in element_styles.selector_arms.iter() {
+            if arm.depth <= reach {
+                break;
+            }
+            let props = element_styles.styles.get(arm.style).map(|ss| ss.props());
+            let selector = props.as_ref().and_then(|props| {
+                let (selector, _) = props.selectors().get(arm.selector)?;
+                selector.arms().nth(arm.arm)
+            });
+            reach = match selector {
+                Some(selector) => reach.max(self.arm_reach(selector, entity, arm.depth)),
+                // The style's contents were replaced since the arms were listed.
+                None => arm.depth,
+            };
+        }
+        reach
+    }
+
+    /// How many levels of the hierarchy, up to `depth`, a selector alternative keeps matching.
+    fn arm_reach(&self, mut selector: &Selector, mut e: Entity, depth: usize) -> usize {
+        for level in 1..depth {
+            match self.match_level(selector, &e) {
+                Some(next) => selector = next,
+                None => return level,
+            }
+            #[cfg(test)]
+            tests::ANCESTOR_LOOKUPS.with(|n| n.set(n.get() + 1));
+            match self.parent_query.get(e) {
+                Ok(parent) => e = **parent,
+                _ => return level,
+            }
+        }
+        depth
+    }
+
+    /// True if the class set of the given entity differs from the one the previous style pass
+    /// saw. Only checked once the component is known to have been written, since hashing the
+    /// classes costs more than checking the change tick. Without a record, any write counts.
+    fn classes_changed(&self, e: &Entity, classes: &ElementClasses) -> bool {
+        self.classes_prev
+            .as_ref()
+            .is_none_or(|prev| prev.changed(*e, classes))
+    }
+
     /// True if the given entity, or an ancestor of it, is in the hover map for PointerId::Mouse.
     ///
     /// This is used to determine whether to apply the :hover pseudo-class.
@@ -100,8 +326,11 @@ impl<'w, 's> SelectorMatcher<'w, 's> {
     }
 
     #[cfg(not(feature = "bevy_mod_picking"))]
-    pub fn is_hovering(&self, _e: &Entity) -> bool {
-        false
+    pub fn is_hovering(&self, e: &Entity) -> bool {
+        match self.hover_map {
+            Some(ref map) => map.hovered.is_some_and(|ha| self.is_descendant(&ha, e)),
+            None => false,
+        }
     }
 
     /// True if the given entity, or an ancestor of it, was previously in the hover map for
@@ -115,8 +344,13 @@ impl<'w, 's> SelectorMatcher<'w, 's> {
     }
 
     #[cfg(not(feature = "bevy_mod_picking"))]
-    fn was_hovering(&self, _e: &Entity) -> bool {
-        false
+    fn was_hovering(&self, e: &Entity) -> bool {
+        match self.hover_map {
+            Some(ref map) => map
+                .hovered_prev
+                .is_some_and(|ha| self.is_descendant(&ha, e)),
+            None => false,
+        }
     }
 
     /// True if the given entity has keyboard focus.
@@ -144,6 +378,11 @@ impl<'w, 's> SelectorMatcher<'w, 's> {
         Some(e) == self.focus.0.as_ref()
     }
 
+    /// The entity which has keyboard focus.
+    pub(crate) fn focus(&self) -> Option<Entity> {
+        self.focus.0
+    }
+
     /// True if the given entity had keyboard focus in the previous frame.
     fn was_focused(&self, e: &Entity) -> bool {
         Some(e) == self.focus_prev.0.as_ref()
@@ -163,6 +402,173 @@ impl<'w, 's> SelectorMatcher<'w, 's> {
         Some(e) == self.focus_prev.0.as_ref()
     }
 
+    /// True if the given entity has the [`Disabled`] component.
+    ///
+    /// This is used to determine whether to apply the :disabled pseudo-class.
+    pub fn is_disabled(&self, e: &Entity) -> bool {
+        self.disabled_query.contains(*e)
+    }
+
+    /// True if the given entity had the [`Disabled`] component when styles were last updated.
+    fn was_disabled(&self, e: &Entity) -> bool {
+        self.pseudo_classes_prev.disabled.contains(e)
+    }
+
+    /// True if the given entity has the [`Checked`] component.
+    ///
+    /// This is used to determine whether to apply the :checked pseudo-class.
+    pub fn is_checked(&self, e: &Entity) -> bool {
+        self.checked_query.contains(*e)
+    }
+
+    /// True if the given entity had the [`Checked`] component when styles were last updated.
+    fn was_checked(&self, e: &Entity) -> bool {
+        self.pseudo_classes_prev.checked.contains(e)
+    }
+
+    /// True if the given entity is being dragged.
+    ///
+    /// This is used to determine whether to apply the :dragging pseudo-class.
+    #[cfg(feature = "bevy_mod_picking")]
+    pub fn is_dragging(&self, e: &Entity) -> bool {
+        self.drag_states.is_dragging(*e)
+    }
+
+    /// Always false, since drags are only tracked with the `bevy_mod_picking` feature. This is
+    /// reported the first time it's used.
+    #[cfg(not(feature = "bevy_mod_picking"))]
+    pub fn is_dragging(&self, _e: &Entity) -> bool {
+        self.unsupported_drag.report();
+        false
+    }
+
+    /// True if the given entity was being dragged when styles were last updated.
+    #[cfg(feature = "bevy_mod_picking")]
+    fn was_dragging(&self, e: &Entity) -> bool {
+        self.drag_states.was_dragging(*e)
+    }
+
+    /// True if something is being dragged over the given entity.
+    ///
+    /// This is used to determine whether to apply the :drag-over pseudo-class.
+    #[cfg(feature = "bevy_mod_picking")]
+    pub fn is_drag_over(&self, e: &Entity) -> bool {
+        self.drag_states.is_drag_over(*e)
+    }
+
+    /// Always false, since drags are only tracked with the `bevy_mod_picking` feature. This is
+    /// reported the first time it's used.
+    #[cfg(not(feature = "bevy_mod_picking"))]
+    pub fn is_drag_over(&self, _e: &Entity) -> bool {
+        self.unsupported_drag.report();
+        false
+    }
+
+    /// True if something was being dragged over the given entity when styles were last updated.
+    #[cfg(feature = "bevy_mod_picking")]
+    fn was_drag_over(&self, e: &Entity) -> bool {
+        self.drag_states.was_drag_over(*e)
+    }
+
+    /// The logical size of the primary window, if there is one.
+    ///
+    /// This is used to evaluate media queries.
+    pub fn window_size(&self) -> Option<Vec2> {
+        self.window_query.get_single().ok().map(logical_size)
+    }
+
+    /// True if the primary window has been resized since styles were last updated.
+    pub(crate) fn window_resized(&self) -> bool {
+        self.window_size() != self.window_size_prev.0
+    }
+
+    /// True if the primary window size matches the media query.
+    fn matches_media(&self, query: &MediaQuery) -> bool {
+        self.window_size().is_some_and(|size| query.matches(size))
+    }
+
+    /// True if the feature flag is enabled in the app's [`StyleFlags`]. Without the resource,
+    /// no flags are enabled.
+    pub fn matches_flag(&self, name: &str) -> bool {
+        self.flags
+            .as_ref()
+            .is_some_and(|flags| flags.is_enabled(name))
+    }
+
+    /// True if the app's [`ColorScheme`] is dark. Without the resource, the scheme is light.
+    ///
+    /// This is used to determine whether to apply the :dark pseudo-class.
+    pub fn is_dark(&self) -> bool {
+        self.color_scheme
+            .as_ref()
+            .is_some_and(|scheme| scheme.is_dark())
+    }
+
+    /// True if the app's [`PeacockCapabilities`] include the capability. Without the resource,
+    /// there are none.
+    ///
+    /// This is used to determine whether to apply pseudo-classes such as :reduced-motion.
+    pub fn has_capability(&self, kind: CapabilityKind) -> bool {
+        self.capabilities
+            .as_ref()
+            .is_some_and(|capabilities| capabilities.has(kind))
+    }
+
+    /// True if the app is in the state: the primary window has or lacks focus, or the
+    /// [`UiPauseState`] is paused. Without a primary window, neither focus state matches, and
+    /// without the resource, the app isn't paused.
+    ///
+    /// This is used to determine whether to apply pseudo-classes such as :window-focused.
+    pub fn matches_app_state(&self, kind: AppStateKind) -> bool {
+        match kind {
+            AppStateKind::WindowFocused => self
+                .window_query
+                .get_single()
+                .is_ok_and(|window| window.focused),
+            AppStateKind::WindowUnfocused => self
+                .window_query
+                .get_single()
+                .is_ok_and(|window| !window.focused),
+            AppStateKind::Paused => self.pause.as_ref().is_some_and(|pause| pause.is_paused()),
+        }
+    }
+
+    /// True if the app's [`Locale`] matches the language tag.
+    ///
+    /// This is used to determine whether to apply the :lang() pseudo-class.
+    pub fn matches_lang(&self, tag: &str) -> bool {
+        self.locale
+            .as_ref()
+            .is_some_and(|locale| locale.matches(tag))
+    }
+
+    /// True if the custom pseudo-class `name` applied to the entity when custom pseudo-classes
+    /// were last evaluated. Pseudo-classes which aren't registered never match, and are
+    /// reported the first time they are used.
+    ///
+    /// This is used to determine whether to apply custom pseudo-classes such as `:--in-combat`.
+    pub fn matches_custom(&self, name: &str, e: &Entity) -> bool {
+        match self
+            .custom_classes
+            .as_ref()
+            .and_then(|registry| registry.matches(name, *e))
+        {
+            Some(matches) => matches,
+            None => {
+                self.unknown_classes.report(name);
+                false
+            }
+        }
+    }
+
+    /// True if any custom pseudo-class started or stopped applying to the entity when they
+    /// were last evaluated.
+    fn custom_changed(&self, e: &Entity) -> bool {
+        self.custom_classes
+            .as_ref()
+            .is_some_and(|registry| registry.changed(*e))
+    }
+
     /// True if this entity is the first child of its parent.
     pub fn is_first_child(&self, entity: &Entity) -> bool {
         match self.parent_query.get(*entity) {
@@ -185,13 +591,59 @@ impl<'w, 's> SelectorMatcher<'w, 's> {
         }
     }
 
+    /// Match the terms of a selector which apply to a single level of the ancestor chain.
+    /// Returns the remaining terms for the parent level, or `None` if the terms did not match
+    /// or the selector does not extend past this level.
+    fn match_level<'a>(&self, selector: &'a Selector, entity: &Entity) -> Option<&'a Selector> {
+        let next_if =
+            |cond: bool, next: &'a Selector| cond.then(|| self.match_level(next, entity)).flatten();
+        match selector {
+            Selector::Accept => None,
+            Selector::Class(cls, next) => match self.classes_query.get(*entity) {
+                Ok(classes) => next_if(classes.has_class(cls), next),
+                _ => None,
+            },
+            Selector::ClassPrefix(prefix, next) => match self.classes_query.get(*entity) {
+                Ok(classes) => next_if(classes.has_class_prefix(prefix), next),
+                _ => None,
+            },
+            Selector::Hover(next) => next_if(self.is_hovering(entity), next),
+            Selector::Focus(next) => next_if(self.is_focused(entity), next),
+            Selector::FocusWithin(next) => next_if(self.is_focus_within(entity), next),
+            Selector::FocusVisible(next) => next_if(self.is_focus_visible(entity), next),
+            Selector::FirstChild(next) => next_if(self.is_first_child(entity), next),
+            Selector::LastChild(next) => next_if(self.is_last_child(entity), next),
+            Selector::Disabled(next) => next_if(self.is_disabled(entity), next),
+            Selector::Checked(next) => next_if(self.is_checked(entity), next),
+            Selector::Dragging(next) => next_if(self.is_dragging(entity), next),
+            Selector::DragOver(next) => next_if(self.is_drag_over(entity), next),
+            Selector::Dark(next) => next_if(self.is_dark(), next),
+            Selector::Capability(kind, next) => next_if(self.has_capability(*kind), next),
+            Selector::AppState(kind, next) => next_if(self.matches_app_state(*kind), next),
+            Selector::Lang(tag, next) => next_if(self.matches_lang(tag), next),
+            Selector::Custom(name, next) => next_if(self.matches_custom(name, entity), next),
+            Selector::Current(next) => self.match_level(next, entity),
+            Selector::Parent(next) => Some(next),
+            // Nested alternatives can't be split by level; keep them until the depth runs out.
+            Selector::Either(_) => Some(selector),
+            Selector::Media(query, next) => next_if(self.matches_media(query), next),
+            Selector::Flag(name, next) => next_if(self.matches_flag(name), next),
+        }
+    }
+
     /// Given an array of match params representing the element's ancestor chain, match the
     /// selector expression with the params.
     pub(crate) fn selector_match(&self, selector: &Selector, entity: &Entity) -> bool {
         match selector {
             Selector::Accept => true,
             Selector::Class(cls, next) => match self.classes_query.get(*entity) {
-                Ok(classes) => classes.0.contains(cls) && self.selector_match(next, entity),
+                Ok(classes) => classes.has_class(cls) && self.selector_match(next, entity),
+                _ => false,
+            },
+            Selector::ClassPrefix(prefix, next) => match self.classes_query.get(*entity) {
+                Ok(classes) => {
+                    classes.has_class_prefix(prefix) && self.selector_match(next, entity)
+                }
                 _ => false,
             },
             Selector::Hover(next) => self.is_hovering(entity) && self.selector_match(next, entity),
@@ -208,24 +660,146 @@ impl<'w, 's> SelectorMatcher<'w, 's> {
             Selector::LastChild(next) => {
                 self.is_last_child(entity) && self.selector_match(next, entity)
             }
+            Selector::Disabled(next) => {
+                self.is_disabled(entity) && self.selector_match(next, entity)
+            }
+            Selector::Checked(next) => self.is_checked(entity) && self.selector_match(next, entity),
+            Selector::Dragging(next) => {
+                self.is_dragging(entity) && self.selector_match(next, entity)
+            }
+            Selector::DragOver(next) => {
+                self.is_drag_over(entity) && self.selector_match(next, entity)
+            }
+            Selector::Dark(next) => self.is_dark() && self.selector_match(next, entity),
+            Selector::Capability(kind, next) => {
+                self.has_capability(*kind) && self.selector_match(next, entity)
+            }
+            Selector::AppState(kind, next) => {
+                self.matches_app_state(*kind) && self.selector_match(next, entity)
+            }
+            Selector::Lang(tag, next) => {
+                self.matches_lang(tag) && self.selector_match(next, entity)
+            }
+            Selector::Custom(name, next) => {
+                self.matches_custom(name, entity) && self.selector_match(next, entity)
+            }
             Selector::Current(next) => self.selector_match(next, entity),
             Selector::Parent(next) => match self.parent_query.get(*entity) {
-                Ok(parent) => self.selector_match(next, &parent.get()),
+                Ok(parent) => self.cached_match(next, &parent.get()),
                 _ => false,
             },
             Selector::Either(opts) => opts.iter().any(|next| self.selector_match(next, entity)),
+            Selector::Media(query, next) => {
+                self.matches_media(query) && self.selector_match(next, entity)
+            }
+            Selector::Flag(name, next) => {
+                self.matches_flag(name) && self.selector_match(next, entity)
+            }
         }
     }
 
-    /// True if the given entity is a descendant of the given ancestor.
+    /// Match a compiled selector, with the same result as matching the selector it was
+    /// compiled from.
+    pub(crate) fn compiled_match(&self, compiled: &CompiledSelector, entity: &Entity) -> bool {
+        self.count_evaluation();
+        self.ops_match(compiled.ops(), *entity)
+    }
+
+    /// Match the remaining instructions of a compiled selector against an ancestor, reusing the
+    /// result if the same pair has already been matched in this style pass.
+    fn cached_ops_match(&self, ops: &[SelectorOp], entity: Entity) -> bool {
+        if ops.is_empty() {
+            return true;
+        }
+        let key = (entity, ops.as_ptr() as usize, replacement_count());
+        let cached = self.match_cache.0.borrow_mut().get(&key).copied();
+        if let Some(result) = cached {
+            return result;
+        }
+        #[cfg(test)]
+        tests::SELECTOR_EVALUATIONS.with(|n| n.set(n.get() + 1));
+        self.count_evaluation();
+        let result = self.ops_match(ops, entity);
+        self.match_cache.0.borrow_mut().insert(key, result);
+        result
+    }
+
+    /// Evaluate compiled selector instructions in order, until one fails or the element
+    /// changes to its parent.
+    fn ops_match(&self, ops: &[SelectorOp], entity: Entity) -> bool {
+        let e = &entity;
+        for (index, op) in ops.iter().enumerate() {
+            let matched = match op {
+                SelectorOp::Class(cls) => match self.classes_query.get(entity) {
+                    Ok(classes) => classes.has_class(cls),
+                    _ => false,
+                },
+                SelectorOp::ClassPrefix(prefix) => match self.classes_query.get(entity) {
+                    Ok(classes) => classes.has_class_prefix(prefix),
+                    _ => false,
+                },
+                SelectorOp::Hover => self.is_hovering(e),
+                SelectorOp::Focus => self.is_focused(e),
+                SelectorOp::FocusWithin => self.is_focus_within(e),
+                SelectorOp::FocusVisible => self.is_focus_visible(e),
+                SelectorOp::FirstChild => self.is_first_child(e),
+                SelectorOp::LastChild => self.is_last_child(e),
+                SelectorOp::Disabled => self.is_disabled(e),
+                SelectorOp::Checked => self.is_checked(e),
+                SelectorOp::Dragging => self.is_dragging(e),
+                SelectorOp::DragOver => self.is_drag_over(e),
+                SelectorOp::Dark => self.is_dark(),
+                SelectorOp::Capability(kind) => self.has_capability(*kind),
+                SelectorOp::AppState(kind) => self.matches_app_state(*kind),
+                SelectorOp::Lang(tag) => self.matches_lang(tag),
+                SelectorOp::Custom(name) => self.matches_custom(name, e),
+                SelectorOp::Media(query) => self.matches_media(query),
+                SelectorOp::Flag(name) => self.matches_flag(name),
+                SelectorOp::Parent => {
+                    return match self.parent_query.get(entity) {
+                        Ok(parent) => self.cached_ops_match(&ops[index + 1..], parent.get()),
+                        _ => false,
+                    };
+                }
+                SelectorOp::Either(opts) => {
+                    return opts.iter().any(|opt| self.ops_match(opt.ops(), entity));
+                }
+            };
+            if !matched {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// True if the nearest scope enclosing the entity, including the entity itself, is named
+    /// `scope`.
+    pub(crate) fn scope_match(&self, scope: &str, entity: &Entity) -> bool {
+        let mut e = *entity;
+        loop {
+            if let Ok(StyleScope(name)) = self.scope_query.get(e) {
+                return name == scope;
+            }
+            match self.parent_query.get(e) {
+                Ok(parent) => e = parent.get(),
+                _ => return false,
+            }
+        }
+    }
+
+    /// True if the given entity is a descendant of the given ancestor. Only UI nodes are walked:
+    /// the walk stops at the UI root, or at an entity outside the UI such as a 3D marker
+    /// parented under a node, so hover and focus don't leak across the boundary in either
+    /// direction.
     fn is_descendant(&self, e: &Entity, ancestor: &Entity) -> bool {
-        let mut ha = e;
+        let mut ha = *e;
         loop {
-            if ha == ancestor {
+            if ha == *ancestor {
                 return true;
             }
-            match self.parent_query.get(*ha) {
-                Ok(parent) => ha = parent,
+            match self.parent_query.get(ha) {
+                // Every UI parent has `Children`, so this checks that the parent is a UI node.
+                Ok(parent) if self.children_query.contains(parent.get()) => ha = parent.get(),
                 _ => return false,
             }
         }
@@ -236,4 +810,378 @@ impl bevy_peacock_style::SelectorMatcher for SelectorMatcher<'_, '_> {
     fn selector_match(&self, selector: &Selector, entity: &Entity) -> bool {
         SelectorMatcher::selector_match(self, selector, entity)
     }
+
+    fn compiled_match(
+        &self,
+        compiled: &CompiledSelector,
+        _selector: &Selector,
+        entity: &Entity,
+    ) -> bool {
+        SelectorMatcher::compiled_match(self, compiled, entity)
+    }
+
+    fn scope_match(&self, scope: &str, entity: &Entity) -> bool {
+        SelectorMatcher::scope_match(self, scope, entity)
+    }
+
+    fn theme_token(&self, token: &str) -> Option<TokenValue> {
+        let value = self.theme.as_ref().and_then(|theme| theme.get(token));
+        if value.is_none() {
+            self.unknown_tokens.report(token);
+        }
+        value
+    }
+
+    fn root_font_size(&self) -> f32 {
+        self.root_font_size
+            .as_ref()
+            .map_or(DEFAULT_ROOT_FONT_SIZE, |size| size.0)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::StyleHandle;
+    use bevy::ecs::system::SystemState;
+    use std::cell::Cell;
+
+    thread_local! {
+        /// Number of parent lookups performed while walking ancestors in `is_changed`, both
+        /// to find how far the selectors reach and to look for changes.
+        pub(crate) static ANCESTOR_LOOKUPS: Cell<usize> = const { Cell::new(0) };
+
+        /// Number of selectors matched against ancestors which weren't already cached.
+        pub(crate) static SELECTOR_EVALUATIONS: Cell<usize> = const { Cell::new(0) };
+    }
+
+    type MatcherState = SystemState<(
+        SelectorMatcher<'static, 'static>,
+        Query<'static, 'static, Ref<'static, ElementStyles>>,
+    )>;
+
+    /// Spawn the chain `.a > .b > {parent_class} > elt`, returning the root and the element.
+    fn spawn_chain(world: &mut World, parent_class: &str) -> (Entity, Entity) {
+        let style = StyleHandle::build(|ss| {
+            ss.selector(".a > .b > .c > &, .x > &", |ss| {
+                ss.background_color("#ff0000")
+            })
+        });
+        let elt = world
+            .spawn((
+                NodeBundle::default(),
+                ElementClasses::default(),
+                ElementStyles::new(&[style]),
+            ))
+            .id();
+        let mut child = elt;
+        let mut root = elt;
+        for cls in [parent_class, "b", "a"] {
+            root = world
+                .spawn((NodeBundle::default(), ElementClasses::new(cls)))
+                .push_children(&[child])
+                .id();
+            child = root;
+        }
+        (root, elt)
+    }
+
+    fn is_changed(world: &World, state: &mut MatcherState, elt: Entity) -> bool {
+        let (matcher, query) = state.get(world);
+        ANCESTOR_LOOKUPS.with(|n| n.set(0));
+        matcher.is_changed(elt, &query.get(elt).unwrap())
+    }
+
+    fn lookups() -> usize {
+        ANCESTOR_LOOKUPS.with(|n| n.get())
+    }
+
+    fn test_world() -> World {
+        let mut world = World::new();
+        init_matcher_resources(&mut world);
+        world
+    }
+
+    #[test]
+    fn test_is_changed_stops_early() {
+        let mut world = test_world();
+        let (root, elt) = spawn_chain(&mut world, "x");
+        let mut state = MatcherState::new(&mut world);
+        assert!(is_changed(&world, &mut state, elt));
+
+        // The deep alternative fails at the parent and the shallow one is satisfied there,
+        // so there's no need to look past the parent.
+        assert!(!is_changed(&world, &mut state, elt));
+        assert_eq!(lookups(), 2);
+
+        // Changes above the parent can't affect the result.
+        world
+            .get_mut::<ElementClasses>(root)
+            .unwrap()
+            .add_class("z");
+        assert!(!is_changed(&world, &mut state, elt));
+        assert_eq!(lookups(), 2);
+    }
+
+    #[test]
+    fn test_is_changed_walks_matching_arm() {
+        let mut world = test_world();
+        let (root, elt) = spawn_chain(&mut world, "c");
+        let mut state = MatcherState::new(&mut world);
+        assert!(is_changed(&world, &mut state, elt));
+
+        // The deep alternative matches all the way up, but there's no need to look for the
+        // root's parent.
+        assert!(!is_changed(&world, &mut state, elt));
+        assert_eq!(lookups(), 6);
+
+        world
+            .get_mut::<ElementClasses>(root)
+            .unwrap()
+            .add_class("z");
+        assert!(is_changed(&world, &mut state, elt));
+    }
+
+    #[test]
+    #[cfg(not(feature = "bevy_mod_picking"))]
+    fn test_descendant_stops_at_ui_boundary() {
+        let mut world = test_world();
+        world.init_resource::<FallbackHoverMap>();
+        // A 3D marker under a UI button, in a UI tree which is itself under a non-UI entity.
+        let marker = world.spawn(SpatialBundle::default()).id();
+        let button = world
+            .spawn(NodeBundle::default())
+            .push_children(&[marker])
+            .id();
+        let panel = world
+            .spawn(NodeBundle::default())
+            .push_children(&[button])
+            .id();
+        let scene = world
+            .spawn(SpatialBundle::default())
+            .push_children(&[panel])
+            .id();
+        let mut state = MatcherState::new(&mut world);
+
+        let hovering = |world: &mut World, state: &mut MatcherState, hovered, e| {
+            world.resource_mut::<FallbackHoverMap>().hovered = Some(hovered);
+            state.get(world).0.is_hovering(&e)
+        };
+        assert!(hovering(&mut world, &mut state, button, button));
+        assert!(hovering(&mut world, &mut state, button, panel));
+        assert!(!hovering(&mut world, &mut state, marker, button));
+        assert!(!hovering(&mut world, &mut state, marker, panel));
+        assert!(!hovering(&mut world, &mut state, panel, scene));
+
+        world.resource_mut::<PeacockFocusSource>().0 = Some(marker);
+        assert!(!state.get(&world).0.is_focus_within(&button));
+        world.resource_mut::<PeacockFocusSource>().0 = Some(button);
+        assert!(state.get(&world).0.is_focus_within(&panel));
+        assert!(!state.get(&world).0.is_focus_within(&scene));
+    }
+
+    #[test]
+    fn test_class_prefix() {
+        let mut world = test_world();
+        let icon = world
+            .spawn((NodeBundle::default(), ElementClasses::new("icon-save")))
+            .id();
+        let icons = world
+            .spawn((NodeBundle::default(), ElementClasses::new("icons")))
+            .id();
+        let menu = world
+            .spawn((NodeBundle::default(), ElementClasses::new("menu-file")))
+            .push_children(&[icon])
+            .id();
+        world
+            .spawn((NodeBundle::default(), ElementClasses::new("menus")))
+            .push_children(&[icons]);
+
+        let mut state = MatcherState::new(&mut world);
+        let (matcher, _) = state.get(&world);
+        let selector = ".icon-*".parse::<Selector>().unwrap();
+        assert!(matcher.selector_match(&selector, &icon));
+        assert!(!matcher.selector_match(&selector, &icons));
+        assert!(!matcher.selector_match(&selector, &menu));
+
+        let selector = ".menu-* > &".parse::<Selector>().unwrap();
+        assert!(matcher.selector_match(&selector, &icon));
+        assert!(!matcher.selector_match(&selector, &icons));
+    }
+
+    #[test]
+    fn test_validate_depth() {
+        let style = StyleHandle::build(|ss| {
+            ss.selector(".a > .b > .c > &, .x > &", |ss| {
+                ss.background_color("#ff0000")
+            })
+        });
+        assert!(style.validate("style", 4));
+        assert!(!style.validate("style", 3));
+        assert_eq!(style.props().deep_selectors(1).count(), 2);
+        assert_eq!(style.props().deep_selectors(3).count(), 1);
+    }
+
+    /// Selectors exercising every kind of term, combinator and alternative.
+    const DIFFERENTIAL_SELECTORS: &[&str] = &[
+        "&",
+        ".item",
+        ".label",
+        ".icon-*",
+        ".menu > &",
+        ".menu > .item > &",
+        ".item > &.label",
+        ".menu > * > &",
+        "* > * > &",
+        ":disabled > &",
+        "&:checked",
+        ".item:checked > &.label",
+        "&:first-child",
+        "&:last-child",
+        ".item:last-child > &:first-child",
+        "&:focus",
+        "&:focus-within",
+        ".menu:focus-within > &",
+        "&:focus-visible",
+        ":hover",
+        "&:lang(de)",
+        "&:lang(fr)",
+        ".menu:lang(de) > &",
+        "@media (min-width: 600) &",
+        "@media (max-width: 600) &.item",
+        "@media (min-width: 100) .menu > &, .item > &",
+        ".selected > &, :disabled > &, &.item",
+        "&:--busy",
+        "&:dragging",
+        "&:drag-over",
+        ".menu > .item:disabled > &.label:first-child",
+        ".x > &, .item > .y > &",
+    ];
+
+    #[test]
+    fn test_cache_keyed_by_replacements() {
+        let mut world = test_world();
+        let elt = world
+            .spawn((NodeBundle::default(), ElementClasses::default()))
+            .id();
+        world
+            .spawn((NodeBundle::default(), ElementClasses::new("x")))
+            .push_children(&[elt]);
+        let mut state = MatcherState::new(&mut world);
+        let (matcher, _) = state.get(&world);
+        let selector = ".x > &".parse::<Selector>().unwrap();
+        let compiled = CompiledSelector::new(&selector);
+        assert!(matcher.selector_match(&selector, &elt));
+        assert!(matcher.compiled_match(&compiled, &elt));
+
+        // After a replacement, the addresses of the selectors may belong to different ones, so
+        // the cached results aren't used again in the same pass.
+        StyleHandle::default().replace(bevy_peacock_style::StylePropList::default());
+        let evaluations = SELECTOR_EVALUATIONS.with(|n| n.get());
+        assert!(matcher.selector_match(&selector, &elt));
+        assert!(matcher.compiled_match(&compiled, &elt));
+        assert_eq!(SELECTOR_EVALUATIONS.with(|n| n.get()), evaluations + 2);
+    }
+
+    #[test]
+    fn test_compiled_match_differential() {
+        let mut world = test_world();
+        crate::test_utils::set_window_size(&mut world, 800., 600.);
+        world.insert_resource(Locale::new("de-AT"));
+        let mut spawn = |classes: &str, children: &[Entity]| {
+            world
+                .spawn((NodeBundle::default(), ElementClasses::new(classes)))
+                .push_children(children)
+                .id()
+        };
+        let a1 = spawn("label", &[]);
+        let b1 = spawn("label icon-open", &[]);
+        let b2 = spawn("label", &[]);
+        let a = spawn("item icon-save", &[a1]);
+        let b = spawn("item selected", &[b1, b2]);
+        let c = spawn("item", &[]);
+        let root = spawn("menu", &[a, b, c]);
+        world.entity_mut(a).insert(Disabled);
+        world.entity_mut(b).insert(Checked);
+        world.resource_mut::<PeacockFocusSource>().0 = Some(b);
+        let entities = [root, a, a1, b, b1, b2, c];
+
+        let mut state = MatcherState::new(&mut world);
+        let (matcher, _) = state.get(&world);
+        for src in DIFFERENTIAL_SELECTORS {
+            let selector = src.parse::<Selector>().unwrap();
+            let compiled = CompiledSelector::new(&selector);
+            // Compiled selectors are matched twice, so that cached results are checked too.
+            for _ in 0..2 {
+                for entity in entities {
+                    assert_eq!(
+                        matcher.compiled_match(&compiled, &entity),
+                        matcher.selector_match(&selector, &entity),
+                        "'{}' on {:?}",
+                        src,
+                        entity
+                    );
+                }
+            }
+            matcher.clear_cache();
+        }
+    }
+
+    /// Compare the time taken to match selectors, including a deep one, over many entities
+    /// with the selector trees and their compiled forms. Run with
+    /// `cargo test --release bench_compiled_match -- --ignored --nocapture`.
+    #[test]
+    #[ignore]
+    fn bench_compiled_match() {
+        let mut world = test_world();
+        let mut leaves = Vec::new();
+        for _ in 0..100 {
+            let children: Vec<Entity> = (0..100)
+                .map(|_| {
+                    world
+                        .spawn((NodeBundle::default(), ElementClasses::new("leaf a b")))
+                        .id()
+                })
+                .collect();
+            leaves.extend(&children);
+            let mut node = children;
+            for level in (0..6).rev() {
+                node = vec![world
+                    .spawn((
+                        NodeBundle::default(),
+                        ElementClasses::new(format!("l{level}")),
+                    ))
+                    .push_children(&node)
+                    .id()];
+            }
+        }
+        let mut state = MatcherState::new(&mut world);
+        let (matcher, _) = state.get(&world);
+        let time = |f: &dyn Fn(&Entity) -> bool| {
+            let start = std::time::Instant::now();
+            let mut matched = 0;
+            for _ in 0..20 {
+                matcher.clear_cache();
+                matched += leaves.iter().filter(|leaf| f(leaf)).count();
+            }
+            (start.elapsed(), matched)
+        };
+        for src in [
+            "&",
+            "&.leaf",
+            "&.leaf.a.b",
+            ".l5 > &",
+            ".l0 > .l1 > .l2 > .l3 > .l4 > .l5 > &.leaf.a.b",
+            "&:first-child, .l5 > &.leaf",
+        ] {
+            let selector = src.parse::<Selector>().unwrap();
+            let compiled = CompiledSelector::new(&selector);
+            for _ in 0..2 {
+                let (tree, tree_matched) = time(&|e| matcher.selector_match(&selector, e));
+                let (flat, flat_matched) = time(&|e| matcher.compiled_match(&compiled, e));
+                assert_eq!(tree_matched, flat_matched);
+                println!("{src} tree: {:?}, compiled: {:?}", tree, flat);
+            }
+        }
+    }
 }
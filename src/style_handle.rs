@@ -2,26 +2,71 @@
 
 use crate::SelectorMatcher;
 
-use bevy::{prelude::*, utils::HashMap};
-use bevy_peacock_style::{ComputedStyle, StyleBuilder, StylePropList};
-use std::sync::Arc;
+use bevy::{asset::AssetPath, prelude::*, utils::HashMap};
+use bevy_peacock_style::{ComputedStyle, SourceSpan, SpanTable, StyleBuilder, StylePropList};
+use std::borrow::Borrow;
+use std::sync::{
+    atomic::{AtomicU32, Ordering},
+    Arc, PoisonError, RwLock, RwLockReadGuard,
+};
 
-/// A sharable reference to a collection of UI style properties.
+/// Incremented whenever the contents of any [`StyleHandle`] are replaced, so that the style
+/// pass only needs to look for stale styles when this has changed.
+static REPLACEMENTS: AtomicU32 = AtomicU32::new(0);
+
+/// Return the number of times the contents of any [`StyleHandle`] have been replaced.
+pub(crate) fn replacement_count() -> u32 {
+    REPLACEMENTS.load(Ordering::Acquire)
+}
+
+/// The shared contents of a [`StyleHandle`].
+#[derive(Default)]
+pub(crate) struct StyleCell {
+    props: RwLock<StylePropList>,
+    /// Incremented each time the properties are replaced.
+    generation: AtomicU32,
+}
+
+/// A sharable reference to a collection of UI style properties. Cloning a handle is cheap, and
+/// the clones share the same properties, which can be replaced at runtime (for example, to
+/// switch themes) using [`StyleHandle::replace`].
 #[derive(Clone, Default)]
-pub struct StyleHandle(pub Arc<StylePropList>);
+pub struct StyleHandle(pub(crate) Arc<StyleCell>);
 
 /// Handle which maintains a shared reference to a set of styles and selectors.
 impl StyleHandle {
     /// Build a [`StylePropList`] from a list of properties.
     pub fn new(props: StylePropList) -> Self {
-        Self(Arc::new(props))
+        Self(Arc::new(StyleCell {
+            props: RwLock::new(props),
+            generation: AtomicU32::new(0),
+        }))
     }
 
     /// Build a [`StylePropList`] using a builder callback.
     pub fn build(builder_fn: impl FnOnce(&mut StyleBuilder) -> &mut StyleBuilder) -> Self {
         let mut builder = StyleBuilder::default();
         builder_fn(&mut builder);
-        Self(Arc::new(StylePropList::from_builder(builder)))
+        Self::new(StylePropList::from_builder(builder))
+    }
+
+    /// Return the style properties. The properties can't be replaced while the returned guard
+    /// is held.
+    pub fn props(&self) -> RwLockReadGuard<'_, StylePropList> {
+        self.0.props.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Replace the style properties of this handle and all of its clones. Every entity using
+    /// this handle is restyled by the next style pass.
+    pub fn replace(&self, props: StylePropList) {
+        *self.0.props.write().unwrap_or_else(PoisonError::into_inner) = props;
+        self.0.generation.fetch_add(1, Ordering::AcqRel);
+        REPLACEMENTS.fetch_add(1, Ordering::AcqRel);
+    }
+
+    /// Return the number of times the properties of this handle have been replaced.
+    pub fn generation(&self) -> u32 {
+        self.0.generation.load(Ordering::Acquire)
     }
 
     /// Merge the style properties into a computed `Style` object.
@@ -31,22 +76,98 @@ impl StyleHandle {
         matcher: &SelectorMatcher,
         entity: &Entity,
     ) {
-        self.0.as_ref().apply_to(computed, matcher, entity);
+        self.props().apply_to(computed, matcher, entity);
     }
 
     /// Return the number of UiNode levels referenced by selectors.
     pub fn depth(&self) -> usize {
-        self.0.as_ref().depth()
+        self.props().depth()
     }
 
     /// Return whether any of the selectors use the ':hover' pseudo-class.
     pub fn uses_hover(&self) -> bool {
-        self.0.as_ref().uses_hover()
+        self.props().uses_hover()
+    }
+
+    /// Check the selectors in this style against `max_depth`, logging a warning naming the
+    /// rule for each selector which exceeds it. Deep selectors make the per-frame change check
+    /// walk further up the ancestor chain for every element using this style. Also logs the
+    /// problems found by [`StylePropList::validate`]. Returns `false` if any were found.
+    pub fn validate(&self, name: &str, max_depth: usize) -> bool {
+        let props = self.props();
+        let mut valid = true;
+        for lint in props.validate() {
+            warn!("Style '{}': {}", name, lint);
+            valid = false;
+        }
+        for selector in props.deep_selectors(max_depth) {
+            warn!(
+                "Style '{}': selector '{}' has depth {}, exceeding the limit of {}",
+                name,
+                selector,
+                selector.depth(),
+                max_depth
+            );
+            valid = false;
+        }
+        valid
     }
 
     /// Return whether any of the selectors use the ':focus-within' pseudo-class.
     pub fn uses_focus_within(&self) -> bool {
-        self.0.as_ref().uses_focus_within()
+        self.props().uses_focus_within()
+    }
+
+    /// Return whether any of the selectors use media queries.
+    pub fn uses_media(&self) -> bool {
+        self.props().uses_media()
+    }
+
+    /// Return whether any of the selectors use the `:dark` pseudo-class.
+    pub fn uses_color_scheme(&self) -> bool {
+        self.props().uses_color_scheme()
+    }
+
+    /// Return whether any of the selectors use capability pseudo-classes such as
+    /// `:reduced-motion`.
+    pub fn uses_capabilities(&self) -> bool {
+        self.props().uses_capabilities()
+    }
+
+    /// Return whether any of the selectors use `:window-focused` or `:window-unfocused`.
+    pub fn uses_window_focus(&self) -> bool {
+        self.props().uses_window_focus()
+    }
+
+    /// Return whether any of the selectors use the `:paused` pseudo-class.
+    pub fn uses_pause(&self) -> bool {
+        self.props().uses_pause()
+    }
+
+    /// Return the names of the feature flags which any of the selectors depend on.
+    pub fn feature_flags(&self) -> Vec<String> {
+        self.props()
+            .feature_flags()
+            .into_iter()
+            .map(str::to_owned)
+            .collect()
+    }
+
+    /// Return whether any of the style props are `calc()` lengths, which depend on the size of
+    /// the parent node.
+    pub fn uses_calc(&self) -> bool {
+        self.props().uses_calc()
+    }
+
+    /// Return the source locations of the style's rule, selectors and properties, if it was
+    /// parsed from a stylesheet.
+    pub fn source(&self) -> Option<SpanTable> {
+        self.props().spans().cloned()
+    }
+
+    /// Return the source location of the unconditional property at `index`, if known.
+    pub fn span_of_prop(&self, index: usize) -> Option<SourceSpan> {
+        self.props().span_of_prop(index)
     }
 }
 
@@ -57,58 +178,390 @@ impl PartialEq for StyleHandle {
     }
 }
 
+/// Build a [`StyleHandle`] from the properties added to a builder.
+impl From<StyleBuilder> for StyleHandle {
+    fn from(builder: StyleBuilder) -> Self {
+        Self::new(StylePropList::from_builder(builder))
+    }
+}
+
 /// A stylesheet is a collection of [`StyleHandle`]s, indexed by name.
 pub type StyleSheet = HashMap<String, StyleHandle>;
 
+/// Where to find one alternative of a selector of an element's styles, and how many levels of
+/// the hierarchy it looks at, including the element itself.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) struct SelectorArm {
+    /// The index of the style in the element's styles.
+    pub(crate) style: usize,
+    /// The index of the selector in the style's selectors.
+    pub(crate) selector: usize,
+    /// The index of the alternative in the selector's arms.
+    pub(crate) arm: usize,
+    pub(crate) depth: usize,
+}
+
+/// Default selector depth above which [`StyleHandle::validate`] reports a warning.
+pub const DEFAULT_MAX_SELECTOR_DEPTH: usize = 4;
+
 /// List of [`StyleHandle`]s which are attached to a given UiNode.
+///
+/// Along with the styles, this caches what their selectors depend on, such as how far up the
+/// hierarchy they look and whether they use `:hover`, which decides when the element needs to
+/// be restyled. The styles can only be changed through methods which keep these up to date.
 #[derive(Component, Default)]
 pub struct ElementStyles {
     /// The collection of styles associated with this element.
-    pub styles: Vec<StyleHandle>,
+    pub(crate) styles: Vec<StyleHandle>,
 
     /// How far up the hierarchy the selectors need to search
     pub(crate) selector_depth: usize,
 
+    /// The alternatives of the selectors, deepest first
+    pub(crate) selector_arms: Vec<SelectorArm>,
+
     /// Whether any selectors use the :hover pseudo-class
     pub(crate) uses_hover: bool,
 
     /// Whether any selectors use the :focus-within pseudo-class
     pub(crate) uses_focus_within: bool,
+
+    /// Whether any selectors use media queries
+    pub(crate) uses_media: bool,
+
+    /// Whether any selectors use the :dark pseudo-class
+    pub(crate) uses_color_scheme: bool,
+
+    /// Whether any selectors use capability pseudo-classes
+    pub(crate) uses_capabilities: bool,
+
+    /// Whether any selectors use the window focus pseudo-classes
+    pub(crate) uses_window_focus: bool,
+
+    /// Whether any selectors use the :paused pseudo-class
+    pub(crate) uses_pause: bool,
+
+    /// Whether any style props are `calc()` lengths
+    pub(crate) uses_calc: bool,
+
+    /// The feature flags which any selectors depend on, sorted and without duplicates
+    pub(crate) feature_flags: Vec<String>,
+
+    /// The generation of each style when the above were computed, used to detect styles
+    /// whose contents have since been replaced.
+    pub(crate) generations: Vec<u32>,
+
+    /// Whether each style is disabled, or empty if none are.
+    pub(crate) disabled: Vec<bool>,
 }
 
 impl ElementStyles {
-    pub fn new(styles: &[StyleHandle]) -> Self {
-        let selector_depth = styles.iter().map(|s| s.depth()).max().unwrap_or(0);
-        let uses_hover = styles.iter().any(|s| s.uses_hover());
-        let uses_focus_within = styles.iter().any(|s| s.uses_focus_within());
+    /// Construct a new `ElementStyles` from a list of styles, which can be either borrowed or
+    /// owned handles.
+    pub fn new<I>(styles: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: Borrow<StyleHandle>,
+    {
+        styles.into_iter().map(|s| s.borrow().clone()).collect()
+    }
+
+    /// Construct an empty `ElementStyles` with room for `capacity` styles.
+    pub fn with_capacity(capacity: usize) -> Self {
         Self {
-            styles: styles.to_vec(),
-            selector_depth,
-            uses_hover,
-            uses_focus_within,
+            styles: Vec::with_capacity(capacity),
+            generations: Vec::with_capacity(capacity),
+            ..default()
         }
     }
 
+    /// Replace the styles. The new styles are all enabled.
     pub fn update(&mut self, styles: &[StyleHandle]) {
         self.styles = styles.to_vec();
+        self.disabled.clear();
+        self.refresh();
+    }
+
+    /// Add a style after the existing ones, so that it takes precedence over them.
+    pub fn push(&mut self, style: StyleHandle) {
+        self.styles.push(style);
+        if !self.disabled.is_empty() {
+            self.disabled.push(false);
+        }
+        self.refresh();
+    }
+
+    /// Enable or disable every occurrence of `style` in the list. A disabled style keeps its
+    /// place, but its properties aren't applied, so that it can be switched off and on again
+    /// without rebuilding the list. Changing this restyles the element, and any transitions
+    /// animate as usual. Returns false if the element doesn't have the style.
+    ///
+    /// The flags such as [`uses_hover`](Self::uses_hover) still cover disabled styles, so the
+    /// element may be restyled when nothing it shows depends on the change.
+    pub fn set_enabled(&mut self, style: &StyleHandle, enabled: bool) -> bool {
+        let mut found = false;
+        for index in 0..self.styles.len() {
+            if self.styles[index] != *style {
+                continue;
+            }
+            found = true;
+            if self.is_enabled_at(index) != enabled {
+                self.disabled.resize(self.styles.len(), false);
+                self.disabled[index] = !enabled;
+            }
+        }
+        if !self.disabled.contains(&true) {
+            self.disabled.clear();
+        }
+        found
+    }
+
+    /// True if the element has `style`, and it isn't disabled by
+    /// [`set_enabled`](Self::set_enabled).
+    pub fn is_enabled(&self, style: &StyleHandle) -> bool {
+        self.styles
+            .iter()
+            .enumerate()
+            .any(|(index, ss)| ss == style && self.is_enabled_at(index))
+    }
+
+    /// True if the style at `index` is applied.
+    pub(crate) fn is_enabled_at(&self, index: usize) -> bool {
+        !self.disabled.get(index).copied().unwrap_or(false)
+    }
+
+    /// The styles, in order of increasing precedence.
+    pub fn styles(&self) -> &[StyleHandle] {
+        &self.styles
+    }
+
+    /// How many levels up the hierarchy the selectors of the styles look, so an element is
+    /// restyled when any of that many ancestors changes.
+    pub fn selector_depth(&self) -> usize {
+        self.selector_depth
+    }
+
+    /// True if any selector of the styles uses `:hover`.
+    pub fn uses_hover(&self) -> bool {
+        self.uses_hover
+    }
+
+    /// True if any selector of the styles uses `:focus-within`.
+    pub fn uses_focus_within(&self) -> bool {
+        self.uses_focus_within
+    }
+
+    /// True if any selector of the styles uses `:dark`, so the element is restyled when the
+    /// [`ColorScheme`](crate::ColorScheme) changes.
+    pub fn uses_color_scheme(&self) -> bool {
+        self.uses_color_scheme
+    }
+
+    /// True if any selector of the styles uses a capability pseudo-class such as
+    /// `:reduced-motion`, so the element is restyled when the
+    /// [`PeacockCapabilities`](crate::PeacockCapabilities) change.
+    pub fn uses_capabilities(&self) -> bool {
+        self.uses_capabilities
+    }
+
+    /// True if any selector of the styles uses `:window-focused` or `:window-unfocused`, so
+    /// the element is restyled when the primary window gains or loses focus.
+    pub fn uses_window_focus(&self) -> bool {
+        self.uses_window_focus
+    }
+
+    /// True if any selector of the styles uses `:paused`, so the element is restyled when the
+    /// [`UiPauseState`](crate::UiPauseState) changes.
+    pub fn uses_pause(&self) -> bool {
+        self.uses_pause
+    }
+
+    /// The feature flags which any selector of the styles depends on, so the element is
+    /// restyled when one of them is enabled or disabled.
+    pub fn feature_flags(&self) -> &[String] {
+        &self.feature_flags
+    }
+
+    /// Recompute what the selectors of the styles depend on. The styles can only be changed
+    /// through methods which already do this, but the contents of a [`StyleHandle`] can be
+    /// replaced in place by [`StyleHandle::replace`]; those are detected
+    /// when styles are next updated, but calling this picks them up straight away.
+    pub fn recompute_flags(&mut self) {
+        self.refresh();
+    }
+
+    /// Return whether the contents of any of the styles have been replaced since the cached
+    /// properties were computed.
+    pub(crate) fn is_stale(&self) -> bool {
+        self.styles.len() != self.generations.len()
+            || self
+                .styles
+                .iter()
+                .zip(self.generations.iter())
+                .any(|(style, generation)| style.generation() != *generation)
+    }
+
+    /// Recompute the cached properties of the styles.
+    pub(crate) fn refresh(&mut self) {
         self.selector_depth = self.styles.iter().map(|s| s.depth()).max().unwrap_or(0);
+        self.selector_arms.clear();
+        for (style, ss) in self.styles.iter().enumerate() {
+            let props = ss.props();
+            for (selector, (sel, _)) in props.selectors().iter().enumerate() {
+                self.selector_arms
+                    .extend(sel.arms().enumerate().map(|(arm, sel)| SelectorArm {
+                        style,
+                        selector,
+                        arm,
+                        depth: sel.depth(),
+                    }));
+            }
+        }
+        self.selector_arms
+            .sort_by_key(|a| std::cmp::Reverse(a.depth));
         self.uses_hover = self.styles.iter().any(|s| s.uses_hover());
         self.uses_focus_within = self.styles.iter().any(|s| s.uses_focus_within());
+        self.uses_media = self.styles.iter().any(|s| s.uses_media());
+        self.uses_color_scheme = self.styles.iter().any(|s| s.uses_color_scheme());
+        self.uses_capabilities = self.styles.iter().any(|s| s.uses_capabilities());
+        self.uses_window_focus = self.styles.iter().any(|s| s.uses_window_focus());
+        self.uses_pause = self.styles.iter().any(|s| s.uses_pause());
+        self.uses_calc = self.styles.iter().any(|s| s.uses_calc());
+        self.feature_flags = self.styles.iter().flat_map(|s| s.feature_flags()).collect();
+        self.feature_flags.sort_unstable();
+        self.feature_flags.dedup();
+        self.generations = self.styles.iter().map(|s| s.generation()).collect();
     }
 }
 
-/// Component used to store inherited text style properties. This is set whenever an element
-/// has one or more style properties which affect text rendering, even if the element is not
-/// a text node itself. This is used to calculate the inherited text style for child nodes,
-/// and also whether or not the text style has changed.
+impl FromIterator<StyleHandle> for ElementStyles {
+    fn from_iter<I: IntoIterator<Item = StyleHandle>>(iter: I) -> Self {
+        let mut element_styles = Self {
+            styles: iter.into_iter().collect(),
+            ..default()
+        };
+        element_styles.refresh();
+        element_styles
+    }
+}
+
+/// Component used to store inherited text style properties. This is set on every text node,
+/// and on other elements whose style properties make their text styles differ from their
+/// parent's. This is used to calculate the inherited text style for child nodes, and also
+/// whether or not the text style has changed.
 #[derive(Component, Default, PartialEq, Clone)]
 pub struct TextStyles {
     /// The collection of styles associated with this element.
     pub font: Option<Handle<Font>>,
 
+    /// The path of the font which the styles of this element or an ancestor set, and the
+    /// handle it was loaded as, before any fallback font was substituted. Descendants whose
+    /// styles set the same path reuse the handle rather than loading it again.
+    pub loaded_font: Option<(AssetPath<'static>, Handle<Font>)>,
+
     /// The size of the font.
     pub font_size: Option<f32>,
 
     /// Text color
     pub color: Option<Color>,
+
+    /// Scale factor for pixel sizes, set by the `ui_scale` property of the root node.
+    pub ui_scale: Option<f32>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{apply_styles_once, set_hovered, test_app};
+    use crate::{ElementClasses, ElementStyles};
+    use bevy_peacock_style::parse_stylesheet;
+
+    #[test]
+    fn test_from_builder() {
+        let mut builder = StyleBuilder::default();
+        builder
+            .width(10.)
+            .when(false, |ss| ss.width(20.))
+            .selector(":hover", |ss| ss.width(30.));
+        let handle = StyleHandle::from(builder);
+        assert_eq!(handle.props().props().len(), 1);
+        assert!(handle.uses_hover());
+    }
+
+    #[test]
+    fn test_element_styles_flags() {
+        let sheet = parse_stylesheet(
+            "PLAIN { width: 1px; }
+            HOVER { :hover > & { width: 2px; } }
+            FOCUS { :focus-within { width: 3px; } }",
+        )
+        .unwrap();
+        let mut handles: HashMap<_, _> = sheet
+            .into_iter()
+            .map(|(name, props)| (name, StyleHandle::new(props)))
+            .collect();
+        let mut take = |name: &str| handles.remove(name).unwrap();
+        let (plain, hover, focus) = (take("PLAIN"), take("HOVER"), take("FOCUS"));
+
+        let styles = ElementStyles::new(std::slice::from_ref(&plain));
+        assert_eq!(styles.selector_depth(), 0);
+        assert!(!styles.uses_hover());
+
+        // Owned handles can be collected, and the flags cover every style.
+        let mut styles: ElementStyles = [plain, hover.clone()].into_iter().collect();
+        assert_eq!(styles.selector_depth(), 2);
+        assert!(styles.uses_hover());
+        assert!(!styles.uses_focus_within());
+        styles.push(focus);
+        assert!(styles.uses_focus_within());
+        assert_eq!(styles.styles().len(), 3);
+
+        // Replacing the contents of a handle is picked up by recomputing the flags.
+        let mut styles = ElementStyles::with_capacity(1);
+        styles.push(hover.clone());
+        hover.replace(StylePropList::default());
+        assert!(styles.uses_hover());
+        styles.recompute_flags();
+        assert!(!styles.uses_hover());
+        assert_eq!(styles.selector_depth(), 0);
+    }
+
+    #[test]
+    fn test_replace_style() {
+        let mut app = test_app();
+        let world = &mut app.world;
+        let bg_color = |world: &World, entity| world.get::<BackgroundColor>(entity).map(|bg| bg.0);
+
+        let theme = StyleHandle::build(|ss| ss.background_color("#ff0000"));
+        let other = StyleHandle::build(|ss| ss.background_color("#ff0000"));
+        let [a, b, c] = [&theme, &theme, &other].map(|style| {
+            world
+                .spawn((
+                    NodeBundle::default(),
+                    ElementStyles::new(std::slice::from_ref(style)),
+                    ElementClasses::default(),
+                ))
+                .id()
+        });
+        apply_styles_once(world);
+
+        // Every entity using the handle is restyled, including with selectors which the
+        // previous contents didn't use.
+        theme.replace(StylePropList::from_builder({
+            let mut builder = StyleBuilder::default();
+            builder
+                .background_color("#00ff00")
+                .selector(":hover", |ss| ss.background_color("#0000ff"));
+            builder
+        }));
+        assert_eq!(theme.generation(), 1);
+        apply_styles_once(world);
+        assert_eq!(bg_color(world, a), Color::hex("#00ff00").ok());
+        assert_eq!(bg_color(world, b), Color::hex("#00ff00").ok());
+        assert_eq!(bg_color(world, c), Color::hex("#ff0000").ok());
+
+        set_hovered(world, Some(b));
+        apply_styles_once(world);
+        assert_eq!(bg_color(world, b), Color::hex("#0000ff").ok());
+    }
 }
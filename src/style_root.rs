@@ -0,0 +1,118 @@
+//! Default styles for a whole UI tree, such as a theme for one of several windows.
+
+use bevy::prelude::*;
+
+use crate::{ElementStyles, StyleHandle};
+
+/// Component which gives a UI tree its own default styles, for example to theme the UI of each
+/// window differently. The styles are applied to the entity itself, and to each of its
+/// descendants which has [`ElementStyles`], before the element's own styles, so they take
+/// lower priority. Text properties set by them are inherited as usual.
+///
+/// It's usually attached to a root node, but can be attached to any node, in which case it
+/// replaces the defaults of any `StyleRoot` above it for that subtree.
+#[derive(Component, Default)]
+pub struct StyleRoot {
+    /// The default styles, with their cached properties.
+    pub(crate) styles: ElementStyles,
+}
+
+impl StyleRoot {
+    /// Construct a new `StyleRoot` with the given default styles.
+    pub fn new(styles: &[StyleHandle]) -> Self {
+        Self {
+            styles: ElementStyles::new(styles),
+        }
+    }
+
+    /// The default styles.
+    pub fn styles(&self) -> &[StyleHandle] {
+        &self.styles.styles
+    }
+
+    /// Replace the default styles, restyling the tree.
+    pub fn update(&mut self, styles: &[StyleHandle]) {
+        self.styles.update(styles);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::apply_styles_once;
+    use crate::StyleRegistry;
+    use bevy_peacock_style::parse_stylesheet;
+
+    fn text_color(world: &World, entity: Entity) -> Color {
+        world.get::<Text>(entity).unwrap().sections[0].style.color
+    }
+
+    #[test]
+    fn test_style_roots() {
+        let mut app = crate::test_utils::test_app();
+        let world = &mut app.world;
+
+        let mut registry = StyleRegistry::default();
+        registry.register_sheet(
+            parse_stylesheet(
+                "GAME { color: #f00; background_color: #111; }
+                EDITOR { color: #00f; background_color: #eee; }
+                PANEL { width: 10px; }
+                BUTTON { width: 20px; .primary { color: #0f0; } }",
+            )
+            .unwrap(),
+        );
+        let theme = |name: &str| registry.get(name).unwrap();
+        let (game, editor) = (theme("GAME"), theme("EDITOR"));
+        let (panel, button) = (theme("PANEL"), theme("BUTTON"));
+        world.insert_resource(registry);
+
+        // Two identical trees, each with a styled panel containing an unstyled label.
+        let spawn_tree = |world: &mut World, theme: StyleHandle| {
+            let root = world
+                .spawn((NodeBundle::default(), StyleRoot::new(&[theme])))
+                .id();
+            let panel = world
+                .spawn((
+                    NodeBundle::default(),
+                    ElementStyles::new(std::slice::from_ref(&panel)),
+                ))
+                .set_parent(root)
+                .id();
+            let label = world
+                .spawn(TextBundle::from_section("Label", TextStyle::default()))
+                .set_parent(panel)
+                .id();
+            (root, panel, label)
+        };
+        let (game_root, game_panel, game_label) = spawn_tree(world, game);
+        let (_, editor_panel, editor_label) = spawn_tree(world, editor.clone());
+        apply_styles_once(world);
+
+        // The defaults apply to the styled elements of each tree, and are inherited by text.
+        assert_eq!(text_color(world, game_label), Color::hex("#f00").unwrap());
+        assert_eq!(text_color(world, editor_label), Color::hex("#00f").unwrap());
+        let bg = |world: &World, entity| world.get::<BackgroundColor>(entity).unwrap().0;
+        assert_eq!(bg(world, game_panel), Color::hex("#111").unwrap());
+        assert_eq!(bg(world, editor_panel), Color::hex("#eee").unwrap());
+        assert_eq!(world.get::<Style>(game_panel).unwrap().width, Val::Px(10.));
+
+        // Element styles take precedence over the defaults.
+        world
+            .entity_mut(editor_panel)
+            .insert(ElementStyles::new(&[button]))
+            .insert(crate::ElementClasses::new("primary"));
+        apply_styles_once(world);
+        assert_eq!(text_color(world, editor_label), Color::hex("#0f0").unwrap());
+        assert_eq!(text_color(world, game_label), Color::hex("#f00").unwrap());
+
+        // Changing the defaults restyles the tree.
+        world
+            .get_mut::<StyleRoot>(game_root)
+            .unwrap()
+            .update(&[editor]);
+        apply_styles_once(world);
+        assert_eq!(text_color(world, game_label), Color::hex("#00f").unwrap());
+        assert_eq!(bg(world, game_panel), Color::hex("#eee").unwrap());
+    }
+}
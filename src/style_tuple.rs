@@ -1,3 +1,4 @@
+use crate::plugin::check_installed;
 use crate::{ElementStyles, StyleHandle};
 use bevy::ecs::{system::EntityCommands, world::EntityWorldMut};
 use impl_trait_for_tuples::*;
@@ -86,7 +87,11 @@ pub trait WithStyles {
 /// Insert styles using commands.
 impl<'w, 's, 'a> WithStyles for EntityCommands<'w, 's, 'a> {
     fn with_styles<S: StyleTuple>(&mut self, styles: S) -> &mut Self {
-        self.insert(ElementStyles::new(&styles.to_vec()));
+        let styles = ElementStyles::new(styles.to_vec());
+        self.add(|mut entity: EntityWorldMut| {
+            entity.world_scope(check_installed);
+            entity.insert(styles);
+        });
         self
     }
 }
@@ -94,10 +99,11 @@ impl<'w, 's, 'a> WithStyles for EntityCommands<'w, 's, 'a> {
 /// Update or insert styles into an [`EntityWorldMut`].
 impl<'w> WithStyles for EntityWorldMut<'w> {
     fn with_styles<S: StyleTuple>(&mut self, styles: S) -> &mut Self {
+        self.world_scope(check_installed);
         if let Some(mut ec) = self.get_mut::<ElementStyles>() {
             ec.update(&styles.to_vec());
         } else {
-            self.insert(ElementStyles::new(&styles.to_vec()));
+            self.insert(ElementStyles::new(styles.to_vec()));
         }
         self
     }
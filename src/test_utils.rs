@@ -0,0 +1,226 @@
+//! Helpers for running the Peacock style pass in tests, without a full `App` or schedule.
+//!
+//! The world must contain an `AssetServer` (e.g. by adding `AssetPlugin`), since styles may
+//! load fonts and images.
+
+use bevy::ecs::system::RunSystemOnce;
+use bevy::prelude::*;
+use bevy::ui::{ui_layout_system, UiSurface};
+use bevy::window::{PrimaryWindow, WindowResized, WindowResolution, WindowScaleFactorChanged};
+#[cfg(feature = "bevy_mod_picking")]
+use bevy_mod_picking::{
+    backend::HitData,
+    focus::{HoverMap, PreviousHoverMap},
+    pointer::PointerId,
+};
+
+use crate::classes::{PreviousClasses, StyledClasses};
+use crate::custom_pseudo_classes::update_custom_pseudo_classes;
+use crate::focus::{PeacockFocusSource, StyledFocus};
+use crate::pseudo_classes::StyledPseudoClasses;
+#[cfg(test)]
+use crate::restyle::{ForcedRestyles, RestyleAll, RestyleEntity, RestyleSubtree};
+use crate::selector_matcher::init_matcher_resources;
+#[cfg(not(feature = "bevy_mod_picking"))]
+use crate::selector_matcher::FallbackHoverMap;
+use crate::update::update_styles_exclusive;
+use crate::update_computed::PendingStyleUpdates;
+use crate::{PeacockInitialized, StyleRegistry};
+
+/// Run the style pass once, and apply the resulting style changes to the world.
+///
+/// Change detection state is preserved between calls, so only entities whose styles, classes,
+/// hover or focus state have changed since the previous call will be updated. Hover and focus
+/// state are recorded as "previous" at the end of each call.
+pub fn apply_styles_once(world: &mut World) {
+    init_style_resources(world);
+
+    let mut nodes = world.query_filtered::<Entity, With<Node>>();
+    update_custom_pseudo_classes(world, &mut nodes);
+    update_styles_exclusive(world);
+
+    save_hover_map(world);
+}
+
+/// An app with the assets, resources and events which the style pass needs, but none of
+/// Peacock's systems, so that each test can add just the ones it exercises.
+#[cfg(test)]
+pub(crate) fn test_app() -> App {
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, AssetPlugin::default()))
+        .init_asset::<Image>()
+        .init_asset::<Font>()
+        .init_resource::<ForcedRestyles>()
+        .add_event::<RestyleEntity>()
+        .add_event::<RestyleSubtree>()
+        .add_event::<RestyleAll>();
+    init_style_resources(&mut app.world);
+    app
+}
+
+/// An app with the [`PeacockPlugin`](crate::PeacockPlugin) and the assets and resources it
+/// relies on, for tests which run the whole schedule with `app.update()`.
+#[cfg(test)]
+pub(crate) fn plugin_test_app() -> App {
+    let mut app = App::new();
+    app.add_plugins((MinimalPlugins, AssetPlugin::default(), crate::PeacockPlugin))
+        .init_asset::<Image>()
+        .init_asset::<Font>()
+        .init_resource::<bevy::a11y::Focus>();
+    app
+}
+
+/// Insert the resources which the style pass reads, unless they're already present. The world
+/// is also marked as initialized, since the style pass is run by hand rather than by the plugin.
+fn init_style_resources(world: &mut World) {
+    world.init_resource::<PeacockInitialized>();
+    init_matcher_resources(world);
+    world.init_resource::<StyledFocus>();
+    world.init_resource::<PreviousClasses>();
+    world.init_resource::<StyledClasses>();
+    world.init_resource::<StyledPseudoClasses>();
+    world.init_resource::<PendingStyleUpdates>();
+    world.init_resource::<StyleRegistry>();
+    init_hover_map(world);
+}
+
+/// Set the entity which the mouse pointer is hovering over, or `None` to clear it.
+#[cfg(feature = "bevy_mod_picking")]
+pub fn set_hovered(world: &mut World, entity: Option<Entity>) {
+    init_hover_map(world);
+    let mut hover_map = world.resource_mut::<HoverMap>();
+    let hovered = hover_map.entry(PointerId::Mouse).or_default();
+    hovered.clear();
+    if let Some(entity) = entity {
+        hovered.insert(entity, HitData::new(Entity::PLACEHOLDER, 0., None, None));
+    }
+}
+
+/// Set the entity which the mouse pointer is hovering over, or `None` to clear it.
+#[cfg(not(feature = "bevy_mod_picking"))]
+pub fn set_hovered(world: &mut World, entity: Option<Entity>) {
+    init_hover_map(world);
+    world.resource_mut::<FallbackHoverMap>().hovered = entity;
+}
+
+/// Set the logical size of the primary window, spawning one if there isn't one already.
+pub fn set_window_size(world: &mut World, width: f32, height: f32) {
+    let mut query = world.query_filtered::<&mut Window, With<PrimaryWindow>>();
+    match query.get_single_mut(world) {
+        Ok(mut window) => window.resolution.set(width, height),
+        Err(_) => {
+            world.spawn((
+                Window {
+                    resolution: WindowResolution::new(width, height),
+                    ..default()
+                },
+                PrimaryWindow,
+            ));
+        }
+    }
+}
+
+/// Run Bevy's UI layout once, so that nodes have a laid-out size and position. The world
+/// must have a primary window, e.g. from [`set_window_size`].
+pub fn run_layout(world: &mut World) {
+    world.init_resource::<UiScale>();
+    world.init_resource::<UiSurface>();
+    world.init_resource::<Events<WindowScaleFactorChanged>>();
+    world.init_resource::<Events<WindowResized>>();
+    world.run_system_once(ui_layout_system);
+}
+
+/// Set the entity which has keyboard focus, or `None` to clear it, by writing
+/// [`PeacockFocusSource`](crate::PeacockFocusSource).
+pub fn set_focus(world: &mut World, entity: Option<Entity>) {
+    world
+        .get_resource_or_insert_with(PeacockFocusSource::default)
+        .0 = entity;
+}
+
+#[cfg(feature = "bevy_mod_picking")]
+fn init_hover_map(world: &mut World) {
+    world.init_resource::<HoverMap>();
+    world.init_resource::<PreviousHoverMap>();
+}
+
+#[cfg(not(feature = "bevy_mod_picking"))]
+fn init_hover_map(world: &mut World) {
+    world.init_resource::<FallbackHoverMap>();
+}
+
+#[cfg(feature = "bevy_mod_picking")]
+fn save_hover_map(world: &mut World) {
+    let hovered = world.resource::<HoverMap>().0.clone();
+    world.resource_mut::<PreviousHoverMap>().0 = hovered;
+}
+
+#[cfg(not(feature = "bevy_mod_picking"))]
+fn save_hover_map(world: &mut World) {
+    let mut hover_map = world.resource_mut::<FallbackHoverMap>();
+    hover_map.hovered_prev = hover_map.hovered;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ElementClasses, ElementStyles, StyleHandle};
+
+    fn test_world() -> World {
+        std::mem::take(&mut test_app().world)
+    }
+
+    fn bg_color(world: &World, entity: Entity) -> Option<Color> {
+        world.get::<BackgroundColor>(entity).map(|bg| bg.0)
+    }
+
+    #[test]
+    fn test_hover() {
+        let mut world = test_world();
+        let style = StyleHandle::build(|ss| {
+            ss.background_color("#ff0000")
+                .selector(":hover", |ss| ss.background_color("#00ff00"))
+        });
+        let button = world
+            .spawn((
+                NodeBundle::default(),
+                ElementStyles::new(&[style]),
+                ElementClasses::default(),
+            ))
+            .id();
+
+        apply_styles_once(&mut world);
+        assert_eq!(bg_color(&world, button), Color::hex("#ff0000").ok());
+
+        set_hovered(&mut world, Some(button));
+        apply_styles_once(&mut world);
+        assert_eq!(bg_color(&world, button), Color::hex("#00ff00").ok());
+
+        set_hovered(&mut world, None);
+        apply_styles_once(&mut world);
+        assert_eq!(bg_color(&world, button), Color::hex("#ff0000").ok());
+    }
+
+    #[test]
+    fn test_focus() {
+        let mut world = test_world();
+        let style = StyleHandle::build(|ss| {
+            ss.background_color("#ff0000")
+                .selector(":focus", |ss| ss.background_color("#0000ff"))
+        });
+        let input = world
+            .spawn((
+                NodeBundle::default(),
+                ElementStyles::new(&[style]),
+                ElementClasses::default(),
+            ))
+            .id();
+
+        apply_styles_once(&mut world);
+        assert_eq!(bg_color(&world, input), Color::hex("#ff0000").ok());
+
+        set_focus(&mut world, Some(input));
+        apply_styles_once(&mut world);
+        assert_eq!(bg_color(&world, input), Color::hex("#0000ff").ok());
+    }
+}
@@ -0,0 +1,380 @@
+use bevy::{
+    ecs::system::SystemChangeTick,
+    prelude::*,
+    text::{BreakLineOn, TextLayoutInfo},
+};
+use bevy_peacock_style::TextOverflow;
+
+const ELLIPSIS: &str = "…";
+
+/// Marker for text entities whose style specifies `text_overflow: ellipsis`.
+#[derive(Component, Default)]
+pub struct TextEllipsis;
+
+/// Stores the original contents of a text entity which has been truncated with an ellipsis,
+/// so that the text can be restored when there is enough room for it.
+#[derive(Component)]
+pub struct FullText {
+    /// The original value of each text section.
+    pub sections: Vec<String>,
+
+    /// The truncated values written to the text sections.
+    written: Vec<String>,
+
+    /// Font size and font of each section when the text was measured.
+    fonts: Vec<(f32, Handle<Font>)>,
+
+    /// Right edge of each glyph of the original text, in layout order.
+    glyphs: Vec<GlyphEdge>,
+
+    /// Width of the original text.
+    full_width: f32,
+
+    /// The width for which the text was last truncated.
+    width: f32,
+}
+
+struct GlyphEdge {
+    section: usize,
+    byte_index: usize,
+    right: f32,
+}
+
+impl FullText {
+    fn new(text: &Text, layout: &TextLayoutInfo) -> Self {
+        Self {
+            sections: text.sections.iter().map(|s| s.value.clone()).collect(),
+            written: Vec::new(),
+            fonts: section_fonts(text),
+            glyphs: layout
+                .glyphs
+                .iter()
+                .map(|glyph| GlyphEdge {
+                    section: glyph.section_index,
+                    byte_index: glyph.byte_index,
+                    right: glyph.position.x + glyph.size.x * 0.5,
+                })
+                .collect(),
+            full_width: layout.logical_size.x,
+            width: 0.,
+        }
+    }
+
+    /// Compute the section values which fit within `width`, or `None` if the full text fits.
+    fn truncate(&self, width: f32) -> Option<Vec<String>> {
+        if self.full_width <= width || self.glyphs.is_empty() {
+            return None;
+        }
+
+        // There's no glyph for the ellipsis in the layout, so estimate its width as the
+        // average glyph width.
+        let ellipsis_width = self.full_width / self.glyphs.len() as f32;
+        let limit = width - ellipsis_width;
+        let fit = self.glyphs.partition_point(|glyph| glyph.right <= limit);
+        let cut = &self.glyphs[fit.min(self.glyphs.len() - 1)];
+        Some(
+            self.sections
+                .iter()
+                .enumerate()
+                .map(|(index, value)| match index.cmp(&cut.section) {
+                    std::cmp::Ordering::Less => value.clone(),
+                    std::cmp::Ordering::Equal => {
+                        format!("{}{}", value[..cut.byte_index].trim_end(), ELLIPSIS)
+                    }
+                    std::cmp::Ordering::Greater => String::new(),
+                })
+                .collect(),
+        )
+    }
+}
+
+fn section_fonts(text: &Text) -> Vec<(f32, Handle<Font>)> {
+    text.sections
+        .iter()
+        .map(|s| (s.style.font_size, s.style.font.clone()))
+        .collect()
+}
+
+fn write_sections(text: &mut Text, values: &[String]) {
+    for (section, value) in text.sections.iter_mut().zip(values) {
+        if section.value != *value {
+            section.value.clone_from(value);
+        }
+    }
+}
+
+/// Add or remove the [`TextEllipsis`] marker on a text entity, restoring the full text if the
+/// ellipsis is removed.
+pub(crate) fn update_text_overflow(e: &mut EntityWorldMut, overflow: Option<TextOverflow>) {
+    if overflow == Some(TextOverflow::Ellipsis) {
+        if !e.contains::<TextEllipsis>() {
+            e.insert(TextEllipsis);
+        }
+    } else if e.contains::<TextEllipsis>() {
+        e.remove::<TextEllipsis>();
+        if let Some(full) = e.take::<FullText>() {
+            if let Some(mut text) = e.get_mut::<Text>() {
+                write_sections(&mut text, &full.sections);
+            }
+        }
+    }
+}
+
+/// Truncate single-line text with an ellipsis when it is wider than its node, and restore it
+/// when there is enough room again.
+///
+/// The full text is measured once, after it has been laid out; subsequent changes to the node
+/// width are handled using those measurements. Changes to the text content or fonts discard
+/// the measurements, and the text is measured again once it has been laid out.
+#[allow(clippy::type_complexity)]
+pub(crate) fn update_text_ellipsis(
+    mut commands: Commands,
+    mut query: Query<
+        (
+            Entity,
+            &Node,
+            &mut Text,
+            Ref<TextLayoutInfo>,
+            Option<&mut FullText>,
+        ),
+        With<TextEllipsis>,
+    >,
+    ticks: SystemChangeTick,
+) {
+    for (entity, node, mut text, layout, full) in query.iter_mut() {
+        let width = node.size().x;
+        match full {
+            Some(mut full) => {
+                let content_changed = text.sections.len() != full.written.len()
+                    || text
+                        .sections
+                        .iter()
+                        .zip(full.written.iter())
+                        .any(|(section, written)| section.value != *written);
+                if content_changed {
+                    // Keep any sections which were replaced, and restore the rest; the text
+                    // will be measured again once it has been laid out.
+                    let text = text.bypass_change_detection();
+                    for ((section, written), original) in text
+                        .sections
+                        .iter_mut()
+                        .zip(full.written.iter())
+                        .zip(full.sections.iter())
+                    {
+                        if section.value == *written {
+                            section.value.clone_from(original);
+                        }
+                    }
+                    commands.entity(entity).remove::<FullText>();
+                } else if section_fonts(&text) != full.fonts
+                    || text.linebreak_behavior != BreakLineOn::NoWrap
+                {
+                    write_sections(&mut text, &full.sections);
+                    commands.entity(entity).remove::<FullText>();
+                } else if width != full.width {
+                    match full.truncate(width) {
+                        Some(values) => {
+                            write_sections(&mut text, &values);
+                            full.written = values;
+                            full.width = width;
+                        }
+                        None => {
+                            write_sections(&mut text, &full.sections);
+                            commands.entity(entity).remove::<FullText>();
+                        }
+                    }
+                }
+            }
+
+            None => {
+                // Wait until the current text has been laid out.
+                if text
+                    .last_changed()
+                    .is_newer_than(layout.last_changed(), ticks.this_run())
+                    || text.linebreak_behavior != BreakLineOn::NoWrap
+                    || width <= 0.
+                {
+                    continue;
+                }
+                let mut full = FullText::new(&text, &layout);
+                if let Some(values) = full.truncate(width) {
+                    write_sections(&mut text, &values);
+                    full.written = values;
+                    full.width = width;
+                    commands.entity(entity).insert(full);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::reflect::Struct;
+    use bevy::text::{GlyphAtlasInfo, PositionedGlyph};
+
+    const GLYPH_WIDTH: f32 = 10.;
+
+    fn test_app() -> App {
+        let mut app = crate::test_utils::test_app();
+        app.add_systems(Update, update_text_ellipsis);
+        app
+    }
+
+    fn spawn_label(world: &mut World, value: &str, width: f32) -> Entity {
+        let mut node = Node::default();
+        *node
+            .field_mut("calculated_size")
+            .unwrap()
+            .downcast_mut::<Vec2>()
+            .unwrap() = Vec2::new(width, 20.);
+        let entity = world
+            .spawn((
+                node,
+                Text::from_section(value, TextStyle::default()).with_no_wrap(),
+                TextEllipsis,
+            ))
+            .id();
+        lay_out(world, entity);
+        entity
+    }
+
+    /// Simulate text layout, with every character being one glyph of the same width.
+    fn lay_out(world: &mut World, entity: Entity) {
+        let text = world.get::<Text>(entity).unwrap();
+        let mut glyphs = Vec::new();
+        for (section_index, section) in text.sections.iter().enumerate() {
+            for (byte_index, _) in section.value.char_indices() {
+                glyphs.push(PositionedGlyph {
+                    position: Vec2::new((glyphs.len() as f32 + 0.5) * GLYPH_WIDTH, 10.),
+                    size: Vec2::new(GLYPH_WIDTH, 20.),
+                    atlas_info: GlyphAtlasInfo {
+                        texture_atlas: Handle::default(),
+                        glyph_index: 0,
+                    },
+                    section_index,
+                    byte_index,
+                });
+            }
+        }
+        let logical_size = Vec2::new(glyphs.len() as f32 * GLYPH_WIDTH, 20.);
+        world.entity_mut(entity).insert(TextLayoutInfo {
+            glyphs,
+            logical_size,
+        });
+    }
+
+    fn set_width(world: &mut World, entity: Entity, width: f32) {
+        let mut node = world.get_mut::<Node>(entity).unwrap();
+        *node
+            .field_mut("calculated_size")
+            .unwrap()
+            .downcast_mut::<Vec2>()
+            .unwrap() = Vec2::new(width, 20.);
+    }
+
+    fn value(world: &World, entity: Entity) -> String {
+        world
+            .get::<Text>(entity)
+            .unwrap()
+            .sections
+            .iter()
+            .map(|s| s.value.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn test_shrink_and_grow() {
+        let mut app = test_app();
+        let label = spawn_label(&mut app.world, "Hello world", 200.);
+        app.update();
+        assert_eq!(value(&app.world, label), "Hello world");
+        assert!(app.world.get::<FullText>(label).is_none());
+
+        // 60px leaves room for 5 glyphs plus the ellipsis.
+        set_width(&mut app.world, label, 60.);
+        app.update();
+        assert_eq!(value(&app.world, label), "Hello…");
+        assert_eq!(
+            app.world.get::<FullText>(label).unwrap().sections,
+            ["Hello world"]
+        );
+
+        set_width(&mut app.world, label, 40.);
+        app.update();
+        assert_eq!(value(&app.world, label), "Hel…");
+
+        set_width(&mut app.world, label, 100.);
+        app.update();
+        assert_eq!(value(&app.world, label), "Hello wor…");
+
+        set_width(&mut app.world, label, 110.);
+        app.update();
+        assert_eq!(value(&app.world, label), "Hello world");
+        assert!(app.world.get::<FullText>(label).is_none());
+    }
+
+    #[test]
+    fn test_content_change() {
+        let mut app = test_app();
+        let label = spawn_label(&mut app.world, "Hello world", 60.);
+        app.update();
+        assert_eq!(value(&app.world, label), "Hello…");
+
+        // Replacing the text discards the truncation, and the new text is measured once it
+        // has been laid out.
+        app.world.get_mut::<Text>(label).unwrap().sections[0].value = "Goodbye world".into();
+        app.update();
+        assert_eq!(value(&app.world, label), "Goodbye world");
+        assert!(app.world.get::<FullText>(label).is_none());
+
+        app.update();
+        assert_eq!(value(&app.world, label), "Goodbye world");
+
+        lay_out(&mut app.world, label);
+        app.update();
+        assert_eq!(value(&app.world, label), "Goodb…");
+
+        // Short text fits without truncation.
+        app.world.get_mut::<Text>(label).unwrap().sections[0].value = "Bye".into();
+        app.update();
+        lay_out(&mut app.world, label);
+        app.update();
+        assert_eq!(value(&app.world, label), "Bye");
+        assert!(app.world.get::<FullText>(label).is_none());
+    }
+
+    #[test]
+    fn test_multiple_sections() {
+        let mut app = test_app();
+        let label = spawn_label(&mut app.world, "Hello", 200.);
+        app.world
+            .get_mut::<Text>(label)
+            .unwrap()
+            .sections
+            .push(TextSection::new(" world", TextStyle::default()));
+        lay_out(&mut app.world, label);
+        app.update();
+        assert_eq!(value(&app.world, label), "Hello world");
+
+        set_width(&mut app.world, label, 90.);
+        app.update();
+        let text = app.world.get::<Text>(label).unwrap();
+        assert_eq!(text.sections[0].value, "Hello");
+        assert_eq!(text.sections[1].value, " wo…");
+
+        set_width(&mut app.world, label, 40.);
+        app.update();
+        let text = app.world.get::<Text>(label).unwrap();
+        assert_eq!(text.sections[0].value, "Hel…");
+        assert_eq!(text.sections[1].value, "");
+
+        // Removing the ellipsis restores the full text.
+        let mut e = app.world.entity_mut(label);
+        update_text_overflow(&mut e, None);
+        assert_eq!(value(&app.world, label), "Hello world");
+        assert!(app.world.get::<TextEllipsis>(label).is_none());
+        assert!(app.world.get::<FullText>(label).is_none());
+    }
+}
@@ -0,0 +1,162 @@
+//! Themes, which give values to the tokens that properties such as
+//! `background_color: token(surface)` refer to.
+
+use std::cell::RefCell;
+
+use bevy::{
+    prelude::*,
+    utils::{HashMap, HashSet},
+};
+use bevy_peacock_style::TokenValue;
+
+use crate::RestyleAll;
+
+/// Resource mapping the names of theme tokens to their values. Unlike values fixed when a
+/// stylesheet is parsed, tokens are resolved whenever styles are computed, so swapping the
+/// theme, for example between light and dark variants, restyles the whole UI. Tokens which
+/// the theme doesn't define, or whose value is of the wrong type for the property, make color
+/// properties magenta, and leave other properties unset.
+#[derive(Resource, Default, Clone, Debug)]
+pub struct Theme {
+    tokens: HashMap<String, TokenValue>,
+}
+
+impl Theme {
+    /// Construct a new, empty theme.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Define the token `name`, returning the theme.
+    pub fn with(mut self, name: impl Into<String>, value: impl Into<TokenValue>) -> Self {
+        self.set(name, value);
+        self
+    }
+
+    /// Define the token `name`, returning its previous value, if any.
+    pub fn set(
+        &mut self,
+        name: impl Into<String>,
+        value: impl Into<TokenValue>,
+    ) -> Option<TokenValue> {
+        self.tokens.insert(name.into(), value.into())
+    }
+
+    /// The value of the token `name`, if it's defined.
+    pub fn get(&self, name: &str) -> Option<TokenValue> {
+        self.tokens.get(name).copied()
+    }
+
+    /// Remove the token `name`, returning its value, if it was defined.
+    pub fn remove(&mut self, name: &str) -> Option<TokenValue> {
+        self.tokens.remove(name)
+    }
+}
+
+/// Names of undefined tokens which have already been reported, so that each is only reported
+/// once.
+#[derive(Default)]
+pub(crate) struct UnknownTokens(RefCell<HashSet<String>>);
+
+impl UnknownTokens {
+    /// Log a warning the first time an undefined token is used.
+    pub(crate) fn report(&self, name: &str) {
+        if !self.0.borrow().contains(name) {
+            self.0.borrow_mut().insert(name.to_owned());
+            warn!(
+                "Unknown theme token '{}'; define it in the Theme resource",
+                name
+            );
+        }
+    }
+}
+
+/// Restyle everything when the theme is inserted, changed or removed.
+pub(crate) fn restyle_changed_theme(
+    theme: Option<Res<Theme>>,
+    mut had_theme: Local<bool>,
+    mut restyle: EventWriter<RestyleAll>,
+) {
+    let changed = match theme {
+        Some(ref theme) => theme.is_changed(),
+        None => *had_theme,
+    };
+    *had_theme = theme.is_some();
+    if changed {
+        restyle.send(RestyleAll);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ElementClasses, ElementStyles, StyleRegistry};
+    use bevy_peacock_style::parse_stylesheet;
+
+    #[test]
+    fn test_theme_tokens() {
+        let mut app = crate::test_utils::plugin_test_app();
+
+        let mut registry = StyleRegistry::default();
+        registry.register_sheet(
+            parse_stylesheet(
+                "PANEL {
+                    background_color: token(surface);
+                    padding: token(gap);
+                    flex_grow: token(grow);
+                    .warning { background_color: token(undefined); }
+                }",
+            )
+            .unwrap(),
+        );
+        let panel = registry.get("PANEL").unwrap();
+        app.world.insert_resource(registry);
+        let entity = app
+            .world
+            .spawn((NodeBundle::default(), ElementStyles::new([panel])))
+            .id();
+        let bg = |app: &App| app.world.get::<BackgroundColor>(entity).unwrap().0;
+        let style = |app: &App| app.world.get::<Style>(entity).unwrap().clone();
+
+        let light = Theme::new()
+            .with("surface", Color::WHITE)
+            .with("gap", Val::Px(4.))
+            .with("grow", 1.);
+        let dark = light.clone().with("surface", Color::BLACK);
+        app.world.insert_resource(light);
+        app.update();
+        assert_eq!(bg(&app), Color::WHITE);
+        assert_eq!(style(&app).padding, UiRect::all(Val::Px(4.)));
+        assert_eq!(style(&app).flex_grow, 1.);
+
+        // The same stylesheet gives different results with a different theme.
+        app.world.insert_resource(dark);
+        app.update();
+        assert_eq!(bg(&app), Color::BLACK);
+        assert_eq!(style(&app).padding, UiRect::all(Val::Px(4.)));
+
+        // Changing a token restyles everything using it.
+        app.world
+            .resource_mut::<Theme>()
+            .set("gap", Val::Percent(2.));
+        app.update();
+        assert_eq!(style(&app).padding, UiRect::all(Val::Percent(2.)));
+
+        // Undefined tokens are magenta, and tokens of the wrong type are ignored.
+        app.world
+            .entity_mut(entity)
+            .insert(ElementClasses::new("warning"));
+        app.update();
+        assert_eq!(bg(&app), Color::FUCHSIA);
+        app.world.resource_mut::<Theme>().set("gap", Color::RED);
+        app.world.entity_mut(entity).remove::<ElementClasses>();
+        app.update();
+        assert_eq!(bg(&app), Color::BLACK);
+        assert_eq!(style(&app).padding, UiRect::all(Val::Percent(2.)));
+
+        // Removing the theme leaves every token undefined.
+        app.world.remove_resource::<Theme>();
+        app.update();
+        assert_eq!(bg(&app), Color::FUCHSIA);
+    }
+}
@@ -0,0 +1,212 @@
+//! Diagnostics recording which styles and selector entries set each of an element's
+//! properties, for tools such as inspector overlays.
+
+use std::fmt;
+
+use bevy::prelude::*;
+use bevy_peacock_style::{Priority, PropSource, StyleProp};
+
+/// Component listing the groups of properties which were applied to an element the last time
+/// its styles were computed, in the order they were applied, so later entries override
+/// earlier ones. It's only recorded while [`PeacockConfig::trace_styles`](crate::PeacockConfig)
+/// is enabled.
+///
+/// This is the effective order: each of the element's styles in turn, and within a style its
+/// unconditional properties, then its matching scopes, then its matching selector entries in
+/// the order they were declared. Selectors have no specificity, so of two matching entries
+/// which set the same property, the one declared last wins. The `!important` properties of all
+/// the styles come after all the normal ones.
+#[derive(Component, Debug, Clone, Default, PartialEq, Eq)]
+pub struct DebugStyleTrace(pub Vec<StyleTraceEntry>);
+
+/// A group of properties applied from one part of a style.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StyleTraceEntry {
+    /// The style the properties came from: its name in the
+    /// [`StyleRegistry`](crate::StyleRegistry), or `#index` of its position in the element's
+    /// [`ElementStyles`](crate::ElementStyles) if it isn't registered. Properties from a user
+    /// override have ` (override)` appended.
+    pub style: String,
+
+    /// The selector entry which matched, such as `:hover`, or `@scope(name)` for a scope
+    /// block. `None` for the style's unconditional properties.
+    pub selector: Option<String>,
+
+    /// The keys of the properties applied, as returned by [`StyleProp::key`], in order.
+    pub props: Vec<&'static str>,
+
+    /// True if the properties were declared `!important`, and so applied in the second pass.
+    pub important: bool,
+}
+
+impl DebugStyleTrace {
+    /// Record a group of properties applied from the style described by `style`.
+    pub(crate) fn record(
+        &mut self,
+        style: &str,
+        source: PropSource,
+        priority: Priority,
+        props: &[StyleProp],
+    ) {
+        self.0.push(StyleTraceEntry {
+            style: style.to_owned(),
+            selector: match source {
+                PropSource::Unconditional => None,
+                PropSource::Scope(scope) => Some(format!("@scope({})", scope)),
+                PropSource::Selector(selector) => Some(selector.to_string()),
+            },
+            props: props.iter().map(StyleProp::key).collect(),
+            important: priority == Priority::Important,
+        });
+    }
+}
+
+/// Writes one line per entry, such as `BUTTON :hover: background_color, width`, with
+/// `!important` after the selector of important properties.
+impl fmt::Display for DebugStyleTrace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for entry in self.0.iter() {
+            write!(f, "{}", entry.style)?;
+            if let Some(ref selector) = entry.selector {
+                write!(f, " {}", selector)?;
+            }
+            if entry.important {
+                write!(f, " !important")?;
+            }
+            writeln!(f, ": {}", entry.props.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{apply_styles_once, set_hovered, test_app};
+    use crate::{ElementClasses, ElementStyles, PeacockConfig, StyleHandle, StyleRegistry};
+    use bevy_peacock_style::parse_stylesheet;
+
+    fn entry(style: &str, selector: Option<&str>, props: &[&'static str]) -> StyleTraceEntry {
+        StyleTraceEntry {
+            style: style.to_owned(),
+            selector: selector.map(str::to_owned),
+            props: props.to_vec(),
+            important: false,
+        }
+    }
+
+    #[test]
+    fn test_style_trace() {
+        let mut app = test_app();
+        let world = &mut app.world;
+
+        let mut registry = StyleRegistry::default();
+        registry.register_sheet(
+            parse_stylesheet(
+                "BUTTON {
+                    background_color: #f00;
+                    size: 10px;
+                    :hover { background_color: #0f0; }
+                    .selected { color: #fff; }
+                }",
+            )
+            .unwrap(),
+        );
+        registry.set_overrides(parse_stylesheet("BUTTON { width: 20px; }").unwrap());
+        let button = registry.get("BUTTON").unwrap();
+        world.insert_resource(registry);
+        let extra = StyleHandle::build(|ss| ss.background_color("#00f"));
+        let entity = world
+            .spawn((
+                NodeBundle::default(),
+                ElementStyles::new(&[button, extra]),
+                ElementClasses::default(),
+            ))
+            .id();
+
+        // Nothing is recorded unless tracing is enabled.
+        apply_styles_once(world);
+        assert!(world.get::<DebugStyleTrace>(entity).is_none());
+
+        world.insert_resource(PeacockConfig {
+            trace_styles: true,
+            ..default()
+        });
+        set_hovered(world, Some(entity));
+        apply_styles_once(world);
+        let trace = world.get::<DebugStyleTrace>(entity).unwrap();
+        assert_eq!(
+            trace.0,
+            vec![
+                entry("BUTTON", None, &["background_color", "width", "height"]),
+                entry("BUTTON", Some(":hover"), &["background_color"]),
+                entry("BUTTON (override)", None, &["width"]),
+                entry("#1", None, &["background_color"]),
+            ]
+        );
+        assert_eq!(
+            trace.to_string(),
+            "BUTTON: background_color, width, height\n\
+            BUTTON :hover: background_color\n\
+            BUTTON (override): width\n\
+            #1: background_color\n"
+        );
+    }
+
+    #[test]
+    fn test_trace_application_order() {
+        let mut app = test_app();
+        let world = &mut app.world;
+
+        let mut registry = StyleRegistry::default();
+        registry.register_sheet(
+            parse_stylesheet(
+                "A {
+                    .c { height: 1px; }
+                    .a { width: 1px; }
+                    .b { width: 2px; color: #fff !important; }
+                }
+                B { height: 3px; width: 4px !important; }",
+            )
+            .unwrap(),
+        );
+        let styles = ElementStyles::new([registry.get("A").unwrap(), registry.get("B").unwrap()]);
+        world.insert_resource(registry);
+        world.insert_resource(PeacockConfig {
+            trace_styles: true,
+            ..default()
+        });
+        let entity = world
+            .spawn((
+                NodeBundle::default(),
+                styles,
+                ElementClasses::new(("b", "a", "c")),
+            ))
+            .id();
+        apply_styles_once(world);
+
+        // Entries are applied in declaration order, whatever the order of the classes, and the
+        // important properties of all the styles come last.
+        let important = |style, selector, props| StyleTraceEntry {
+            important: true,
+            ..entry(style, selector, props)
+        };
+        assert_eq!(
+            world.get::<DebugStyleTrace>(entity).unwrap().0,
+            vec![
+                entry("A", Some(".c"), &["height"]),
+                entry("A", Some(".a"), &["width"]),
+                entry("A", Some(".b"), &["width"]),
+                entry("B", None, &["height"]),
+                important("A", Some(".b"), &["color"]),
+                important("B", None, &["width"]),
+            ]
+        );
+        assert_eq!(
+            world.get::<DebugStyleTrace>(entity).unwrap().to_string(),
+            "A .c: height\nA .a: width\nA .b: width\nB: height\nA .b !important: color\n\
+            B !important: width\n"
+        );
+        assert_eq!(world.get::<Style>(entity).unwrap().width, Val::Px(4.));
+    }
+}
@@ -1,83 +1,691 @@
 use bevy::{
-    a11y::Focus,
+    asset::AssetPath,
+    ecs::{query::WorldQuery, system::SystemParam},
+    log::info_span,
     prelude::*,
     render::texture::{ImageLoaderSettings, ImageSampler},
+    utils::HashSet,
+    window::PrimaryWindow,
 };
 
 use crate::{
-    ElementStyles, SelectorMatcher, {ComputedStyle, UpdateComputedStyle},
+    animate::AnimatedFontSize,
+    asset_checks::AssetLoadChecks,
+    classes::ClassChanges,
+    diagnostics::StyleUpdateCounts,
+    focus::StyledFocus,
+    layout_measures::LayoutMeasures,
+    orphans::{OrphanedStylesConfig, StyleVisits},
+    plugin::{PeacockConfig, SkipHidden},
+    pseudo_classes::PseudoClassChanges,
+    restyle::{ForcedRestyles, RestyleEntity},
+    selector_matcher::logical_size,
+    style_handle::replacement_count,
+    update_computed::{ApplyStyleUpdates, PendingStyleUpdates, StyleUpdate},
+    DebugStyleTrace, ElementStyles, PeacockCacheRegistry, Priority, SelectorMatcher, StyleRegistry,
+    StyleRoot, {ComputedStyle, UpdateComputedStyle, UpdateTextStyle},
 };
+#[cfg(any(test, feature = "test-utils"))]
+use crate::{
+    classes::update_previous_classes, focus::PreviousFocus, pseudo_classes::update_pseudo_classes,
+};
+#[cfg(any(test, feature = "test-utils"))]
+use bevy::ecs::system::RunSystemOnce;
 
 use super::style_handle::TextStyles;
 
+/// The logical size of the primary window when styles were last updated, used to detect
+/// changes which affect media queries.
 #[derive(Resource, Default)]
-pub(crate) struct PreviousFocus(pub(crate) Option<Entity>);
+pub(crate) struct PreviousWindowSize(pub(crate) Option<Vec2>);
+
+/// The font and image most recently loaded for an element, along with the paths they were
+/// loaded from, so that restyling an element whose paths haven't changed reuses the handles
+/// rather than asking the asset server for them again.
+#[derive(Component, Default, Clone)]
+pub(crate) struct CachedAssets {
+    font: Option<(AssetPath<'static>, Handle<Font>)>,
+    image: Option<(AssetPath<'static>, Handle<Image>)>,
+}
+
+/// Set of entities below the root of their tree which have already been warned about setting
+/// `ui_scale`, so that each is only reported once.
+#[derive(Resource, Default)]
+pub(crate) struct UiScaleWarnings(pub(crate) HashSet<Entity>);
+
+/// Forget the `ui_scale` warnings about entities which were despawned or lost their styles, so
+/// that the set of reported entities doesn't grow without bound.
+pub(crate) fn forget_ui_scale_warnings(
+    mut removed: RemovedComponents<ElementStyles>,
+    warnings: Option<ResMut<UiScaleWarnings>>,
+) {
+    let Some(mut warnings) = warnings else {
+        removed.clear();
+        return;
+    };
+    for entity in removed.read() {
+        warnings.0.remove(&entity);
+    }
+}
+
+#[cfg(test)]
+thread_local! {
+    /// Number of assets loaded by the style pass on this thread.
+    static ASSET_LOADS: std::cell::Cell<usize> = const { std::cell::Cell::new(0) };
+}
+
+/// Return the handle for the asset at `path`, loading it only if `cached` holds a different
+/// path. Returns whether `cached` was changed.
+fn load_cached<A: Asset>(
+    cached: &mut Option<(AssetPath<'static>, Handle<A>)>,
+    path: Option<&AssetPath<'static>>,
+    load: impl FnOnce(&AssetPath<'static>) -> Handle<A>,
+) -> (Option<Handle<A>>, bool) {
+    match (cached.as_ref(), path) {
+        (_, None) => (None, cached.take().is_some()),
+        (Some((cached_path, handle)), Some(path)) if cached_path == path => {
+            (Some(handle.clone()), false)
+        }
+        (_, Some(path)) => {
+            #[cfg(test)]
+            ASSET_LOADS.with(|loads| loads.set(loads.get() + 1));
+            let handle = load(path);
+            *cached = Some((path.clone(), handle.clone()));
+            (Some(handle), true)
+        }
+    }
+}
+
+/// Marks a hidden element whose descendants were skipped by the style pass, so that they're
+/// all restyled once it's visible again.
+#[derive(Component)]
+pub(crate) struct SkippedSubtree;
+
+/// The size of the content box of an element's parent node, which `calc()` lengths and layout
+/// transitions are resolved against, and whether it changed since styles were last updated.
+#[derive(Clone, Copy, Default)]
+struct ParentSize {
+    size: Vec2,
+    changed: bool,
+}
+
+/// The parts of a UI node which the style pass reads.
+#[derive(WorldQuery)]
+pub(crate) struct StyledNode {
+    style: Ref<'static, Style>,
+    element_styles: Option<Ref<'static, ElementStyles>>,
+    /// The text styles which were cached for the node by the previous pass, if any.
+    text_styles: Option<&'static TextStyles>,
+    text: Option<Ref<'static, Text>>,
+    has_parent: Has<Parent>,
+    cached_assets: Option<&'static CachedAssets>,
+    style_root: Option<Ref<'static, StyleRoot>>,
+}
+
+/// The queries which the style pass uses to find the UI trees and walk them.
+#[derive(SystemParam)]
+pub(crate) struct StyleTrees<'w, 's> {
+    roots: Query<'w, 's, Entity, (With<Node>, Without<Parent>)>,
+    off_tree: Query<'w, 's, (Entity, &'static Parent), With<Node>>,
+    nodes: Query<'w, 's, StyledNode, With<Node>>,
+    children: Query<'w, 's, &'static Children, (With<Node>, With<Visibility>)>,
+    visibility: Query<'w, 's, NodeVisibility, With<Node>>,
+}
+
+/// Whether a UI node is hidden, and whether the style pass skipped its descendants.
+#[derive(WorldQuery)]
+pub(crate) struct NodeVisibility {
+    visibility: &'static Visibility,
+    view_visibility: Option<&'static ViewVisibility>,
+    skipped: Has<SkippedSubtree>,
+}
+
+/// The resources which the style pass is configured by, and records its results in.
+#[derive(SystemParam)]
+pub(crate) struct StylePassResources<'w, 's> {
+    assets: Res<'w, AssetServer>,
+    registry: Res<'w, StyleRegistry>,
+    pending: ResMut<'w, PendingStyleUpdates>,
+    config: Option<Res<'w, PeacockConfig>>,
+    orphans: Option<Res<'w, OrphanedStylesConfig>>,
+    visits: Option<ResMut<'w, StyleVisits>>,
+    forced: Option<ResMut<'w, ForcedRestyles>>,
+    total_counts: Option<ResMut<'w, StyleUpdateCounts>>,
+    styled_focus: Option<ResMut<'w, StyledFocus>>,
+    cache_registry: Option<ResMut<'w, PeacockCacheRegistry>>,
+    load_checks: Option<ResMut<'w, AssetLoadChecks>>,
+    class_changes: ClassChanges<'w, 's>,
+    pseudo_class_changes: PseudoClassChanges<'w, 's>,
+    /// The style replacement count seen by the previous pass.
+    replacements: Local<'s, u32>,
+}
+
+/// The state shared by every element which a style pass visits.
+struct StylePassContext<'a> {
+    commands: Commands<'a, 'a>,
+    pending: &'a mut PendingStyleUpdates,
+    nodes: &'a Query<'a, 'a, StyledNode, With<Node>>,
+    matcher: &'a SelectorMatcher<'a, 'a>,
+    assets: &'a AssetServer,
+    registry: &'a StyleRegistry,
+    load_checks: Option<&'a mut AssetLoadChecks>,
+    fallback_font: Option<&'a Handle<Font>>,
+    /// The restyles requested since the last pass.
+    forced: ForcedRestyles,
+    /// Whether the contents of any style were replaced since the last pass.
+    styles_replaced: bool,
+    trace_styles: bool,
+    /// The size of the window, which viewport units are relative to.
+    viewport: Vec2,
+    counts: StyleUpdateCounts,
+}
+
+/// An element which the style pass has yet to visit, with the state it inherits from its
+/// parent.
+struct NodeVisit {
+    entity: Entity,
+    inherited_styles: TextStyles,
+    inherited_styles_changed: bool,
+    parent_size: ParentSize,
+    /// Whether an ancestor's subtree is being forcibly restyled.
+    subtree: bool,
+    /// The nearest ancestor with a [`StyleRoot`].
+    style_root: Option<Entity>,
+}
+
+impl NodeVisit {
+    /// Visit the root of a tree, whose parent is the window.
+    fn root(entity: Entity, window_size: ParentSize, subtree: bool) -> Self {
+        Self {
+            entity,
+            inherited_styles: TextStyles::default(),
+            inherited_styles_changed: false,
+            parent_size: window_size,
+            subtree,
+            style_root: None,
+        }
+    }
+}
+
+/// The result of styling an element: the text styles its children inherit, whether they
+/// changed, and whether the UI scale changed, so that all of its descendants need restyling.
+struct StyledElement {
+    text_styles: TextStyles,
+    text_styles_changed: bool,
+    rescaled: bool,
+}
 
-#[allow(clippy::too_many_arguments)]
-#[allow(clippy::type_complexity)]
 pub(crate) fn update_styles(
-    mut commands: Commands,
-    query_root: Query<Entity, (With<Node>, Without<Parent>)>,
-    query_styles: Query<
-        (
-            Ref<Style>,
-            Option<Ref<ElementStyles>>,
-            Option<&TextStyles>,
-            Option<Ref<Text>>,
-        ),
-        With<Node>,
-    >,
-    matcher: SelectorMatcher<'_, '_>,
-    query_children: Query<&'static Children, (With<Node>, With<Visibility>)>,
-    assets: Res<AssetServer>,
+    commands: Commands,
+    trees: StyleTrees,
+    matcher: SelectorMatcher,
+    measures: LayoutMeasures,
+    res: StylePassResources,
 ) {
-    for root_node in &query_root {
-        update_element_styles(
-            &mut commands,
-            &query_styles,
-            &query_children,
-            &matcher,
-            &assets,
-            root_node,
-            &TextStyles::default(),
-            false,
-        )
+    let StylePassResources {
+        assets,
+        registry,
+        mut pending,
+        config,
+        orphans,
+        visits,
+        forced,
+        total_counts,
+        styled_focus,
+        cache_registry,
+        mut load_checks,
+        mut class_changes,
+        mut pseudo_class_changes,
+        mut replacements,
+    } = res;
+
+    pending.start_pass();
+    matcher.clear_cache();
+    if let Some(cache_registry) = &cache_registry {
+        matcher.set_cache_budget(cache_registry);
+    }
+    let skip_hidden = config
+        .as_ref()
+        .map_or(SkipHidden::Never, |config| config.skip_hidden);
+
+    // Root nodes are sized relative to the window.
+    let viewport = measures.viewport_size().unwrap_or_default();
+    let window_size = ParentSize {
+        size: viewport,
+        changed: matcher.window_resized(),
+    };
+
+    // Only look for styles whose contents were replaced if any have been since the last pass.
+    // The count is read once, so that a replacement made meanwhile isn't missed.
+    let count = replacement_count();
+    let styles_replaced = count != *replacements;
+    *replacements = count;
+
+    // Take the restyles requested since the last pass, so that each only applies once.
+    let forced = match forced {
+        Some(mut forced) if !forced.is_empty() => std::mem::take(forced.as_mut()),
+        _ => ForcedRestyles::default(),
+    };
+
+    let mut ctx = StylePassContext {
+        commands,
+        pending: &mut pending,
+        nodes: &trees.nodes,
+        matcher: &matcher,
+        assets: &assets,
+        registry: &registry,
+        load_checks: load_checks.as_deref_mut(),
+        fallback_font: config
+            .as_ref()
+            .and_then(|config| config.fallback_font.as_ref()),
+        styles_replaced,
+        trace_styles: config.as_ref().is_some_and(|config| config.trace_styles),
+        viewport,
+        counts: StyleUpdateCounts::default(),
+        forced,
+    };
+
+    // Traverse each tree depth-first using an explicit stack rather than recursion, since UI
+    // trees can be very deep.
+    let mut roots: Vec<NodeVisit> = trees
+        .roots
+        .iter()
+        .map(|root_node| NodeVisit::root(root_node, window_size, ctx.forced.all))
+        .collect();
+
+    // Optionally, treat the top node of each subtree which isn't connected to a root as a root
+    // too. Its parent is either missing or not a UI node, so it can't be reached otherwise.
+    if orphans.as_ref().is_some_and(|config| config.style_as_roots) {
+        roots.extend(
+            trees
+                .off_tree
+                .iter()
+                .filter(|(_, parent)| !trees.children.contains(parent.get()))
+                .map(|(entity, _)| NodeVisit::root(entity, window_size, ctx.forced.all)),
+        );
+    }
+
+    // Record which entities are visited, so that orphaned ones can be reported.
+    let mut visits =
+        visits.filter(|_| orphans.is_some_and(|config| config.check_interval.is_some()));
+    if let Some(ref mut visits) = visits {
+        visits.begin_pass();
+    }
+
+    let mut stack = Vec::new();
+    for root in roots {
+        let _span = info_span!("style_tree", root = ?root.entity).entered();
+        stack.push(root);
+        while let Some(mut node) = stack.pop() {
+            let entity = node.entity;
+            node.subtree = node.subtree || ctx.forced.subtrees.contains(&entity);
+            if let Ok(styled_node) = trees.nodes.get(entity) {
+                if styled_node.style_root.is_some() {
+                    node.style_root = Some(entity);
+                }
+                ctx.counts.entities_visited += 1;
+                if let Some(ref mut visits) = visits {
+                    visits.visit(entity);
+                }
+            }
+            let styled = update_element_styles(&mut ctx, &node);
+
+            // Skip the descendants of hidden elements, and restyle all of them once the element
+            // is revealed, since anything could have changed while they were skipped.
+            let (hidden, skipped) = match trees.visibility.get(entity) {
+                Ok(shown) => (
+                    match skip_hidden {
+                        SkipHidden::Never => false,
+                        SkipHidden::Hidden => *shown.visibility == Visibility::Hidden,
+                        SkipHidden::NotVisible => shown.view_visibility.is_some_and(|v| !v.get()),
+                    },
+                    shown.skipped,
+                ),
+                Err(_) => (false, false),
+            };
+            if hidden {
+                if !skipped {
+                    ctx.commands.entity(entity).insert(SkippedSubtree);
+                }
+                // Skipped descendants aren't orphaned.
+                if let Some(ref mut visits) = visits {
+                    let mut descendants = vec![entity];
+                    while let Some(descendant) = descendants.pop() {
+                        if let Ok(children) = trees.children.get(descendant) {
+                            descendants.extend(children.iter().copied());
+                            children.iter().for_each(|child| visits.visit(*child));
+                        }
+                    }
+                }
+                continue;
+            }
+            let subtree = if skipped {
+                ctx.commands.entity(entity).remove::<SkippedSubtree>();
+                true
+            } else {
+                node.subtree || styled.rescaled
+            };
+
+            if let Ok(children) = trees.children.get(entity) {
+                let size = ParentSize {
+                    size: measures
+                        .content_size_of(entity, node.parent_size.size.x)
+                        .unwrap_or_default(),
+                    changed: measures.size_changed(entity),
+                };
+                // Push in reverse so that children are visited in order.
+                stack.extend(children.iter().rev().map(|child| NodeVisit {
+                    entity: *child,
+                    inherited_styles: styled.text_styles.clone(),
+                    inherited_styles_changed: styled.text_styles_changed,
+                    parent_size: size,
+                    subtree,
+                    style_root: node.style_root,
+                }));
+            }
+        }
+    }
+    let StylePassContext {
+        mut commands,
+        pending,
+        mut counts,
+        ..
+    } = ctx;
+
+    // Record the focus, classes and pseudo-class markers which were matched against, to compare
+    // with on the next pass.
+    if let Some(mut styled_focus) = styled_focus {
+        styled_focus.set_if_neq(StyledFocus(matcher.focus()));
+    }
+    class_changes.record();
+    pseudo_class_changes.record();
+
+    counts.selector_evaluations = matcher.take_evaluations();
+    if let Some(mut cache_registry) = cache_registry {
+        matcher.report_cache(&mut cache_registry);
+    }
+    if let Some(mut total_counts) = total_counts {
+        *total_counts += counts;
+    }
+
+    // Apply all of the computed styles in a single command, after any other commands issued
+    // this frame, so that an entity which is computed more than once is only updated once.
+    if !pending.is_empty() {
+        commands.add(ApplyStyleUpdates);
+    }
+}
+
+/// Apply the cached text styles of text nodes whose sections have changed since the last run,
+/// for example sections which were cleared and repopulated. This is cheaper than a full
+/// restyle, and ensures that new sections are styled in the same frame they appear. Sections
+/// whose font size is being animated get the current size of the animation.
+pub(crate) fn update_text_sections(
+    mut query: Query<(&TextStyles, &mut Text, Option<&AnimatedFontSize>), Changed<Text>>,
+) {
+    for (text_styles, mut text, animated_size) in query.iter_mut() {
+        // White is the default.
+        let color = text_styles.color.unwrap_or(Color::WHITE);
+        let font_size = animated_size
+            .map(|anim| anim.0.value())
+            .or(text_styles.font_size);
+        let stale = text.sections.iter().any(|section| {
+            section.style.color != color
+                || font_size.is_some_and(|size| section.style.font_size != size)
+                || text_styles
+                    .font
+                    .as_ref()
+                    .is_some_and(|font| section.style.font != *font)
+        });
+        if !stale {
+            continue;
+        }
+        for section in text.sections.iter_mut() {
+            section.style.color = color;
+            if let Some(font_size) = font_size {
+                section.style.font_size = font_size;
+            }
+            if let Some(ref font) = text_styles.font {
+                section.style.font = font.clone();
+            }
+        }
+    }
+}
+
+/// Restyle nodes which gained or lost a [`Text`] component. Text nodes always keep a
+/// [`TextStyles`] cache, while other nodes only keep one if their text styles differ from their
+/// parent's, so the cache has to be inserted or removed when the component comes or goes.
+pub(crate) fn restyle_changed_text(
+    added: Query<Entity, (Added<Text>, With<Node>)>,
+    mut removed: RemovedComponents<Text>,
+    mut restyle: EventWriter<RestyleEntity>,
+) {
+    restyle.send_batch(added.iter().chain(removed.read()).map(RestyleEntity));
+}
+
+pub(crate) fn update_window_size(
+    windows: Query<&Window, With<PrimaryWindow>>,
+    mut window_size_prev: ResMut<PreviousWindowSize>,
+) {
+    let size = windows.get_single().ok().map(logical_size);
+    if window_size_prev.0 != size {
+        window_size_prev.0 = size;
     }
 }
 
-pub(crate) fn update_focus(focus: Res<Focus>, mut focus_prev: ResMut<PreviousFocus>) {
-    focus_prev.0 = focus.0;
+/// Cached instance of the [`update_styles`] system, so that the style pass can be run from an
+/// exclusive context while retaining change detection state between runs.
+#[cfg(any(test, feature = "test-utils"))]
+#[derive(Resource)]
+pub(crate) struct StylePass(Box<dyn System<In = (), Out = ()>>);
+
+/// Run the style pass directly against the world, outside of a schedule. The focus, classes and
+/// pseudo-class markers seen by the previous run are recorded as the previous ones first, as
+/// [`update_focus`](crate::focus::update_focus),
+/// [`update_previous_classes`](crate::classes::update_previous_classes) and
+/// [`update_pseudo_classes`](crate::pseudo_classes::update_pseudo_classes) would. The pass then
+/// runs, any commands it issued are flushed, and the current window size is recorded as the
+/// previous one, just as [`update_window_size`] would.
+#[cfg(any(test, feature = "test-utils"))]
+pub(crate) fn update_styles_exclusive(world: &mut World) {
+    let styled = world.resource::<StyledFocus>().0;
+    world.resource_mut::<PreviousFocus>().0 = styled;
+    world.run_system_once(update_previous_classes);
+    world.run_system_once(update_pseudo_classes);
+
+    let mut pass = match world.remove_resource::<StylePass>() {
+        Some(pass) => pass,
+        None => {
+            let mut system = IntoSystem::into_system(update_styles);
+            system.initialize(world);
+            StylePass(Box::new(system))
+        }
+    };
+    pass.0.run((), world);
+    pass.0.apply_deferred(world);
+    world.insert_resource(pass);
+
+    let size = world
+        .query_filtered::<&Window, With<PrimaryWindow>>()
+        .get_single(world)
+        .ok()
+        .map(logical_size);
+    world.resource_mut::<PreviousWindowSize>().0 = size;
+
+    #[cfg(feature = "bevy_mod_picking")]
+    world.resource_mut::<crate::drag::DragStates>().save();
 }
 
+/// Compute the styles of `entity`, as the style pass does: the tree's default styles
+/// (`root_styles`) come first, so that the element's own styles take precedence, and each style
+/// is followed by its user override. The `!important` properties of all the styles are applied
+/// after the normal ones, so that they win wherever they come in the order. Returns the
+/// computed style, and, if `trace` is set, where each of its properties came from.
 #[allow(clippy::too_many_arguments)]
-#[allow(clippy::type_complexity)]
-fn update_element_styles(
-    commands: &mut Commands,
-    query_styles: &Query<
-        (
-            Ref<Style>,
-            Option<Ref<ElementStyles>>,
-            Option<&TextStyles>,
-            Option<Ref<Text>>,
-        ),
-        With<Node>,
-    >,
-    children_query: &Query<'_, '_, &Children, (With<Node>, With<Visibility>)>,
-    matcher: &SelectorMatcher<'_, '_>,
-    assets: &Res<AssetServer>,
+pub(crate) fn compute_element_style(
+    matcher: &SelectorMatcher,
+    registry: &StyleRegistry,
     entity: Entity,
+    root_styles: Option<&ElementStyles>,
+    elt_styles: Option<&ElementStyles>,
+    style: &Style,
     inherited_styles: &TextStyles,
-    mut inherited_styles_changed: bool,
-) {
+    px_scale: Option<f32>,
+    parent_size: Vec2,
+    trace: bool,
+) -> (ComputedStyle, Option<DebugStyleTrace>) {
+    // Only the fields of `Style` which the styles set are written to the element, so there's
+    // no need to clone its current `Style`, apart from the grid placements, which properties
+    // such as `grid_row_start` update in part.
+    let mut computed = ComputedStyle::new();
+    computed.style.grid_row = style.grid_row;
+    computed.style.grid_column = style.grid_column;
+    computed.px_scale = px_scale;
+
+    // Inherited properties
+    computed.font_handle = inherited_styles.font.clone();
+    computed.font_size = inherited_styles.font_size;
+    computed.color = inherited_styles.color;
+    computed.inherited_color = inherited_styles.color;
+    computed.inherited_font_size = inherited_styles.font_size;
+
+    // Disabled styles keep their place in the order, so that traces name them by it.
+    let root_handles = root_styles.map_or(&[][..], |rs| &rs.styles[..]);
+    let elt_handles = elt_styles.map_or(&[][..], |es| &es.styles[..]);
+    let is_enabled = |index: usize| match index.checked_sub(root_handles.len()) {
+        None => root_styles.unwrap().is_enabled_at(index),
+        Some(index) => elt_styles.unwrap().is_enabled_at(index),
+    };
+
+    let mut trace = trace.then(DebugStyleTrace::default);
+    for priority in [Priority::Normal, Priority::Important] {
+        for (index, ss) in root_handles.iter().chain(elt_handles).enumerate() {
+            if !is_enabled(index) {
+                continue;
+            }
+            let Some(ref mut trace) = trace else {
+                ss.props()
+                    .apply_pass(&mut computed, matcher, &entity, priority);
+                if let Some(ovr) = registry.override_for(ss) {
+                    ovr.props()
+                        .apply_pass(&mut computed, matcher, &entity, priority);
+                }
+                continue;
+            };
+            let name = match registry.name_of(ss) {
+                Some(name) => name.to_owned(),
+                None if index < root_handles.len() => format!("root #{}", index),
+                None => format!("#{}", index - root_handles.len()),
+            };
+            ss.props().apply_pass_traced(
+                &mut computed,
+                matcher,
+                &entity,
+                priority,
+                |source, props| trace.record(&name, source, priority, props),
+            );
+            if let Some(ovr) = registry.override_for(ss) {
+                let name = format!("{} (override)", name);
+                ovr.props().apply_pass_traced(
+                    &mut computed,
+                    matcher,
+                    &entity,
+                    priority,
+                    |source, props| trace.record(&name, source, priority, props),
+                );
+            }
+        }
+    }
+    computed.resolve_calc(parent_size);
+    (computed, trace)
+}
+
+/// Update the styles of a single element, returning what its children inherit from it.
+fn update_element_styles(ctx: &mut StylePassContext, node: &NodeVisit) -> StyledElement {
+    let NodeVisit {
+        entity,
+        ref inherited_styles,
+        mut inherited_styles_changed,
+        parent_size,
+        style_root,
+        ..
+    } = *node;
+    let forced = node.subtree || ctx.forced.entities.contains(&entity);
+    let (matcher, registry) = (ctx.matcher, ctx.registry);
     let mut text_styles = inherited_styles.clone();
+    let mut rescaled = false;
 
-    if let Ok((style, elt_styles, prev_text_styles, txt)) = query_styles.get(entity) {
-        // Check if the element styles or ancestor classes have changed.
-        let mut changed = match elt_styles {
-            Some(ref element_style) => matcher.is_changed(entity, element_style),
-            None => false,
-        };
+    if let Ok(StyledNodeItem {
+        style,
+        element_styles: elt_styles,
+        text_styles: prev_text_styles,
+        text: txt,
+        has_parent,
+        cached_assets: prev_assets,
+        ..
+    }) = ctx.nodes.get(entity)
+    {
+        // The default styles of the tree apply to the style root itself, and to each element
+        // which has styles of its own.
+        let root_styles = style_root
+            .filter(|root| *root == entity || elt_styles.is_some())
+            .and_then(|root| ctx.nodes.get(root).ok())
+            .and_then(|root| root.style_root)
+            .map(|root| Ref::map(root, |root| &root.styles));
+        let styled = elt_styles.is_some() || root_styles.is_some();
+
+        // Check if a restyle was requested, or the element styles or ancestor classes have
+        // changed.
+        let mut changed = forced
+            || match elt_styles {
+                Some(ref element_style) => matcher.is_changed(entity, element_style),
+                None => false,
+            }
+            || root_styles
+                .as_ref()
+                .is_some_and(|root_styles| matcher.is_changed(entity, root_styles));
+
+        // Elements using a style whose contents were replaced are restyled, and their cached
+        // selector properties recomputed.
+        if ctx.styles_replaced && elt_styles.as_ref().is_some_and(|es| es.is_stale()) {
+            changed = true;
+            ctx.commands
+                .entity(entity)
+                .add(|mut entity: EntityWorldMut| {
+                    if let Some(mut element_styles) = entity.get_mut::<ElementStyles>() {
+                        element_styles.bypass_change_detection().refresh();
+                    }
+                });
+        }
+        if ctx.styles_replaced && root_styles.as_ref().is_some_and(|rs| rs.is_stale()) {
+            changed = true;
+            ctx.commands
+                .entity(style_root.unwrap())
+                .add(|mut entity: EntityWorldMut| {
+                    if let Some(mut root) = entity.get_mut::<StyleRoot>() {
+                        root.bypass_change_detection().styles.refresh();
+                    }
+                });
+        }
 
+        // Elements using `calc()` lengths are restyled when their parent is resized. Overrides
+        // may introduce `calc()` lengths into styles which don't otherwise use them.
+        if !changed && parent_size.changed {
+            for element_styles in elt_styles.iter().chain(root_styles.iter()) {
+                changed = changed
+                    || element_styles.uses_calc
+                    || element_styles
+                        .styles
+                        .iter()
+                        .any(|ss| registry.override_for(ss).is_some_and(|ovr| ovr.uses_calc()));
+            }
+        }
+
+        // Whether anything besides the text and the inherited text styles changed. If not, the
+        // element's styles are still recomputed, since they may inherit the text styles, but
+        // only its text is updated, so that its `Style` isn't rewritten and relaid out.
+        let style_changed = changed;
+
+        // Any change to the text, including sections being added or removed, restyles it.
         if let Some(ref text_node) = txt {
             if text_node.is_changed() {
                 changed = true;
@@ -85,23 +693,120 @@ fn update_element_styles(
         }
 
         if changed || inherited_styles_changed {
-            // Compute computed style. Initialize to the current state.
-            let mut computed = ComputedStyle::new();
-            computed.style = style.clone();
-
-            // Inherited properties
-            computed.font_handle = inherited_styles.font.clone();
-            computed.font_size = inherited_styles.font_size;
-            computed.color = inherited_styles.color;
-
-            // Apply element styles to computed
-            if let Some(ref element_styles) = elt_styles {
-                for ss in element_styles.styles.iter() {
-                    ss.apply_to(&mut computed, matcher, &entity);
+            ctx.counts.entities_recomputed += 1;
+            let compute_span = info_span!("compute_style", ?entity).entered();
+
+            let compute = |px_scale: Option<f32>| {
+                compute_element_style(
+                    matcher,
+                    registry,
+                    entity,
+                    root_styles.as_deref(),
+                    elt_styles.as_deref(),
+                    &style,
+                    inherited_styles,
+                    px_scale,
+                    parent_size.size,
+                    ctx.trace_styles,
+                )
+            };
+
+            // The UI scale is set by the root node, and applies to the whole tree. The root's
+            // own scale isn't known until its styles have been applied, so guess that it's
+            // unchanged, and apply the styles again if not.
+            let (mut computed, mut trace);
+            if has_parent {
+                (computed, trace) = compute(inherited_styles.ui_scale);
+                if computed.ui_scale.is_some() {
+                    ctx.commands.add(move |world: &mut World| {
+                        if world
+                            .get_resource_or_insert_with(UiScaleWarnings::default)
+                            .0
+                            .insert(entity)
+                        {
+                            warn!(
+                                "ui_scale is only valid on root nodes, ignoring it on {:?}",
+                                entity
+                            );
+                        }
+                    });
+                }
+                text_styles.ui_scale = inherited_styles.ui_scale;
+            } else {
+                let prev_scale = prev_text_styles.and_then(|ts| ts.ui_scale);
+                (computed, trace) = compute(prev_scale);
+                if computed.ui_scale != prev_scale {
+                    (computed, trace) = compute(computed.ui_scale);
+                    // The scale applies to the lengths of the whole tree, not just its text.
+                    rescaled = true;
                 }
-                // Load font asset if non-null.
-                if let Some(ref font_path) = computed.font {
-                    computed.font_handle = Some(assets.load(font_path));
+                text_styles.ui_scale = computed.ui_scale;
+            }
+
+            if let (Some(trace), true) = (trace, styled) {
+                ctx.commands.entity(entity).insert(trace);
+            }
+            drop(compute_span);
+
+            // Load the font and image, unless they were already loaded from the same paths.
+            if styled {
+                let _span = info_span!("load_style_assets", ?entity).entered();
+                let mut cache = prev_assets.cloned().unwrap_or_default();
+                // A font with the same path as the inherited one is the same asset, so reuse
+                // its handle, letting the element's text styles match its parent's.
+                let inherited_font = inherited_styles
+                    .loaded_font
+                    .as_ref()
+                    .filter(|(path, _)| computed.font.as_ref() == Some(path));
+                let (font, font_changed) = match inherited_font {
+                    Some((path, handle)) => {
+                        let changed = cache.font.as_ref().map(|(_, h)| h) != Some(handle);
+                        cache.font = Some((path.clone(), handle.clone()));
+                        (Some(handle.clone()), changed)
+                    }
+                    None => load_cached(&mut cache.font, computed.font.as_ref(), |path| {
+                        ctx.assets.load(path)
+                    }),
+                };
+                if let Some(ref loaded) = cache.font {
+                    text_styles.loaded_font = Some(loaded.clone());
+                }
+                if let (Some(checks), Some(path), Some(handle)) = (
+                    ctx.load_checks.as_deref_mut(),
+                    computed.font.as_ref(),
+                    font.as_ref(),
+                ) {
+                    // Fonts which failed to load are replaced by the fallback, if any.
+                    match ctx.fallback_font {
+                        Some(fallback) if checks.has_failed(path) => {
+                            computed.font_handle = Some(fallback.clone());
+                        }
+                        _ => computed.font_handle = font.clone(),
+                    }
+                    if font_changed {
+                        checks.watch(entity, path, handle.id(), true);
+                    }
+                } else if font.is_some() {
+                    computed.font_handle = font;
+                }
+                let (image, image_changed) =
+                    load_cached(&mut cache.image, computed.image.as_ref(), |path| {
+                        ctx.assets
+                            .load_with_settings(path, |s: &mut ImageLoaderSettings| {
+                                s.sampler = ImageSampler::linear()
+                            })
+                    });
+                if let (Some(checks), Some(path), Some(handle), true) = (
+                    ctx.load_checks.as_deref_mut(),
+                    computed.image.as_ref(),
+                    image.as_ref(),
+                    image_changed,
+                ) {
+                    checks.watch(entity, path, handle.id(), false);
+                }
+                computed.image_handle = image;
+                if font_changed || image_changed {
+                    ctx.commands.entity(entity).insert(cache);
                 }
             }
 
@@ -113,29 +818,48 @@ fn update_element_styles(
             if text_styles == *inherited_styles && txt.is_none() {
                 // No change from parent, so we can remove the cached styles and rely on inherited
                 // styles only. Note that for text nodes, we always want to store the inherited
-                // styles, even if they are the same as the parent.
-                inherited_styles_changed = prev_text_styles.is_some();
-                if inherited_styles_changed {
+                // styles, even if they are the same as the parent. The children inherit the same
+                // styles as this element, so they changed if its own did.
+                if prev_text_styles.is_some() {
+                    inherited_styles_changed = true;
                     changed = true;
-                    commands.entity(entity).remove::<TextStyles>();
+                    ctx.commands.entity(entity).remove::<TextStyles>();
                 }
             } else {
                 // Text styles are different from parent, so we need to store a cached copy.
                 inherited_styles_changed = prev_text_styles != Some(&text_styles);
                 if inherited_styles_changed {
                     changed = true;
-                    commands.entity(entity).insert(text_styles.clone());
+                    ctx.commands.entity(entity).insert(text_styles.clone());
                 }
             }
 
             if changed {
-                computed.image_handle = computed.image.as_ref().map(|path| {
-                    assets.load_with_settings(path, |s: &mut ImageLoaderSettings| {
-                        s.sampler = ImageSampler::linear()
-                    })
-                });
-
-                commands.add(UpdateComputedStyle { entity, computed });
+                if styled {
+                    ctx.pending.push(
+                        entity,
+                        StyleUpdate::Computed(UpdateComputedStyle {
+                            entity,
+                            computed,
+                            parent_size: parent_size.size,
+                            viewport: ctx.viewport,
+                            text_only: !style_changed,
+                            #[cfg(any(test, feature = "diagnostics"))]
+                            styles: root_styles
+                                .iter()
+                                .chain(elt_styles.iter())
+                                .flat_map(|es| es.styles.iter().cloned())
+                                .collect(),
+                        }),
+                    );
+                } else {
+                    // Element has no styles of its own, so only the inherited text styles
+                    // apply. Don't touch background, border, image, etc.
+                    ctx.pending.push(
+                        entity,
+                        StyleUpdate::Text(UpdateTextStyle { entity, computed }),
+                    );
+                }
             }
         } else if let Some(prev) = prev_text_styles {
             // Styles didn't change, but we need to pass inherited text styles to children.
@@ -143,20 +867,1009 @@ fn update_element_styles(
         }
     }
 
-    if let Ok(children) = children_query.get(entity) {
-        for child in children.iter() {
-            update_element_styles(
-                commands,
-                query_styles,
-                // classes_query,
-                // parent_query,
-                children_query,
-                matcher,
-                assets,
-                *child,
-                &text_styles,
-                inherited_styles_changed,
-            );
+    StyledElement {
+        text_styles,
+        text_styles_changed: inherited_styles_changed,
+        rescaled,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::selector_matcher::tests::SELECTOR_EVALUATIONS;
+    use crate::test_utils::{apply_styles_once, set_window_size, test_app};
+    use crate::{ElementClasses, StyleHandle};
+    use bevy::ecs::schedule::ExecutorKind;
+    use bevy::ecs::system::RunSystemOnce;
+
+    fn spawn_text(world: &mut World, styles: Option<ElementStyles>) -> Entity {
+        let mut e = world.spawn((
+            Node::default(),
+            Style::default(),
+            Text::from_section("Hello", TextStyle::default()),
+            Visibility::default(),
+            Transform::default(),
+            GlobalTransform::default(),
+        ));
+        if let Some(styles) = styles {
+            e.insert(styles);
+        }
+        e.id()
+    }
+
+    #[test]
+    fn test_text_children_do_not_inherit_background() {
+        let mut app = test_app();
+        let world = &mut app.world;
+
+        let parent_style = StyleHandle::build(|ss| {
+            ss.background_color("#ff0000")
+                .color("#00ff00")
+                .background_image(Some("image.png".into()))
+        });
+        let child_style = StyleHandle::build(|ss| ss.background_color("#0000ff"));
+
+        let plain = spawn_text(world, None);
+        let styled = spawn_text(world, Some(ElementStyles::new(&[child_style])));
+        let parent = world
+            .spawn((NodeBundle::default(), ElementStyles::new(&[parent_style])))
+            .push_children(&[plain, styled])
+            .id();
+
+        world.run_system_once(update_styles);
+
+        let red = Color::hex("#ff0000").unwrap();
+        let green = Color::hex("#00ff00").unwrap();
+        let blue = Color::hex("#0000ff").unwrap();
+
+        assert_eq!(world.get::<BackgroundColor>(parent).unwrap().0, red);
+        assert!(world.get::<UiImage>(parent).is_some());
+
+        // Unstyled text child only picks up inherited text styles.
+        assert!(world.get::<BackgroundColor>(plain).is_none());
+        assert!(world.get::<BorderColor>(plain).is_none());
+        assert!(world.get::<UiImage>(plain).is_none());
+        assert_eq!(
+            world.get::<Text>(plain).unwrap().sections[0].style.color,
+            green
+        );
+
+        // Styled text child gets its own background, but not the parent's image.
+        assert_eq!(world.get::<BackgroundColor>(styled).unwrap().0, blue);
+        assert!(world.get::<UiImage>(styled).is_none());
+        assert_eq!(
+            world.get::<Text>(styled).unwrap().sections[0].style.color,
+            green
+        );
+    }
+
+    #[test]
+    fn test_cached_asset_handles() {
+        let mut app = test_app();
+        let world = &mut app.world;
+        let image_style = |path: &'static str| {
+            StyleHandle::build(move |ss| ss.background_image(Some(path.into())))
+        };
+        let node = world
+            .spawn((
+                NodeBundle::default(),
+                ElementStyles::new(&[image_style("image.png")]),
+            ))
+            .id();
+        ASSET_LOADS.with(|loads| loads.set(0));
+
+        // Each run of a new system instance restyles everything, but the image is only loaded
+        // the first time.
+        for _ in 0..3 {
+            world.run_system_once(update_styles);
+        }
+        assert_eq!(ASSET_LOADS.with(|loads| loads.get()), 1);
+        let texture = world.get::<UiImage>(node).unwrap().texture.clone();
+        assert_eq!(texture.path(), Some(&AssetPath::from("image.png")));
+
+        world
+            .entity_mut(node)
+            .insert(ElementStyles::new(&[image_style("other.png")]));
+        world.run_system_once(update_styles);
+        world.run_system_once(update_styles);
+        assert_eq!(ASSET_LOADS.with(|loads| loads.get()), 2);
+        let texture = world.get::<UiImage>(node).unwrap().texture.clone();
+        assert_eq!(texture.path(), Some(&AssetPath::from("other.png")));
+    }
+
+    #[test]
+    fn test_redundant_font_reuses_handle() {
+        let mut app = test_app();
+        let world = &mut app.world;
+        let font_style = StyleHandle::build(|ss| ss.font(Some("fonts/Inter.ttf".into())));
+        let panel_style = StyleHandle::build(|ss| {
+            ss.font(Some("fonts/Inter.ttf".into()))
+                .background_color("#ff0000")
+        });
+        let text = spawn_text(world, None);
+        let panel = world
+            .spawn((NodeBundle::default(), ElementStyles::new(&[panel_style])))
+            .add_child(text)
+            .id();
+        let root = world
+            .spawn((NodeBundle::default(), ElementStyles::new(&[font_style])))
+            .add_child(panel)
+            .id();
+        ASSET_LOADS.with(|loads| loads.set(0));
+
+        // The panel's font is the same as the root's, so it isn't loaded again, and the panel
+        // doesn't need text styles of its own. Restyling loads nothing.
+        for _ in 0..3 {
+            world.run_system_once(update_styles);
+        }
+        assert_eq!(ASSET_LOADS.with(|loads| loads.get()), 1);
+        let font = world.get::<TextStyles>(root).unwrap().font.clone().unwrap();
+        assert_eq!(font.path(), Some(&AssetPath::from("fonts/Inter.ttf")));
+        assert!(world.get::<TextStyles>(panel).is_none());
+        assert_eq!(
+            world.get::<Text>(text).unwrap().sections[0].style.font,
+            font
+        );
+
+        // Once the root's font changes, the panel keeps the handle it shared without loading
+        // it again, and needs text styles of its own.
+        world
+            .entity_mut(root)
+            .insert(ElementStyles::new(&[StyleHandle::build(|ss| {
+                ss.font(Some("fonts/Other.ttf".into()))
+            })]));
+        world.run_system_once(update_styles);
+        world.run_system_once(update_styles);
+        assert_eq!(ASSET_LOADS.with(|loads| loads.get()), 2);
+        let panel_styles = world.get::<TextStyles>(panel).unwrap();
+        assert_eq!(panel_styles.font, Some(font.clone()));
+        assert_eq!(
+            world.get::<Text>(text).unwrap().sections[0].style.font,
+            font
+        );
+    }
+
+    #[test]
+    fn test_selector_match_cache() {
+        let mut app = test_app();
+        let world = &mut app.world;
+        let style = StyleHandle::build(|ss| {
+            ss.selector(".a > .b > &", |ss| ss.background_color("#ff0000"))
+        });
+        let root = world
+            .spawn((NodeBundle::default(), ElementClasses::new("a")))
+            .id();
+        let mut leaves = Vec::new();
+        for _ in 0..40 {
+            let children: Vec<Entity> = (0..50)
+                .map(|_| {
+                    world
+                        .spawn((
+                            NodeBundle::default(),
+                            ElementStyles::new(std::slice::from_ref(&style)),
+                            ElementClasses::default(),
+                        ))
+                        .id()
+                })
+                .collect();
+            let parent = world
+                .spawn((NodeBundle::default(), ElementClasses::new("b")))
+                .push_children(&children)
+                .id();
+            world.entity_mut(root).add_child(parent);
+            leaves.extend(children);
+        }
+        let red = Some(Color::hex("#ff0000").unwrap());
+        let bg = |world: &World, e: Entity| world.get::<BackgroundColor>(e).map(|bg| bg.0);
+
+        // Each of the 2000 leaves matches against its parent and grandparent, but each
+        // ancestor is only matched once.
+        let mut schedule = Schedule::default();
+        schedule
+            .set_executor_kind(ExecutorKind::SingleThreaded)
+            .add_systems(update_styles);
+        SELECTOR_EVALUATIONS.with(|n| n.set(0));
+        schedule.run(world);
+        assert_eq!(SELECTOR_EVALUATIONS.with(|n| n.get()), 41);
+        assert!(leaves.iter().all(|leaf| bg(world, *leaf) == red));
+
+        // Results from the previous pass aren't reused.
+        world
+            .get_mut::<ElementClasses>(root)
+            .unwrap()
+            .remove_class("a");
+        SELECTOR_EVALUATIONS.with(|n| n.set(0));
+        schedule.run(world);
+        assert_eq!(SELECTOR_EVALUATIONS.with(|n| n.get()), 41);
+        assert!(leaves.iter().all(|leaf| bg(world, *leaf) != red));
+    }
+
+    #[test]
+    fn test_inherit_keyword() {
+        let mut app = test_app();
+        let world = &mut app.world;
+
+        let root_style = StyleHandle::build(|ss| ss.color("#ff0000").font_size(20.));
+        let container_style = StyleHandle::build(|ss| {
+            ss.color("#0000ff")
+                .font_size(10.)
+                .selector(".plain", |ss| ss.inherit_color().inherit_font_size())
+        });
+
+        let leaf = spawn_text(world, None);
+        let container = world
+            .spawn((
+                NodeBundle::default(),
+                ElementStyles::new(&[container_style]),
+                ElementClasses::default(),
+            ))
+            .push_children(&[leaf])
+            .id();
+        world
+            .spawn((NodeBundle::default(), ElementStyles::new(&[root_style])))
+            .push_children(&[container]);
+
+        world.run_system_once(update_styles);
+        let section = &world.get::<Text>(leaf).unwrap().sections[0];
+        assert_eq!(section.style.color, Color::hex("#0000ff").unwrap());
+        assert_eq!(section.style.font_size, 10.);
+
+        // Undo the container's overrides for its subtree.
+        world
+            .get_mut::<ElementClasses>(container)
+            .unwrap()
+            .add_class("plain");
+        world.run_system_once(update_styles);
+        let section = &world.get::<Text>(leaf).unwrap().sections[0];
+        assert_eq!(section.style.color, Color::hex("#ff0000").unwrap());
+        assert_eq!(section.style.font_size, 20.);
+    }
+
+    #[test]
+    fn test_inherited_text_keeps_style() {
+        let mut app = test_app();
+        let world = &mut app.world;
+
+        let parent_style = StyleHandle::build(|ss| {
+            ss.color("#ff0000")
+                .width(100.)
+                .selector(".dark", |ss| ss.color("#0000ff"))
+        });
+        let child_style = StyleHandle::build(|ss| ss.display(Display::Grid).width(10.));
+        let label = spawn_text(world, Some(ElementStyles::new([child_style.clone()])));
+        let child = world
+            .spawn((NodeBundle::default(), ElementStyles::new([child_style])))
+            .id();
+        let parent = world
+            .spawn((
+                NodeBundle::default(),
+                ElementStyles::new([parent_style]),
+                ElementClasses::default(),
+            ))
+            .push_children(&[label, child])
+            .id();
+        // The same system runs each time, so that it only restyles what changed since.
+        let mut schedule = Schedule::default();
+        schedule.add_systems(update_styles);
+        schedule.run(world);
+        // Another system sizes the label, which its styles only set initially.
+        world.get_mut::<Style>(label).unwrap().width = Val::Px(50.);
+        let style_tick =
+            |world: &World, e| world.entity(e).get_ref::<Style>().unwrap().last_changed();
+        let ticks = [style_tick(world, label), style_tick(world, child)];
+        let color = |world: &World| world.get::<Text>(label).unwrap().sections[0].style.color;
+        assert_eq!(color(world), Color::hex("#ff0000").unwrap());
+
+        // Only the inherited color changes, so the children's layout isn't touched, and isn't
+        // reset to the styled values.
+        world
+            .get_mut::<ElementClasses>(parent)
+            .unwrap()
+            .add_class("dark");
+        schedule.run(world);
+        assert_eq!(color(world), Color::hex("#0000ff").unwrap());
+        assert_eq!([style_tick(world, label), style_tick(world, child)], ticks);
+        assert_eq!(world.get::<Style>(label).unwrap().display, Display::Grid);
+        assert_eq!(world.get::<Style>(label).unwrap().width, Val::Px(50.));
+
+        // Applying the same text styles again doesn't touch the text, which would restyle it.
+        let text_tick = world
+            .entity(label)
+            .get_ref::<Text>()
+            .unwrap()
+            .last_changed();
+        schedule.run(world);
+        schedule.run(world);
+        assert_eq!(
+            world
+                .entity(label)
+                .get_ref::<Text>()
+                .unwrap()
+                .last_changed(),
+            text_tick
+        );
+
+        // A change to the child's own styles still updates its layout.
+        world
+            .get_mut::<ElementStyles>(label)
+            .unwrap()
+            .push(StyleHandle::build(|ss| ss.width(20.)));
+        schedule.run(world);
+        assert_ne!(style_tick(world, label), ticks[0]);
+        assert_eq!(world.get::<Style>(label).unwrap().width, Val::Px(20.));
+    }
+
+    #[test]
+    fn test_deep_hierarchy() {
+        let mut app = test_app();
+        let world = &mut app.world;
+
+        let root_style = StyleHandle::build(|ss| ss.color("#ff0000"));
+        let leaf = spawn_text(world, None);
+        let mut child = leaf;
+        for _ in 0..1000 {
+            child = world
+                .spawn(NodeBundle::default())
+                .push_children(&[child])
+                .id();
+        }
+        world
+            .spawn((NodeBundle::default(), ElementStyles::new(&[root_style])))
+            .push_children(&[child]);
+
+        world.run_system_once(update_styles);
+        assert_eq!(
+            world.get::<Text>(leaf).unwrap().sections[0].style.color,
+            Color::hex("#ff0000").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_text_sections_repopulated() {
+        let mut app = test_app();
+        app.add_plugins(crate::PeacockPlugin);
+        let red = Color::hex("#ff0000").unwrap();
+
+        let style = StyleHandle::build(|ss| ss.color("#ff0000").font_size(20.));
+        let text = spawn_text(&mut app.world, Some(ElementStyles::new(&[style])));
+        app.update();
+        assert_eq!(
+            app.world.get::<Text>(text).unwrap().sections[0].style.color,
+            red
+        );
+
+        // Sections are cleared, then repopulated with default styles on a later frame.
+        app.world.get_mut::<Text>(text).unwrap().sections.clear();
+        app.update();
+        assert!(app.world.get::<Text>(text).unwrap().sections.is_empty());
+
+        for count in 1..=3 {
+            app.world
+                .get_mut::<Text>(text)
+                .unwrap()
+                .sections
+                .push(TextSection::new("Hello", TextStyle::default()));
+            app.update();
+            let sections = &app.world.get::<Text>(text).unwrap().sections;
+            assert_eq!(sections.len(), count);
+            for section in sections {
+                assert_eq!(section.style.color, red);
+                assert_eq!(section.style.font_size, 20.);
+            }
+        }
+    }
+
+    #[test]
+    fn test_text_added_and_removed() {
+        let mut app = test_app();
+        app.add_plugins(crate::PeacockPlugin);
+        let red = Color::hex("#ff0000").unwrap();
+        let blue = Color::hex("#0000ff").unwrap();
+
+        let style = StyleHandle::build(|ss| {
+            ss.color("#ff0000")
+                .selector(".blue", |ss| ss.color("#0000ff"))
+        });
+        let leaf = spawn_text(&mut app.world, None);
+        let mid = app.world.spawn(NodeBundle::default()).add_child(leaf).id();
+        let root = app
+            .world
+            .spawn((
+                NodeBundle::default(),
+                ElementStyles::new(&[style]),
+                ElementClasses::default(),
+            ))
+            .add_child(mid)
+            .id();
+        let color = |app: &App, entity| {
+            app.world.get::<Text>(entity).unwrap().sections[0]
+                .style
+                .color
+        };
+        let set_blue = |app: &mut App, blue| {
+            app.world
+                .get_mut::<ElementClasses>(root)
+                .unwrap()
+                .set_class("blue", blue);
+            app.update();
+        };
+
+        // Only the text node caches its text styles, since the middle node inherits them
+        // unchanged.
+        app.update();
+        assert!(app.world.get::<TextStyles>(mid).is_none());
+        assert!(app.world.get::<TextStyles>(leaf).is_some());
+        assert_eq!(color(&app, leaf), red);
+
+        // Gaining text makes the middle node cache them too.
+        app.world
+            .entity_mut(mid)
+            .insert(Text::from_section("Middle", TextStyle::default()));
+        app.update();
+        assert!(app.world.get::<TextStyles>(mid).is_some());
+        assert_eq!(color(&app, mid), red);
+        set_blue(&mut app, true);
+        assert_eq!(color(&app, mid), blue);
+        assert_eq!(color(&app, leaf), blue);
+
+        // Losing it removes the cache, and changes still reach the descendants.
+        app.world.entity_mut(mid).remove::<Text>();
+        app.update();
+        assert!(app.world.get::<TextStyles>(mid).is_none());
+        assert_eq!(color(&app, leaf), blue);
+        set_blue(&mut app, false);
+        assert!(app.world.get::<TextStyles>(mid).is_none());
+        assert_eq!(color(&app, leaf), red);
+
+        // And it comes back with the text.
+        app.world
+            .entity_mut(mid)
+            .insert(Text::from_section("Middle", TextStyle::default()));
+        app.update();
+        assert!(app.world.get::<TextStyles>(mid).is_some());
+        assert_eq!(color(&app, mid), red);
+    }
+
+    #[test]
+    fn test_skip_hidden_subtrees() {
+        let mut app = test_app();
+        app.add_plugins(crate::PeacockPlugin);
+        app.world.resource_mut::<PeacockConfig>().skip_hidden = SkipHidden::Hidden;
+        let red = Color::hex("#ff0000").unwrap();
+        let blue = Color::hex("#0000ff").unwrap();
+
+        let style = StyleHandle::build(|ss| {
+            ss.color("#ff0000")
+                .selector(".blue", |ss| ss.color("#0000ff"))
+        });
+        let leaf = spawn_text(&mut app.world, None);
+        let mid = app.world.spawn(NodeBundle::default()).add_child(leaf).id();
+        let root = app
+            .world
+            .spawn((
+                NodeBundle::default(),
+                ElementStyles::new([style]),
+                ElementClasses::default(),
+            ))
+            .add_child(mid)
+            .id();
+        let color = |app: &App| app.world.get::<Text>(leaf).unwrap().sections[0].style.color;
+        let set_visibility = |app: &mut App, visibility| {
+            *app.world.get_mut::<Visibility>(mid).unwrap() = visibility;
+        };
+        let set_blue = |app: &mut App, blue| {
+            app.world
+                .get_mut::<ElementClasses>(root)
+                .unwrap()
+                .set_class("blue", blue);
+        };
+        app.update();
+        assert_eq!(color(&app), red);
+
+        // While the middle node is hidden, changes don't reach its descendants.
+        set_visibility(&mut app, Visibility::Hidden);
+        set_blue(&mut app, true);
+        app.update();
+        assert_eq!(color(&app), red);
+        assert!(app.world.get::<SkippedSubtree>(mid).is_some());
+
+        // They're restyled in the same frame it's revealed.
+        set_visibility(&mut app, Visibility::Inherited);
+        app.update();
+        assert_eq!(color(&app), blue);
+        assert!(app.world.get::<SkippedSubtree>(mid).is_none());
+
+        // Even if the change which makes them differ happens in that frame too.
+        set_visibility(&mut app, Visibility::Hidden);
+        app.update();
+        set_visibility(&mut app, Visibility::Inherited);
+        set_blue(&mut app, false);
+        app.update();
+        assert_eq!(color(&app), red);
+
+        // Hidden subtrees are styled as usual by default.
+        app.world.resource_mut::<PeacockConfig>().skip_hidden = SkipHidden::Never;
+        set_visibility(&mut app, Visibility::Hidden);
+        set_blue(&mut app, true);
+        app.update();
+        assert_eq!(color(&app), blue);
+        assert!(app.world.get::<SkippedSubtree>(mid).is_none());
+    }
+
+    #[test]
+    fn test_update_text_sections() {
+        let mut app = test_app();
+        let world = &mut app.world;
+        let blue = Color::hex("#0000ff").unwrap();
+
+        // Cached text styles are applied to new sections without a full restyle.
+        let text = spawn_text(world, None);
+        world.entity_mut(text).insert(TextStyles {
+            color: Some(blue),
+            font_size: Some(12.),
+            ..default()
+        });
+        world.run_system_once(update_text_sections);
+        let section = &world.get::<Text>(text).unwrap().sections[0];
+        assert_eq!(section.style.color, blue);
+        assert_eq!(section.style.font_size, 12.);
+    }
+
+    /// Spawn a root with a panel child, which contains a text leaf, returning the entities.
+    fn spawn_scaled_tree(world: &mut World, scale: f32) -> (Entity, Entity, Entity) {
+        let mut stylesheet = crate::parse_stylesheet(
+            "ROOT {
+                width: 200px;
+                height: 100%;
+                padding: 10px;
+                font_size: 20;
+             }
+             PANEL {
+                width: 50%;
+                height: 40px;
+                margin: 4px 2%;
+                border: 2px;
+                gap: 6px;
+                outline_width: 1px;
+                outline_color: #fff;
+             }",
+        )
+        .unwrap();
+        let panel_style = StyleHandle::new(stylesheet.pop().unwrap().1);
+        let root_style = StyleHandle::new(stylesheet.pop().unwrap().1);
+        let scale_style = StyleHandle::build(|ss| ss.ui_scale(scale));
+
+        let leaf = spawn_text(world, None);
+        let panel = world
+            .spawn((NodeBundle::default(), ElementStyles::new(&[panel_style])))
+            .push_children(&[leaf])
+            .id();
+        let root = world
+            .spawn((
+                NodeBundle::default(),
+                ElementStyles::new(&[root_style, scale_style]),
+            ))
+            .push_children(&[panel])
+            .id();
+        (root, panel, leaf)
+    }
+
+    #[test]
+    fn test_ui_scale() {
+        let mut app = test_app();
+        let world = &mut app.world;
+        let (root_1, panel_1, leaf_1) = spawn_scaled_tree(world, 1.);
+        let (root_2, panel_2, leaf_2) = spawn_scaled_tree(world, 0.5);
+        world.run_system_once(update_styles);
+
+        let root = world.get::<Style>(root_1).unwrap();
+        assert_eq!(root.width, Val::Px(200.));
+        assert_eq!(root.padding, UiRect::all(Val::Px(10.)));
+        let root = world.get::<Style>(root_2).unwrap();
+        assert_eq!(root.width, Val::Px(100.));
+        assert_eq!(root.height, Val::Percent(100.));
+        assert_eq!(root.padding, UiRect::all(Val::Px(5.)));
+
+        let panel = world.get::<Style>(panel_1).unwrap();
+        assert_eq!(panel.height, Val::Px(40.));
+        assert_eq!(panel.margin, UiRect::axes(Val::Px(4.), Val::Percent(2.)));
+        assert_eq!(panel.border, UiRect::all(Val::Px(2.)));
+        assert_eq!(panel.row_gap, Val::Px(6.));
+        let panel = world.get::<Style>(panel_2).unwrap();
+        assert_eq!(panel.width, Val::Percent(50.));
+        assert_eq!(panel.height, Val::Px(20.));
+        assert_eq!(panel.margin, UiRect::axes(Val::Px(2.), Val::Percent(2.)));
+        assert_eq!(panel.border, UiRect::all(Val::Px(1.)));
+        assert_eq!(panel.row_gap, Val::Px(3.));
+        assert_eq!(panel.column_gap, Val::Px(3.));
+        assert_eq!(world.get::<Outline>(panel_2).unwrap().width, Val::Px(0.5));
+
+        let font_size =
+            |world: &World, e| world.get::<Text>(e).unwrap().sections[0].style.font_size;
+        assert_eq!(font_size(world, leaf_1), 20.);
+        assert_eq!(font_size(world, leaf_2), 10.);
+
+        // Changing the scale restyles the whole tree.
+        let root_style = world.get::<ElementStyles>(root_1).unwrap().styles[0].clone();
+        world.entity_mut(root_1).insert(ElementStyles::new(&[
+            root_style,
+            StyleHandle::build(|ss| ss.ui_scale(2.)),
+        ]));
+        world.run_system_once(update_styles);
+        assert_eq!(world.get::<Style>(root_1).unwrap().width, Val::Px(400.));
+        assert_eq!(world.get::<Style>(panel_1).unwrap().height, Val::Px(80.));
+        assert_eq!(font_size(world, leaf_1), 40.);
+    }
+
+    #[test]
+    fn test_ui_scale_ignored_below_root() {
+        let mut app = test_app();
+        let world = &mut app.world;
+        let style = StyleHandle::build(|ss| ss.ui_scale(0.5).width(100.));
+        let child = world
+            .spawn((NodeBundle::default(), ElementStyles::new(&[style])))
+            .id();
+        world.spawn(NodeBundle::default()).push_children(&[child]);
+        world.run_system_once(update_styles);
+        assert_eq!(world.get::<Style>(child).unwrap().width, Val::Px(100.));
+
+        // The element is only reported once, however often it's restyled, and forgotten when
+        // its styles are removed.
+        let warned = |world: &World| world.resource::<UiScaleWarnings>().0.clone();
+        assert_eq!(warned(world), HashSet::from([child]));
+        world.run_system_once(|mut forced: ResMut<ForcedRestyles>| forced.all = true);
+        world.run_system_once(update_styles);
+        assert_eq!(warned(world), HashSet::from([child]));
+        world.entity_mut(child).remove::<ElementStyles>();
+        world.run_system_once(forget_ui_scale_warnings);
+        assert!(warned(world).is_empty());
+    }
+
+    #[test]
+    fn test_coalesce_style_passes() {
+        let mut app = test_app();
+        let world = &mut app.world;
+        let style = StyleHandle::build(|ss| {
+            ss.width(10.)
+                .selector(".dark > .mid > &", |ss| ss.width(30.))
+                .selector(".mid > &", |ss| ss.height(20.))
+        });
+        let child = world
+            .spawn((
+                NodeBundle::default(),
+                ElementStyles::new(&[style]),
+                ElementClasses::default(),
+            ))
+            .id();
+        let parent = world
+            .spawn((NodeBundle::default(), ElementClasses::default()))
+            .push_children(&[child])
+            .id();
+        let grandparent = world
+            .spawn((NodeBundle::default(), ElementClasses::default()))
+            .push_children(&[parent])
+            .id();
+
+        // Two style passes in the same frame, as when a manual restyle runs alongside the
+        // usual one. Commands aren't applied until the end of the schedule, so both passes
+        // compute the same entities.
+        let mut schedule = Schedule::default();
+        schedule.add_systems((update_styles, update_styles).chain());
+        schedule.run(world);
+        assert!(world.resource::<PendingStyleUpdates>().is_empty());
+        assert_eq!(world.resource::<PendingStyleUpdates>().redundant_count(), 1);
+        assert_eq!(world.get::<Style>(child).unwrap().width, Val::Px(10.));
+
+        // Change the classes of both ancestors in the same frame.
+        for (entity, class) in [(grandparent, "dark"), (parent, "mid")] {
+            world
+                .get_mut::<ElementClasses>(entity)
+                .unwrap()
+                .add_class(class);
+        }
+        // The count is per frame.
+        schedule.run(world);
+        assert_eq!(world.resource::<PendingStyleUpdates>().redundant_count(), 1);
+        let style = world.get::<Style>(child).unwrap();
+        assert_eq!(style.width, Val::Px(30.));
+        assert_eq!(style.height, Val::Px(20.));
+    }
+
+    #[test]
+    fn test_calc() {
+        let mut app = test_app();
+        let world = &mut app.world;
+        let style = StyleHandle::new(
+            bevy_peacock_style::parse_stylesheet(
+                "CONTENT { width: calc(100% - 32px); height: calc(50% + 10px); }",
+            )
+            .unwrap()
+            .pop()
+            .unwrap()
+            .1,
+        );
+        let child = world
+            .spawn((
+                NodeBundle::default(),
+                ElementStyles::new(std::slice::from_ref(&style)),
+            ))
+            .id();
+        let parent = world
+            .spawn(NodeBundle {
+                style: Style {
+                    width: Val::Px(400.),
+                    height: Val::Px(300.),
+                    ..default()
+                },
+                ..default()
+            })
+            .push_children(&[child])
+            .id();
+        let root = world
+            .spawn((NodeBundle::default(), ElementStyles::new(&[style])))
+            .id();
+        crate::test_utils::set_window_size(world, 800., 600.);
+        crate::test_utils::run_layout(world);
+
+        crate::test_utils::apply_styles_once(world);
+        let child_style = world.get::<Style>(child).unwrap();
+        assert_eq!(child_style.width, Val::Px(368.));
+        assert_eq!(child_style.height, Val::Px(160.));
+        // Root nodes are resolved against the window.
+        assert_eq!(world.get::<Style>(root).unwrap().width, Val::Px(768.));
+
+        // Resizing the parent restyles the child.
+        world.get_mut::<Style>(parent).unwrap().width = Val::Px(200.);
+        crate::test_utils::run_layout(world);
+        crate::test_utils::apply_styles_once(world);
+        assert_eq!(world.get::<Style>(child).unwrap().width, Val::Px(168.));
+
+        crate::test_utils::set_window_size(world, 1000., 600.);
+        crate::test_utils::apply_styles_once(world);
+        assert_eq!(world.get::<Style>(root).unwrap().width, Val::Px(968.));
+        assert_eq!(world.get::<Style>(child).unwrap().width, Val::Px(168.));
+    }
+
+    #[test]
+    fn test_calc_padded_parent() {
+        let mut app = test_app();
+        let world = &mut app.world;
+        let style = StyleHandle::new(
+            bevy_peacock_style::parse_stylesheet(
+                "CONTENT { width: calc(100% - 10px); height: calc(50% + 0px); }",
+            )
+            .unwrap()
+            .pop()
+            .unwrap()
+            .1,
+        );
+        let child = world
+            .spawn((NodeBundle::default(), ElementStyles::new(&[style])))
+            .id();
+        // Percentage padding is relative to the width of the grandparent's content box.
+        let grandparent = world
+            .spawn(NodeBundle {
+                style: Style {
+                    width: Val::Px(500.),
+                    height: Val::Px(400.),
+                    padding: UiRect::all(Val::Px(50.)),
+                    ..default()
+                },
+                ..default()
+            })
+            .id();
+        let parent = world
+            .spawn(NodeBundle {
+                style: Style {
+                    width: Val::Px(300.),
+                    height: Val::Px(200.),
+                    padding: UiRect::axes(Val::Px(20.), Val::Percent(5.)),
+                    border: UiRect::all(Val::Px(5.)),
+                    ..default()
+                },
+                ..default()
+            })
+            .push_children(&[child])
+            .id();
+        world.entity_mut(grandparent).push_children(&[parent]);
+        crate::test_utils::set_window_size(world, 800., 600.);
+        crate::test_utils::run_layout(world);
+        assert_eq!(
+            world.get::<Node>(parent).unwrap().size(),
+            Vec2::new(300., 200.)
+        );
+
+        // The content box of the parent is 300 - 2 * (20 + 5) wide, and 200 - 2 * (20 + 5)
+        // high, the vertical padding being 5% of the grandparent's 400px content width.
+        crate::test_utils::apply_styles_once(world);
+        let child_style = world.get::<Style>(child).unwrap();
+        assert_eq!(child_style.width, Val::Px(240.));
+        assert_eq!(child_style.height, Val::Px(75.));
+
+        // Changing the parent's padding restyles the child, even though its size is unchanged.
+        world.get_mut::<Style>(parent).unwrap().padding = UiRect::all(Val::Px(0.));
+        crate::test_utils::run_layout(world);
+        crate::test_utils::apply_styles_once(world);
+        let child_style = world.get::<Style>(child).unwrap();
+        assert_eq!(child_style.width, Val::Px(280.));
+        assert_eq!(child_style.height, Val::Px(95.));
+    }
+
+    #[test]
+    fn test_important() {
+        let mut app = test_app();
+        let world = &mut app.world;
+        let sheet = bevy_peacock_style::parse_stylesheet(
+            "BASE {
+                background_color: #f00 !important;
+                width: 10px;
+                .wide { width: 30px !important; }
+            }
+            LATER {
+                background_color: #0f0;
+                width: 20px;
+                height: 5px;
+            }",
+        )
+        .unwrap();
+        world.resource_mut::<StyleRegistry>().register_sheet(sheet);
+        world
+            .resource_mut::<StyleRegistry>()
+            .set_overrides(bevy_peacock_style::parse_stylesheet("LATER { width: 40px; }").unwrap());
+        let registry = world.resource::<StyleRegistry>();
+        let styles = ElementStyles::new([
+            registry.get("BASE").unwrap(),
+            registry.get("LATER").unwrap(),
+        ]);
+        let entity = world
+            .spawn((NodeBundle::default(), styles, ElementClasses::default()))
+            .id();
+
+        // The later style and its override win over the normal properties of the earlier
+        // one, but not over its important ones.
+        crate::test_utils::apply_styles_once(world);
+        assert_eq!(
+            world.get::<BackgroundColor>(entity).unwrap().0,
+            Color::rgb(1., 0., 0.)
+        );
+        let style = world.get::<Style>(entity).unwrap();
+        assert_eq!(style.width, Val::Px(40.));
+        assert_eq!(style.height, Val::Px(5.));
+
+        world
+            .get_mut::<ElementClasses>(entity)
+            .unwrap()
+            .add_class("wide");
+        crate::test_utils::apply_styles_once(world);
+        assert_eq!(world.get::<Style>(entity).unwrap().width, Val::Px(30.));
+    }
+
+    #[test]
+    fn test_unset_style_fields_kept() {
+        let mut app = test_app();
+        let world = &mut app.world;
+        let style = StyleHandle::build(|ss| {
+            ss.width(10.)
+                .grid_row_start(2)
+                .selector(".wide", |ss| ss.width(30.))
+        });
+        let entity = world
+            .spawn((
+                NodeBundle::default(),
+                ElementStyles::new(&[style]),
+                ElementClasses::default(),
+            ))
+            .id();
+        let mut schedule = Schedule::default();
+        schedule.add_systems(update_styles);
+        schedule.run(world);
+
+        // Fields the styles don't set, tweaked by hand, survive a recompute, as do the parts
+        // of a grid placement which the styles don't set.
+        let mut style = world.get_mut::<Style>(entity).unwrap();
+        style.flex_grow = 2.;
+        style.grid_row = style.grid_row.set_span(3);
+        world
+            .get_mut::<ElementClasses>(entity)
+            .unwrap()
+            .add_class("wide");
+        schedule.run(world);
+        let style = world.get::<Style>(entity).unwrap();
+        assert_eq!(style.width, Val::Px(30.));
+        assert_eq!(style.flex_grow, 2.);
+        assert_eq!(style.grid_row, GridPlacement::start_span(2, 3));
+    }
+
+    /// Measure the time taken to restyle many entities, all of whose classes change each
+    /// frame. Run with `cargo test --release bench_restyle -- --ignored --nocapture`.
+    #[test]
+    #[ignore]
+    fn bench_restyle() {
+        let mut app = test_app();
+        let world = &mut app.world;
+        let style = StyleHandle::build(|ss| {
+            ss.width(10.)
+                .height(10.)
+                .flex_grow(1.)
+                .selector(".wide", |ss| ss.width(30.))
+        });
+        let entities: Vec<Entity> = (0..10000)
+            .map(|_| {
+                world
+                    .spawn((
+                        NodeBundle::default(),
+                        ElementStyles::new(std::slice::from_ref(&style)),
+                        ElementClasses::default(),
+                    ))
+                    .id()
+            })
+            .collect();
+        let mut schedule = Schedule::default();
+        schedule.add_systems(update_styles);
+        schedule.run(world);
+
+        let frames = 50;
+        let start = std::time::Instant::now();
+        for frame in 0..frames {
+            for entity in entities.iter() {
+                world
+                    .get_mut::<ElementClasses>(*entity)
+                    .unwrap()
+                    .set_class("wide", frame % 2 == 0);
+            }
+            schedule.run(world);
         }
+        let elapsed = start.elapsed();
+        println!(
+            "restyled {} entities in {:?} per frame",
+            entities.len(),
+            elapsed / frames
+        );
+    }
+
+    #[test]
+    fn test_media_query() {
+        let mut app = test_app();
+        let world = &mut app.world;
+
+        let mut stylesheet = crate::parse_stylesheet(
+            "MAIN {
+                padding: 16px;
+                @media (max-width: 800) { padding: 4px; }
+                @media (max-width: 800) &.dense { padding: 2px; }
+             }",
+        )
+        .unwrap();
+        let style = StyleHandle::new(stylesheet.pop().unwrap().1);
+        let main = world
+            .spawn((
+                NodeBundle::default(),
+                ElementStyles::new(&[style]),
+                ElementClasses::default(),
+            ))
+            .id();
+        let padding = |world: &World| world.get::<Style>(main).unwrap().padding.left;
+
+        set_window_size(world, 1920., 1080.);
+        apply_styles_once(world);
+        assert_eq!(padding(world), Val::Px(16.));
+
+        // Resizing the window restyles elements which use media queries.
+        set_window_size(world, 640., 480.);
+        apply_styles_once(world);
+        assert_eq!(padding(world), Val::Px(4.));
+
+        // Media queries compose with other selectors.
+        world
+            .get_mut::<ElementClasses>(main)
+            .unwrap()
+            .add_class("dense");
+        apply_styles_once(world);
+        assert_eq!(padding(world), Val::Px(2.));
+
+        set_window_size(world, 1024., 768.);
+        apply_styles_once(world);
+        assert_eq!(padding(world), Val::Px(16.));
     }
 }
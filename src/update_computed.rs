@@ -1,41 +1,87 @@
 use super::animate::{
-    AnimatedBackgroundColor, AnimatedBorderColor, AnimatedLayout, AnimatedLayoutProp,
-    AnimatedTransform,
+    adopt_transition, percent_basis, AnimatedBackgroundColor, AnimatedBorderColor, AnimatedColor,
+    AnimatedFontSize, AnimatedIn, AnimatedLayout, AnimatedLayoutProp, AnimatedLengths,
+    AnimatedOutline, AnimatedTransform, ElevateWhileAnimating, ElevatedZIndex,
 };
+use crate::layout_measures::resolve_val;
+use crate::mask::{drop_masked_transitions, PeacockPropertyMask};
+use crate::text_overflow::update_text_overflow;
+use crate::watch::notify_watchers;
+use crate::ElementStyles;
+#[cfg(any(test, feature = "diagnostics"))]
+use crate::{StyleHandle, StyleRegistry};
 use bevy::ecs::system::Command;
+use bevy::log::info_span;
 use bevy::prelude::*;
 use bevy::ui::widget::UiImageSize;
 use bevy::ui::ContentSize;
-use bevy::utils::HashMap;
+use bevy::utils::{HashMap, HashSet};
 #[cfg(feature = "bevy_mod_picking")]
 use bevy_mod_picking::prelude::Pickable;
+use bevy_peacock_style::{unit_name, Transition, TransitionProperty};
 use bevy_peacock_style::{ComputedStyle, PointerEvents};
-use bevy_peacock_style::{TransitionProperty, TransitionState};
 
 /// Custom command that updates the style of an entity.
 pub struct UpdateComputedStyle {
     pub(crate) entity: Entity,
     pub(crate) computed: ComputedStyle,
+    /// The size of the parent node, or of the window for root nodes, when the style was
+    /// computed, which relative lengths in layout transitions are resolved against.
+    pub(crate) parent_size: Vec2,
+    /// The size of the window when the style was computed.
+    pub(crate) viewport: Vec2,
+    /// Only the inherited text styles changed, so only the text is updated, leaving the
+    /// `Style`, transform and other components untouched.
+    pub(crate) text_only: bool,
+    /// The styles which produced the computed style, for diagnostics.
+    #[cfg(any(test, feature = "diagnostics"))]
+    pub(crate) styles: Vec<StyleHandle>,
 }
 
 impl Command for UpdateComputedStyle {
-    fn apply(self, world: &mut World) {
+    fn apply(mut self, world: &mut World) {
+        let _span = info_span!("apply_computed_style", entity = ?self.entity).entered();
+        let mask = world.get::<PeacockPropertyMask>(self.entity).cloned();
+        let entering = self.merge_animate_in(world);
+        if let Some(mask) = &mask {
+            drop_masked_transitions(world, self.entity, mask, &mut self.computed.transitions);
+        }
+        let masked = |keys: &[&str]| mask.as_ref().is_some_and(|mask| mask.masks_any(keys));
+
+        #[cfg(any(test, feature = "diagnostics"))]
+        warn_unset_transitions(world, self.entity, &self.computed, &self.styles);
+        warn_unanimatable_transitions(
+            world,
+            self.entity,
+            &self.computed,
+            #[cfg(any(test, feature = "diagnostics"))]
+            &self.styles,
+        );
+
+        notify_watchers(world, self.entity, &self.computed);
+
         let Some(mut e) = world.get_entity_mut(self.entity) else {
             return;
         };
+        if let Some(origin) = entering {
+            self.start_animate_in(&mut e, &origin);
+        }
 
         let mut is_animated_bg_color = false;
         let mut is_animated_border_color = false;
         let mut is_animated_transform = false;
         let mut is_animated_layout = false;
-
-        let mut next_style = self.computed.style;
+        let mut is_animated_outline = false;
+        let mut is_animated_font_size = false;
 
         self.computed
             .transitions
             .iter()
             .for_each(|tr| match tr.property {
-                TransitionProperty::Transform => is_animated_transform = true,
+                TransitionProperty::Transform
+                | TransitionProperty::Scale
+                | TransitionProperty::Rotation
+                | TransitionProperty::Translation => is_animated_transform = true,
                 TransitionProperty::BackgroundColor => is_animated_bg_color = true,
                 TransitionProperty::BorderColor => is_animated_border_color = true,
                 TransitionProperty::Height
@@ -47,9 +93,72 @@ impl Command for UpdateComputedStyle {
                 | TransitionProperty::BorderLeft
                 | TransitionProperty::BorderTop
                 | TransitionProperty::BorderRight
-                | TransitionProperty::BorderBottom => is_animated_layout = true,
+                | TransitionProperty::BorderBottom
+                | TransitionProperty::PaddingLeft
+                | TransitionProperty::PaddingRight
+                | TransitionProperty::PaddingTop
+                | TransitionProperty::PaddingBottom
+                | TransitionProperty::MarginLeft
+                | TransitionProperty::MarginRight
+                | TransitionProperty::MarginTop
+                | TransitionProperty::MarginBottom
+                | TransitionProperty::RowGap
+                | TransitionProperty::ColumnGap => is_animated_layout = true,
+                TransitionProperty::OutlineWidth
+                | TransitionProperty::OutlineOffset
+                | TransitionProperty::OutlineColor => is_animated_outline = true,
+                TransitionProperty::FontSize => is_animated_font_size = true,
             });
 
+        if let Some(mut text) = e.get_mut::<Text>() {
+            let prev_font_size = text.length(TransitionProperty::FontSize);
+            update_text_styles(&self.computed, &mut text, mask.as_ref());
+            let font_size = self
+                .computed
+                .font_size
+                .filter(|_| is_animated_font_size && !masked(&["font_size"]));
+            match (font_size, prev_font_size) {
+                (Some(font_size), Some(Val::Px(prev))) => {
+                    // Animate from the current size, or start at the target if the size
+                    // wasn't animated before, like the transform.
+                    let transition = self
+                        .computed
+                        .transitions
+                        .iter()
+                        .find(|tr| tr.property == TransitionProperty::FontSize)
+                        .unwrap();
+                    let value = match e.get_mut::<AnimatedFontSize>() {
+                        Some(mut anim) => {
+                            anim.0.retarget(prev, font_size);
+                            adopt_transition(&mut anim.0.state, transition);
+                            anim.0.value()
+                        }
+                        None => {
+                            e.insert(AnimatedFontSize(AnimatedLayoutProp::starting_at(
+                                transition.clone(),
+                                font_size,
+                            )));
+                            font_size
+                        }
+                    };
+                    let mut text = e.get_mut::<Text>().unwrap();
+                    if text.length(TransitionProperty::FontSize) != Some(Val::Px(value)) {
+                        text.set_length(TransitionProperty::FontSize, value);
+                    }
+                }
+                _ => {
+                    e.remove::<AnimatedFontSize>();
+                }
+            }
+            if !masked(&["text_overflow"]) {
+                update_text_overflow(&mut e, self.computed.text_overflow);
+            }
+        }
+        if self.text_only {
+            return;
+        }
+
+        let mut next_style = self.computed.style;
         let bg_image = self.computed.image_handle;
 
         // If any layout properties are animated, insert animation components and mutate
@@ -61,267 +170,394 @@ impl Command for UpdateComputedStyle {
                 None => next_style.clone(),
             };
 
-            // TODO: Make sure the set of transitions hasn't changed.
+            // The computed style only holds the fields which the styles set, so the rest are
+            // taken from the current style, and transitions on them animate to their current
+            // values.
+            let mut target = prev_style.clone();
+            self.computed.style_fields.copy(&next_style, &mut target);
+            next_style = target;
 
-            // If there's already animations
-            if let Some(mut anim) = e.get_mut::<AnimatedLayout>() {
-                for (prop, trans) in anim.0.iter_mut() {
-                    trans.restart_if_changed(*prop, &prev_style, &next_style);
-                    trans.update(*prop, &mut next_style, 0., true);
-                }
-            } else {
-                let mut anim =
-                    AnimatedLayout(HashMap::with_capacity(self.computed.transitions.len()));
+            // Lengths are animated in pixels, so relative lengths are resolved against the
+            // size of the parent and the window. Properties set to `auto` aren't animated.
+            let resolve = |prop: TransitionProperty, style: &Style| {
+                let val = prop.layout_val(style)?;
+                let basis = percent_basis(prop, self.parent_size);
+                resolve_val(val, basis, self.viewport).map(|px| (val, px))
+            };
+
+            if !e.contains::<AnimatedLayout>() {
+                e.insert(AnimatedLayout(HashMap::with_capacity(
+                    self.computed.transitions.len(),
+                )));
+            }
+            let mut anim = e.get_mut::<AnimatedLayout>().unwrap();
+            let anim = &mut anim.0;
+            // Drop animations for properties which no longer have a transition.
+            anim.retain(|prop, _| {
                 self.computed
                     .transitions
                     .iter()
-                    .for_each(|tr| match tr.property {
-                        TransitionProperty::Left
-                        | TransitionProperty::Top
-                        | TransitionProperty::Right
-                        | TransitionProperty::Bottom
-                        | TransitionProperty::Height
-                        | TransitionProperty::Width
-                        | TransitionProperty::BorderLeft
-                        | TransitionProperty::BorderTop
-                        | TransitionProperty::BorderRight
-                        | TransitionProperty::BorderBottom => {
-                            let mut ap = AnimatedLayoutProp::new(TransitionState {
-                                transition: tr.clone(),
-                                clock: 0.,
-                            });
-                            ap.update(tr.property, &mut next_style, 0., true);
-                            anim.0.insert(tr.property, ap);
+                    .any(|tr| tr.property == *prop)
+            });
+            for tr in self.computed.transitions.iter() {
+                if tr.property.layout_val(&next_style).is_none() {
+                    continue;
+                }
+                let Some((declared, target)) = resolve(tr.property, &next_style) else {
+                    anim.remove(&tr.property);
+                    continue;
+                };
+                match anim.get_mut(&tr.property) {
+                    Some(trans) => {
+                        if let Some((_, current)) = resolve(tr.property, &prev_style) {
+                            trans.retarget_resolved(current, target, declared);
+                            adopt_transition(&mut trans.state, tr);
                         }
-                        _ => (),
-                    });
-                e.insert(anim);
+                        trans.update(tr.property, &mut next_style, 0., true);
+                    }
+                    None => {
+                        // Start at the target if the property wasn't animated before, like
+                        // the transform.
+                        let mut trans = AnimatedLayoutProp::starting_at(tr.clone(), target);
+                        trans.retarget_resolved(target, target, declared);
+                        anim.insert(tr.property, trans);
+                    }
+                }
             }
+        } else {
+            e.remove::<AnimatedLayout>();
         }
 
         if let Some(mut existing_style) = e.get_mut::<Style>() {
-            // Update the existing style
-            if !existing_style.eq(&next_style) {
-                *existing_style = next_style;
+            // Update only the fields which the styles set, apart from masked ones, so that
+            // other systems can own the rest, and writes they made since the style was
+            // computed are kept.
+            let mut fields = self.computed.style_fields;
+            if let Some(mask) = &mask {
+                fields = fields - mask.masked_style_fields();
+            }
+            if !fields.eq(&existing_style, &next_style) {
+                fields.copy(&next_style, &mut existing_style);
             }
         } else {
             // Insert a new Style component
             e.insert(next_style);
         }
 
-        if let Some(mut text) = e.get_mut::<Text>() {
-            // White is the default.
-            let color = self.computed.color.unwrap_or(Color::WHITE);
-            for section in text.sections.iter_mut() {
-                if section.style.color != color {
-                    section.style.color = color;
-                }
-            }
+        // Bevy draws the image of a node tinted by its `BackgroundColor`, and can't fill the
+        // node behind the image. So with an image, the background color is used as the tint,
+        // unless there's an image tint, and the image is drawn as is if neither is set.
+        let background_color = match bg_image {
+            Some(_) => Some(
+                self.computed
+                    .image_tint
+                    .or(self.computed.background_color)
+                    .unwrap_or(Color::WHITE),
+            ),
+            None => self.computed.background_color,
+        };
+        // When the image is added or removed, the color switches between tinting the image and
+        // filling the node, so animating it would briefly show a fill of the tint color.
+        let image_changed =
+            !masked(&["background_image"]) && e.contains::<UiImage>() != bg_image.is_some();
 
-            if let Some(ws) = self.computed.line_break {
-                if text.linebreak_behavior != ws {
-                    text.linebreak_behavior = ws;
+        let background_color = if is_animated_bg_color {
+            let transition = transition_for(
+                &self.computed.transitions,
+                TransitionProperty::BackgroundColor,
+            );
+            let current = e.get::<BackgroundColor>().map(|bg| bg.0);
+            match (background_color, e.get_mut::<AnimatedBackgroundColor>()) {
+                (target, Some(mut anim)) if !image_changed => {
+                    // A background which is removed fades out instead.
+                    let target = target.unwrap_or(transparent(anim.0.target));
+                    anim.0
+                        .retarget(current.unwrap_or(transparent(target)), target);
+                    adopt_transition(&mut anim.0.state, transition);
+                    Some(anim.0.value())
                 }
-            }
-
-            if let Some(font_size) = self.computed.font_size {
-                for section in text.sections.iter_mut() {
-                    if section.style.font_size != font_size {
-                        section.style.font_size = font_size;
-                    }
+                (Some(target), _) => {
+                    // Start at the target if the color wasn't animated before, like the
+                    // transform.
+                    e.insert(AnimatedBackgroundColor(AnimatedColor::starting_at(
+                        transition.clone(),
+                        target,
+                    )));
+                    Some(target)
                 }
-            }
-
-            if let Some(ref font) = self.computed.font_handle {
-                for section in text.sections.iter_mut() {
-                    if section.style.font != *font {
-                        section.style.font = font.clone();
-                    }
+                (None, _) => {
+                    e.remove::<AnimatedBackgroundColor>();
+                    None
                 }
             }
-        }
-
-        if is_animated_bg_color {
-            match e.get_mut::<AnimatedBackgroundColor>() {
-                Some(_) => todo!(),
-                None => todo!(),
-            }
         } else {
             e.remove::<AnimatedBackgroundColor>();
-            match e.get_mut::<BackgroundColor>() {
-                Some(mut bg_comp) => {
-                    if self.computed.background_color.is_none() {
-                        if bg_image.is_none() {
-                            // Remove the background
-                            e.remove::<BackgroundColor>();
-                        }
-                    } else {
-                        let color = self.computed.background_color.unwrap();
-                        // Mutate the background
-                        if bg_comp.0 != color {
-                            bg_comp.0 = color
-                        }
+            background_color
+        };
+        if !masked(&["background_color", "background_image_tint"]) {
+            match (background_color, e.get_mut::<BackgroundColor>()) {
+                (Some(color), Some(mut bg_comp)) => {
+                    // Mutate the background
+                    if bg_comp.0 != color {
+                        bg_comp.0 = color
                     }
                 }
 
-                None => {
-                    if self.computed.background_color.is_some() {
-                        // Insert a new background
-                        e.insert(BackgroundColor(self.computed.background_color.unwrap()));
-                    } else if bg_image.is_some() {
-                        // Images require a background color to be set.
-                        e.insert(BackgroundColor::DEFAULT);
-                    }
+                (None, Some(_)) => {
+                    // Remove the background
+                    e.remove::<BackgroundColor>();
                 }
+
+                (Some(color), None) => {
+                    // Insert a new background
+                    e.insert(BackgroundColor(color));
+                }
+
+                (None, None) => {}
             }
         }
 
-        if is_animated_border_color {
-            match e.get_mut::<AnimatedBorderColor>() {
-                Some(_) => todo!(),
-                None => todo!(),
+        let border_color = if is_animated_border_color {
+            let transition =
+                transition_for(&self.computed.transitions, TransitionProperty::BorderColor);
+            let current = e.get::<BorderColor>().map(|bc| bc.0);
+            match (
+                self.computed.border_color,
+                e.get_mut::<AnimatedBorderColor>(),
+            ) {
+                (target, Some(mut anim)) => {
+                    // A border color which is removed fades out instead.
+                    let target = target.unwrap_or(transparent(anim.0.target));
+                    anim.0
+                        .retarget(current.unwrap_or(transparent(target)), target);
+                    adopt_transition(&mut anim.0.state, transition);
+                    Some(anim.0.value())
+                }
+                (Some(target), None) => {
+                    e.insert(AnimatedBorderColor(AnimatedColor::starting_at(
+                        transition.clone(),
+                        target,
+                    )));
+                    Some(target)
+                }
+                (None, _) => {
+                    e.remove::<AnimatedBorderColor>();
+                    None
+                }
             }
         } else {
             e.remove::<AnimatedBorderColor>();
-            match e.get_mut::<BorderColor>() {
-                Some(mut bc_comp) => {
-                    if self.computed.border_color.is_none() {
-                        // Remove the border color
-                        e.remove::<BorderColor>();
-                    } else {
-                        let color = self.computed.border_color.unwrap();
-                        if bc_comp.0 != color {
-                            bc_comp.0 = color
-                        }
+            self.computed.border_color
+        };
+        if !masked(&["border_color"]) {
+            match (border_color, e.get_mut::<BorderColor>()) {
+                (Some(color), Some(mut bc_comp)) => {
+                    if bc_comp.0 != color {
+                        bc_comp.0 = color
                     }
                 }
 
-                None => {
-                    if self.computed.border_color.is_some() {
-                        // Insert a new background color
-                        e.insert(BorderColor(self.computed.border_color.unwrap()));
-                    }
+                (None, Some(_)) => {
+                    // Remove the border color
+                    e.remove::<BorderColor>();
+                }
+
+                (Some(color), None) => {
+                    // Insert a new border color
+                    e.insert(BorderColor(color));
                 }
+
+                (None, None) => {}
             }
         }
 
-        match e.get_mut::<UiImage>() {
-            Some(mut img) => {
-                match bg_image {
-                    Some(src) => {
-                        if img.texture != src {
-                            img.texture = src;
+        if !masked(&["background_image"]) {
+            match e.get_mut::<UiImage>() {
+                Some(mut img) => {
+                    match bg_image {
+                        Some(src) => {
+                            if img.texture != src {
+                                img.texture = src;
+                            }
+                            if img.flip_x != self.computed.flip_x
+                                && !masked(&["background_image_flip_x"])
+                            {
+                                img.flip_x = self.computed.flip_x;
+                            }
+                            if img.flip_y != self.computed.flip_y
+                                && !masked(&["background_image_flip_y"])
+                            {
+                                img.flip_y = self.computed.flip_y;
+                            }
                         }
-                        if img.flip_x != self.computed.flip_x {
-                            img.flip_x = self.computed.flip_x;
+                        None => {
+                            // Remove the image.
+                            e.remove::<UiImage>();
                         }
-                        if img.flip_y != self.computed.flip_y {
-                            img.flip_y = self.computed.flip_y;
-                        }
-                    }
-                    None => {
-                        // Remove the image.
-                        e.remove::<UiImage>();
                     }
                 }
-            }
 
-            None => {
-                if let Some(src) = bg_image {
-                    // Create image component
-                    e.insert((
-                        UiImage {
-                            texture: src,
-                            flip_x: self.computed.flip_x,
-                            flip_y: self.computed.flip_y,
-                        },
-                        ContentSize::default(),
-                        UiImageSize::default(),
-                    ));
+                None => {
+                    if let Some(src) = bg_image {
+                        // Create image component
+                        e.insert((
+                            UiImage {
+                                texture: src,
+                                flip_x: self.computed.flip_x,
+                                flip_y: self.computed.flip_y,
+                            },
+                            ContentSize::default(),
+                            UiImageSize::default(),
+                        ));
+                    }
                 }
             }
         }
 
-        // Update outline
-        match (self.computed.outline_color, e.get_mut::<Outline>()) {
-            (Some(color), Some(mut outline)) => {
-                outline.width = self.computed.outline_width;
-                outline.offset = self.computed.outline_offset;
-                outline.color = color;
+        // Update outline. An outline which disappears while its color is animated fades out
+        // in place instead, and one which appears while animated grows from nothing and fades
+        // in.
+        let current_outline = e.get::<Outline>().cloned();
+        let fades_out = self
+            .computed
+            .transitions
+            .iter()
+            .any(|tr| tr.property == TransitionProperty::OutlineColor);
+        let next_outline = match (self.computed.outline_color, &current_outline) {
+            (Some(color), _) => Some(Outline {
+                width: self.computed.outline_width,
+                offset: self.computed.outline_offset,
+                color,
+            }),
+            (None, Some(current)) if fades_out => Some(Outline {
+                color: current.color.with_a(0.),
+                ..*current
+            }),
+            (None, _) => None,
+        };
+        match (next_outline, current_outline) {
+            (Some(mut next), Some(current)) => {
+                if masked(&["outline_width"]) {
+                    next.width = current.width;
+                }
+                if masked(&["outline_offset"]) {
+                    next.offset = current.offset;
+                }
+                if masked(&["outline_color"]) {
+                    next.color = current.color;
+                }
+                if is_animated_outline {
+                    let transitions = &self.computed.transitions;
+                    match e.get_mut::<AnimatedOutline>() {
+                        Some(mut anim) => anim.retarget(&current, &mut next, transitions),
+                        None => {
+                            let mut anim = AnimatedOutline::default();
+                            anim.retarget(&current, &mut next, transitions);
+                            e.insert(anim);
+                        }
+                    }
+                } else {
+                    e.remove::<AnimatedOutline>();
+                }
+                let mut outline = e.get_mut::<Outline>().unwrap();
+                if outline.width != next.width
+                    || outline.offset != next.offset
+                    || outline.color != next.color
+                {
+                    *outline = next;
+                }
             }
-            (None, Some(_)) => {
-                e.remove::<Outline>();
+            (None, Some(_)) if !masked(&["outline_color"]) => {
+                e.remove::<(Outline, AnimatedOutline)>();
             }
-            (Some(color), None) => {
-                e.insert(Outline {
-                    width: self.computed.outline_width,
-                    offset: self.computed.outline_offset,
-                    color,
-                });
+            (Some(mut next), None) if !masked(&["outline_color"]) => {
+                if is_animated_outline {
+                    let start = Outline {
+                        width: Val::Px(0.),
+                        offset: next.offset,
+                        color: next.color.with_a(0.),
+                    };
+                    let mut anim = AnimatedOutline::default();
+                    anim.retarget(&start, &mut next, &self.computed.transitions);
+                    e.insert(anim);
+                }
+                e.insert(next);
             }
-            (None, None) => {}
+            _ => {}
         }
 
-        // Update Z-Index
-        match (self.computed.z_index, e.get::<ZIndex>()) {
-            // Don't change if value is the same. Also, local(0) is the same as not at all.
-            (ZIndex::Local(zi), Some(ZIndex::Local(zo))) if zi != 0 && zi == *zo => {}
-            (ZIndex::Global(zi), Some(ZIndex::Global(zo))) if zi == *zo => {}
-            (ZIndex::Local(zi), Some(_)) if zi != 0 => {
-                e.insert(ZIndex::Local(zi));
-            }
-            (ZIndex::Global(zi), Some(_)) => {
-                e.insert(ZIndex::Global(zi));
+        // Update Z-Index. While the element is raised for a transform animation, the z-index
+        // it returns to is updated instead.
+        if !masked(&["z_index"]) {
+            if let Some(mut elevated) = e.get_mut::<ElevatedZIndex>() {
+                let prior = styled_z_index(self.computed.z_index);
+                if !same_z_index(elevated.prior, prior) {
+                    elevated.prior = prior;
+                    let raised = elevated.raised();
+                    e.insert(raised);
+                }
+            } else {
+                let current = e.get::<ZIndex>().copied();
+                match styled_z_index(self.computed.z_index) {
+                    z_index if same_z_index(z_index, current) => {}
+                    Some(z_index) => {
+                        e.insert(z_index);
+                    }
+                    None => {
+                        e.remove::<ZIndex>();
+                    }
+                }
             }
-            (_, Some(_)) => {
-                e.remove::<ZIndex>();
+        }
+        let elevate = self.computed.elevate_while_animating && !masked(&["z_index"]);
+        if elevate != e.contains::<ElevateWhileAnimating>() {
+            if elevate {
+                e.insert(ElevateWhileAnimating);
+            } else {
+                e.remove::<ElevateWhileAnimating>();
             }
-            (_, None) => {}
         }
 
-        UpdateComputedStyle::update_picking(self.computed.pickable, &mut e);
+        if !masked(&["pointer_events"]) {
+            UpdateComputedStyle::update_picking(self.computed.pickable, &mut e);
+        }
 
+        let has_transform_props = (self.computed.translation.is_some()
+            && !masked(&["translation"]))
+            || (self.computed.scale_x.is_some() && !masked(&["scale_x", "scale"]))
+            || (self.computed.scale_y.is_some() && !masked(&["scale_y", "scale"]))
+            || (self.computed.rotation.is_some() && !masked(&["rotation"]));
         let mut transform = Transform::default();
         transform.translation = self.computed.translation.unwrap_or(transform.translation);
         transform.scale.x = self.computed.scale_x.unwrap_or(1.);
         transform.scale.y = self.computed.scale_y.unwrap_or(1.);
         transform.rotate_z(self.computed.rotation.unwrap_or(0.));
+        // Leave the parts of the transform which other systems own as they are.
+        if let (Some(mask), Some(current)) = (&mask, e.get::<Transform>()) {
+            mask.keep_masked_transform(current, &mut transform);
+        }
         if is_animated_transform {
-            let prev_transform = *e.get_mut::<Transform>().unwrap();
-            let transition = self
-                .computed
-                .transitions
-                .iter()
-                .find(|t| t.property == TransitionProperty::Transform)
-                .unwrap();
+            // Some UI crates insert the `Transform` later; start the animation at the target.
+            let prev_transform = match e.get::<Transform>() {
+                Some(tr) => *tr,
+                None => {
+                    e.insert(transform);
+                    transform
+                }
+            };
+            let transitions = &self.computed.transitions;
             match e.get_mut::<AnimatedTransform>() {
-                Some(at) => {
-                    if at.target.translation != transform.translation
-                        || at.target.scale != transform.scale
-                        || at.target.rotation != transform.rotation
-                    {
-                        e.insert(AnimatedTransform {
-                            state: TransitionState {
-                                transition: transition.clone(),
-                                clock: 0.,
-                            },
-                            origin: prev_transform,
-                            target: transform,
-                        });
+                Some(mut at) => {
+                    if at.needs_retarget(&transform, transitions) {
+                        at.retarget(&prev_transform, transform, transitions);
                     }
                 }
                 None => {
-                    e.insert(AnimatedTransform {
-                        state: TransitionState {
-                            transition: transition.clone(),
-                            clock: 0.,
-                        },
-                        origin: transform,
-                        target: transform,
-                    });
+                    e.insert(AnimatedTransform::new(transform, transitions));
                 }
             }
         } else {
+            e.remove::<AnimatedTransform>();
             match e.get_mut::<Transform>() {
                 Some(tr) => {
                     if tr.translation != transform.translation
@@ -332,14 +568,148 @@ impl Command for UpdateComputedStyle {
                     }
                 }
                 None => {
-                    panic!("Element has no transform!")
+                    // Some UI crates insert the `Transform` later, so only insert one if
+                    // it's needed.
+                    if has_transform_props {
+                        e.insert(transform);
+                    }
                 }
             }
         }
     }
 }
 
+/// Return the [`ZIndex`] an element should have when its styles set `z_index`. `local(0)` is
+/// the same as not having one at all.
+fn styled_z_index(z_index: ZIndex) -> Option<ZIndex> {
+    match z_index {
+        ZIndex::Local(0) => None,
+        z_index => Some(z_index),
+    }
+}
+
+/// Return whether two optional [`ZIndex`] values are the same, since `ZIndex` can't be compared.
+pub(crate) fn same_z_index(a: Option<ZIndex>, b: Option<ZIndex>) -> bool {
+    match (a, b) {
+        (Some(ZIndex::Local(a)), Some(ZIndex::Local(b)))
+        | (Some(ZIndex::Global(a)), Some(ZIndex::Global(b))) => a == b,
+        (None, None) => true,
+        _ => false,
+    }
+}
+
 impl UpdateComputedStyle {
+    /// Treat the element's entrance animations as transitions of their properties, replacing
+    /// any declared ones, from when they start until they finish. Returns the style they start
+    /// from if they start now, which is when the first style declaring them is applied.
+    fn merge_animate_in(&mut self, world: &mut World) -> Option<ComputedStyle> {
+        if self.computed.animate_in.is_empty() {
+            return None;
+        }
+        let now = world
+            .get_resource::<Time>()
+            .map_or(0., |time| time.elapsed_seconds());
+        let mut e = world.get_entity_mut(self.entity)?;
+        let origin = match e.get::<AnimatedIn>() {
+            Some(played) if now >= played.until => return None,
+            Some(_) => None,
+            None => {
+                let until = self
+                    .computed
+                    .animate_in
+                    .iter()
+                    .map(|anim| now + anim.transition.delay + anim.transition.duration)
+                    .fold(now, f32::max);
+                e.insert(AnimatedIn { until });
+                Some(self.computed.animate_in_origin())
+            }
+        };
+        let transitions = &mut self.computed.transitions;
+        for anim in self.computed.animate_in.iter() {
+            transitions.retain(|tr| tr.property != anim.transition.property);
+            transitions.insert(0, anim.transition.clone());
+        }
+        origin
+    }
+
+    /// Set the properties animated in to their starting values, and start their animations
+    /// there, so that applying the style animates them to their targets.
+    fn start_animate_in(&self, e: &mut EntityWorldMut<'_>, origin: &ComputedStyle) {
+        let transitions = &self.computed.transitions;
+        let animated = |prop: TransitionProperty| transitions.iter().find(|tr| tr.property == prop);
+        // The parts of the transform which are animated in start from their values in
+        // `origin`, and the rest, which other systems may own, are left as they are.
+        let mut start = e.get::<Transform>().copied();
+        for anim in self.computed.animate_in.iter() {
+            let prop = anim.transition.property;
+            // Masked properties have had their transitions dropped.
+            let Some(tr) = animated(prop) else {
+                continue;
+            };
+            match prop {
+                TransitionProperty::Scale => {
+                    let start = start.get_or_insert_with(Transform::default);
+                    start.scale.x = origin.scale_x.unwrap_or(1.);
+                    start.scale.y = origin.scale_y.unwrap_or(1.);
+                }
+                TransitionProperty::Rotation => {
+                    let rotation = Quat::from_rotation_z(origin.rotation.unwrap_or(0.));
+                    start.get_or_insert_with(Transform::default).rotation = rotation;
+                }
+                TransitionProperty::Translation => {
+                    let translation = origin.translation.unwrap_or_default();
+                    start.get_or_insert_with(Transform::default).translation = translation;
+                }
+                TransitionProperty::FontSize => {
+                    let (Some(from), Some(mut text)) = (origin.font_size, e.get_mut::<Text>())
+                    else {
+                        continue;
+                    };
+                    text.set_length(prop, from);
+                    e.insert(AnimatedFontSize(AnimatedLayoutProp::starting_at(
+                        tr.clone(),
+                        from,
+                    )));
+                }
+                TransitionProperty::OutlineWidth
+                | TransitionProperty::OutlineOffset
+                | TransitionProperty::OutlineColor => {
+                    if let Some(color) = origin.outline_color {
+                        e.insert(Outline {
+                            width: origin.outline_width,
+                            offset: origin.outline_offset,
+                            color,
+                        });
+                        e.remove::<AnimatedOutline>();
+                    }
+                }
+                _ => {
+                    let Some(val) = prop.layout_val(&origin.style) else {
+                        continue;
+                    };
+                    let basis = percent_basis(prop, self.parent_size);
+                    let Some(from) = resolve_val(val, basis, self.viewport) else {
+                        continue;
+                    };
+                    if !e.contains::<Style>() {
+                        e.insert(self.computed.style.clone());
+                    }
+                    e.get_mut::<Style>().unwrap().set_val(prop, val);
+                    if !e.contains::<AnimatedLayout>() {
+                        e.insert(AnimatedLayout::default());
+                    }
+                    let mut layout = e.get_mut::<AnimatedLayout>().unwrap();
+                    layout
+                        .0
+                        .insert(prop, AnimatedLayoutProp::starting_at(tr.clone(), from));
+                }
+            }
+        }
+        if let Some(start) = start.filter(|start| Some(start) != e.get::<Transform>()) {
+            e.insert((start, AnimatedTransform::new(start, transitions)));
+        }
+    }
+
     #[cfg(feature = "bevy_mod_picking")]
     fn update_picking(pickable: Option<PointerEvents>, e: &mut EntityWorldMut<'_>) {
         // Update Pickable
@@ -364,3 +734,1661 @@ impl UpdateComputedStyle {
     #[cfg(not(feature = "bevy_mod_picking"))]
     fn update_picking(_pickable: Option<PointerEvents>, _e: &mut EntityWorldMut<'_>) {}
 }
+
+/// Return `color` made fully transparent, which a color fades to when it's removed.
+fn transparent(color: Color) -> Color {
+    color.with_a(0.)
+}
+
+/// Return the transition for `property`, which must be one of `transitions`.
+fn transition_for(transitions: &[Transition], property: TransitionProperty) -> &Transition {
+    transitions
+        .iter()
+        .find(|tr| tr.property == property)
+        .unwrap()
+}
+
+/// Custom command that updates only the text styles of an entity. This is used for nodes
+/// which have no styles of their own, but which inherit text styles from an ancestor: such
+/// nodes should not have any of their other components (background, image, etc.) touched.
+pub struct UpdateTextStyle {
+    pub(crate) entity: Entity,
+    pub(crate) computed: ComputedStyle,
+}
+
+impl Command for UpdateTextStyle {
+    fn apply(self, world: &mut World) {
+        notify_watchers(world, self.entity, &self.computed);
+        let Some(mut e) = world.get_entity_mut(self.entity) else {
+            return;
+        };
+
+        let mask = e.get::<PeacockPropertyMask>().cloned();
+        if let Some(mut text) = e.get_mut::<Text>() {
+            update_text_styles(&self.computed, &mut text, mask.as_ref());
+        }
+    }
+}
+
+/// A style update for a single entity, waiting to be applied.
+pub(crate) enum StyleUpdate {
+    Computed(UpdateComputedStyle),
+    Text(UpdateTextStyle),
+}
+
+/// Style updates computed during the current frame, which are applied together by
+/// [`ApplyStyleUpdates`]. If an entity is computed more than once before the updates are
+/// applied, only the last result is kept.
+#[derive(Resource, Default)]
+pub struct PendingStyleUpdates {
+    /// Updates in the order in which entities were first computed.
+    updates: Vec<(Entity, StyleUpdate)>,
+    /// Index of each entity in `updates`.
+    index: HashMap<Entity, usize>,
+    /// Number of times an entity was computed again before its previous update was applied,
+    /// during the current frame.
+    redundant: usize,
+}
+
+impl PendingStyleUpdates {
+    /// Start counting redundant updates afresh, if the previous frame's updates have been
+    /// applied. Passes which run before then are part of the same frame, and add to its count.
+    pub(crate) fn start_pass(&mut self) {
+        if self.updates.is_empty() {
+            self.redundant = 0;
+        }
+    }
+
+    pub(crate) fn push(&mut self, entity: Entity, update: StyleUpdate) {
+        match self.index.get(&entity) {
+            Some(&index) => {
+                let mut update = update;
+                // A full update which is pending must still be applied in full.
+                if let (StyleUpdate::Computed(next), StyleUpdate::Computed(prev)) =
+                    (&mut update, &self.updates[index].1)
+                {
+                    next.text_only &= prev.text_only;
+                }
+                self.updates[index].1 = update;
+                self.redundant += 1;
+                debug!("Style of {:?} computed more than once in a frame", entity);
+            }
+            None => {
+                self.index.insert(entity, self.updates.len());
+                self.updates.push((entity, update));
+            }
+        }
+    }
+
+    /// Return whether there are no updates waiting to be applied.
+    pub fn is_empty(&self) -> bool {
+        self.updates.is_empty()
+    }
+
+    /// Return the number of times any entity has had its style computed more than once in the
+    /// most recent frame. Only the last result is applied, so a non-zero count indicates
+    /// redundant work.
+    pub fn redundant_count(&self) -> usize {
+        self.redundant
+    }
+}
+
+/// Custom command that applies all of the [`PendingStyleUpdates`] at once.
+pub struct ApplyStyleUpdates;
+
+impl Command for ApplyStyleUpdates {
+    fn apply(self, world: &mut World) {
+        let Some(mut pending) = world.get_resource_mut::<PendingStyleUpdates>() else {
+            return;
+        };
+        pending.index.clear();
+        let updates = std::mem::take(&mut pending.updates);
+        let _span = info_span!("apply_style_updates", count = updates.len()).entered();
+        for (_, update) in updates {
+            match update {
+                StyleUpdate::Computed(cmd) => cmd.apply(world),
+                StyleUpdate::Text(cmd) => cmd.apply(world),
+            }
+        }
+    }
+}
+
+/// Set of `(entity, property)` pairs which have already been reported by
+/// [`warn_unset_transitions`], so that each is only reported once.
+#[cfg(any(test, feature = "diagnostics"))]
+#[derive(Resource, Default)]
+pub(crate) struct UnsetTransitionWarnings(pub(crate) HashSet<(Entity, TransitionProperty)>);
+
+/// Warn about transitions on properties which none of the entity's styles set, since such
+/// transitions have no effect.
+#[cfg(any(test, feature = "diagnostics"))]
+fn warn_unset_transitions(
+    world: &mut World,
+    entity: Entity,
+    computed: &ComputedStyle,
+    styles: &[StyleHandle],
+) {
+    let default_style = Style::default();
+    for tr in computed.transitions.iter() {
+        if computed.explicit_props.contains(tr.property)
+            || styles
+                .iter()
+                .any(|ss| ss.props().transition_properties().contains(tr.property))
+        {
+            continue;
+        }
+        let is_default = match tr.property {
+            TransitionProperty::Transform => {
+                computed.scale_x.is_none()
+                    && computed.scale_y.is_none()
+                    && computed.rotation.is_none()
+                    && computed.translation.is_none()
+            }
+            TransitionProperty::Scale => computed.scale_x.is_none() && computed.scale_y.is_none(),
+            TransitionProperty::Rotation => computed.rotation.is_none(),
+            TransitionProperty::Translation => computed.translation.is_none(),
+            TransitionProperty::BackgroundColor => computed.background_color.is_none(),
+            TransitionProperty::BorderColor => computed.border_color.is_none(),
+            TransitionProperty::Left => computed.style.left == default_style.left,
+            TransitionProperty::Top => computed.style.top == default_style.top,
+            TransitionProperty::Right => computed.style.right == default_style.right,
+            TransitionProperty::Bottom => computed.style.bottom == default_style.bottom,
+            TransitionProperty::Height => computed.style.height == default_style.height,
+            TransitionProperty::Width => computed.style.width == default_style.width,
+            TransitionProperty::BorderLeft => {
+                computed.style.border.left == default_style.border.left
+            }
+            TransitionProperty::BorderTop => computed.style.border.top == default_style.border.top,
+            TransitionProperty::BorderRight => {
+                computed.style.border.right == default_style.border.right
+            }
+            TransitionProperty::BorderBottom => {
+                computed.style.border.bottom == default_style.border.bottom
+            }
+            TransitionProperty::PaddingLeft => {
+                computed.style.padding.left == default_style.padding.left
+            }
+            TransitionProperty::PaddingRight => {
+                computed.style.padding.right == default_style.padding.right
+            }
+            TransitionProperty::PaddingTop => {
+                computed.style.padding.top == default_style.padding.top
+            }
+            TransitionProperty::PaddingBottom => {
+                computed.style.padding.bottom == default_style.padding.bottom
+            }
+            TransitionProperty::MarginLeft => {
+                computed.style.margin.left == default_style.margin.left
+            }
+            TransitionProperty::MarginRight => {
+                computed.style.margin.right == default_style.margin.right
+            }
+            TransitionProperty::MarginTop => computed.style.margin.top == default_style.margin.top,
+            TransitionProperty::MarginBottom => {
+                computed.style.margin.bottom == default_style.margin.bottom
+            }
+            TransitionProperty::RowGap => computed.style.row_gap == default_style.row_gap,
+            TransitionProperty::ColumnGap => computed.style.column_gap == default_style.column_gap,
+            TransitionProperty::OutlineWidth => computed.outline_width == Val::DEFAULT,
+            TransitionProperty::OutlineOffset => computed.outline_offset == Val::DEFAULT,
+            TransitionProperty::OutlineColor => computed.outline_color.is_none(),
+            TransitionProperty::FontSize => computed.font_size.is_none(),
+        };
+        if is_default
+            && world
+                .get_resource_or_insert_with(UnsetTransitionWarnings::default)
+                .0
+                .insert((entity, tr.property))
+        {
+            let declared_in: Vec<String> = styles
+                .iter()
+                .enumerate()
+                .filter(|(_, ss)| ss.props().declares_transition(tr.property))
+                .map(|(index, _)| format!("#{}", index))
+                .collect();
+            warn!(
+                "Entity {:?}: transition on {:?} (declared in style {} of {}) has no effect, \
+                because none of the element's styles set {:?}",
+                entity,
+                tr.property,
+                declared_in.join(", "),
+                styles.len(),
+                tr.property
+            );
+        }
+    }
+}
+
+/// Set of entities which have already been reported by [`warn_unanimatable_transitions`], so
+/// that each is only reported once.
+#[derive(Resource, Default)]
+pub(crate) struct UnanimatableTransitionWarnings(pub(crate) HashSet<Entity>);
+
+/// Forget the transition warnings about entities which were despawned or lost their styles, so
+/// that the sets of reported entities don't grow without bound.
+pub(crate) fn forget_transition_warnings(
+    mut removed: RemovedComponents<ElementStyles>,
+    #[cfg(any(test, feature = "diagnostics"))] unset: Option<ResMut<UnsetTransitionWarnings>>,
+    unanimatable: Option<ResMut<UnanimatableTransitionWarnings>>,
+) {
+    let removed: HashSet<Entity> = removed.read().collect();
+    if removed.is_empty() {
+        return;
+    }
+    #[cfg(any(test, feature = "diagnostics"))]
+    if let Some(mut unset) = unset {
+        unset.0.retain(|(entity, _)| !removed.contains(entity));
+    }
+    if let Some(mut unanimatable) = unanimatable {
+        unanimatable.0.retain(|entity| !removed.contains(entity));
+    }
+}
+
+/// Warn about layout transitions whose current or target value can't be resolved to pixels,
+/// which is `auto` for layout properties, and anything other than pixels for outlines. Such
+/// transitions don't animate. With the `diagnostics` feature, this also names the styles
+/// declaring the transition, and is a debug assertion.
+fn warn_unanimatable_transitions(
+    world: &mut World,
+    entity: Entity,
+    computed: &ComputedStyle,
+    #[cfg(any(test, feature = "diagnostics"))] styles: &[StyleHandle],
+) {
+    let current = world.get::<Style>(entity);
+    let animated = world.get::<AnimatedLayout>(entity);
+    let found = computed
+        .transitions
+        .iter()
+        // Transitions on properties which aren't set are reported by `warn_unset_transitions`.
+        .filter(|tr| computed.explicit_props.contains(tr.property))
+        .find_map(|tr| {
+            let target = match tr.property {
+                TransitionProperty::OutlineWidth => computed.outline_width,
+                TransitionProperty::OutlineOffset => computed.outline_offset,
+                _ => tr.property.layout_val(&computed.style)?,
+            };
+            // The current value is only interpolated from if the property is already animated.
+            let current = current
+                .filter(|_| animated.is_some_and(|anim| anim.0.contains_key(&tr.property)))
+                .and_then(|style| tr.property.layout_val(style));
+            let animatable = |val: &Val| match tr.property {
+                TransitionProperty::OutlineWidth | TransitionProperty::OutlineOffset => {
+                    matches!(val, Val::Px(_))
+                }
+                _ => *val != Val::Auto,
+            };
+            [Some(target), current]
+                .into_iter()
+                .flatten()
+                .find(|val| !animatable(val))
+                .map(|val| (tr.property, val))
+        });
+    let Some((property, val)) = found else {
+        return;
+    };
+    if !world
+        .get_resource_or_insert_with(UnanimatableTransitionWarnings::default)
+        .0
+        .insert(entity)
+    {
+        return;
+    }
+
+    #[cfg(any(test, feature = "diagnostics"))]
+    let declared_in = describe_transition_styles(world, property, styles);
+    #[cfg(not(any(test, feature = "diagnostics")))]
+    let declared_in = String::new();
+    let message = format!(
+        "Entity {:?}: transition on {:?}{} can't animate {} values",
+        entity,
+        property,
+        declared_in,
+        unit_name(val)
+    );
+    warn!("{}", message);
+    // Tests check for the warning instead.
+    #[cfg(all(feature = "diagnostics", not(test)))]
+    debug_assert!(false, "{}", message);
+}
+
+/// Describe the styles which declare a transition on `property`: by name if they are in the
+/// [`StyleRegistry`], otherwise by index, along with where they were defined if known.
+#[cfg(any(test, feature = "diagnostics"))]
+fn describe_transition_styles(
+    world: &World,
+    property: TransitionProperty,
+    styles: &[StyleHandle],
+) -> String {
+    let registry = world.get_resource::<StyleRegistry>();
+    let described: Vec<String> = styles
+        .iter()
+        .enumerate()
+        .filter(|(_, ss)| ss.props().declares_transition(property))
+        .map(|(index, ss)| {
+            let mut desc = match registry.and_then(|registry| registry.name_of(ss)) {
+                Some(name) => format!("'{}'", name),
+                None => format!("#{}", index),
+            };
+            if let Some(spans) = ss.source() {
+                desc.push_str(&format!(
+                    " at {}:{}:{}",
+                    spans.path.as_deref().unwrap_or("<stylesheet>"),
+                    spans.rule.start.line,
+                    spans.rule.start.column
+                ));
+            }
+            desc
+        })
+        .collect();
+    if described.is_empty() {
+        String::new()
+    } else {
+        format!(" (declared in style {})", described.join(", "))
+    }
+}
+
+/// Apply the inheritable text properties of a computed style to a text component, apart from
+/// those which the entity's mask stops Peacock writing.
+fn update_text_styles(
+    computed: &ComputedStyle,
+    text: &mut Mut<Text>,
+    mask: Option<&PeacockPropertyMask>,
+) {
+    let masked = |key| mask.is_some_and(|mask| mask.masks(key));
+    // Borrowing the sections mutably marks the text as changed, which restyles it again, so
+    // only do so if a section differs.
+    let differs = |text: &Text, differs: &dyn Fn(&TextStyle) -> bool| {
+        text.sections.iter().any(|section| differs(&section.style))
+    };
+
+    // White is the default.
+    let color = computed.color.unwrap_or(Color::WHITE);
+    if !masked("color") && differs(text, &|style| style.color != color) {
+        for section in text.sections.iter_mut() {
+            section.style.color = color;
+        }
+    }
+
+    if let Some(ws) = computed.line_break {
+        if text.linebreak_behavior != ws {
+            text.linebreak_behavior = ws;
+        }
+    }
+
+    if let Some(alignment) = computed.alignment.filter(|_| !masked("text_align")) {
+        if text.alignment != alignment {
+            text.alignment = alignment;
+        }
+    }
+
+    if let Some(font_size) = computed.font_size.filter(|_| !masked("font_size")) {
+        if differs(text, &|style| style.font_size != font_size) {
+            for section in text.sections.iter_mut() {
+                section.style.font_size = font_size;
+            }
+        }
+    }
+
+    if let Some(font) = computed.font_handle.as_ref().filter(|_| !masked("font")) {
+        if differs(text, &|style| style.font != *font) {
+            for section in text.sections.iter_mut() {
+                section.style.font = font.clone();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        describe_transition_styles, forget_transition_warnings, same_z_index, ApplyStyleUpdates,
+        PendingStyleUpdates, StyleUpdate, UnanimatableTransitionWarnings, UnsetTransitionWarnings,
+        UpdateComputedStyle,
+    };
+    use crate::animate::{
+        animate_bg_colors, animate_border_colors, animate_font_sizes, animate_layout,
+        animate_outlines, animate_transforms, elevate_animated_transforms, lerp_color,
+        AnimatedTransform, ElevatedZIndex, PeacockAnimationConfig,
+    };
+    use crate::test_utils::{apply_styles_once, plugin_test_app, set_hovered, test_app};
+    use crate::{
+        ElementClasses, ElementStyles, PeacockPropertyMask, PeacockSystemSet, StyleHandle,
+        StyleRegistry, Transition, TransitionProperty,
+    };
+    use bevy::ecs::system::{Command, RunSystemOnce};
+    use bevy::prelude::*;
+    use bevy::utils::Duration;
+    use bevy_peacock_style::{parse_stylesheet, ComputedStyle, StyleField};
+
+    #[test]
+    fn test_hover_scale_transform() {
+        let mut app = test_app();
+        let world = &mut app.world;
+
+        let mut stylesheet = parse_stylesheet(
+            "button {
+                rotation: 90deg;
+                :hover { scale: 1.1; }
+            }",
+        )
+        .unwrap();
+        let (_, style) = stylesheet.pop().unwrap();
+        let button = world
+            .spawn((
+                NodeBundle::default(),
+                ElementStyles::new(&[StyleHandle::new(style)]),
+                ElementClasses::default(),
+            ))
+            .id();
+
+        apply_styles_once(world);
+        let transform = world.get::<Transform>(button).unwrap();
+        assert_eq!(transform.scale, Vec3::ONE);
+        assert_eq!(
+            transform.rotation,
+            Quat::from_rotation_z(std::f32::consts::FRAC_PI_2)
+        );
+
+        set_hovered(world, Some(button));
+        apply_styles_once(world);
+        let transform = world.get::<Transform>(button).unwrap();
+        assert_eq!(transform.scale, Vec3::new(1.1, 1.1, 1.));
+    }
+
+    #[test]
+    fn test_disabled_hover_scale() {
+        let mut app = test_app();
+        let world = &mut app.world;
+
+        let base = StyleHandle::build(|ss| {
+            ss.scale(1.)
+                .transition(&[Transition {
+                    property: TransitionProperty::Scale,
+                    duration: 1.,
+                    ..default()
+                }])
+                .rotation(0.5)
+        });
+        let hover = StyleHandle::build(|ss| ss.selector(":hover", |ss| ss.scale(2.)));
+        let button = world
+            .spawn((
+                NodeBundle::default(),
+                ElementStyles::new([base.clone(), hover.clone()]),
+                ElementClasses::default(),
+            ))
+            .id();
+        set_hovered(world, Some(button));
+        apply_styles_once(world);
+        advance_transforms(world, 1.);
+        let transform = |world: &World| *world.get::<Transform>(button).unwrap();
+        assert_eq!(transform(world).scale, Vec3::new(2., 2., 1.));
+
+        // Disabling the handle animates the scale back, leaving the other styles alone.
+        let mut styles = world.get_mut::<ElementStyles>(button).unwrap();
+        assert!(styles.set_enabled(&hover, false));
+        assert!(!styles.is_enabled(&hover));
+        assert!(styles.is_enabled(&base));
+        // The flags still cover the disabled style, and survive being recomputed.
+        styles.recompute_flags();
+        assert!(styles.uses_hover());
+        apply_styles_once(world);
+        advance_transforms(world, 0.5);
+        assert_eq!(transform(world).scale, Vec3::new(1.5, 1.5, 1.));
+        advance_transforms(world, 0.5);
+        assert_eq!(transform(world).scale, Vec3::ONE);
+        assert_eq!(transform(world).rotation, Quat::from_rotation_z(0.5));
+
+        // Re-enabling it restores the same result as before.
+        let mut styles = world.get_mut::<ElementStyles>(button).unwrap();
+        assert!(styles.set_enabled(&hover, true));
+        apply_styles_once(world);
+        advance_transforms(world, 1.);
+        assert_eq!(transform(world).scale, Vec3::new(2., 2., 1.));
+
+        // Styles which the element doesn't have are reported.
+        let mut styles = world.get_mut::<ElementStyles>(button).unwrap();
+        assert!(!styles.set_enabled(&StyleHandle::default(), false));
+        assert!(styles.disabled.is_empty());
+    }
+
+    #[test]
+    fn test_elevate_while_animating() {
+        let mut app = test_app();
+        app.init_resource::<PeacockAnimationConfig>();
+        let world = &mut app.world;
+
+        let base = StyleHandle::build(|ss| {
+            ss.scale(1.)
+                .transition(&[Transition {
+                    property: TransitionProperty::Scale,
+                    duration: 1.,
+                    ..default()
+                }])
+                .elevate_while_animating(true)
+                .selector(":hover", |ss| ss.scale(2.))
+        });
+        let layered = StyleHandle::build(|ss| ss.z_index(2).selector(":hover", |ss| ss.z_index(5)));
+        let button = world
+            .spawn((
+                NodeBundle::default(),
+                ElementStyles::new([base.clone(), layered]),
+                ElementClasses::default(),
+            ))
+            .id();
+        let plain = world
+            .spawn((
+                NodeBundle::default(),
+                ElementStyles::new([base.clone()]),
+                ElementClasses::default(),
+            ))
+            .id();
+        let advance = |world: &mut World, seconds: f32| {
+            advance_transforms(world, seconds);
+            world.run_system_once(elevate_animated_transforms);
+        };
+        let z_index = |world: &World, entity| world.get::<ZIndex>(entity).copied();
+        let assert_z_index = |world: &World, entity, expected: Option<ZIndex>| {
+            let actual = z_index(world, entity);
+            assert!(
+                same_z_index(actual, expected),
+                "{:?} != {:?}",
+                actual,
+                expected
+            );
+        };
+        apply_styles_once(world);
+        advance(world, 0.);
+        assert_z_index(world, button, Some(ZIndex::Local(2)));
+        assert_z_index(world, plain, None);
+
+        // The z-index is raised while the scale animates, on top of the hover z-index, and
+        // restored exactly afterwards, including not having one at all.
+        set_hovered(world, Some(button));
+        apply_styles_once(world);
+        advance(world, 0.5);
+        assert_z_index(world, button, Some(ZIndex::Local(1005)));
+        assert_z_index(world, plain, None);
+        advance(world, 0.5);
+        assert_z_index(world, button, Some(ZIndex::Local(5)));
+        assert!(world.get::<ElevatedZIndex>(button).is_none());
+
+        set_hovered(world, Some(plain));
+        apply_styles_once(world);
+        advance(world, 0.5);
+        assert_z_index(world, plain, Some(ZIndex::Local(1000)));
+        // The button animates back to its own scale meanwhile.
+        assert_z_index(world, button, Some(ZIndex::Local(1002)));
+        advance(world, 0.5);
+        assert_z_index(world, plain, None);
+        assert!(world.get::<ElevatedZIndex>(plain).is_none());
+        assert_z_index(world, button, Some(ZIndex::Local(2)));
+
+        // Interrupting the animation keeps the element raised until the new one finishes, and
+        // a z-index which changes meanwhile is the one restored.
+        set_hovered(world, Some(button));
+        apply_styles_once(world);
+        advance(world, 0.5);
+        set_hovered(world, None);
+        apply_styles_once(world);
+        advance(world, 0.25);
+        assert_z_index(world, button, Some(ZIndex::Local(1002)));
+        advance(world, 0.5);
+        assert_z_index(world, button, Some(ZIndex::Local(1002)));
+        advance(world, 0.5);
+        assert_z_index(world, button, Some(ZIndex::Local(2)));
+
+        // Removing the transition ends the animation, and restores the z-index.
+        set_hovered(world, Some(plain));
+        apply_styles_once(world);
+        advance(world, 0.25);
+        assert_z_index(world, plain, Some(ZIndex::Local(1000)));
+        world
+            .entity_mut(plain)
+            .insert(ElementStyles::new([StyleHandle::build(|ss| ss.scale(1.))]));
+        apply_styles_once(world);
+        advance(world, 0.);
+        assert_z_index(world, plain, None);
+        assert!(world.get::<ElevatedZIndex>(plain).is_none());
+    }
+
+    #[test]
+    fn test_parsed_z_index() {
+        let mut app = test_app();
+        let world = &mut app.world;
+
+        let mut stylesheet = parse_stylesheet(
+            "local { z_index: 3; }
+            global { z_index: global(-2); }",
+        )
+        .unwrap();
+        let mut spawn = |world: &mut World| {
+            let (_, style) = stylesheet.remove(0);
+            world
+                .spawn((
+                    NodeBundle::default(),
+                    ElementStyles::new(&[StyleHandle::new(style)]),
+                ))
+                .remove::<ZIndex>()
+                .id()
+        };
+        let local = spawn(world);
+        let global = spawn(world);
+        assert!(world.get::<ZIndex>(local).is_none());
+        apply_styles_once(world);
+        assert!(same_z_index(
+            world.get::<ZIndex>(local).copied(),
+            Some(ZIndex::Local(3))
+        ));
+        assert!(same_z_index(
+            world.get::<ZIndex>(global).copied(),
+            Some(ZIndex::Global(-2))
+        ));
+    }
+
+    #[test]
+    fn test_z_index_from_selectors_only() {
+        let mut app = test_app();
+        app.init_resource::<PeacockAnimationConfig>();
+        let world = &mut app.world;
+
+        // Only the hover and class rules set a z-index, so the first pass removes the default.
+        let style = StyleHandle::build(|ss| {
+            ss.scale(1.)
+                .transition(&[Transition {
+                    property: TransitionProperty::Scale,
+                    duration: 1.,
+                    ..default()
+                }])
+                .elevate_while_animating(true)
+                .selector(":hover", |ss| ss.z_index(5))
+                .selector(".grow", |ss| ss.scale(2.))
+                .selector(".top", |ss| ss.z_index(7))
+        });
+        let button = world
+            .spawn((
+                NodeBundle::default(),
+                ElementStyles::new([style]),
+                ElementClasses::default(),
+            ))
+            .id();
+        let advance = |world: &mut World, seconds: f32| {
+            advance_transforms(world, seconds);
+            world.run_system_once(elevate_animated_transforms);
+        };
+        let assert_z_index = |world: &World, expected: Option<ZIndex>| {
+            let actual = world.get::<ZIndex>(button).copied();
+            assert!(
+                same_z_index(actual, expected),
+                "{:?} != {:?}",
+                actual,
+                expected
+            );
+        };
+        apply_styles_once(world);
+        assert_z_index(world, None);
+
+        set_hovered(world, Some(button));
+        apply_styles_once(world);
+        assert_z_index(world, Some(ZIndex::Local(5)));
+        set_hovered(world, None);
+        apply_styles_once(world);
+        assert_z_index(world, None);
+
+        // A z-index styled while the element is raised, with none to return to, is restored.
+        let mut classes = world.get_mut::<ElementClasses>(button).unwrap();
+        classes.add_class("grow");
+        apply_styles_once(world);
+        advance(world, 0.5);
+        assert_z_index(world, Some(ZIndex::Local(1000)));
+        let mut classes = world.get_mut::<ElementClasses>(button).unwrap();
+        classes.add_class("top");
+        apply_styles_once(world);
+        assert_z_index(world, Some(ZIndex::Local(1007)));
+        advance(world, 0.5);
+        assert!(world.get::<ElevatedZIndex>(button).is_none());
+        assert_z_index(world, Some(ZIndex::Local(7)));
+    }
+
+    #[test]
+    fn test_external_style_fields() {
+        let mut app = plugin_test_app();
+
+        // A splitter which drags the width every frame, after the styles are computed but
+        // before they're written.
+        fn drag_width(mut query: Query<&mut Style>, mut frame: Local<f32>) {
+            *frame += 1.;
+            for mut style in query.iter_mut() {
+                style.width = Val::Px(*frame);
+            }
+        }
+        app.add_systems(Update, drag_width.after(PeacockSystemSet));
+
+        let style = StyleHandle::build(|ss| {
+            ss.background_color(Color::WHITE)
+                .height(20.)
+                .selector(".active", |ss| ss.background_color(Color::RED))
+        });
+        let entity = app
+            .world
+            .spawn((
+                NodeBundle::default(),
+                ElementStyles::new([style]),
+                ElementClasses::default(),
+            ))
+            .id();
+        for frame in 1..=4 {
+            // Restyle every frame.
+            app.world
+                .entity_mut(entity)
+                .insert(ElementClasses::new(if frame % 2 == 0 {
+                    "active"
+                } else {
+                    ""
+                }));
+            app.update();
+            let style = app.world.get::<Style>(entity).unwrap();
+            assert_eq!(style.width, Val::Px(frame as f32));
+            assert_eq!(style.height, Val::Px(20.));
+            let bg = app.world.get::<BackgroundColor>(entity).unwrap().0;
+            assert_eq!(
+                bg,
+                if frame % 2 == 0 {
+                    Color::RED
+                } else {
+                    Color::WHITE
+                }
+            );
+        }
+
+        // Nor are masked fields written, even when the styles set them.
+        app.world
+            .entity_mut(entity)
+            .insert(PeacockPropertyMask::new(["height"]));
+        app.world.get_mut::<Style>(entity).unwrap().height = Val::Px(5.);
+        app.world
+            .entity_mut(entity)
+            .insert(ElementClasses::new("active"));
+        app.update();
+        assert_eq!(app.world.get::<Style>(entity).unwrap().height, Val::Px(5.));
+    }
+
+    #[test]
+    fn test_missing_transform() {
+        let mut world = World::new();
+        let node = world.spawn((Node::default(), Style::default())).id();
+
+        // No transform properties: nothing to insert.
+        UpdateComputedStyle {
+            entity: node,
+            computed: ComputedStyle::default(),
+            parent_size: Vec2::ZERO,
+            viewport: Vec2::ZERO,
+            text_only: false,
+            styles: Vec::new(),
+        }
+        .apply(&mut world);
+        assert!(world.get::<Transform>(node).is_none());
+
+        UpdateComputedStyle {
+            entity: node,
+            computed: ComputedStyle {
+                scale_x: Some(2.),
+                ..default()
+            },
+            parent_size: Vec2::ZERO,
+            viewport: Vec2::ZERO,
+            text_only: false,
+            styles: Vec::new(),
+        }
+        .apply(&mut world);
+        assert_eq!(
+            world.get::<Transform>(node).unwrap().scale,
+            Vec3::new(2., 1., 1.)
+        );
+
+        // An animated transform starts at its target when there's no existing transform.
+        let animated = world.spawn((Node::default(), Style::default())).id();
+        UpdateComputedStyle {
+            entity: animated,
+            computed: ComputedStyle {
+                rotation: Some(1.),
+                transitions: vec![Transition {
+                    property: TransitionProperty::Transform,
+                    duration: 0.3,
+                    ..default()
+                }],
+                ..default()
+            },
+            parent_size: Vec2::ZERO,
+            viewport: Vec2::ZERO,
+            text_only: false,
+            styles: Vec::new(),
+        }
+        .apply(&mut world);
+        let at = world.get::<AnimatedTransform>(animated).unwrap();
+        assert_eq!(at.origin, at.target);
+        assert_eq!(
+            *world.get::<Transform>(animated).unwrap(),
+            Transform::from_rotation(Quat::from_rotation_z(1.))
+        );
+    }
+
+    #[test]
+    fn test_text_align() {
+        let mut world = World::new();
+        let label = world
+            .spawn((
+                Node::default(),
+                Style::default(),
+                Text::from_section("Label", TextStyle::default()),
+            ))
+            .id();
+        let sheet = parse_stylesheet("LABEL { text_align: center; }").unwrap();
+        let (_, props) = &sheet[0];
+        let mut computed = ComputedStyle::default();
+        props.apply_attrs_to(props.props(), &mut computed);
+        assert_eq!(computed.alignment, Some(TextAlignment::Center));
+
+        world.clear_trackers();
+        UpdateComputedStyle {
+            entity: label,
+            computed,
+            parent_size: Vec2::ZERO,
+            viewport: Vec2::ZERO,
+            text_only: false,
+            styles: Vec::new(),
+        }
+        .apply(&mut world);
+        let text = world.entity(label).get_ref::<Text>().unwrap();
+        assert_eq!(text.alignment, TextAlignment::Center);
+        assert!(text.is_changed());
+
+        // Styles which don't set the alignment leave it alone.
+        world.clear_trackers();
+        UpdateComputedStyle {
+            entity: label,
+            computed: ComputedStyle::default(),
+            parent_size: Vec2::ZERO,
+            viewport: Vec2::ZERO,
+            text_only: false,
+            styles: Vec::new(),
+        }
+        .apply(&mut world);
+        assert_eq!(
+            world.get::<Text>(label).unwrap().alignment,
+            TextAlignment::Center
+        );
+    }
+
+    /// Advance the transform animations by `seconds`.
+    fn advance_transforms(world: &mut World, seconds: f32) {
+        let mut time = Time::<()>::default();
+        time.advance_by(Duration::from_secs_f32(seconds));
+        world.insert_resource(time);
+        world.run_system_once(animate_transforms);
+    }
+
+    fn add_class(world: &mut World, entity: Entity, class: &str) {
+        world
+            .get_mut::<ElementClasses>(entity)
+            .unwrap()
+            .add_class(class);
+    }
+
+    #[test]
+    fn test_independent_transform_transitions() {
+        let mut app = test_app();
+        let world = &mut app.world;
+
+        let transition = |property, duration| Transition {
+            property,
+            duration,
+            ..default()
+        };
+        let style = |transitions: &[Transition]| {
+            StyleHandle::build(|ss| {
+                ss.scale(1.)
+                    .translation(Vec3::ZERO)
+                    .transition(transitions)
+                    .selector(".on", |ss| ss.scale(2.).translation(Vec3::X * 100.))
+                    .selector(".far", |ss| ss.translation(Vec3::X * 200.))
+                    .selector(".big", |ss| ss.scale(3.))
+            })
+        };
+        let independent = style(&[
+            transition(TransitionProperty::Scale, 1.),
+            transition(TransitionProperty::Translation, 4.),
+        ]);
+        let shorthand = style(&[transition(TransitionProperty::Transform, 1.)]);
+        let mut spawn = |style: StyleHandle| {
+            world
+                .spawn((
+                    NodeBundle::default(),
+                    ElementStyles::new(&[style]),
+                    ElementClasses::default(),
+                ))
+                .id()
+        };
+        let a = spawn(independent);
+        let b = spawn(shorthand);
+        apply_styles_once(world);
+
+        add_class(world, a, "on");
+        add_class(world, b, "on");
+        apply_styles_once(world);
+        advance_transforms(world, 0.5);
+        let transform = |world: &World, e| *world.get::<Transform>(e).unwrap();
+        assert_eq!(transform(world, a).scale, Vec3::new(1.5, 1.5, 1.));
+        assert_eq!(transform(world, a).translation, Vec3::X * 12.5);
+        assert_eq!(transform(world, b).translation, Vec3::X * 50.);
+
+        // Retargeting the translation mid-flight doesn't restart the scale.
+        add_class(world, a, "far");
+        // With the shorthand, all parts share a clock, so changing the scale restarts the
+        // translation too.
+        add_class(world, b, "big");
+        apply_styles_once(world);
+        advance_transforms(world, 0.5);
+        let t = transform(world, a);
+        assert_eq!(t.scale, Vec3::new(2., 2., 1.));
+        assert_eq!(t.translation, Vec3::X * (12.5 + 187.5 * 0.125));
+        assert_eq!(t.rotation, Quat::IDENTITY);
+        let t = transform(world, b);
+        assert_eq!(t.scale, Vec3::new(2.25, 2.25, 1.));
+        assert_eq!(t.translation, Vec3::X * 75.);
+    }
+
+    #[test]
+    fn test_outline_and_font_size_transitions() {
+        let mut app = test_app();
+        app.init_resource::<PeacockAnimationConfig>();
+        let world = &mut app.world;
+
+        let transition = |property| Transition {
+            property,
+            duration: 1.,
+            ..default()
+        };
+        let style = StyleHandle::build(|ss| {
+            ss.font_size(16.)
+                .transition(&[
+                    transition(TransitionProperty::OutlineWidth),
+                    transition(TransitionProperty::OutlineColor),
+                    transition(TransitionProperty::FontSize),
+                ])
+                .selector(".focus", |ss| {
+                    ss.outline_color(Color::WHITE)
+                        .outline_width(2.)
+                        .outline_offset(1.)
+                        .font_size(20.)
+                })
+        });
+        let label = world
+            .spawn((
+                TextBundle::from_section("Label", TextStyle::default()),
+                ElementStyles::new(&[style]),
+                ElementClasses::default(),
+            ))
+            .id();
+        let advance = |world: &mut World, seconds: f32| {
+            let mut time = Time::<()>::default();
+            time.advance_by(Duration::from_secs_f32(seconds));
+            world.insert_resource(time);
+            world.run_system_once(animate_outlines);
+            world.run_system_once(animate_font_sizes);
+        };
+        let font_size = |world: &World| {
+            world.get::<Text>(label).unwrap().sections[0]
+                .style
+                .font_size
+        };
+
+        // The first font size isn't animated.
+        apply_styles_once(world);
+        assert!(world.get::<Outline>(label).is_none());
+        assert_eq!(font_size(world), 16.);
+
+        // A new outline grows from nothing and fades in. The offset has no transition.
+        add_class(world, label, "focus");
+        apply_styles_once(world);
+        let outline = world.get::<Outline>(label).unwrap();
+        assert_eq!(outline.width, Val::Px(0.));
+        assert_eq!(outline.offset, Val::Px(1.));
+        assert_eq!(outline.color.a(), 0.);
+        assert_eq!(font_size(world), 16.);
+
+        advance(world, 0.25);
+        let outline = world.get::<Outline>(label).unwrap();
+        assert_eq!(outline.width, Val::Px(0.5));
+        assert_eq!(outline.color.a(), 0.25);
+        assert_eq!(font_size(world), 17.);
+
+        let mut widths = Vec::new();
+        for _ in 0..4 {
+            advance(world, 0.25);
+            widths.push(world.get::<Outline>(label).unwrap().width);
+        }
+        assert_eq!(widths, [1., 1.5, 2., 2.].map(Val::Px));
+        assert_eq!(world.get::<Outline>(label).unwrap().color, Color::WHITE);
+        assert_eq!(font_size(world), 20.);
+
+        // Losing the outline fades it out, rather than removing it.
+        world
+            .get_mut::<ElementClasses>(label)
+            .unwrap()
+            .remove_class("focus");
+        apply_styles_once(world);
+        advance(world, 0.5);
+        assert_eq!(world.get::<Outline>(label).unwrap().color.a(), 0.5);
+        assert_eq!(font_size(world), 18.);
+        advance(world, 0.5);
+        let outline = world.get::<Outline>(label).unwrap();
+        assert_eq!(outline.color.a(), 0.);
+        assert_eq!(outline.width, Val::Px(2.));
+        assert_eq!(font_size(world), 16.);
+    }
+
+    #[test]
+    fn test_background_image_and_color_layering() {
+        let mut app = test_app();
+        let world = &mut app.world;
+
+        let red = Color::rgb(1., 0., 0.);
+        let gray = Color::rgb(0.5, 0.5, 0.5);
+        let transition = Transition {
+            property: TransitionProperty::BackgroundColor,
+            duration: 1.,
+            ..default()
+        };
+        // Every combination of image, color, tint and transition, with the color the node
+        // should be drawn with: the fill without an image, and the tint with one.
+        let mut cases = Vec::new();
+        for image in [false, true] {
+            for color in [None, Some(red)] {
+                for tint in [None, Some(gray)] {
+                    for animated in [false, true] {
+                        let style = StyleHandle::build(|ss| {
+                            if image {
+                                ss.background_image(Some("panel.png".into()));
+                            }
+                            if animated {
+                                ss.transition(std::slice::from_ref(&transition));
+                            }
+                            ss.background_color(color).background_image_tint(tint)
+                        });
+                        let expected = match image {
+                            true => Some(tint.or(color).unwrap_or(Color::WHITE)),
+                            false => color,
+                        };
+                        let entity = world
+                            .spawn((NodeBundle::default(), ElementStyles::new([style])))
+                            .id();
+                        cases.push((entity, (image, color, tint, animated), expected));
+                    }
+                }
+            }
+        }
+        apply_styles_once(world);
+        for (entity, case, expected) in cases {
+            let bg = world.get::<BackgroundColor>(entity).map(|bg| bg.0);
+            assert_eq!(bg, expected, "{:?}", case);
+            assert_eq!(world.get::<UiImage>(entity).is_some(), case.0, "{:?}", case);
+        }
+    }
+
+    #[test]
+    fn test_background_color_transitions() {
+        let mut app = test_app();
+        let world = &mut app.world;
+
+        let red = Color::rgb(1., 0., 0.);
+        let blue = Color::rgb(0., 0., 1.);
+        let transition = |property| Transition {
+            property,
+            duration: 1.,
+            ..default()
+        };
+        let style = StyleHandle::build(|ss| {
+            ss.transition(&[
+                transition(TransitionProperty::BackgroundColor),
+                transition(TransitionProperty::BorderColor),
+            ])
+            .selector(".red", |ss| ss.background_color(red).border_color(red))
+            .selector(".blue", |ss| ss.background_color(blue).border_color(blue))
+            .selector(".image", |ss| ss.background_image(Some("panel.png".into())))
+        });
+        let panel = world
+            .spawn((
+                NodeBundle::default(),
+                ElementStyles::new([style]),
+                ElementClasses::new("red"),
+            ))
+            .id();
+        let advance = |world: &mut World, seconds: f32| {
+            let mut time = Time::<()>::default();
+            time.advance_by(Duration::from_secs_f32(seconds));
+            world.insert_resource(time);
+            world.run_system_once(animate_bg_colors);
+            world.run_system_once(animate_border_colors);
+        };
+        let bg = |world: &World| world.get::<BackgroundColor>(panel).unwrap().0;
+        let border = |world: &World| world.get::<BorderColor>(panel).unwrap().0;
+        let remove_class = |world: &mut World, class: &str| {
+            world
+                .get_mut::<ElementClasses>(panel)
+                .unwrap()
+                .remove_class(class)
+        };
+
+        // The first colors aren't animated.
+        apply_styles_once(world);
+        assert_eq!(bg(world), red);
+        assert_eq!(border(world), red);
+
+        add_class(world, panel, "blue");
+        apply_styles_once(world);
+        assert_eq!(bg(world), red);
+        advance(world, 0.5);
+        assert_eq!(bg(world), lerp_color(red, blue, 0.5));
+        assert_eq!(border(world), lerp_color(red, blue, 0.5));
+
+        // Adding an image turns the color into a tint, so it jumps there rather than showing
+        // a fill of the tint color.
+        add_class(world, panel, "image");
+        apply_styles_once(world);
+        assert_eq!(bg(world), blue);
+        advance(world, 0.25);
+        assert_eq!(bg(world), blue);
+
+        // With the image, changes of the tint are animated.
+        remove_class(world, "blue");
+        apply_styles_once(world);
+        advance(world, 0.5);
+        assert_eq!(bg(world), lerp_color(blue, red, 0.5));
+
+        // Removing the image mid-animation jumps to the fill color.
+        remove_class(world, "image");
+        apply_styles_once(world);
+        assert!(world.get::<UiImage>(panel).is_none());
+        assert_eq!(bg(world), red);
+        advance(world, 0.25);
+        assert_eq!(bg(world), red);
+
+        // Removing the colors fades them out, rather than removing them.
+        remove_class(world, "red");
+        apply_styles_once(world);
+        advance(world, 0.5);
+        assert_eq!(bg(world).a(), 0.5);
+        assert_eq!(border(world).a(), 0.5);
+        advance(world, 0.5);
+        assert_eq!(bg(world), red.with_a(0.));
+        assert_eq!(border(world), red.with_a(0.));
+
+        // An image without a color is drawn as is.
+        add_class(world, panel, "image");
+        apply_styles_once(world);
+        assert_eq!(bg(world), Color::WHITE);
+    }
+
+    #[test]
+    fn test_relative_layout_transitions() {
+        let mut app = test_app();
+        app.init_resource::<PeacockAnimationConfig>();
+        let world = &mut app.world;
+        crate::test_utils::set_window_size(world, 800., 600.);
+
+        let style = StyleHandle::build(|ss| {
+            ss.width(100.)
+                .height(10.)
+                .transition(&[
+                    Transition {
+                        property: TransitionProperty::Width,
+                        duration: 1.,
+                        ..default()
+                    },
+                    Transition {
+                        property: TransitionProperty::Height,
+                        duration: 1.,
+                        ..default()
+                    },
+                ])
+                .selector(".wide", |ss| {
+                    ss.width(Val::Percent(50.)).height(Val::Vh(10.))
+                })
+        });
+        let child = world
+            .spawn((
+                NodeBundle::default(),
+                ElementStyles::new(&[style]),
+                ElementClasses::default(),
+            ))
+            .id();
+        world
+            .spawn(NodeBundle {
+                style: Style {
+                    width: Val::Px(400.),
+                    height: Val::Px(300.),
+                    ..default()
+                },
+                ..default()
+            })
+            .add_child(child);
+        crate::test_utils::run_layout(world);
+        let advance = |world: &mut World, seconds: f32| {
+            let mut time = Time::<()>::default();
+            time.advance_by(Duration::from_secs_f32(seconds));
+            world.insert_resource(time);
+            world.run_system_once(animate_layout);
+            let style = world.get::<Style>(child).unwrap();
+            (style.width, style.height)
+        };
+
+        apply_styles_once(world);
+        assert_eq!(advance(world, 0.5), (Val::Px(100.), Val::Px(10.)));
+
+        // Percentages are resolved against the parent, and viewport units against the window.
+        // Once the animation finishes, the declared values are written.
+        add_class(world, child, "wide");
+        apply_styles_once(world);
+        assert_eq!(advance(world, 0.5), (Val::Px(150.), Val::Px(35.)));
+        assert_eq!(advance(world, 0.5), (Val::Percent(50.), Val::Vh(10.)));
+
+        // Animating back starts from the resolved size.
+        world
+            .get_mut::<ElementClasses>(child)
+            .unwrap()
+            .remove_class("wide");
+        apply_styles_once(world);
+        assert_eq!(advance(world, 0.25), (Val::Px(175.), Val::Px(47.5)));
+        assert_eq!(advance(world, 0.75), (Val::Px(100.), Val::Px(10.)));
+    }
+
+    #[test]
+    fn test_padding_and_gap_transitions() {
+        let mut app = test_app();
+        app.init_resource::<PeacockAnimationConfig>();
+        let world = &mut app.world;
+
+        let (_, list) = bevy_peacock_style::parse_stylesheet(
+            "ITEM {
+                padding: 0px;
+                row_gap: 0px;
+                transition: padding_left 1s ease_out, padding_top 1s ease_out, row_gap 1s;
+                .expanded { padding: 16px; row_gap: 8px; }
+            }",
+        )
+        .unwrap()
+        .pop()
+        .unwrap();
+        let item = world
+            .spawn((
+                NodeBundle::default(),
+                ElementStyles::new([StyleHandle::new(list)]),
+                ElementClasses::default(),
+            ))
+            .id();
+        let advance = |world: &mut World, seconds: f32| {
+            let mut time = Time::<()>::default();
+            time.advance_by(Duration::from_secs_f32(seconds));
+            world.insert_resource(time);
+            world.run_system_once(animate_layout);
+            world.get::<Style>(item).unwrap().clone()
+        };
+        let px = |val: Val| match val {
+            Val::Px(px) => px,
+            val => panic!("expected a length in pixels, got {:?}", val),
+        };
+
+        apply_styles_once(world);
+        add_class(world, item, "expanded");
+        apply_styles_once(world);
+        let steps: Vec<Style> = (0..10).map(|_| advance(world, 0.1)).collect();
+
+        // Padding eases out: it rises at every step, quickly at first and slowly at the end.
+        let left: Vec<f32> = steps.iter().map(|style| px(style.padding.left)).collect();
+        assert!(left.windows(2).all(|w| w[0] < w[1]), "{:?}", left);
+        assert!(left[1] - left[0] > left[9] - left[8], "{:?}", left);
+        assert!(left[4] > 8.);
+        assert_eq!(left[9], 16.);
+        assert!(steps
+            .iter()
+            .all(|style| style.padding.top == style.padding.left));
+
+        // Sides without a transition jump to the target, and the gap animates linearly.
+        assert_eq!(steps[0].padding.right, Val::Px(16.));
+        assert!((px(steps[4].row_gap) - 4.).abs() < 1e-3);
+        assert_eq!(steps[9].row_gap, Val::Px(8.));
+    }
+
+    #[test]
+    fn test_animate_in() {
+        let mut app = test_app();
+        app.init_resource::<PeacockAnimationConfig>();
+        let world = &mut app.world;
+
+        let sheet = parse_stylesheet(
+            "TIP {
+                width: 100px;
+                scale: 1;
+                animate_in: scale 0.5 1s, width 0px 1s;
+                .tall { height: 50px; }
+            }",
+        )
+        .unwrap();
+        let style = StyleHandle::new(sheet[0].1.clone());
+        let spawn = |world: &mut World| {
+            world
+                .spawn((
+                    NodeBundle::default(),
+                    ElementStyles::new([style.clone()]),
+                    ElementClasses::default(),
+                ))
+                .id()
+        };
+        let advance = |world: &mut World, seconds: f32| {
+            world
+                .resource_mut::<Time>()
+                .advance_by(Duration::from_secs_f32(seconds));
+            world.run_system_once(animate_transforms);
+            world.run_system_once(animate_layout);
+        };
+        let state = |world: &World, e| {
+            (
+                world.get::<Transform>(e).unwrap().scale.x,
+                world.get::<Style>(e).unwrap().width,
+            )
+        };
+
+        // The properties start at the values they're animated in from.
+        let tip = spawn(world);
+        apply_styles_once(world);
+        assert_eq!(state(world, tip), (0.5, Val::Px(0.)));
+        advance(world, 0.5);
+        assert_eq!(state(world, tip), (0.75, Val::Px(50.)));
+
+        // Restyling while they play doesn't interrupt them.
+        add_class(world, tip, "tall");
+        apply_styles_once(world);
+        advance(world, 0.25);
+        assert_eq!(state(world, tip), (0.875, Val::Px(75.)));
+        advance(world, 0.25);
+        assert_eq!(state(world, tip), (1., Val::Px(100.)));
+
+        // Nor are they played again once they've finished, even by a restyle which replaces
+        // the styles.
+        world
+            .entity_mut(tip)
+            .insert(ElementStyles::new([StyleHandle::new(sheet[0].1.clone())]));
+        apply_styles_once(world);
+        advance(world, 0.1);
+        assert_eq!(state(world, tip), (1., Val::Px(100.)));
+        assert_eq!(world.get::<Style>(tip).unwrap().height, Val::Px(50.));
+
+        // A new element plays them.
+        let next = spawn(world);
+        apply_styles_once(world);
+        advance(world, 0.5);
+        assert_eq!(state(world, next), (0.75, Val::Px(50.)));
+        assert_eq!(state(world, tip), (1., Val::Px(100.)));
+    }
+
+    #[test]
+    fn test_replaced_transitions() {
+        let mut app = test_app();
+        app.init_resource::<PeacockAnimationConfig>();
+        let world = &mut app.world;
+
+        let props = |duration| {
+            bevy_peacock_style::StylePropList::build(|ss| {
+                ss.width(100.)
+                    .transition(&[Transition {
+                        property: TransitionProperty::Width,
+                        duration,
+                        ..default()
+                    }])
+                    .selector(".wide", |ss| ss.width(200.))
+            })
+        };
+        let style = StyleHandle::new(props(1.));
+        let entity = world
+            .spawn((
+                NodeBundle::default(),
+                ElementStyles::new([style.clone()]),
+                ElementClasses::default(),
+            ))
+            .id();
+        let advance = |world: &mut World, seconds: f32| {
+            let mut time = Time::<()>::default();
+            time.advance_by(Duration::from_secs_f32(seconds));
+            world.insert_resource(time);
+            world.run_system_once(animate_layout);
+            world.get::<Style>(entity).unwrap().width
+        };
+
+        apply_styles_once(world);
+        add_class(world, entity, "wide");
+        apply_styles_once(world);
+        assert_eq!(advance(world, 0.5), Val::Px(150.));
+
+        // The running animation keeps its duration, and the next one uses the new duration.
+        style.replace(props(0.25));
+        apply_styles_once(world);
+        assert_eq!(advance(world, 0.25), Val::Px(175.));
+        world
+            .get_mut::<ElementClasses>(entity)
+            .unwrap()
+            .remove_class("wide");
+        apply_styles_once(world);
+        assert_eq!(advance(world, 0.125), Val::Px(137.5));
+        assert_eq!(advance(world, 0.125), Val::Px(100.));
+    }
+
+    #[test]
+    fn test_warn_unset_transitions() {
+        let mut app = test_app();
+        let world = &mut app.world;
+
+        let transitions = [Transition {
+            property: TransitionProperty::Width,
+            duration: 0.3,
+            ..default()
+        }];
+        let unset = StyleHandle::build(|ss| ss.height(10.).transition(&transitions));
+        let set = StyleHandle::build(|ss| {
+            ss.transition(&transitions)
+                .selector(".wide", |ss| ss.width(100.))
+        });
+        let a = world
+            .spawn((
+                NodeBundle::default(),
+                ElementStyles::new(&[unset]),
+                ElementClasses::default(),
+            ))
+            .id();
+        let b = world
+            .spawn((
+                NodeBundle::default(),
+                ElementStyles::new(&[set]),
+                ElementClasses::default(),
+            ))
+            .id();
+
+        apply_styles_once(world);
+        let warnings = &world.resource::<UnsetTransitionWarnings>().0;
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings.contains(&(a, TransitionProperty::Width)));
+
+        // Restyling doesn't report the same property again.
+        world
+            .get_mut::<ElementClasses>(a)
+            .unwrap()
+            .add_class("wide");
+        world
+            .get_mut::<ElementClasses>(b)
+            .unwrap()
+            .add_class("wide");
+        apply_styles_once(world);
+        assert_eq!(world.resource::<UnsetTransitionWarnings>().0.len(), 1);
+
+        // Despawned entities are forgotten.
+        world.despawn(a);
+        world.run_system_once(forget_transition_warnings);
+        assert!(world.resource::<UnsetTransitionWarnings>().0.is_empty());
+    }
+
+    #[test]
+    fn test_warn_unanimatable_transitions() {
+        let mut app = test_app();
+        let world = &mut app.world;
+
+        let mut registry = StyleRegistry::default();
+        registry.register_sheet(
+            parse_stylesheet(
+                "PANEL { width: auto; transition: width 0.3s; }
+                BUTTON { width: 100px; transition: width 0.3s; .auto { width: auto; } }",
+            )
+            .unwrap(),
+        );
+        let panel = registry.get("PANEL").unwrap();
+        let button = registry.get("BUTTON").unwrap();
+        world.insert_resource(registry);
+        let mut spawn = |style: &StyleHandle| {
+            world
+                .spawn((
+                    NodeBundle::default(),
+                    ElementStyles::new(std::slice::from_ref(style)),
+                    ElementClasses::default(),
+                ))
+                .id()
+        };
+        let a = spawn(&panel);
+        let b = spawn(&button);
+
+        apply_styles_once(world);
+        let warnings = &world.resource::<UnanimatableTransitionWarnings>().0;
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings.contains(&a));
+        assert_eq!(
+            describe_transition_styles(world, TransitionProperty::Width, &[button, panel]),
+            " (declared in style 'BUTTON' at <stylesheet>:2:17, 'PANEL' at <stylesheet>:1:1)"
+        );
+
+        // Changing the target to `auto` is reported too, but only once per entity.
+        world
+            .get_mut::<ElementClasses>(b)
+            .unwrap()
+            .add_class("auto");
+        world.get_mut::<ElementStyles>(a).unwrap().set_changed();
+        apply_styles_once(world);
+        let warnings = &world.resource::<UnanimatableTransitionWarnings>().0;
+        assert_eq!(warnings.len(), 2);
+        assert!(warnings.contains(&b));
+    }
+
+    #[test]
+    fn test_coalesce_updates() {
+        let mut world = World::new();
+        world.init_resource::<PendingStyleUpdates>();
+        let a = world.spawn((Node::default(), Style::default())).id();
+        let b = world.spawn((Node::default(), Style::default())).id();
+        let update = |entity, width: f32, transitions: Vec<Transition>| {
+            let mut computed = ComputedStyle::default();
+            computed.style.width = Val::Px(width);
+            computed.style_fields.insert(StyleField::Width);
+            computed.transitions = transitions;
+            StyleUpdate::Computed(UpdateComputedStyle {
+                entity,
+                computed,
+                parent_size: Vec2::ZERO,
+                viewport: Vec2::ZERO,
+                text_only: false,
+                styles: Vec::new(),
+            })
+        };
+
+        // The first update for `a` has a transition on a property which isn't set, which would
+        // be reported if the update were applied.
+        let stale = vec![Transition {
+            property: TransitionProperty::Height,
+            duration: 0.3,
+            ..default()
+        }];
+        let mut pending = world.resource_mut::<PendingStyleUpdates>();
+        pending.push(a, update(a, 10., stale));
+        pending.push(b, update(b, 20., Vec::new()));
+        pending.push(a, update(a, 30., Vec::new()));
+        assert_eq!(pending.redundant_count(), 1);
+
+        ApplyStyleUpdates.apply(&mut world);
+        assert!(world.resource::<PendingStyleUpdates>().is_empty());
+        assert_eq!(world.get::<Style>(a).unwrap().width, Val::Px(30.));
+        assert_eq!(world.get::<Style>(b).unwrap().width, Val::Px(20.));
+        assert!(world
+            .get_resource::<UnsetTransitionWarnings>()
+            .is_none_or(|warnings| warnings.0.is_empty()));
+
+        // Updates applied in an earlier batch don't count as redundant, and the count starts
+        // again with the next frame.
+        let mut pending = world.resource_mut::<PendingStyleUpdates>();
+        pending.start_pass();
+        assert_eq!(pending.redundant_count(), 0);
+        pending.push(a, update(a, 40., Vec::new()));
+        pending.start_pass();
+        assert_eq!(pending.redundant_count(), 0);
+        pending.push(a, update(a, 50., Vec::new()));
+        assert_eq!(pending.redundant_count(), 1);
+        ApplyStyleUpdates.apply(&mut world);
+        assert_eq!(world.get::<Style>(a).unwrap().width, Val::Px(50.));
+    }
+
+    #[test]
+    fn test_image_tint_and_flip() {
+        let mut app = test_app();
+        let world = &mut app.world;
+        let bg_color = |world: &World, entity| world.get::<BackgroundColor>(entity).map(|bg| bg.0);
+
+        let style = StyleHandle::build(|ss| {
+            ss.background_image(Some("panel.png".into()))
+                .background_color("#ff0000")
+                .selector(":hover", |ss| {
+                    ss.background_image_tint("#808080")
+                        .background_image_flip_x(true)
+                        .background_image_flip_y(true)
+                })
+        });
+        let panel = world
+            .spawn((
+                NodeBundle::default(),
+                ElementStyles::new(&[style]),
+                ElementClasses::default(),
+            ))
+            .id();
+        let flip = |world: &World| {
+            let image = world.get::<UiImage>(panel).unwrap();
+            (image.flip_x, image.flip_y)
+        };
+
+        // Without a tint, the background color tints the image.
+        apply_styles_once(world);
+        assert_eq!(bg_color(world, panel), Color::hex("#ff0000").ok());
+        assert_eq!(flip(world), (false, false));
+
+        set_hovered(world, Some(panel));
+        apply_styles_once(world);
+        assert_eq!(bg_color(world, panel), Color::hex("#808080").ok());
+        assert_eq!(flip(world), (true, true));
+
+        set_hovered(world, None);
+        apply_styles_once(world);
+        assert_eq!(bg_color(world, panel), Color::hex("#ff0000").ok());
+        assert_eq!(flip(world), (false, false));
+
+        // The tint only applies when there is an image.
+        let style = StyleHandle::build(|ss| ss.background_image_tint("#808080"));
+        let plain = world
+            .spawn((NodeBundle::default(), ElementStyles::new(&[style])))
+            .id();
+        apply_styles_once(world);
+        assert_eq!(bg_color(world, plain), None);
+    }
+}
@@ -0,0 +1,256 @@
+use bevy::prelude::*;
+use bevy_peacock_style::{ComputedStyle, StyleField};
+
+/// The value of a computed property, as reported by [`ComputedPropChanged`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ComputedValue {
+    /// A color, such as `background_color`.
+    Color(Color),
+    /// A length, such as `width`.
+    Val(Val),
+    /// A number, such as `font_size`.
+    Number(f32),
+}
+
+/// Return the value of the property named `key` in `computed`: `Some(None)` if the styles don't
+/// set it, and `None` if the property can't be watched. Lengths are read from the `Style` only
+/// if a style set them.
+fn computed_value(c: &ComputedStyle, key: &str) -> Option<Option<ComputedValue>> {
+    use ComputedValue::{Color, Number};
+    let style = &c.style;
+    Some(match key {
+        "background_color" => c.background_color.map(Color),
+        "background_image_tint" => c.image_tint.map(Color),
+        "border_color" => c.border_color.map(Color),
+        "color" => c.color.map(Color),
+        "outline_color" => c.outline_color.map(Color),
+        "font_size" => c.font_size.map(Number),
+        "scale_x" => c.scale_x.map(Number),
+        "scale_y" => c.scale_y.map(Number),
+        "rotation" => c.rotation.map(Number),
+        "flex_grow" => style_number(c, StyleField::FlexGrow, style.flex_grow),
+        "flex_shrink" => style_number(c, StyleField::FlexShrink, style.flex_shrink),
+        "outline_width" => outline_val(c, c.outline_width),
+        "outline_offset" => outline_val(c, c.outline_offset),
+        "left" => style_val(c, StyleField::Left, style.left),
+        "right" => style_val(c, StyleField::Right, style.right),
+        "top" => style_val(c, StyleField::Top, style.top),
+        "bottom" => style_val(c, StyleField::Bottom, style.bottom),
+        "width" => style_val(c, StyleField::Width, style.width),
+        "height" => style_val(c, StyleField::Height, style.height),
+        "min_width" => style_val(c, StyleField::MinWidth, style.min_width),
+        "min_height" => style_val(c, StyleField::MinHeight, style.min_height),
+        "max_width" => style_val(c, StyleField::MaxWidth, style.max_width),
+        "max_height" => style_val(c, StyleField::MaxHeight, style.max_height),
+        "flex_basis" => style_val(c, StyleField::FlexBasis, style.flex_basis),
+        "row_gap" => style_val(c, StyleField::RowGap, style.row_gap),
+        "column_gap" => style_val(c, StyleField::ColumnGap, style.column_gap),
+        "margin_left" => style_val(c, StyleField::MarginLeft, style.margin.left),
+        "margin_right" => style_val(c, StyleField::MarginRight, style.margin.right),
+        "margin_top" => style_val(c, StyleField::MarginTop, style.margin.top),
+        "margin_bottom" => style_val(c, StyleField::MarginBottom, style.margin.bottom),
+        "padding_left" => style_val(c, StyleField::PaddingLeft, style.padding.left),
+        "padding_right" => style_val(c, StyleField::PaddingRight, style.padding.right),
+        "padding_top" => style_val(c, StyleField::PaddingTop, style.padding.top),
+        "padding_bottom" => style_val(c, StyleField::PaddingBottom, style.padding.bottom),
+        "border_left" => style_val(c, StyleField::BorderLeft, style.border.left),
+        "border_right" => style_val(c, StyleField::BorderRight, style.border.right),
+        "border_top" => style_val(c, StyleField::BorderTop, style.border.top),
+        "border_bottom" => style_val(c, StyleField::BorderBottom, style.border.bottom),
+        _ => return None,
+    })
+}
+
+fn style_val(computed: &ComputedStyle, field: StyleField, val: Val) -> Option<ComputedValue> {
+    computed
+        .style_fields
+        .contains(field)
+        .then_some(ComputedValue::Val(val))
+}
+
+fn style_number(computed: &ComputedStyle, field: StyleField, n: f32) -> Option<ComputedValue> {
+    computed
+        .style_fields
+        .contains(field)
+        .then_some(ComputedValue::Number(n))
+}
+
+/// The outline lengths only apply if there's an outline, which needs a color.
+fn outline_val(computed: &ComputedStyle, val: Val) -> Option<ComputedValue> {
+    computed.outline_color.map(|_| ComputedValue::Val(val))
+}
+
+/// Watches computed properties of an element, so that systems which aren't part of the UI can
+/// react to changes in them without polling its components. Whenever the element is restyled
+/// and the value of one of the watched properties changes, a [`ComputedPropChanged`] event is
+/// sent.
+///
+/// Properties are named by their keys, such as `"background_color"` or `"width"`; colors, lengths
+/// and numbers can be watched, but not shorthands such as `"margin"`. The value is the one the
+/// styles computed, which is the target of any transition, rather than each step of the
+/// animation.
+#[derive(Component, Debug, Clone, Default)]
+pub struct WatchComputedProp {
+    /// The watched keys, and the value of each when the element was last restyled.
+    watched: Vec<(&'static str, Option<ComputedValue>)>,
+}
+
+impl WatchComputedProp {
+    /// Watch the properties named by `keys`. Keys which can't be watched are ignored, with a
+    /// warning.
+    pub fn new(keys: impl IntoIterator<Item = &'static str>) -> Self {
+        let watched = keys
+            .into_iter()
+            .filter(|key| {
+                let watchable = computed_value(&ComputedStyle::default(), key).is_some();
+                if !watchable {
+                    warn!("WatchComputedProp: property '{}' can't be watched", key);
+                }
+                watchable
+            })
+            .map(|key| (key, None))
+            .collect();
+        Self { watched }
+    }
+
+    /// The keys of the watched properties.
+    pub fn keys(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.watched.iter().map(|(key, _)| *key)
+    }
+}
+
+/// Event sent when the computed value of a property watched by a [`WatchComputedProp`] changes.
+/// The values are `None` when the styles don't set the property.
+#[derive(Event, Clone, Debug, PartialEq)]
+pub struct ComputedPropChanged {
+    /// The element which was restyled.
+    pub entity: Entity,
+    /// The key of the property, such as `"background_color"`.
+    pub key: &'static str,
+    /// The value before the element was restyled.
+    pub old: Option<ComputedValue>,
+    /// The new value.
+    pub new: Option<ComputedValue>,
+}
+
+/// Send a [`ComputedPropChanged`] event for each of the properties watched by `entity` whose
+/// value differs in `computed`, the style being applied to it.
+pub(crate) fn notify_watchers(world: &mut World, entity: Entity, computed: &ComputedStyle) {
+    let Some(watch) = world.get::<WatchComputedProp>(entity) else {
+        return;
+    };
+    let changes: Vec<ComputedPropChanged> = watch
+        .watched
+        .iter()
+        .filter_map(|&(key, old)| {
+            let new = computed_value(computed, key).flatten();
+            (new != old).then_some(ComputedPropChanged {
+                entity,
+                key,
+                old,
+                new,
+            })
+        })
+        .collect();
+    if changes.is_empty() {
+        return;
+    }
+    let mut watch = world.get_mut::<WatchComputedProp>(entity).unwrap();
+    for change in changes.iter() {
+        if let Some(entry) = watch.watched.iter_mut().find(|(k, _)| *k == change.key) {
+            entry.1 = change.new;
+        }
+    }
+    world.send_event_batch(changes);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::apply_styles_once;
+    use crate::{ElementClasses, ElementStyles, StyleHandle};
+
+    fn changes(world: &mut World) -> Vec<ComputedPropChanged> {
+        world
+            .resource_mut::<Events<ComputedPropChanged>>()
+            .drain()
+            .collect()
+    }
+
+    #[test]
+    fn test_watch_background_color() {
+        let mut app = crate::test_utils::test_app();
+        app.add_event::<ComputedPropChanged>();
+        let world = &mut app.world;
+
+        let red = Color::rgb(1., 0., 0.);
+        let green = Color::rgb(0., 1., 0.);
+        let style = StyleHandle::build(|ss| {
+            ss.background_color(green)
+                .width(10.)
+                .selector(".low", |ss| ss.background_color(red).width(20.))
+        });
+        let bars: Vec<Entity> = (0..10)
+            .map(|_| {
+                world
+                    .spawn((
+                        NodeBundle::default(),
+                        ElementStyles::new([style.clone()]),
+                        ElementClasses::default(),
+                    ))
+                    .id()
+            })
+            .collect();
+        let watched = bars[3];
+        world
+            .entity_mut(watched)
+            .insert(WatchComputedProp::new(["background_color", "no_such_prop"]));
+        assert_eq!(
+            world
+                .get::<WatchComputedProp>(watched)
+                .unwrap()
+                .keys()
+                .collect::<Vec<_>>(),
+            ["background_color"]
+        );
+        let set_class = |world: &mut World, entity: Entity, low: bool| {
+            world
+                .get_mut::<ElementClasses>(entity)
+                .unwrap()
+                .set_class("low", low);
+        };
+        let changed = |old: Option<Color>, new| ComputedPropChanged {
+            entity: watched,
+            key: "background_color",
+            old: old.map(ComputedValue::Color),
+            new: Some(ComputedValue::Color(new)),
+        };
+
+        apply_styles_once(world);
+        assert_eq!(changes(world), [changed(None, green)]);
+
+        // Only the watched entity sends events, and only for the watched property.
+        for bar in bars.iter() {
+            set_class(world, *bar, true);
+        }
+        apply_styles_once(world);
+        assert_eq!(changes(world), [changed(Some(green), red)]);
+
+        // Restyling without changing the value sends nothing.
+        for bar in bars.iter() {
+            set_class(world, *bar, false);
+        }
+        world
+            .get_mut::<ElementClasses>(watched)
+            .unwrap()
+            .add_class("unrelated");
+        apply_styles_once(world);
+        assert_eq!(changes(world), [changed(Some(red), green)]);
+        world
+            .get_mut::<ElementClasses>(watched)
+            .unwrap()
+            .remove_class("unrelated");
+        apply_styles_once(world);
+        assert_eq!(changes(world), []);
+    }
+}
@@ -0,0 +1,226 @@
+//! Runs the setup code of the gallery, transitions, theming and stylesheet examples headlessly,
+//! and checks the styles they compute as they're driven from the keyboard.
+
+#[path = "../examples/common/mod.rs"]
+mod common;
+
+use bevy::{
+    prelude::*,
+    window::{PrimaryWindow, WindowResolution},
+};
+use common::{bg_color, find, headless_app, press_key};
+
+fn highlighted(app: &mut App, name: &str) -> bool {
+    let row = find::<common::gallery::GalleryRow>(app, |row| row.0 == name);
+    bg_color(app, row) == Some(common::gallery::HIGHLIGHT)
+}
+
+#[test]
+fn test_gallery() {
+    let mut app = headless_app();
+    app.add_plugins(common::gallery::GalleryPlugin);
+    app.update();
+
+    // Only the first and last rows match, and only by their position.
+    assert!(highlighted(&mut app, "first-child"));
+    assert!(highlighted(&mut app, "last-child"));
+    for name in [
+        "hover",
+        "focus",
+        "focus-visible",
+        "focus-within",
+        "checked",
+        "disabled",
+        "dragging",
+        "drag-over",
+        "lang",
+    ] {
+        assert!(!highlighted(&mut app, name), ":{} is highlighted", name);
+    }
+
+    // Tab moves the focus through the focus rows, and then into the focus-within row.
+    press_key(&mut app, KeyCode::Tab);
+    assert!(highlighted(&mut app, "focus"));
+    assert!(!highlighted(&mut app, "focus-visible"));
+    press_key(&mut app, KeyCode::Tab);
+    assert!(!highlighted(&mut app, "focus"));
+    assert!(highlighted(&mut app, "focus-visible"));
+    assert!(!highlighted(&mut app, "focus-within"));
+    press_key(&mut app, KeyCode::Tab);
+    assert!(!highlighted(&mut app, "focus-visible"));
+    assert!(highlighted(&mut app, "focus-within"));
+    press_key(&mut app, KeyCode::Tab);
+    assert!(!highlighted(&mut app, "focus-within"));
+
+    press_key(&mut app, KeyCode::Space);
+    assert!(highlighted(&mut app, "checked"));
+    press_key(&mut app, KeyCode::D);
+    assert!(highlighted(&mut app, "disabled"));
+    press_key(&mut app, KeyCode::Space);
+    assert!(!highlighted(&mut app, "checked"));
+    assert!(highlighted(&mut app, "disabled"));
+
+    press_key(&mut app, KeyCode::L);
+    assert!(highlighted(&mut app, "lang"));
+    press_key(&mut app, KeyCode::L);
+    assert!(!highlighted(&mut app, "lang"));
+
+    // The custom pseudo-class follows the clock.
+    let mut blinks = Vec::new();
+    for _ in 0..25 {
+        app.update();
+        blinks.push(highlighted(&mut app, "--blink"));
+    }
+    assert!(blinks.contains(&true) && blinks.contains(&false));
+}
+
+#[test]
+fn test_transitions() {
+    use common::transitions::{
+        AnimatedBox, TransitionDemo, ACTIVE_COLOR, INACTIVE_COLOR, INITIAL_DURATION,
+    };
+
+    let mut app = headless_app();
+    app.add_plugins(common::transitions::TransitionsPlugin);
+    app.update();
+    let entity = find::<AnimatedBox>(&mut app, |_| true);
+    let width = |app: &App| match app.world.get::<Style>(entity).unwrap().width {
+        Val::Px(width) => width,
+        width => panic!("unexpected width {:?}", width),
+    };
+    let scale = |app: &App| app.world.get::<Transform>(entity).unwrap().scale.x;
+    let outline_color = |app: &App| app.world.get::<Outline>(entity).unwrap().color;
+    let settle = |app: &mut App, duration: f32| {
+        for _ in 0..(duration / common::FRAME).ceil() as usize {
+            app.update();
+        }
+    };
+
+    // The outline fades in when the box appears.
+    settle(&mut app, INITIAL_DURATION);
+    assert_eq!(width(&app), 100.);
+    assert_eq!(scale(&app), 1.);
+    assert_eq!(outline_color(&app), INACTIVE_COLOR);
+
+    // Part way through the transitions, every property is between its endpoints.
+    press_key(&mut app, KeyCode::Space);
+    app.update();
+    assert!(width(&app) > 100. && width(&app) < 300., "{}", width(&app));
+    assert!(scale(&app) > 1. && scale(&app) < 1.5, "{}", scale(&app));
+    assert_ne!(outline_color(&app), INACTIVE_COLOR);
+    assert_ne!(outline_color(&app), ACTIVE_COLOR);
+
+    settle(&mut app, INITIAL_DURATION);
+    assert_eq!(width(&app), 300.);
+    assert!((scale(&app) - 1.5).abs() < 1e-5, "{}", scale(&app));
+    assert_eq!(outline_color(&app), ACTIVE_COLOR);
+
+    // Shortening the transitions replaces the style, so they finish sooner.
+    press_key(&mut app, KeyCode::Down);
+    press_key(&mut app, KeyCode::Down);
+    let duration = app.world.resource::<TransitionDemo>().duration;
+    assert_eq!(duration, 0.5);
+    press_key(&mut app, KeyCode::Space);
+    settle(&mut app, duration);
+    assert_eq!(width(&app), 100.);
+    assert_eq!(outline_color(&app), INACTIVE_COLOR);
+}
+
+#[test]
+fn test_theming() {
+    use common::theming::Card;
+
+    let mut app = headless_app();
+    app.add_plugins(common::theming::ThemingPlugin);
+    app.update();
+    let cards: Vec<Entity> = (0..common::theming::CARD_COUNT)
+        .map(|index| find::<Card>(&mut app, |card| card.0 == index))
+        .collect();
+    let border = |app: &App, entity| app.world.get::<BorderColor>(entity).unwrap().0;
+    let width = |app: &App, entity| app.world.get::<Style>(entity).unwrap().width;
+    let light = common::theming::light_theme();
+    let dark = common::theming::dark_theme();
+    let token = |theme: &bevy_peacock::Theme, name| match theme.get(name) {
+        Some(bevy_peacock::TokenValue::Color(color)) => Some(color),
+        _ => None,
+    };
+    assert_eq!(bg_color(&app, cards[0]), token(&light, "surface"));
+    assert_eq!(Some(border(&app, cards[0])), token(&light, "accent"));
+    assert_eq!(Some(border(&app, cards[1])), token(&light, "surface"));
+    assert_eq!(width(&app, cards[0]), Val::Px(160.));
+
+    // Swapping the theme restyles everything.
+    press_key(&mut app, KeyCode::T);
+    assert_eq!(bg_color(&app, cards[0]), token(&dark, "surface"));
+    assert_eq!(Some(border(&app, cards[0])), token(&dark, "accent"));
+
+    // So does swapping class names.
+    press_key(&mut app, KeyCode::C);
+    press_key(&mut app, KeyCode::Right);
+    for &card in &cards {
+        assert_eq!(width(&app, card), Val::Px(80.));
+    }
+    assert_eq!(Some(border(&app, cards[0])), token(&dark, "surface"));
+    assert_eq!(Some(border(&app, cards[1])), token(&dark, "accent"));
+
+    press_key(&mut app, KeyCode::T);
+    assert_eq!(bg_color(&app, cards[1]), token(&light, "surface"));
+    assert_eq!(Some(border(&app, cards[1])), token(&light, "accent"));
+}
+
+#[test]
+fn test_stylesheet() {
+    use common::stylesheet::MenuItem;
+
+    let mut app = headless_app();
+    app.world.spawn((
+        Window {
+            resolution: WindowResolution::new(1280., 720.),
+            ..default()
+        },
+        PrimaryWindow,
+    ));
+    app.add_plugins(common::stylesheet::StylesheetPlugin);
+    app.update();
+    let items: Vec<Entity> = (0..common::stylesheet::ITEMS.len())
+        .map(|index| find::<MenuItem>(&mut app, |item| item.0 == index))
+        .collect();
+    let menu = app.world.get::<Parent>(items[0]).unwrap().get();
+    let style = |app: &App, entity| app.world.get::<Style>(entity).unwrap().clone();
+    let selected = Color::hex("#e09a1a").ok();
+    let idle = Color::hex("#3a3a4a").ok();
+
+    assert_eq!(style(&app, menu).width, Val::Px(320.));
+    assert_eq!(style(&app, items[0]).height, Val::Px(40.));
+    assert_eq!(style(&app, items[0]).margin.top, Val::Px(8.));
+    assert_eq!(style(&app, items[1]).margin.top, Val::Px(0.));
+    assert_eq!(bg_color(&app, items[0]), selected);
+    assert_eq!(bg_color(&app, items[1]), idle);
+
+    // The selection wraps around.
+    press_key(&mut app, KeyCode::Up);
+    assert_eq!(bg_color(&app, items[0]), idle);
+    assert_eq!(bg_color(&app, items[4]), selected);
+
+    // Touch screens get larger items.
+    let touch = |app: &mut App, enabled| {
+        app.world
+            .resource_mut::<bevy_peacock::PeacockCapabilities>()
+            .touch = enabled;
+        app.update();
+    };
+    touch(&mut app, true);
+    assert_eq!(style(&app, items[0]).height, Val::Px(56.));
+    touch(&mut app, false);
+    assert_eq!(style(&app, items[0]).height, Val::Px(40.));
+
+    // Narrowing the window applies the media queries.
+    let mut windows = app.world.query::<&mut Window>();
+    windows
+        .single_mut(&mut app.world)
+        .resolution
+        .set(500., 720.);
+    app.update();
+    assert_eq!(style(&app, menu).width, Val::Px(200.));
+    assert_eq!(style(&app, items[0]).height, Val::Px(32.));
+}